@@ -0,0 +1,639 @@
+//! Plain `solana_program` instruction builders for the astrolend program.
+//!
+//! Integrators that only need to CPI into astrolend can depend on this crate instead of the
+//! program crate, avoiding the Anchor dependency tree. Account metas are laid out in the exact
+//! order the program expects, including the optional Token-2022 mint slot and the
+//! bank/oracle observation accounts consumed by the risk engine.
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+#[cfg(feature = "mainnet-beta")]
+solana_program::declare_id!("Astro1oWvtB7cBTwi3efLMFB47WXx7DJDQeoxi235kA");
+#[cfg(all(not(feature = "mainnet-beta"), feature = "devnet"))]
+solana_program::declare_id!("neetcne3Ctrrud7vLdt2ypMm21gZHGN2mCmqWaMVcBQ");
+#[cfg(all(
+    not(feature = "mainnet-beta"),
+    not(feature = "devnet"),
+    feature = "staging"
+))]
+solana_program::declare_id!("stag8sTKds2h4KzjUw3zKTsxbqvT4XKHdaR9X9E6Rct");
+#[cfg(not(any(feature = "mainnet-beta", feature = "devnet", feature = "staging")))]
+solana_program::declare_id!("2jGhuVUuy3umdzByFx8sNWUAaf5vaeuDm78RDPEnhrMr");
+
+pub const LIQUIDITY_VAULT_AUTHORITY_SEED: &str = "liquidity_vault_auth";
+pub const LIQUIDITY_VAULT_SEED: &str = "liquidity_vault";
+pub const INSURANCE_VAULT_SEED: &str = "insurance_vault";
+pub const WHITELIST_SEED: &str = "whitelist";
+pub const RECEIPT_MINT_SEED: &str = "receipt_mint";
+pub const EMISSIONS_AUTH_SEED: &str = "emissions_auth_seed";
+pub const EMISSIONS_TOKEN_ACCOUNT_SEED: &str = "emissions_token_account_seed";
+
+pub const SYSTEM_PROGRAM_ID: Pubkey = solana_program::pubkey!("11111111111111111111111111111111");
+
+pub const SPL_TOKEN_2022_PROGRAM_ID: Pubkey =
+    solana_program::pubkey!("TokenzQdBNbLqP5VEhdkAS6EnLLG5uRSZQHnsgD4ptDb");
+
+/// Anchor instruction discriminators, `sha256("global:<instruction_name>")[..8]`.
+pub mod discriminator {
+    pub const LENDING_ACCOUNT_DEPOSIT: [u8; 8] = [171, 94, 235, 103, 82, 64, 212, 140];
+    pub const LENDING_ACCOUNT_WITHDRAW: [u8; 8] = [36, 72, 74, 19, 210, 210, 192, 192];
+    pub const LENDING_ACCOUNT_BORROW: [u8; 8] = [4, 126, 116, 53, 48, 5, 212, 31];
+    pub const LENDING_ACCOUNT_REPAY: [u8; 8] = [79, 209, 172, 177, 222, 51, 173, 151];
+    pub const LENDING_ACCOUNT_LIQUIDATE: [u8; 8] = [214, 169, 151, 213, 251, 167, 86, 219];
+    pub const LENDING_ACCOUNT_REDEEM_RECEIPTS: [u8; 8] = [99, 51, 165, 6, 213, 228, 47, 97];
+    pub const ASTROLEND_ACCOUNT_INITIALIZE: [u8; 8] = [62, 220, 16, 240, 167, 236, 72, 107];
+    pub const LENDING_ACCOUNT_WITHDRAW_EMISSIONS: [u8; 8] = [234, 22, 84, 214, 118, 176, 140, 170];
+}
+
+pub fn find_liquidity_vault_address(bank: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LIQUIDITY_VAULT_SEED.as_bytes(), bank.as_ref()], &ID)
+}
+
+pub fn find_liquidity_vault_authority_address(bank: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[LIQUIDITY_VAULT_AUTHORITY_SEED.as_bytes(), bank.as_ref()],
+        &ID,
+    )
+}
+
+pub fn find_insurance_vault_address(bank: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[INSURANCE_VAULT_SEED.as_bytes(), bank.as_ref()], &ID)
+}
+
+pub fn find_receipt_mint_address(bank: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[RECEIPT_MINT_SEED.as_bytes(), bank.as_ref()], &ID)
+}
+
+pub fn find_emissions_auth_address(bank: &Pubkey, emissions_mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            EMISSIONS_AUTH_SEED.as_bytes(),
+            bank.as_ref(),
+            emissions_mint.as_ref(),
+        ],
+        &ID,
+    )
+}
+
+pub fn find_emissions_vault_address(bank: &Pubkey, emissions_mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            EMISSIONS_TOKEN_ACCOUNT_SEED.as_bytes(),
+            bank.as_ref(),
+            emissions_mint.as_ref(),
+        ],
+        &ID,
+    )
+}
+
+pub fn find_whitelist_entry_address(astrolend_group: &Pubkey, wallet: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            WHITELIST_SEED.as_bytes(),
+            astrolend_group.as_ref(),
+            wallet.as_ref(),
+        ],
+        &ID,
+    )
+}
+
+/// A bank and the oracle account the program reads its price from.
+///
+/// For Pyth push oracles this is the price update account, not the feed id stored in the bank config.
+#[derive(Clone, Copy, Debug)]
+pub struct BankObservation {
+    pub bank: Pubkey,
+    pub oracle: Pubkey,
+}
+
+/// Bank/oracle pairs for every active balance of an account, in the order the risk engine expects.
+pub fn observation_account_metas(observations: &[BankObservation]) -> Vec<AccountMeta> {
+    observations
+        .iter()
+        .flat_map(|o| {
+            [
+                AccountMeta::new_readonly(o.bank, false),
+                AccountMeta::new_readonly(o.oracle, false),
+            ]
+        })
+        .collect()
+}
+
+/// Groups with a TVL cap track deposits and withdrawals, which needs the bank and its oracle as
+/// the last remaining accounts of `make_deposit_ix`/`make_withdraw_ix`.
+pub fn add_tvl_observation(ix: &mut Instruction, observation: BankObservation) {
+    ix.accounts.extend(observation_account_metas(&[observation]));
+}
+
+/// Token-2022 banks require the mint as the first remaining account.
+fn push_mint_if_t22(accounts: &mut Vec<AccountMeta>, token_program: &Pubkey, mint: &Pubkey) {
+    if *token_program == SPL_TOKEN_2022_PROGRAM_ID {
+        accounts.push(AccountMeta::new_readonly(*mint, false));
+    }
+}
+
+/// Optional account of deposits and borrows, only read by groups that require a whitelist.
+fn push_whitelist_entry(
+    accounts: &mut Vec<AccountMeta>,
+    astrolend_group: &Pubkey,
+    wallet: &Pubkey,
+) {
+    accounts.push(AccountMeta::new_readonly(
+        find_whitelist_entry_address(astrolend_group, wallet).0,
+        false,
+    ));
+}
+
+fn instruction_data(discriminator: [u8; 8], amount: u64, flag: Option<Option<bool>>) -> Vec<u8> {
+    let mut data = Vec::with_capacity(18);
+    data.extend_from_slice(&discriminator);
+    data.extend_from_slice(&amount.to_le_bytes());
+    match flag {
+        Some(None) => data.push(0),
+        Some(Some(value)) => data.extend_from_slice(&[1, value as u8]),
+        None => {}
+    }
+    data
+}
+
+fn push_option_bool(data: &mut Vec<u8>, value: Option<bool>) {
+    match value {
+        Some(value) => data.extend_from_slice(&[1, value as u8]),
+        None => data.push(0),
+    }
+}
+
+fn push_min_amount_out(data: &mut Vec<u8>, min_amount_out: Option<u64>) {
+    match min_amount_out {
+        Some(value) => {
+            data.push(1);
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        None => data.push(0),
+    }
+}
+
+/// `astrolend_account` is a new account signing for its creation, a fresh keypair or a PDA of the
+/// calling program. `authority` doesn't pay for it and can be a PDA as well.
+pub fn make_initialize_account_ix(
+    astrolend_group: Pubkey,
+    astrolend_account: Pubkey,
+    authority: Pubkey,
+    fee_payer: Pubkey,
+    account_tag: Option<[u8; 8]>,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(astrolend_group, false),
+        AccountMeta::new(astrolend_account, true),
+        AccountMeta::new_readonly(authority, true),
+        AccountMeta::new(fee_payer, true),
+        AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+        AccountMeta::new_readonly(
+            find_whitelist_entry_address(&astrolend_group, &authority).0,
+            false,
+        ),
+    ];
+
+    let mut data = discriminator::ASTROLEND_ACCOUNT_INITIALIZE.to_vec();
+    match account_tag {
+        Some(account_tag) => {
+            data.push(1);
+            data.extend_from_slice(&account_tag);
+        }
+        None => data.push(0),
+    }
+
+    Instruction {
+        program_id: ID,
+        accounts,
+        data,
+    }
+}
+
+/// Passes the whitelist entry of `authority`, groups that require a whitelist reject deposits
+/// made `on_behalf_of` the account, the entry of the account authority isn't known here.
+/// With `receipt_token_account` the deposit mints receipts to it instead of crediting the
+/// account, the bank must have been created with a receipt mint.
+#[allow(clippy::too_many_arguments)]
+pub fn make_deposit_ix(
+    astrolend_group: Pubkey,
+    astrolend_account: Pubkey,
+    authority: Pubkey,
+    bank: Pubkey,
+    bank_mint: Pubkey,
+    signer_token_account: Pubkey,
+    token_program: Pubkey,
+    amount: u64,
+    on_behalf_of: Option<bool>,
+    receipt_token_account: Option<Pubkey>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(astrolend_group, false),
+        AccountMeta::new(astrolend_account, false),
+        AccountMeta::new_readonly(authority, true),
+        AccountMeta::new(bank, false),
+        AccountMeta::new(signer_token_account, false),
+        AccountMeta::new(find_liquidity_vault_address(&bank).0, false),
+        AccountMeta::new_readonly(token_program, false),
+    ];
+    if on_behalf_of.unwrap_or(false) {
+        accounts.push(AccountMeta::new_readonly(ID, false));
+    } else {
+        push_whitelist_entry(&mut accounts, &astrolend_group, &authority);
+    }
+    push_mint_if_t22(&mut accounts, &token_program, &bank_mint);
+    if let Some(receipt_token_account) = receipt_token_account {
+        accounts.extend([
+            AccountMeta::new(find_receipt_mint_address(&bank).0, false),
+            AccountMeta::new(receipt_token_account, false),
+            AccountMeta::new_readonly(find_liquidity_vault_authority_address(&bank).0, false),
+        ]);
+    }
+
+    let mut data = instruction_data(
+        discriminator::LENDING_ACCOUNT_DEPOSIT,
+        amount,
+        Some(on_behalf_of),
+    );
+    push_option_bool(&mut data, receipt_token_account.map(|_| true));
+
+    Instruction {
+        program_id: ID,
+        accounts,
+        data,
+    }
+}
+
+/// Burns `receipts` of the receipt token account owned by `signer` for the asset shares they
+/// stand for, credited to `astrolend_account`. Passes the whitelist entry of the account
+/// `authority`, only read by groups that require a whitelist.
+#[allow(clippy::too_many_arguments)]
+pub fn make_redeem_receipts_ix(
+    astrolend_group: Pubkey,
+    astrolend_account: Pubkey,
+    authority: Pubkey,
+    signer: Pubkey,
+    bank: Pubkey,
+    signer_receipt_account: Pubkey,
+    token_program: Pubkey,
+    receipts: u64,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(astrolend_group, false),
+        AccountMeta::new(astrolend_account, false),
+        AccountMeta::new_readonly(signer, true),
+        AccountMeta::new(bank, false),
+        AccountMeta::new(find_receipt_mint_address(&bank).0, false),
+        AccountMeta::new(signer_receipt_account, false),
+        AccountMeta::new_readonly(token_program, false),
+    ];
+    push_whitelist_entry(&mut accounts, &astrolend_group, &authority);
+
+    Instruction {
+        program_id: ID,
+        accounts,
+        data: instruction_data(
+            discriminator::LENDING_ACCOUNT_REDEEM_RECEIPTS,
+            receipts,
+            None,
+        ),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn make_repay_ix(
+    astrolend_group: Pubkey,
+    astrolend_account: Pubkey,
+    authority: Pubkey,
+    bank: Pubkey,
+    bank_mint: Pubkey,
+    signer_token_account: Pubkey,
+    token_program: Pubkey,
+    amount: u64,
+    repay_all: Option<bool>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(astrolend_group, false),
+        AccountMeta::new(astrolend_account, false),
+        AccountMeta::new_readonly(authority, true),
+        AccountMeta::new(bank, false),
+        AccountMeta::new(signer_token_account, false),
+        AccountMeta::new(find_liquidity_vault_address(&bank).0, false),
+        AccountMeta::new_readonly(token_program, false),
+    ];
+    push_mint_if_t22(&mut accounts, &token_program, &bank_mint);
+
+    Instruction {
+        program_id: ID,
+        accounts,
+        data: instruction_data(
+            discriminator::LENDING_ACCOUNT_REPAY,
+            amount,
+            Some(repay_all),
+        ),
+    }
+}
+
+/// `observations` must cover every balance that stays active after the withdraw;
+/// when `withdraw_all` is set, the withdrawn bank should be left out.
+/// Accounts without liabilities can pass no observations at all.
+/// With `min_amount_out`, the withdraw fails if less than that arrives after transfer fees.
+#[allow(clippy::too_many_arguments)]
+pub fn make_withdraw_ix(
+    astrolend_group: Pubkey,
+    astrolend_account: Pubkey,
+    authority: Pubkey,
+    bank: Pubkey,
+    bank_mint: Pubkey,
+    destination_token_account: Pubkey,
+    token_program: Pubkey,
+    amount: u64,
+    withdraw_all: Option<bool>,
+    min_amount_out: Option<u64>,
+    observations: &[BankObservation],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(astrolend_group, false),
+        AccountMeta::new(astrolend_account, false),
+        AccountMeta::new_readonly(authority, true),
+        AccountMeta::new(bank, false),
+        AccountMeta::new(destination_token_account, false),
+        AccountMeta::new(find_liquidity_vault_authority_address(&bank).0, false),
+        AccountMeta::new(find_liquidity_vault_address(&bank).0, false),
+        AccountMeta::new_readonly(token_program, false),
+    ];
+    push_mint_if_t22(&mut accounts, &token_program, &bank_mint);
+    accounts.extend(observation_account_metas(observations));
+
+    let mut data = instruction_data(
+        discriminator::LENDING_ACCOUNT_WITHDRAW,
+        amount,
+        Some(withdraw_all),
+    );
+    push_min_amount_out(&mut data, min_amount_out);
+
+    Instruction {
+        program_id: ID,
+        accounts,
+        data,
+    }
+}
+
+/// `observations` must cover every active balance, including the borrowed bank.
+/// `referrer_token_account` receives the referral share of the bank origination fee.
+/// With `min_amount_out`, the borrow fails if less than that arrives after transfer fees.
+#[allow(clippy::too_many_arguments)]
+pub fn make_borrow_ix(
+    astrolend_group: Pubkey,
+    astrolend_account: Pubkey,
+    authority: Pubkey,
+    bank: Pubkey,
+    bank_mint: Pubkey,
+    destination_token_account: Pubkey,
+    token_program: Pubkey,
+    amount: u64,
+    referrer_token_account: Option<Pubkey>,
+    min_amount_out: Option<u64>,
+    observations: &[BankObservation],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(astrolend_group, false),
+        AccountMeta::new(astrolend_account, false),
+        AccountMeta::new_readonly(authority, true),
+        AccountMeta::new(bank, false),
+        AccountMeta::new(destination_token_account, false),
+        AccountMeta::new(find_liquidity_vault_authority_address(&bank).0, false),
+        AccountMeta::new(find_liquidity_vault_address(&bank).0, false),
+        AccountMeta::new_readonly(token_program, false),
+    ];
+    push_whitelist_entry(&mut accounts, &astrolend_group, &authority);
+    push_mint_if_t22(&mut accounts, &token_program, &bank_mint);
+    if let Some(referrer_token_account) = referrer_token_account {
+        accounts.push(AccountMeta::new(referrer_token_account, false));
+    }
+    accounts.extend(observation_account_metas(observations));
+
+    let mut data = instruction_data(
+        discriminator::LENDING_ACCOUNT_BORROW,
+        amount,
+        Some(referrer_token_account.map(|_| true)),
+    );
+    push_min_amount_out(&mut data, min_amount_out);
+
+    Instruction {
+        program_id: ID,
+        accounts,
+        data,
+    }
+}
+
+/// Claims the emissions of the account balance in `bank` to `destination_account`.
+/// `observations` are only read by groups letting settled emissions count as collateral, where
+/// they must cover every active balance.
+#[allow(clippy::too_many_arguments)]
+pub fn make_withdraw_emissions_ix(
+    astrolend_group: Pubkey,
+    astrolend_account: Pubkey,
+    authority: Pubkey,
+    bank: Pubkey,
+    emissions_mint: Pubkey,
+    destination_account: Pubkey,
+    token_program: Pubkey,
+    observations: &[BankObservation],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(astrolend_group, false),
+        AccountMeta::new(astrolend_account, false),
+        AccountMeta::new_readonly(authority, true),
+        AccountMeta::new(bank, false),
+        AccountMeta::new_readonly(emissions_mint, false),
+        AccountMeta::new_readonly(find_emissions_auth_address(&bank, &emissions_mint).0, false),
+        AccountMeta::new(find_emissions_vault_address(&bank, &emissions_mint).0, false),
+        AccountMeta::new(destination_account, false),
+        AccountMeta::new_readonly(token_program, false),
+    ];
+    accounts.extend(observation_account_metas(observations));
+
+    Instruction {
+        program_id: ID,
+        accounts,
+        data: discriminator::LENDING_ACCOUNT_WITHDRAW_EMISSIONS.to_vec(),
+    }
+}
+
+/// Remaining accounts are laid out as:
+/// `[liab mint (Token-2022 only), asset oracle, liab oracle, liquidator observations.., liquidatee observations.., transfer hook accounts..]`
+///
+/// `transfer_hook_accounts` are the extra accounts of the liability mint transfer hook, for the
+/// insurance fee transfer out of the liquidity vault. They go last, the observations are read
+/// from fixed positions.
+#[allow(clippy::too_many_arguments)]
+pub fn make_liquidate_ix(
+    astrolend_group: Pubkey,
+    liquidator_astrolend_account: Pubkey,
+    authority: Pubkey,
+    liquidatee_astrolend_account: Pubkey,
+    asset_bank: BankObservation,
+    liab_bank: BankObservation,
+    liab_mint: Pubkey,
+    token_program: Pubkey,
+    asset_amount: u64,
+    liquidator_observations: &[BankObservation],
+    liquidatee_observations: &[BankObservation],
+    transfer_hook_accounts: &[AccountMeta],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(astrolend_group, false),
+        AccountMeta::new(asset_bank.bank, false),
+        AccountMeta::new(liab_bank.bank, false),
+        AccountMeta::new(liquidator_astrolend_account, false),
+        AccountMeta::new_readonly(authority, true),
+        AccountMeta::new(liquidatee_astrolend_account, false),
+        AccountMeta::new(find_liquidity_vault_authority_address(&liab_bank.bank).0, false),
+        AccountMeta::new(find_liquidity_vault_address(&liab_bank.bank).0, false),
+        AccountMeta::new(find_insurance_vault_address(&liab_bank.bank).0, false),
+        AccountMeta::new_readonly(token_program, false),
+    ];
+    push_mint_if_t22(&mut accounts, &token_program, &liab_mint);
+    accounts.push(AccountMeta::new_readonly(asset_bank.oracle, false));
+    accounts.push(AccountMeta::new_readonly(liab_bank.oracle, false));
+    accounts.extend(observation_account_metas(liquidator_observations));
+    accounts.extend(observation_account_metas(liquidatee_observations));
+    accounts.extend_from_slice(transfer_hook_accounts);
+
+    Instruction {
+        program_id: ID,
+        accounts,
+        data: instruction_data(discriminator::LENDING_ACCOUNT_LIQUIDATE, asset_amount, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::{Discriminator, InstructionData};
+
+    #[test]
+    fn matches_program() {
+        assert_eq!(ID, astrolend::ID);
+        assert_eq!(
+            discriminator::LENDING_ACCOUNT_DEPOSIT,
+            astrolend::instruction::LendingAccountDeposit::DISCRIMINATOR
+        );
+        assert_eq!(
+            discriminator::LENDING_ACCOUNT_WITHDRAW,
+            astrolend::instruction::LendingAccountWithdraw::DISCRIMINATOR
+        );
+        assert_eq!(
+            discriminator::LENDING_ACCOUNT_BORROW,
+            astrolend::instruction::LendingAccountBorrow::DISCRIMINATOR
+        );
+        assert_eq!(
+            discriminator::LENDING_ACCOUNT_REPAY,
+            astrolend::instruction::LendingAccountRepay::DISCRIMINATOR
+        );
+        assert_eq!(
+            discriminator::LENDING_ACCOUNT_LIQUIDATE,
+            astrolend::instruction::LendingAccountLiquidate::DISCRIMINATOR
+        );
+        assert_eq!(
+            discriminator::LENDING_ACCOUNT_REDEEM_RECEIPTS,
+            astrolend::instruction::LendingAccountRedeemReceipts::DISCRIMINATOR
+        );
+        assert_eq!(
+            discriminator::ASTROLEND_ACCOUNT_INITIALIZE,
+            astrolend::instruction::AstrolendAccountInitialize::DISCRIMINATOR
+        );
+        assert_eq!(
+            discriminator::LENDING_ACCOUNT_WITHDRAW_EMISSIONS,
+            astrolend::instruction::LendingAccountWithdrawEmissions::DISCRIMINATOR
+        );
+        assert_eq!(find_receipt_mint_address(&ID), astrolend::utils::find_receipt_mint_pda(&ID));
+        assert_eq!(SYSTEM_PROGRAM_ID, solana_program::system_program::ID);
+    }
+
+    #[test]
+    fn initialize_account_data_matches_program() {
+        let key = Pubkey::new_unique();
+        for account_tag in [None, Some(*b"vault-01")] {
+            let ix = make_initialize_account_ix(key, key, key, key, account_tag);
+            assert_eq!(
+                ix.data,
+                astrolend::instruction::AstrolendAccountInitialize { account_tag }.data()
+            );
+        }
+    }
+
+    #[test]
+    fn deposit_and_redeem_data_match_program() {
+        let key = Pubkey::new_unique();
+        let ix = make_deposit_ix(key, key, key, key, key, key, key, 1_000, None, Some(key));
+        assert_eq!(
+            ix.data,
+            astrolend::instruction::LendingAccountDeposit {
+                amount: 1_000,
+                on_behalf_of: None,
+                mint_receipts: Some(true),
+            }
+            .data()
+        );
+
+        let ix = make_deposit_ix(key, key, key, key, key, key, key, 1_000, Some(true), None);
+        assert_eq!(
+            ix.data,
+            astrolend::instruction::LendingAccountDeposit {
+                amount: 1_000,
+                on_behalf_of: Some(true),
+                mint_receipts: None,
+            }
+            .data()
+        );
+
+        let ix = make_redeem_receipts_ix(key, key, key, key, key, key, key, 1_000);
+        assert_eq!(
+            ix.data,
+            astrolend::instruction::LendingAccountRedeemReceipts { receipts: 1_000 }.data()
+        );
+    }
+
+    #[test]
+    fn withdraw_and_borrow_data_match_program() {
+        let key = Pubkey::new_unique();
+        let ix = make_withdraw_ix(
+            key,
+            key,
+            key,
+            key,
+            key,
+            key,
+            key,
+            1_000,
+            Some(true),
+            Some(990),
+            &[],
+        );
+        assert_eq!(
+            ix.data,
+            astrolend::instruction::LendingAccountWithdraw {
+                amount: 1_000,
+                withdraw_all: Some(true),
+                min_amount_out: Some(990),
+            }
+            .data()
+        );
+
+        let ix = make_borrow_ix(key, key, key, key, key, key, key, 1_000, None, None, &[]);
+        assert_eq!(
+            ix.data,
+            astrolend::instruction::LendingAccountBorrow {
+                amount: 1_000,
+                with_referrer: None,
+                min_amount_out: None,
+            }
+            .data()
+        );
+    }
+}
@@ -2,7 +2,8 @@ use anchor_lang::prelude::*;
 use fixed::types::I80F48;
 use fixed_macro::types::I80F48;
 use pyth_solana_receiver_sdk::price_update::VerificationLevel;
-use solana_program::pubkey;
+use solana_program::{pubkey, pubkey::MAX_SEED_LEN};
+use static_assertions::const_assert;
 
 pub const LIQUIDITY_VAULT_AUTHORITY_SEED: &str = "liquidity_vault_auth";
 pub const INSURANCE_VAULT_AUTHORITY_SEED: &str = "insurance_vault_auth";
@@ -15,6 +16,16 @@ pub const FEE_VAULT_SEED: &str = "fee_vault";
 pub const EMISSIONS_AUTH_SEED: &str = "emissions_auth_seed";
 pub const EMISSIONS_TOKEN_ACCOUNT_SEED: &str = "emissions_token_account_seed";
 
+pub const WHITELIST_SEED: &str = "whitelist";
+
+pub const INSURANCE_STAKE_SEED: &str = "insurance_stake";
+
+pub const RECEIPT_MINT_SEED: &str = "receipt_mint";
+
+pub const NATIVE_TOKEN_ACCOUNT_SEED: &str = "native_token_account";
+
+pub const PENDING_CONFIG_SEED: &str = "pending_config";
+
 cfg_if::cfg_if! {
     if #[cfg(feature = "devnet")] {
         pub const PYTH_ID: Pubkey = pubkey!("gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s");
@@ -37,6 +48,32 @@ cfg_if::cfg_if! {
 /// TODO: Make these variable per bank
 pub const LIQUIDATION_LIQUIDATOR_FEE: I80F48 = I80F48!(0.025);
 pub const LIQUIDATION_INSURANCE_FEE: I80F48 = I80F48!(0.025);
+/// Penalty on self liquidations, all of it goes to insurance.
+pub const SELF_LIQUIDATION_INSURANCE_FEE: I80F48 = I80F48!(0.025);
+
+/// Cap on the bank `withdraw_fee_rate`.
+pub const MAX_WITHDRAW_FEE_RATE: I80F48 = I80F48!(0.01);
+
+/// Number of most recent liquidations kept on a astrolend account.
+pub const LIQUIDATION_HISTORY_LEN: usize = 2;
+
+/// Number of (collateral tag, liability tag) pairs in a group emode table.
+pub const MAX_EMODE_ENTRIES: usize = 8;
+/// Bank `emode_tag` of banks outside of any emode pair.
+pub const EMODE_TAG_NONE: u16 = 0;
+
+/// Number of destinations in a group fee split.
+pub const MAX_FEE_SPLIT_ENTRIES: usize = 4;
+/// Sum of the `bps` of the entries of a fee split.
+pub const FEE_SPLIT_TOTAL_BPS: u16 = 10_000;
+
+/// Number of utilization snapshots kept on a bank.
+pub const UTILIZATION_HISTORY_LEN: usize = 24;
+/// Minimum number of seconds between two utilization snapshots.
+pub const UTILIZATION_SNAPSHOT_INTERVAL: i64 = 3600;
+
+/// Seconds insurance stakes stay locked after a bankruptcy drew on the insurance vault.
+pub const INSURANCE_STAKE_COOLDOWN: i64 = 7 * 24 * 3600;
 
 pub const SECONDS_PER_YEAR: I80F48 = I80F48!(31_536_000);
 
@@ -49,13 +86,37 @@ pub const MAX_SWB_ORACLE_AGE: u64 = 3 * 60;
 pub const CONF_INTERVAL_MULTIPLE: I80F48 = I80F48!(2.12);
 /// Range that contains 95% price data distribution in a normal distribution
 pub const STD_DEV_MULTIPLE: I80F48 = I80F48!(1.96);
-/// Maximum confidence interval allowed
+/// Maximum confidence interval allowed, as a share of the price, for banks leaving
+/// `oracle_max_confidence_bps` at 0
 pub const MAX_CONF_INTERVAL: I80F48 = I80F48!(0.05);
+/// Highest `oracle_max_confidence_bps` a bank can be configured with
+pub const MAX_ORACLE_CONFIDENCE_BPS: u16 = 2_000;
+/// Highest `oracle_max_age` a bank can be configured with through `OracleParamsOpt`
+pub const MAX_ORACLE_MAX_AGE: u16 = 3_600;
+/// Highest `init_health_buffer_bps` a group can be configured with
+pub const MAX_INIT_HEALTH_BUFFER_BPS: u16 = 5_000;
+/// Highest `oracle_change_max_deviation_bps` a group can be configured with
+pub const MAX_ORACLE_CHANGE_DEVIATION_BPS: u16 = 10_000;
+/// Highest `config_timelock_secs` a group can be configured with, 30 days
+pub const MAX_CONFIG_TIMELOCK_SECS: u32 = 30 * 24 * 60 * 60;
 
 pub const USDC_EXPONENT: i32 = 6;
 
 pub const MAX_ORACLE_KEYS: usize = 5;
 
+/// Number of balance slots of a astrolend account.
+pub const MAX_LENDING_ACCOUNT_BALANCES: usize = 16;
+
+/// Current `AstrolendAccount` layout version, accounts created before versioning are at 0 until
+/// migrated, see `lending_account_migrate`.
+pub const ASTROLEND_ACCOUNT_VERSION: u8 = 1;
+
+/// Highest mint decimals a bank can be created with.
+///
+/// Valuation scales native amounts to UI amounts before applying prices, so at 18 decimals
+/// balances below ~1e-15 UI tokens are valued at zero. Must stay below `MAX_EXP_10_I80F48`.
+pub const MAX_MINT_DECIMALS: u8 = 18;
+
 /// Any balance below 1 SPL token amount is treated as none,
 /// this is to account for any artifacts resulting from binary fraction arithemtic.
 pub const EMPTY_BALANCE_THRESHOLD: I80F48 = I80F48!(1);
@@ -66,20 +127,73 @@ pub const EMPTY_BALANCE_THRESHOLD: I80F48 = I80F48!(1);
 /// This is USD denominated, so 0.001 = $0.1
 pub const BANKRUPT_THRESHOLD: I80F48 = I80F48!(0.1);
 
+/// Maximum relative difference between the current and the new oracle price when
+/// changing the oracle of a bank, for groups with `oracle_change_max_deviation_bps` at 0.
+pub const ORACLE_CHANGE_MAX_PRICE_DEVIATION: I80F48 = I80F48!(0.2);
+
+/// Utilization used for interest rates when liabilities exceed deposits by more, including
+/// banks left with no deposits at all after socialized losses.
+pub const MAX_ACCRUAL_UTILIZATION_RATE: I80F48 = I80F48!(10);
+
+/// Cap on the group `ir_emergency_multiplier`.
+pub const MAX_IR_EMERGENCY_MULTIPLIER: I80F48 = I80F48!(5);
+
 /// Comparios threshold used to account for arithmetic artifacts on balances
 pub const ZERO_AMOUNT_THRESHOLD: I80F48 = I80F48!(0.0001);
 
 pub const EMISSIONS_FLAG_BORROW_ACTIVE: u64 = 1 << 0;
 pub const EMISSIONS_FLAG_LENDING_ACTIVE: u64 = 1 << 1;
 pub const PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG: u64 = 1 << 2;
+pub const UTILIZATION_HISTORY_FLAG: u64 = 1 << 3;
+/// Set at creation on banks created with `lending_pool_add_bank_with_seed`, never changed after.
+pub const SEEDED_BANK_FLAG: u64 = 1 << 4;
+/// Set at creation on banks added without an insurance vault, never changed after.
+/// Insurance fees are collected as group fees and bad debt is socialized right away.
+pub const INSURANCE_DISABLED_FLAG: u64 = 1 << 5;
+/// Set by the group admin to wind the bank down, see `Bank::settlement_price`.
+pub const SETTLEMENT_MODE_FLAG: u64 = 1 << 6;
+/// Deposits can be borrowed but never count as collateral, asset weights must stay zero.
+pub const BORROW_ONLY_FLAG: u64 = 1 << 7;
+/// Set at creation on banks added with a receipt mint, never changed after.
+/// Deposits can export their asset shares as transferable receipt tokens.
+pub const RECEIPTS_ENABLED_FLAG: u64 = 1 << 8;
+/// Set by the group admin through `BankConfigOpt::config_frozen`. The bank config then only
+/// changes along with unfreezing it, group wide oracle parameter updates skip the bank.
+pub const CONFIG_FROZEN_FLAG: u64 = 1 << 9;
 
 pub(crate) const EMISSION_FLAGS: u64 = EMISSIONS_FLAG_BORROW_ACTIVE | EMISSIONS_FLAG_LENDING_ACTIVE;
-pub(crate) const GROUP_FLAGS: u64 = PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG;
+pub(crate) const GROUP_FLAGS: u64 = PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG
+    | UTILIZATION_HISTORY_FLAG
+    | BORROW_ONLY_FLAG
+    | CONFIG_FROZEN_FLAG;
+pub(crate) const CREATION_FLAGS: u64 =
+    SEEDED_BANK_FLAG | INSURANCE_DISABLED_FLAG | RECEIPTS_ENABLED_FLAG;
+
+/// Group flag, emit an `AccountHealthSnapshotEvent` after the health check of borrows and
+/// withdrawals. Off by default to keep log volume down.
+pub const HEALTH_SNAPSHOT_EVENTS_FLAG: u64 = 1 << 0;
+
+/// Group flag, only wallets with a whitelist entry can open accounts, deposit or borrow.
+/// Accounts of wallets removed from the whitelist can still withdraw, repay and be liquidated.
+pub const WHITELIST_REQUIRED_FLAG: u64 = 1 << 1;
+
+/// Group flag, settled emissions count as collateral when the emissions mint has a bank in the
+/// group, see `RiskEngine`. Claiming them then requires a health check.
+pub const EMISSIONS_COLLATERAL_FLAG: u64 = 1 << 2;
+
+pub(crate) const ASTROLEND_GROUP_FLAGS: u64 =
+    HEALTH_SNAPSHOT_EVENTS_FLAG | WHITELIST_REQUIRED_FLAG | EMISSIONS_COLLATERAL_FLAG;
 
 /// Cutoff timestamp for balance last_update used in accounting collected emissions.
 /// Any balance updates before this timestamp are ignored, and current_timestamp is used instead.
 pub const MIN_EMISSIONS_START_TIME: u64 = 1681989983;
 
+/// Maximum number of accounts settled by a single `lending_account_settle_emissions_many`.
+pub const MAX_SETTLE_EMISSIONS_ACCOUNTS: usize = 16;
+
+/// Remaining accounts per bank claimed by `lending_account_withdraw_emissions_many`.
+pub const EMISSIONS_CLAIM_ACCOUNTS_PER_BANK: usize = 5;
+
 pub const MAX_EXP_10_I80F48: usize = 24;
 pub const EXP_10_I80F48: [I80F48; MAX_EXP_10_I80F48] = [
     I80F48!(1),                        // 10^0
@@ -139,3 +253,37 @@ pub const TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE: u64 = 0;
 pub const MIN_PYTH_PUSH_VERIFICATION_LEVEL: VerificationLevel = VerificationLevel::Full;
 pub const PYTH_PUSH_PYTH_SPONSORED_SHARD_ID: u16 = 0;
 pub const PYTH_PUSH_ASTROLEND_SPONSORED_SHARD_ID: u16 = 3301;
+
+/// Number of oracle keys of a bank config, for clients sizing buffers.
+pub const fn max_oracle_keys() -> usize {
+    MAX_ORACLE_KEYS
+}
+
+/// Number of balance slots of a astrolend account, for clients sizing buffers.
+pub const fn max_balances() -> usize {
+    MAX_LENDING_ACCOUNT_BALANCES
+}
+
+// Every seed is used as a single PDA seed
+const_assert!(LIQUIDITY_VAULT_AUTHORITY_SEED.len() <= MAX_SEED_LEN);
+const_assert!(INSURANCE_VAULT_AUTHORITY_SEED.len() <= MAX_SEED_LEN);
+const_assert!(FEE_VAULT_AUTHORITY_SEED.len() <= MAX_SEED_LEN);
+const_assert!(LIQUIDITY_VAULT_SEED.len() <= MAX_SEED_LEN);
+const_assert!(INSURANCE_VAULT_SEED.len() <= MAX_SEED_LEN);
+const_assert!(FEE_VAULT_SEED.len() <= MAX_SEED_LEN);
+const_assert!(EMISSIONS_AUTH_SEED.len() <= MAX_SEED_LEN);
+const_assert!(EMISSIONS_TOKEN_ACCOUNT_SEED.len() <= MAX_SEED_LEN);
+const_assert!(WHITELIST_SEED.len() <= MAX_SEED_LEN);
+const_assert!(INSURANCE_STAKE_SEED.len() <= MAX_SEED_LEN);
+const_assert!(RECEIPT_MINT_SEED.len() <= MAX_SEED_LEN);
+const_assert!(NATIVE_TOKEN_ACCOUNT_SEED.len() <= MAX_SEED_LEN);
+const_assert!(PENDING_CONFIG_SEED.len() <= MAX_SEED_LEN);
+
+// Mint decimals and price exponents index the powers of ten tables
+const_assert!((MAX_MINT_DECIMALS as usize) < MAX_EXP_10_I80F48);
+const_assert!(MAX_EXP_10 <= MAX_EXP_10_I80F48);
+
+// Bank flags changed by different paths never overlap
+const_assert!(EMISSION_FLAGS & GROUP_FLAGS == 0);
+const_assert!((EMISSION_FLAGS | GROUP_FLAGS) & CREATION_FLAGS == 0);
+const_assert!((EMISSION_FLAGS | GROUP_FLAGS | CREATION_FLAGS) & SETTLEMENT_MODE_FLAG == 0);
@@ -24,7 +24,7 @@ pub enum AstrolendError {
     InvalidBankAccount,
     #[msg("RiskEngine rejected due to either bad health or stale oracles")] // 6010
     RiskEngineInitRejected,
-    #[msg("Lending account balance slots are full")] // 6011
+    #[msg("All 16 lending account balance slots are in use")] // 6011
     LendingAccountBalanceSlotsFull,
     #[msg("Bank already exists")] // 6012
     BankAlreadyExists,
@@ -96,6 +96,96 @@ pub enum AstrolendError {
     IllegalAction,
     #[msg("Token22 Banks require mint account as first remaining account")] // 6047
     T22MintRequired,
+    #[msg("Bank mint decimals exceed the supported maximum")] // 6048
+    MintDecimalsNotSupported,
+    #[msg("Account does not accept deposits from third parties")] // 6049
+    ThirdPartyDepositNotAllowed,
+    #[msg("Token program does not match the bank mint")] // 6050
+    InvalidTokenProgram,
+    #[msg("Borrow value exceeds the group single borrow cap")] // 6051
+    BorrowCapExceeded,
+    #[msg("New oracle price deviates too much from the current oracle")] // 6052
+    OracleChangePriceMismatch,
+    #[msg("Invalid referrer token account")] // 6053
+    InvalidReferrer,
+    #[msg("Borrower cannot be its own referrer")] // 6054
+    SelfReferral,
+    #[msg("Deposit opening a new position is below the bank minimum")] // 6055
+    DepositBelowMinimum,
+    #[msg("Borrow opening a new position is below the bank minimum")] // 6056
+    BorrowBelowMinimum,
+    #[msg("Deposit exceeds the group TVL cap")] // 6057
+    GroupTvlCapExceeded,
+    #[msg("Insurance vault required for banks with insurance enabled")] // 6058
+    InsuranceVaultRequired,
+    #[msg("Amount received is below the requested minimum")] // 6059
+    SlippageExceeded,
+    #[msg("Only asset positions can be transferred between accounts")] // 6060
+    IllegalPositionTransfer,
+    #[msg("Too many accounts to settle emissions for")] // 6061
+    TooManyEmissionsSettlements,
+    #[msg("Oracle account doesn't match the bank oracle keys")] // 6062
+    WrongOracleAccountKeys,
+    #[msg("Bank is in settlement mode")] // 6063
+    BankInSettlement,
+    #[msg("The group already has a bank for this mint")] // 6064
+    DuplicateBankMint,
+    #[msg("Wallet is not whitelisted in this group")] // 6065
+    WalletNotWhitelisted,
+    #[msg("Account is healthy or holds enough collateral to be liquidated")] // 6066
+    AccountNotBadDebtCandidate,
+    #[msg("Not enough insurance stake shares")] // 6067
+    InsufficientInsuranceStake,
+    #[msg("Insurance stakes are locked after a bankruptcy paid out insurance")] // 6068
+    InsuranceStakeCooldown,
+    #[msg("Amount exceeds the admin part of the insurance vault")] // 6069
+    InsufficientAdminInsurance,
+    #[msg("Mint account doesn't match the bank mint")] // 6070
+    MintMismatch,
+    #[msg("Invalid address lookup table")] // 6071
+    InvalidLookupTable,
+    #[msg("Deposit receipts are not enabled for this bank")] // 6072
+    ReceiptsDisabled,
+    #[msg("Invalid deposit receipt accounts")] // 6073
+    InvalidReceiptAccounts,
+    #[msg("Withdrawals are frozen in the slot a bankruptcy was processed")] // 6074
+    BankruptcyFreeze,
+    #[msg("Asset weights can't be changed while a weight change is scheduled")] // 6075
+    WeightRampActive,
+    #[msg("Account is already at the current version")] // 6076
+    AccountAlreadyMigrated,
+    #[msg("Invalid emissions claim accounts")] // 6077
+    InvalidEmissionsClaimAccounts,
+    #[msg("Not enough liquidity in the bank vault")] // 6078
+    InsufficientLiquidity,
+    #[msg("Emissions are not fully claimed")] // 6079
+    EmissionsNotFullyClaimed,
+    #[msg("Share values only decrease when a loss is socialized")] // 6080
+    IllegalShareValueDecrease,
+    #[msg("Oracle price is zero, negative or out of range")] // 6081
+    OraclePriceNonPositive,
+    #[msg("Donations need existing deposits to credit")] // 6082
+    DonationWithoutDeposits,
+    #[msg("Bank is in the middle of another instruction")] // 6083
+    ReentrancyGuardActive,
+    #[msg("Reentrancy guard is not stuck")] // 6084
+    ReentrancyGuardNotStuck,
+    #[msg("Bank config is frozen")] // 6085
+    BankConfigFrozen,
+    #[msg("Group has no fee split")] // 6086
+    FeeSplitNotSet,
+    #[msg("Invalid fee split destination token account")] // 6087
+    InvalidFeeSplitDestination,
+    #[msg("Bank mint is not the native mint")] // 6088
+    NativeMintRequired,
+    #[msg("Bank config changes need a pending config while the group has a timelock")] // 6089
+    PendingConfigRequired,
+    #[msg("Pending bank config timelock has not elapsed")] // 6090
+    PendingConfigNotExecutable,
+    #[msg("Destination token account is not allowed by the account")] // 6091
+    DestinationNotAllowed,
+    #[msg("Account needs to be migrated to the current version first")] // 6092
+    AccountNotMigrated,
 }
 
 impl From<AstrolendError> for ProgramError {
@@ -1,4 +1,14 @@
-use crate::{prelude::*, state::astrolend_group::BankConfigOpt};
+use crate::{
+    prelude::*,
+    state::{
+        astrolend_account::AccountConfigOpt,
+        astrolend_group::{
+            BankConfigOpt, EmodeEntryConfig, FeeSplitEntryConfig, GroupConfigValues, OracleConfig,
+            PriceBiasMode, WrappedI80F48,
+        },
+        price::OraclePriceType,
+    },
+};
 use anchor_lang::prelude::*;
 
 // Event headers
@@ -17,17 +27,88 @@ pub struct AccountEventHeader {
     pub astrolend_group: Pubkey,
 }
 
+// Event metadata
+//
+// Carried in the last field of every event, after every other field, so parsers built for an
+// older layout of an event still decode its leading fields and ignore the trailing bytes.
+
+/// Layout version carried in the metadata of every event, bumped whenever an event layout changes.
+pub const EVENT_VERSION: u8 = 2;
+
+/// Built with `group_event_meta!` in instruction handlers.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GroupEventMeta {
+    pub version: u8,
+    /// Discriminator of the instruction that emitted the event.
+    pub instruction: [u8; 8],
+}
+
+/// Built with `account_event_meta!` in instruction handlers.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct AccountEventMeta {
+    pub version: u8,
+    /// Discriminator of the instruction that emitted the event.
+    pub instruction: [u8; 8],
+    /// `account_tag` of the account, since version 2.
+    pub account_tag: [u8; 8],
+}
+
 // astrolend group events
 
 #[event]
 pub struct AstrolendGroupCreateEvent {
     pub header: GroupEventHeader,
+    pub meta: GroupEventMeta,
 }
 
 #[event]
 pub struct AstrolendGroupConfigureEvent {
     pub header: GroupEventHeader,
     pub config: GroupConfig,
+    pub old_admin: Pubkey,
+    pub new_admin: Pubkey,
+    /// Every group parameter before and after the update, including the ones left unchanged.
+    pub old_values: GroupConfigValues,
+    pub new_values: GroupConfigValues,
+    pub meta: GroupEventMeta,
+}
+
+#[event]
+pub struct AstrolendGroupConfigureEmodeEvent {
+    pub header: GroupEventHeader,
+    pub entries: Vec<EmodeEntryConfig>,
+    pub meta: GroupEventMeta,
+}
+
+#[event]
+pub struct AstrolendGroupConfigureFeeSplitEvent {
+    pub header: GroupEventHeader,
+    pub entries: Vec<FeeSplitEntryConfig>,
+    pub meta: GroupEventMeta,
+}
+
+/// Emitted when the group interest rate multiplier is set, alongside the configure event.
+#[event]
+pub struct AstrolendGroupIrEmergencyMultiplierEvent {
+    pub header: GroupEventHeader,
+    pub multiplier: f64,
+    pub expires_at: i64,
+    pub meta: GroupEventMeta,
+}
+
+#[event]
+pub struct AstrolendGroupWhitelistEvent {
+    pub header: GroupEventHeader,
+    pub wallet: Pubkey,
+    pub whitelisted: bool,
+    pub meta: GroupEventMeta,
+}
+
+#[event]
+pub struct AstrolendGroupLookupTableEvent {
+    pub header: GroupEventHeader,
+    pub lookup_table: Pubkey,
+    pub meta: GroupEventMeta,
 }
 
 #[event]
@@ -35,6 +116,9 @@ pub struct LendingPoolBankCreateEvent {
     pub header: GroupEventHeader,
     pub bank: Pubkey,
     pub mint: Pubkey,
+    /// Banks are created able to collateralize, `BORROW_ONLY_FLAG` is set by configuring them.
+    pub borrow_only: bool,
+    pub meta: GroupEventMeta,
 }
 
 #[event]
@@ -43,6 +127,138 @@ pub struct LendingPoolBankConfigureEvent {
     pub bank: Pubkey,
     pub mint: Pubkey,
     pub config: BankConfigOpt,
+    /// Whether the bank is borrow-only after the update, see `BORROW_ONLY_FLAG`.
+    pub borrow_only: bool,
+    /// The oracle setup or keys changed, `config.oracle_keys_and_setup`.
+    pub oracle_changed: bool,
+    /// The oracle max age, confidence cap or bias mode changed, `config.oracle_params`.
+    pub oracle_params_changed: bool,
+    pub meta: GroupEventMeta,
+}
+
+/// Emitted when a bank config change is written to a `PendingBankConfig` instead of the bank,
+/// the bank emits `LendingPoolBankConfigureEvent` once it is executed.
+#[event]
+pub struct LendingPoolBankConfigProposeEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub config: BankConfigOpt,
+    pub executable_at: i64,
+    pub meta: GroupEventMeta,
+}
+
+/// Emitted when the admin cancels a pending bank config change.
+#[event]
+pub struct LendingPoolBankConfigCancelEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub config: BankConfigOpt,
+    pub meta: GroupEventMeta,
+}
+
+/// One per bank of `lending_pool_update_oracle_max_confidence`.
+#[event]
+pub struct LendingPoolBankOracleParamsUpdateEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    /// The bank config is frozen and was left untouched, old and new values are equal.
+    pub skipped: bool,
+    pub old_max_age: u16,
+    pub new_max_age: u16,
+    pub old_max_confidence_bps: u16,
+    pub new_max_confidence_bps: u16,
+    pub old_price_bias_mode: PriceBiasMode,
+    pub new_price_bias_mode: PriceBiasMode,
+    pub meta: GroupEventMeta,
+}
+
+#[event]
+pub struct LendingPoolBankConfigureOracleUncheckedEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub old_oracle: OracleConfig,
+    pub new_oracle: OracleConfig,
+    pub meta: GroupEventMeta,
+}
+
+/// Emitted when a bank enters or leaves settlement mode.
+#[event]
+pub struct LendingPoolBankSettlementPriceEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    /// UI price the bank is settled at, `None` when settlement mode is cleared.
+    pub settlement_price: Option<f64>,
+    /// Oracle price the bank returns to when settlement mode is cleared.
+    pub oracle_price: Option<f64>,
+    pub meta: GroupEventMeta,
+}
+
+/// Emitted when a gradual asset weight change is scheduled, see `Bank::get_asset_weight`.
+#[event]
+pub struct LendingPoolBankWeightRampScheduleEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub start_asset_weight_init: f64,
+    pub start_asset_weight_maint: f64,
+    pub target_asset_weight_init: f64,
+    pub target_asset_weight_maint: f64,
+    pub start_timestamp: i64,
+    pub end_timestamp: i64,
+    pub meta: GroupEventMeta,
+}
+
+/// Emitted when a scheduled asset weight change is cancelled, with the weights it reached.
+#[event]
+pub struct LendingPoolBankWeightRampCancelEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub asset_weight_init: f64,
+    pub asset_weight_maint: f64,
+    pub meta: GroupEventMeta,
+}
+
+/// Emitted when the targets of a scheduled asset weight change are written to the bank config,
+/// by the first bank admin instruction or interest accrual after the change ended.
+#[event]
+pub struct LendingPoolBankWeightRampCompleteEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub asset_weight_init: f64,
+    pub asset_weight_maint: f64,
+    pub meta: GroupEventMeta,
+}
+
+/// Emitted when funding an emissions campaign left the emissions vault with another amount than
+/// the emissions added, the campaign is credited the lower of both.
+#[event]
+pub struct LendingPoolBankEmissionsFundingMismatchEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub emissions_mint: Pubkey,
+    /// Native emissions tokens the campaign was funded with.
+    pub expected: u64,
+    /// Native emissions tokens the emissions vault received after the transfer fee.
+    pub received: u64,
+    pub meta: GroupEventMeta,
+}
+
+/// Emitted when the emissions vault of a fully claimed campaign is closed.
+#[event]
+pub struct LendingPoolBankCloseEmissionsEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub emissions_mint: Pubkey,
+    /// Native emissions tokens left in the vault, sent to the admin before closing it.
+    pub swept_amount: f64,
+    pub meta: GroupEventMeta,
 }
 
 #[event]
@@ -53,6 +269,27 @@ pub struct LendingPoolBankAccrueInterestEvent {
     pub delta: u64,
     pub fees_collected: f64,
     pub insurance_collected: f64,
+    /// Paid from the group fees to top depositors up to the deposit rate floor.
+    pub deposit_floor_subsidy: f64,
+    /// Liabilities exceed deposits, only reachable after socialized losses.
+    pub deficit: bool,
+    pub meta: GroupEventMeta,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct BankCheckpoint {
+    pub bank: Pubkey,
+    pub asset_share_value: f64,
+    pub liability_share_value: f64,
+    pub last_update: i64,
+}
+
+#[event]
+pub struct LendingPoolBanksCheckpointEvent {
+    pub header: GroupEventHeader,
+    pub checkpoint_id: Option<u64>,
+    pub banks: Vec<BankCheckpoint>,
+    pub meta: GroupEventMeta,
 }
 
 #[event]
@@ -64,6 +301,54 @@ pub struct LendingPoolBankCollectFeesEvent {
     pub group_fees_outstanding: f64,
     pub insurance_fees_collected: f64,
     pub insurance_fees_outstanding: f64,
+    pub meta: GroupEventMeta,
+}
+
+#[event]
+pub struct LendingPoolWithdrawFeesEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    /// Fee vault balance after the transfer.
+    pub vault_balance: u64,
+    /// `Bank::lifetime_fees_withdrawn`, including this withdrawal.
+    pub lifetime_withdrawn: u64,
+    pub meta: GroupEventMeta,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct FeeSplitTransfer {
+    /// Token account the share was sent to.
+    pub destination: Pubkey,
+    pub amount: u64,
+}
+
+/// Fee vault balance sent out along the group fee split by `lending_pool_distribute_fees`.
+#[event]
+pub struct LendingPoolDistributeFeesEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub amount: u64,
+    /// One per fee split entry, in table order. Amounts leave the vault, transfer fees of
+    /// Token-2022 mints are taken out of them.
+    pub transfers: Vec<FeeSplitTransfer>,
+    /// `Bank::lifetime_fees_withdrawn`, including this distribution.
+    pub lifetime_withdrawn: u64,
+    pub meta: GroupEventMeta,
+}
+
+#[event]
+pub struct LendingPoolWithdrawInsuranceEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    /// Insurance vault balance after the transfer, staked tokens included.
+    pub vault_balance: u64,
+    /// `Bank::lifetime_insurance_withdrawn`, including this withdrawal.
+    pub lifetime_withdrawn: u64,
+    pub meta: GroupEventMeta,
 }
 
 #[event]
@@ -74,6 +359,61 @@ pub struct LendingPoolBankHandleBankruptcyEvent {
     pub bad_debt: f64,
     pub covered_amount: f64,
     pub socialized_amount: f64,
+    pub pre_asset_share_value: f64,
+    pub post_asset_share_value: f64,
+    pub cumulative_socialized_loss: f64,
+    pub meta: AccountEventMeta,
+}
+
+/// Dust collateral of a bad debt candidate moved to the bank insurance fees during bankruptcy,
+/// transferred to the insurance vault on the next fee collection.
+#[event]
+pub struct LendingPoolBankSweepDustEvent {
+    pub header: AccountEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    /// Native amount removed from the account.
+    pub amount: f64,
+    pub meta: AccountEventMeta,
+}
+
+/// Third party deposit into, or withdrawal from, a bank insurance vault.
+#[event]
+pub struct LendingPoolInsuranceStakeEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub depositor: Pubkey,
+    /// Native amount credited to, or debited from, the stake.
+    pub amount: f64,
+    /// Shares minted on deposit, burned on withdrawal.
+    pub shares: f64,
+    pub deposit: bool,
+    pub meta: GroupEventMeta,
+}
+
+/// Donation credited to the depositors of a bank through the asset share value.
+#[event]
+pub struct LendingPoolDonateEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub donor: Pubkey,
+    /// Native amount received by the liquidity vault, after transfer fees.
+    pub amount: u64,
+    /// Asset share value after the donation.
+    pub asset_share_value: f64,
+    pub meta: GroupEventMeta,
+}
+
+/// Reentrancy guard left set by an earlier slot, cleared by `lending_pool_clear_stuck_guard`.
+#[event]
+pub struct LendingPoolClearStuckGuardEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    /// Slot the guard was entered at.
+    pub guard_slot: u64,
+    pub meta: GroupEventMeta,
 }
 
 // astrolend account events
@@ -81,6 +421,7 @@ pub struct LendingPoolBankHandleBankruptcyEvent {
 #[event]
 pub struct AstrolendAccountCreateEvent {
     pub header: AccountEventHeader,
+    pub meta: AccountEventMeta,
 }
 
 #[event]
@@ -89,6 +430,7 @@ pub struct LendingAccountDepositEvent {
     pub bank: Pubkey,
     pub mint: Pubkey,
     pub amount: u64,
+    pub meta: AccountEventMeta,
 }
 
 #[event]
@@ -98,6 +440,10 @@ pub struct LendingAccountRepayEvent {
     pub mint: Pubkey,
     pub amount: u64,
     pub close_balance: bool,
+    /// Added to the lifetime `total_repaid_native` of the balance, zero for accounts not
+    /// migrated to hold a credit history.
+    pub total_repaid_native_delta: u64,
+    pub meta: AccountEventMeta,
 }
 
 #[event]
@@ -105,7 +451,27 @@ pub struct LendingAccountBorrowEvent {
     pub header: AccountEventHeader,
     pub bank: Pubkey,
     pub mint: Pubkey,
+    /// Same as `amount_pre_fee`, kept for existing consumers.
     pub amount: u64,
+    pub origination_fee: f64,
+    /// Token account that received the referral share of the origination fee, if any.
+    pub referrer: Option<Pubkey>,
+    pub referral_fee: u64,
+    /// Received by the destination token account, less the Token-2022 transfer fee with a
+    /// `min_amount_out`.
+    pub amount_requested: u64,
+    /// Recorded as liability before the origination fee, `amount_requested` plus `fee`.
+    pub amount_pre_fee: u64,
+    /// Token-2022 transfer fee of the current epoch the borrow was grossed up by. Zero for other
+    /// mints and with a `min_amount_out`, the transfer fee is then taken out of what the
+    /// destination receives.
+    pub fee: u64,
+    /// Origination fee skipped, the account is exempt from the per-action fees.
+    pub fee_waived: bool,
+    /// Added to the lifetime `total_borrowed_native` of the balance, origination fee included.
+    /// Zero for accounts not migrated to hold a credit history.
+    pub total_borrowed_native_delta: u64,
+    pub meta: AccountEventMeta,
 }
 
 #[event]
@@ -113,8 +479,83 @@ pub struct LendingAccountWithdrawEvent {
     pub header: AccountEventHeader,
     pub bank: Pubkey,
     pub mint: Pubkey,
+    /// Debited from the balance, including the withdraw fee.
     pub amount: u64,
     pub close_balance: bool,
+    /// Sent out of the liquidity vault, `amount` less the withdraw fee.
+    pub amount_net: u64,
+    /// Amount passed to the instruction, the whole balance when closing it.
+    pub amount_requested: u64,
+    /// Same as `amount`, `amount_requested` plus `fee`.
+    pub amount_pre_fee: u64,
+    /// Token-2022 transfer fee the requested amount was grossed up by. Zero when closing the
+    /// balance or with a `min_amount_out`, the transfer fee is then taken out of what the
+    /// destination receives.
+    pub fee: u64,
+    /// Withdraw fee skipped, the account is exempt from the per-action fees.
+    pub fee_waived: bool,
+    pub meta: AccountEventMeta,
+}
+
+/// Account health after a borrow or withdrawal, only emitted while the group has
+/// `HEALTH_SNAPSHOT_EVENTS_FLAG` set. Values are weighted USD values.
+#[event]
+pub struct AccountHealthSnapshotEvent {
+    pub header: AccountEventHeader,
+    pub init_assets: f64,
+    pub init_liabilities: f64,
+    pub init_equity: f64,
+    pub maint_assets: f64,
+    pub maint_liabilities: f64,
+    pub maint_equity: f64,
+    pub active_balances: u8,
+    pub meta: AccountEventMeta,
+}
+
+/// Account flagged with `BAD_DEBT_CANDIDATE_FLAG`, or cleared of it, values are USD values.
+#[event]
+pub struct LendingAccountFlagUnprofitableEvent {
+    pub header: AccountEventHeader,
+    pub maint_health: f64,
+    pub collateral_value: f64,
+    /// False if the flag was cleared, the account is no longer a bad debt candidate.
+    pub flagged: bool,
+    pub meta: AccountEventMeta,
+}
+
+/// Legacy account brought to the current layout `version`.
+#[event]
+pub struct LendingAccountMigrateEvent {
+    pub header: AccountEventHeader,
+    pub version: u8,
+    pub meta: AccountEventMeta,
+}
+
+#[event]
+pub struct LendingAccountTransferPositionEvent {
+    /// Header of the source account.
+    pub header: AccountEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub destination_astrolend_account: Pubkey,
+    pub asset_shares: f64,
+    /// Native amount of the shares at the current share value.
+    pub amount: f64,
+    pub meta: AccountEventMeta,
+}
+
+#[event]
+pub struct LendingAccountReceiptEvent {
+    /// Header of the depositing account when minting, of the credited account when redeeming.
+    pub header: AccountEventHeader,
+    pub bank: Pubkey,
+    pub receipt_mint: Pubkey,
+    /// Receipt tokens minted or burned, one per asset share.
+    pub receipts: u64,
+    /// Native amount of the shares at the current share value.
+    pub amount: f64,
+    pub redeem: bool,
+    pub meta: AccountEventMeta,
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
@@ -125,6 +566,14 @@ pub struct LiquidationBalances {
     pub liquidator_liability_balance: f64,
 }
 
+/// Oracle price a liquidation was valued at.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct LiquidationPrice {
+    /// Exact I80F48 price used, biased low for the asset and high for the liability.
+    pub price: WrappedI80F48,
+    pub price_type: OraclePriceType,
+}
+
 #[event]
 pub struct LendingAccountLiquidateEvent {
     pub header: AccountEventHeader,
@@ -134,10 +583,51 @@ pub struct LendingAccountLiquidateEvent {
     pub asset_mint: Pubkey,
     pub liability_bank: Pubkey,
     pub liability_mint: Pubkey,
+    /// Maintenance health of the liquidatee.
     pub liquidatee_pre_health: f64,
     pub liquidatee_post_health: f64,
     pub pre_balances: LiquidationBalances,
     pub post_balances: LiquidationBalances,
+    /// Index of the record written to the liquidatee's liquidation history, `None` for legacy
+    /// accounts which keep no history.
+    pub liquidation_record_index: Option<u8>,
+    /// Split of the liquidation discount, in USD at the liquidation prices.
+    pub liquidator_bonus: f64,
+    pub insurance_fee: f64,
+    pub protocol_fee: f64,
+    pub asset_price: LiquidationPrice,
+    pub liability_price: LiquidationPrice,
+    /// Native amount of the liability taken as insurance fee.
+    pub insurance_fee_amount: f64,
+    pub meta: AccountEventMeta,
+}
+
+#[event]
+pub struct LendingAccountSelfLiquidateEvent {
+    pub header: AccountEventHeader,
+    pub asset_bank: Pubkey,
+    pub asset_mint: Pubkey,
+    pub liability_bank: Pubkey,
+    pub liability_mint: Pubkey,
+    pub pre_health: f64,
+    pub post_health: f64,
+    /// Native amount of collateral sent to the authority.
+    pub asset_amount: u64,
+    /// Native amount of liability paid by the authority.
+    pub liability_amount_paid: u64,
+    /// Native amount of liability removed from the account.
+    pub liability_amount_repaid: f64,
+    pub liquidation_record_index: Option<u8>,
+    /// Penalty in USD at the liquidation prices.
+    pub insurance_fee: f64,
+    pub meta: AccountEventMeta,
+}
+
+#[event]
+pub struct AstrolendAccountConfigureEvent {
+    pub header: AccountEventHeader,
+    pub config: AccountConfigOpt,
+    pub meta: AccountEventMeta,
 }
 
 #[event]
@@ -145,4 +635,5 @@ pub struct AstrolendAccountTransferAccountAuthorityEvent {
     pub header: AccountEventHeader,
     pub old_account_authority: Pubkey,
     pub new_account_authority: Pubkey,
+    pub meta: AccountEventMeta,
 }
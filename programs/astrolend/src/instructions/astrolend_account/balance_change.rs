@@ -0,0 +1,806 @@
+use crate::{
+    bank_signer, check,
+    events::{AccountEventHeader, AccountEventMeta, AccountHealthSnapshotEvent},
+    math_error,
+    prelude::{AstrolendError, AstrolendGroup, AstrolendResult},
+    state::{
+        astrolend_account::{
+            calc_value, check_operation_allowed, load_mut_with_credit_history,
+            AccountHealthSnapshot, AstrolendAccount, BalanceSide, BankAccountWrapper,
+            BankOperation, HealthCheckContext, RequirementType, RiskEngine, DISABLED_FLAG,
+            FEE_EXEMPT_FLAG, IN_FLASHLOAN_FLAG, THIRD_PARTY_DEPOSIT_ALLOWED_FLAG,
+        },
+        astrolend_group::{Bank, BankVaultType},
+        price::{PriceAdapter, PriceBias},
+    },
+    utils,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::get_associated_token_address_with_program_id,
+    token_interface::{mint_to, Mint, MintTo, TokenAccount, TokenInterface},
+};
+use fixed::types::I80F48;
+use solana_program::{clock::Clock, sysvar::Sysvar};
+
+/// Whether a balance change grows or shrinks the side it applies to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BalanceDirection {
+    Increase,
+    Decrease,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AmountMode {
+    Exact(u64),
+    /// Close the balance, only valid when decreasing it.
+    All,
+}
+
+/// A user initiated balance change against a single bank:
+///
+/// | side        | direction | instruction |
+/// |-------------|-----------|-------------|
+/// | Assets      | Increase  | deposit     |
+/// | Assets      | Decrease  | withdraw    |
+/// | Liabilities | Increase  | borrow      |
+/// | Liabilities | Decrease  | repay       |
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BalanceChange {
+    pub side: BalanceSide,
+    pub direction: BalanceDirection,
+    pub amount_mode: AmountMode,
+}
+
+impl BalanceChange {
+    pub fn deposit(amount: u64) -> Self {
+        Self {
+            side: BalanceSide::Assets,
+            direction: BalanceDirection::Increase,
+            amount_mode: AmountMode::Exact(amount),
+        }
+    }
+
+    pub fn withdraw(amount: u64, withdraw_all: bool) -> Self {
+        Self {
+            side: BalanceSide::Assets,
+            direction: BalanceDirection::Decrease,
+            amount_mode: AmountMode::new(amount, withdraw_all),
+        }
+    }
+
+    pub fn borrow(amount: u64) -> Self {
+        Self {
+            side: BalanceSide::Liabilities,
+            direction: BalanceDirection::Increase,
+            amount_mode: AmountMode::Exact(amount),
+        }
+    }
+
+    pub fn repay(amount: u64, repay_all: bool) -> Self {
+        Self {
+            side: BalanceSide::Liabilities,
+            direction: BalanceDirection::Decrease,
+            amount_mode: AmountMode::new(amount, repay_all),
+        }
+    }
+
+    pub fn operation(&self) -> BankOperation {
+        match (self.side, self.direction) {
+            (BalanceSide::Assets, BalanceDirection::Increase) => BankOperation::Deposit,
+            (BalanceSide::Assets, BalanceDirection::Decrease) => BankOperation::Withdraw,
+            (BalanceSide::Liabilities, BalanceDirection::Increase) => BankOperation::Borrow,
+            (BalanceSide::Liabilities, BalanceDirection::Decrease) => BankOperation::Repay,
+        }
+    }
+
+    /// Tokens flow from the user into the liquidity vault.
+    pub fn is_inflow(&self) -> bool {
+        matches!(
+            (self.side, self.direction),
+            (BalanceSide::Assets, BalanceDirection::Increase)
+                | (BalanceSide::Liabilities, BalanceDirection::Decrease)
+        )
+    }
+
+    /// Outflows lower the account health and must pass the initial health check.
+    pub fn requires_health_check(&self) -> bool {
+        !self.is_inflow()
+    }
+
+    fn exact_amount(&self) -> AstrolendResult<u64> {
+        match self.amount_mode {
+            AmountMode::Exact(amount) => Ok(amount),
+            AmountMode::All => err!(AstrolendError::IllegalBalanceState),
+        }
+    }
+}
+
+impl AmountMode {
+    pub fn new(amount: u64, all: bool) -> Self {
+        if all {
+            AmountMode::All
+        } else {
+            AmountMode::Exact(amount)
+        }
+    }
+
+    pub fn is_all(&self) -> bool {
+        matches!(self, AmountMode::All)
+    }
+}
+
+pub struct BalanceChangeAccounts<'a, 'info> {
+    pub astrolend_group: &'a AccountLoader<'info, AstrolendGroup>,
+    pub astrolend_account: &'a AccountLoader<'info, AstrolendAccount>,
+    pub signer: &'a Signer<'info>,
+    pub bank: &'a AccountLoader<'info, Bank>,
+    /// Source of inflows, destination of outflows
+    pub user_token_account: AccountInfo<'info>,
+    pub bank_liquidity_vault: AccountInfo<'info>,
+    /// Only needed for outflows
+    pub bank_liquidity_vault_authority: Option<AccountInfo<'info>>,
+    pub token_program: &'a Interface<'info, TokenInterface>,
+    /// Only needed for increases in groups requiring a whitelist
+    pub whitelist_entry: Option<&'a AccountInfo<'info>>,
+}
+
+#[derive(Default)]
+pub struct BalanceChangeOptions {
+    /// Deposit signed and funded by a third party
+    pub on_behalf_of: bool,
+    /// Borrow paying part of the origination fee to the referrer token account
+    pub with_referrer: bool,
+    pub min_amount_out: Option<u64>,
+    /// Deposit exporting its asset shares as receipt tokens instead of crediting the account
+    pub mint_receipts: bool,
+}
+
+/// What the instruction events report about an executed [BalanceChange].
+pub struct BalanceChangeOutcome {
+    pub mint: Pubkey,
+    /// Amount the instruction was called with, before the Token-2022 transfer fee is added to
+    /// outflows. The whole balance for closing withdrawals. Nothing is grossed up for them, nor
+    /// for outflows with a `min_amount_out`.
+    pub amount_requested: u64,
+    /// Change of the balance, before the origination fee and the withdraw fee. Outflows
+    /// include the Token-2022 transfer fee.
+    pub amount: u64,
+    /// `amount` net of the withdraw fee, equal to `amount` for every other operation
+    pub amount_net: u64,
+    pub origination_fee: I80F48,
+    /// Origination or withdraw fee skipped, the account has `FEE_EXEMPT_FLAG`
+    pub fee_waived: bool,
+    pub referrer: Option<Pubkey>,
+    pub referral_fee: u64,
+    /// Receipt mint and receipt tokens minted by deposits with `mint_receipts`
+    pub receipts: Option<(Pubkey, u64)>,
+    /// Added to the lifetime borrowed or repaid counter of the balance, see
+    /// [crate::state::astrolend_account::CreditHistory::record_borrow]. Zero for deposits and
+    /// withdraws, and on accounts without a credit history.
+    pub credit_delta: u64,
+    pub health_snapshot: Option<AccountHealthSnapshot>,
+}
+
+/// Shared core of the deposit, withdraw, borrow and repay instructions, handlers only adapt
+/// their accounts and arguments and emit their event from the outcome.
+///
+/// 1. Check the operation is allowed on the bank and account, the whitelist for increases
+///    and the signer for inflows
+/// 2. Accrue interest
+/// 3. Apply the change to the user's bank account, creating it for increases, along with
+///    the minimum amounts, fees and caps of the operation
+/// 4. Transfer funds between the user token account and the bank's liquidity vault
+/// 5. Mint receipts for the deposited shares, if requested
+/// 6. Verify that the user account is in a healthy state after outflows
+///
+/// The bank's reentrancy guard is set from the checks to the end of the transfers, so
+/// instructions reentered from a transfer hook can't observe the bank mid-change.
+///
+/// Deposits minting receipts move the deposited shares out of the account right away, the
+/// receipts are the only claim on them and they never count towards the account health. They
+/// are minted under the same destination restrictions as a withdraw, see
+/// `RESTRICTED_DESTINATIONS_FLAG`.
+/// One receipt per whole share, the fraction of a share left over stays with the bank.
+///
+/// Expected remaining account schema
+/// [
+///    bank_mint_ai (if token2022 mint),
+///    referrer_token_account_ai (if with_referrer),
+///    receipt_mint_ai, receipt_token_account_ai, liquidity_vault_authority_ai (if mint_receipts),
+///    observation_ais...,
+/// ]
+///
+/// Deposits into a bank the account has no balance in also expect the banks of its
+/// liabilities among the observations, to check their risk tier.
+///
+/// `instruction` is the discriminator of the calling instruction, stamped on the accrual event.
+pub fn execute_balance_change<'info>(
+    change: BalanceChange,
+    accounts: BalanceChangeAccounts<'_, 'info>,
+    options: BalanceChangeOptions,
+    #[cfg_attr(feature = "client", allow(unused_variables))] instruction: [u8; 8],
+    mut remaining_ais: &'info [AccountInfo<'info>],
+) -> AstrolendResult<BalanceChangeOutcome> {
+    let BalanceChangeAccounts {
+        astrolend_group: astrolend_group_loader,
+        astrolend_account: astrolend_account_loader,
+        signer,
+        bank: bank_loader,
+        user_token_account,
+        bank_liquidity_vault,
+        bank_liquidity_vault_authority,
+        token_program,
+        whitelist_entry,
+    } = accounts;
+    let clock = Clock::get()?;
+    let op = change.operation();
+    let bank_pk = bank_loader.key();
+
+    let maybe_bank_mint = utils::maybe_take_bank_mint(
+        &mut remaining_ais,
+        &*bank_loader.load()?,
+        token_program.key,
+        bank_liquidity_vault.owner,
+    )?;
+    let maybe_referrer_token_account = if options.with_referrer {
+        Some(utils::take_referrer_token_account(
+            &mut remaining_ais,
+            &*bank_loader.load()?,
+        )?)
+    } else {
+        None
+    };
+    let maybe_receipt_accounts = if options.mint_receipts {
+        check!(
+            op == BankOperation::Deposit,
+            AstrolendError::InvalidReceiptAccounts
+        );
+
+        Some(utils::take_receipt_accounts(
+            &mut remaining_ais,
+            &bank_pk,
+            &*bank_loader.load()?,
+        )?)
+    } else {
+        None
+    };
+
+    let (mut astrolend_account, mut credit_history) =
+        load_mut_with_credit_history(astrolend_account_loader)?;
+
+    // Receipts are the only claim on the deposited shares, they leave the account the same as
+    // withdrawn tokens would
+    if let Some(receipt_accounts) = &maybe_receipt_accounts {
+        astrolend_account.check_destination_allowed(
+            receipt_accounts.receipt_token_account.key,
+            &get_associated_token_address_with_program_id(
+                &astrolend_account.authority,
+                receipt_accounts.receipt_mint.key,
+                token_program.key,
+            ),
+        )?;
+    }
+
+    if let Some(referrer_token_account) = &maybe_referrer_token_account {
+        check!(
+            referrer_token_account.owner != astrolend_account.authority
+                && referrer_token_account.key() != user_token_account.key(),
+            AstrolendError::SelfReferral
+        );
+    }
+
+    check_operation_allowed(
+        op,
+        &*bank_loader.load()?,
+        &astrolend_account,
+    )?;
+    bank_loader.load_mut()?.enter_reentrancy_guard(clock.slot)?;
+
+    if change.direction == BalanceDirection::Increase {
+        astrolend_group_loader.load()?.check_wallet_whitelisted(
+            &astrolend_group_loader.key(),
+            &astrolend_account.authority,
+            whitelist_entry,
+        )?;
+    }
+
+    // Withdraws and borrows are restricted to the authority by their accounts constraints
+    match op {
+        BankOperation::Deposit if options.on_behalf_of => {
+            check!(
+                astrolend_account.get_flag(THIRD_PARTY_DEPOSIT_ALLOWED_FLAG),
+                AstrolendError::ThirdPartyDepositNotAllowed
+            );
+        }
+        BankOperation::Deposit => {
+            check!(
+                signer.key() == astrolend_account.authority,
+                AstrolendError::Unauthorized
+            );
+        }
+        // Third parties may still repay the debt of a disabled account
+        BankOperation::Repay => {
+            check!(
+                signer.key() != astrolend_account.authority
+                    || !astrolend_account.get_flag(DISABLED_FLAG),
+                AstrolendError::AccountDisabled
+            );
+        }
+        _ => {}
+    }
+
+    if op == BankOperation::Deposit {
+        astrolend_account
+            .lending_account
+            .check_deposit_allowed(&bank_pk, remaining_ais)?;
+    }
+
+    if op == BankOperation::Withdraw {
+        bank_loader.load()?.check_bankruptcy_freeze(clock.slot)?;
+    }
+
+    bank_loader.load_mut()?.accrue_interest(
+        clock.unix_timestamp,
+        &*astrolend_group_loader.load()?,
+        #[cfg(not(feature = "client"))]
+        bank_pk,
+        #[cfg(not(feature = "client"))]
+        instruction,
+    )?;
+
+    let mut bank = bank_loader.load_mut()?;
+    let has_balance = astrolend_account.lending_account.has_balance(&bank_pk);
+    let fee_waived = astrolend_account.get_flag(FEE_EXEMPT_FLAG)
+        && matches!(op, BankOperation::Borrow | BankOperation::Withdraw);
+
+    let mut origination_fee = I80F48::ZERO;
+    let mut referral_fee = 0;
+    let mut receipts = 0;
+    let (amount_requested, amount, amount_net) = match (change.side, change.direction) {
+        (BalanceSide::Assets, BalanceDirection::Increase) => {
+            let amount = change.exact_amount()?;
+            if !has_balance {
+                bank.config.check_min_deposit_amount(amount)?;
+            }
+
+            let mut bank_account = BankAccountWrapper::find_or_create(
+                &bank_pk,
+                &mut bank,
+                &mut astrolend_account.lending_account,
+            )?;
+            bank_account.deposit(I80F48::from_num(amount))?;
+
+            if astrolend_group_loader.load()?.is_tvl_cap_enabled() {
+                let deposit_value = utils::calc_tvl_value(
+                    &bank_pk,
+                    bank_account.bank,
+                    amount,
+                    remaining_ais,
+                    &clock,
+                )?;
+
+                astrolend_group_loader
+                    .load_mut()?
+                    .increase_tvl(deposit_value)?;
+            }
+
+            if maybe_receipt_accounts.is_some() {
+                let shares = bank_account
+                    .bank
+                    .get_asset_shares(I80F48::from_num(amount))?;
+                receipts = bank_account
+                    .transfer_asset_shares_out(shares)?
+                    .checked_floor()
+                    .and_then(|shares| shares.checked_to_num())
+                    .ok_or_else(math_error!())?;
+            }
+
+            (amount, amount, amount)
+        }
+        (BalanceSide::Liabilities, BalanceDirection::Decrease) => {
+            let mut bank_account = BankAccountWrapper::find(
+                &bank_pk,
+                &mut bank,
+                &mut astrolend_account.lending_account,
+            )?;
+
+            let amount = match change.amount_mode {
+                AmountMode::All => bank_account.repay_all()?,
+                AmountMode::Exact(amount) => {
+                    bank_account.repay(I80F48::from_num(amount))?;
+
+                    amount
+                }
+            };
+
+            (amount, amount, amount)
+        }
+        (BalanceSide::Assets, BalanceDirection::Decrease) => {
+            let mut bank_account = BankAccountWrapper::find(
+                &bank_pk,
+                &mut bank,
+                &mut astrolend_account.lending_account,
+            )?;
+
+            let (amount_requested, amount_pre_fee) = match change.amount_mode {
+                AmountMode::All => {
+                    let amount = bank_account.withdraw_all()?;
+
+                    (amount, amount)
+                }
+                AmountMode::Exact(amount) => {
+                    let amount_pre_fee = calc_amount_debited(
+                        maybe_bank_mint.as_ref(),
+                        amount,
+                        clock.epoch,
+                        options.min_amount_out,
+                    )?;
+                    bank_account.withdraw(I80F48::from_num(amount_pre_fee))?;
+
+                    (amount, amount_pre_fee)
+                }
+            };
+
+            let withdraw_fee = if fee_waived {
+                0
+            } else {
+                bank_account.bank.collect_withdraw_fee(amount_pre_fee)?
+            };
+            let amount_out = amount_pre_fee - withdraw_fee;
+
+            utils::check_min_amount_out(
+                maybe_bank_mint.as_ref(),
+                amount_out,
+                clock.epoch,
+                options.min_amount_out,
+            )?;
+
+            (amount_requested, amount_pre_fee, amount_out)
+        }
+        (BalanceSide::Liabilities, BalanceDirection::Increase) => {
+            let amount = change.exact_amount()?;
+            if !astrolend_account.get_flag(IN_FLASHLOAN_FLAG) {
+                let borrow_value =
+                    calc_borrow_value(&bank_pk, &mut bank, amount, remaining_ais, &clock)?;
+
+                bank.record_slot_borrow_value(
+                    &*astrolend_group_loader.load()?,
+                    borrow_value,
+                    clock.slot,
+                )?;
+            }
+
+            if !has_balance {
+                bank.config.check_min_borrow_amount(amount)?;
+            }
+
+            let amount_pre_fee = calc_amount_debited(
+                maybe_bank_mint.as_ref(),
+                amount,
+                clock.epoch,
+                options.min_amount_out,
+            )?;
+
+            utils::check_min_amount_out(
+                maybe_bank_mint.as_ref(),
+                amount_pre_fee,
+                clock.epoch,
+                options.min_amount_out,
+            )?;
+
+            if !fee_waived {
+                origination_fee = bank.calc_origination_fee(I80F48::from_num(amount_pre_fee))?;
+            }
+            let group_fee = if maybe_referrer_token_account.is_some() {
+                let (referrer_fee, group_fee) = bank.split_origination_fee(origination_fee)?;
+                referral_fee = referrer_fee;
+
+                group_fee
+            } else {
+                origination_fee
+            };
+
+            bank.add_group_fees_outstanding(group_fee)?;
+
+            let mut bank_account = BankAccountWrapper::find_or_create(
+                &bank_pk,
+                &mut bank,
+                &mut astrolend_account.lending_account,
+            )?;
+
+            bank_account.borrow(
+                I80F48::from_num(amount_pre_fee)
+                    .checked_add(origination_fee)
+                    .ok_or_else(math_error!())?,
+            )?;
+
+            (amount, amount_pre_fee, amount_pre_fee)
+        }
+    };
+
+    // Balances closed by the change, or by any instruction before, are rolled up on the way
+    let credit_delta = match (op, credit_history.as_deref_mut()) {
+        (BankOperation::Borrow, Some(credit_history)) => credit_history.record_borrow(
+            &astrolend_account.lending_account,
+            &bank_pk,
+            I80F48::from_num(amount)
+                .checked_add(origination_fee)
+                .and_then(|borrowed| borrowed.checked_to_num())
+                .ok_or_else(math_error!())?,
+        ),
+        (BankOperation::Repay, Some(credit_history)) => {
+            credit_history.record_repay(&astrolend_account.lending_account, &bank_pk, amount)
+        }
+        _ => 0,
+    };
+    if let Some(credit_history) = credit_history.as_deref_mut() {
+        credit_history.sync(&astrolend_account.lending_account);
+    }
+
+    // Native limits were checked as the shares were added, USD limits need the bank price
+    match op {
+        BankOperation::Deposit if bank.config.is_usd_deposit_limit_active() => {
+            let price =
+                load_bank_price(&bank_pk, &mut bank, remaining_ais, &clock, PriceBias::Low)?;
+            bank.check_usd_deposit_limit(price)?;
+        }
+        BankOperation::Borrow if bank.config.is_usd_borrow_limit_active() => {
+            let price =
+                load_bank_price(&bank_pk, &mut bank, remaining_ais, &clock, PriceBias::High)?;
+            bank.check_usd_borrow_limit(price)?;
+        }
+        _ => {}
+    }
+
+    if change.is_inflow() {
+        bank.deposit_spl_transfer(
+            calc_amount_pre_fee(maybe_bank_mint.as_ref(), amount, clock.epoch)?,
+            user_token_account,
+            bank_liquidity_vault,
+            signer.to_account_info(),
+            maybe_bank_mint.as_ref(),
+            token_program.to_account_info(),
+            remaining_ais,
+        )?;
+    } else {
+        let bank_liquidity_vault_authority =
+            bank_liquidity_vault_authority.ok_or(AstrolendError::InvalidTransfer)?;
+        let liquidity_vault_authority_bump = bank.liquidity_vault_authority_bump;
+
+        bank.withdraw_spl_transfer(
+            amount_net,
+            bank_liquidity_vault.clone(),
+            user_token_account,
+            bank_liquidity_vault_authority.clone(),
+            maybe_bank_mint.as_ref(),
+            token_program.to_account_info(),
+            bank_signer!(
+                BankVaultType::Liquidity,
+                bank_pk,
+                liquidity_vault_authority_bump
+            ),
+            remaining_ais,
+        )?;
+
+        if let Some(referrer_token_account) = &maybe_referrer_token_account {
+            if referral_fee > 0 {
+                bank.withdraw_spl_transfer(
+                    referral_fee,
+                    bank_liquidity_vault,
+                    referrer_token_account.to_account_info(),
+                    bank_liquidity_vault_authority,
+                    maybe_bank_mint.as_ref(),
+                    token_program.to_account_info(),
+                    bank_signer!(
+                        BankVaultType::Liquidity,
+                        bank_pk,
+                        liquidity_vault_authority_bump
+                    ),
+                    remaining_ais,
+                )?;
+            }
+        }
+
+        if op == BankOperation::Withdraw && astrolend_group_loader.load()?.is_tvl_cap_enabled() {
+            let withdraw_value =
+                utils::calc_tvl_value(&bank_pk, &mut bank, amount_net, remaining_ais, &clock)?;
+
+            astrolend_group_loader
+                .load_mut()?
+                .decrease_tvl(withdraw_value)?;
+        }
+    }
+
+    if let Some(receipt_accounts) = &maybe_receipt_accounts {
+        mint_to(
+            CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                MintTo {
+                    mint: receipt_accounts.receipt_mint.clone(),
+                    to: receipt_accounts.receipt_token_account.clone(),
+                    authority: receipt_accounts.liquidity_vault_authority.clone(),
+                },
+                bank_signer!(
+                    BankVaultType::Liquidity,
+                    bank_pk,
+                    bank.liquidity_vault_authority_bump
+                ),
+            ),
+            receipts,
+        )?;
+    }
+
+    let mint = bank.mint;
+    bank.exit_reentrancy_guard();
+    drop(bank);
+
+    // Check account health, if below threshold fail transaction
+    // Assuming `remaining_ais` holds only oracle accounts
+    let health_snapshot = if change.requires_health_check() {
+        RiskEngine::check_post_action(
+            &*astrolend_group_loader.load()?,
+            &astrolend_account,
+            remaining_ais,
+            HealthCheckContext::of_account(&astrolend_account),
+        )?
+    } else {
+        None
+    };
+
+    Ok(BalanceChangeOutcome {
+        mint,
+        amount_requested,
+        amount,
+        amount_net,
+        origination_fee,
+        fee_waived,
+        referrer: maybe_referrer_token_account
+            .as_ref()
+            .map(|referrer_token_account| referrer_token_account.key()),
+        referral_fee,
+        receipts: maybe_receipt_accounts
+            .as_ref()
+            .map(|receipt_accounts| (receipt_accounts.receipt_mint.key(), receipts)),
+        credit_delta,
+        health_snapshot,
+    })
+}
+
+/// Check the destination of a withdraw or borrow: any token account of the bank mint, narrowed
+/// down by the account's `RESTRICTED_DESTINATIONS_FLAG`, see
+/// [AstrolendAccount::check_destination_allowed].
+pub fn check_outflow_destination(
+    astrolend_account: &AstrolendAccount,
+    bank: &Bank,
+    destination_token_account: &InterfaceAccount<TokenAccount>,
+    token_program: &Pubkey,
+) -> AstrolendResult {
+    check!(
+        destination_token_account.mint == bank.mint,
+        AstrolendError::DestinationNotAllowed
+    );
+
+    astrolend_account.check_destination_allowed(
+        &destination_token_account.key(),
+        &get_associated_token_address_with_program_id(
+            &astrolend_account.authority,
+            &bank.mint,
+            token_program,
+        ),
+    )
+}
+
+pub fn health_snapshot_event(
+    header: AccountEventHeader,
+    health: AccountHealthSnapshot,
+    meta: AccountEventMeta,
+) -> AccountHealthSnapshotEvent {
+    AccountHealthSnapshotEvent {
+        header,
+        init_assets: health.init_assets.to_num(),
+        init_liabilities: health.init_liabilities.to_num(),
+        init_equity: health.init_equity.to_num(),
+        maint_assets: health.maint_assets.to_num(),
+        maint_liabilities: health.maint_liabilities.to_num(),
+        maint_equity: health.maint_equity.to_num(),
+        active_balances: health.active_balances,
+        meta,
+    }
+}
+
+/// Amount to debit for an outflow of `amount`. Grossed up so `amount` arrives after the Token-2022
+/// transfer fee of the current epoch, unless the caller set a `min_amount_out`: `amount` is then
+/// debited as is and the fee taken out of what arrives, so a fee raised after the caller quoted
+/// it is caught by the minimum instead of silently growing the debit.
+fn calc_amount_debited(
+    maybe_bank_mint: Option<&InterfaceAccount<Mint>>,
+    amount: u64,
+    epoch: u64,
+    min_amount_out: Option<u64>,
+) -> AstrolendResult<u64> {
+    if min_amount_out.is_some() {
+        return Ok(amount);
+    }
+
+    calc_amount_pre_fee(maybe_bank_mint, amount, epoch)
+}
+
+/// Amount to transfer so `amount` arrives after the Token-2022 transfer fee of the current epoch.
+fn calc_amount_pre_fee(
+    maybe_bank_mint: Option<&InterfaceAccount<Mint>>,
+    amount: u64,
+    epoch: u64,
+) -> AstrolendResult<u64> {
+    Ok(maybe_bank_mint
+        .map(|mint| {
+            utils::calculate_pre_fee_spl_deposit_amount(mint.to_account_info(), amount, epoch)
+        })
+        .transpose()?
+        .unwrap_or(amount))
+}
+
+/// Bank price for the initial health check, `PriceBias::Low` for assets and `PriceBias::High`
+/// for liabilities.
+fn load_bank_price<'info>(
+    bank_pk: &Pubkey,
+    bank: &mut Bank,
+    remaining_ais: &'info [AccountInfo<'info>],
+    clock: &Clock,
+    bias: PriceBias,
+) -> AstrolendResult<I80F48> {
+    let oracle_ais = utils::find_oracle_ais(bank_pk, remaining_ais)?;
+
+    let price_feed = bank.load_price_feed(oracle_ais, clock)?;
+    price_feed.get_price_of_type(RequirementType::Initial.get_oracle_price_type(), Some(bias))
+}
+
+/// USD value of `amount` of the bank mint, priced the same way as liabilities in the initial health check.
+fn calc_borrow_value<'info>(
+    bank_pk: &Pubkey,
+    bank: &mut Bank,
+    amount: u64,
+    remaining_ais: &'info [AccountInfo<'info>],
+    clock: &Clock,
+) -> AstrolendResult<I80F48> {
+    let price = load_bank_price(bank_pk, bank, remaining_ais, clock, PriceBias::High)?;
+
+    calc_value(I80F48::from_num(amount), price, bank.mint_decimals, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balance_change_operations() {
+        let cases = [
+            (BalanceChange::deposit(1), BankOperation::Deposit, true),
+            (BalanceChange::withdraw(1, false), BankOperation::Withdraw, false),
+            (BalanceChange::borrow(1), BankOperation::Borrow, false),
+            (BalanceChange::repay(1, false), BankOperation::Repay, true),
+        ];
+
+        for (change, op, is_inflow) in cases {
+            assert_eq!(change.operation(), op);
+            assert_eq!(change.is_inflow(), is_inflow);
+            assert_eq!(change.requires_health_check(), !is_inflow);
+        }
+    }
+
+    #[test]
+    fn close_balance_only_when_decreasing() {
+        assert_eq!(
+            BalanceChange::withdraw(1, true).amount_mode,
+            AmountMode::All
+        );
+        assert_eq!(BalanceChange::repay(1, true).amount_mode, AmountMode::All);
+
+        let change = BalanceChange {
+            amount_mode: AmountMode::All,
+            ..BalanceChange::deposit(0)
+        };
+        assert!(change.exact_amount().is_err());
+        assert_eq!(BalanceChange::borrow(7).exact_amount().unwrap(), 7);
+    }
+}
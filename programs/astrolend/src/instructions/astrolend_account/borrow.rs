@@ -1,116 +1,129 @@
+use super::balance_change::{
+    check_outflow_destination, execute_balance_change, health_snapshot_event, BalanceChange,
+    BalanceChangeAccounts, BalanceChangeOptions,
+};
 use crate::{
-    bank_signer, check,
-    constants::{LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED},
+    account_event_meta,
+    constants::{LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED, WHITELIST_SEED},
     events::{AccountEventHeader, LendingAccountBorrowEvent},
-    prelude::{AstrolendError, AstrolendGroup, AstrolendResult},
-    state::{
-        astrolend_account::{BankAccountWrapper, AstrolendAccount, RiskEngine, DISABLED_FLAG},
-        astrolend_group::{Bank, BankVaultType},
-    },
-    utils,
+    instruction_discriminator,
+    prelude::{AstrolendGroup, AstrolendResult},
+    state::{astrolend_account::AstrolendAccount, astrolend_group::Bank},
 };
 use anchor_lang::prelude::*;
 use anchor_spl::token_interface::{TokenAccount, TokenInterface};
-use fixed::types::I80F48;
-use solana_program::{clock::Clock, sysvar::Sysvar};
 
 /// 1. Accrue interest
-/// 2. Create the user's bank account for the asset borrowed if it does not exist yet
+/// 2. Create the user's bank account for the asset borrowed if it does not exist yet,
+///    the borrow must be at least the bank `min_borrow_amount`
 /// 3. Record liability increase in the bank account
 /// 4. Transfer funds from the bank's liquidity vault to the signer's token account
 /// 5. Verify that the user account is in a healthy state
 ///
 /// Will error if there is an existing asset <=> withdrawing is not allowed.
+/// Will error if the value borrowed from the bank in the slot, this borrow included, exceeds the
+/// group `max_borrow_value_per_slot_usd`. Borrows in a flashloan don't count.
+/// Will error if the group requires a whitelist and the account authority isn't whitelisted.
+/// Will error if the account restricts destinations and the destination is neither the
+/// authority's associated token account nor the approved destination.
+/// Will error if the bank liabilities reach the borrow limit, valued at the bank oracle price
+/// for banks with USD denominated limits.
+///
+/// The bank origination fee is added to the liability. With `with_referrer`,
+/// `referral_fee_share_pct` of the fee is paid to the referrer token account,
+/// the rest is collected as group fees.
+///
+/// With `min_amount_out`, `amount` is not grossed up for the Token-2022 transfer fee, the
+/// destination receives it net of the transfer fee of the current epoch, and the borrow will
+/// error if that is less than `min_amount_out`.
+///
+/// Expected remaining account schema
+/// [
+///    bank_mint_ai (if token2022 mint),
+///    referrer_token_account_ai (if with_referrer),
+///    observation_ais...,
+/// ]
 pub fn lending_account_borrow<'info>(
-    mut ctx: Context<'_, '_, 'info, 'info, LendingAccountBorrow<'info>>,
+    ctx: Context<'_, '_, 'info, 'info, LendingAccountBorrow<'info>>,
     amount: u64,
+    with_referrer: Option<bool>,
+    min_amount_out: Option<u64>,
 ) -> AstrolendResult {
     let LendingAccountBorrow {
+        astrolend_group,
         astrolend_account: astrolend_account_loader,
+        signer,
+        bank,
         destination_token_account,
+        bank_liquidity_vault_authority,
         bank_liquidity_vault,
         token_program,
-        bank_liquidity_vault_authority,
-        bank: bank_loader,
-        ..
+        whitelist_entry,
     } = ctx.accounts;
-    let clock = Clock::get()?;
-    let maybe_bank_mint = utils::maybe_take_bank_mint(
-        &mut ctx.remaining_accounts,
-        &*bank_loader.load()?,
+
+    check_outflow_destination(
+        &*astrolend_account_loader.load()?,
+        &*bank.load()?,
+        destination_token_account,
         token_program.key,
     )?;
 
-    let mut astrolend_account = astrolend_account_loader.load_mut()?;
-
-    check!(
-        !astrolend_account.get_flag(DISABLED_FLAG),
-        AstrolendError::AccountDisabled
-    );
-
-    bank_loader.load_mut()?.accrue_interest(
-        clock.unix_timestamp,
-        #[cfg(not(feature = "client"))]
-        bank_loader.key(),
+    let outcome = execute_balance_change(
+        BalanceChange::borrow(amount),
+        BalanceChangeAccounts {
+            astrolend_group,
+            astrolend_account: astrolend_account_loader,
+            signer,
+            bank,
+            user_token_account: destination_token_account.to_account_info(),
+            bank_liquidity_vault: bank_liquidity_vault.to_account_info(),
+            bank_liquidity_vault_authority: Some(
+                bank_liquidity_vault_authority.to_account_info(),
+            ),
+            token_program,
+            whitelist_entry: whitelist_entry.as_ref(),
+        },
+        BalanceChangeOptions {
+            with_referrer: with_referrer.unwrap_or(false),
+            min_amount_out,
+            ..Default::default()
+        },
+        instruction_discriminator!(LendingAccountBorrow),
+        ctx.remaining_accounts,
     )?;
 
-    {
-        let mut bank = bank_loader.load_mut()?;
-
-        let liquidity_vault_authority_bump = bank.liquidity_vault_authority_bump;
-
-        let mut bank_account = BankAccountWrapper::find_or_create(
-            &bank_loader.key(),
-            &mut bank,
-            &mut astrolend_account.lending_account,
-        )?;
-
-        // User needs to borrow amount + fee to receive amount
-        let amount_pre_fee = maybe_bank_mint
-            .as_ref()
-            .map(|mint| {
-                utils::calculate_pre_fee_spl_deposit_amount(
-                    mint.to_account_info(),
-                    amount,
-                    clock.epoch,
-                )
-            })
-            .transpose()?
-            .unwrap_or(amount);
-
-        bank_account.borrow(I80F48::from_num(amount_pre_fee))?;
-        bank_account.withdraw_spl_transfer(
-            amount_pre_fee,
-            bank_liquidity_vault.to_account_info(),
-            destination_token_account.to_account_info(),
-            bank_liquidity_vault_authority.to_account_info(),
-            maybe_bank_mint.as_ref(),
-            token_program.to_account_info(),
-            bank_signer!(
-                BankVaultType::Liquidity,
-                bank_loader.key(),
-                liquidity_vault_authority_bump
-            ),
-            ctx.remaining_accounts,
-        )?;
-
-        emit!(LendingAccountBorrowEvent {
-            header: AccountEventHeader {
-                signer: Some(ctx.accounts.signer.key()),
-                astrolend_account: astrolend_account_loader.key(),
-                astrolend_account_authority: astrolend_account.authority,
-                astrolend_group: astrolend_account.group,
-            },
-            bank: bank_loader.key(),
-            mint: bank.mint,
-            amount: amount_pre_fee,
-        });
+    let astrolend_account = astrolend_account_loader.load()?;
+    let header = || AccountEventHeader {
+        signer: Some(signer.key()),
+        astrolend_account: astrolend_account_loader.key(),
+        astrolend_account_authority: astrolend_account.authority,
+        astrolend_group: astrolend_account.group,
+    };
+
+    emit!(LendingAccountBorrowEvent {
+        header: header(),
+        bank: bank.key(),
+        mint: outcome.mint,
+        amount: outcome.amount,
+        origination_fee: outcome.origination_fee.to_num::<f64>(),
+        referrer: outcome.referrer,
+        referral_fee: outcome.referral_fee,
+        amount_requested: outcome.amount_requested,
+        amount_pre_fee: outcome.amount,
+        fee: outcome.amount - outcome.amount_requested,
+        fee_waived: outcome.fee_waived,
+        total_borrowed_native_delta: outcome.credit_delta,
+        meta: account_event_meta!(LendingAccountBorrow, astrolend_account.account_tag),
+    });
+
+    if let Some(health) = outcome.health_snapshot {
+        emit!(health_snapshot_event(
+            header(),
+            health,
+            account_event_meta!(LendingAccountBorrow, astrolend_account.account_tag)
+        ));
     }
 
-    // Check account health, if below threshold fail transaction
-    // Assuming `ctx.remaining_accounts` holds only oracle accounts
-    RiskEngine::check_account_init_health(&astrolend_account, ctx.remaining_accounts)?;
-
     Ok(())
 }
 
@@ -160,4 +173,16 @@ pub struct LendingAccountBorrow<'info> {
     pub bank_liquidity_vault: InterfaceAccount<'info, TokenAccount>,
 
     pub token_program: Interface<'info, TokenInterface>,
+
+    /// CHECK: Seed constraint check, only read when the group requires a whitelist.
+    /// Without the whitelist entry of the account authority the account is reduce only.
+    #[account(
+        seeds = [
+            WHITELIST_SEED.as_bytes(),
+            astrolend_group.key().as_ref(),
+            astrolend_account.load() ?.authority.as_ref(),
+        ],
+        bump,
+    )]
+    pub whitelist_entry: Option<AccountInfo<'info>>,
 }
@@ -4,19 +4,23 @@ use crate::{
     check,
     prelude::*,
     state::{
-        astrolend_account::{BankAccountWrapper, AstrolendAccount, DISABLED_FLAG},
+        astrolend_account::{
+            load_mut_with_credit_history, AstrolendAccount, BankAccountWrapper, DISABLED_FLAG,
+        },
         astrolend_group::Bank,
     },
 };
 
 pub fn lending_account_close_balance(ctx: Context<LendingAccountCloseBalance>) -> AstrolendResult {
     let LendingAccountCloseBalance {
+        astrolend_group,
         astrolend_account,
         bank: bank_loader,
         ..
     } = ctx.accounts;
 
-    let mut astrolend_account = astrolend_account.load_mut()?;
+    let (mut astrolend_account, mut credit_history) =
+        load_mut_with_credit_history(astrolend_account)?;
     let mut bank = bank_loader.load_mut()?;
 
     check!(
@@ -26,8 +30,11 @@ pub fn lending_account_close_balance(ctx: Context<LendingAccountCloseBalance>) -
 
     bank.accrue_interest(
         Clock::get()?.unix_timestamp,
+        &*astrolend_group.load()?,
         #[cfg(not(feature = "client"))]
         bank_loader.key(),
+        #[cfg(not(feature = "client"))]
+        crate::instruction_discriminator!(LendingAccountCloseBalance),
     )?;
 
     let mut bank_account = BankAccountWrapper::find(
@@ -37,6 +44,9 @@ pub fn lending_account_close_balance(ctx: Context<LendingAccountCloseBalance>) -
     )?;
 
     bank_account.close_balance()?;
+    if let Some(credit_history) = credit_history.as_deref_mut() {
+        credit_history.sync(&astrolend_account.lending_account);
+    }
 
     Ok(())
 }
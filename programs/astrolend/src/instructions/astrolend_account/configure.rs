@@ -0,0 +1,41 @@
+use crate::{
+    account_event_meta,
+    events::{AccountEventHeader, AstrolendAccountConfigureEvent},
+    prelude::*,
+    state::astrolend_account::{AccountConfigOpt, AstrolendAccount},
+};
+use anchor_lang::prelude::*;
+
+/// Apply the authority-settable account settings, see `AccountConfigOpt`.
+pub fn lending_account_configure(
+    ctx: Context<LendingAccountConfigure>,
+    config: AccountConfigOpt,
+) -> AstrolendResult {
+    let mut astrolend_account = ctx.accounts.astrolend_account.load_mut()?;
+
+    astrolend_account.configure(&config)?;
+
+    emit!(AstrolendAccountConfigureEvent {
+        header: AccountEventHeader {
+            signer: Some(ctx.accounts.signer.key()),
+            astrolend_account: ctx.accounts.astrolend_account.key(),
+            astrolend_account_authority: astrolend_account.authority,
+            astrolend_group: astrolend_account.group,
+        },
+        config,
+        meta: account_event_meta!(LendingAccountConfigure, astrolend_account.account_tag),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountConfigure<'info> {
+    #[account(mut)]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        address = astrolend_account.load()?.authority,
+    )]
+    pub signer: Signer<'info>,
+}
@@ -1,103 +1,118 @@
+use super::balance_change::{
+    execute_balance_change, BalanceChange, BalanceChangeAccounts, BalanceChangeOptions,
+};
 use crate::{
-    check,
-    constants::LIQUIDITY_VAULT_SEED,
-    events::{AccountEventHeader, LendingAccountDepositEvent},
+    account_event_meta,
+    constants::{LIQUIDITY_VAULT_SEED, WHITELIST_SEED},
+    events::{AccountEventHeader, LendingAccountDepositEvent, LendingAccountReceiptEvent},
+    instruction_discriminator,
     prelude::*,
-    state::{
-        astrolend_account::{BankAccountWrapper, AstrolendAccount, DISABLED_FLAG},
-        astrolend_group::Bank,
-    },
-    utils,
+    state::{astrolend_account::AstrolendAccount, astrolend_group::Bank},
 };
 use anchor_lang::prelude::*;
 use anchor_spl::token_interface::TokenInterface;
 use fixed::types::I80F48;
-use solana_program::clock::Clock;
-use solana_program::sysvar::Sysvar;
 
 /// 1. Accrue interest
-/// 2. Create the user's bank account for the asset deposited if it does not exist yet
+/// 2. Create the user's bank account for the asset deposited if it does not exist yet,
+///    the deposit must be at least the bank `min_deposit_amount`
 /// 3. Record asset increase in the bank account
-/// 4. Transfer funds from the signer's token account to the bank's liquidity vault
+/// 4. Add the deposit value to the group TVL, if the group has a TVL cap
+/// 5. Transfer funds from the signer's token account to the bank's liquidity vault
 ///
 /// Will error if there is an existing liability <=> repaying is not allowed.
+/// Will error if the account has an isolated liability and the bank is not already part of the account.
+/// Will error if the deposit takes the group TVL above `group_tvl_cap_usd`.
+/// Will error if the group requires a whitelist and the account authority isn't whitelisted.
+///
+/// With a group TVL cap the group must be passed as writable and the bank and its oracle
+/// appended to the remaining accounts. Banks with USD denominated limits need the bank and its
+/// oracle appended as well, the deposit limit is checked against the deposits value.
+///
+/// With `on_behalf_of` any wallet can sign and fund the deposit, as long as the account
+/// authority opted in with `THIRD_PARTY_DEPOSIT_ALLOWED_FLAG`.
+///
+/// With `mint_receipts` the deposited shares aren't credited to the account, receipt tokens are
+/// minted for them instead, see `lending_account_redeem_receipts`. Only on banks created with
+/// a receipt mint, the receipt mint, the receipt token account and the liquidity vault
+/// authority follow the bank mint in the remaining accounts.
 pub fn lending_account_deposit<'info>(
-    mut ctx: Context<'_, '_, 'info, 'info, LendingAccountDeposit<'info>>,
+    ctx: Context<'_, '_, 'info, 'info, LendingAccountDeposit<'info>>,
     amount: u64,
+    on_behalf_of: Option<bool>,
+    mint_receipts: Option<bool>,
 ) -> AstrolendResult {
     let LendingAccountDeposit {
+        astrolend_group,
         astrolend_account: astrolend_account_loader,
         signer,
+        bank,
         signer_token_account,
         bank_liquidity_vault,
         token_program,
-        bank: bank_loader,
-        ..
+        whitelist_entry,
     } = ctx.accounts;
-    let clock = Clock::get()?;
-    let maybe_bank_mint = utils::maybe_take_bank_mint(
-        &mut ctx.remaining_accounts,
-        &*bank_loader.load()?,
-        token_program.key,
-    )?;
-
-    let mut bank = bank_loader.load_mut()?;
-    let mut astrolend_account = astrolend_account_loader.load_mut()?;
-
-    check!(
-        !astrolend_account.get_flag(DISABLED_FLAG),
-        AstrolendError::AccountDisabled
-    );
-
-    bank.accrue_interest(
-        clock.unix_timestamp,
-        #[cfg(not(feature = "client"))]
-        bank_loader.key(),
-    )?;
-
-    let mut bank_account = BankAccountWrapper::find_or_create(
-        &bank_loader.key(),
-        &mut bank,
-        &mut astrolend_account.lending_account,
-    )?;
-
-    bank_account.deposit(I80F48::from_num(amount))?;
-
-    let amount_pre_fee = maybe_bank_mint
-        .as_ref()
-        .map(|mint| {
-            utils::calculate_pre_fee_spl_deposit_amount(mint.to_account_info(), amount, clock.epoch)
-        })
-        .transpose()?
-        .unwrap_or(amount);
 
-    bank_account.deposit_spl_transfer(
-        amount_pre_fee,
-        signer_token_account.to_account_info(),
-        bank_liquidity_vault.to_account_info(),
-        signer.to_account_info(),
-        maybe_bank_mint.as_ref(),
-        token_program.to_account_info(),
+    let outcome = execute_balance_change(
+        BalanceChange::deposit(amount),
+        BalanceChangeAccounts {
+            astrolend_group,
+            astrolend_account: astrolend_account_loader,
+            signer,
+            bank,
+            user_token_account: signer_token_account.to_account_info(),
+            bank_liquidity_vault: bank_liquidity_vault.to_account_info(),
+            bank_liquidity_vault_authority: None,
+            token_program,
+            whitelist_entry: whitelist_entry.as_ref(),
+        },
+        BalanceChangeOptions {
+            on_behalf_of: on_behalf_of.unwrap_or(false),
+            mint_receipts: mint_receipts.unwrap_or(false),
+            ..Default::default()
+        },
+        instruction_discriminator!(LendingAccountDeposit),
         ctx.remaining_accounts,
     )?;
 
+    let astrolend_account = astrolend_account_loader.load()?;
+    let header = || AccountEventHeader {
+        signer: Some(signer.key()),
+        astrolend_account: astrolend_account_loader.key(),
+        astrolend_account_authority: astrolend_account.authority,
+        astrolend_group: astrolend_account.group,
+    };
+
     emit!(LendingAccountDepositEvent {
-        header: AccountEventHeader {
-            signer: Some(signer.key()),
-            astrolend_account: astrolend_account_loader.key(),
-            astrolend_account_authority: astrolend_account.authority,
-            astrolend_group: astrolend_account.group,
-        },
-        bank: bank_loader.key(),
-        mint: bank.mint,
-        amount,
+        header: header(),
+        bank: bank.key(),
+        mint: outcome.mint,
+        amount: outcome.amount,
+        meta: account_event_meta!(LendingAccountDeposit, astrolend_account.account_tag),
     });
 
+    if let Some((receipt_mint, receipts)) = outcome.receipts {
+        emit!(LendingAccountReceiptEvent {
+            header: header(),
+            bank: bank.key(),
+            receipt_mint,
+            receipts,
+            amount: bank
+                .load()?
+                .get_asset_amount(I80F48::from_num(receipts))?
+                .to_num::<f64>(),
+            redeem: false,
+            meta: account_event_meta!(LendingAccountDeposit, astrolend_account.account_tag),
+        });
+    }
+
     Ok(())
 }
 
 #[derive(Accounts)]
 pub struct LendingAccountDeposit<'info> {
+    /// Only written to track the TVL of groups with a `group_tvl_cap_usd`.
+    #[account(mut)]
     pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
 
     #[account(
@@ -106,9 +121,7 @@ pub struct LendingAccountDeposit<'info> {
     )]
     pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
 
-    #[account(
-        address = astrolend_account.load()?.authority,
-    )]
+    /// Account authority, or any wallet when depositing on behalf of the account
     pub signer: Signer<'info>,
 
     #[account(
@@ -133,4 +146,16 @@ pub struct LendingAccountDeposit<'info> {
     pub bank_liquidity_vault: AccountInfo<'info>,
 
     pub token_program: Interface<'info, TokenInterface>,
+
+    /// CHECK: Seed constraint check, only read when the group requires a whitelist.
+    /// Without the whitelist entry of the account authority the account is reduce only.
+    #[account(
+        seeds = [
+            WHITELIST_SEED.as_bytes(),
+            astrolend_group.key().as_ref(),
+            astrolend_account.load()?.authority.as_ref(),
+        ],
+        bump,
+    )]
+    pub whitelist_entry: Option<AccountInfo<'info>>,
 }
@@ -5,28 +5,43 @@ use anchor_spl::token_interface::{
 
 use crate::{
     check,
-    constants::{EMISSIONS_AUTH_SEED, EMISSIONS_TOKEN_ACCOUNT_SEED},
-    debug,
+    constants::{
+        EMISSIONS_AUTH_SEED, EMISSIONS_CLAIM_ACCOUNTS_PER_BANK, EMISSIONS_COLLATERAL_FLAG,
+        EMISSIONS_TOKEN_ACCOUNT_SEED, MAX_SETTLE_EMISSIONS_ACCOUNTS,
+    },
+    debug, emissions_signer,
     prelude::{AstrolendError, AstrolendResult},
     state::{
-        astrolend_account::{BankAccountWrapper, AstrolendAccount, DISABLED_FLAG},
+        astrolend_account::{
+            check_operation_allowed, BankAccountWrapper, BankOperation, AstrolendAccount,
+            HealthCheckContext, RiskEngine,
+        },
         astrolend_group::{Bank, AstrolendGroup},
     },
 };
 
+/// Claim the emissions of the account balance in `bank`.
+///
+/// In groups with `EMISSIONS_COLLATERAL_FLAG` set settled emissions can collateralize borrows,
+/// so claims are health checked, the remaining accounts are the health check observation
+/// accounts.
 pub fn lending_account_withdraw_emissions<'info>(
     ctx: Context<'_, '_, 'info, 'info, LendingAccountWithdrawEmissions<'info>>,
 ) -> AstrolendResult {
+    let astrolend_group = ctx.accounts.astrolend_group.load()?;
     let mut astrolend_account = ctx.accounts.astrolend_account.load_mut()?;
+    let mut bank = ctx.accounts.bank.load_mut()?;
 
-    check!(
-        !astrolend_account.get_flag(DISABLED_FLAG),
-        AstrolendError::AccountDisabled
-    );
+    check_operation_allowed(
+        BankOperation::ClaimEmissions,
+        &bank,
+        &astrolend_account,
+    )?;
 
-    let mut bank = ctx.accounts.bank.load_mut()?;
+    let emissions_auth_bump = bank.get_emissions_auth_bump(&ctx.accounts.bank.key());
 
-    let mut balance = BankAccountWrapper::find(
+    // Balances closed with emissions outstanding stay claimable
+    let mut balance = BankAccountWrapper::find_claimable(
         ctx.accounts.bank.to_account_info().key,
         &mut bank,
         &mut astrolend_account.lending_account,
@@ -38,29 +53,58 @@ pub fn lending_account_withdraw_emissions<'info>(
     if emissions_settle_amount > 0 {
         debug!("Transferring {} emissions to user", emissions_settle_amount);
 
-        let signer_seeds: &[&[&[u8]]] = &[&[
-            EMISSIONS_AUTH_SEED.as_bytes(),
-            &ctx.accounts.bank.key().to_bytes(),
-            &ctx.accounts.emissions_mint.key().to_bytes(),
-            &[ctx.bumps.emissions_auth],
-        ]];
-
-        transfer_checked(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                TransferChecked {
-                    from: ctx.accounts.emissions_vault.to_account_info(),
-                    to: ctx.accounts.destination_account.to_account_info(),
-                    authority: ctx.accounts.emissions_auth.to_account_info(),
-                    mint: ctx.accounts.emissions_mint.to_account_info(),
-                },
-                signer_seeds,
-            ),
+        transfer_emissions(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.emissions_vault.to_account_info(),
+            ctx.accounts.destination_account.to_account_info(),
+            ctx.accounts.emissions_auth.to_account_info(),
+            &ctx.accounts.emissions_mint,
+            ctx.accounts.bank.key(),
+            emissions_auth_bump,
             emissions_settle_amount,
-            ctx.accounts.emissions_mint.decimals,
         )?;
     }
 
+    drop(bank);
+
+    if astrolend_group.get_group_flag(EMISSIONS_COLLATERAL_FLAG) {
+        RiskEngine::check_post_action(
+            &astrolend_group,
+            &astrolend_account,
+            ctx.remaining_accounts,
+            HealthCheckContext::of_account(&astrolend_account),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn transfer_emissions<'info>(
+    token_program: AccountInfo<'info>,
+    emissions_vault: AccountInfo<'info>,
+    destination_account: AccountInfo<'info>,
+    emissions_auth: AccountInfo<'info>,
+    emissions_mint: &InterfaceAccount<'info, Mint>,
+    bank_pk: Pubkey,
+    emissions_auth_bump: u8,
+    amount: u64,
+) -> AstrolendResult {
+    transfer_checked(
+        CpiContext::new_with_signer(
+            token_program,
+            TransferChecked {
+                from: emissions_vault,
+                to: destination_account,
+                authority: emissions_auth,
+                mint: emissions_mint.to_account_info(),
+            },
+            emissions_signer!(bank_pk, emissions_mint.key(), emissions_auth_bump),
+        ),
+        amount,
+        emissions_mint.decimals,
+    )?;
+
     Ok(())
 }
 
@@ -96,7 +140,7 @@ pub struct LendingAccountWithdrawEmissions<'info> {
             bank.key().as_ref(),
             emissions_mint.key().as_ref(),
         ],
-        bump
+        bump = bank.load()?.get_emissions_auth_bump(&bank.key()),
     )]
     /// CHECK: Asserted by PDA
     pub emissions_auth: AccountInfo<'info>,
@@ -117,6 +161,151 @@ pub struct LendingAccountWithdrawEmissions<'info> {
     pub token_program: Interface<'info, TokenInterface>,
 }
 
+/// Withdraw the emissions of several banks at once.
+///
+/// Remaining accounts, `EMISSIONS_CLAIM_ACCOUNTS_PER_BANK` per bank, in this order:
+/// [
+///    bank (writable),
+///    emissions_mint,
+///    emissions_auth,
+///    emissions_vault (writable),
+///    destination_account (writable),
+///  ]
+///
+/// All emissions mints must belong to `token_program`. Banks the account has no claimable
+/// balance in, or nothing to claim from, are skipped.
+///
+/// There is no room for health check observation accounts, so in groups with
+/// `EMISSIONS_COLLATERAL_FLAG` set accounts with liabilities claim one bank at a time.
+pub fn lending_account_withdraw_emissions_many<'info>(
+    ctx: Context<'_, '_, 'info, 'info, LendingAccountWithdrawEmissionsMany<'info>>,
+) -> AstrolendResult {
+    check!(
+        !ctx.remaining_accounts.is_empty()
+            && ctx.remaining_accounts.len() % EMISSIONS_CLAIM_ACCOUNTS_PER_BANK == 0,
+        AstrolendError::InvalidEmissionsClaimAccounts,
+        "Expected {} accounts per bank",
+        EMISSIONS_CLAIM_ACCOUNTS_PER_BANK
+    );
+
+    let astrolend_group_pk = ctx.accounts.astrolend_group.key();
+    let astrolend_group = ctx.accounts.astrolend_group.load()?;
+    let mut astrolend_account = ctx.accounts.astrolend_account.load_mut()?;
+
+    check!(
+        !(astrolend_group.get_group_flag(EMISSIONS_COLLATERAL_FLAG)
+            && astrolend_account.lending_account.has_liabilities()),
+        AstrolendError::InvalidEmissionsClaimAccounts,
+        "Emissions count as collateral, claim them one bank at a time"
+    );
+
+    for claim_ais in ctx
+        .remaining_accounts
+        .chunks_exact(EMISSIONS_CLAIM_ACCOUNTS_PER_BANK)
+    {
+        let [bank_ai, emissions_mint_ai, emissions_auth_ai, emissions_vault_ai, destination_ai] =
+            claim_ais
+        else {
+            unreachable!()
+        };
+
+        let bank_loader = AccountLoader::<Bank>::try_from(bank_ai)?;
+        let mut bank = bank_loader.load_mut()?;
+        check!(
+            bank.group == astrolend_group_pk,
+            AstrolendError::InvalidEmissionsClaimAccounts
+        );
+
+        check!(
+            emissions_mint_ai.key == &bank.emissions_mint
+                && emissions_mint_ai.owner == ctx.accounts.token_program.key,
+            AstrolendError::InvalidEmissionsClaimAccounts
+        );
+        let emissions_mint = InterfaceAccount::<Mint>::try_from(emissions_mint_ai)?;
+
+        let emissions_auth_bump = bank.get_emissions_auth_bump(bank_ai.key);
+        let emissions_auth = Pubkey::create_program_address(
+            &[
+                EMISSIONS_AUTH_SEED.as_bytes(),
+                bank_ai.key.as_ref(),
+                emissions_mint_ai.key.as_ref(),
+                &[emissions_auth_bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| AstrolendError::InvalidEmissionsClaimAccounts)?;
+        let (emissions_vault, _) = Pubkey::find_program_address(
+            &[
+                EMISSIONS_TOKEN_ACCOUNT_SEED.as_bytes(),
+                bank_ai.key.as_ref(),
+                emissions_mint_ai.key.as_ref(),
+            ],
+            &crate::ID,
+        );
+        check!(
+            emissions_auth_ai.key == &emissions_auth && emissions_vault_ai.key == &emissions_vault,
+            AstrolendError::InvalidEmissionsClaimAccounts
+        );
+
+        check_operation_allowed(
+            BankOperation::ClaimEmissions,
+            &bank,
+            &astrolend_account,
+        )?;
+
+        let Ok(mut balance) = BankAccountWrapper::find_claimable(
+            bank_ai.key,
+            &mut bank,
+            &mut astrolend_account.lending_account,
+        ) else {
+            debug!("Skipping {}, no balance", bank_ai.key);
+            continue;
+        };
+
+        let emissions_settle_amount = balance.settle_emissions_and_get_transfer_amount()?;
+        if emissions_settle_amount == 0 {
+            debug!("Skipping {}, nothing to claim", bank_ai.key);
+            continue;
+        }
+
+        debug!(
+            "Transferring {} emissions of {} to user",
+            emissions_settle_amount, bank_ai.key
+        );
+
+        transfer_emissions(
+            ctx.accounts.token_program.to_account_info(),
+            emissions_vault_ai.clone(),
+            destination_ai.clone(),
+            emissions_auth_ai.clone(),
+            &emissions_mint,
+            *bank_ai.key,
+            emissions_auth_bump,
+            emissions_settle_amount,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountWithdrawEmissionsMany<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = astrolend_account.load()?.group == astrolend_group.key(),
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        address = astrolend_account.load()?.authority,
+    )]
+    pub signer: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
 /// Permissionlessly settle unclaimed emissions to a users account.
 pub fn lending_account_settle_emissions(
     ctx: Context<LendingAccountSettleEmissions>,
@@ -146,3 +335,43 @@ pub struct LendingAccountSettleEmissions<'info> {
     #[account(mut)]
     pub bank: AccountLoader<'info, Bank>,
 }
+
+/// Permissionlessly settle unclaimed emissions of `bank` for a batch of accounts, crediting
+/// their balances with what they accrued out of `emissions_reserved`.
+///
+/// Remaining accounts: up to `MAX_SETTLE_EMISSIONS_ACCOUNTS` astrolend accounts, writable.
+/// Accounts without an active balance in the bank are skipped.
+pub fn lending_account_settle_emissions_many<'info>(
+    ctx: Context<'_, '_, 'info, 'info, LendingAccountSettleEmissionsMany<'info>>,
+) -> AstrolendResult {
+    check!(
+        ctx.remaining_accounts.len() <= MAX_SETTLE_EMISSIONS_ACCOUNTS,
+        AstrolendError::TooManyEmissionsSettlements
+    );
+
+    let bank_pk = ctx.accounts.bank.key();
+    let mut bank = ctx.accounts.bank.load_mut()?;
+    let current_timestamp: u64 = Clock::get()?.unix_timestamp.try_into().unwrap();
+
+    for astrolend_account_ai in ctx.remaining_accounts.iter() {
+        let astrolend_account_loader =
+            AccountLoader::<AstrolendAccount>::try_from(astrolend_account_ai)?;
+        let mut astrolend_account = astrolend_account_loader.load_mut()?;
+
+        if !astrolend_account.lending_account.has_balance(&bank_pk) {
+            debug!("Skipping {}, no balance", astrolend_account_ai.key);
+            continue;
+        }
+
+        BankAccountWrapper::find(&bank_pk, &mut bank, &mut astrolend_account.lending_account)?
+            .claim_emissions(current_timestamp)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountSettleEmissionsMany<'info> {
+    #[account(mut)]
+    pub bank: AccountLoader<'info, Bank>,
+}
@@ -0,0 +1,76 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    account_event_meta, check,
+    events::{AccountEventHeader, LendingAccountFlagUnprofitableEvent},
+    prelude::*,
+    state::{
+        astrolend_account::{AstrolendAccount, RiskEngine, BAD_DEBT_CANDIDATE_FLAG},
+        astrolend_group::AstrolendGroup,
+    },
+};
+
+/// Permissionlessly flag an account below the maintenance requirement whose collateral is worth
+/// less than the group `min_liquidation_value_usd`. No liquidator would pay the transaction cost
+/// to seize it, left alone the account rots into bad debt.
+///
+/// Flagged accounts can go through bankruptcy while still holding that dust, see
+/// [RiskEngine::check_account_bankrupt].
+///
+/// Calling it on a flagged account that is no longer a candidate, e.g. because its health
+/// recovered after a repay or deposit, clears the flag.
+///
+/// Expected remaining account schema: the bank and oracle of every active balance, as for
+/// health checks.
+pub fn lending_account_flag_unprofitable<'info>(
+    ctx: Context<'_, '_, 'info, 'info, LendingAccountFlagUnprofitable<'info>>,
+) -> AstrolendResult {
+    let astrolend_group = ctx.accounts.astrolend_group.load()?;
+    let mut astrolend_account = ctx.accounts.astrolend_account.load_mut()?;
+
+    let (is_candidate, maint_health, collateral_value) =
+        RiskEngine::new(&astrolend_group, &astrolend_account, ctx.remaining_accounts)?
+            .get_bad_debt_candidate_status()?;
+
+    if is_candidate {
+        astrolend_account.set_flag(BAD_DEBT_CANDIDATE_FLAG);
+    } else {
+        check!(
+            astrolend_account.get_flag(BAD_DEBT_CANDIDATE_FLAG),
+            AstrolendError::AccountNotBadDebtCandidate
+        );
+
+        astrolend_account.unset_flag(BAD_DEBT_CANDIDATE_FLAG);
+    }
+
+    emit!(LendingAccountFlagUnprofitableEvent {
+        header: AccountEventHeader {
+            signer: Some(ctx.accounts.signer.key()),
+            astrolend_account: ctx.accounts.astrolend_account.key(),
+            astrolend_account_authority: astrolend_account.authority,
+            astrolend_group: astrolend_account.group,
+        },
+        maint_health: maint_health.to_num::<f64>(),
+        collateral_value: collateral_value.to_num::<f64>(),
+        flagged: is_candidate,
+        meta: account_event_meta!(
+            LendingAccountFlagUnprofitable,
+            astrolend_account.account_tag
+        ),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountFlagUnprofitable<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = astrolend_account.load()?.group == astrolend_group.key(),
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    pub signer: Signer<'info>,
+}
@@ -7,7 +7,9 @@ use solana_program::{
 use crate::{
     check,
     prelude::*,
-    state::astrolend_account::{AstrolendAccount, RiskEngine, DISABLED_FLAG, IN_FLASHLOAN_FLAG},
+    state::astrolend_account::{
+        AstrolendAccount, HealthCheckContext, RiskEngine, DISABLED_FLAG, IN_FLASHLOAN_FLAG,
+    },
 };
 
 pub fn lending_account_start_flashloan(
@@ -133,7 +135,13 @@ pub fn lending_account_end_flashloan<'info>(
 
     astrolend_account.unset_flag(IN_FLASHLOAN_FLAG);
 
-    RiskEngine::check_account_init_health(&astrolend_account, ctx.remaining_accounts)?;
+    // Single enforcement point of the checks deferred during the flashloan
+    RiskEngine::check_post_action(
+        &*ctx.accounts.astrolend_group.load()?,
+        &astrolend_account,
+        ctx.remaining_accounts,
+        HealthCheckContext::Normal,
+    )?;
 
     Ok(())
 }
@@ -144,4 +152,6 @@ pub struct LendingAccountEndFlashloan<'info> {
     pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
     #[account(address = astrolend_account.load()?.authority)]
     pub signer: Signer<'info>,
+    #[account(address = astrolend_account.load()?.group)]
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
 }
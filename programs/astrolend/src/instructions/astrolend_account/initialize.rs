@@ -1,12 +1,30 @@
 use crate::{
+    account_event_meta,
+    constants::WHITELIST_SEED,
     events::{AccountEventHeader, AstrolendAccountCreateEvent},
     prelude::*,
-    state::astrolend_account::AstrolendAccount,
+    state::astrolend_account::{AstrolendAccount, ASTROLEND_ACCOUNT_SPACE},
 };
 use anchor_lang::prelude::*;
 use solana_program::sysvar::Sysvar;
 
-pub fn initialize_account(ctx: Context<AstrolendAccountInitialize>) -> AstrolendResult {
+/// Will error if the group requires a whitelist and the authority isn't whitelisted.
+///
+/// The authority only signs, rent is paid by `fee_payer`, so programs can own accounts through a
+/// PDA authority signing with `invoke_signed`. Every account instruction but flashloans, which
+/// must be top level, can then be called through CPI.
+///
+/// `account_tag` is stored on the account and in the metadata of its events, zeroed if `None`.
+pub fn initialize_account(
+    ctx: Context<AstrolendAccountInitialize>,
+    account_tag: Option<[u8; 8]>,
+) -> AstrolendResult {
+    ctx.accounts.astrolend_group.load()?.check_wallet_whitelisted(
+        &ctx.accounts.astrolend_group.key(),
+        ctx.accounts.authority.key,
+        Some(&ctx.accounts.whitelist_entry),
+    )?;
+
     let AstrolendAccountInitialize {
         authority,
         astrolend_group,
@@ -16,7 +34,11 @@ pub fn initialize_account(ctx: Context<AstrolendAccountInitialize>) -> Astrolend
 
     let mut astrolend_account = astrolend_account_loader.load_init()?;
 
-    astrolend_account.initialize(astrolend_group.key(), authority.key());
+    astrolend_account.initialize(
+        astrolend_group.key(),
+        authority.key(),
+        account_tag.unwrap_or_default(),
+    );
 
     emit!(AstrolendAccountCreateEvent {
         header: AccountEventHeader {
@@ -24,7 +46,8 @@ pub fn initialize_account(ctx: Context<AstrolendAccountInitialize>) -> Astrolend
             astrolend_account: astrolend_account_loader.key(),
             astrolend_account_authority: astrolend_account.authority,
             astrolend_group: astrolend_account.group,
-        }
+        },
+        meta: account_event_meta!(AstrolendAccountInitialize, astrolend_account.account_tag),
     });
 
     Ok(())
@@ -37,7 +60,7 @@ pub struct AstrolendAccountInitialize<'info> {
     #[account(
         init,
         payer = fee_payer,
-        space = 8 + std::mem::size_of::<AstrolendAccount>()
+        space = ASTROLEND_ACCOUNT_SPACE
     )]
     pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
 
@@ -47,4 +70,15 @@ pub struct AstrolendAccountInitialize<'info> {
     pub fee_payer: Signer<'info>,
 
     pub system_program: Program<'info, System>,
+
+    /// CHECK: Seed constraint check, only read when the group requires a whitelist
+    #[account(
+        seeds = [
+            WHITELIST_SEED.as_bytes(),
+            astrolend_group.key().as_ref(),
+            authority.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub whitelist_entry: AccountInfo<'info>,
 }
@@ -1,18 +1,23 @@
 use crate::constants::{
     INSURANCE_VAULT_SEED, LIQUIDATION_INSURANCE_FEE, LIQUIDATION_LIQUIDATOR_FEE,
 };
-use crate::events::{AccountEventHeader, LendingAccountLiquidateEvent, LiquidationBalances};
-use crate::state::astrolend_account::{calc_amount, calc_value, RiskEngine};
+use crate::events::{
+    AccountEventHeader, LendingAccountLiquidateEvent, LiquidationBalances, LiquidationPrice,
+};
+use crate::state::astrolend_account::{
+    calc_amount, calc_value, check_operation_allowed, load_mut_with_credit_history, BankOperation,
+    CreditHistory, HealthCheckContext, LiquidationRecord, RiskEngine,
+};
 use crate::state::astrolend_group::{Bank, BankVaultType};
-use crate::state::price::{OraclePriceFeedAdapter, OraclePriceType, PriceAdapter, PriceBias};
+use crate::state::price::{OraclePriceType, PriceAdapter, PriceBias};
 use crate::{
-    bank_signer,
+    account_event_meta, bank_signer,
     constants::{LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED},
     state::astrolend_account::{BankAccountWrapper, AstrolendAccount},
 };
 use crate::{check, debug, prelude::*, utils};
 use anchor_lang::prelude::*;
-use anchor_spl::token_interface::{TokenAccount, TokenInterface};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 use fixed::types::I80F48;
 use solana_program::clock::Clock;
 use solana_program::sysvar::Sysvar;
@@ -31,6 +36,7 @@ use solana_program::sysvar::Sysvar;
 /// - `p_a`: Price of `A`
 /// - `f_l`: Liquidation fee
 /// - `f_i`: Insurance fee
+/// - `f_p`: Group liquidation protocol fee
 ///
 /// The liquidator invokes this instruction with `q_a` as input (the total amount of collateral to be liquidated).
 /// This is done because `q_a` is the most bounded variable in this process, as if the `q_a` is larger than what the liquidatee has, the instruction will fail.
@@ -43,16 +49,30 @@ use solana_program::sysvar::Sysvar;
 /// The insurance fee is taken from the difference between liability being paid by the liquidator and the liability being received by the liquidatee.
 /// This difference is deposited into the insurance fund.
 ///
+/// The protocol fee is carved from the seized collateral before the liquidator fee is applied, and credited to the asset bank group fees.
+/// The liquidator fee applies to the remaining collateral, so the liquidator bonus stays at `f_l` of what it receives,
+/// while the protocol fee comes out of the insurance fee. Together the three add up to the `f_l + f_i` discount.
+///
 /// Accounting changes in the liquidation process:
 /// 1. The liquidator removes `q_ll` of `L`
 /// 2. The liquidatee receives `q_lf` of `L`
 /// 3. The liquidatee removes `q_a` of `A`
-/// 4. The liquidator receives `q_a` of `A`
+/// 4. The liquidator receives `q_a * (1 - f_p)` of `A`
 /// 5. The insurance fund receives `q_ll - q_lf` of `L`
+/// 6. The asset bank group fees receive `q_a * f_p` of `A`
+///
+/// Only the insurance fee leaves a vault, the collateral and the liability move between the two
+/// accounts as shares, so the liquidator needs no token accounts and keeps the collateral
+/// deposited. Its init health check counts both the collateral received and the liability taken
+/// over.
+///
+/// The asset and liability banks can share a mint, e.g. two seeded banks of one mint: each side
+/// is priced with the oracle account of its own bank and converted to shares with the share
+/// values of its own bank. Only the same bank on both sides is rejected.
 ///
 /// Calculations:
 ///  
-/// `q_ll = q_a * p_a * (1 - f_l) / p_l`
+/// `q_ll = q_a * (1 - f_p) * p_a * (1 - f_l) / p_l`
 /// `q_lf = q_a * p_a * (1 - (f_l + f_i)) / p_l`
 ///
 /// Risk model
@@ -73,6 +93,7 @@ use solana_program::sysvar::Sysvar;
 ///    liab_oracle_ai,
 ///    liquidator_observation_ais...,
 ///    liquidatee_observation_ais...,
+///    liquidatee_emissions_observation_ais... (optional, see `EMISSIONS_COLLATERAL_FLAG`),
 ///  ]
 
 pub fn lending_account_liquidate<'info>(
@@ -97,106 +118,172 @@ pub fn lending_account_liquidate<'info>(
         ..
     } = ctx.accounts;
 
-    let mut liquidator_astrolend_account = liquidator_astrolend_account_loader.load_mut()?;
-    let mut liquidatee_astrolend_account = liquidatee_astrolend_account_loader.load_mut()?;
+    let (mut liquidator_astrolend_account, mut liquidator_credit_history) =
+        load_mut_with_credit_history(liquidator_astrolend_account_loader)?;
+    let (mut liquidatee_astrolend_account, mut liquidatee_credit_history) =
+        load_mut_with_credit_history(liquidatee_astrolend_account_loader)?;
     let clock = Clock::get()?;
     let current_timestamp = clock.unix_timestamp;
 
+    let liquidation_protocol_fee_pct = {
+        let astrolend_group = ctx.accounts.astrolend_group.load()?;
+
+        check_operation_allowed(
+            BankOperation::LiquidateSeize,
+            &*ctx.accounts.asset_bank.load()?,
+            &liquidator_astrolend_account,
+        )?;
+        check_operation_allowed(
+            BankOperation::LiquidateRepay,
+            &*ctx.accounts.liab_bank.load()?,
+            &liquidator_astrolend_account,
+        )?;
+
+        ctx.accounts
+            .asset_bank
+            .load_mut()?
+            .enter_reentrancy_guard(clock.slot)?;
+        ctx.accounts
+            .liab_bank
+            .load_mut()?
+            .enter_reentrancy_guard(clock.slot)?;
+
+        I80F48::from(astrolend_group.liquidation_protocol_fee_pct)
+    };
+
     let maybe_liab_bank_mint = utils::maybe_take_bank_mint(
         &mut ctx.remaining_accounts,
         &*ctx.accounts.liab_bank.load()?,
         ctx.accounts.token_program.key,
+        ctx.accounts.bank_liquidity_vault.to_account_info().owner,
     )?;
     {
+        let astrolend_group = ctx.accounts.astrolend_group.load()?;
+
         ctx.accounts.asset_bank.load_mut()?.accrue_interest(
             current_timestamp,
+            &astrolend_group,
             #[cfg(not(feature = "client"))]
             ctx.accounts.asset_bank.key(),
+            #[cfg(not(feature = "client"))]
+            crate::instruction_discriminator!(LendingAccountLiquidate),
         )?;
         ctx.accounts.liab_bank.load_mut()?.accrue_interest(
             current_timestamp,
+            &astrolend_group,
             #[cfg(not(feature = "client"))]
             ctx.accounts.liab_bank.key(),
+            #[cfg(not(feature = "client"))]
+            crate::instruction_discriminator!(LendingAccountLiquidate),
         )?;
     }
-    let init_liquidatee_remaining_len = liquidatee_astrolend_account.get_remaining_accounts_len();
+
+    // The liquidator observation accounts are for its post liquidation balances, which always
+    // include the asset and liability banks, as liquidations don't close liquidator balances
+    let liquidator_accounts_starting_pos = 2;
+    let liquidatee_accounts_starting_pos = liquidator_accounts_starting_pos
+        + liquidator_astrolend_account.get_remaining_accounts_len_with(&[
+            ctx.accounts.asset_bank.key(),
+            ctx.accounts.liab_bank.key(),
+        ]);
+    check!(
+        liquidatee_accounts_starting_pos <= ctx.remaining_accounts.len(),
+        AstrolendError::MissingPythOrBankAccount
+    );
+
     let pre_liquidation_health = {
-        let liquidatee_accounts_starting_pos =
-            ctx.remaining_accounts.len() - init_liquidatee_remaining_len;
         let liquidatee_remaining_accounts =
             &ctx.remaining_accounts[liquidatee_accounts_starting_pos..];
 
-        RiskEngine::new(&liquidatee_astrolend_account, liquidatee_remaining_accounts)?
-            .check_pre_liquidation_condition_and_get_account_health(&ctx.accounts.liab_bank.key())?
+        RiskEngine::new(
+            &*ctx.accounts.astrolend_group.load()?,
+            &liquidatee_astrolend_account,
+            liquidatee_remaining_accounts,
+        )?
+        .check_pre_liquidation_condition_and_get_account_health(&ctx.accounts.liab_bank.key())?
     };
 
     // ##Accounting changes##
 
-    let (pre_balances, post_balances) = {
+    let (pre_balances, post_balances, liquidation_record, fees, prices) = {
         let asset_amount = I80F48::from_num(asset_amount);
 
         let mut asset_bank = ctx.accounts.asset_bank.load_mut()?;
-        let asset_price = {
-            let oracle_ais = &ctx.remaining_accounts[0..1];
-            let asset_pf = OraclePriceFeedAdapter::try_from_bank_config(
-                &asset_bank.config,
-                oracle_ais,
-                &clock,
-            )?;
-            asset_pf.get_price_of_type(OraclePriceType::RealTime, Some(PriceBias::Low))?
-        };
-
         let mut liab_bank = ctx.accounts.liab_bank.load_mut()?;
-        let liab_price = {
-            let oracle_ais = &ctx.remaining_accounts[1..2];
-            let liab_pf = OraclePriceFeedAdapter::try_from_bank_config(
-                &liab_bank.config,
-                oracle_ais,
-                &clock,
-            )?;
-            liab_pf.get_price_of_type(OraclePriceType::RealTime, Some(PriceBias::High))?
-        };
+        let (asset_price, liab_price) = load_liquidation_prices(
+            &mut asset_bank,
+            &mut liab_bank,
+            &ctx.remaining_accounts[0..2],
+            &clock,
+        )?;
 
         let final_discount = I80F48::ONE - (LIQUIDATION_INSURANCE_FEE + LIQUIDATION_LIQUIDATOR_FEE);
         let liquidator_discount = I80F48::ONE - LIQUIDATION_LIQUIDATOR_FEE;
 
+        // Protocol fee is carved from the seized collateral before the liquidator discount
+        let protocol_fee = asset_amount
+            .checked_mul(liquidation_protocol_fee_pct)
+            .ok_or(AstrolendError::MathError)?;
+        let asset_amount_liquidator = asset_amount
+            .checked_sub(protocol_fee)
+            .ok_or(AstrolendError::MathError)?;
+
         // Quantity of liability to be paid off by liquidator
-        let liab_amount_liquidator = calc_amount(
-            calc_value(
-                asset_amount,
-                asset_price,
-                asset_bank.mint_decimals,
-                Some(liquidator_discount),
-            )?,
+        let liab_amount_liquidator = calc_liab_amount(
+            asset_amount_liquidator,
+            asset_price,
+            &asset_bank,
             liab_price,
-            liab_bank.mint_decimals,
+            &liab_bank,
+            Some(liquidator_discount),
         )?;
 
         // Quantity of liability to be received by liquidatee
-        let liab_amount_final = calc_amount(
-            calc_value(
-                asset_amount,
-                asset_price,
-                asset_bank.mint_decimals,
-                Some(final_discount),
-            )?,
+        let liab_amount_final = calc_liab_amount(
+            asset_amount,
+            asset_price,
+            &asset_bank,
             liab_price,
-            liab_bank.mint_decimals,
+            &liab_bank,
+            Some(final_discount),
         )?;
 
         // Insurance fund fee
         let insurance_fund_fee = liab_amount_liquidator - liab_amount_final;
 
-        assert!(
-            insurance_fund_fee >= I80F48::ZERO,
-            "Insurance fund fee cannot be negative"
-        );
-
         debug!(
-            "liab_quantity_liq: {}, liab_q_final: {}, asset_amount: {}, insurance_fund_fee: {}",
-            liab_amount_liquidator, liab_amount_final, asset_amount, insurance_fund_fee
+            "liab_quantity_liq: {}, liab_q_final: {}, asset_amount: {}, insurance_fund_fee: {}, protocol_fee: {}",
+            liab_amount_liquidator,
+            liab_amount_final,
+            asset_amount,
+            insurance_fund_fee,
+            protocol_fee
         );
 
+        let fees = LiquidationFees {
+            liquidator_bonus: calc_value(
+                asset_amount_liquidator,
+                asset_price,
+                asset_bank.mint_decimals,
+                None,
+            )?
+            .checked_sub(calc_value(
+                liab_amount_liquidator,
+                liab_price,
+                liab_bank.mint_decimals,
+                None,
+            )?)
+            .ok_or(AstrolendError::MathError)?,
+            insurance_fee: calc_value(
+                insurance_fund_fee,
+                liab_price,
+                liab_bank.mint_decimals,
+                None,
+            )?,
+            protocol_fee: calc_value(protocol_fee, asset_price, asset_bank.mint_decimals, None)?,
+            insurance_fee_amount: insurance_fund_fee,
+        };
+
         // Liquidator pays off liability
         let (liquidator_liability_pre_balance, liquidator_liability_post_balance) = {
             let mut bank_account = BankAccountWrapper::find_or_create(
@@ -253,7 +340,7 @@ pub fn lending_account_liquidate<'info>(
                 .bank
                 .get_asset_amount(bank_account.balance.asset_shares.into())?;
 
-            bank_account.increase_balance_in_liquidation(asset_amount)?;
+            bank_account.increase_balance_in_liquidation(asset_amount_liquidator)?;
 
             let post_balance = bank_account
                 .bank
@@ -262,17 +349,11 @@ pub fn lending_account_liquidate<'info>(
             (pre_balance, post_balance)
         };
 
-        let (insurance_fee_to_transfer, insurance_fee_dust) = (
-            insurance_fund_fee
-                .checked_to_num::<u64>()
-                .ok_or(AstrolendError::MathError)?,
-            insurance_fund_fee.frac(),
-        );
+        // Protocol fee tokens stay in the asset bank liquidity vault until collected
+        asset_bank.add_group_fees_outstanding(protocol_fee)?;
 
         let (liquidatee_liability_pre_balance, liquidatee_liability_post_balance) = {
             // Liquidatee receives liability payment
-            let liab_bank_liquidity_authority_bump = liab_bank.liquidity_vault_authority_bump;
-
             let mut liquidatee_liab_bank_account = BankAccountWrapper::find_or_create(
                 &ctx.accounts.liab_bank.key(),
                 &mut liab_bank,
@@ -291,36 +372,43 @@ pub fn lending_account_liquidate<'info>(
                     liquidatee_liab_bank_account.balance.liability_shares.into(),
                 )?;
 
-            // ## SPL transfer ##
-            // Insurance fund receives fee
-            liquidatee_liab_bank_account.withdraw_spl_transfer(
-                insurance_fee_to_transfer,
-                ctx.accounts.bank_liquidity_vault.to_account_info(),
-                ctx.accounts.bank_insurance_vault.to_account_info(),
-                ctx.accounts
-                    .bank_liquidity_vault_authority
-                    .to_account_info(),
-                maybe_liab_bank_mint.as_ref(),
-                ctx.accounts.token_program.to_account_info(),
-                bank_signer!(
-                    BankVaultType::Liquidity,
-                    ctx.accounts.liab_bank.key(),
-                    liab_bank_liquidity_authority_bump
-                ),
-                ctx.remaining_accounts,
-            )?;
-
             (
                 liquidatee_liability_pre_balance,
                 liquidatee_liability_post_balance,
             )
         };
 
-        liab_bank.collected_insurance_fees_outstanding =
-            I80F48::from(liab_bank.collected_insurance_fees_outstanding)
-                .checked_add(insurance_fee_dust)
-                .ok_or(AstrolendError::MathError)?
-                .into();
+        // ## SPL transfer ##
+        // Insurance fund receives fee
+        pay_insurance_fee(
+            &mut liab_bank,
+            ctx.accounts.liab_bank.key(),
+            insurance_fund_fee,
+            ctx.accounts.bank_liquidity_vault.to_account_info(),
+            ctx.accounts.bank_insurance_vault.to_account_info(),
+            ctx.accounts
+                .bank_liquidity_vault_authority
+                .to_account_info(),
+            maybe_liab_bank_mint.as_ref(),
+            ctx.accounts.token_program.to_account_info(),
+            ctx.remaining_accounts,
+        )?;
+
+        asset_bank.exit_reentrancy_guard();
+        liab_bank.exit_reentrancy_guard();
+
+        let liquidation_record = LiquidationRecord {
+            liquidator_account: liquidator_astrolend_account_loader.key(),
+            asset_bank: ctx.accounts.asset_bank.key(),
+            liability_bank: ctx.accounts.liab_bank.key(),
+            timestamp: current_timestamp,
+            asset_amount: asset_amount
+                .checked_to_num::<u64>()
+                .ok_or(AstrolendError::MathError)?,
+            liability_amount: liab_amount_final
+                .checked_to_num::<u64>()
+                .ok_or(AstrolendError::MathError)?,
+        };
 
         (
             LiquidationBalances {
@@ -335,32 +423,46 @@ pub fn lending_account_liquidate<'info>(
                 liquidator_asset_balance: liquidator_asset_post_balance.to_num::<f64>(),
                 liquidator_liability_balance: liquidator_liability_post_balance.to_num::<f64>(),
             },
+            liquidation_record,
+            fees,
+            (asset_price, liab_price),
         )
     };
 
-    // ## Risk checks ##
+    let liquidation_record_index = record_liquidation(
+        &mut liquidatee_astrolend_account,
+        liquidatee_credit_history.as_deref_mut(),
+        liquidation_record,
+    );
+    if let Some(credit_history) = liquidator_credit_history.as_deref_mut() {
+        credit_history.sync(&liquidator_astrolend_account.lending_account);
+    }
 
-    let liquidatee_accounts_starting_pos =
-        ctx.remaining_accounts.len() - init_liquidatee_remaining_len;
-    let liquidator_accounts_starting_pos =
-        liquidatee_accounts_starting_pos - liquidator_astrolend_account.get_remaining_accounts_len();
+    // ## Risk checks ##
 
     let liquidatee_remaining_accounts = &ctx.remaining_accounts[liquidatee_accounts_starting_pos..];
     let liquidator_remaining_accounts =
         &ctx.remaining_accounts[liquidator_accounts_starting_pos..liquidatee_accounts_starting_pos];
 
     // Verify liquidatee liquidation post health
-    let post_liquidation_health =
-        RiskEngine::new(&liquidatee_astrolend_account, liquidatee_remaining_accounts)?
-            .check_post_liquidation_condition_and_get_account_health(
-                &ctx.accounts.liab_bank.key(),
-                pre_liquidation_health,
-            )?;
+    let astrolend_group = ctx.accounts.astrolend_group.load()?;
+
+    let post_liquidation_health = RiskEngine::new(
+        &astrolend_group,
+        &liquidatee_astrolend_account,
+        liquidatee_remaining_accounts,
+    )?
+    .check_post_liquidation_condition_and_get_account_health(
+        &ctx.accounts.liab_bank.key(),
+        pre_liquidation_health,
+    )?;
 
     // Verify liquidator account health
-    RiskEngine::check_account_init_health(
+    RiskEngine::check_post_action(
+        &astrolend_group,
         &liquidator_astrolend_account,
         liquidator_remaining_accounts,
+        HealthCheckContext::Liquidation,
     )?;
 
     emit!(LendingAccountLiquidateEvent {
@@ -380,11 +482,154 @@ pub fn lending_account_liquidate<'info>(
         liquidatee_post_health: post_liquidation_health.to_num::<f64>(),
         pre_balances,
         post_balances,
+        liquidation_record_index: liquidation_record_index.map(|index| index as u8),
+        liquidator_bonus: fees.liquidator_bonus.to_num::<f64>(),
+        insurance_fee: fees.insurance_fee.to_num::<f64>(),
+        protocol_fee: fees.protocol_fee.to_num::<f64>(),
+        asset_price: LiquidationPrice {
+            price: prices.0.into(),
+            price_type: LIQUIDATION_PRICE_TYPE,
+        },
+        liability_price: LiquidationPrice {
+            price: prices.1.into(),
+            price_type: LIQUIDATION_PRICE_TYPE,
+        },
+        insurance_fee_amount: fees.insurance_fee_amount.to_num::<f64>(),
+        meta: account_event_meta!(
+            LendingAccountLiquidate,
+            liquidator_astrolend_account.account_tag
+        ),
     });
 
     Ok(())
 }
 
+/// Both sides of a liquidation are valued at real time prices, biased against the liquidatee.
+const LIQUIDATION_PRICE_TYPE: OraclePriceType = OraclePriceType::RealTime;
+
+/// Prices of the collateral and the liability of a liquidation, see [LIQUIDATION_PRICE_TYPE].
+///
+/// Expects the asset bank oracle followed by the liability bank oracle in `oracle_ais`.
+pub(super) fn load_liquidation_prices(
+    asset_bank: &mut Bank,
+    liab_bank: &mut Bank,
+    oracle_ais: &[AccountInfo],
+    clock: &Clock,
+) -> AstrolendResult<(I80F48, I80F48)> {
+    let asset_pf = asset_bank.load_price_feed(&oracle_ais[0..1], clock)?;
+    let asset_price = asset_pf.get_price_of_type(LIQUIDATION_PRICE_TYPE, Some(PriceBias::Low))?;
+
+    let liab_pf = liab_bank.load_price_feed(&oracle_ais[1..2], clock)?;
+    let liab_price = liab_pf.get_price_of_type(LIQUIDATION_PRICE_TYPE, Some(PriceBias::High))?;
+
+    Ok((asset_price, liab_price))
+}
+
+/// Quantity of liability worth `asset_amount` of collateral, its value discounted by
+/// `discount`.
+pub(super) fn calc_liab_amount(
+    asset_amount: I80F48,
+    asset_price: I80F48,
+    asset_bank: &Bank,
+    liab_price: I80F48,
+    liab_bank: &Bank,
+    discount: Option<I80F48>,
+) -> AstrolendResult<I80F48> {
+    calc_amount(
+        calc_value(
+            asset_amount,
+            asset_price,
+            asset_bank.mint_decimals,
+            discount,
+        )?,
+        liab_price,
+        liab_bank.mint_decimals,
+    )
+}
+
+/// Send `insurance_fee` native liability tokens from the liability bank liquidity vault to its
+/// insurance vault. The fraction of a token left, or the whole fee without an insurance vault,
+/// stays in the liquidity vault as outstanding fees, see [Bank::add_insurance_fees_outstanding].
+#[allow(clippy::too_many_arguments)]
+pub(super) fn pay_insurance_fee<'info>(
+    liab_bank: &mut Bank,
+    liab_bank_pk: Pubkey,
+    insurance_fee: I80F48,
+    liquidity_vault: AccountInfo<'info>,
+    insurance_vault: AccountInfo<'info>,
+    liquidity_vault_authority: AccountInfo<'info>,
+    maybe_liab_bank_mint: Option<&InterfaceAccount<'info, Mint>>,
+    token_program: AccountInfo<'info>,
+    remaining_ais: &[AccountInfo<'info>],
+) -> AstrolendResult {
+    assert!(
+        insurance_fee >= I80F48::ZERO,
+        "Insurance fund fee cannot be negative"
+    );
+
+    let (insurance_fee_to_transfer, insurance_fee_dust) = if liab_bank.is_insurance_disabled() {
+        (0, insurance_fee)
+    } else {
+        (
+            insurance_fee
+                .checked_to_num::<u64>()
+                .ok_or(AstrolendError::MathError)?,
+            insurance_fee.frac(),
+        )
+    };
+
+    if insurance_fee_to_transfer > 0 {
+        let liquidity_authority_bump = liab_bank.liquidity_vault_authority_bump;
+
+        liab_bank.withdraw_spl_transfer(
+            insurance_fee_to_transfer,
+            liquidity_vault,
+            insurance_vault,
+            liquidity_vault_authority,
+            maybe_liab_bank_mint,
+            token_program,
+            bank_signer!(
+                BankVaultType::Liquidity,
+                liab_bank_pk,
+                liquidity_authority_bump
+            ),
+            remaining_ais,
+        )?;
+    }
+
+    liab_bank.add_insurance_fees_outstanding(insurance_fee_dust)
+}
+
+/// Record a liquidation suffered by `liquidatee`, and the liability it repaid in its credit
+/// history. Returns the index of the record, see [AstrolendAccount::record_liquidation].
+pub(super) fn record_liquidation(
+    liquidatee: &mut AstrolendAccount,
+    credit_history: Option<&mut CreditHistory>,
+    record: LiquidationRecord,
+) -> Option<usize> {
+    let index = liquidatee.record_liquidation(record);
+
+    if let Some(credit_history) = credit_history {
+        credit_history.record_repay(
+            &liquidatee.lending_account,
+            &record.liability_bank,
+            record.liability_amount,
+        );
+        credit_history.sync(&liquidatee.lending_account);
+    }
+
+    index
+}
+
+/// USD value of each part of the liquidation discount
+struct LiquidationFees {
+    liquidator_bonus: I80F48,
+    insurance_fee: I80F48,
+    protocol_fee: I80F48,
+    /// The insurance fee in native liability tokens
+    insurance_fee_amount: I80F48,
+}
+
 #[derive(Accounts)]
 pub struct LendingAccountLiquidate<'info> {
     pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
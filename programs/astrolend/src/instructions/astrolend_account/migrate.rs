@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    account_event_meta,
+    events::{AccountEventHeader, LendingAccountMigrateEvent},
+    prelude::*,
+    state::astrolend_account::{load_mut_with_extension, AstrolendAccount, ASTROLEND_ACCOUNT_SPACE},
+};
+
+/// Permissionlessly bring a legacy account to the current layout version, zeroing the fields
+/// added since it was created. Features stored in those fields no-op on legacy accounts until
+/// then, see [AstrolendAccount::migrate].
+///
+/// The account is grown to hold its [CreditHistory] first, the signer pays the rent of the
+/// added space. Other instructions load legacy accounts as they are until then, and record no
+/// credit history.
+///
+/// [CreditHistory]: crate::state::astrolend_account::CreditHistory
+pub fn lending_account_migrate(ctx: Context<LendingAccountMigrate>) -> AstrolendResult {
+    let (mut astrolend_account, credit_history) =
+        load_mut_with_extension(&ctx.accounts.astrolend_account)?;
+    let mut credit_history = credit_history.ok_or(AstrolendError::AccountNotMigrated)?;
+
+    astrolend_account.migrate(&mut credit_history)?;
+
+    emit!(LendingAccountMigrateEvent {
+        header: AccountEventHeader {
+            signer: Some(ctx.accounts.signer.key()),
+            astrolend_account: ctx.accounts.astrolend_account.key(),
+            astrolend_account_authority: astrolend_account.authority,
+            astrolend_group: astrolend_account.group,
+        },
+        version: astrolend_account.version,
+        meta: account_event_meta!(LendingAccountMigrate, astrolend_account.account_tag),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountMigrate<'info> {
+    #[account(
+        mut,
+        realloc = ASTROLEND_ACCOUNT_SPACE,
+        realloc::payer = signer,
+        realloc::zero = false,
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
@@ -1,23 +1,39 @@
+mod balance_change;
 mod borrow;
 mod close;
 mod close_balance;
+mod configure;
 mod deposit;
 mod emissions;
+mod flag_unprofitable;
 mod flashloan;
 mod initialize;
 mod liquidate;
+mod migrate;
+mod native_sol;
+mod redeem_receipts;
 mod repay;
+mod self_liquidate;
 mod transfer_authority;
+mod transfer_position;
 mod withdraw;
 
+pub use balance_change::*;
 pub use borrow::*;
 pub use close::*;
 pub use close_balance::*;
+pub use configure::*;
 pub use deposit::*;
 pub use emissions::*;
+pub use flag_unprofitable::*;
 pub use flashloan::*;
 pub use initialize::*;
 pub use liquidate::*;
+pub use migrate::*;
+pub use native_sol::*;
+pub use redeem_receipts::*;
 pub use repay::*;
+pub use self_liquidate::*;
 pub use transfer_authority::*;
+pub use transfer_position::*;
 pub use withdraw::*;
@@ -0,0 +1,358 @@
+use super::balance_change::{
+    execute_balance_change, health_snapshot_event, BalanceChange, BalanceChangeAccounts,
+    BalanceChangeOptions,
+};
+use crate::{
+    account_event_meta, check,
+    constants::{
+        LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED, NATIVE_TOKEN_ACCOUNT_SEED,
+        WHITELIST_SEED,
+    },
+    events::{AccountEventHeader, LendingAccountDepositEvent, LendingAccountWithdrawEvent},
+    instruction_discriminator,
+    prelude::*,
+    state::{astrolend_account::AstrolendAccount, astrolend_group::Bank},
+};
+use anchor_lang::{
+    prelude::*,
+    system_program::{self, Transfer},
+};
+use anchor_spl::{
+    token::spl_token::native_mint,
+    token_interface::{
+        close_account, sync_native, CloseAccount, Mint, SyncNative, TokenAccount, TokenInterface,
+    },
+};
+
+/// Deposit `amount` lamports of the signer into a bank of the native mint, without holding
+/// wSOL beforehand.
+///
+/// 1. Create an ephemeral wSOL token account of the signer, its rent paid by the signer
+/// 2. Wrap `amount` lamports into it with a system transfer and `sync_native`
+/// 3. Deposit them, see `lending_account_deposit`
+/// 4. Close the ephemeral account, its rent goes back to the signer
+///
+/// Signer only pays `amount`. Deposits on behalf of other accounts and receipts are not
+/// supported, remaining accounts are the same as a plain deposit.
+pub fn lending_account_deposit_native<'info>(
+    ctx: Context<'_, '_, 'info, 'info, LendingAccountDepositNative<'info>>,
+    amount: u64,
+) -> AstrolendResult {
+    let LendingAccountDepositNative {
+        astrolend_group,
+        astrolend_account: astrolend_account_loader,
+        signer,
+        bank,
+        native_mint: _,
+        native_token_account,
+        bank_liquidity_vault,
+        token_program,
+        system_program,
+        whitelist_entry,
+    } = ctx.accounts;
+
+    check!(
+        bank.load()?.mint == native_mint::ID,
+        AstrolendError::NativeMintRequired
+    );
+
+    system_program::transfer(
+        CpiContext::new(
+            system_program.to_account_info(),
+            Transfer {
+                from: signer.to_account_info(),
+                to: native_token_account.to_account_info(),
+            },
+        ),
+        amount,
+    )?;
+    sync_native(CpiContext::new(
+        token_program.to_account_info(),
+        SyncNative {
+            account: native_token_account.to_account_info(),
+        },
+    ))?;
+
+    let outcome = execute_balance_change(
+        BalanceChange::deposit(amount),
+        BalanceChangeAccounts {
+            astrolend_group,
+            astrolend_account: astrolend_account_loader,
+            signer,
+            bank,
+            user_token_account: native_token_account.to_account_info(),
+            bank_liquidity_vault: bank_liquidity_vault.to_account_info(),
+            bank_liquidity_vault_authority: None,
+            token_program,
+            whitelist_entry: whitelist_entry.as_ref(),
+        },
+        BalanceChangeOptions::default(),
+        instruction_discriminator!(LendingAccountDepositNative),
+        ctx.remaining_accounts,
+    )?;
+
+    close_native_token_account(native_token_account, signer, token_program)?;
+
+    let astrolend_account = astrolend_account_loader.load()?;
+    emit!(LendingAccountDepositEvent {
+        header: AccountEventHeader {
+            signer: Some(signer.key()),
+            astrolend_account: astrolend_account_loader.key(),
+            astrolend_account_authority: astrolend_account.authority,
+            astrolend_group: astrolend_account.group,
+        },
+        bank: bank.key(),
+        mint: outcome.mint,
+        amount: outcome.amount,
+        meta: account_event_meta!(LendingAccountDepositNative, astrolend_account.account_tag),
+    });
+
+    Ok(())
+}
+
+/// Withdraw from a bank of the native mint as lamports of the signer.
+///
+/// 1. Create an ephemeral wSOL token account of the signer, its rent paid by the signer
+/// 2. Withdraw into it, see `lending_account_withdraw`
+/// 3. Close the ephemeral account, the signer receives the withdrawn lamports and the rent
+///
+/// The signer must be the account authority, the lamports end up in its wallet. Restricted
+/// destinations apply the same as a plain withdraw to its associated token account, see
+/// `RESTRICTED_DESTINATIONS_FLAG`.
+///
+/// Remaining accounts are the same as a plain withdraw.
+pub fn lending_account_withdraw_native<'info>(
+    ctx: Context<'_, '_, 'info, 'info, LendingAccountWithdrawNative<'info>>,
+    amount: u64,
+    withdraw_all: Option<bool>,
+) -> AstrolendResult {
+    let LendingAccountWithdrawNative {
+        astrolend_group,
+        astrolend_account: astrolend_account_loader,
+        signer,
+        bank,
+        native_mint: _,
+        native_token_account,
+        bank_liquidity_vault_authority,
+        bank_liquidity_vault,
+        token_program,
+        system_program: _,
+    } = ctx.accounts;
+
+    check!(
+        bank.load()?.mint == native_mint::ID,
+        AstrolendError::NativeMintRequired
+    );
+    {
+        let astrolend_account = astrolend_account_loader.load()?;
+        astrolend_account.check_destination_allowed(&signer.key(), &astrolend_account.authority)?;
+    }
+
+    let change = BalanceChange::withdraw(amount, withdraw_all.unwrap_or(false));
+    let outcome = execute_balance_change(
+        change,
+        BalanceChangeAccounts {
+            astrolend_group,
+            astrolend_account: astrolend_account_loader,
+            signer,
+            bank,
+            user_token_account: native_token_account.to_account_info(),
+            bank_liquidity_vault: bank_liquidity_vault.to_account_info(),
+            bank_liquidity_vault_authority: Some(
+                bank_liquidity_vault_authority.to_account_info(),
+            ),
+            token_program,
+            whitelist_entry: None,
+        },
+        BalanceChangeOptions::default(),
+        instruction_discriminator!(LendingAccountWithdrawNative),
+        ctx.remaining_accounts,
+    )?;
+
+    close_native_token_account(native_token_account, signer, token_program)?;
+
+    let astrolend_account = astrolend_account_loader.load()?;
+    let header = || AccountEventHeader {
+        signer: Some(signer.key()),
+        astrolend_account: astrolend_account_loader.key(),
+        astrolend_account_authority: astrolend_account.authority,
+        astrolend_group: astrolend_account.group,
+    };
+
+    emit!(LendingAccountWithdrawEvent {
+        header: header(),
+        bank: bank.key(),
+        mint: outcome.mint,
+        amount: outcome.amount,
+        close_balance: change.amount_mode.is_all(),
+        amount_net: outcome.amount_net,
+        amount_requested: outcome.amount_requested,
+        amount_pre_fee: outcome.amount,
+        fee: outcome.amount - outcome.amount_requested,
+        fee_waived: outcome.fee_waived,
+        meta: account_event_meta!(LendingAccountWithdrawNative, astrolend_account.account_tag),
+    });
+
+    if let Some(health) = outcome.health_snapshot {
+        emit!(health_snapshot_event(
+            header(),
+            health,
+            account_event_meta!(LendingAccountWithdrawNative, astrolend_account.account_tag)
+        ));
+    }
+
+    Ok(())
+}
+
+/// Native accounts close with a balance, whatever wSOL is left is unwrapped along with the rent.
+fn close_native_token_account<'info>(
+    native_token_account: &InterfaceAccount<'info, TokenAccount>,
+    signer: &Signer<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> AstrolendResult {
+    close_account(CpiContext::new(
+        token_program.to_account_info(),
+        CloseAccount {
+            account: native_token_account.to_account_info(),
+            destination: signer.to_account_info(),
+            authority: signer.to_account_info(),
+        },
+    ))?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountDepositNative<'info> {
+    /// Only written to track the TVL of groups with a `group_tvl_cap_usd`.
+    #[account(mut)]
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = astrolend_account.load()?.group == astrolend_group.key(),
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        mut,
+        address = astrolend_account.load()?.authority,
+    )]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(address = native_mint::ID)]
+    pub native_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// Ephemeral, closed by the end of the instruction
+    #[account(
+        init,
+        payer = signer,
+        token::mint = native_mint,
+        token::authority = signer,
+        seeds = [
+            NATIVE_TOKEN_ACCOUNT_SEED.as_bytes(),
+            astrolend_account.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub native_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Seed constraint check
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.liquidity_vault_bump,
+    )]
+    pub bank_liquidity_vault: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: Seed constraint check, only read when the group requires a whitelist.
+    #[account(
+        seeds = [
+            WHITELIST_SEED.as_bytes(),
+            astrolend_group.key().as_ref(),
+            astrolend_account.load()?.authority.as_ref(),
+        ],
+        bump,
+    )]
+    pub whitelist_entry: Option<AccountInfo<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountWithdrawNative<'info> {
+    /// Only written to track the TVL of groups with a `group_tvl_cap_usd`.
+    #[account(mut)]
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = astrolend_account.load()?.group == astrolend_group.key(),
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        mut,
+        address = astrolend_account.load()?.authority,
+    )]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(address = native_mint::ID)]
+    pub native_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// Ephemeral, closed by the end of the instruction
+    #[account(
+        init,
+        payer = signer,
+        token::mint = native_mint,
+        token::authority = signer,
+        seeds = [
+            NATIVE_TOKEN_ACCOUNT_SEED.as_bytes(),
+            astrolend_account.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub native_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Seed constraint check
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.liquidity_vault_authority_bump,
+    )]
+    pub bank_liquidity_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.liquidity_vault_bump,
+    )]
+    pub bank_liquidity_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    pub system_program: Program<'info, System>,
+}
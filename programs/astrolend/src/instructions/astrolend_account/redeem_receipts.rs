@@ -0,0 +1,175 @@
+use crate::{
+    account_event_meta, check,
+    constants::WHITELIST_SEED,
+    events::{AccountEventHeader, LendingAccountReceiptEvent},
+    prelude::*,
+    state::{
+        astrolend_account::{
+            check_operation_allowed, AstrolendAccount, BankAccountWrapper, BankOperation,
+            THIRD_PARTY_DEPOSIT_ALLOWED_FLAG,
+        },
+        astrolend_group::Bank,
+    },
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{burn, Burn, TokenInterface};
+use fixed::types::I80F48;
+use solana_program::{clock::Clock, sysvar::Sysvar};
+
+/// Burn `receipts` deposit receipts of the signer and credit the asset shares they stand for,
+/// one share per receipt, no tokens are transferred.
+///
+/// 1. Accrue interest
+/// 2. Burn the receipts from the signer's receipt token account
+/// 3. Credit the asset shares to the account, creating its bank account if it does not exist yet
+///
+/// Any wallet holding receipts can redeem them into its own accounts, or into accounts whose
+/// authority opted in with `THIRD_PARTY_DEPOSIT_ALLOWED_FLAG`.
+/// Will error if there is an existing liability in the bank.
+/// Will error if the account has an isolated liability and no balance in the bank yet, the
+/// banks of its liabilities are expected in the remaining accounts.
+/// Will error if the group requires a whitelist and the account authority isn't whitelisted.
+/// Will error in the slot a bankruptcy was handled for the bank.
+pub fn lending_account_redeem_receipts<'info>(
+    ctx: Context<'_, '_, 'info, 'info, LendingAccountRedeemReceipts<'info>>,
+    receipts: u64,
+) -> AstrolendResult {
+    let LendingAccountRedeemReceipts {
+        astrolend_group: astrolend_group_loader,
+        astrolend_account: astrolend_account_loader,
+        signer,
+        bank: bank_loader,
+        receipt_mint,
+        signer_receipt_account,
+        token_program,
+        whitelist_entry,
+    } = ctx.accounts;
+    let clock = Clock::get()?;
+    let bank_pk = bank_loader.key();
+
+    {
+        let bank = bank_loader.load()?;
+        check!(
+            receipt_mint.key() == bank.get_receipt_mint(&bank_pk)?,
+            AstrolendError::InvalidReceiptAccounts
+        );
+        bank.check_token_program(token_program.key, receipt_mint.owner)?;
+        bank.check_bankruptcy_freeze(clock.slot)?;
+    }
+
+    let mut astrolend_account = astrolend_account_loader.load_mut()?;
+
+    check_operation_allowed(
+        BankOperation::RedeemReceipts,
+        &*bank_loader.load()?,
+        &astrolend_account,
+    )?;
+    bank_loader.load_mut()?.enter_reentrancy_guard(clock.slot)?;
+
+    if signer.key() != astrolend_account.authority {
+        check!(
+            astrolend_account.get_flag(THIRD_PARTY_DEPOSIT_ALLOWED_FLAG),
+            AstrolendError::ThirdPartyDepositNotAllowed
+        );
+    }
+
+    astrolend_group_loader.load()?.check_wallet_whitelisted(
+        &astrolend_group_loader.key(),
+        &astrolend_account.authority,
+        whitelist_entry.as_ref(),
+    )?;
+
+    astrolend_account
+        .lending_account
+        .check_deposit_allowed(&bank_pk, ctx.remaining_accounts)?;
+
+    bank_loader.load_mut()?.accrue_interest(
+        clock.unix_timestamp,
+        &*astrolend_group_loader.load()?,
+        #[cfg(not(feature = "client"))]
+        bank_pk,
+        #[cfg(not(feature = "client"))]
+        crate::instruction_discriminator!(LendingAccountRedeemReceipts),
+    )?;
+
+    burn(
+        CpiContext::new(
+            token_program.to_account_info(),
+            Burn {
+                mint: receipt_mint.to_account_info(),
+                from: signer_receipt_account.to_account_info(),
+                authority: signer.to_account_info(),
+            },
+        ),
+        receipts,
+    )?;
+
+    let mut bank = bank_loader.load_mut()?;
+    let shares = I80F48::from_num(receipts);
+
+    BankAccountWrapper::find_or_create(
+        &bank_pk,
+        &mut bank,
+        &mut astrolend_account.lending_account,
+    )?
+    .transfer_asset_shares_in(shares)?;
+    bank.exit_reentrancy_guard();
+
+    emit!(LendingAccountReceiptEvent {
+        header: AccountEventHeader {
+            signer: Some(signer.key()),
+            astrolend_account: astrolend_account_loader.key(),
+            astrolend_account_authority: astrolend_account.authority,
+            astrolend_group: astrolend_account.group,
+        },
+        bank: bank_pk,
+        receipt_mint: receipt_mint.key(),
+        receipts,
+        amount: bank.get_asset_amount(shares)?.to_num::<f64>(),
+        redeem: true,
+        meta: account_event_meta!(LendingAccountRedeemReceipts, astrolend_account.account_tag),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountRedeemReceipts<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = astrolend_account.load()?.group == astrolend_group.key(),
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    /// Owner of the receipts
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    /// CHECK: Checked against the bank receipt mint
+    #[account(mut)]
+    pub receipt_mint: AccountInfo<'info>,
+
+    /// CHECK: Token mint/authority are checked at burn
+    #[account(mut)]
+    pub signer_receipt_account: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    /// CHECK: Seed constraint check, only read when the group requires a whitelist.
+    #[account(
+        seeds = [
+            WHITELIST_SEED.as_bytes(),
+            astrolend_group.key().as_ref(),
+            astrolend_account.load()?.authority.as_ref(),
+        ],
+        bump,
+    )]
+    pub whitelist_entry: Option<AccountInfo<'info>>,
+}
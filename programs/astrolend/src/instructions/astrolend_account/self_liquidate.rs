@@ -0,0 +1,464 @@
+use super::balance_change::check_outflow_destination;
+use super::liquidate::{
+    calc_liab_amount, load_liquidation_prices, pay_insurance_fee, record_liquidation,
+};
+use crate::constants::{INSURANCE_VAULT_SEED, SELF_LIQUIDATION_INSURANCE_FEE};
+use crate::events::{AccountEventHeader, LendingAccountSelfLiquidateEvent};
+use crate::state::astrolend_account::{
+    calc_value, check_operation_allowed, load_mut_with_credit_history, BankOperation,
+    LiquidationRecord, RiskEngine,
+};
+use crate::state::astrolend_group::{Bank, BankVaultType};
+use crate::{
+    account_event_meta, bank_signer,
+    constants::{LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED},
+    state::astrolend_account::{AstrolendAccount, BankAccountWrapper},
+};
+use crate::{check, debug, prelude::*, utils};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{TokenAccount, TokenInterface};
+use fixed::types::I80F48;
+use solana_program::clock::Clock;
+use solana_program::sysvar::Sysvar;
+
+/// Instruction lets the authority of an unhealthy astrol account deleverage it before third party
+/// liquidators do, at a reduced penalty.
+///
+/// The authority pays the liability from a token account and receives `q_a` of the collateral.
+/// The liability is valued with the same prices and biases as in `lending_account_liquidate`,
+/// but there is no liquidator bonus and no protocol fee, the only discount is `f_s`
+/// (`SELF_LIQUIDATION_INSURANCE_FEE`) which goes to the insurance fund.
+///
+/// Calculations:
+///
+/// `q_lp = q_a * p_a / p_l` paid by the authority
+/// `q_lf = q_a * p_a * (1 - f_s) / p_l` repaid on the account
+///
+/// The insurance fund receives `q_lp - q_lf`.
+///
+/// The account must be below maintenance health, and the same post liquidation checks as normal
+/// liquidations apply, so a self liquidation can't bring the account back above maintenance
+/// and can't be used to unwind a healthy position without the init health check.
+///
+/// Both banks must use the same token program. The collateral goes to `destination_token_account`
+/// under the same restrictions as a withdraw, see `RESTRICTED_DESTINATIONS_FLAG`. Like a
+/// withdraw, it waits out the bankruptcy freeze of the asset bank and leaves the group TVL.
+///
+/// Expected remaining account schema
+/// [
+///    asset_mint_ai (if token2022 mint),
+///    liab_mint_ai (if token2022 mint),
+///    asset_oracle_ai,
+///    liab_oracle_ai,
+///    observation_ais...,
+///    emissions_observation_ais... (optional, see `EMISSIONS_COLLATERAL_FLAG`),
+///  ]
+pub fn lending_account_self_liquidate<'info>(
+    mut ctx: Context<'_, '_, 'info, 'info, LendingAccountSelfLiquidate<'info>>,
+    asset_amount: u64,
+) -> AstrolendResult {
+    check!(
+        asset_amount > 0,
+        AstrolendError::IllegalLiquidation,
+        "Asset amount must be positive"
+    );
+
+    check!(
+        ctx.accounts.asset_bank.key() != ctx.accounts.liab_bank.key(),
+        AstrolendError::IllegalLiquidation,
+        "Asset and liability bank cannot be the same"
+    );
+
+    let astrolend_account_loader = &ctx.accounts.astrolend_account;
+    let (mut astrolend_account, mut credit_history) =
+        load_mut_with_credit_history(astrolend_account_loader)?;
+    let clock = Clock::get()?;
+    let current_timestamp = clock.unix_timestamp;
+
+    {
+        check_operation_allowed(
+            BankOperation::LiquidateSeize,
+            &*ctx.accounts.asset_bank.load()?,
+            &astrolend_account,
+        )?;
+        check_operation_allowed(
+            BankOperation::LiquidateRepay,
+            &*ctx.accounts.liab_bank.load()?,
+            &astrolend_account,
+        )?;
+        check_outflow_destination(
+            &astrolend_account,
+            &*ctx.accounts.asset_bank.load()?,
+            &ctx.accounts.destination_token_account,
+            ctx.accounts.token_program.key,
+        )?;
+        ctx.accounts
+            .asset_bank
+            .load()?
+            .check_bankruptcy_freeze(clock.slot)?;
+
+        ctx.accounts
+            .asset_bank
+            .load_mut()?
+            .enter_reentrancy_guard(clock.slot)?;
+        ctx.accounts
+            .liab_bank
+            .load_mut()?
+            .enter_reentrancy_guard(clock.slot)?;
+    }
+
+    let maybe_asset_bank_mint = utils::maybe_take_bank_mint(
+        &mut ctx.remaining_accounts,
+        &*ctx.accounts.asset_bank.load()?,
+        ctx.accounts.token_program.key,
+        ctx.accounts.asset_liquidity_vault.owner,
+    )?;
+    let maybe_liab_bank_mint = utils::maybe_take_bank_mint(
+        &mut ctx.remaining_accounts,
+        &*ctx.accounts.liab_bank.load()?,
+        ctx.accounts.token_program.key,
+        ctx.accounts.liab_liquidity_vault.owner,
+    )?;
+    {
+        let astrolend_group = ctx.accounts.astrolend_group.load()?;
+
+        ctx.accounts.asset_bank.load_mut()?.accrue_interest(
+            current_timestamp,
+            &astrolend_group,
+            #[cfg(not(feature = "client"))]
+            ctx.accounts.asset_bank.key(),
+            #[cfg(not(feature = "client"))]
+            crate::instruction_discriminator!(LendingAccountSelfLiquidate),
+        )?;
+        ctx.accounts.liab_bank.load_mut()?.accrue_interest(
+            current_timestamp,
+            &astrolend_group,
+            #[cfg(not(feature = "client"))]
+            ctx.accounts.liab_bank.key(),
+            #[cfg(not(feature = "client"))]
+            crate::instruction_discriminator!(LendingAccountSelfLiquidate),
+        )?;
+    }
+
+    let observation_accounts_starting_pos = 2;
+    let pre_liquidation_health = RiskEngine::new(
+        &*ctx.accounts.astrolend_group.load()?,
+        &astrolend_account,
+        &ctx.remaining_accounts[observation_accounts_starting_pos..],
+    )?
+    .check_pre_liquidation_condition_and_get_account_health(&ctx.accounts.liab_bank.key())?;
+
+    // ##Accounting changes##
+
+    let (liquidation_record, liab_amount_paid, liab_amount_final, insurance_fee) = {
+        let asset_amount = I80F48::from_num(asset_amount);
+
+        let mut asset_bank = ctx.accounts.asset_bank.load_mut()?;
+        let mut liab_bank = ctx.accounts.liab_bank.load_mut()?;
+        let (asset_price, liab_price) = load_liquidation_prices(
+            &mut asset_bank,
+            &mut liab_bank,
+            &ctx.remaining_accounts[0..2],
+            &clock,
+        )?;
+
+        let final_discount = I80F48::ONE - SELF_LIQUIDATION_INSURANCE_FEE;
+
+        // Quantity of liability paid by the authority, rounded up to whole tokens
+        let liab_amount_paid = calc_liab_amount(
+            asset_amount,
+            asset_price,
+            &asset_bank,
+            liab_price,
+            &liab_bank,
+            None,
+        )?
+        .checked_ceil()
+        .ok_or(AstrolendError::MathError)?;
+
+        // Quantity of liability repaid on the account
+        let liab_amount_final = calc_liab_amount(
+            asset_amount,
+            asset_price,
+            &asset_bank,
+            liab_price,
+            &liab_bank,
+            Some(final_discount),
+        )?;
+
+        let insurance_fund_fee = liab_amount_paid - liab_amount_final;
+
+        debug!(
+            "liab_amount_paid: {}, liab_q_final: {}, asset_amount: {}, insurance_fund_fee: {}",
+            liab_amount_paid,
+            liab_amount_final,
+            asset_amount,
+            insurance_fund_fee
+        );
+
+        let insurance_fee = calc_value(
+            insurance_fund_fee,
+            liab_price,
+            liab_bank.mint_decimals,
+            None,
+        )?;
+
+        let liab_amount_paid = liab_amount_paid
+            .checked_to_num::<u64>()
+            .ok_or(AstrolendError::MathError)?;
+
+        // Account gives up `asset_amount` of collateral to the authority
+        {
+            let asset_liquidity_authority_bump = asset_bank.liquidity_vault_authority_bump;
+
+            let mut bank_account = BankAccountWrapper::find(
+                &ctx.accounts.asset_bank.key(),
+                &mut asset_bank,
+                &mut astrolend_account.lending_account,
+            )?;
+
+            bank_account
+                .withdraw(asset_amount)
+                .map_err(|_| AstrolendError::IllegalLiquidation)?;
+
+            bank_account.withdraw_spl_transfer(
+                asset_amount.to_num::<u64>(),
+                ctx.accounts.asset_liquidity_vault.to_account_info(),
+                ctx.accounts.destination_token_account.to_account_info(),
+                ctx.accounts
+                    .asset_liquidity_vault_authority
+                    .to_account_info(),
+                maybe_asset_bank_mint.as_ref(),
+                ctx.accounts.token_program.to_account_info(),
+                bank_signer!(
+                    BankVaultType::Liquidity,
+                    ctx.accounts.asset_bank.key(),
+                    asset_liquidity_authority_bump
+                ),
+                ctx.remaining_accounts,
+            )?;
+        }
+
+        // Authority pays off the liability
+        {
+            let mut bank_account = BankAccountWrapper::find_or_create(
+                &ctx.accounts.liab_bank.key(),
+                &mut liab_bank,
+                &mut astrolend_account.lending_account,
+            )?;
+
+            bank_account.increase_balance(liab_amount_final)?;
+
+            let liab_amount_paid_pre_fee = maybe_liab_bank_mint
+                .as_ref()
+                .map(|mint| {
+                    utils::calculate_pre_fee_spl_deposit_amount(
+                        mint.to_account_info(),
+                        liab_amount_paid,
+                        clock.epoch,
+                    )
+                })
+                .transpose()?
+                .unwrap_or(liab_amount_paid);
+
+            // ## SPL transfer ##
+            bank_account.deposit_spl_transfer(
+                liab_amount_paid_pre_fee,
+                ctx.accounts.signer_token_account.to_account_info(),
+                ctx.accounts.liab_liquidity_vault.to_account_info(),
+                ctx.accounts.signer.to_account_info(),
+                maybe_liab_bank_mint.as_ref(),
+                ctx.accounts.token_program.to_account_info(),
+                ctx.remaining_accounts,
+            )?;
+        }
+
+        // Insurance fund receives fee
+        pay_insurance_fee(
+            &mut liab_bank,
+            ctx.accounts.liab_bank.key(),
+            insurance_fund_fee,
+            ctx.accounts.liab_liquidity_vault.to_account_info(),
+            ctx.accounts.liab_insurance_vault.to_account_info(),
+            ctx.accounts
+                .liab_liquidity_vault_authority
+                .to_account_info(),
+            maybe_liab_bank_mint.as_ref(),
+            ctx.accounts.token_program.to_account_info(),
+            ctx.remaining_accounts,
+        )?;
+
+        if ctx.accounts.astrolend_group.load()?.is_tvl_cap_enabled() {
+            let withdraw_value = utils::calc_tvl_value(
+                &ctx.accounts.asset_bank.key(),
+                &mut asset_bank,
+                asset_amount.to_num::<u64>(),
+                &ctx.remaining_accounts[observation_accounts_starting_pos..],
+                &clock,
+            )?;
+
+            ctx.accounts
+                .astrolend_group
+                .load_mut()?
+                .decrease_tvl(withdraw_value)?;
+        }
+
+        asset_bank.exit_reentrancy_guard();
+        liab_bank.exit_reentrancy_guard();
+
+        let liquidation_record = LiquidationRecord {
+            liquidator_account: astrolend_account_loader.key(),
+            asset_bank: ctx.accounts.asset_bank.key(),
+            liability_bank: ctx.accounts.liab_bank.key(),
+            timestamp: current_timestamp,
+            asset_amount: asset_amount
+                .checked_to_num::<u64>()
+                .ok_or(AstrolendError::MathError)?,
+            liability_amount: liab_amount_final
+                .checked_to_num::<u64>()
+                .ok_or(AstrolendError::MathError)?,
+        };
+
+        (
+            liquidation_record,
+            liab_amount_paid,
+            liab_amount_final,
+            insurance_fee,
+        )
+    };
+
+    let liquidation_record_index = record_liquidation(
+        &mut astrolend_account,
+        credit_history.as_deref_mut(),
+        liquidation_record,
+    );
+
+    // ## Risk checks ##
+
+    let post_liquidation_health = RiskEngine::new(
+        &*ctx.accounts.astrolend_group.load()?,
+        &astrolend_account,
+        &ctx.remaining_accounts[observation_accounts_starting_pos..],
+    )?
+    .check_post_liquidation_condition_and_get_account_health(
+        &ctx.accounts.liab_bank.key(),
+        pre_liquidation_health,
+    )?;
+
+    emit!(LendingAccountSelfLiquidateEvent {
+        header: AccountEventHeader {
+            signer: Some(ctx.accounts.signer.key()),
+            astrolend_account: astrolend_account_loader.key(),
+            astrolend_account_authority: astrolend_account.authority,
+            astrolend_group: ctx.accounts.astrolend_group.key(),
+        },
+        asset_bank: ctx.accounts.asset_bank.key(),
+        asset_mint: ctx.accounts.asset_bank.load()?.mint,
+        liability_bank: ctx.accounts.liab_bank.key(),
+        liability_mint: ctx.accounts.liab_bank.load()?.mint,
+        pre_health: pre_liquidation_health.to_num::<f64>(),
+        post_health: post_liquidation_health.to_num::<f64>(),
+        asset_amount,
+        liability_amount_paid: liab_amount_paid,
+        liability_amount_repaid: liab_amount_final.to_num::<f64>(),
+        liquidation_record_index: liquidation_record_index.map(|index| index as u8),
+        insurance_fee: insurance_fee.to_num::<f64>(),
+        meta: account_event_meta!(LendingAccountSelfLiquidate, astrolend_account.account_tag),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountSelfLiquidate<'info> {
+    /// Only written to track the TVL of groups with a `group_tvl_cap_usd`.
+    #[account(mut)]
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = astrolend_account.load()?.group == astrolend_group.key()
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        address = astrolend_account.load()?.authority
+    )]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = asset_bank.load()?.group == astrolend_group.key()
+    )]
+    pub asset_bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        mut,
+        constraint = liab_bank.load()?.group == astrolend_group.key()
+    )]
+    pub liab_bank: AccountLoader<'info, Bank>,
+
+    /// CHECK: Token mint/authority are checked at transfer
+    #[account(mut)]
+    pub signer_token_account: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub destination_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_AUTHORITY_SEED.as_bytes(),
+            asset_bank.key().as_ref(),
+        ],
+        bump = asset_bank.load()?.liquidity_vault_authority_bump
+    )]
+    pub asset_liquidity_vault_authority: AccountInfo<'info>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            asset_bank.key().as_ref(),
+        ],
+        bump = asset_bank.load()?.liquidity_vault_bump
+    )]
+    pub asset_liquidity_vault: AccountInfo<'info>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_AUTHORITY_SEED.as_bytes(),
+            liab_bank.key().as_ref(),
+        ],
+        bump = liab_bank.load()?.liquidity_vault_authority_bump
+    )]
+    pub liab_liquidity_vault_authority: AccountInfo<'info>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            liab_bank.key().as_ref(),
+        ],
+        bump = liab_bank.load()?.liquidity_vault_bump
+    )]
+    pub liab_liquidity_vault: AccountInfo<'info>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        mut,
+        seeds = [
+            INSURANCE_VAULT_SEED.as_bytes(),
+            liab_bank.key().as_ref(),
+        ],
+        bump = liab_bank.load()?.insurance_vault_bump
+    )]
+    pub liab_insurance_vault: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
@@ -0,0 +1,150 @@
+use crate::{
+    account_event_meta, check,
+    events::{AccountEventHeader, LendingAccountTransferPositionEvent},
+    prelude::*,
+    state::{
+        astrolend_account::{
+            check_operation_allowed, AstrolendAccount, BankAccountWrapper, BankOperation,
+            HealthCheckContext, RiskEngine,
+        },
+        astrolend_group::{Bank, WrappedI80F48},
+    },
+};
+use anchor_lang::prelude::*;
+use fixed::types::I80F48;
+use solana_program::{clock::Clock, sysvar::Sysvar};
+
+/// Move `shares` of an asset position to another account of the same authority,
+/// no tokens are transferred.
+///
+/// 1. Accrue interest
+/// 2. Debit the asset shares from the source balance, a remainder below the empty balance
+///    threshold moves along and the source balance is closed
+/// 3. Credit the asset shares to the destination balance, creating it if it does not exist yet
+/// 4. Verify that the source account is in a healthy state
+///
+/// Liabilities can't be transferred.
+/// The remaining accounts are the observations of the source account after the transfer,
+/// followed by the banks of the destination liabilities if the destination has no balance in
+/// the bank yet.
+pub fn lending_account_transfer_position<'info>(
+    ctx: Context<'_, '_, 'info, 'info, LendingAccountTransferPosition<'info>>,
+    shares: WrappedI80F48,
+) -> AstrolendResult {
+    let LendingAccountTransferPosition {
+        astrolend_group: astrolend_group_loader,
+        source_astrolend_account: source_astrolend_account_loader,
+        destination_astrolend_account: destination_astrolend_account_loader,
+        signer,
+        asset_bank: bank_loader,
+    } = ctx.accounts;
+    let clock = Clock::get()?;
+
+    check!(
+        source_astrolend_account_loader.key() != destination_astrolend_account_loader.key(),
+        AstrolendError::IllegalPositionTransfer
+    );
+
+    let mut source_astrolend_account = source_astrolend_account_loader.load_mut()?;
+    let mut destination_astrolend_account = destination_astrolend_account_loader.load_mut()?;
+
+    check!(
+        destination_astrolend_account.authority == source_astrolend_account.authority,
+        AstrolendError::Unauthorized
+    );
+
+    for astrolend_account in [&source_astrolend_account, &destination_astrolend_account] {
+        check_operation_allowed(
+            BankOperation::TransferPosition,
+            &*bank_loader.load()?,
+            astrolend_account,
+        )?;
+    }
+
+    destination_astrolend_account
+        .lending_account
+        .check_deposit_allowed(&bank_loader.key(), ctx.remaining_accounts)?;
+
+    bank_loader.load_mut()?.accrue_interest(
+        clock.unix_timestamp,
+        &*astrolend_group_loader.load()?,
+        #[cfg(not(feature = "client"))]
+        bank_loader.key(),
+        #[cfg(not(feature = "client"))]
+        crate::instruction_discriminator!(LendingAccountTransferPosition),
+    )?;
+
+    {
+        let mut bank = bank_loader.load_mut()?;
+
+        let shares = BankAccountWrapper::find(
+            &bank_loader.key(),
+            &mut bank,
+            &mut source_astrolend_account.lending_account,
+        )?
+        .transfer_asset_shares_out(I80F48::from(shares))?;
+
+        BankAccountWrapper::find_or_create(
+            &bank_loader.key(),
+            &mut bank,
+            &mut destination_astrolend_account.lending_account,
+        )?
+        .transfer_asset_shares_in(shares)?;
+
+        emit!(LendingAccountTransferPositionEvent {
+            header: AccountEventHeader {
+                signer: Some(signer.key()),
+                astrolend_account: source_astrolend_account_loader.key(),
+                astrolend_account_authority: source_astrolend_account.authority,
+                astrolend_group: source_astrolend_account.group,
+            },
+            bank: bank_loader.key(),
+            mint: bank.mint,
+            destination_astrolend_account: destination_astrolend_account_loader.key(),
+            asset_shares: shares.to_num::<f64>(),
+            amount: bank.get_asset_amount(shares)?.to_num::<f64>(),
+            meta: account_event_meta!(
+                LendingAccountTransferPosition,
+                source_astrolend_account.account_tag
+            ),
+        });
+    }
+
+    // The source account might be collateralizing borrows
+    RiskEngine::check_post_action(
+        &*astrolend_group_loader.load()?,
+        &source_astrolend_account,
+        ctx.remaining_accounts,
+        HealthCheckContext::of_account(&source_astrolend_account),
+    )?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountTransferPosition<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = source_astrolend_account.load()?.group == astrolend_group.key(),
+    )]
+    pub source_astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        mut,
+        constraint = destination_astrolend_account.load()?.group == astrolend_group.key(),
+    )]
+    pub destination_astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        address = source_astrolend_account.load()?.authority,
+    )]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = asset_bank.load()?.group == astrolend_group.key(),
+    )]
+    pub asset_bank: AccountLoader<'info, Bank>,
+}
@@ -1,131 +1,122 @@
+use super::balance_change::{
+    check_outflow_destination, execute_balance_change, health_snapshot_event, BalanceChange,
+    BalanceChangeAccounts, BalanceChangeOptions,
+};
 use crate::{
-    bank_signer, check,
+    account_event_meta,
     constants::{LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED},
     events::{AccountEventHeader, LendingAccountWithdrawEvent},
+    instruction_discriminator,
     prelude::*,
-    state::{
-        astrolend_account::{BankAccountWrapper, AstrolendAccount, RiskEngine, DISABLED_FLAG},
-        astrolend_group::{Bank, BankVaultType},
-    },
-    utils,
+    state::{astrolend_account::AstrolendAccount, astrolend_group::Bank},
 };
 use anchor_lang::prelude::*;
 use anchor_spl::token_interface::{TokenAccount, TokenInterface};
-use fixed::types::I80F48;
-use solana_program::{clock::Clock, sysvar::Sysvar};
 
 /// 1. Accrue interest
 /// 2. Find the user's existing bank account for the asset withdrawn
 /// 3. Record asset decrease in the bank account
-/// 4. Transfer funds from the bank's liquidity vault to the signer's token account
-/// 5. Verify that the user account is in a healthy state
+/// 4. Keep the bank withdraw fee in the liquidity vault, credited to the remaining depositors
+/// 5. Transfer the rest from the bank's liquidity vault to the signer's token account
+/// 6. Remove the withdrawn value from the group TVL, if the group has a TVL cap
+/// 7. Verify that the user account is in a healthy state
 ///
 /// Will error if there is no existing asset <=> borrowing is not allowed.
+/// Will error if the account restricts destinations and the destination is neither the
+/// authority's associated token account nor the approved destination.
+/// Accounts without liabilities are always healthy, their observation accounts can be omitted.
+/// With a group TVL cap the group must be passed as writable and the bank and its oracle
+/// appended to the remaining accounts.
+///
+/// `amount` is debited from the balance in full, the withdraw fee is taken out of it.
+/// With `min_amount_out`, `amount` is not grossed up for the Token-2022 transfer fee, the
+/// destination receives it net of the withdraw fee and the transfer fee of the current epoch,
+/// and the withdraw will error if that is less than `min_amount_out`.
 pub fn lending_account_withdraw<'info>(
-    mut ctx: Context<'_, '_, 'info, 'info, LendingAccountWithdraw<'info>>,
+    ctx: Context<'_, '_, 'info, 'info, LendingAccountWithdraw<'info>>,
     amount: u64,
     withdraw_all: Option<bool>,
+    min_amount_out: Option<u64>,
 ) -> AstrolendResult {
     let LendingAccountWithdraw {
+        astrolend_group,
         astrolend_account: astrolend_account_loader,
+        signer,
+        bank,
         destination_token_account,
+        bank_liquidity_vault_authority,
         bank_liquidity_vault,
         token_program,
-        bank_liquidity_vault_authority,
-        bank: bank_loader,
-        ..
     } = ctx.accounts;
-    let clock = Clock::get()?;
-
-    let withdraw_all = withdraw_all.unwrap_or(false);
-    let mut astrolend_account = astrolend_account_loader.load_mut()?;
-
-    check!(
-        !astrolend_account.get_flag(DISABLED_FLAG),
-        AstrolendError::AccountDisabled
-    );
-
-    let maybe_bank_mint = utils::maybe_take_bank_mint(
-        &mut ctx.remaining_accounts,
-        &*bank_loader.load()?,
+    check_outflow_destination(
+        &*astrolend_account_loader.load()?,
+        &*bank.load()?,
+        destination_token_account,
         token_program.key,
     )?;
 
-    bank_loader.load_mut()?.accrue_interest(
-        clock.unix_timestamp,
-        #[cfg(not(feature = "client"))]
-        bank_loader.key(),
-    )?;
-
-    {
-        let mut bank = bank_loader.load_mut()?;
-
-        let liquidity_vault_authority_bump = bank.liquidity_vault_authority_bump;
-
-        let mut bank_account = BankAccountWrapper::find(
-            &bank_loader.key(),
-            &mut bank,
-            &mut astrolend_account.lending_account,
-        )?;
-
-        let amount_pre_fee = if withdraw_all {
-            bank_account.withdraw_all()?
-        } else {
-            let amount_pre_fee = maybe_bank_mint
-                .as_ref()
-                .map(|mint| {
-                    utils::calculate_pre_fee_spl_deposit_amount(
-                        mint.to_account_info(),
-                        amount,
-                        clock.epoch,
-                    )
-                })
-                .transpose()?
-                .unwrap_or(amount);
-
-            bank_account.withdraw(I80F48::from_num(amount_pre_fee))?;
-
-            amount_pre_fee
-        };
-
-        bank_account.withdraw_spl_transfer(
-            amount_pre_fee,
-            bank_liquidity_vault.to_account_info(),
-            destination_token_account.to_account_info(),
-            bank_liquidity_vault_authority.to_account_info(),
-            maybe_bank_mint.as_ref(),
-            token_program.to_account_info(),
-            bank_signer!(
-                BankVaultType::Liquidity,
-                bank_loader.key(),
-                liquidity_vault_authority_bump
+    let change = BalanceChange::withdraw(amount, withdraw_all.unwrap_or(false));
+
+    let outcome = execute_balance_change(
+        change,
+        BalanceChangeAccounts {
+            astrolend_group,
+            astrolend_account: astrolend_account_loader,
+            signer,
+            bank,
+            user_token_account: destination_token_account.to_account_info(),
+            bank_liquidity_vault: bank_liquidity_vault.to_account_info(),
+            bank_liquidity_vault_authority: Some(
+                bank_liquidity_vault_authority.to_account_info(),
             ),
-            ctx.remaining_accounts,
-        )?;
+            token_program,
+            whitelist_entry: None,
+        },
+        BalanceChangeOptions {
+            min_amount_out,
+            ..Default::default()
+        },
+        instruction_discriminator!(LendingAccountWithdraw),
+        ctx.remaining_accounts,
+    )?;
 
-        emit!(LendingAccountWithdrawEvent {
-            header: AccountEventHeader {
-                signer: Some(ctx.accounts.signer.key()),
-                astrolend_account: astrolend_account_loader.key(),
-                astrolend_account_authority: astrolend_account.authority,
-                astrolend_group: astrolend_account.group,
-            },
-            bank: bank_loader.key(),
-            mint: bank.mint,
-            amount: amount_pre_fee,
-            close_balance: withdraw_all,
-        });
+    let astrolend_account = astrolend_account_loader.load()?;
+    let header = || AccountEventHeader {
+        signer: Some(signer.key()),
+        astrolend_account: astrolend_account_loader.key(),
+        astrolend_account_authority: astrolend_account.authority,
+        astrolend_group: astrolend_account.group,
+    };
+
+    emit!(LendingAccountWithdrawEvent {
+        header: header(),
+        bank: bank.key(),
+        mint: outcome.mint,
+        amount: outcome.amount,
+        close_balance: change.amount_mode.is_all(),
+        amount_net: outcome.amount_net,
+        amount_requested: outcome.amount_requested,
+        amount_pre_fee: outcome.amount,
+        fee: outcome.amount - outcome.amount_requested,
+        fee_waived: outcome.fee_waived,
+        meta: account_event_meta!(LendingAccountWithdraw, astrolend_account.account_tag),
+    });
+
+    if let Some(health) = outcome.health_snapshot {
+        emit!(health_snapshot_event(
+            header(),
+            health,
+            account_event_meta!(LendingAccountWithdraw, astrolend_account.account_tag)
+        ));
     }
 
-    // Check account health, if below threshold fail transaction
-    // Assuming `ctx.remaining_accounts` holds only oracle accounts
-    RiskEngine::check_account_init_health(&astrolend_account, ctx.remaining_accounts)?;
-
     Ok(())
 }
 
 #[derive(Accounts)]
 pub struct LendingAccountWithdraw<'info> {
+    /// Only written to track the TVL of groups with a `group_tvl_cap_usd`.
+    #[account(mut)]
     pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
 
     #[account(
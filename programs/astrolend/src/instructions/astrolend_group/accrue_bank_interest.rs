@@ -1,21 +1,42 @@
+use super::configure_bank::maybe_complete_weight_ramp;
 use crate::{
+    events::GroupEventHeader,
+    group_event_meta,
     state::astrolend_group::{Bank, AstrolendGroup},
     AstrolendResult,
 };
 use anchor_lang::prelude::*;
 
+/// Also completes a scheduled asset weight change that ended, see
+/// [super::lending_pool_schedule_weight_change].
+
 pub fn lending_pool_accrue_bank_interest(
     ctx: Context<LendingPoolAccrueBankInterest>,
 ) -> AstrolendResult {
     let clock = Clock::get()?;
     let mut bank = ctx.accounts.bank.load_mut()?;
+    bank.check_reentrancy_guard()?;
 
     bank.accrue_interest(
         clock.unix_timestamp,
+        &*ctx.accounts.astrolend_group.load()?,
         #[cfg(not(feature = "client"))]
         ctx.accounts.bank.key(),
+        #[cfg(not(feature = "client"))]
+        crate::instruction_discriminator!(LendingPoolAccrueBankInterest),
     )?;
 
+    maybe_complete_weight_ramp(
+        &mut bank,
+        ctx.accounts.bank.key(),
+        clock.unix_timestamp,
+        GroupEventHeader {
+            astrolend_group: ctx.accounts.astrolend_group.key(),
+            signer: None,
+        },
+        group_event_meta!(LendingPoolAccrueBankInterest),
+    );
+
     Ok(())
 }
 
@@ -1,11 +1,14 @@
 use crate::{
+    check,
     constants::{
         FEE_VAULT_AUTHORITY_SEED, FEE_VAULT_SEED, INSURANCE_VAULT_AUTHORITY_SEED,
         INSURANCE_VAULT_SEED, LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED,
+        MAX_MINT_DECIMALS, RECEIPT_MINT_SEED,
     },
     events::{GroupEventHeader, LendingPoolBankCreateEvent},
-    state::astrolend_group::{Bank, BankConfig, BankConfigCompact, AstrolendGroup},
-    AstrolendResult,
+    group_event_meta,
+    state::astrolend_group::{Bank, BankConfig, BankConfigCompact, BankVaultType, AstrolendGroup},
+    utils, AstrolendError, AstrolendResult,
 };
 use anchor_lang::prelude::*;
 use anchor_spl::token_interface::*;
@@ -14,25 +17,66 @@ use anchor_spl::token_interface::*;
 ///
 /// Admin only
 ///
+/// Omitting the insurance vault creates the bank with `INSURANCE_DISABLED_FLAG` set,
+/// this can't be changed after creation.
+///
+/// Passing the receipt mint creates it and sets `RECEIPTS_ENABLED_FLAG`, deposits can then mint
+/// transferable receipts for their asset shares. Also only possible at creation.
+///
+/// Only one keypair bank per mint: the bank claims the seed 0 bank address of its mint and fails
+/// with `DuplicateBankMint` if it is taken, by an earlier keypair bank or by a seeded bank with
+/// seed 0. `lending_pool_add_bank_with_seed` is the way to add more banks for a mint.
+/// Keypair banks added before the check don't hold the address.
+///
 /// TODO: Allow for different oracle configurations
 pub fn lending_pool_add_bank(
     ctx: Context<LendingPoolAddBank>,
     bank_config: BankConfig,
 ) -> AstrolendResult {
     let LendingPoolAddBank {
+        fee_payer,
         bank_mint,
         liquidity_vault,
         insurance_vault,
         fee_vault,
         bank: bank_loader,
+        seed_zero_bank,
+        system_program,
         ..
     } = ctx.accounts;
 
+    check!(
+        bank_mint.decimals <= MAX_MINT_DECIMALS,
+        AstrolendError::MintDecimalsNotSupported
+    );
+
+    check!(
+        seed_zero_bank.owner != &crate::ID,
+        AstrolendError::DuplicateBankMint
+    );
+
+    let group_key = ctx.accounts.astrolend_group.key();
+    let mint_key = bank_mint.key();
+    utils::claim_pda(
+        &fee_payer.to_account_info(),
+        seed_zero_bank,
+        &system_program.to_account_info(),
+        &[
+            group_key.as_ref(),
+            mint_key.as_ref(),
+            &0u64.to_le_bytes(),
+            &[ctx.bumps.seed_zero_bank],
+        ],
+    )?;
+
     let mut bank = bank_loader.load_init()?;
 
     let liquidity_vault_bump = ctx.bumps.liquidity_vault;
     let liquidity_vault_authority_bump = ctx.bumps.liquidity_vault_authority;
-    let insurance_vault_bump = ctx.bumps.insurance_vault;
+    // Without a vault the canonical bump is still stored, so the PDA constraints keep matching
+    let insurance_vault_bump = ctx.bumps.insurance_vault.unwrap_or_else(|| {
+        utils::find_bank_vault_pda(&bank_loader.key(), BankVaultType::Insurance).1
+    });
     let insurance_vault_authority_bump = ctx.bumps.insurance_vault_authority;
     let fee_vault_bump = ctx.bumps.fee_vault;
     let fee_vault_authority_bump = ctx.bumps.fee_vault_authority;
@@ -42,8 +86,12 @@ pub fn lending_pool_add_bank(
         bank_config,
         bank_mint.key(),
         bank_mint.decimals,
+        ctx.accounts.token_program.key(),
         liquidity_vault.key(),
-        insurance_vault.key(),
+        insurance_vault
+            .as_ref()
+            .map(|insurance_vault| insurance_vault.key())
+            .unwrap_or_default(),
         fee_vault.key(),
         Clock::get().unwrap().unix_timestamp,
         liquidity_vault_bump,
@@ -53,6 +101,12 @@ pub fn lending_pool_add_bank(
         fee_vault_bump,
         fee_vault_authority_bump,
     );
+    if insurance_vault.is_none() {
+        bank.disable_insurance();
+    }
+    if let Some(receipt_mint_bump) = ctx.bumps.receipt_mint {
+        bank.enable_receipts(receipt_mint_bump);
+    }
 
     bank.config.validate()?;
     bank.config.validate_oracle_setup(ctx.remaining_accounts)?;
@@ -60,10 +114,12 @@ pub fn lending_pool_add_bank(
     emit!(LendingPoolBankCreateEvent {
         header: GroupEventHeader {
             astrolend_group: ctx.accounts.astrolend_group.key(),
-            signer: Some(*ctx.accounts.admin.key)
+            signer: Some(*ctx.accounts.admin.key),
         },
         bank: bank_loader.key(),
         mint: bank_mint.key(),
+        borrow_only: bank.is_borrow_only(),
+        meta: group_event_meta!(LendingPoolAddBank),
     });
 
     Ok(())
@@ -92,6 +148,18 @@ pub struct LendingPoolAddBank<'info> {
     )]
     pub bank: AccountLoader<'info, Bank>,
 
+    /// CHECK: Seed 0 bank address of the mint, claimed by the new bank
+    #[account(
+        mut,
+        seeds = [
+            astrolend_group.key().as_ref(),
+            bank_mint.key().as_ref(),
+            &0u64.to_le_bytes(),
+        ],
+        bump,
+    )]
+    pub seed_zero_bank: AccountInfo<'info>,
+
     /// CHECK: ⋐ ͡⋄ ω ͡⋄ ⋑
     #[account(
         seeds = [
@@ -125,6 +193,7 @@ pub struct LendingPoolAddBank<'info> {
     )]
     pub insurance_vault_authority: AccountInfo<'info>,
 
+    /// Optional, banks created without it have insurance disabled
     #[account(
         init,
         payer = fee_payer,
@@ -136,7 +205,7 @@ pub struct LendingPoolAddBank<'info> {
         ],
         bump,
     )]
-    pub insurance_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub insurance_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
     /// CHECK: ⋐ ͡⋄ ω ͡⋄ ⋑
     #[account(
@@ -164,16 +233,33 @@ pub struct LendingPoolAddBank<'info> {
     pub rent: Sysvar<'info, Rent>,
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
+
+    /// Optional, banks created with it can mint deposit receipts
+    #[account(
+        init,
+        payer = fee_payer,
+        mint::decimals = bank_mint.decimals,
+        mint::authority = liquidity_vault_authority,
+        seeds = [
+            RECEIPT_MINT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub receipt_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
 }
 
 /// A copy of lending_pool_add_bank but with an additional bank seed provided.
 /// This seed is used by the LendingPoolAddBankWithSeed.bank to generate a
 /// PDA account to sign for newly added bank transactions securely.
 /// The previous lending_pool_add_bank is preserved for backwards-compatibility.
+///
+/// The seed and bump are stored on the bank and `SEEDED_BANK_FLAG` is set, so the bank
+/// address can be re-derived with `find_bank_pda`.
 pub fn lending_pool_add_bank_with_seed(
     ctx: Context<LendingPoolAddBankWithSeed>,
     bank_config: BankConfig,
-    _bank_seed: u64,
+    bank_seed: u64,
 ) -> AstrolendResult {
     let LendingPoolAddBankWithSeed {
         bank_mint,
@@ -184,11 +270,19 @@ pub fn lending_pool_add_bank_with_seed(
         ..
     } = ctx.accounts;
 
+    check!(
+        bank_mint.decimals <= MAX_MINT_DECIMALS,
+        AstrolendError::MintDecimalsNotSupported
+    );
+
     let mut bank = bank_loader.load_init()?;
 
     let liquidity_vault_bump = ctx.bumps.liquidity_vault;
     let liquidity_vault_authority_bump = ctx.bumps.liquidity_vault_authority;
-    let insurance_vault_bump = ctx.bumps.insurance_vault;
+    // Without a vault the canonical bump is still stored, so the PDA constraints keep matching
+    let insurance_vault_bump = ctx.bumps.insurance_vault.unwrap_or_else(|| {
+        utils::find_bank_vault_pda(&bank_loader.key(), BankVaultType::Insurance).1
+    });
     let insurance_vault_authority_bump = ctx.bumps.insurance_vault_authority;
     let fee_vault_bump = ctx.bumps.fee_vault;
     let fee_vault_authority_bump = ctx.bumps.fee_vault_authority;
@@ -198,8 +292,12 @@ pub fn lending_pool_add_bank_with_seed(
         bank_config,
         bank_mint.key(),
         bank_mint.decimals,
+        ctx.accounts.token_program.key(),
         liquidity_vault.key(),
-        insurance_vault.key(),
+        insurance_vault
+            .as_ref()
+            .map(|insurance_vault| insurance_vault.key())
+            .unwrap_or_default(),
         fee_vault.key(),
         Clock::get().unwrap().unix_timestamp,
         liquidity_vault_bump,
@@ -209,6 +307,13 @@ pub fn lending_pool_add_bank_with_seed(
         fee_vault_bump,
         fee_vault_authority_bump,
     );
+    if insurance_vault.is_none() {
+        bank.disable_insurance();
+    }
+    if let Some(receipt_mint_bump) = ctx.bumps.receipt_mint {
+        bank.enable_receipts(receipt_mint_bump);
+    }
+    bank.set_seed(bank_seed, ctx.bumps.bank);
 
     bank.config.validate()?;
     bank.config.validate_oracle_setup(ctx.remaining_accounts)?;
@@ -216,10 +321,12 @@ pub fn lending_pool_add_bank_with_seed(
     emit!(LendingPoolBankCreateEvent {
         header: GroupEventHeader {
             astrolend_group: ctx.accounts.astrolend_group.key(),
-            signer: Some(*ctx.accounts.admin.key)
+            signer: Some(*ctx.accounts.admin.key),
         },
         bank: bank_loader.key(),
         mint: bank_mint.key(),
+        borrow_only: bank.is_borrow_only(),
+        meta: group_event_meta!(LendingPoolAddBankWithSeed),
     });
 
     Ok(())
@@ -291,6 +398,7 @@ pub struct LendingPoolAddBankWithSeed<'info> {
     )]
     pub insurance_vault_authority: AccountInfo<'info>,
 
+    /// Optional, banks created without it have insurance disabled
     #[account(
         init,
         payer = fee_payer,
@@ -302,7 +410,7 @@ pub struct LendingPoolAddBankWithSeed<'info> {
         ],
         bump,
     )]
-    pub insurance_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub insurance_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
     /// CHECK: ⋐ ͡⋄ ω ͡⋄ ⋑
     #[account(
@@ -330,4 +438,18 @@ pub struct LendingPoolAddBankWithSeed<'info> {
     pub rent: Sysvar<'info, Rent>,
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
+
+    /// Optional, banks created with it can mint deposit receipts
+    #[account(
+        init,
+        payer = fee_payer,
+        mint::decimals = bank_mint.decimals,
+        mint::authority = liquidity_vault_authority,
+        seeds = [
+            RECEIPT_MINT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub receipt_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
 }
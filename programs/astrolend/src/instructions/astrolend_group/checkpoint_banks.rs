@@ -0,0 +1,80 @@
+use crate::{
+    check,
+    events::{BankCheckpoint, GroupEventHeader, LendingPoolBanksCheckpointEvent},
+    group_event_meta,
+    prelude::AstrolendError,
+    state::astrolend_group::{AstrolendGroup, Bank},
+    AstrolendResult,
+};
+use anchor_lang::prelude::*;
+use fixed::types::I80F48;
+
+/// Accrue interest on every bank passed in remaining accounts and record a common checkpoint.
+///
+/// Meant to be run right before a group-wide config migration, so every bank has
+/// `last_update` and share values settled at the same timestamp.
+///
+/// Admin only
+///
+/// Remaining accounts: banks of the group, writable.
+pub fn lending_pool_checkpoint_banks<'info>(
+    ctx: Context<'_, '_, 'info, 'info, LendingPoolCheckpointBanks<'info>>,
+    checkpoint_id: Option<u64>,
+) -> AstrolendResult {
+    let clock = Clock::get()?;
+    let group_key = ctx.accounts.astrolend_group.key();
+    let astrolend_group = ctx.accounts.astrolend_group.load()?;
+
+    check!(
+        !ctx.remaining_accounts.is_empty(),
+        AstrolendError::InvalidBankAccount
+    );
+
+    let mut checkpoints = Vec::with_capacity(ctx.remaining_accounts.len());
+
+    for bank_ai in ctx.remaining_accounts.iter() {
+        let bank_loader = AccountLoader::<Bank>::try_from(bank_ai)?;
+        let mut bank = bank_loader.load_mut()?;
+
+        check!(bank.group == group_key, AstrolendError::InvalidBankAccount);
+
+        bank.checkpoint(
+            clock.unix_timestamp,
+            &astrolend_group,
+            checkpoint_id,
+            #[cfg(not(feature = "client"))]
+            bank_loader.key(),
+            #[cfg(not(feature = "client"))]
+            crate::instruction_discriminator!(LendingPoolCheckpointBanks),
+        )?;
+
+        checkpoints.push(BankCheckpoint {
+            bank: bank_loader.key(),
+            asset_share_value: I80F48::from(bank.asset_share_value).to_num::<f64>(),
+            liability_share_value: I80F48::from(bank.liability_share_value).to_num::<f64>(),
+            last_update: bank.last_update,
+        });
+    }
+
+    emit!(LendingPoolBanksCheckpointEvent {
+        header: GroupEventHeader {
+            astrolend_group: group_key,
+            signer: Some(ctx.accounts.admin.key()),
+        },
+        checkpoint_id,
+        banks: checkpoints,
+        meta: group_event_meta!(LendingPoolCheckpointBanks),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolCheckpointBanks<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        address = astrolend_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+}
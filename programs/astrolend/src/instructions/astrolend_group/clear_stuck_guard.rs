@@ -0,0 +1,42 @@
+use crate::{
+    events::{GroupEventHeader, LendingPoolClearStuckGuardEvent},
+    group_event_meta,
+    state::astrolend_group::{AstrolendGroup, Bank},
+    AstrolendResult,
+};
+use anchor_lang::prelude::*;
+
+/// Permissionless fallback clearing the reentrancy guard of a bank, see
+/// `Bank::reentrancy_guard`. Failed transactions revert the guard and every instruction setting it
+/// clears it before returning, so a set guard between slots means an instruction missed its exit.
+/// Guards of the current slot are left alone, they may belong to a running instruction.
+pub fn lending_pool_clear_stuck_guard(ctx: Context<LendingPoolClearStuckGuard>) -> AstrolendResult {
+    let clock = Clock::get()?;
+    let mut bank = ctx.accounts.bank.load_mut()?;
+    let guard_slot = bank.reentrancy_guard_slot;
+
+    bank.clear_stuck_reentrancy_guard(clock.slot)?;
+
+    emit!(LendingPoolClearStuckGuardEvent {
+        header: GroupEventHeader {
+            astrolend_group: ctx.accounts.astrolend_group.key(),
+            signer: None,
+        },
+        bank: ctx.accounts.bank.key(),
+        guard_slot,
+        meta: group_event_meta!(LendingPoolClearStuckGuard),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolClearStuckGuard<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+}
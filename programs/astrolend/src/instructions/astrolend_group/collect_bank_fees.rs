@@ -1,16 +1,21 @@
 use crate::constants::{FEE_VAULT_AUTHORITY_SEED, INSURANCE_VAULT_AUTHORITY_SEED};
-use crate::events::{GroupEventHeader, LendingPoolBankCollectFeesEvent};
+use crate::events::{
+    FeeSplitTransfer, GroupEventHeader, LendingPoolBankCollectFeesEvent,
+    LendingPoolDistributeFeesEvent, LendingPoolWithdrawFeesEvent,
+    LendingPoolWithdrawInsuranceEvent,
+};
 use crate::utils;
 use crate::{
-    bank_signer,
+    bank_signer, check,
     constants::{
         FEE_VAULT_SEED, INSURANCE_VAULT_SEED, LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED,
     },
-    math_error,
+    group_event_meta, math_error,
     state::astrolend_group::{Bank, BankVaultType, AstrolendGroup},
-    AstrolendResult,
+    AstrolendError, AstrolendResult,
 };
 use anchor_lang::prelude::*;
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
 use anchor_spl::token_interface::{TokenAccount, TokenInterface};
 use fixed::types::I80F48;
 use std::cmp::min;
@@ -28,8 +33,16 @@ pub fn lending_pool_collect_bank_fees<'info>(
     } = ctx.accounts;
 
     let mut bank = ctx.accounts.bank.load_mut()?;
-    let maybe_bank_mint =
-        utils::maybe_take_bank_mint(&mut ctx.remaining_accounts, &bank, token_program.key)?;
+    bank.enter_reentrancy_guard(Clock::get()?.slot)?;
+    let maybe_bank_mint = utils::maybe_take_bank_mint(
+        &mut ctx.remaining_accounts,
+        &bank,
+        token_program.key,
+        liquidity_vault.to_account_info().owner,
+    )?;
+
+    // Also the reconciliation point for tokens sent to the liquidity vault directly
+    bank.sync_liquidity_vault_balance(liquidity_vault.amount);
 
     let mut available_liquidity = I80F48::from_num(liquidity_vault.amount);
 
@@ -71,6 +84,7 @@ pub fn lending_pool_collect_bank_fees<'info>(
 
     bank.collected_group_fees_outstanding = new_outstanding_group_fees.into();
 
+    let liquidity_vault_authority_bump = bank.liquidity_vault_authority_bump;
     bank.withdraw_spl_transfer(
         group_fee_transfer_amount
             .checked_to_num()
@@ -83,32 +97,40 @@ pub fn lending_pool_collect_bank_fees<'info>(
         bank_signer!(
             BankVaultType::Liquidity,
             ctx.accounts.bank.key(),
-            bank.liquidity_vault_authority_bump
+            liquidity_vault_authority_bump
         ),
         ctx.remaining_accounts,
     )?;
 
-    bank.withdraw_spl_transfer(
-        insurance_fee_transfer_amount
-            .checked_to_num()
-            .ok_or_else(math_error!())?,
-        liquidity_vault.to_account_info(),
-        insurance_vault.to_account_info(),
-        liquidity_vault_authority.to_account_info(),
-        maybe_bank_mint.as_ref(),
-        token_program.to_account_info(),
-        bank_signer!(
-            BankVaultType::Liquidity,
-            ctx.accounts.bank.key(),
-            bank.liquidity_vault_authority_bump
-        ),
-        ctx.remaining_accounts,
-    )?;
+    // Banks with insurance disabled route insurance fees to the group fees on accrual
+    if !bank.is_insurance_disabled() {
+        let insurance_vault = insurance_vault
+            .as_ref()
+            .ok_or(AstrolendError::InsuranceVaultRequired)?;
+
+        bank.withdraw_spl_transfer(
+            insurance_fee_transfer_amount
+                .checked_to_num()
+                .ok_or_else(math_error!())?,
+            liquidity_vault.to_account_info(),
+            insurance_vault.to_account_info(),
+            liquidity_vault_authority.to_account_info(),
+            maybe_bank_mint.as_ref(),
+            token_program.to_account_info(),
+            bank_signer!(
+                BankVaultType::Liquidity,
+                ctx.accounts.bank.key(),
+                liquidity_vault_authority_bump
+            ),
+            ctx.remaining_accounts,
+        )?;
+    }
+    bank.exit_reentrancy_guard();
 
     emit!(LendingPoolBankCollectFeesEvent {
         header: GroupEventHeader {
             astrolend_group: ctx.accounts.astrolend_group.key(),
-            signer: None
+            signer: None,
         },
         bank: ctx.accounts.bank.key(),
         mint: liquidity_vault.mint,
@@ -116,6 +138,7 @@ pub fn lending_pool_collect_bank_fees<'info>(
         insurance_fees_outstanding: new_outstanding_insurance_fees.to_num::<f64>(),
         group_fees_collected: group_fee_transfer_amount.to_num::<f64>(),
         group_fees_outstanding: new_outstanding_group_fees.to_num::<f64>(),
+        meta: group_event_meta!(LendingPoolCollectBankFees),
     });
 
     Ok(())
@@ -161,7 +184,7 @@ pub struct LendingPoolCollectBankFees<'info> {
         ],
         bump = bank.load()?.insurance_vault_bump
     )]
-    pub insurance_vault: AccountInfo<'info>,
+    pub insurance_vault: Option<AccountInfo<'info>>,
 
     /// CHECK: ⋐ ͡⋄ ω ͡⋄ ⋑
     #[account(
@@ -182,7 +205,9 @@ pub fn lending_pool_withdraw_fees<'info>(
     amount: u64,
 ) -> AstrolendResult {
     let LendingPoolWithdrawFees {
+        astrolend_group,
         bank: bank_loader,
+        admin,
         fee_vault,
         fee_vault_authority,
         dst_token_account,
@@ -190,11 +215,15 @@ pub fn lending_pool_withdraw_fees<'info>(
         ..
     } = ctx.accounts;
 
-    let bank = bank_loader.load()?;
-    let maybe_bank_mint =
-        utils::maybe_take_bank_mint(&mut ctx.remaining_accounts, &bank, token_program.key)?;
+    let mut bank = bank_loader.load_mut()?;
+    let maybe_bank_mint = utils::maybe_take_bank_mint(
+        &mut ctx.remaining_accounts,
+        &bank,
+        token_program.key,
+        fee_vault.owner,
+    )?;
 
-    bank.withdraw_spl_transfer(
+    Bank::vault_spl_transfer(
         amount,
         fee_vault.to_account_info(),
         dst_token_account.to_account_info(),
@@ -209,6 +238,21 @@ pub fn lending_pool_withdraw_fees<'info>(
         ctx.remaining_accounts,
     )?;
 
+    let lifetime_withdrawn = bank.add_fees_withdrawn(amount)?;
+
+    emit!(LendingPoolWithdrawFeesEvent {
+        header: GroupEventHeader {
+            astrolend_group: astrolend_group.key(),
+            signer: Some(admin.key()),
+        },
+        bank: bank_loader.key(),
+        destination: dst_token_account.key(),
+        amount,
+        vault_balance: utils::token_account_amount(fee_vault)?,
+        lifetime_withdrawn,
+        meta: group_event_meta!(LendingPoolWithdrawFees),
+    });
+
     Ok(())
 }
 
@@ -217,6 +261,7 @@ pub struct LendingPoolWithdrawFees<'info> {
     pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
 
     #[account(
+        mut,
         constraint = bank.load()?.group == astrolend_group.key(),
     )]
     pub bank: AccountLoader<'info, Bank>,
@@ -254,12 +299,154 @@ pub struct LendingPoolWithdrawFees<'info> {
     pub token_program: Interface<'info, TokenInterface>,
 }
 
+/// Send the whole fee vault balance to the destinations of the group fee split, see
+/// `AstrolendGroup::configure_fee_split`. Each destination gets its bps share rounded down, the
+/// rounding dust goes to the first one.
+///
+/// Shares are counted as they leave the vault, Token-2022 transfer fees come out of what each
+/// destination receives.
+///
+/// Permissionless
+///
+/// Expected remaining account schema
+/// [
+///    bank_mint (Token-2022 banks only),
+///    associated token account of each fee split destination, in table order,
+///    transfer hook accounts,
+/// ]
+pub fn lending_pool_distribute_fees<'info>(
+    mut ctx: Context<'_, '_, 'info, 'info, LendingPoolDistributeFees<'info>>,
+) -> AstrolendResult {
+    let LendingPoolDistributeFees {
+        astrolend_group,
+        bank: bank_loader,
+        fee_vault,
+        fee_vault_authority,
+        token_program,
+    } = ctx.accounts;
+
+    let group = astrolend_group.load()?;
+    let mut bank = bank_loader.load_mut()?;
+    bank.enter_reentrancy_guard(Clock::get()?.slot)?;
+    let maybe_bank_mint = utils::maybe_take_bank_mint(
+        &mut ctx.remaining_accounts,
+        &bank,
+        token_program.key,
+        fee_vault.owner,
+    )?;
+
+    let amount = utils::token_account_amount(fee_vault)?;
+    let shares = group.split_fee_amount(amount)?;
+
+    check!(
+        ctx.remaining_accounts.len() >= shares.len(),
+        AstrolendError::InvalidFeeSplitDestination
+    );
+    let (destination_ais, hook_ais) = ctx.remaining_accounts.split_at(shares.len());
+
+    let mut transfers = Vec::with_capacity(shares.len());
+    for ((entry, destination_ai), share) in group
+        .get_fee_split_entries()
+        .zip(destination_ais)
+        .zip(shares)
+    {
+        check!(
+            destination_ai.key()
+                == get_associated_token_address_with_program_id(
+                    &entry.destination,
+                    &bank.mint,
+                    token_program.key
+                ),
+            AstrolendError::InvalidFeeSplitDestination
+        );
+
+        if share > 0 {
+            Bank::vault_spl_transfer(
+                share,
+                fee_vault.to_account_info(),
+                destination_ai.to_account_info(),
+                fee_vault_authority.to_account_info(),
+                maybe_bank_mint.as_ref(),
+                token_program.to_account_info(),
+                bank_signer!(
+                    BankVaultType::Fee,
+                    bank_loader.key(),
+                    bank.fee_vault_authority_bump
+                ),
+                hook_ais,
+            )?;
+        }
+
+        transfers.push(FeeSplitTransfer {
+            destination: destination_ai.key(),
+            amount: share,
+        });
+    }
+
+    let lifetime_withdrawn = bank.add_fees_withdrawn(amount)?;
+    bank.exit_reentrancy_guard();
+
+    emit!(LendingPoolDistributeFeesEvent {
+        header: GroupEventHeader {
+            astrolend_group: astrolend_group.key(),
+            signer: None,
+        },
+        bank: bank_loader.key(),
+        amount,
+        transfers,
+        lifetime_withdrawn,
+        meta: group_event_meta!(LendingPoolDistributeFees),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolDistributeFees<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    /// CHECK: ⋐ ͡⋄ ω ͡⋄ ⋑
+    #[account(
+        mut,
+        seeds = [
+            FEE_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.fee_vault_bump
+    )]
+    pub fee_vault: AccountInfo<'info>,
+
+    /// CHECK: ⋐ ͡⋄ ω ͡⋄ ⋑
+    #[account(
+        seeds = [
+            FEE_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.fee_vault_authority_bump
+    )]
+    pub fee_vault_authority: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Withdraw from the insurance vault, limited to the part not staked by third parties,
+/// see `InsuranceStake`.
+///
+/// Admin only
 pub fn lending_pool_withdraw_insurance<'info>(
     mut ctx: Context<'_, '_, 'info, 'info, LendingPoolWithdrawInsurance<'info>>,
     amount: u64,
 ) -> AstrolendResult {
     let LendingPoolWithdrawInsurance {
+        astrolend_group,
         bank: bank_loader,
+        admin,
         insurance_vault,
         insurance_vault_authority,
         dst_token_account,
@@ -267,11 +454,20 @@ pub fn lending_pool_withdraw_insurance<'info>(
         ..
     } = ctx.accounts;
 
-    let bank = bank_loader.load()?;
-    let maybe_bank_mint =
-        utils::maybe_take_bank_mint(&mut ctx.remaining_accounts, &bank, token_program.key)?;
+    let mut bank = bank_loader.load_mut()?;
+    let maybe_bank_mint = utils::maybe_take_bank_mint(
+        &mut ctx.remaining_accounts,
+        &bank,
+        token_program.key,
+        insurance_vault.to_account_info().owner,
+    )?;
 
-    bank.withdraw_spl_transfer(
+    check!(
+        amount <= bank.get_admin_insurance_amount(insurance_vault.amount)?,
+        AstrolendError::InsufficientAdminInsurance
+    );
+
+    Bank::vault_spl_transfer(
         amount,
         insurance_vault.to_account_info(),
         dst_token_account.to_account_info(),
@@ -286,6 +482,22 @@ pub fn lending_pool_withdraw_insurance<'info>(
         ctx.remaining_accounts,
     )?;
 
+    let lifetime_withdrawn = bank.add_insurance_withdrawn(amount)?;
+    insurance_vault.reload()?;
+
+    emit!(LendingPoolWithdrawInsuranceEvent {
+        header: GroupEventHeader {
+            astrolend_group: astrolend_group.key(),
+            signer: Some(admin.key()),
+        },
+        bank: bank_loader.key(),
+        destination: dst_token_account.key(),
+        amount,
+        vault_balance: insurance_vault.amount,
+        lifetime_withdrawn,
+        meta: group_event_meta!(LendingPoolWithdrawInsurance),
+    });
+
     Ok(())
 }
 
@@ -294,6 +506,7 @@ pub struct LendingPoolWithdrawInsurance<'info> {
     pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
 
     #[account(
+        mut,
         constraint = bank.load()?.group == astrolend_group.key(),
     )]
     pub bank: AccountLoader<'info, Bank>,
@@ -303,7 +516,6 @@ pub struct LendingPoolWithdrawInsurance<'info> {
     )]
     pub admin: Signer<'info>,
 
-    /// CHECK: ⋐ ͡⋄ ω ͡⋄ ⋑
     #[account(
         mut,
         seeds = [
@@ -312,7 +524,7 @@ pub struct LendingPoolWithdrawInsurance<'info> {
         ],
         bump = bank.load()?.insurance_vault_bump
     )]
-    pub insurance_vault: AccountInfo<'info>,
+    pub insurance_vault: InterfaceAccount<'info, TokenAccount>,
 
     /// CHECK: ⋐ ͡⋄ ω ͡⋄ ⋑
     #[account(
@@ -1,14 +1,19 @@
-use crate::check;
-use crate::events::{GroupEventHeader, AstrolendGroupConfigureEvent};
+use crate::events::{
+    AstrolendGroupConfigureEmodeEvent, AstrolendGroupConfigureEvent,
+    AstrolendGroupConfigureFeeSplitEvent, AstrolendGroupIrEmergencyMultiplierEvent,
+    GroupEventHeader,
+};
 use crate::prelude::AstrolendError;
 use crate::state::astrolend_account::{
-    AstrolendAccount, FLASHLOAN_ENABLED_FLAG, TRANSFER_AUTHORITY_ALLOWED_FLAG,
+    AstrolendAccount, FEE_EXEMPT_FLAG, FLASHLOAN_ENABLED_FLAG, TRANSFER_AUTHORITY_ALLOWED_FLAG,
 };
+use crate::{check, group_event_meta};
 use crate::{
-    state::astrolend_group::{GroupConfig, AstrolendGroup},
+    state::astrolend_group::{AstrolendGroup, EmodeEntryConfig, FeeSplitEntryConfig, GroupConfig},
     AstrolendResult,
 };
 use anchor_lang::prelude::*;
+use fixed::types::I80F48;
 
 /// Configure astrol group
 ///
@@ -16,14 +21,80 @@ use anchor_lang::prelude::*;
 pub fn configure(ctx: Context<AstrolendGroupConfigure>, config: GroupConfig) -> AstrolendResult {
     let astrolend_group = &mut ctx.accounts.astrolend_group.load_mut()?;
 
+    let old_values = astrolend_group.get_config_values();
+
     astrolend_group.configure(&config)?;
 
+    if config.ir_emergency_multiplier.is_some() {
+        emit!(AstrolendGroupIrEmergencyMultiplierEvent {
+            header: GroupEventHeader {
+                astrolend_group: ctx.accounts.astrolend_group.key(),
+                signer: Some(*ctx.accounts.admin.key),
+            },
+            multiplier: I80F48::from(astrolend_group.ir_emergency_multiplier).to_num::<f64>(),
+            expires_at: astrolend_group.multiplier_expires_at,
+            meta: group_event_meta!(AstrolendGroupConfigure),
+        });
+    }
+
     emit!(AstrolendGroupConfigureEvent {
         header: GroupEventHeader {
             astrolend_group: ctx.accounts.astrolend_group.key(),
-            signer: Some(*ctx.accounts.admin.key)
+            signer: Some(*ctx.accounts.admin.key),
         },
         config,
+        old_admin: old_values.admin,
+        new_admin: astrolend_group.admin,
+        old_values,
+        new_values: astrolend_group.get_config_values(),
+        meta: group_event_meta!(AstrolendGroupConfigure),
+    });
+
+    Ok(())
+}
+
+/// Replace the emode table of the group
+///
+/// Admin only
+pub fn configure_emode(
+    ctx: Context<AstrolendGroupConfigure>,
+    entries: Vec<EmodeEntryConfig>,
+) -> AstrolendResult {
+    let astrolend_group = &mut ctx.accounts.astrolend_group.load_mut()?;
+
+    astrolend_group.configure_emode(&entries)?;
+
+    emit!(AstrolendGroupConfigureEmodeEvent {
+        header: GroupEventHeader {
+            astrolend_group: ctx.accounts.astrolend_group.key(),
+            signer: Some(*ctx.accounts.admin.key),
+        },
+        entries,
+        meta: group_event_meta!(AstrolendGroupConfigureEmode),
+    });
+
+    Ok(())
+}
+
+/// Replace the fee split of the group, fee vault distributions are sent to `entries` pro rata
+/// to their bps by `lending_pool_distribute_fees`. An empty table removes the split.
+///
+/// Admin only
+pub fn configure_fee_split(
+    ctx: Context<AstrolendGroupConfigure>,
+    entries: Vec<FeeSplitEntryConfig>,
+) -> AstrolendResult {
+    let astrolend_group = &mut ctx.accounts.astrolend_group.load_mut()?;
+
+    astrolend_group.configure_fee_split(&entries)?;
+
+    emit!(AstrolendGroupConfigureFeeSplitEvent {
+        header: GroupEventHeader {
+            astrolend_group: ctx.accounts.astrolend_group.key(),
+            signer: Some(*ctx.accounts.admin.key),
+        },
+        entries,
+        meta: group_event_meta!(AstrolendGroupConfigureFeeSplit),
     });
 
     Ok(())
@@ -50,7 +121,8 @@ pub struct AstrolendGroupConfigure<'info> {
 /// 0b1000 is a valid flag
 /// 0b01100 is a valid flag
 /// 0b0101 is not a valid flag
-const CONFIGURABLE_FLAGS: u64 = FLASHLOAN_ENABLED_FLAG + TRANSFER_AUTHORITY_ALLOWED_FLAG;
+const CONFIGURABLE_FLAGS: u64 =
+    FLASHLOAN_ENABLED_FLAG + TRANSFER_AUTHORITY_ALLOWED_FLAG + FEE_EXEMPT_FLAG;
 
 fn flag_can_be_set(flag: u64) -> bool {
     // If bitwise AND operation between flag and its bitwise NOT of CONFIGURABLE_FLAGS is 0,
@@ -107,7 +179,8 @@ pub struct UnsetAccountFlag<'info> {
 #[cfg(test)]
 mod tests {
     use crate::state::astrolend_account::{
-        DISABLED_FLAG, FLASHLOAN_ENABLED_FLAG, IN_FLASHLOAN_FLAG, TRANSFER_AUTHORITY_ALLOWED_FLAG,
+        DISABLED_FLAG, FEE_EXEMPT_FLAG, FLASHLOAN_ENABLED_FLAG, IN_FLASHLOAN_FLAG,
+        TRANSFER_AUTHORITY_ALLOWED_FLAG,
     };
 
     #[test]
@@ -127,6 +200,8 @@ mod tests {
             + FLASHLOAN_ENABLED_FLAG
             + IN_FLASHLOAN_FLAG
             + TRANSFER_AUTHORITY_ALLOWED_FLAG;
+        let flag8 = FEE_EXEMPT_FLAG;
+        let flag9 = FEE_EXEMPT_FLAG + DISABLED_FLAG;
 
         // Malformed flags should fail
         assert!(!super::flag_can_be_set(flag2));
@@ -134,9 +209,11 @@ mod tests {
         assert!(!super::flag_can_be_set(flag4));
         assert!(!super::flag_can_be_set(flag6));
         assert!(!super::flag_can_be_set(flag7));
+        assert!(!super::flag_can_be_set(flag9));
 
         // Good flags should succeed
         assert!(super::flag_can_be_set(flag1));
         assert!(super::flag_can_be_set(flag5));
+        assert!(super::flag_can_be_set(flag8));
     }
 }
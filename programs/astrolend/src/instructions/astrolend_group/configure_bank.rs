@@ -1,46 +1,456 @@
-use crate::constants::{EMISSIONS_AUTH_SEED, EMISSIONS_TOKEN_ACCOUNT_SEED};
-use crate::events::{GroupEventHeader, LendingPoolBankConfigureEvent};
+use crate::constants::{EMISSIONS_AUTH_SEED, EMISSIONS_TOKEN_ACCOUNT_SEED, PENDING_CONFIG_SEED};
+use crate::events::{
+    GroupEventHeader, GroupEventMeta, LendingPoolBankCloseEmissionsEvent,
+    LendingPoolBankConfigCancelEvent, LendingPoolBankConfigProposeEvent,
+    LendingPoolBankConfigureEvent, LendingPoolBankConfigureOracleUncheckedEvent,
+    LendingPoolBankEmissionsFundingMismatchEvent, LendingPoolBankSettlementPriceEvent,
+    LendingPoolBankWeightRampCancelEvent, LendingPoolBankWeightRampCompleteEvent,
+    LendingPoolBankWeightRampScheduleEvent,
+};
 use crate::prelude::AstrolendError;
-use crate::{check, math_error, utils};
+use crate::state::price::{FixedPriceFeed, OraclePriceType, PriceAdapter};
+use crate::{check, emissions_signer, group_event_meta, math_error, utils};
 use crate::{
-    state::astrolend_group::{Bank, BankConfigOpt, AstrolendGroup},
+    state::astrolend_group::{
+        AstrolendGroup, Bank, BankConfig, BankConfigOpt, OracleConfig, PendingBankConfig,
+        WrappedI80F48,
+    },
     AstrolendResult,
 };
 use anchor_lang::prelude::*;
-use anchor_spl::token_2022::{transfer_checked, TransferChecked};
+use anchor_spl::token_2022::{close_account, transfer_checked, CloseAccount, TransferChecked};
 use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 use fixed::types::I80F48;
 
+/// Changing the oracle requires the new price to agree with the current oracle price,
+/// see [crate::state::astrolend_group::BankConfig::check_oracle_change].
+///
+/// Moving a Pyth push bank to another shard requires its oracle account on the new shard to
+/// report a fresh price, see [crate::state::astrolend_group::BankConfig::check_pyth_shard_change].
+///
+/// Asset weights can't be changed while a weight change is scheduled, see
+/// [lending_pool_schedule_weight_change].
+///
+/// Expected remaining account schema when changing the oracle or the Pyth shard
+/// [
+///    new_oracle_ai,
+///    old_oracle_ai (only if the oracle changed),
+/// ]
+///
+/// `oracle_params` only changes parameters of the configured oracle, no accounts are needed.
+///
+/// While the group has a `config_timelock_secs`, the config is written to the `pending_config`
+/// account instead and applied by [lending_pool_execute_pending_config] once the timelock
+/// elapsed. Emergency changes skip the timelock, see `BankConfigOpt::bypasses_timelock`, as do
+/// [lending_pool_configure_bank_oracle_unchecked] and [lending_pool_set_bank_settlement_price].
 pub fn lending_pool_configure_bank(
     ctx: Context<LendingPoolConfigureBank>,
     bank_config: BankConfigOpt,
 ) -> AstrolendResult {
     let mut bank = ctx.accounts.bank.load_mut()?;
+    let header = || GroupEventHeader {
+        astrolend_group: ctx.accounts.astrolend_group.key(),
+        signer: Some(*ctx.accounts.admin.key),
+    };
+
+    maybe_complete_weight_ramp(
+        &mut bank,
+        ctx.accounts.bank.key(),
+        Clock::get()?.unix_timestamp,
+        header(),
+        group_event_meta!(LendingPoolConfigureBank),
+    );
 
-    bank.configure(&bank_config)?;
-
-    if bank_config.oracle.is_some() {
-        bank.config.validate_oracle_setup(ctx.remaining_accounts)?;
+    let timelock_secs = ctx.accounts.astrolend_group.load()?.config_timelock_secs;
+    let timelocked = timelock_secs > 0 && !bank_config.bypasses_timelock(&bank.config);
+
+    match (timelocked, ctx.accounts.pending_config.as_mut()) {
+        (true, Some(pending_config)) => {
+            // Mistakes surface now rather than once the timelock elapsed, the config is
+            // validated again against the bank when executed
+            bank_config.apply_and_validate(&bank.config)?;
+
+            let executable_at = Clock::get()?
+                .unix_timestamp
+                .checked_add(timelock_secs.into())
+                .ok_or_else(math_error!())?;
+            pending_config.set_inner(PendingBankConfig {
+                bank: ctx.accounts.bank.key(),
+                proposer: ctx.accounts.admin.key(),
+                executable_at,
+                config: bank_config.clone(),
+            });
+
+            emit!(LendingPoolBankConfigProposeEvent {
+                header: header(),
+                bank: ctx.accounts.bank.key(),
+                mint: bank.mint,
+                config: bank_config,
+                executable_at,
+                meta: group_event_meta!(LendingPoolConfigureBank),
+            });
+
+            return Ok(());
+        }
+        (true, None) => return err!(AstrolendError::PendingConfigRequired),
+        (false, Some(_)) => return err!(AstrolendError::InvalidConfig),
+        (false, None) => {}
     }
 
+    let old_config = apply_bank_config(
+        &mut bank,
+        &*ctx.accounts.astrolend_group.load()?,
+        &bank_config,
+        ctx.remaining_accounts,
+    )?;
+
+    emit!(LendingPoolBankConfigureEvent {
+        header: header(),
+        bank: ctx.accounts.bank.key(),
+        mint: bank.mint,
+        config: bank_config,
+        borrow_only: bank.is_borrow_only(),
+        oracle_changed: bank.config.oracle_changed(&old_config),
+        oracle_params_changed: bank.config.oracle_params_changed(&old_config),
+        meta: group_event_meta!(LendingPoolConfigureBank),
+    });
+
+    Ok(())
+}
+
+/// Apply a bank config change proposed through [lending_pool_configure_bank] once its timelock
+/// elapsed. Permissionless, the rent of the pending config goes back to its proposer.
+///
+/// The change is validated against the bank as it is now, oracle changes against the live
+/// oracle, with the same remaining accounts as [lending_pool_configure_bank].
+pub fn lending_pool_execute_pending_config(
+    ctx: Context<LendingPoolExecutePendingConfig>,
+) -> AstrolendResult {
+    let pending_config = &ctx.accounts.pending_config;
+    let current_timestamp = Clock::get()?.unix_timestamp;
+    check!(
+        current_timestamp >= pending_config.executable_at,
+        AstrolendError::PendingConfigNotExecutable
+    );
+
+    let mut bank = ctx.accounts.bank.load_mut()?;
+    let header = || GroupEventHeader {
+        astrolend_group: ctx.accounts.astrolend_group.key(),
+        signer: None,
+    };
+
+    maybe_complete_weight_ramp(
+        &mut bank,
+        ctx.accounts.bank.key(),
+        current_timestamp,
+        header(),
+        group_event_meta!(LendingPoolExecutePendingConfig),
+    );
+
+    let old_config = apply_bank_config(
+        &mut bank,
+        &*ctx.accounts.astrolend_group.load()?,
+        &pending_config.config,
+        ctx.remaining_accounts,
+    )?;
+
     emit!(LendingPoolBankConfigureEvent {
+        header: header(),
+        bank: ctx.accounts.bank.key(),
+        mint: bank.mint,
+        config: pending_config.config.clone(),
+        borrow_only: bank.is_borrow_only(),
+        oracle_changed: bank.config.oracle_changed(&old_config),
+        oracle_params_changed: bank.config.oracle_params_changed(&old_config),
+        meta: group_event_meta!(LendingPoolExecutePendingConfig),
+    });
+
+    Ok(())
+}
+
+/// Drop a pending bank config change, its rent goes back to its proposer.
+pub fn lending_pool_cancel_pending_config(
+    ctx: Context<LendingPoolCancelPendingConfig>,
+) -> AstrolendResult {
+    emit!(LendingPoolBankConfigCancelEvent {
+        header: GroupEventHeader {
+            astrolend_group: ctx.accounts.astrolend_group.key(),
+            signer: Some(*ctx.accounts.admin.key),
+        },
+        bank: ctx.accounts.bank.key(),
+        mint: ctx.accounts.bank.load()?.mint,
+        config: ctx.accounts.pending_config.config.clone(),
+        meta: group_event_meta!(LendingPoolCancelPendingConfig),
+    });
+
+    Ok(())
+}
+
+/// Configure `bank` with `bank_config`, validating oracle and Pyth shard changes against
+/// `remaining_accounts`, see [lending_pool_configure_bank]. Returns the config before the change.
+fn apply_bank_config(
+    bank: &mut Bank,
+    group: &AstrolendGroup,
+    bank_config: &BankConfigOpt,
+    remaining_accounts: &[AccountInfo],
+) -> AstrolendResult<BankConfig> {
+    let old_config = bank.config;
+
+    bank.configure(bank_config)?;
+
+    if bank_config.oracle_keys_and_setup.is_some() {
+        let (oracle_ais, old_oracle_ais) =
+            remaining_accounts.split_at(remaining_accounts.len().min(1));
+
+        bank.config.validate_oracle_setup(oracle_ais)?;
+
+        if bank.config.oracle_changed(&old_config) {
+            bank.config.check_oracle_change(
+                oracle_ais,
+                &old_config,
+                old_oracle_ais,
+                group.get_oracle_change_max_deviation(),
+                &Clock::get()?,
+            )?;
+        }
+    }
+
+    if bank.config.pyth_shard_id != old_config.pyth_shard_id {
+        bank.config.check_pyth_shard_change(
+            &remaining_accounts[..remaining_accounts.len().min(1)],
+            &Clock::get()?,
+        )?;
+    }
+
+    Ok(old_config)
+}
+
+/// Change the bank oracle without comparing prices against the current oracle,
+/// e.g. to replace a feed that stopped updating. Emits a dedicated event so the change
+/// can be monitored.
+///
+/// An emergency change, it applies right away even while the group has a
+/// `config_timelock_secs`: a bank whose oracle stopped updating can't be withdrawn from,
+/// borrowed from or liquidated until it is replaced. No pending config is accepted.
+///
+/// Expected remaining account schema
+/// [
+///    new_oracle_ai,
+/// ]
+pub fn lending_pool_configure_bank_oracle_unchecked(
+    ctx: Context<LendingPoolConfigureBank>,
+    oracle: OracleConfig,
+) -> AstrolendResult {
+    check!(
+        ctx.accounts.pending_config.is_none(),
+        AstrolendError::InvalidConfig
+    );
+
+    let mut bank = ctx.accounts.bank.load_mut()?;
+    let old_oracle = OracleConfig {
+        setup: bank.config.oracle_setup,
+        keys: bank.config.oracle_keys,
+    };
+
+    bank.configure(&BankConfigOpt {
+        oracle_keys_and_setup: Some(oracle),
+        ..Default::default()
+    })?;
+    bank.config.validate_oracle_setup(ctx.remaining_accounts)?;
+
+    msg!(
+        "Oracle of bank {} changed without price check: {} -> {}",
+        ctx.accounts.bank.key(),
+        old_oracle.keys[0],
+        oracle.keys[0]
+    );
+
+    emit!(LendingPoolBankConfigureOracleUncheckedEvent {
         header: GroupEventHeader {
             astrolend_group: ctx.accounts.astrolend_group.key(),
-            signer: Some(*ctx.accounts.admin.key)
+            signer: Some(*ctx.accounts.admin.key),
         },
         bank: ctx.accounts.bank.key(),
         mint: bank.mint,
-        config: bank_config,
+        old_oracle,
+        new_oracle: oracle,
+        meta: group_event_meta!(LendingPoolConfigureBankOracleUnchecked),
     });
 
     Ok(())
 }
 
+/// Wind the bank down at a fixed price, e.g. after a delisting or when its oracle is permanently
+/// dead. While set the bank is valued at `price * 10^expo` (UI price) whatever oracle account is
+/// passed, deposits and borrows are rejected and withdrawals, repays and liquidations go on at
+/// the settlement price. Can be called again to change the price.
+///
+/// An emergency change like pausing the bank, it applies right away even while the group has a
+/// `config_timelock_secs`. No pending config is accepted.
+pub fn lending_pool_set_bank_settlement_price(
+    ctx: Context<LendingPoolConfigureBank>,
+    price: u64,
+    expo: i32,
+) -> AstrolendResult {
+    check!(
+        ctx.accounts.pending_config.is_none(),
+        AstrolendError::InvalidConfig
+    );
+
+    let mut bank = ctx.accounts.bank.load_mut()?;
+
+    let settlement_price = FixedPriceFeed::price_from_components(price, expo)?;
+    bank.enter_settlement(settlement_price)?;
+
+    emit!(LendingPoolBankSettlementPriceEvent {
+        header: GroupEventHeader {
+            astrolend_group: ctx.accounts.astrolend_group.key(),
+            signer: Some(*ctx.accounts.admin.key),
+        },
+        bank: ctx.accounts.bank.key(),
+        mint: bank.mint,
+        settlement_price: Some(settlement_price.to_num()),
+        oracle_price: None,
+        meta: group_event_meta!(LendingPoolSetBankSettlementPrice),
+    });
+
+    Ok(())
+}
+
+/// Return a bank in settlement mode to its oracle, which must report a fresh price.
+///
+/// Expected remaining account schema
+/// [
+///    oracle_ai,
+/// ]
+pub fn lending_pool_clear_bank_settlement_price(
+    ctx: Context<LendingPoolConfigureBank>,
+) -> AstrolendResult {
+    let mut bank = ctx.accounts.bank.load_mut()?;
+
+    check!(bank.is_in_settlement(), AstrolendError::InvalidConfig);
+
+    bank.exit_settlement();
+
+    let oracle_price = bank
+        .load_price_feed(ctx.remaining_accounts, &Clock::get()?)?
+        .get_price_of_type(OraclePriceType::RealTime, None)?;
+
+    emit!(LendingPoolBankSettlementPriceEvent {
+        header: GroupEventHeader {
+            astrolend_group: ctx.accounts.astrolend_group.key(),
+            signer: Some(*ctx.accounts.admin.key),
+        },
+        bank: ctx.accounts.bank.key(),
+        mint: bank.mint,
+        settlement_price: None,
+        oracle_price: Some(oracle_price.to_num()),
+        meta: group_event_meta!(LendingPoolClearBankSettlementPrice),
+    });
+
+    Ok(())
+}
+
+/// Move the bank asset weights to the targets gradually, reaching them at `end_timestamp`, so
+/// lowering them doesn't make accounts liquidatable at once. The risk engine interpolates the
+/// weights linearly in between. Liability weights are not ramped.
+///
+/// Asset weights can't be configured until the change completes or is cancelled. The targets are
+/// written to the config by the first bank admin instruction or interest accrual after
+/// `end_timestamp`.
+pub fn lending_pool_schedule_weight_change(
+    ctx: Context<LendingPoolConfigureBank>,
+    target_asset_weight_init: WrappedI80F48,
+    target_asset_weight_maint: WrappedI80F48,
+    end_timestamp: i64,
+) -> AstrolendResult {
+    let mut bank = ctx.accounts.bank.load_mut()?;
+    let current_timestamp = Clock::get()?.unix_timestamp;
+    let header = || GroupEventHeader {
+        astrolend_group: ctx.accounts.astrolend_group.key(),
+        signer: Some(*ctx.accounts.admin.key),
+    };
+
+    maybe_complete_weight_ramp(
+        &mut bank,
+        ctx.accounts.bank.key(),
+        current_timestamp,
+        header(),
+        group_event_meta!(LendingPoolScheduleWeightChange),
+    );
+
+    bank.schedule_weight_ramp(
+        target_asset_weight_init,
+        target_asset_weight_maint,
+        current_timestamp,
+        end_timestamp,
+    )?;
+
+    emit!(LendingPoolBankWeightRampScheduleEvent {
+        header: header(),
+        bank: ctx.accounts.bank.key(),
+        mint: bank.mint,
+        start_asset_weight_init: I80F48::from(bank.config.asset_weight_init).to_num(),
+        start_asset_weight_maint: I80F48::from(bank.config.asset_weight_maint).to_num(),
+        target_asset_weight_init: I80F48::from(target_asset_weight_init).to_num(),
+        target_asset_weight_maint: I80F48::from(target_asset_weight_maint).to_num(),
+        start_timestamp: current_timestamp,
+        end_timestamp,
+        meta: group_event_meta!(LendingPoolScheduleWeightChange),
+    });
+
+    Ok(())
+}
+
+/// Stop the scheduled asset weight change, the weights it reached become the config weights.
+pub fn lending_pool_cancel_weight_change(
+    ctx: Context<LendingPoolConfigureBank>,
+) -> AstrolendResult {
+    let mut bank = ctx.accounts.bank.load_mut()?;
+
+    bank.cancel_weight_ramp(Clock::get()?.unix_timestamp)?;
+
+    emit!(LendingPoolBankWeightRampCancelEvent {
+        header: GroupEventHeader {
+            astrolend_group: ctx.accounts.astrolend_group.key(),
+            signer: Some(*ctx.accounts.admin.key),
+        },
+        bank: ctx.accounts.bank.key(),
+        mint: bank.mint,
+        asset_weight_init: I80F48::from(bank.config.asset_weight_init).to_num(),
+        asset_weight_maint: I80F48::from(bank.config.asset_weight_maint).to_num(),
+        meta: group_event_meta!(LendingPoolCancelWeightChange),
+    });
+
+    Ok(())
+}
+
+/// Write the targets of a scheduled weight change that ended to the bank config.
+pub(super) fn maybe_complete_weight_ramp(
+    bank: &mut Bank,
+    bank_pk: Pubkey,
+    current_timestamp: i64,
+    header: GroupEventHeader,
+    meta: GroupEventMeta,
+) {
+    if bank.complete_weight_ramp(current_timestamp) {
+        emit!(LendingPoolBankWeightRampCompleteEvent {
+            header,
+            bank: bank_pk,
+            mint: bank.mint,
+            asset_weight_init: I80F48::from(bank.config.asset_weight_init).to_num(),
+            asset_weight_maint: I80F48::from(bank.config.asset_weight_maint).to_num(),
+            meta,
+        });
+    }
+}
+
 #[derive(Accounts)]
 pub struct LendingPoolConfigureBank<'info> {
     pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
 
     #[account(
+        mut,
         address = astrolend_group.load()?.admin,
     )]
     pub admin: Signer<'info>,
@@ -50,6 +460,82 @@ pub struct LendingPoolConfigureBank<'info> {
         constraint = bank.load()?.group == astrolend_group.key(),
     )]
     pub bank: AccountLoader<'info, Bank>,
+
+    /// Only passed to `lending_pool_configure_bank` to propose a change while the group has a
+    /// config timelock, `None` otherwise.
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + PendingBankConfig::LEN,
+        seeds = [
+            PENDING_CONFIG_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub pending_config: Option<Box<Account<'info, PendingBankConfig>>>,
+
+    pub system_program: Option<Program<'info, System>>,
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolExecutePendingConfig<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        mut,
+        has_one = bank,
+        has_one = proposer,
+        close = proposer,
+        seeds = [
+            PENDING_CONFIG_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub pending_config: Box<Account<'info, PendingBankConfig>>,
+
+    /// CHECK: Checked against `pending_config`
+    #[account(mut)]
+    pub proposer: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolCancelPendingConfig<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        address = astrolend_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        mut,
+        has_one = bank,
+        has_one = proposer,
+        close = proposer,
+        seeds = [
+            PENDING_CONFIG_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub pending_config: Box<Account<'info, PendingBankConfig>>,
+
+    /// CHECK: Checked against `pending_config`
+    #[account(mut)]
+    pub proposer: AccountInfo<'info>,
 }
 
 pub fn lending_pool_setup_emissions(
@@ -66,11 +552,13 @@ pub fn lending_pool_setup_emissions(
     );
 
     bank.emissions_mint = ctx.accounts.emissions_mint.key();
+    bank.emissions_auth_bump = ctx.bumps.emissions_auth;
 
     bank.override_emissions_flag(emissions_flags);
+    bank.emissions_campaign_start_ts = Clock::get()?.unix_timestamp;
+    bank.emissions_checkpoint_ts = bank.emissions_campaign_start_ts;
 
     bank.emissions_rate = emissions_rate;
-    bank.emissions_remaining = I80F48::from_num(total_emissions).into();
 
     let initial_emissions_amount_pre_fee = utils::calculate_pre_fee_spl_deposit_amount(
         ctx.accounts.emissions_mint.to_account_info(),
@@ -78,6 +566,7 @@ pub fn lending_pool_setup_emissions(
         Clock::get()?.epoch,
     )?;
 
+    let balance_before = ctx.accounts.emissions_token_account.amount;
     transfer_checked(
         CpiContext::new(
             ctx.accounts.token_program.to_account_info(),
@@ -91,10 +580,58 @@ pub fn lending_pool_setup_emissions(
         initial_emissions_amount_pre_fee,
         ctx.accounts.emissions_mint.decimals,
     )?;
+    ctx.accounts.emissions_token_account.reload()?;
+
+    let credited_emissions = credit_emissions_funding(
+        total_emissions,
+        ctx.accounts
+            .emissions_token_account
+            .amount
+            .checked_sub(balance_before)
+            .ok_or_else(math_error!())?,
+        GroupEventHeader {
+            astrolend_group: ctx.accounts.astrolend_group.key(),
+            signer: Some(*ctx.accounts.admin.key),
+        },
+        group_event_meta!(LendingPoolSetupEmissions),
+        ctx.accounts.bank.key(),
+        bank.emissions_mint,
+    );
+    bank.emissions_remaining = I80F48::from_num(credited_emissions).into();
 
     Ok(())
 }
 
+/// Emissions a funding transfer adds to a campaign: the `expected` amount, or what the emissions
+/// vault `received` if the pre-fee estimate of the mint transfer fee fell short of it, so the
+/// campaign never promises more than the vault holds. Any excess received stays in the vault.
+fn credit_emissions_funding(
+    expected: u64,
+    received: u64,
+    header: GroupEventHeader,
+    meta: GroupEventMeta,
+    bank: Pubkey,
+    emissions_mint: Pubkey,
+) -> u64 {
+    if received != expected {
+        msg!(
+            "Emissions vault received {}, expected {}",
+            received,
+            expected
+        );
+        emit!(LendingPoolBankEmissionsFundingMismatchEvent {
+            header,
+            bank,
+            emissions_mint,
+            expected,
+            received,
+            meta,
+        });
+    }
+
+    expected.min(received)
+}
+
 #[derive(Accounts)]
 pub struct LendingPoolSetupEmissions<'info> {
     pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
@@ -164,9 +701,22 @@ pub fn lending_pool_update_emissions_parameters(
         AstrolendError::EmissionsUpdateError
     );
 
+    // Banks set up before the bump was stored need it persisted for emissions withdrawals
+    bank.emissions_auth_bump = bank.get_emissions_auth_bump(&ctx.accounts.bank.key());
+
+    // Accruals up to now are reserved at the current flags, rate and pool size
+    let current_timestamp = Clock::get()?.unix_timestamp;
+    bank.checkpoint_emissions(current_timestamp)?;
+
     if let Some(flags) = emissions_flags {
         msg!("Updating emissions flags to {:#010b}", flags);
-        bank.flags = flags;
+        let was_active = bank.is_emissions_active();
+        bank.override_flags(flags);
+
+        // Reactivated emissions don't pay out for the time they were inactive
+        if !was_active && bank.is_emissions_active() {
+            bank.emissions_campaign_start_ts = current_timestamp;
+        }
     }
 
     if let Some(rate) = emissions_rate {
@@ -175,16 +725,11 @@ pub fn lending_pool_update_emissions_parameters(
     }
 
     if let Some(additional_emissions) = additional_emissions {
-        bank.emissions_remaining = I80F48::from(bank.emissions_remaining)
-            .checked_add(I80F48::from_num(additional_emissions))
-            .ok_or_else(math_error!())?
-            .into();
-
-        msg!(
-            "Adding {} emissions, total {}",
-            additional_emissions,
-            I80F48::from(bank.emissions_remaining)
-        );
+        // Topping up an exhausted campaign restarts it, like reactivated emissions
+        if bank.emissions_exhausted_at != 0 {
+            bank.emissions_exhausted_at = 0;
+            bank.emissions_campaign_start_ts = current_timestamp;
+        }
 
         let additional_emissions_amount_pre_fee = utils::calculate_pre_fee_spl_deposit_amount(
             ctx.accounts.emissions_mint.to_account_info(),
@@ -192,6 +737,7 @@ pub fn lending_pool_update_emissions_parameters(
             Clock::get()?.epoch,
         )?;
 
+        let balance_before = ctx.accounts.emissions_token_account.amount;
         transfer_checked(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
@@ -205,6 +751,34 @@ pub fn lending_pool_update_emissions_parameters(
             additional_emissions_amount_pre_fee,
             ctx.accounts.emissions_mint.decimals,
         )?;
+        ctx.accounts.emissions_token_account.reload()?;
+
+        let credited_emissions = credit_emissions_funding(
+            additional_emissions,
+            ctx.accounts
+                .emissions_token_account
+                .amount
+                .checked_sub(balance_before)
+                .ok_or_else(math_error!())?,
+            GroupEventHeader {
+                astrolend_group: ctx.accounts.astrolend_group.key(),
+                signer: Some(*ctx.accounts.admin.key),
+            },
+            group_event_meta!(LendingPoolUpdateEmissionsParameters),
+            ctx.accounts.bank.key(),
+            bank.emissions_mint,
+        );
+
+        bank.emissions_remaining = I80F48::from(bank.emissions_remaining)
+            .checked_add(I80F48::from_num(credited_emissions))
+            .ok_or_else(math_error!())?
+            .into();
+
+        msg!(
+            "Adding {} emissions, total {}",
+            credited_emissions,
+            I80F48::from(bank.emissions_remaining)
+        );
     }
 
     Ok(())
@@ -245,3 +819,112 @@ pub struct LendingPoolUpdateEmissionsParameters<'info> {
 
     pub token_program: Interface<'info, TokenInterface>,
 }
+
+/// Close the emissions vault of a campaign that ran out and was fully withdrawn, see
+/// [Bank::check_emissions_closable]. Tokens left in the vault, fractions of a token the
+/// balances can't withdraw and accruals never settled, go to `destination_account` and the
+/// rent to the admin. The bank can set up a new campaign afterwards.
+pub fn lending_pool_close_emissions(ctx: Context<LendingPoolCloseEmissions>) -> AstrolendResult {
+    let mut bank = ctx.accounts.bank.load_mut()?;
+
+    // A campaign that ran out since the last checkpoint only shows it once checkpointed
+    bank.checkpoint_emissions(Clock::get()?.unix_timestamp)?;
+    bank.check_emissions_closable()?;
+
+    let bank_pk = ctx.accounts.bank.key();
+    let emissions_mint_pk = ctx.accounts.emissions_mint.key();
+    let emissions_auth_bump = ctx.bumps.emissions_auth;
+    let swept_amount = ctx.accounts.emissions_token_account.amount;
+
+    if swept_amount > 0 {
+        transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.emissions_token_account.to_account_info(),
+                    to: ctx.accounts.destination_account.to_account_info(),
+                    authority: ctx.accounts.emissions_auth.to_account_info(),
+                    mint: ctx.accounts.emissions_mint.to_account_info(),
+                },
+                emissions_signer!(bank_pk, emissions_mint_pk, emissions_auth_bump),
+            ),
+            swept_amount,
+            ctx.accounts.emissions_mint.decimals,
+        )?;
+    }
+
+    close_account(CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        CloseAccount {
+            account: ctx.accounts.emissions_token_account.to_account_info(),
+            destination: ctx.accounts.admin.to_account_info(),
+            authority: ctx.accounts.emissions_auth.to_account_info(),
+        },
+        emissions_signer!(bank_pk, emissions_mint_pk, emissions_auth_bump),
+    ))?;
+
+    bank.reset_emissions();
+
+    emit!(LendingPoolBankCloseEmissionsEvent {
+        header: GroupEventHeader {
+            astrolend_group: ctx.accounts.astrolend_group.key(),
+            signer: Some(*ctx.accounts.admin.key),
+        },
+        bank: bank_pk,
+        emissions_mint: emissions_mint_pk,
+        swept_amount: swept_amount as f64,
+        meta: group_event_meta!(LendingPoolCloseEmissions),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolCloseEmissions<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        address = astrolend_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        address = bank.load()?.emissions_mint,
+    )]
+    pub emissions_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [
+            EMISSIONS_AUTH_SEED.as_bytes(),
+            bank.key().as_ref(),
+            emissions_mint.key().as_ref(),
+        ],
+        bump
+    )]
+    /// CHECK: Asserted by PDA constraints
+    pub emissions_auth: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            EMISSIONS_TOKEN_ACCOUNT_SEED.as_bytes(),
+            bank.key().as_ref(),
+            emissions_mint.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub emissions_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Token account receiving the tokens left in the vault, checked by the token program
+    #[account(mut)]
+    pub destination_account: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
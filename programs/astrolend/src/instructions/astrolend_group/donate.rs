@@ -0,0 +1,134 @@
+use crate::{
+    check,
+    constants::LIQUIDITY_VAULT_SEED,
+    events::{GroupEventHeader, LendingPoolDonateEvent},
+    group_event_meta,
+    prelude::AstrolendError,
+    state::astrolend_group::{AstrolendGroup, Bank, BankOperationalState},
+    utils, AstrolendResult,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::TokenInterface;
+use fixed::types::I80F48;
+
+/// Donate `amount` to the depositors of a bank, pro-rata to their shares. Permissionless.
+///
+/// 1. Accrue interest
+/// 2. Transfer funds from the donor's token account to the bank's liquidity vault
+/// 3. Raise the asset share value by the amount received over the total asset shares
+///
+/// Will error if the bank has no deposits, the donation couldn't be claimed.
+/// Will error if the bank is paused.
+///
+/// Expected remaining account schema
+/// [
+///    bank_mint_ai (if token2022 mint),
+/// ]
+pub fn lending_pool_donate<'info>(
+    mut ctx: Context<'_, '_, 'info, 'info, LendingPoolDonate<'info>>,
+    amount: u64,
+) -> AstrolendResult {
+    let LendingPoolDonate {
+        astrolend_group,
+        bank: bank_loader,
+        donor,
+        signer_token_account,
+        bank_liquidity_vault,
+        token_program,
+    } = ctx.accounts;
+
+    let mut bank = bank_loader.load_mut()?;
+    check!(
+        !matches!(bank.config.operational_state, BankOperationalState::Paused),
+        AstrolendError::BankPaused
+    );
+
+    let clock = Clock::get()?;
+    bank.enter_reentrancy_guard(clock.slot)?;
+
+    let maybe_bank_mint = utils::maybe_take_bank_mint(
+        &mut ctx.remaining_accounts,
+        &bank,
+        token_program.key,
+        bank_liquidity_vault.owner,
+    )?;
+
+    bank.accrue_interest(
+        clock.unix_timestamp,
+        &*astrolend_group.load()?,
+        #[cfg(not(feature = "client"))]
+        bank_loader.key(),
+        #[cfg(not(feature = "client"))]
+        crate::instruction_discriminator!(LendingPoolDonate),
+    )?;
+
+    let amount_received = maybe_bank_mint
+        .as_ref()
+        .map(|mint| {
+            utils::calculate_post_fee_spl_deposit_amount(
+                mint.to_account_info(),
+                amount,
+                clock.epoch,
+            )
+        })
+        .transpose()?
+        .unwrap_or(amount);
+
+    bank.donate(amount_received)?;
+
+    bank.deposit_spl_transfer(
+        amount,
+        signer_token_account.to_account_info(),
+        bank_liquidity_vault.to_account_info(),
+        donor.to_account_info(),
+        maybe_bank_mint.as_ref(),
+        token_program.to_account_info(),
+        ctx.remaining_accounts,
+    )?;
+    bank.exit_reentrancy_guard();
+
+    emit!(LendingPoolDonateEvent {
+        header: GroupEventHeader {
+            astrolend_group: astrolend_group.key(),
+            signer: Some(donor.key()),
+        },
+        bank: bank_loader.key(),
+        mint: bank.mint,
+        donor: donor.key(),
+        amount: amount_received,
+        asset_share_value: I80F48::from(bank.asset_share_value).to_num::<f64>(),
+        meta: group_event_meta!(LendingPoolDonate),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolDonate<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    pub donor: Signer<'info>,
+
+    /// CHECK: Token mint/authority are checked at transfer
+    #[account(mut)]
+    pub signer_token_account: AccountInfo<'info>,
+
+    /// CHECK: Seed constraint check
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.liquidity_vault_bump,
+    )]
+    pub bank_liquidity_vault: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
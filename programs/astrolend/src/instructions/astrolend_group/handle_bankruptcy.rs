@@ -1,23 +1,28 @@
 use crate::{
-    bank_signer, check,
+    account_event_meta, bank_signer, check,
     constants::{
         INSURANCE_VAULT_AUTHORITY_SEED, INSURANCE_VAULT_SEED, LIQUIDITY_VAULT_SEED,
         PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG, ZERO_AMOUNT_THRESHOLD,
     },
     debug,
-    events::{AccountEventHeader, LendingPoolBankHandleBankruptcyEvent},
+    events::{
+        AccountEventHeader, LendingPoolBankHandleBankruptcyEvent, LendingPoolBankSweepDustEvent,
+    },
     math_error,
     prelude::AstrolendError,
     state::{
-        astrolend_account::{BankAccountWrapper, AstrolendAccount, RiskEngine, DISABLED_FLAG},
-        astrolend_group::{Bank, BankVaultType, AstrolendGroup},
+        astrolend_account::{
+            check_operation_allowed, BalanceSide, BankAccountWrapper, BankOperation,
+            AstrolendAccount, RiskEngine, BAD_DEBT_CANDIDATE_FLAG, DISABLED_FLAG,
+        },
+        astrolend_group::{AstrolendGroup, Bank, BankVaultType, LossSocializationReason},
     },
     utils, AstrolendResult,
 };
 use anchor_lang::prelude::*;
 use anchor_spl::token_interface::{TokenAccount, TokenInterface};
 use fixed::types::I80F48;
-use std::cmp::{max, min};
+use std::{cmp::max, ops::Not};
 
 /// Handle a bankrupt astrolend account.
 /// 1. Verify account is bankrupt, and lending account belonging to account contains bad debt.
@@ -25,6 +30,18 @@ use std::cmp::{max, min};
 /// 3. Cover the bad debt of the bankrupt account.
 /// 4. Transfer the insured amount from the insurance fund.
 /// 5. Socialize the loss between lenders if any.
+///
+/// Insurance stakes are haircut by their pro-rata part of the insurance payout and locked for
+/// `INSURANCE_STAKE_COOLDOWN`.
+///
+/// Banks with insurance disabled have no insurance fund, the insurance accounts can be omitted
+/// and all bad debt is socialized.
+///
+/// Accounts flagged with `BAD_DEBT_CANDIDATE_FLAG` may still hold dust collateral, it is swept
+/// to the insurance fees of its banks first. The banks of the health check remaining accounts
+/// must be writable for those. The dust tokens stay in the liquidity vault of each bank until
+/// `lending_pool_collect_bank_fees` moves them to its insurance vault, like any other insurance
+/// fee, so the vaults of every dust bank don't have to be passed here.
 pub fn lending_pool_handle_bankruptcy<'info>(
     mut ctx: Context<'_, '_, 'info, 'info, LendingPoolHandleBankruptcy<'info>>,
 ) -> AstrolendResult {
@@ -37,8 +54,12 @@ pub fn lending_pool_handle_bankruptcy<'info>(
         ..
     } = ctx.accounts;
     let bank = bank_loader.load()?;
-    let maybe_bank_mint =
-        utils::maybe_take_bank_mint(&mut ctx.remaining_accounts, &bank, token_program.key)?;
+    let maybe_bank_mint = utils::maybe_take_bank_mint(
+        &mut ctx.remaining_accounts,
+        &bank,
+        token_program.key,
+        ctx.accounts.liquidity_vault.owner,
+    )?;
 
     let clock = Clock::get()?;
 
@@ -53,14 +74,57 @@ pub fn lending_pool_handle_bankruptcy<'info>(
 
     let mut astrolend_account = astrolend_account_loader.load_mut()?;
 
-    RiskEngine::new(&astrolend_account, ctx.remaining_accounts)?.check_account_bankrupt()?;
+    check_operation_allowed(
+        BankOperation::Bankruptcy,
+        &*bank_loader.load()?,
+        &astrolend_account,
+    )?;
+
+    RiskEngine::new(
+        &*astrolend_group_loader.load()?,
+        &astrolend_account,
+        ctx.remaining_accounts,
+    )?
+    .check_account_bankrupt()?;
+
+    if astrolend_account.get_flag(BAD_DEBT_CANDIDATE_FLAG) {
+        let swept = sweep_dust(
+            &mut astrolend_account,
+            &*astrolend_group_loader.load()?,
+            &bank_loader.key(),
+            ctx.remaining_accounts,
+            clock.unix_timestamp,
+        )?;
+
+        for (bank, mint, amount) in swept {
+            emit!(LendingPoolBankSweepDustEvent {
+                header: AccountEventHeader {
+                    signer: Some(ctx.accounts.signer.key()),
+                    astrolend_account: astrolend_account_loader.key(),
+                    astrolend_account_authority: astrolend_account.authority,
+                    astrolend_group: astrolend_account.group,
+                },
+                bank,
+                mint,
+                amount: amount.to_num::<f64>(),
+                meta: account_event_meta!(
+                    LendingPoolHandleBankruptcy,
+                    astrolend_account.account_tag
+                ),
+            });
+        }
+    }
 
     let mut bank = bank_loader.load_mut()?;
+    bank.enter_reentrancy_guard(clock.slot)?;
 
     bank.accrue_interest(
         clock.unix_timestamp,
+        &*astrolend_group_loader.load()?,
         #[cfg(not(feature = "client"))]
         bank_loader.key(),
+        #[cfg(not(feature = "client"))]
+        crate::instruction_discriminator!(LendingPoolHandleBankruptcy),
     )?;
 
     let lending_account_balance = astrolend_account
@@ -83,66 +147,97 @@ pub fn lending_pool_handle_bankruptcy<'info>(
         AstrolendError::BalanceNotBadDebt
     );
 
-    let (covered_by_insurance, socialized_loss) = {
-        let available_insurance_fund: I80F48 = maybe_bank_mint
+    let maybe_insurance_vault = if bank.is_insurance_disabled() {
+        None
+    } else {
+        Some(
+            insurance_vault
+                .as_ref()
+                .ok_or(AstrolendError::InsuranceVaultRequired)?,
+        )
+    };
+
+    // Insurance covers the bad debt rounded up, grossed up by the transfer fee so the liquidity
+    // vault receives the full coverage, and capped at the insurance vault balance.
+    let bad_debt_rounded_up: u64 = bad_debt
+        .checked_ceil()
+        .ok_or_else(math_error!())?
+        .checked_to_num()
+        .ok_or_else(math_error!())?;
+
+    let insurance_coverage_deposit_pre_fee = match maybe_insurance_vault {
+        Some(insurance_vault) => maybe_bank_mint
             .as_ref()
             .map(|mint| {
-                utils::calculate_post_fee_spl_deposit_amount(
+                utils::calculate_pre_fee_spl_deposit_amount(
                     mint.to_account_info(),
-                    insurance_vault.amount,
+                    bad_debt_rounded_up,
                     clock.epoch,
                 )
             })
             .transpose()?
-            .unwrap_or(insurance_vault.amount)
-            .into();
-
-        let covered_by_insurance = min(bad_debt, available_insurance_fund);
-        let socialized_loss = max(bad_debt - covered_by_insurance, I80F48::ZERO);
-
-        (covered_by_insurance, socialized_loss)
+            .unwrap_or(bad_debt_rounded_up)
+            .min(insurance_vault.amount),
+        None => 0,
     };
 
-    // Cover bad debt with insurance funds.
-    let covered_by_insurance_rounded_up: u64 = covered_by_insurance
-        .checked_ceil()
-        .ok_or_else(math_error!())?
-        .checked_to_num()
-        .ok_or_else(math_error!())?;
-    debug!(
-        "covered_by_insurance_rounded_up: {}; socialized loss {}",
-        covered_by_insurance_rounded_up, socialized_loss
-    );
-
-    let insurance_coverage_deposit_pre_fee = maybe_bank_mint
+    // Only the remainder left uncovered by what the liquidity vault actually receives is
+    // socialized.
+    let covered_by_insurance: I80F48 = maybe_bank_mint
         .as_ref()
         .map(|mint| {
-            utils::calculate_pre_fee_spl_deposit_amount(
+            utils::calculate_post_fee_spl_deposit_amount(
                 mint.to_account_info(),
-                covered_by_insurance_rounded_up,
+                insurance_coverage_deposit_pre_fee,
                 clock.epoch,
             )
         })
         .transpose()?
-        .unwrap_or(covered_by_insurance_rounded_up);
-
-    bank.withdraw_spl_transfer(
-        insurance_coverage_deposit_pre_fee,
-        ctx.accounts.insurance_vault.to_account_info(),
-        ctx.accounts.liquidity_vault.to_account_info(),
-        ctx.accounts.insurance_vault_authority.to_account_info(),
-        maybe_bank_mint.as_ref(),
-        token_program.to_account_info(),
-        bank_signer!(
-            BankVaultType::Insurance,
-            bank_loader.key(),
-            bank.insurance_vault_authority_bump
-        ),
-        ctx.remaining_accounts,
-    )?;
+        .unwrap_or(insurance_coverage_deposit_pre_fee)
+        .into();
+    let socialized_loss = max(bad_debt - covered_by_insurance, I80F48::ZERO);
+
+    debug!(
+        "insurance coverage pre fee: {}; covered by insurance {}; socialized loss {}",
+        insurance_coverage_deposit_pre_fee, covered_by_insurance, socialized_loss
+    );
+
+    if let Some(insurance_vault) = maybe_insurance_vault {
+        // Stakers and the group admin take the payout pro-rata
+        bank.haircut_insurance_stakes(
+            insurance_coverage_deposit_pre_fee,
+            insurance_vault.amount,
+            clock.unix_timestamp,
+        )?;
+
+        let insurance_vault_authority = ctx
+            .accounts
+            .insurance_vault_authority
+            .as_ref()
+            .ok_or(AstrolendError::InsuranceVaultRequired)?;
+
+        let insurance_vault_authority_bump = bank.insurance_vault_authority_bump;
+        bank.withdraw_spl_transfer(
+            insurance_coverage_deposit_pre_fee,
+            insurance_vault.to_account_info(),
+            ctx.accounts.liquidity_vault.to_account_info(),
+            insurance_vault_authority.to_account_info(),
+            maybe_bank_mint.as_ref(),
+            token_program.to_account_info(),
+            bank_signer!(
+                BankVaultType::Insurance,
+                bank_loader.key(),
+                insurance_vault_authority_bump
+            ),
+            ctx.remaining_accounts,
+        )?;
+    }
 
     // Socialize bad debt among depositors.
-    bank.socialize_loss(socialized_loss)?;
+    let pre_asset_share_value: I80F48 = bank.asset_share_value.into();
+    bank.socialize_loss(socialized_loss, LossSocializationReason::BadDebt)?;
+    let post_asset_share_value: I80F48 = bank.asset_share_value.into();
+    bank.last_bankruptcy_slot = clock.slot;
 
     // Settle bad debt.
     // The liabilities of this account and global total liabilities are reduced by `bad_debt`
@@ -152,6 +247,7 @@ pub fn lending_pool_handle_bankruptcy<'info>(
         &mut astrolend_account.lending_account,
     )?
     .repay(bad_debt)?;
+    bank.exit_reentrancy_guard();
 
     astrolend_account.set_flag(DISABLED_FLAG);
 
@@ -167,11 +263,75 @@ pub fn lending_pool_handle_bankruptcy<'info>(
         bad_debt: bad_debt.to_num::<f64>(),
         covered_amount: covered_by_insurance.to_num::<f64>(),
         socialized_amount: socialized_loss.to_num::<f64>(),
+        pre_asset_share_value: pre_asset_share_value.to_num::<f64>(),
+        post_asset_share_value: post_asset_share_value.to_num::<f64>(),
+        cumulative_socialized_loss: I80F48::from(bank.cumulative_socialized_loss).to_num::<f64>(),
+        meta: account_event_meta!(LendingPoolHandleBankruptcy, astrolend_account.account_tag),
     });
 
     Ok(())
 }
 
+/// Withdraw every asset balance of the account outside of `liability_bank_pk` and credit it to
+/// the outstanding insurance fees of the bank, collected to the insurance vault with the other
+/// fees. Banks with insurance disabled credit the group fees instead. The banks are taken from
+/// the health check `remaining_ais`.
+///
+/// Returns the (bank, mint, native amount) of each swept balance.
+fn sweep_dust<'info>(
+    astrolend_account: &mut AstrolendAccount,
+    astrolend_group: &AstrolendGroup,
+    liability_bank_pk: &Pubkey,
+    remaining_ais: &'info [AccountInfo<'info>],
+    current_timestamp: i64,
+) -> AstrolendResult<Vec<(Pubkey, Pubkey, I80F48)>> {
+    let dust_bank_pks = astrolend_account
+        .lending_account
+        .balances
+        .iter()
+        .filter(|balance| {
+            balance.active
+                && balance.bank_pk != *liability_bank_pk
+                && balance.is_empty(BalanceSide::Assets).not()
+        })
+        .map(|balance| balance.bank_pk)
+        .collect::<Vec<_>>();
+
+    let mut swept = Vec::with_capacity(dust_bank_pks.len());
+    for bank_pk in dust_bank_pks {
+        let bank_ai = remaining_ais
+            .iter()
+            .step_by(2)
+            .find(|ai| ai.key == &bank_pk)
+            .ok_or(AstrolendError::InvalidBankAccount)?;
+        let bank_loader = AccountLoader::<Bank>::try_from(bank_ai)?;
+        let mut bank = bank_loader.load_mut()?;
+
+        bank.accrue_interest(
+            current_timestamp,
+            astrolend_group,
+            #[cfg(not(feature = "client"))]
+            bank_pk,
+            #[cfg(not(feature = "client"))]
+            crate::instruction_discriminator!(LendingPoolHandleBankruptcy),
+        )?;
+
+        let amount = BankAccountWrapper::find(
+            &bank_pk,
+            &mut bank,
+            &mut astrolend_account.lending_account,
+        )?
+        .withdraw_all_at(current_timestamp as u64)?;
+        // `withdraw_all` already credits the fraction below one native unit
+        bank.add_insurance_fees_outstanding(I80F48::from_num(amount))?;
+
+        debug!("Swept {} of dust from bank {}", amount, bank_pk);
+        swept.push((bank_pk, bank.mint, I80F48::from_num(amount)));
+    }
+
+    Ok(swept)
+}
+
 #[derive(Accounts)]
 pub struct LendingPoolHandleBankruptcy<'info> {
     pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
@@ -210,7 +370,7 @@ pub struct LendingPoolHandleBankruptcy<'info> {
         ],
         bump = bank.load()?.insurance_vault_bump
     )]
-    pub insurance_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub insurance_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
     /// CHECK: Seed constraint
     #[account(
@@ -220,7 +380,7 @@ pub struct LendingPoolHandleBankruptcy<'info> {
         ],
         bump = bank.load()?.insurance_vault_authority_bump
     )]
-    pub insurance_vault_authority: AccountInfo<'info>,
+    pub insurance_vault_authority: Option<AccountInfo<'info>>,
 
     pub token_program: Interface<'info, TokenInterface>,
 }
@@ -1,5 +1,5 @@
-use crate::events::{GroupEventHeader, AstrolendGroupCreateEvent};
-use crate::{state::astrolend_group::AstrolendGroup, AstrolendResult};
+use crate::events::{AstrolendGroupCreateEvent, GroupEventHeader};
+use crate::{group_event_meta, state::astrolend_group::AstrolendGroup, AstrolendResult};
 use anchor_lang::prelude::*;
 
 pub fn initialize_group(ctx: Context<AstrolendGroupInitialize>) -> AstrolendResult {
@@ -10,8 +10,9 @@ pub fn initialize_group(ctx: Context<AstrolendGroupInitialize>) -> AstrolendResu
     emit!(AstrolendGroupCreateEvent {
         header: GroupEventHeader {
             astrolend_group: ctx.accounts.astrolend_group.key(),
-            signer: Some(*ctx.accounts.admin.key)
+            signer: Some(*ctx.accounts.admin.key),
         },
+        meta: group_event_meta!(AstrolendGroupInitialize),
     });
 
     Ok(())
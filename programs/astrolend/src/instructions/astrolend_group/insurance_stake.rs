@@ -0,0 +1,340 @@
+use crate::{
+    bank_signer, check,
+    constants::{
+        INSURANCE_STAKE_COOLDOWN, INSURANCE_STAKE_SEED, INSURANCE_VAULT_AUTHORITY_SEED,
+        INSURANCE_VAULT_SEED,
+    },
+    events::{GroupEventHeader, LendingPoolInsuranceStakeEvent},
+    group_event_meta, math_error,
+    prelude::AstrolendError,
+    state::astrolend_group::{AstrolendGroup, Bank, BankVaultType, InsuranceStake},
+    utils, AstrolendResult,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::TokenInterface;
+use fixed::types::I80F48;
+
+/// Create the insurance stake of `depositor` in the bank insurance vault
+pub fn lending_pool_init_insurance_stake(
+    ctx: Context<LendingPoolInitInsuranceStake>,
+) -> AstrolendResult {
+    let bank = ctx.accounts.bank.load()?;
+    check!(
+        !bank.is_insurance_disabled(),
+        AstrolendError::InsuranceVaultRequired
+    );
+
+    let mut insurance_stake = ctx.accounts.insurance_stake.load_init()?;
+
+    insurance_stake.bank = ctx.accounts.bank.key();
+    insurance_stake.depositor = ctx.accounts.depositor.key();
+    insurance_stake.epoch = bank.insurance_stake_epoch;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolInitInsuranceStake<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    pub depositor: Signer<'info>,
+
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = fee_payer,
+        space = 8 + std::mem::size_of::<InsuranceStake>(),
+        seeds = [
+            INSURANCE_STAKE_SEED.as_bytes(),
+            bank.key().as_ref(),
+            depositor.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub insurance_stake: AccountLoader<'info, InsuranceStake>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Deposit into the bank insurance vault for insurance stake shares. The stake shares
+/// bankruptcies covered by the insurance vault pro-rata with the admin part of the vault.
+///
+/// Expected remaining account schema
+/// [
+///    bank_mint_ai (if token2022 mint),
+/// ]
+pub fn lending_pool_deposit_insurance<'info>(
+    mut ctx: Context<'_, '_, 'info, 'info, LendingPoolDepositInsurance<'info>>,
+    amount: u64,
+) -> AstrolendResult {
+    let LendingPoolDepositInsurance {
+        astrolend_group,
+        bank: bank_loader,
+        depositor,
+        insurance_stake,
+        signer_token_account,
+        insurance_vault,
+        token_program,
+    } = ctx.accounts;
+
+    let clock = Clock::get()?;
+    let mut bank = bank_loader.load_mut()?;
+    bank.enter_reentrancy_guard(clock.slot)?;
+    let maybe_bank_mint = utils::maybe_take_bank_mint(
+        &mut ctx.remaining_accounts,
+        &bank,
+        token_program.key,
+        insurance_vault.owner,
+    )?;
+
+    let amount_received = maybe_bank_mint
+        .as_ref()
+        .map(|mint| {
+            utils::calculate_post_fee_spl_deposit_amount(
+                mint.to_account_info(),
+                amount,
+                clock.epoch,
+            )
+        })
+        .transpose()?
+        .unwrap_or(amount);
+
+    bank.deposit_insurance_spl_transfer(
+        amount,
+        signer_token_account.to_account_info(),
+        insurance_vault.to_account_info(),
+        depositor.to_account_info(),
+        maybe_bank_mint.as_ref(),
+        token_program.to_account_info(),
+        ctx.remaining_accounts,
+    )?;
+
+    let shares = bank.stake_insurance(I80F48::from_num(amount_received))?;
+
+    let mut insurance_stake = insurance_stake.load_mut()?;
+    insurance_stake.shares = insurance_stake
+        .get_shares(&bank)
+        .checked_add(shares)
+        .ok_or_else(math_error!())?
+        .into();
+    insurance_stake.epoch = bank.insurance_stake_epoch;
+    bank.exit_reentrancy_guard();
+
+    emit!(LendingPoolInsuranceStakeEvent {
+        header: GroupEventHeader {
+            astrolend_group: astrolend_group.key(),
+            signer: Some(depositor.key()),
+        },
+        bank: bank_loader.key(),
+        mint: bank.mint,
+        depositor: depositor.key(),
+        amount: amount_received as f64,
+        shares: shares.to_num::<f64>(),
+        deposit: true,
+        meta: group_event_meta!(LendingPoolDepositInsurance),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolDepositInsurance<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    pub depositor: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            INSURANCE_STAKE_SEED.as_bytes(),
+            bank.key().as_ref(),
+            depositor.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub insurance_stake: AccountLoader<'info, InsuranceStake>,
+
+    /// CHECK: Token mint/authority are checked at transfer
+    #[account(mut)]
+    pub signer_token_account: AccountInfo<'info>,
+
+    /// CHECK: Seed constraint check
+    #[account(
+        mut,
+        seeds = [
+            INSURANCE_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_bump,
+    )]
+    pub insurance_vault: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Withdraw `amount` of the depositor insurance stake, or all of it with `withdraw_all`, at the
+/// current stake share value.
+///
+/// Will error within `INSURANCE_STAKE_COOLDOWN` of a bankruptcy covered by the insurance vault.
+///
+/// Expected remaining account schema
+/// [
+///    bank_mint_ai (if token2022 mint),
+/// ]
+pub fn lending_pool_withdraw_insurance_stake<'info>(
+    mut ctx: Context<'_, '_, 'info, 'info, LendingPoolWithdrawInsuranceStake<'info>>,
+    amount: u64,
+    withdraw_all: Option<bool>,
+) -> AstrolendResult {
+    let LendingPoolWithdrawInsuranceStake {
+        astrolend_group,
+        bank: bank_loader,
+        depositor,
+        insurance_stake,
+        insurance_vault,
+        insurance_vault_authority,
+        dst_token_account,
+        token_program,
+    } = ctx.accounts;
+
+    let clock = Clock::get()?;
+    let mut bank = bank_loader.load_mut()?;
+    bank.enter_reentrancy_guard(clock.slot)?;
+    let maybe_bank_mint = utils::maybe_take_bank_mint(
+        &mut ctx.remaining_accounts,
+        &bank,
+        token_program.key,
+        insurance_vault.owner,
+    )?;
+
+    check!(
+        clock.unix_timestamp
+            >= bank.last_insurance_payout_ts.saturating_add(INSURANCE_STAKE_COOLDOWN),
+        AstrolendError::InsuranceStakeCooldown
+    );
+
+    let mut insurance_stake = insurance_stake.load_mut()?;
+    let stake_shares = insurance_stake.get_shares(&bank);
+
+    let withdraw_all = withdraw_all.unwrap_or(false);
+    let shares = if withdraw_all {
+        stake_shares
+    } else {
+        bank.get_insurance_stake_shares(I80F48::from_num(amount))?
+    };
+    check!(
+        shares <= stake_shares,
+        AstrolendError::InsufficientInsuranceStake
+    );
+
+    let unstaked_amount = bank.unstake_insurance(shares)?;
+
+    insurance_stake.shares = (stake_shares - shares).into();
+    insurance_stake.epoch = bank.insurance_stake_epoch;
+
+    let transfer_amount = if withdraw_all {
+        unstaked_amount
+            .checked_floor()
+            .ok_or_else(math_error!())?
+            .checked_to_num()
+            .ok_or_else(math_error!())?
+    } else {
+        amount
+    };
+
+    Bank::vault_spl_transfer(
+        transfer_amount,
+        insurance_vault.to_account_info(),
+        dst_token_account.to_account_info(),
+        insurance_vault_authority.to_account_info(),
+        maybe_bank_mint.as_ref(),
+        token_program.to_account_info(),
+        bank_signer!(
+            BankVaultType::Insurance,
+            bank_loader.key(),
+            bank.insurance_vault_authority_bump
+        ),
+        ctx.remaining_accounts,
+    )?;
+    bank.exit_reentrancy_guard();
+
+    emit!(LendingPoolInsuranceStakeEvent {
+        header: GroupEventHeader {
+            astrolend_group: astrolend_group.key(),
+            signer: Some(depositor.key()),
+        },
+        bank: bank_loader.key(),
+        mint: bank.mint,
+        depositor: depositor.key(),
+        amount: unstaked_amount.to_num::<f64>(),
+        shares: shares.to_num::<f64>(),
+        deposit: false,
+        meta: group_event_meta!(LendingPoolWithdrawInsuranceStake),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolWithdrawInsuranceStake<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    pub depositor: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            INSURANCE_STAKE_SEED.as_bytes(),
+            bank.key().as_ref(),
+            depositor.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub insurance_stake: AccountLoader<'info, InsuranceStake>,
+
+    /// CHECK: Seed constraint check
+    #[account(
+        mut,
+        seeds = [
+            INSURANCE_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_bump,
+    )]
+    pub insurance_vault: AccountInfo<'info>,
+
+    /// CHECK: Seed constraint check
+    #[account(
+        seeds = [
+            INSURANCE_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_authority_bump,
+    )]
+    pub insurance_vault_authority: AccountInfo<'info>,
+
+    /// CHECK: Token mint is checked at transfer
+    #[account(mut)]
+    pub dst_token_account: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
@@ -0,0 +1,84 @@
+use crate::{
+    check,
+    events::{AstrolendGroupLookupTableEvent, GroupEventHeader},
+    group_event_meta,
+    prelude::AstrolendError,
+    state::astrolend_group::{AstrolendGroup, Bank},
+    AstrolendResult,
+};
+use anchor_lang::prelude::*;
+use solana_program::address_lookup_table::{self, state::AddressLookupTable};
+
+/// Register the address lookup table clients should use for the group's transactions.
+///
+/// Best effort check: every bank passed in remaining accounts must belong to the group and
+/// have its key and oracle keys in the table. Banks added later aren't checked.
+///
+/// Admin only
+///
+/// Remaining accounts: banks of the group.
+pub fn set_lookup_table<'info>(
+    ctx: Context<'_, '_, 'info, 'info, AstrolendGroupSetLookupTable<'info>>,
+) -> AstrolendResult {
+    let group_key = ctx.accounts.astrolend_group.key();
+    let lookup_table_ai = &ctx.accounts.lookup_table;
+
+    check!(
+        *lookup_table_ai.owner == address_lookup_table::program::ID,
+        AstrolendError::InvalidLookupTable
+    );
+
+    let lookup_table_data = lookup_table_ai.try_borrow_data()?;
+    let lookup_table = AddressLookupTable::deserialize(&lookup_table_data)
+        .map_err(|_| AstrolendError::InvalidLookupTable)?;
+
+    for bank_ai in ctx.remaining_accounts.iter() {
+        let bank_loader = AccountLoader::<Bank>::try_from(bank_ai)?;
+        let bank = bank_loader.load()?;
+
+        check!(bank.group == group_key, AstrolendError::InvalidBankAccount);
+
+        let missing_key = std::iter::once(bank_loader.key())
+            .chain(
+                bank.config
+                    .oracle_keys
+                    .iter()
+                    .copied()
+                    .filter(|key| *key != Pubkey::default()),
+            )
+            .find(|key| !lookup_table.addresses.contains(key));
+
+        if let Some(key) = missing_key {
+            msg!("{} is missing from the lookup table", key);
+            return err!(AstrolendError::InvalidLookupTable);
+        }
+    }
+
+    let mut astrolend_group = ctx.accounts.astrolend_group.load_mut()?;
+    astrolend_group.registered_lookup_table = lookup_table_ai.key();
+
+    emit!(AstrolendGroupLookupTableEvent {
+        header: GroupEventHeader {
+            astrolend_group: group_key,
+            signer: Some(ctx.accounts.admin.key()),
+        },
+        lookup_table: lookup_table_ai.key(),
+        meta: group_event_meta!(AstrolendGroupSetLookupTable),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AstrolendGroupSetLookupTable<'info> {
+    #[account(mut)]
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        address = astrolend_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+
+    /// CHECK: Owner and contents checked in the instruction
+    pub lookup_table: AccountInfo<'info>,
+}
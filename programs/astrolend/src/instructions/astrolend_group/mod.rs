@@ -1,15 +1,29 @@
 mod accrue_bank_interest;
 mod add_pool;
+mod checkpoint_banks;
+mod clear_stuck_guard;
 mod collect_bank_fees;
 mod configure;
 mod configure_bank;
+mod donate;
 mod handle_bankruptcy;
 mod initialize;
+mod insurance_stake;
+mod lookup_table;
+mod update_oracle_params;
+mod whitelist;
 
 pub use accrue_bank_interest::*;
 pub use add_pool::*;
+pub use checkpoint_banks::*;
+pub use clear_stuck_guard::*;
 pub use collect_bank_fees::*;
 pub use configure::*;
 pub use configure_bank::*;
+pub use donate::*;
 pub use handle_bankruptcy::*;
 pub use initialize::*;
+pub use insurance_stake::*;
+pub use lookup_table::*;
+pub use update_oracle_params::*;
+pub use whitelist::*;
@@ -0,0 +1,78 @@
+use crate::{
+    check,
+    events::{GroupEventHeader, LendingPoolBankOracleParamsUpdateEvent},
+    group_event_meta,
+    prelude::AstrolendError,
+    state::astrolend_group::{AstrolendGroup, Bank, BankConfigOpt, OracleParamsOpt},
+    AstrolendResult,
+};
+use anchor_lang::prelude::*;
+
+/// Apply the same `oracle_params` to every bank passed in remaining accounts, e.g. to tighten
+/// the confidence cap of the whole group in one transaction.
+///
+/// Banks with a frozen config, see `CONFIG_FROZEN_FLAG`, are left untouched and reported as
+/// skipped. Any other failing bank aborts the whole update.
+///
+/// Admin only
+///
+/// Remaining accounts: banks of the group, writable.
+pub fn lending_pool_update_oracle_max_confidence<'info>(
+    ctx: Context<'_, '_, 'info, 'info, LendingPoolUpdateOracleMaxConfidence<'info>>,
+    oracle_params: OracleParamsOpt,
+) -> AstrolendResult {
+    let group_key = ctx.accounts.astrolend_group.key();
+
+    check!(
+        !ctx.remaining_accounts.is_empty(),
+        AstrolendError::InvalidBankAccount
+    );
+
+    for bank_ai in ctx.remaining_accounts.iter() {
+        let bank_loader = AccountLoader::<Bank>::try_from(bank_ai)?;
+        let mut bank = bank_loader.load_mut()?;
+
+        check!(bank.group == group_key, AstrolendError::InvalidBankAccount);
+
+        let old_config = bank.config;
+        let skipped = bank.is_config_frozen();
+
+        if skipped {
+            msg!("Bank {} config is frozen, skipped", bank_loader.key());
+        } else {
+            bank.configure(&BankConfigOpt {
+                oracle_params: Some(oracle_params),
+                ..Default::default()
+            })?;
+        }
+
+        emit!(LendingPoolBankOracleParamsUpdateEvent {
+            header: GroupEventHeader {
+                astrolend_group: group_key,
+                signer: Some(ctx.accounts.admin.key()),
+            },
+            bank: bank_loader.key(),
+            mint: bank.mint,
+            skipped,
+            old_max_age: old_config.oracle_max_age,
+            new_max_age: bank.config.oracle_max_age,
+            old_max_confidence_bps: old_config.oracle_max_confidence_bps,
+            new_max_confidence_bps: bank.config.oracle_max_confidence_bps,
+            old_price_bias_mode: old_config.oracle_price_bias_mode,
+            new_price_bias_mode: bank.config.oracle_price_bias_mode,
+            meta: group_event_meta!(LendingPoolUpdateOracleMaxConfidence),
+        });
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolUpdateOracleMaxConfidence<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        address = astrolend_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+}
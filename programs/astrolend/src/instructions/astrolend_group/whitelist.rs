@@ -0,0 +1,100 @@
+use crate::{
+    constants::WHITELIST_SEED,
+    events::{AstrolendGroupWhitelistEvent, GroupEventHeader},
+    group_event_meta,
+    state::astrolend_group::{AstrolendGroup, WhitelistEntry},
+    AstrolendResult,
+};
+use anchor_lang::prelude::*;
+
+/// Whitelist a wallet in the group, only enforced while `WHITELIST_REQUIRED_FLAG` is set
+///
+/// Admin only
+pub fn group_whitelist_add(ctx: Context<GroupWhitelistAdd>, wallet: Pubkey) -> AstrolendResult {
+    let mut whitelist_entry = ctx.accounts.whitelist_entry.load_init()?;
+
+    whitelist_entry.group = ctx.accounts.astrolend_group.key();
+    whitelist_entry.wallet = wallet;
+
+    emit!(AstrolendGroupWhitelistEvent {
+        header: GroupEventHeader {
+            astrolend_group: ctx.accounts.astrolend_group.key(),
+            signer: Some(*ctx.accounts.admin.key),
+        },
+        wallet,
+        whitelisted: true,
+        meta: group_event_meta!(GroupWhitelistAdd),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct GroupWhitelistAdd<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        address = astrolend_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = fee_payer,
+        space = 8 + std::mem::size_of::<WhitelistEntry>(),
+        seeds = [
+            WHITELIST_SEED.as_bytes(),
+            astrolend_group.key().as_ref(),
+            wallet.as_ref(),
+        ],
+        bump,
+    )]
+    pub whitelist_entry: AccountLoader<'info, WhitelistEntry>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Remove a wallet from the group whitelist, its accounts become reduce only while
+/// `WHITELIST_REQUIRED_FLAG` is set
+///
+/// Admin only
+pub fn group_whitelist_remove(ctx: Context<GroupWhitelistRemove>) -> AstrolendResult {
+    let wallet = ctx.accounts.whitelist_entry.load()?.wallet;
+
+    emit!(AstrolendGroupWhitelistEvent {
+        header: GroupEventHeader {
+            astrolend_group: ctx.accounts.astrolend_group.key(),
+            signer: Some(*ctx.accounts.admin.key),
+        },
+        wallet,
+        whitelisted: false,
+        meta: group_event_meta!(GroupWhitelistRemove),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct GroupWhitelistRemove<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        address = astrolend_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        close = rent_receiver,
+        constraint = whitelist_entry.load()?.group == astrolend_group.key(),
+    )]
+    pub whitelist_entry: AccountLoader<'info, WhitelistEntry>,
+
+    /// CHECK: Receives the rent of the closed entry
+    #[account(mut)]
+    pub rent_receiver: AccountInfo<'info>,
+}
@@ -10,7 +10,13 @@ pub mod utils;
 use anchor_lang::prelude::*;
 use instructions::*;
 use prelude::*;
-use state::astrolend_group::{BankConfigCompact, BankConfigOpt};
+use state::{
+    astrolend_account::AccountConfigOpt,
+    astrolend_group::{
+        BankConfigCompact, BankConfigOpt, EmodeEntryConfig, FeeSplitEntryConfig, OracleConfig,
+        OracleParamsOpt, WrappedI80F48,
+    },
+};
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "mainnet-beta")] {
@@ -32,6 +38,7 @@ pub mod astrolend {
         astrolend_group::initialize_group(ctx)
     }
 
+    /// Update the group parameters set in `config`, the others are left as they are
     pub fn astrolend_group_configure(
         ctx: Context<AstrolendGroupConfigure>,
         config: GroupConfig,
@@ -39,6 +46,39 @@ pub mod astrolend {
         astrolend_group::configure(ctx, config)
     }
 
+    /// Replace the group emode table of boosted collateral weights for tagged bank pairs
+    pub fn astrolend_group_configure_emode(
+        ctx: Context<AstrolendGroupConfigure>,
+        entries: Vec<EmodeEntryConfig>,
+    ) -> AstrolendResult {
+        astrolend_group::configure_emode(ctx, entries)
+    }
+
+    /// Replace the group fee split, destinations and bps fee vault distributions are split by
+    pub fn astrolend_group_configure_fee_split(
+        ctx: Context<AstrolendGroupConfigure>,
+        entries: Vec<FeeSplitEntryConfig>,
+    ) -> AstrolendResult {
+        astrolend_group::configure_fee_split(ctx, entries)
+    }
+
+    /// Register the address lookup table clients should use for the group's transactions
+    pub fn astrolend_group_set_lookup_table<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AstrolendGroupSetLookupTable<'info>>,
+    ) -> AstrolendResult {
+        astrolend_group::set_lookup_table(ctx)
+    }
+
+    /// Allow a wallet to open accounts in a group that requires a whitelist
+    pub fn group_whitelist_add(ctx: Context<GroupWhitelistAdd>, wallet: Pubkey) -> AstrolendResult {
+        astrolend_group::group_whitelist_add(ctx, wallet)
+    }
+
+    /// Remove a wallet from the group whitelist, its existing accounts become reduce only
+    pub fn group_whitelist_remove(ctx: Context<GroupWhitelistRemove>) -> AstrolendResult {
+        astrolend_group::group_whitelist_remove(ctx)
+    }
+
     pub fn lending_pool_add_bank(
         ctx: Context<LendingPoolAddBank>,
         bank_config: BankConfigCompact,
@@ -64,6 +104,61 @@ pub mod astrolend {
         astrolend_group::lending_pool_configure_bank(ctx, bank_config_opt)
     }
 
+    /// Apply a bank config change once the group config timelock elapsed, permissionless
+    pub fn lending_pool_execute_pending_config(
+        ctx: Context<LendingPoolExecutePendingConfig>,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_execute_pending_config(ctx)
+    }
+
+    /// Drop a bank config change waiting on the group config timelock
+    pub fn lending_pool_cancel_pending_config(
+        ctx: Context<LendingPoolCancelPendingConfig>,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_cancel_pending_config(ctx)
+    }
+
+    pub fn lending_pool_configure_bank_oracle_unchecked(
+        ctx: Context<LendingPoolConfigureBank>,
+        oracle: OracleConfig,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_configure_bank_oracle_unchecked(ctx, oracle)
+    }
+
+    pub fn lending_pool_set_bank_settlement_price(
+        ctx: Context<LendingPoolConfigureBank>,
+        price: u64,
+        expo: i32,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_set_bank_settlement_price(ctx, price, expo)
+    }
+
+    pub fn lending_pool_clear_bank_settlement_price(
+        ctx: Context<LendingPoolConfigureBank>,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_clear_bank_settlement_price(ctx)
+    }
+
+    pub fn lending_pool_schedule_weight_change(
+        ctx: Context<LendingPoolConfigureBank>,
+        target_asset_weight_init: WrappedI80F48,
+        target_asset_weight_maint: WrappedI80F48,
+        end_timestamp: i64,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_schedule_weight_change(
+            ctx,
+            target_asset_weight_init,
+            target_asset_weight_maint,
+            end_timestamp,
+        )
+    }
+
+    pub fn lending_pool_cancel_weight_change(
+        ctx: Context<LendingPoolConfigureBank>,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_cancel_weight_change(ctx)
+    }
+
     pub fn lending_pool_setup_emissions(
         ctx: Context<LendingPoolSetupEmissions>,
         flags: u64,
@@ -87,6 +182,13 @@ pub mod astrolend {
         )
     }
 
+    /// Close the emissions vault of a bank once its campaign is fully claimed
+    pub fn lending_pool_close_emissions(
+        ctx: Context<LendingPoolCloseEmissions>,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_close_emissions(ctx)
+    }
+
     /// Handle bad debt of a bankrupt astrolend account for a given bank.
     pub fn lending_pool_handle_bankruptcy<'info>(
         ctx: Context<'_, '_, 'info, 'info, LendingPoolHandleBankruptcy<'info>>,
@@ -99,15 +201,18 @@ pub mod astrolend {
     /// Initialize a astrolend account for a given group
     pub fn astrolend_account_initialize(
         ctx: Context<AstrolendAccountInitialize>,
+        account_tag: Option<[u8; 8]>,
     ) -> AstrolendResult {
-        astrolend_account::initialize_account(ctx)
+        astrolend_account::initialize_account(ctx, account_tag)
     }
 
     pub fn lending_account_deposit<'info>(
         ctx: Context<'_, '_, 'info, 'info, LendingAccountDeposit<'info>>,
         amount: u64,
+        on_behalf_of: Option<bool>,
+        mint_receipts: Option<bool>,
     ) -> AstrolendResult {
-        astrolend_account::lending_account_deposit(ctx, amount)
+        astrolend_account::lending_account_deposit(ctx, amount, on_behalf_of, mint_receipts)
     }
 
     pub fn lending_account_repay<'info>(
@@ -122,15 +227,43 @@ pub mod astrolend {
         ctx: Context<'_, '_, 'info, 'info, LendingAccountWithdraw<'info>>,
         amount: u64,
         withdraw_all: Option<bool>,
+        min_amount_out: Option<u64>,
     ) -> AstrolendResult {
-        astrolend_account::lending_account_withdraw(ctx, amount, withdraw_all)
+        astrolend_account::lending_account_withdraw(ctx, amount, withdraw_all, min_amount_out)
+    }
+
+    /// Deposit lamports of the signer into a bank of the native mint, wrapped on the fly
+    pub fn lending_account_deposit_native<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingAccountDepositNative<'info>>,
+        amount: u64,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_deposit_native(ctx, amount)
+    }
+
+    /// Withdraw from a bank of the native mint, unwrapped into lamports of the signer
+    pub fn lending_account_withdraw_native<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingAccountWithdrawNative<'info>>,
+        amount: u64,
+        withdraw_all: Option<bool>,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_withdraw_native(ctx, amount, withdraw_all)
     }
 
     pub fn lending_account_borrow<'info>(
         ctx: Context<'_, '_, 'info, 'info, LendingAccountBorrow<'info>>,
         amount: u64,
+        with_referrer: Option<bool>,
+        min_amount_out: Option<u64>,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_borrow(ctx, amount, with_referrer, min_amount_out)
+    }
+
+    /// Update authority-settable account settings
+    pub fn lending_account_configure(
+        ctx: Context<LendingAccountConfigure>,
+        config: AccountConfigOpt,
     ) -> AstrolendResult {
-        astrolend_account::lending_account_borrow(ctx, amount)
+        astrolend_account::lending_account_configure(ctx, config)
     }
 
     pub fn lending_account_close_balance(
@@ -145,12 +278,26 @@ pub mod astrolend {
         astrolend_account::lending_account_withdraw_emissions(ctx)
     }
 
+    /// Withdraw emissions of several banks, the per bank accounts go in remaining accounts
+    pub fn lending_account_withdraw_emissions_many<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingAccountWithdrawEmissionsMany<'info>>,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_withdraw_emissions_many(ctx)
+    }
+
     pub fn lending_account_settle_emissions(
         ctx: Context<LendingAccountSettleEmissions>,
     ) -> AstrolendResult {
         astrolend_account::lending_account_settle_emissions(ctx)
     }
 
+    /// Settle emissions of a bank for a batch of accounts passed in remaining accounts
+    pub fn lending_account_settle_emissions_many<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingAccountSettleEmissionsMany<'info>>,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_settle_emissions_many(ctx)
+    }
+
     /// Liquidate a lending account balance of an unhealthy astrolend account
     pub fn lending_account_liquidate<'info>(
         ctx: Context<'_, '_, 'info, 'info, LendingAccountLiquidate<'info>>,
@@ -159,6 +306,43 @@ pub mod astrolend {
         astrolend_account::lending_account_liquidate(ctx, asset_amount)
     }
 
+    /// Deleverage an unhealthy astrolend account by its own authority, at a reduced penalty
+    pub fn lending_account_self_liquidate<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingAccountSelfLiquidate<'info>>,
+        asset_amount: u64,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_self_liquidate(ctx, asset_amount)
+    }
+
+    /// Flag an unhealthy account holding too little collateral to be profitably liquidated, or
+    /// clear the flag of an account that recovered
+    pub fn lending_account_flag_unprofitable<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingAccountFlagUnprofitable<'info>>,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_flag_unprofitable(ctx)
+    }
+
+    /// Bring a legacy account to the current layout version
+    pub fn lending_account_migrate(ctx: Context<LendingAccountMigrate>) -> AstrolendResult {
+        astrolend_account::lending_account_migrate(ctx)
+    }
+
+    /// Move an asset position to another account of the same authority
+    pub fn lending_account_transfer_position<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingAccountTransferPosition<'info>>,
+        shares: WrappedI80F48,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_transfer_position(ctx, shares)
+    }
+
+    /// Burn deposit receipts for the asset shares they stand for
+    pub fn lending_account_redeem_receipts<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingAccountRedeemReceipts<'info>>,
+        receipts: u64,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_redeem_receipts(ctx, receipts)
+    }
+
     pub fn lending_account_start_flashloan(
         ctx: Context<LendingAccountStartFlashloan>,
         end_index: u64,
@@ -179,12 +363,42 @@ pub mod astrolend {
         astrolend_group::lending_pool_accrue_bank_interest(ctx)
     }
 
+    pub fn lending_pool_clear_stuck_guard(
+        ctx: Context<LendingPoolClearStuckGuard>,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_clear_stuck_guard(ctx)
+    }
+
+    /// Accrue interest on all banks passed in remaining accounts and tag them with a common checkpoint id.
+    pub fn lending_pool_checkpoint_banks<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingPoolCheckpointBanks<'info>>,
+        checkpoint_id: Option<u64>,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_checkpoint_banks(ctx, checkpoint_id)
+    }
+
+    /// Apply the same oracle parameters to all banks passed in remaining accounts, banks with a
+    /// frozen config are skipped.
+    pub fn lending_pool_update_oracle_max_confidence<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingPoolUpdateOracleMaxConfidence<'info>>,
+        oracle_params: OracleParamsOpt,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_update_oracle_max_confidence(ctx, oracle_params)
+    }
+
     pub fn lending_pool_collect_bank_fees<'info>(
         ctx: Context<'_, '_, 'info, 'info, LendingPoolCollectBankFees<'info>>,
     ) -> AstrolendResult {
         astrolend_group::lending_pool_collect_bank_fees(ctx)
     }
 
+    /// Send the fee vault balance to the destinations of the group fee split, permissionless
+    pub fn lending_pool_distribute_fees<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingPoolDistributeFees<'info>>,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_distribute_fees(ctx)
+    }
+
     pub fn lending_pool_withdraw_fees<'info>(
         ctx: Context<'_, '_, 'info, 'info, LendingPoolWithdrawFees<'info>>,
         amount: u64,
@@ -199,6 +413,34 @@ pub mod astrolend {
         astrolend_group::lending_pool_withdraw_insurance(ctx, amount)
     }
 
+    pub fn lending_pool_donate<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingPoolDonate<'info>>,
+        amount: u64,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_donate(ctx, amount)
+    }
+
+    pub fn lending_pool_init_insurance_stake(
+        ctx: Context<LendingPoolInitInsuranceStake>,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_init_insurance_stake(ctx)
+    }
+
+    pub fn lending_pool_deposit_insurance<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingPoolDepositInsurance<'info>>,
+        amount: u64,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_deposit_insurance(ctx, amount)
+    }
+
+    pub fn lending_pool_withdraw_insurance_stake<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingPoolWithdrawInsuranceStake<'info>>,
+        amount: u64,
+        withdraw_all: Option<bool>,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_withdraw_insurance_stake(ctx, amount, withdraw_all)
+    }
+
     pub fn set_account_flag(ctx: Context<SetAccountFlag>, flag: u64) -> AstrolendResult {
         astrolend_group::set_account_flag(ctx, flag)
     }
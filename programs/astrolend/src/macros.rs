@@ -59,6 +59,39 @@ macro_rules! set_if_some {
     };
 }
 
+/// Discriminator of the instruction `$ix`.
+#[macro_export]
+macro_rules! instruction_discriminator {
+    ($ix: ident) => {
+        <$crate::instruction::$ix as anchor_lang::Discriminator>::DISCRIMINATOR
+    };
+}
+
+/// Builds a `GroupEventMeta` stamped with the event version and the discriminator of `$ix`, the
+/// instruction emitting the event.
+#[macro_export]
+macro_rules! group_event_meta {
+    ($ix: ident) => {
+        $crate::events::GroupEventMeta {
+            version: $crate::events::EVENT_VERSION,
+            instruction: $crate::instruction_discriminator!($ix),
+        }
+    };
+}
+
+/// Builds an `AccountEventMeta` stamped with the event version, the discriminator of `$ix`, the
+/// instruction emitting the event, and the `$account_tag` of the account.
+#[macro_export]
+macro_rules! account_event_meta {
+    ($ix: ident, $account_tag: expr) => {
+        $crate::events::AccountEventMeta {
+            version: $crate::events::EVENT_VERSION,
+            instruction: $crate::instruction_discriminator!($ix),
+            account_tag: $account_tag,
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! bank_seed {
     ($vault_type: expr, $bank_pk: expr) => {
@@ -84,6 +117,18 @@ macro_rules! bank_signer {
     };
 }
 
+#[macro_export]
+macro_rules! emissions_signer {
+    ($bank_pk: expr, $emissions_mint: expr, $authority_bump: expr) => {
+        &[&[
+            $crate::constants::EMISSIONS_AUTH_SEED.as_bytes(),
+            &$bank_pk.to_bytes(),
+            &$emissions_mint.to_bytes(),
+            &[$authority_bump],
+        ]]
+    };
+}
+
 #[macro_export]
 macro_rules! debug {
     ($($arg:tt)*) => {
@@ -107,3 +152,10 @@ macro_rules! assert_struct_align {
         static_assertions::const_assert_eq!(std::mem::align_of::<$struct>(), $align);
     };
 }
+
+#[macro_export]
+macro_rules! assert_struct_field_offset {
+    ($struct: ty, $field: ident, $offset: expr) => {
+        static_assertions::const_assert_eq!(memoffset::offset_of!($struct, $field), $offset);
+    };
+}
@@ -3,6 +3,7 @@ use anchor_lang::prelude::*;
 pub type AstrolendResult<G = ()> = Result<G>;
 
 pub use crate::{
+    constants,
     errors::AstrolendError,
     state::astrolend_group::{GroupConfig, AstrolendGroup},
 };
@@ -0,0 +1,110 @@
+//! Offsets of the fields indexers filter program accounts on, e.g. with the `memcmp` filters of
+//! `getProgramAccounts`. Offsets include the 8 byte account discriminator.
+
+use super::{astrolend_account::AstrolendAccount, astrolend_group::Bank};
+use crate::assert_struct_field_offset;
+#[cfg(any(feature = "test", feature = "client"))]
+use anchor_lang::{prelude::Pubkey, Discriminator};
+
+const DISCRIMINATOR_LEN: usize = 8;
+
+pub const ASTROLEND_ACCOUNT_GROUP_OFFSET: usize = DISCRIMINATOR_LEN;
+pub const ASTROLEND_ACCOUNT_AUTHORITY_OFFSET: usize = DISCRIMINATOR_LEN + 32;
+pub const ASTROLEND_ACCOUNT_TAG_OFFSET: usize = DISCRIMINATOR_LEN + 2288;
+pub const ASTROLEND_ACCOUNT_VERSION_OFFSET: usize = DISCRIMINATOR_LEN + 2296;
+
+pub const BANK_MINT_OFFSET: usize = DISCRIMINATOR_LEN;
+pub const BANK_GROUP_OFFSET: usize = DISCRIMINATOR_LEN + 33;
+
+assert_struct_field_offset!(
+    AstrolendAccount,
+    group,
+    ASTROLEND_ACCOUNT_GROUP_OFFSET - DISCRIMINATOR_LEN
+);
+assert_struct_field_offset!(
+    AstrolendAccount,
+    authority,
+    ASTROLEND_ACCOUNT_AUTHORITY_OFFSET - DISCRIMINATOR_LEN
+);
+assert_struct_field_offset!(
+    AstrolendAccount,
+    account_tag,
+    ASTROLEND_ACCOUNT_TAG_OFFSET - DISCRIMINATOR_LEN
+);
+assert_struct_field_offset!(
+    AstrolendAccount,
+    version,
+    ASTROLEND_ACCOUNT_VERSION_OFFSET - DISCRIMINATOR_LEN
+);
+assert_struct_field_offset!(Bank, mint, BANK_MINT_OFFSET - DISCRIMINATOR_LEN);
+assert_struct_field_offset!(Bank, group, BANK_GROUP_OFFSET - DISCRIMINATOR_LEN);
+
+/// Bytes an account must hold at `offset` to match
+#[cfg(any(feature = "test", feature = "client"))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemcmpFilter {
+    pub offset: usize,
+    pub bytes: Vec<u8>,
+}
+
+#[cfg(any(feature = "test", feature = "client"))]
+impl MemcmpFilter {
+    fn new(offset: usize, bytes: &[u8]) -> Self {
+        Self {
+            offset,
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    /// Check the filter against raw account data, as the RPC node would
+    pub fn matches(&self, data: &[u8]) -> bool {
+        data.get(self.offset..self.offset + self.bytes.len()) == Some(self.bytes.as_slice())
+    }
+}
+
+#[cfg(any(feature = "test", feature = "client"))]
+impl AstrolendAccount {
+    pub fn discriminator() -> [u8; 8] {
+        <Self as Discriminator>::DISCRIMINATOR
+    }
+
+    pub fn discriminator_filter() -> MemcmpFilter {
+        MemcmpFilter::new(0, &Self::discriminator())
+    }
+
+    pub fn group_filter(group: &Pubkey) -> MemcmpFilter {
+        MemcmpFilter::new(ASTROLEND_ACCOUNT_GROUP_OFFSET, group.as_ref())
+    }
+
+    pub fn authority_filter(authority: &Pubkey) -> MemcmpFilter {
+        MemcmpFilter::new(ASTROLEND_ACCOUNT_AUTHORITY_OFFSET, authority.as_ref())
+    }
+
+    pub fn account_tag_filter(account_tag: &[u8; 8]) -> MemcmpFilter {
+        MemcmpFilter::new(ASTROLEND_ACCOUNT_TAG_OFFSET, account_tag)
+    }
+
+    /// Accounts at layout `version`, e.g. 0 to find the legacy accounts left to migrate
+    pub fn version_filter(version: u8) -> MemcmpFilter {
+        MemcmpFilter::new(ASTROLEND_ACCOUNT_VERSION_OFFSET, &[version])
+    }
+}
+
+#[cfg(any(feature = "test", feature = "client"))]
+impl Bank {
+    pub fn discriminator() -> [u8; 8] {
+        <Self as Discriminator>::DISCRIMINATOR
+    }
+
+    pub fn discriminator_filter() -> MemcmpFilter {
+        MemcmpFilter::new(0, &Self::discriminator())
+    }
+
+    pub fn group_filter(group: &Pubkey) -> MemcmpFilter {
+        MemcmpFilter::new(BANK_GROUP_OFFSET, group.as_ref())
+    }
+
+    pub fn mint_filter(mint: &Pubkey) -> MemcmpFilter {
+        MemcmpFilter::new(BANK_MINT_OFFSET, mint.as_ref())
+    }
+}
@@ -0,0 +1,372 @@
+//! Property tests for the share accounting of `Bank` and `BankAccountWrapper`.
+//!
+//! Random sequences of deposits, borrows, repays, withdrawals and interest accruals are applied
+//! to a bank and a handful of balances, tracking the tokens moved in and out of the liquidity
+//! vault as the program would. Operations the program rejects are rolled back, like a failed
+//! transaction. The invariants are checked after every step.
+//!
+//! Failures print the RNG seed, rerun with `ACCOUNTING_PROPTEST_SEED=<seed>` to reproduce.
+//! `PROPTEST_CASES` overrides the number of cases.
+
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use proptest::{
+    prelude::*,
+    test_runner::{Config, RngSeed, TestCaseError, TestRunner},
+};
+
+use super::{
+    astrolend_account::{Balance, BalanceSide, BankAccountWrapper},
+    astrolend_group::{
+        AstrolendGroup, Bank, BankConfig, BankOperationalState, InterestRateConfig,
+    },
+};
+use crate::prelude::AstrolendResult;
+
+const USERS: usize = 3;
+const MAX_DEPOSIT: u64 = 1_000_000_000;
+const MAX_ACCRUAL_PERIOD: u64 = 30 * 24 * 60 * 60;
+const DEFAULT_CASES: u32 = 64;
+
+/// Fixed point rounding budget over a whole sequence, well below a single native token
+const TOLERANCE: I80F48 = I80F48!(0.1);
+
+#[derive(Debug, Clone)]
+enum Op {
+    Deposit { user: usize, amount: u64 },
+    /// Borrow `bps` of the tokens in the liquidity vault
+    Borrow { user: usize, bps: u64 },
+    /// Repay `bps` of the outstanding liability
+    Repay { user: usize, bps: u64 },
+    /// Withdraw `bps` of the deposited amount
+    Withdraw { user: usize, bps: u64 },
+    RepayAll { user: usize },
+    WithdrawAll { user: usize },
+    Accrue { seconds: u64 },
+}
+
+fn op_strategy() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        3 => (0..USERS, 1..=MAX_DEPOSIT).prop_map(|(user, amount)| Op::Deposit { user, amount }),
+        2 => (0..USERS, 1..=10_000u64).prop_map(|(user, bps)| Op::Borrow { user, bps }),
+        2 => (0..USERS, 1..=10_000u64).prop_map(|(user, bps)| Op::Repay { user, bps }),
+        2 => (0..USERS, 1..=10_000u64).prop_map(|(user, bps)| Op::Withdraw { user, bps }),
+        1 => (0..USERS).prop_map(|user| Op::RepayAll { user }),
+        1 => (0..USERS).prop_map(|user| Op::WithdrawAll { user }),
+        2 => (1..=MAX_ACCRUAL_PERIOD).prop_map(|seconds| Op::Accrue { seconds }),
+    ]
+}
+
+enum Transfer {
+    None,
+    In(u64),
+    Out(u64),
+}
+
+#[derive(Default, Clone, Copy)]
+struct UserFlows {
+    tokens_in: u64,
+    tokens_out: u64,
+    /// Borrowed tokens not repaid yet, interest excluded
+    principal: u64,
+    /// Interest earned on assets minus interest paid on liabilities
+    interest: I80F48,
+}
+
+struct Model {
+    bank: Bank,
+    balances: [Balance; USERS],
+    flows: [UserFlows; USERS],
+    vault: u64,
+    now: u64,
+}
+
+impl Model {
+    fn new() -> Self {
+        let now = 1_000;
+
+        Self {
+            bank: Bank {
+                asset_share_value: I80F48::ONE.into(),
+                liability_share_value: I80F48::ONE.into(),
+                last_update: now as i64,
+                config: BankConfig {
+                    operational_state: BankOperationalState::Operational,
+                    deposit_limit: u64::MAX,
+                    borrow_limit: u64::MAX,
+                    interest_rate_config: InterestRateConfig {
+                        optimal_utilization_rate: I80F48!(0.5).into(),
+                        plateau_interest_rate: I80F48!(0.6).into(),
+                        max_interest_rate: I80F48!(3).into(),
+                        protocol_fixed_fee_apr: I80F48!(0.01).into(),
+                        protocol_ir_fee: I80F48!(0.1).into(),
+                        insurance_fee_fixed_apr: I80F48!(0.005).into(),
+                        insurance_ir_fee: I80F48!(0.05).into(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            balances: [Balance {
+                active: true,
+                ..Balance::empty_deactivated()
+            }; USERS],
+            flows: [UserFlows::default(); USERS],
+            vault: 0,
+            now,
+        }
+    }
+
+    fn asset_amount(&self, user: usize) -> I80F48 {
+        self.bank
+            .get_asset_amount(self.balances[user].asset_shares.into())
+            .unwrap()
+    }
+
+    fn liability_amount(&self, user: usize) -> I80F48 {
+        self.bank
+            .get_liability_amount(self.balances[user].liability_shares.into())
+            .unwrap()
+    }
+
+    fn apply(&mut self, op: &Op) -> std::result::Result<(), TestCaseError> {
+        if let Op::Accrue { seconds } = *op {
+            return self.accrue(seconds);
+        }
+
+        let user = match *op {
+            Op::Deposit { user, .. }
+            | Op::Borrow { user, .. }
+            | Op::Repay { user, .. }
+            | Op::Withdraw { user, .. }
+            | Op::RepayAll { user }
+            | Op::WithdrawAll { user } => user,
+            Op::Accrue { .. } => unreachable!(),
+        };
+
+        let snapshot = (self.bank, self.balances[user]);
+        let side = self.balances[user].get_side();
+
+        let result: AstrolendResult<Transfer> = {
+            let now = self.now;
+            let vault = self.vault;
+            let asset_amount = self.asset_amount(user);
+            let liability_amount = self.liability_amount(user);
+            let mut wrapper = BankAccountWrapper {
+                balance: &mut self.balances[user],
+                bank: &mut self.bank,
+            };
+
+            match *op {
+                Op::Deposit { amount, .. } => match side {
+                    Some(BalanceSide::Liabilities) => Ok(Transfer::None),
+                    _ => wrapper
+                        .deposit_at(I80F48::from_num(amount), now)
+                        .map(|_| Transfer::In(amount)),
+                },
+                Op::Borrow { bps, .. } => match (side, vault * bps / 10_000) {
+                    (Some(BalanceSide::Assets), _) | (_, 0) => Ok(Transfer::None),
+                    (_, amount) => wrapper
+                        .borrow_at(I80F48::from_num(amount), now)
+                        .map(|_| Transfer::Out(amount)),
+                },
+                Op::Repay { bps, .. } => {
+                    let amount = bps_of(liability_amount, bps);
+                    if amount == 0 {
+                        Ok(Transfer::None)
+                    } else {
+                        wrapper
+                            .repay_at(I80F48::from_num(amount), now)
+                            .map(|_| Transfer::In(amount))
+                    }
+                }
+                Op::Withdraw { bps, .. } => {
+                    let amount = bps_of(asset_amount, bps).min(vault);
+                    if amount == 0 {
+                        Ok(Transfer::None)
+                    } else {
+                        wrapper
+                            .withdraw_at(I80F48::from_num(amount), now)
+                            .map(|_| Transfer::Out(amount))
+                    }
+                }
+                Op::RepayAll { .. } => wrapper.repay_all_at(now).map(Transfer::In),
+                Op::WithdrawAll { .. } => wrapper.withdraw_all_at(now).map(Transfer::Out),
+                Op::Accrue { .. } => unreachable!(),
+            }
+        };
+
+        let flows = &mut self.flows[user];
+        match result {
+            Ok(Transfer::In(amount)) => {
+                flows.tokens_in += amount;
+                self.vault += amount;
+
+                if matches!(op, Op::RepayAll { .. }) {
+                    // Clearing a borrow never takes fewer tokens than were borrowed
+                    prop_assert!(
+                        amount >= flows.principal,
+                        "repay all of {} for {} borrowed",
+                        amount,
+                        flows.principal
+                    );
+                    flows.principal = 0;
+                } else if matches!(op, Op::Repay { .. }) {
+                    flows.principal = flows.principal.saturating_sub(amount);
+                }
+            }
+            Ok(Transfer::Out(amount)) => {
+                prop_assert!(amount <= self.vault, "{} out of a {} vault", amount, self.vault);
+
+                flows.tokens_out += amount;
+                self.vault -= amount;
+
+                if matches!(op, Op::Borrow { .. }) {
+                    flows.principal += amount;
+                }
+            }
+            Ok(Transfer::None) => {}
+            Err(_) => (self.bank, self.balances[user]) = snapshot,
+        }
+
+        Ok(())
+    }
+
+    fn accrue(&mut self, seconds: u64) -> std::result::Result<(), TestCaseError> {
+        let asset_share_value = I80F48::from(self.bank.asset_share_value);
+        let liability_share_value = I80F48::from(self.bank.liability_share_value);
+
+        self.now += seconds;
+        let res = self.bank.accrue_interest(
+            self.now as i64,
+            &AstrolendGroup::default(),
+            #[cfg(not(feature = "client"))]
+            anchor_lang::prelude::Pubkey::default(),
+            #[cfg(not(feature = "client"))]
+            [0; 8],
+        );
+        prop_assert!(res.is_ok(), "accrual failed: {:?}", res);
+
+        let asset_share_value_delta =
+            I80F48::from(self.bank.asset_share_value) - asset_share_value;
+        let liability_share_value_delta =
+            I80F48::from(self.bank.liability_share_value) - liability_share_value;
+
+        for (balance, flows) in self.balances.iter().zip(self.flows.iter_mut()) {
+            flows.interest += I80F48::from(balance.asset_shares) * asset_share_value_delta
+                - I80F48::from(balance.liability_shares) * liability_share_value_delta;
+        }
+
+        Ok(())
+    }
+
+    fn check_invariants(&self) -> std::result::Result<(), TestCaseError> {
+        // Balance shares add up to the bank totals, exactly
+        let (asset_shares, liability_shares) = self.balances.iter().fold(
+            (I80F48::ZERO, I80F48::ZERO),
+            |(assets, liabilities), balance| {
+                (
+                    assets + I80F48::from(balance.asset_shares),
+                    liabilities + I80F48::from(balance.liability_shares),
+                )
+            },
+        );
+        prop_assert_eq!(asset_shares, I80F48::from(self.bank.total_asset_shares));
+        prop_assert_eq!(
+            liability_shares,
+            I80F48::from(self.bank.total_liability_shares)
+        );
+
+        // Vault tokens and outstanding liabilities cover the deposits and the collected fees
+        let total_assets = self
+            .bank
+            .get_asset_amount(self.bank.total_asset_shares.into())
+            .unwrap();
+        let total_liabilities = self
+            .bank
+            .get_liability_amount(self.bank.total_liability_shares.into())
+            .unwrap();
+        let fees_outstanding = I80F48::from(self.bank.collected_group_fees_outstanding)
+            + I80F48::from(self.bank.collected_insurance_fees_outstanding);
+        let surplus =
+            I80F48::from_num(self.vault) + total_liabilities - total_assets - fees_outstanding;
+        prop_assert!(
+            surplus.abs() <= TOLERANCE,
+            "vault {} + liabilities {} != assets {} + fees {}",
+            self.vault,
+            total_liabilities,
+            total_assets,
+            fees_outstanding
+        );
+
+        for (user, flows) in self.flows.iter().enumerate() {
+            // Nobody extracts more than they put in plus the interest they earned
+            let extracted = I80F48::from_num(flows.tokens_out) - I80F48::from_num(flows.tokens_in)
+                + self.asset_amount(user)
+                - self.liability_amount(user);
+            prop_assert!(
+                extracted <= flows.interest + TOLERANCE,
+                "user {} extracted {} with {} interest",
+                user,
+                extracted,
+                flows.interest
+            );
+
+            // Liabilities never round below the borrowed tokens
+            let liability = self.liability_amount(user).checked_ceil().unwrap();
+            prop_assert!(
+                liability >= I80F48::from_num(flows.principal),
+                "user {} owes {} for {} borrowed",
+                user,
+                liability,
+                flows.principal
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn bps_of(amount: I80F48, bps: u64) -> u64 {
+    (amount * I80F48::from_num(bps) / I80F48!(10_000))
+        .checked_floor()
+        .unwrap()
+        .to_num()
+}
+
+fn env_var<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok().and_then(|value| value.parse().ok())
+}
+
+#[test]
+fn share_accounting_invariants_hold() {
+    let seed = env_var("ACCOUNTING_PROPTEST_SEED").unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64
+    });
+
+    let mut runner = TestRunner::new(Config {
+        cases: env_var("PROPTEST_CASES").unwrap_or(DEFAULT_CASES),
+        rng_seed: RngSeed::Fixed(seed),
+        failure_persistence: None,
+        ..Config::default()
+    });
+
+    let result = runner.run(&prop::collection::vec(op_strategy(), 1..64), |ops| {
+        let mut model = Model::new();
+
+        for op in ops.iter() {
+            model.apply(op)?;
+            model.check_invariants()?;
+        }
+
+        Ok(())
+    });
+
+    if let Err(err) = result {
+        panic!("{}\nrerun with ACCOUNTING_PROPTEST_SEED={}", err, seed);
+    }
+}
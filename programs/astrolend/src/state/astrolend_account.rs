@@ -1,13 +1,15 @@
 use super::{
-    astrolend_group::{Bank, RiskTier, WrappedI80F48},
+    astrolend_group::{AstrolendGroup, Bank, BankOperationalState, RiskTier, WrappedI80F48},
     price::{OraclePriceFeedAdapter, OraclePriceType, PriceAdapter, PriceBias},
 };
 use crate::{
     assert_struct_align, assert_struct_size, check,
     constants::{
-        BANKRUPT_THRESHOLD, EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE,
-        EMPTY_BALANCE_THRESHOLD, EXP_10_I80F48, MIN_EMISSIONS_START_TIME, SECONDS_PER_YEAR,
-        ZERO_AMOUNT_THRESHOLD,
+        ASTROLEND_ACCOUNT_VERSION, BANKRUPT_THRESHOLD, EMISSIONS_COLLATERAL_FLAG,
+        EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE, EMPTY_BALANCE_THRESHOLD,
+        EXP_10_I80F48,
+        HEALTH_SNAPSHOT_EVENTS_FLAG, LIQUIDATION_HISTORY_LEN, MAX_LENDING_ACCOUNT_BALANCES,
+        MIN_EMISSIONS_START_TIME, SECONDS_PER_YEAR, ZERO_AMOUNT_THRESHOLD,
     },
     debug, math_error,
     prelude::{AstrolendError, AstrolendResult},
@@ -17,14 +19,25 @@ use anchor_lang::prelude::*;
 use anchor_spl::token_interface::Mint;
 use fixed::types::I80F48;
 use std::{
+    cell::RefMut,
     cmp::{max, min},
     ops::Not,
 };
 #[cfg(any(feature = "test", feature = "client"))]
+use std::collections::HashMap;
+#[cfg(any(feature = "test", feature = "client"))]
 use type_layout::TypeLayout;
 
+/// Space of the account data: discriminator, [AstrolendAccount], then [CreditHistory].
+pub const ASTROLEND_ACCOUNT_SPACE: usize =
+    8 + std::mem::size_of::<AstrolendAccount>() + std::mem::size_of::<CreditHistory>();
+
 assert_struct_size!(AstrolendAccount, 2304);
 assert_struct_align!(AstrolendAccount, 8);
+// Accounts are created by CPI, which caps the size of new accounts
+static_assertions::const_assert!(
+    ASTROLEND_ACCOUNT_SPACE <= solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE
+);
 #[account(zero_copy(unsafe))]
 #[repr(C)]
 #[cfg_attr(
@@ -41,20 +54,93 @@ pub struct AstrolendAccount {
     /// Flags:
     /// - DISABLED_FLAG = 1 << 0 = 1 - This flag indicates that the account is disabled,
     /// and no further actions can be taken on it.
+    /// - THIRD_PARTY_DEPOSIT_ALLOWED_FLAG = 1 << 4 = 16 - Set by the account authority to
+    /// accept deposits signed by other wallets.
+    /// - BAD_DEBT_CANDIDATE_FLAG = 1 << 5 = 32 - Set permissionlessly on underwater accounts
+    /// holding too little collateral to be profitably liquidated.
+    /// - FEE_EXEMPT_FLAG = 1 << 6 = 64 - Set by the group admin, e.g. on market makers, to waive
+    /// the origination and withdraw fees of the account.
+    /// - RESTRICTED_DESTINATIONS_FLAG = 1 << 7 = 128 - Set by the account authority to only
+    /// withdraw and borrow to its associated token accounts and `approved_destination`.
     pub account_flags: u64, // 8
-    pub _padding: [u64; 63],             // 504
+    /// Ring buffer of the most recent liquidations suffered by this account.
+    pub liquidation_history: [LiquidationRecord; LIQUIDATION_HISTORY_LEN], // 240
+    /// Token account withdraws and borrows may be sent to besides the authority's associated
+    /// token accounts while `RESTRICTED_DESTINATIONS_FLAG` is set, e.g. one owned by the PDA
+    /// of a vault program. Default for none.
+    pub approved_destination: Pubkey, // 32
+    pub _padding0: [u64; 26],            // 208
+    /// Total number of liquidations recorded, the next record is written at
+    /// `liquidation_count % LIQUIDATION_HISTORY_LEN`.
+    pub liquidation_count: u64, // 8
+    /// Set by the creator at initialization, e.g. by integrators to filter their accounts and
+    /// events on, never changed after. Zeroed if not given.
+    pub account_tag: [u8; 8], // 8
+    /// Layout version, see `ASTROLEND_ACCOUNT_VERSION`. Accounts created before versioning are
+    /// at 0 and may hold leftover data in the fields added since, until they are migrated.
+    pub version: u8,       // 1
+    pub _padding: [u8; 7], // 7
 }
 
 pub const DISABLED_FLAG: u64 = 1 << 0;
 pub const IN_FLASHLOAN_FLAG: u64 = 1 << 1;
 pub const FLASHLOAN_ENABLED_FLAG: u64 = 1 << 2;
 pub const TRANSFER_AUTHORITY_ALLOWED_FLAG: u64 = 1 << 3;
+pub const THIRD_PARTY_DEPOSIT_ALLOWED_FLAG: u64 = 1 << 4;
+pub const BAD_DEBT_CANDIDATE_FLAG: u64 = 1 << 5;
+/// Origination and withdraw fees are skipped, interest is charged as usual.
+pub const FEE_EXEMPT_FLAG: u64 = 1 << 6;
+/// Withdraws and borrows only go to the authority's associated token account of the bank mint
+/// or `approved_destination`, see [AstrolendAccount::check_destination_allowed].
+pub const RESTRICTED_DESTINATIONS_FLAG: u64 = 1 << 7;
+
+/// Authority-settable account settings, `None` fields are left unchanged.
+#[cfg_attr(any(feature = "test", feature = "client"), derive(PartialEq, Eq))]
+#[derive(AnchorSerialize, AnchorDeserialize, Default, Debug, Clone)]
+pub struct AccountConfigOpt {
+    /// Accept deposits signed by wallets other than the account authority.
+    pub third_party_deposits_allowed: Option<bool>,
+    /// Only withdraw and borrow to the authority's associated token accounts and the approved
+    /// destination.
+    pub destinations_restricted: Option<bool>,
+    /// Token account allowed as destination on top of the associated token accounts, default
+    /// to remove it.
+    pub approved_destination: Option<Pubkey>,
+}
 
 impl AstrolendAccount {
     /// Set the initial data for the astrolend account.
-    pub fn initialize(&mut self, group: Pubkey, authority: Pubkey) {
+    pub fn initialize(&mut self, group: Pubkey, authority: Pubkey, account_tag: [u8; 8]) {
         self.authority = authority;
         self.group = group;
+        self.account_tag = account_tag;
+        self.version = ASTROLEND_ACCOUNT_VERSION;
+    }
+
+    /// Accounts created before versioning, their data ends with the account and the fields
+    /// added since may hold leftover data. Features stored in them no-op until the account is
+    /// migrated, see [AstrolendAccount::migrate].
+    pub fn is_legacy(&self) -> bool {
+        self.version == 0
+    }
+
+    /// Bring a legacy account to the current layout, zeroing the fields added since, which may
+    /// hold leftover data of the padding they replaced.
+    ///
+    /// `credit_history` is the region after the account, grown to its full size beforehand.
+    pub fn migrate(&mut self, credit_history: &mut CreditHistory) -> AstrolendResult {
+        check!(self.is_legacy(), AstrolendError::AccountAlreadyMigrated);
+
+        self.liquidation_history = [LiquidationRecord::default(); LIQUIDATION_HISTORY_LEN];
+        self.approved_destination = Pubkey::default();
+        self._padding0 = [0; 26];
+        self.liquidation_count = 0;
+        self.account_tag = [0; 8];
+        *credit_history = bytemuck::Zeroable::zeroed();
+
+        self.version = ASTROLEND_ACCOUNT_VERSION;
+
+        Ok(())
     }
 
     pub fn get_remaining_accounts_len(&self) -> usize {
@@ -66,6 +152,17 @@ impl AstrolendAccount {
             * 2 // TODO: Make account count oracle setup specific
     }
 
+    /// `get_remaining_accounts_len` once balances are open in each of `bank_pks`, which must be
+    /// distinct, for instructions opening them before the health check of the account.
+    pub fn get_remaining_accounts_len_with(&self, bank_pks: &[Pubkey]) -> usize {
+        let new_balances = bank_pks
+            .iter()
+            .filter(|bank_pk| self.lending_account.get_balance(bank_pk).is_none())
+            .count();
+
+        self.get_remaining_accounts_len() + new_balances * 2
+    }
+
     pub fn set_flag(&mut self, flag: u64) {
         msg!("Setting account flag {:b}", flag);
         self.account_flags |= flag;
@@ -80,6 +177,56 @@ impl AstrolendAccount {
         self.account_flags & flag != 0
     }
 
+    /// Apply the authority-settable config, flags reserved to the program or the group admin
+    /// can't be reached from it.
+    pub fn configure(&mut self, config: &AccountConfigOpt) -> AstrolendResult {
+        if let Some(allowed) = config.third_party_deposits_allowed {
+            if allowed {
+                self.set_flag(THIRD_PARTY_DEPOSIT_ALLOWED_FLAG);
+            } else {
+                self.unset_flag(THIRD_PARTY_DEPOSIT_ALLOWED_FLAG);
+            }
+        }
+
+        if let Some(restricted) = config.destinations_restricted {
+            if restricted {
+                self.set_flag(RESTRICTED_DESTINATIONS_FLAG);
+            } else {
+                self.unset_flag(RESTRICTED_DESTINATIONS_FLAG);
+            }
+        }
+
+        if let Some(approved_destination) = config.approved_destination {
+            check!(!self.is_legacy(), AstrolendError::AccountNotMigrated);
+            self.approved_destination = approved_destination;
+        }
+
+        Ok(())
+    }
+
+    /// Withdraws and borrows go to any token account of the bank mint, or once the account has
+    /// `RESTRICTED_DESTINATIONS_FLAG`, to `authority_ata`, the associated token account of the
+    /// authority, or the approved destination only.
+    pub fn check_destination_allowed(
+        &self,
+        destination: &Pubkey,
+        authority_ata: &Pubkey,
+    ) -> AstrolendResult {
+        if !self.get_flag(RESTRICTED_DESTINATIONS_FLAG) {
+            return Ok(());
+        }
+
+        let is_approved = !self.is_legacy()
+            && self.approved_destination != Pubkey::default()
+            && *destination == self.approved_destination;
+        check!(
+            destination == authority_ata || is_approved,
+            AstrolendError::DestinationNotAllowed
+        );
+
+        Ok(())
+    }
+
     pub fn set_new_account_authority_checked(&mut self, new_authority: Pubkey) -> AstrolendResult {
         // check if new account authority flag is set
         if !self.get_flag(TRANSFER_AUTHORITY_ALLOWED_FLAG) || self.get_flag(DISABLED_FLAG) {
@@ -102,6 +249,70 @@ impl AstrolendAccount {
         Ok(())
     }
 
+    /// Record a liquidation suffered by this account, overwriting the oldest record once
+    /// the history is full. Returns the index the record was written at.
+    ///
+    /// Legacy accounts keep no history until migrated, nothing is recorded.
+    pub fn record_liquidation(&mut self, record: LiquidationRecord) -> Option<usize> {
+        if self.is_legacy() {
+            return None;
+        }
+
+        let index = (self.liquidation_count % LIQUIDATION_HISTORY_LEN as u64) as usize;
+
+        self.liquidation_history[index] = record;
+        self.liquidation_count = self.liquidation_count.wrapping_add(1);
+
+        Some(index)
+    }
+
+    /// Recorded liquidations, oldest first.
+    #[cfg(any(feature = "test", feature = "client"))]
+    pub fn get_liquidation_history(&self) -> Vec<LiquidationRecord> {
+        if self.is_legacy() {
+            return vec![];
+        }
+
+        let len = LIQUIDATION_HISTORY_LEN as u64;
+        let start = if self.liquidation_count > len {
+            self.liquidation_count % len
+        } else {
+            0
+        };
+
+        (0..self.liquidation_count.min(len))
+            .map(|i| self.liquidation_history[((start + i) % len) as usize])
+            .collect()
+    }
+
+    /// Native amounts of the active balances `seconds` after the last update of their banks,
+    /// see [Bank::project]. Errors if a bank of a balance is missing from `banks`.
+    #[cfg(any(feature = "test", feature = "client"))]
+    pub fn project_with(
+        &self,
+        banks: &HashMap<Pubkey, Bank>,
+        seconds: u64,
+    ) -> AstrolendResult<Vec<ProjectedBalance>> {
+        self.get_active_balances_iter()
+            .map(|balance| {
+                let projected = banks
+                    .get(&balance.bank_pk)
+                    .ok_or(AstrolendError::BankAccountNotFound)?
+                    .project(seconds)?;
+
+                Ok(ProjectedBalance {
+                    bank_pk: balance.bank_pk,
+                    asset_amount: I80F48::from(balance.asset_shares)
+                        .checked_mul(projected.asset_share_value)
+                        .ok_or_else(math_error!())?,
+                    liability_amount: I80F48::from(balance.liability_shares)
+                        .checked_mul(projected.liability_share_value)
+                        .ok_or_else(math_error!())?,
+                })
+            })
+            .collect()
+    }
+
     pub fn can_be_closed(&self) -> bool {
         let is_disabled = self.get_flag(DISABLED_FLAG);
         let only_has_empty_balances = self
@@ -112,6 +323,316 @@ impl AstrolendAccount {
 
         !is_disabled && only_has_empty_balances
     }
+
+    pub fn get_active_balances_iter(&self) -> impl Iterator<Item = &Balance> {
+        self.lending_account.get_active_balances_iter()
+    }
+
+    /// (bank, oracle) keys of the active balances, the observation accounts expected by health
+    /// checked instructions. Works on-chain, e.g. for programs composing with astrolend through
+    /// CPI, and off-chain.
+    ///
+    /// Pairs are in the slot order of the balances, the order the risk engine reads them in.
+    /// `banks` resolves each bank key, banks it returns `None` for are left out.
+    pub fn get_observation_account_keys(
+        &self,
+        banks: impl Fn(&Pubkey) -> Option<BankRef>,
+    ) -> Vec<(Pubkey, Pubkey)> {
+        self.get_active_balances_iter()
+            .filter_map(|balance| {
+                banks(&balance.bank_pk).map(|bank| (balance.bank_pk, bank.oracle))
+            })
+            .collect()
+    }
+
+    /// (bank, oracle) keys of the optional emissions observation accounts, to follow
+    /// [AstrolendAccount::get_observation_account_keys] in groups with `EMISSIONS_COLLATERAL_FLAG`
+    /// set. One pair per emissions mint of the active balance banks.
+    ///
+    /// `emissions_banks` resolves an emissions mint to the group bank of that mint, mints it
+    /// returns `None` for are left out, as are balances `banks` returns `None` for.
+    pub fn get_emissions_observation_account_keys(
+        &self,
+        banks: impl Fn(&Pubkey) -> Option<BankRef>,
+        emissions_banks: impl Fn(&Pubkey) -> Option<(Pubkey, BankRef)>,
+    ) -> Vec<(Pubkey, Pubkey)> {
+        let mut emissions_mints: Vec<Pubkey> = vec![];
+        for bank in self
+            .get_active_balances_iter()
+            .filter_map(|balance| banks(&balance.bank_pk))
+        {
+            if bank.emissions_mint != Pubkey::default()
+                && !emissions_mints.contains(&bank.emissions_mint)
+            {
+                emissions_mints.push(bank.emissions_mint);
+            }
+        }
+
+        emissions_mints
+            .iter()
+            .filter_map(|emissions_mint| emissions_banks(emissions_mint))
+            .map(|(bank_pk, bank)| (bank_pk, bank.oracle))
+            .collect()
+    }
+}
+
+/// What [AstrolendAccount::get_observation_account_keys] needs to know about a bank, built from
+/// a loaded [Bank] or from the bank account.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BankRef {
+    /// See [crate::state::astrolend_group::BankConfig::get_oracle_account]
+    pub oracle: Pubkey,
+    /// Default for banks without emissions
+    pub emissions_mint: Pubkey,
+}
+
+impl From<&Bank> for BankRef {
+    fn from(bank: &Bank) -> Self {
+        Self {
+            oracle: bank.config.get_oracle_account(),
+            emissions_mint: bank.emissions_mint,
+        }
+    }
+}
+
+impl BankRef {
+    pub fn load<'info>(bank_ai: &'info AccountInfo<'info>) -> AstrolendResult<Self> {
+        let bank_loader = AccountLoader::<Bank>::try_from(bank_ai)?;
+        let bank = bank_loader.load()?;
+
+        Ok(Self::from(&*bank))
+    }
+}
+
+assert_struct_size!(LiquidationRecord, 120);
+assert_struct_align!(LiquidationRecord, 8);
+#[zero_copy(unsafe)]
+#[repr(C)]
+#[derive(Default)]
+#[cfg_attr(
+    any(feature = "test", feature = "client"),
+    derive(Debug, PartialEq, Eq, TypeLayout)
+)]
+pub struct LiquidationRecord {
+    /// The astrolend account of the liquidator.
+    pub liquidator_account: Pubkey,
+    pub asset_bank: Pubkey,
+    pub liability_bank: Pubkey,
+    pub timestamp: i64,
+    /// Native amount of collateral seized from the liquidatee.
+    pub asset_amount: u64,
+    /// Native amount of liability repaid on behalf of the liquidatee.
+    pub liability_amount: u64,
+}
+
+assert_struct_size!(BalanceCreditHistory, 48);
+assert_struct_align!(BalanceCreditHistory, 8);
+#[zero_copy(unsafe)]
+#[repr(C)]
+#[derive(Default)]
+#[cfg_attr(
+    any(feature = "test", feature = "client"),
+    derive(Debug, PartialEq, Eq, TypeLayout)
+)]
+pub struct BalanceCreditHistory {
+    /// Bank of the balance the counters belong to, default while the slot counts nothing.
+    pub bank_pk: Pubkey,
+    /// Native tokens borrowed over the lifetime of the balance, origination fees included.
+    /// Saturates.
+    pub total_borrowed_native: u64,
+    /// Native tokens repaid by the authority, third parties and liquidators. Saturates.
+    pub total_repaid_native: u64,
+}
+
+/// Load the account along with its credit history, `None` for legacy accounts, see
+/// [AstrolendAccount::is_legacy]. Errors like `AccountLoader::load_mut`.
+pub fn load_mut_with_credit_history<'a, 'info>(
+    loader: &'a AccountLoader<'info, AstrolendAccount>,
+) -> AstrolendResult<(RefMut<'a, AstrolendAccount>, Option<RefMut<'a, CreditHistory>>)> {
+    let (astrolend_account, credit_history) = load_mut_with_extension(loader)?;
+
+    match astrolend_account.is_legacy() {
+        true => Ok((astrolend_account, None)),
+        false => Ok((astrolend_account, credit_history)),
+    }
+}
+
+/// Load the account along with the region after it, whatever its version, `None` while the
+/// account data is too short to hold a [CreditHistory].
+pub fn load_mut_with_extension<'a, 'info>(
+    loader: &'a AccountLoader<'info, AstrolendAccount>,
+) -> AstrolendResult<(RefMut<'a, AstrolendAccount>, Option<RefMut<'a, CreditHistory>>)> {
+    // Discriminator and writable checks
+    drop(loader.load_mut()?);
+
+    let data = loader.as_ref().try_borrow_mut_data()?;
+    let (astrolend_account, extension) = RefMut::map_split(data, |data| {
+        let (astrolend_account, extension) =
+            data[8..].split_at_mut(std::mem::size_of::<AstrolendAccount>());
+
+        (bytemuck::from_bytes_mut(astrolend_account), extension)
+    });
+    let credit_history = RefMut::filter_map(extension, |extension| {
+        extension
+            .get_mut(..std::mem::size_of::<CreditHistory>())
+            .map(bytemuck::from_bytes_mut)
+    })
+    .ok();
+
+    Ok((astrolend_account, credit_history))
+}
+
+assert_struct_size!(CreditHistory, 816);
+assert_struct_align!(CreditHistory, 8);
+/// Lifetime borrow and repay counters of an account, stored in the account data after the
+/// [AstrolendAccount]. Legacy accounts are only grown to hold it by `lending_account_migrate`,
+/// and count nothing until then.
+#[zero_copy(unsafe)]
+#[repr(C)]
+#[cfg_attr(
+    any(feature = "test", feature = "client"),
+    derive(Debug, PartialEq, Eq, TypeLayout)
+)]
+pub struct CreditHistory {
+    /// Counters of the balances, slot for slot with `lending_account.balances`.
+    pub balances: [BalanceCreditHistory; MAX_LENDING_ACCOUNT_BALANCES], // 768
+    /// Counters of the balances closed since, rolled up across banks, see
+    /// [CreditHistory::sync].
+    pub closed_total_borrowed_native: u64, // 8
+    pub closed_total_repaid_native: u64, // 8
+    pub _padding: [u64; 4],              // 32
+}
+
+impl CreditHistory {
+    /// Add `amount` native tokens borrowed to the counters of the balance of `bank_pk`.
+    /// Returns the amount added, 0 without a balance.
+    pub fn record_borrow(
+        &mut self,
+        lending_account: &LendingAccount,
+        bank_pk: &Pubkey,
+        amount: u64,
+    ) -> u64 {
+        match self.balance_mut(lending_account, bank_pk) {
+            Some(credit) => {
+                credit.total_borrowed_native = credit.total_borrowed_native.saturating_add(amount);
+                amount
+            }
+            None => 0,
+        }
+    }
+
+    /// Add `amount` native tokens repaid to the counters of the balance of `bank_pk`, which
+    /// may have been closed by the repayment. Returns the amount added, 0 without a balance.
+    pub fn record_repay(
+        &mut self,
+        lending_account: &LendingAccount,
+        bank_pk: &Pubkey,
+        amount: u64,
+    ) -> u64 {
+        match self.balance_mut(lending_account, bank_pk) {
+            Some(credit) => {
+                credit.total_repaid_native = credit.total_repaid_native.saturating_add(amount);
+                amount
+            }
+            None => 0,
+        }
+    }
+
+    /// Counters of the balance of `bank_pk`: its current ones, or those of the slot its
+    /// active balance sits in. Whatever that slot still counts for a closed balance is rolled
+    /// up first.
+    fn balance_mut(
+        &mut self,
+        lending_account: &LendingAccount,
+        bank_pk: &Pubkey,
+    ) -> Option<&mut BalanceCreditHistory> {
+        let index = match self
+            .balances
+            .iter()
+            .position(|credit| credit.bank_pk == *bank_pk)
+        {
+            Some(index) => index,
+            None => {
+                let index = lending_account
+                    .balances
+                    .iter()
+                    .position(|balance| balance.active && balance.bank_pk == *bank_pk)?;
+                self.roll_up(index);
+                self.balances[index].bank_pk = *bank_pk;
+
+                index
+            }
+        };
+
+        Some(&mut self.balances[index])
+    }
+
+    /// Roll the counters of closed balances up into the account totals, freeing their slots.
+    /// Counters of balances left parked with emissions are rolled up too, they can no longer
+    /// borrow or repay.
+    pub fn sync(&mut self, lending_account: &LendingAccount) {
+        for index in 0..MAX_LENDING_ACCOUNT_BALANCES {
+            let balance = &lending_account.balances[index];
+            let bank_pk = self.balances[index].bank_pk;
+            if bank_pk != Pubkey::default() && !(balance.active && balance.bank_pk == bank_pk) {
+                self.roll_up(index);
+            }
+        }
+    }
+
+    fn roll_up(&mut self, index: usize) {
+        let credit = std::mem::take(&mut self.balances[index]);
+
+        self.closed_total_borrowed_native = self
+            .closed_total_borrowed_native
+            .saturating_add(credit.total_borrowed_native);
+        self.closed_total_repaid_native = self
+            .closed_total_repaid_native
+            .saturating_add(credit.total_repaid_native);
+    }
+
+    /// Counters of the open balance of `bank_pk`, if it ever borrowed or repaid.
+    #[cfg(any(feature = "test", feature = "client"))]
+    pub fn get_balance(
+        &self,
+        lending_account: &LendingAccount,
+        bank_pk: &Pubkey,
+    ) -> Option<BalanceCreditHistory> {
+        self.balances
+            .iter()
+            .zip(lending_account.balances.iter())
+            .find(|(credit, balance)| {
+                credit.bank_pk == *bank_pk && balance.active && balance.bank_pk == *bank_pk
+            })
+            .map(|(credit, _)| *credit)
+    }
+
+    /// Lifetime counters of the whole account, closed and open balances alike. Native amounts
+    /// of different banks are summed as is.
+    #[cfg(any(feature = "test", feature = "client"))]
+    pub fn get_totals(&self) -> (u64, u64) {
+        self.balances.iter().fold(
+            (
+                self.closed_total_borrowed_native,
+                self.closed_total_repaid_native,
+            ),
+            |(borrowed, repaid), credit| {
+                (
+                    borrowed.saturating_add(credit.total_borrowed_native),
+                    repaid.saturating_add(credit.total_repaid_native),
+                )
+            },
+        )
+    }
+}
+
+/// A balance projected forward with [AstrolendAccount::project_with].
+#[cfg(any(feature = "test", feature = "client"))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ProjectedBalance {
+    pub bank_pk: Pubkey,
+    pub asset_amount: I80F48,
+    pub liability_amount: I80F48,
 }
 
 #[derive(Debug)]
@@ -154,8 +675,13 @@ pub struct BankAccountWithPriceFeed<'a, 'info> {
     bank: AccountInfo<'info>,
     price_feed: Box<AstrolendResult<OraclePriceFeedAdapter>>,
     balance: &'a Balance,
+    emode_tag: u16,
+    emissions_mint: Pubkey,
+    /// Clock timestamp the asset weights are evaluated at, see `Bank::get_asset_weight`.
+    timestamp: i64,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum BalanceSide {
     Assets,
     Liabilities,
@@ -196,22 +722,29 @@ impl<'info> BankAccountWithPriceFeed<'_, 'info> {
                     AstrolendError::InvalidBankAccount
                 );
 
-                let price_adapter = {
+                let (price_adapter, emode_tag, emissions_mint) = {
                     let oracle_ais = &remaining_ais[oracle_ai_idx..oracle_ai_idx + 1];
                     let bank_al = AccountLoader::<Bank>::try_from(bank_ai)?;
                     let bank = bank_al.load()?;
 
-                    Box::new(OraclePriceFeedAdapter::try_from_bank_config(
-                        &bank.config,
-                        oracle_ais,
-                        &clock,
-                    ))
+                    (
+                        Box::new(OraclePriceFeedAdapter::try_from_bank(
+                            &bank,
+                            oracle_ais,
+                            &clock,
+                        )),
+                        bank.config.emode_tag,
+                        bank.emissions_mint,
+                    )
                 };
 
                 Ok(BankAccountWithPriceFeed {
                     bank: bank_ai.clone(),
                     price_feed: price_adapter,
                     balance,
+                    emode_tag,
+                    emissions_mint,
+                    timestamp: clock.unix_timestamp,
                 })
             })
             .collect::<Result<Vec<_>>>()
@@ -226,9 +759,11 @@ impl<'info> BankAccountWithPriceFeed<'_, 'info> {
     /// 3. Initial requirement is discounted by the initial discount, if enabled and the usd limit is exceeded.
     /// 4. Assets are only calculated for collateral risk tier.
     /// 5. Oracle errors are ignored for deposits in isolated risk tier.
+    /// 6. Asset weights are raised to the `(init, maint)` emode weights, if provided.
     fn calc_weighted_assets_and_liabilities_values<'a>(
         &'a self,
         requirement_type: RequirementType,
+        emode_asset_weights: Option<(I80F48, I80F48)>,
     ) -> AstrolendResult<(I80F48, I80F48)>
     where
         'info: 'a,
@@ -241,7 +776,7 @@ impl<'info> BankAccountWithPriceFeed<'_, 'info> {
                 let bank = bank_al.load()?;
                 match side {
                     BalanceSide::Assets => Ok((
-                        self.calc_weighted_assets(requirement_type, &bank)?,
+                        self.calc_weighted_assets(requirement_type, &bank, emode_asset_weights)?,
                         I80F48::ZERO,
                     )),
                     BalanceSide::Liabilities => Ok((
@@ -259,49 +794,16 @@ impl<'info> BankAccountWithPriceFeed<'_, 'info> {
         &'a self,
         requirement_type: RequirementType,
         bank: &'a Bank,
+        emode_asset_weights: Option<(I80F48, I80F48)>,
     ) -> AstrolendResult<I80F48> {
-        match bank.config.risk_tier {
-            RiskTier::Collateral => {
-                let price_feed = self.try_get_price_feed();
-
-                if matches!(
-                    (&price_feed, requirement_type),
-                    (&Err(PriceFeedError::StaleOracle), RequirementType::Initial)
-                ) {
-                    debug!("Skipping stale oracle");
-                    return Ok(I80F48::ZERO);
-                }
-
-                let price_feed = price_feed?;
-
-                let mut asset_weight = bank
-                    .config
-                    .get_weight(requirement_type, BalanceSide::Assets);
-
-                let lower_price = price_feed.get_price_of_type(
-                    requirement_type.get_oracle_price_type(),
-                    Some(PriceBias::Low),
-                )?;
-
-                if matches!(requirement_type, RequirementType::Initial) {
-                    if let Some(discount) =
-                        bank.maybe_get_asset_weight_init_discount(lower_price)?
-                    {
-                        asset_weight = asset_weight
-                            .checked_mul(discount)
-                            .ok_or_else(math_error!())?;
-                    }
-                }
-
-                calc_value(
-                    bank.get_asset_amount(self.balance.asset_shares.into())?,
-                    lower_price,
-                    bank.mint_decimals,
-                    Some(asset_weight),
-                )
-            }
-            RiskTier::Isolated => Ok(I80F48::ZERO),
-        }
+        calc_weighted_asset_value(
+            bank,
+            bank.get_asset_amount(self.balance.asset_shares.into())?,
+            self.try_get_price_feed(),
+            requirement_type,
+            emode_asset_weights,
+            self.timestamp,
+        )
     }
 
     #[inline(always)]
@@ -310,21 +812,11 @@ impl<'info> BankAccountWithPriceFeed<'_, 'info> {
         requirement_type: RequirementType,
         bank: &Bank,
     ) -> AstrolendResult<I80F48> {
-        let price_feed = self.try_get_price_feed()?;
-        let liability_weight = bank
-            .config
-            .get_weight(requirement_type, BalanceSide::Liabilities);
-
-        let higher_price = price_feed.get_price_of_type(
-            requirement_type.get_oracle_price_type(),
-            Some(PriceBias::High),
-        )?;
-
-        calc_value(
-            bank.get_liability_amount(self.balance.liability_shares.into())?,
-            higher_price,
-            bank.mint_decimals,
-            Some(liability_weight),
+        calc_weighted_liability_value(
+            bank,
+            self.balance,
+            self.try_get_price_feed(),
+            requirement_type,
         )
     }
 
@@ -345,6 +837,91 @@ impl<'info> BankAccountWithPriceFeed<'_, 'info> {
     }
 }
 
+/// Settled emissions of an account, valued as a deposit in the group bank of the emissions mint
+/// when the group has `EMISSIONS_COLLATERAL_FLAG` set.
+///
+/// Only whole native units count, as claims transfer, and only emissions of active balances:
+/// the emissions mint is read from the balance bank in the observation accounts.
+pub struct EmissionsWithPriceFeed<'info> {
+    bank: &'info AccountInfo<'info>,
+    mint: Pubkey,
+    price_feed: Box<AstrolendResult<OraclePriceFeedAdapter>>,
+    amount: I80F48,
+    timestamp: i64,
+}
+
+impl<'info> EmissionsWithPriceFeed<'info> {
+    /// Load the optional `[bank, oracle]` pairs following the observation accounts of the
+    /// balances, one per emissions mint. Banks of mints no balance emits add nothing.
+    pub fn load(
+        astrolend_account: &AstrolendAccount,
+        bank_accounts_with_price: &[BankAccountWithPriceFeed<'_, 'info>],
+        emissions_ais: &'info [AccountInfo<'info>],
+    ) -> AstrolendResult<Vec<EmissionsWithPriceFeed<'info>>> {
+        check!(
+            emissions_ais.len() % 2 == 0,
+            AstrolendError::MissingPythOrBankAccount
+        );
+
+        let clock = Clock::get()?;
+        let mut emissions: Vec<EmissionsWithPriceFeed> = vec![];
+
+        for ais in emissions_ais.chunks_exact(2) {
+            let bank_al = AccountLoader::<Bank>::try_from(&ais[0])?;
+            let bank = bank_al.load()?;
+
+            check!(
+                bank.group == astrolend_account.group
+                    && emissions.iter().all(|e| e.mint != bank.mint),
+                AstrolendError::InvalidBankAccount
+            );
+
+            let amount = bank_accounts_with_price
+                .iter()
+                .filter(|a| a.emissions_mint == bank.mint)
+                .try_fold(I80F48::ZERO, |total, a| {
+                    total
+                        .checked_add(I80F48::from(a.balance.emissions_outstanding).floor())
+                        .ok_or_else(math_error!())
+                })?;
+
+            debug!("Emissions of {}: {}", bank.mint, amount);
+
+            emissions.push(EmissionsWithPriceFeed {
+                bank: &ais[0],
+                mint: bank.mint,
+                price_feed: Box::new(OraclePriceFeedAdapter::try_from_bank(
+                    &bank,
+                    &ais[1..],
+                    &clock,
+                )),
+                amount,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+
+        Ok(emissions)
+    }
+
+    /// Weighted value of the emissions, at the bank asset weights without emode.
+    fn calc_weighted_value(&self, requirement_type: RequirementType) -> AstrolendResult<I80F48> {
+        let bank_al = AccountLoader::<Bank>::try_from(self.bank)?;
+        let bank = bank_al.load()?;
+
+        calc_weighted_asset_value(
+            &bank,
+            self.amount,
+            self.price_feed
+                .as_ref()
+                .as_ref()
+                .map_err(|_| PriceFeedError::StaleOracle),
+            requirement_type,
+            None,
+            self.timestamp,
+        )
+    }
+}
+
 enum PriceFeedError {
     StaleOracle,
 }
@@ -357,6 +934,129 @@ impl From<PriceFeedError> for Error {
     }
 }
 
+/// Weighted value of `asset_amount` deposited in `bank`, see
+/// `BankAccountWithPriceFeed::calc_weighted_assets_and_liabilities_values`.
+#[inline(always)]
+fn calc_weighted_asset_value(
+    bank: &Bank,
+    asset_amount: I80F48,
+    price_feed: std::result::Result<&OraclePriceFeedAdapter, PriceFeedError>,
+    requirement_type: RequirementType,
+    emode_asset_weights: Option<(I80F48, I80F48)>,
+    timestamp: i64,
+) -> AstrolendResult<I80F48> {
+    // Borrow-only deposits never collateralize, whatever the configured or emode weights.
+    // They still count towards equity, the account isn't bankrupt while it holds them.
+    if bank.is_borrow_only() && !matches!(requirement_type, RequirementType::Equity) {
+        return Ok(I80F48::ZERO);
+    }
+
+    match bank.config.risk_tier {
+        RiskTier::Collateral => {
+            // Only an oracle that failed to load is skipped, and only for initial health where
+            // valuing the deposit at zero is the conservative side. Errors pricing a loaded
+            // oracle, `OraclePriceNonPositive` included, abort in every context.
+            if matches!(
+                (&price_feed, requirement_type),
+                (&Err(PriceFeedError::StaleOracle), RequirementType::Initial)
+            ) {
+                debug!("Skipping stale oracle");
+                return Ok(I80F48::ZERO);
+            }
+
+            let price_feed = price_feed?;
+
+            let mut asset_weight = bank.get_asset_weight(requirement_type, timestamp)?;
+
+            let emode_asset_weight = match (requirement_type, emode_asset_weights) {
+                (RequirementType::Initial, Some((init, _))) => Some(init),
+                (RequirementType::Maintenance, Some((_, maint))) => Some(maint),
+                _ => None,
+            };
+
+            if let Some(emode_asset_weight) = emode_asset_weight {
+                asset_weight = asset_weight.max(emode_asset_weight);
+            }
+
+            let lower_price = price_feed.get_price_of_type(
+                requirement_type.get_oracle_price_type(),
+                Some(PriceBias::Low),
+            )?;
+
+            if matches!(requirement_type, RequirementType::Initial) {
+                if let Some(discount) = bank.maybe_get_asset_weight_init_discount(lower_price)? {
+                    asset_weight = asset_weight
+                        .checked_mul(discount)
+                        .ok_or_else(math_error!())?;
+                }
+            }
+
+            calc_value(
+                asset_amount,
+                lower_price,
+                bank.mint_decimals,
+                Some(asset_weight),
+            )
+        }
+        RiskTier::Isolated => Ok(I80F48::ZERO),
+    }
+}
+
+/// Weighted value of the liabilities of `balance`, see
+/// `BankAccountWithPriceFeed::calc_weighted_assets_and_liabilities_values`.
+#[inline(always)]
+fn calc_weighted_liability_value(
+    bank: &Bank,
+    balance: &Balance,
+    price_feed: std::result::Result<&OraclePriceFeedAdapter, PriceFeedError>,
+    requirement_type: RequirementType,
+) -> AstrolendResult<I80F48> {
+    // Liabilities are never valued without a price, any oracle error aborts
+    let price_feed = price_feed?;
+    let liability_weight = bank
+        .config
+        .get_weight(requirement_type, BalanceSide::Liabilities);
+
+    let higher_price = price_feed.get_price_of_type(
+        requirement_type.get_oracle_price_type(),
+        Some(PriceBias::High),
+    )?;
+
+    calc_value(
+        bank.get_liability_amount(balance.liability_shares.into())?,
+        higher_price,
+        bank.mint_decimals,
+        Some(liability_weight),
+    )
+}
+
+/// Convert a native token amount to a UI amount.
+///
+/// Scaling happens before any price or weight is applied, so a max u64 amount stays well
+/// within I80F48 range regardless of the mint decimals.
+#[inline]
+pub fn native_to_ui(amount: I80F48, mint_decimals: u8) -> AstrolendResult<I80F48> {
+    let scaling_factor = EXP_10_I80F48
+        .get(mint_decimals as usize)
+        .ok_or_else(math_error!())?;
+
+    Ok(amount
+        .checked_div(*scaling_factor)
+        .ok_or_else(math_error!())?)
+}
+
+/// Convert a UI token amount to a native amount.
+#[inline]
+pub fn ui_to_native(ui_amount: I80F48, mint_decimals: u8) -> AstrolendResult<I80F48> {
+    let scaling_factor = EXP_10_I80F48
+        .get(mint_decimals as usize)
+        .ok_or_else(math_error!())?;
+
+    Ok(ui_amount
+        .checked_mul(*scaling_factor)
+        .ok_or_else(math_error!())?)
+}
+
 /// Calculate the value of an asset, given its quantity with a decimal exponent, and a price with a decimal exponent, and an optional weight.
 #[inline]
 pub fn calc_value(
@@ -369,12 +1069,12 @@ pub fn calc_value(
         return Ok(I80F48::ZERO);
     }
 
-    let scaling_factor = EXP_10_I80F48[mint_decimals as usize];
+    let ui_amount = native_to_ui(amount, mint_decimals)?;
 
     let weighted_asset_amount = if let Some(weight) = weight {
-        amount.checked_mul(weight).unwrap()
+        ui_amount.checked_mul(weight).ok_or_else(math_error!())?
     } else {
-        amount
+        ui_amount
     };
 
     // #[cfg(target_os = "solana")]
@@ -385,8 +1085,6 @@ pub fn calc_value(
 
     let value = weighted_asset_amount
         .checked_mul(price)
-        .ok_or_else(math_error!())?
-        .checked_div(scaling_factor)
         .ok_or_else(math_error!())?;
 
     Ok(value)
@@ -394,15 +1092,9 @@ pub fn calc_value(
 
 #[inline]
 pub fn calc_amount(value: I80F48, price: I80F48, mint_decimals: u8) -> AstrolendResult<I80F48> {
-    let scaling_factor = EXP_10_I80F48[mint_decimals as usize];
-
-    let qt = value
-        .checked_mul(scaling_factor)
-        .ok_or_else(math_error!())?
-        .checked_div(price)
-        .ok_or_else(math_error!())?;
+    let ui_amount = value.checked_div(price).ok_or_else(math_error!())?;
 
-    Ok(qt)
+    ui_to_native(ui_amount, mint_decimals)
 }
 
 pub enum RiskRequirementType {
@@ -421,13 +1113,52 @@ impl RiskRequirementType {
     }
 }
 
+/// Context of the health check an instruction runs after changing an account, deciding what
+/// `RiskEngine::check_post_action` skips.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HealthCheckContext {
+    /// Account outside of a flashloan, the initial requirement is enforced.
+    Normal,
+    /// Account between `start_flashloan` and `end_flashloan`, the check is deferred to
+    /// `end_flashloan`.
+    InFlashloan,
+    /// Liquidator account of a liquidation, deferred to `end_flashloan` if the liquidator is in a
+    /// flashloan, enforced otherwise.
+    Liquidation,
+}
+
+impl HealthCheckContext {
+    /// Context of an action of the account itself, `InFlashloan` with `IN_FLASHLOAN_FLAG` set.
+    pub fn of_account(astrolend_account: &AstrolendAccount) -> Self {
+        if astrolend_account.get_flag(IN_FLASHLOAN_FLAG) {
+            HealthCheckContext::InFlashloan
+        } else {
+            HealthCheckContext::Normal
+        }
+    }
+}
+
+/// Weighted USD values of an account, taken by `RiskEngine::check_post_action`.
+pub struct AccountHealthSnapshot {
+    pub init_assets: I80F48,
+    pub init_liabilities: I80F48,
+    pub init_equity: I80F48,
+    pub maint_assets: I80F48,
+    pub maint_liabilities: I80F48,
+    pub maint_equity: I80F48,
+    pub active_balances: u8,
+}
+
 pub struct RiskEngine<'a, 'info> {
+    astrolend_group: &'a AstrolendGroup,
     astrolend_account: &'a AstrolendAccount,
     bank_accounts_with_price: Vec<BankAccountWithPriceFeed<'a, 'info>>,
+    emissions_with_price: Vec<EmissionsWithPriceFeed<'info>>,
 }
 
 impl<'info> RiskEngine<'_, 'info> {
     pub fn new<'a>(
+        astrolend_group: &'a AstrolendGroup,
         astrolend_account: &'a AstrolendAccount,
         remaining_ais: &'info [AccountInfo<'info>],
     ) -> AstrolendResult<RiskEngine<'a, 'info>> {
@@ -436,41 +1167,134 @@ impl<'info> RiskEngine<'_, 'info> {
             AstrolendError::AccountInFlashloan
         );
 
-        Self::new_no_flashloan_check(astrolend_account, remaining_ais)
+        Self::new_no_flashloan_check(astrolend_group, astrolend_account, remaining_ais)
     }
 
     /// Internal constructor used either after manually checking account is not in a flashloan,
     /// or explicity checking health for flashloan enabled actions.
+    ///
+    /// With `EMISSIONS_COLLATERAL_FLAG` set, the accounts following the observation accounts of
+    /// the balances are the optional `[bank, oracle]` pairs of `EmissionsWithPriceFeed::load`.
     fn new_no_flashloan_check<'a>(
+        astrolend_group: &'a AstrolendGroup,
         astrolend_account: &'a AstrolendAccount,
         remaining_ais: &'info [AccountInfo<'info>],
     ) -> AstrolendResult<RiskEngine<'a, 'info>> {
         let bank_accounts_with_price =
             BankAccountWithPriceFeed::load(&astrolend_account.lending_account, remaining_ais)?;
 
+        let emissions_with_price = if astrolend_group.get_group_flag(EMISSIONS_COLLATERAL_FLAG) {
+            EmissionsWithPriceFeed::load(
+                astrolend_account,
+                &bank_accounts_with_price,
+                &remaining_ais[bank_accounts_with_price.len() * 2..],
+            )?
+        } else {
+            vec![]
+        };
+
         Ok(RiskEngine {
+            astrolend_group,
             astrolend_account,
             bank_accounts_with_price,
+            emissions_with_price,
         })
     }
 
-    /// Checks account is healthy after performing actions that increase risk (removing liquidity).
+    /// Checks account is healthy after performing actions that increase risk (removing liquidity).
+    /// Single entry point of these checks, the skip logic of each `ctx_kind`:
+    /// - `Normal`: enforced, errors if the account is in a flashloan.
+    /// - `InFlashloan`: skipped, `remaining_ais` can be an empty vec. Errors if the account is not
+    ///   in a flashloan. `start_flashloan` only lets a flashloan begin with its `end_flashloan`
+    ///   in the transaction, which clears the flag and enforces the check as `Normal`.
+    /// - `Liquidation`: skipped while the liquidator is in a flashloan, enforced otherwise.
+    ///
+    /// Returns a snapshot of the account health if the check passed and the group has
+    /// `HEALTH_SNAPSHOT_EVENTS_FLAG` set, for the caller to emit.
+    pub fn check_post_action<'a>(
+        astrolend_group: &'a AstrolendGroup,
+        astrolend_account: &'a AstrolendAccount,
+        remaining_ais: &'info [AccountInfo<'info>],
+        ctx_kind: HealthCheckContext,
+    ) -> AstrolendResult<Option<AccountHealthSnapshot>> {
+        let in_flashloan = astrolend_account.get_flag(IN_FLASHLOAN_FLAG);
+
+        match ctx_kind {
+            HealthCheckContext::Normal => {
+                check!(!in_flashloan, AstrolendError::AccountInFlashloan);
+            }
+            HealthCheckContext::InFlashloan => {
+                check!(in_flashloan, AstrolendError::IllegalFlashloan);
+                debug!("In flashloan, skipping health check");
+                return Ok(None);
+            }
+            HealthCheckContext::Liquidation => {
+                if in_flashloan {
+                    debug!("Liquidator in flashloan, skipping health check");
+                    return Ok(None);
+                }
+            }
+        }
+
+        Self::check_account_init_health(astrolend_group, astrolend_account, remaining_ais)
+    }
+
+    /// Accounts without liabilities are always healthy, no prices are loaded and no snapshot is
+    /// taken, `remaining_ais` can be an empty vec. Callers check after their mutation of the
+    /// account, so a liability opened by the instruction itself is always valued.
+    fn check_account_init_health<'a>(
+        astrolend_group: &'a AstrolendGroup,
+        astrolend_account: &'a AstrolendAccount,
+        remaining_ais: &'info [AccountInfo<'info>],
+    ) -> AstrolendResult<Option<AccountHealthSnapshot>> {
+        if !astrolend_account.lending_account.has_liabilities() {
+            debug!("No liabilities, skipping health check");
+            return Ok(None);
+        }
+
+        let risk_engine =
+            Self::new_no_flashloan_check(astrolend_group, astrolend_account, remaining_ais)?;
+        let (init_assets, init_liabilities) =
+            risk_engine.check_account_health(RiskRequirementType::Initial)?;
+
+        if !astrolend_group.get_group_flag(HEALTH_SNAPSHOT_EVENTS_FLAG) {
+            return Ok(None);
+        }
+
+        let (maint_assets, maint_liabilities) =
+            risk_engine.get_account_health_components(RiskRequirementType::Maintenance)?;
+
+        Ok(Some(AccountHealthSnapshot {
+            init_assets,
+            init_liabilities,
+            init_equity: init_assets
+                .checked_sub(init_liabilities)
+                .ok_or_else(math_error!())?,
+            maint_assets,
+            maint_liabilities,
+            maint_equity: maint_assets
+                .checked_sub(maint_liabilities)
+                .ok_or_else(math_error!())?,
+            active_balances: risk_engine.bank_accounts_with_price.len() as u8,
+        }))
+    }
+
+    /// Emode tags of all liabilities of the account, deduplicated.
     ///
-    /// `IN_FLASHLOAN_FLAG` behavior.
-    /// - Health check is skipped.
-    /// - `remaining_ais` can be an empty vec.
-    pub fn check_account_init_health<'a>(
-        astrolend_account: &'a AstrolendAccount,
-        remaining_ais: &'info [AccountInfo<'info>],
-    ) -> AstrolendResult<()> {
-        if astrolend_account.get_flag(IN_FLASHLOAN_FLAG) {
-            return Ok(());
-        }
+    /// Pre-pass deciding which collateral qualifies for boosted emode weights,
+    /// an untagged liability disqualifies all of it.
+    fn get_emode_liability_tags(&self) -> Vec<u16> {
+        let mut tags = self
+            .bank_accounts_with_price
+            .iter()
+            .filter(|a| a.is_empty(BalanceSide::Liabilities).not())
+            .map(|a| a.emode_tag)
+            .collect::<Vec<_>>();
 
-        Self::new_no_flashloan_check(astrolend_account, remaining_ais)?
-            .check_account_health(RiskRequirementType::Initial)?;
+        tags.sort_unstable();
+        tags.dedup();
 
-        Ok(())
+        tags
     }
 
     /// Returns the total assets and liabilities of the account in the form of (assets, liabilities)
@@ -481,9 +1305,17 @@ impl<'info> RiskEngine<'_, 'info> {
         let mut total_assets = I80F48::ZERO;
         let mut total_liabilities = I80F48::ZERO;
 
+        let emode_liability_tags = self.get_emode_liability_tags();
+
         for a in &self.bank_accounts_with_price {
-            let (assets, liabilities) =
-                a.calc_weighted_assets_and_liabilities_values(requirement_type.to_weight_type())?;
+            let emode_asset_weights = self
+                .astrolend_group
+                .get_emode_asset_weights(a.emode_tag, &emode_liability_tags);
+
+            let (assets, liabilities) = a.calc_weighted_assets_and_liabilities_values(
+                requirement_type.to_weight_type(),
+                emode_asset_weights,
+            )?;
 
             debug!(
                 "Balance {}, assets: {}, liabilities: {}",
@@ -496,6 +1328,14 @@ impl<'info> RiskEngine<'_, 'info> {
                 .ok_or_else(math_error!())?;
         }
 
+        for e in &self.emissions_with_price {
+            let assets = e.calc_weighted_value(requirement_type.to_weight_type())?;
+
+            debug!("Emissions {}, assets: {}", e.mint, assets);
+
+            total_assets = total_assets.checked_add(assets).ok_or_else(math_error!())?;
+        }
+
         Ok((total_assets, total_liabilities))
     }
 
@@ -511,7 +1351,11 @@ impl<'info> RiskEngine<'_, 'info> {
             .ok_or_else(math_error!())?)
     }
 
-    fn check_account_health(&self, requirement_type: RiskRequirementType) -> AstrolendResult {
+    /// Returns the (assets, liabilities) the check was made on.
+    fn check_account_health(
+        &self,
+        requirement_type: RiskRequirementType,
+    ) -> AstrolendResult<(I80F48, I80F48)> {
         let (total_weighted_assets, total_weighted_liabilities) =
             self.get_account_health_components(requirement_type)?;
 
@@ -520,14 +1364,22 @@ impl<'info> RiskEngine<'_, 'info> {
             total_weighted_assets, total_weighted_liabilities
         );
 
+        // The group buffer only raises the bar for initial health
+        let required_liabilities = match requirement_type {
+            RiskRequirementType::Initial => self
+                .astrolend_group
+                .get_init_health_required_liabilities(total_weighted_liabilities)?,
+            _ => total_weighted_liabilities,
+        };
+
         check!(
-            total_weighted_assets >= total_weighted_liabilities,
+            total_weighted_assets >= required_liabilities,
             AstrolendError::RiskEngineInitRejected
         );
 
         self.check_account_risk_tiers()?;
 
-        Ok(())
+        Ok((total_weighted_assets, total_weighted_liabilities))
     }
 
     /// Checks
@@ -644,12 +1496,49 @@ impl<'info> RiskEngine<'_, 'info> {
         Ok(account_health)
     }
 
+    /// Whether the account is below the maintenance requirement and its collateral is worth less
+    /// than the group `min_liquidation_value_usd`, too little for a liquidation to pay for
+    /// itself.
+    ///
+    /// Returns the (candidate, maintenance health, collateral value) of the account.
+    pub fn get_bad_debt_candidate_status(&self) -> AstrolendResult<(bool, I80F48, I80F48)> {
+        let (maint_assets, maint_liabs) =
+            self.get_account_health_components(RiskRequirementType::Maintenance)?;
+        let maint_health = maint_assets
+            .checked_sub(maint_liabs)
+            .ok_or_else(math_error!())?;
+        let (collateral_value, _) =
+            self.get_account_health_components(RiskRequirementType::Equity)?;
+
+        debug!(
+            "bad_debt_candidate: maint health {}, collateral {}",
+            maint_health, collateral_value
+        );
+
+        let is_candidate = maint_health < I80F48::ZERO
+            && collateral_value < self.astrolend_group.get_min_liquidation_value();
+
+        Ok((is_candidate, maint_health, collateral_value))
+    }
+
     /// Check that the account is in a bankrupt state.
     /// Account needs to be insolvent and total value of assets need to be below the bankruptcy threshold.
+    ///
+    /// Accounts flagged with `BAD_DEBT_CANDIDATE_FLAG` may hold assets up to the group
+    /// `min_liquidation_value_usd`, the handler sweeps them before settling the debt.
     pub fn check_account_bankrupt(&self) -> AstrolendResult {
         let (total_assets, total_liabilities) =
             self.get_account_health_components(RiskRequirementType::Equity)?;
 
+        let bankrupt_threshold = if self.astrolend_account.get_flag(BAD_DEBT_CANDIDATE_FLAG) {
+            max(
+                BANKRUPT_THRESHOLD,
+                self.astrolend_group.get_min_liquidation_value(),
+            )
+        } else {
+            BANKRUPT_THRESHOLD
+        };
+
         check!(
             !self.astrolend_account.get_flag(IN_FLASHLOAN_FLAG),
             AstrolendError::AccountInFlashloan
@@ -666,7 +1555,7 @@ impl<'info> RiskEngine<'_, 'info> {
             AstrolendError::AccountNotBankrupt
         );
         check!(
-            total_assets < BANKRUPT_THRESHOLD && total_liabilities > ZERO_AMOUNT_THRESHOLD,
+            total_assets < bankrupt_threshold && total_liabilities > ZERO_AMOUNT_THRESHOLD,
             AstrolendError::AccountNotBankrupt
         );
 
@@ -705,9 +1594,313 @@ impl<'info> RiskEngine<'_, 'info> {
     }
 }
 
-const MAX_LENDING_ACCOUNT_BALANCES: usize = 16;
+/// Offchain simulation of the borrow and withdraw checks, for clients quoting the largest
+/// amount an account can take out of a bank. Banks are expected to be accrued up to
+/// `timestamp` and keyed by address in `bank_map`, `price_map` holds the price feed of each bank
+/// the account has a balance in, plus the target bank.
+#[cfg(any(feature = "test", feature = "client"))]
+impl RiskEngine<'_, '_> {
+    /// Largest native amount `astrolend_account` can borrow from `bank_pk`, 0 if it can't borrow.
+    ///
+    /// The borrow is replayed on copies of the bank and account with the same share accounting,
+    /// origination fee, liability weight and high biased price as the instruction, against the
+    /// initial health with the group `init_health_buffer_bps`, the bank borrow limits and
+    /// utilization, the group per slot borrow cap and the liquidity vault balance. Token-2022
+    /// transfer fees and borrows made earlier in the same slot are not included.
+    pub fn get_max_borrow_for_bank(
+        astrolend_group: &AstrolendGroup,
+        astrolend_account: &AstrolendAccount,
+        bank_pk: &Pubkey,
+        bank_map: &HashMap<Pubkey, Bank>,
+        price_map: &HashMap<Pubkey, OraclePriceFeedAdapter>,
+        timestamp: i64,
+    ) -> AstrolendResult<I80F48> {
+        let bank = bank_map
+            .get(bank_pk)
+            .ok_or(AstrolendError::BankAccountNotFound)?;
+
+        let max_amount = find_max_amount(bank.liquidity_vault_balance_cached, |amount| {
+            Self::simulate_borrow(
+                astrolend_group,
+                astrolend_account,
+                bank_pk,
+                bank_map,
+                price_map,
+                amount,
+                timestamp,
+            )
+            .is_ok()
+        });
+
+        // Searched without the minimum, it doesn't hold for the amounts below it
+        let has_balance = astrolend_account.lending_account.has_balance(bank_pk);
+        if !has_balance && bank.config.check_min_borrow_amount(max_amount).is_err() {
+            return Ok(I80F48::ZERO);
+        }
+
+        Ok(I80F48::from_num(max_amount))
+    }
+
+    /// Largest native amount `astrolend_account` can withdraw from `bank_pk`, 0 if it has no
+    /// deposit there.
+    ///
+    /// The withdrawal is replayed like [RiskEngine::get_max_borrow_for_bank], against the
+    /// initial health of accounts with liabilities, the bank utilization and the liquidity vault
+    /// balance after the withdraw fee. Token-2022 transfer fees are not included.
+    pub fn get_max_withdraw_for_bank(
+        astrolend_group: &AstrolendGroup,
+        astrolend_account: &AstrolendAccount,
+        bank_pk: &Pubkey,
+        bank_map: &HashMap<Pubkey, Bank>,
+        price_map: &HashMap<Pubkey, OraclePriceFeedAdapter>,
+        timestamp: i64,
+    ) -> AstrolendResult<I80F48> {
+        let bank = bank_map
+            .get(bank_pk)
+            .ok_or(AstrolendError::BankAccountNotFound)?;
+        let Some(balance) = astrolend_account.lending_account.get_balance(bank_pk) else {
+            return Ok(I80F48::ZERO);
+        };
+
+        let asset_amount: u64 = bank
+            .get_asset_amount(balance.asset_shares.into())?
+            .checked_floor()
+            .and_then(|amount| amount.checked_to_num())
+            .ok_or_else(math_error!())?;
+
+        let max_amount = find_max_amount(asset_amount, |amount| {
+            Self::simulate_withdraw(
+                astrolend_group,
+                astrolend_account,
+                bank_pk,
+                bank_map,
+                price_map,
+                amount,
+                timestamp,
+            )
+            .is_ok()
+        });
+
+        Ok(I80F48::from_num(max_amount))
+    }
+
+    fn simulate_borrow(
+        astrolend_group: &AstrolendGroup,
+        astrolend_account: &AstrolendAccount,
+        bank_pk: &Pubkey,
+        bank_map: &HashMap<Pubkey, Bank>,
+        price_map: &HashMap<Pubkey, OraclePriceFeedAdapter>,
+        amount: u64,
+        timestamp: i64,
+    ) -> AstrolendResult {
+        let mut bank = *bank_map
+            .get(bank_pk)
+            .ok_or(AstrolendError::BankAccountNotFound)?;
+        let mut lending_account = astrolend_account.lending_account;
+
+        let price = price_map
+            .get(bank_pk)
+            .ok_or(AstrolendError::MissingPythOrBankAccount)?
+            .get_price_of_type(
+                RequirementType::Initial.get_oracle_price_type(),
+                Some(PriceBias::High),
+            )?;
+        astrolend_group.check_slot_borrow_value(calc_value(
+            I80F48::from_num(amount),
+            price,
+            bank.mint_decimals,
+            None,
+        )?)?;
+
+        let origination_fee = if astrolend_account.get_flag(FEE_EXEMPT_FLAG) {
+            I80F48::ZERO
+        } else {
+            bank.calc_origination_fee(I80F48::from_num(amount))?
+        };
+
+        BankAccountWrapper::find_or_create_at(
+            bank_pk,
+            &mut bank,
+            &mut lending_account,
+            timestamp as u64,
+        )?
+        .borrow_at(
+            I80F48::from_num(amount)
+                .checked_add(origination_fee)
+                .ok_or_else(math_error!())?,
+            timestamp as u64,
+        )?;
+
+        bank.check_usd_borrow_limit(price)?;
+
+        check!(
+            amount <= bank.liquidity_vault_balance_cached,
+            AstrolendError::InsufficientLiquidity
+        );
+
+        let mut bank_map = bank_map.clone();
+        bank_map.insert(*bank_pk, bank);
+
+        Self::simulate_init_health_check(
+            astrolend_group,
+            &lending_account,
+            &bank_map,
+            price_map,
+            timestamp,
+        )
+    }
+
+    fn simulate_withdraw(
+        astrolend_group: &AstrolendGroup,
+        astrolend_account: &AstrolendAccount,
+        bank_pk: &Pubkey,
+        bank_map: &HashMap<Pubkey, Bank>,
+        price_map: &HashMap<Pubkey, OraclePriceFeedAdapter>,
+        amount: u64,
+        timestamp: i64,
+    ) -> AstrolendResult {
+        let mut bank = *bank_map
+            .get(bank_pk)
+            .ok_or(AstrolendError::BankAccountNotFound)?;
+        let mut lending_account = astrolend_account.lending_account;
+
+        let mut bank_account = BankAccountWrapper::find(bank_pk, &mut bank, &mut lending_account)?;
+        bank_account.withdraw_at(I80F48::from_num(amount), timestamp as u64)?;
+
+        let withdraw_fee = if astrolend_account.get_flag(FEE_EXEMPT_FLAG) {
+            0
+        } else {
+            bank_account.bank.collect_withdraw_fee(amount)?
+        };
+
+        check!(
+            amount - withdraw_fee <= bank.liquidity_vault_balance_cached,
+            AstrolendError::InsufficientLiquidity
+        );
+
+        if !lending_account.has_liabilities() {
+            return Ok(());
+        }
+
+        let mut bank_map = bank_map.clone();
+        bank_map.insert(*bank_pk, bank);
+
+        Self::simulate_init_health_check(
+            astrolend_group,
+            &lending_account,
+            &bank_map,
+            price_map,
+            timestamp,
+        )
+    }
+
+    /// Same checks as `check_post_action`, on banks and prices from the maps.
+    fn simulate_init_health_check(
+        astrolend_group: &AstrolendGroup,
+        lending_account: &LendingAccount,
+        bank_map: &HashMap<Pubkey, Bank>,
+        price_map: &HashMap<Pubkey, OraclePriceFeedAdapter>,
+        timestamp: i64,
+    ) -> AstrolendResult {
+        let balances = lending_account
+            .get_active_balances_iter()
+            .map(|balance| {
+                let bank = bank_map
+                    .get(&balance.bank_pk)
+                    .ok_or(AstrolendError::BankAccountNotFound)?;
+
+                Ok((balance, bank, price_map.get(&balance.bank_pk)))
+            })
+            .collect::<AstrolendResult<Vec<_>>>()?;
+
+        let liability_banks = balances
+            .iter()
+            .filter(|(balance, ..)| balance.is_empty(BalanceSide::Liabilities).not())
+            .map(|(_, bank, _)| bank)
+            .collect::<Vec<_>>();
+
+        let mut emode_liability_tags = liability_banks
+            .iter()
+            .map(|bank| bank.config.emode_tag)
+            .collect::<Vec<_>>();
+        emode_liability_tags.sort_unstable();
+        emode_liability_tags.dedup();
+
+        let mut total_assets = I80F48::ZERO;
+        let mut total_liabilities = I80F48::ZERO;
+        for (balance, bank, price_feed) in &balances {
+            let (assets, liabilities) = match balance.get_side() {
+                Some(BalanceSide::Assets) => (
+                    calc_weighted_asset_value(
+                        bank,
+                        bank.get_asset_amount(balance.asset_shares.into())?,
+                        price_feed.ok_or(PriceFeedError::StaleOracle),
+                        RequirementType::Initial,
+                        astrolend_group
+                            .get_emode_asset_weights(bank.config.emode_tag, &emode_liability_tags),
+                        timestamp,
+                    )?,
+                    I80F48::ZERO,
+                ),
+                Some(BalanceSide::Liabilities) => (
+                    I80F48::ZERO,
+                    calc_weighted_liability_value(
+                        bank,
+                        balance,
+                        price_feed.ok_or(PriceFeedError::StaleOracle),
+                        RequirementType::Initial,
+                    )?,
+                ),
+                None => (I80F48::ZERO, I80F48::ZERO),
+            };
+
+            total_assets = total_assets.checked_add(assets).ok_or_else(math_error!())?;
+            total_liabilities = total_liabilities
+                .checked_add(liabilities)
+                .ok_or_else(math_error!())?;
+        }
+
+        let required_liabilities =
+            astrolend_group.get_init_health_required_liabilities(total_liabilities)?;
+        check!(
+            total_assets >= required_liabilities,
+            AstrolendError::RiskEngineInitRejected
+        );
+
+        let is_in_isolated_risk_tier = liability_banks
+            .iter()
+            .any(|bank| bank.config.risk_tier == RiskTier::Isolated);
+        check!(
+            !is_in_isolated_risk_tier || liability_banks.len() == 1,
+            AstrolendError::IsolatedAccountIllegalState
+        );
+
+        Ok(())
+    }
+}
+
+/// Largest amount in `0..=upper` accepted by `accepts`, which must accept every amount below
+/// an accepted one. 0 if none is accepted.
+#[cfg(any(feature = "test", feature = "client"))]
+fn find_max_amount(upper: u64, accepts: impl Fn(u64) -> bool) -> u64 {
+    let (mut low, mut high) = (0, upper);
+    while low < high {
+        let mid = low + (high - low).div_ceil(2);
+        if accepts(mid) {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    low
+}
 
 assert_struct_size!(LendingAccount, 1728);
+static_assertions::const_assert!(
+    MAX_LENDING_ACCOUNT_BALANCES * std::mem::size_of::<Balance>()
+        <= std::mem::size_of::<LendingAccount>()
+);
 assert_struct_align!(LendingAccount, 8);
 #[zero_copy(unsafe)]
 #[repr(C)]
@@ -722,21 +1915,85 @@ pub struct LendingAccount {
 
 impl LendingAccount {
     pub fn get_first_empty_balance(&self) -> Option<usize> {
-        self.balances.iter().position(|b| !b.active)
+        self.balances
+            .iter()
+            .position(|b| !b.active && !b.is_parked())
+    }
+
+    pub fn get_parked_balance(&self, bank_pk: &Pubkey) -> Option<usize> {
+        self.balances
+            .iter()
+            .position(|b| b.is_parked() && b.bank_pk.eq(bank_pk))
+    }
+
+    pub fn has_balance(&self, bank_pk: &Pubkey) -> bool {
+        self.balances
+            .iter()
+            .any(|balance| balance.active && balance.bank_pk.eq(bank_pk))
+    }
+
+    /// An account borrowing an isolated asset can only add to balances it already has,
+    /// new collateral banks are rejected until the isolated liability is repaid.
+    ///
+    /// Risk tiers are read from the banks of the liabilities, expected in `bank_ais`, so
+    /// changes to a bank's tier apply to existing liabilities right away.
+    pub fn check_deposit_allowed<'info>(
+        &self,
+        bank_pk: &Pubkey,
+        bank_ais: &'info [AccountInfo<'info>],
+    ) -> AstrolendResult {
+        self.check_deposit_allowed_with(bank_pk, |liability_bank_pk| {
+            let bank_ai = bank_ais
+                .iter()
+                .find(|ai| ai.key == liability_bank_pk)
+                .ok_or(AstrolendError::MissingPythOrBankAccount)?;
+            let bank_al = AccountLoader::<Bank>::try_from(bank_ai)?;
+            let risk_tier = bank_al.load()?.config.risk_tier;
+
+            Ok(risk_tier)
+        })
+    }
+
+    fn check_deposit_allowed_with(
+        &self,
+        bank_pk: &Pubkey,
+        load_risk_tier: impl Fn(&Pubkey) -> AstrolendResult<RiskTier>,
+    ) -> AstrolendResult {
+        if self.has_balance(bank_pk) {
+            return Ok(());
+        }
+
+        for balance in self.get_active_balances_iter() {
+            if balance.is_empty(BalanceSide::Liabilities) {
+                continue;
+            }
+
+            check!(
+                load_risk_tier(&balance.bank_pk)? != RiskTier::Isolated,
+                AstrolendError::IsolatedAccountIllegalState
+            );
+        }
+
+        Ok(())
     }
-}
 
-#[cfg(any(feature = "test", feature = "client"))]
-impl LendingAccount {
     pub fn get_balance(&self, bank_pk: &Pubkey) -> Option<&Balance> {
         self.balances
             .iter()
             .find(|balance| balance.active && balance.bank_pk.eq(bank_pk))
     }
 
+    /// Active balances in slot order, the order the risk engine expects their observation
+    /// accounts in.
     pub fn get_active_balances_iter(&self) -> impl Iterator<Item = &Balance> {
         self.balances.iter().filter(|b| b.active)
     }
+
+    /// Any liability shares at all in an active balance, even below `EMPTY_BALANCE_THRESHOLD`.
+    pub fn has_liabilities(&self) -> bool {
+        self.get_active_balances_iter()
+            .any(|balance| I80F48::from(balance.liability_shares) != I80F48::ZERO)
+    }
 }
 
 assert_struct_size!(Balance, 104);
@@ -790,17 +2047,29 @@ impl Balance {
         Ok(())
     }
 
+    /// Deactivate the balance. Outstanding emissions of at least one token are parked:
+    /// the balance keeps `bank_pk` and `emissions_outstanding` so they can still be claimed
+    /// through `lending_account_withdraw_emissions`.
     pub fn close(&mut self) -> AstrolendResult {
-        check!(
-            I80F48::from(self.emissions_outstanding) < I80F48::ONE,
-            AstrolendError::CannotCloseOutstandingEmissions
-        );
+        let bank_pk = self.bank_pk;
+        let emissions_outstanding = self.emissions_outstanding;
 
         *self = Self::empty_deactivated();
 
+        if I80F48::from(emissions_outstanding) >= I80F48::ONE {
+            self.bank_pk = bank_pk;
+            self.emissions_outstanding = emissions_outstanding;
+        }
+
         Ok(())
     }
 
+    /// Closed balance holding unclaimed emissions, see [Balance::close].
+    /// Its slot is only reused by the same bank, or once the emissions are claimed.
+    pub fn is_parked(&self) -> bool {
+        !self.active && I80F48::from(self.emissions_outstanding) >= I80F48::ONE
+    }
+
     pub fn get_side(&self) -> Option<BalanceSide> {
         let asset_shares = I80F48::from(self.asset_shares);
         let liability_shares = I80F48::from(self.liability_shares);
@@ -832,6 +2101,10 @@ impl Balance {
     }
 }
 
+fn current_timestamp() -> AstrolendResult<u64> {
+    Ok(Clock::get()?.unix_timestamp as u64)
+}
+
 pub struct BankAccountWrapper<'a> {
     pub balance: &'a mut Balance,
     pub bank: &'a mut Bank,
@@ -850,15 +2123,45 @@ impl<'a> BankAccountWrapper<'a> {
             .find(|balance| balance.active && balance.bank_pk.eq(bank_pk))
             .ok_or_else(|| error!(AstrolendError::BankAccountNotFound))?;
 
+
+        Ok(Self { balance, bank })
+    }
+
+    // Find existing user lending account balance, or the parked balance left behind by
+    // closing it with outstanding emissions, by bank address.
+    pub fn find_claimable(
+        bank_pk: &Pubkey,
+        bank: &'a mut Bank,
+        lending_account: &'a mut LendingAccount,
+    ) -> AstrolendResult<BankAccountWrapper<'a>> {
+        let balance = lending_account
+            .balances
+            .iter_mut()
+            .find(|balance| (balance.active || balance.is_parked()) && balance.bank_pk.eq(bank_pk))
+            .ok_or_else(|| error!(AstrolendError::BankAccountNotFound))?;
+
         Ok(Self { balance, bank })
     }
 
     // Find existing user lending account balance by bank address.
-    // Create it if not found.
+    // Create it if not found, reopening a parked balance keeps its outstanding emissions.
     pub fn find_or_create(
         bank_pk: &Pubkey,
         bank: &'a mut Bank,
         lending_account: &'a mut LendingAccount,
+    ) -> AstrolendResult<BankAccountWrapper<'a>> {
+        Self::find_or_create_at(bank_pk, bank, lending_account, current_timestamp()?)
+    }
+
+    /// Slots are taken in order: the active balance of the bank, its parked balance, then the
+    /// first free slot. New balances overwrite the whole slot. Fails with
+    /// `LendingAccountBalanceSlotsFull` once all `MAX_LENDING_ACCOUNT_BALANCES` slots are
+    /// active or parked for other banks.
+    pub fn find_or_create_at(
+        bank_pk: &Pubkey,
+        bank: &'a mut Bank,
+        lending_account: &'a mut LendingAccount,
+        current_timestamp: u64,
     ) -> AstrolendResult<BankAccountWrapper<'a>> {
         let balance_index = lending_account
             .balances
@@ -867,17 +2170,24 @@ impl<'a> BankAccountWrapper<'a> {
 
         match balance_index {
             Some(balance_index) => {
-                let balance = lending_account
-                    .balances
-                    .get_mut(balance_index)
-                    .ok_or_else(|| error!(AstrolendError::BankAccountNotFound))?;
+                let balance = &mut lending_account.balances[balance_index];
 
                 Ok(Self { balance, bank })
             }
             None => {
-                let empty_index = lending_account
-                    .get_first_empty_balance()
-                    .ok_or_else(|| error!(AstrolendError::LendingAccountBalanceSlotsFull))?;
+                let (empty_index, emissions_outstanding) =
+                    match lending_account.get_parked_balance(bank_pk) {
+                        Some(parked_index) => (
+                            parked_index,
+                            lending_account.balances[parked_index].emissions_outstanding,
+                        ),
+                        None => (
+                            lending_account.get_first_empty_balance().ok_or_else(|| {
+                                error!(AstrolendError::LendingAccountBalanceSlotsFull)
+                            })?,
+                            I80F48::ZERO.into(),
+                        ),
+                    };
 
                 lending_account.balances[empty_index] = Balance {
                     active: true,
@@ -885,13 +2195,13 @@ impl<'a> BankAccountWrapper<'a> {
                     _pad0: [0; 7],
                     asset_shares: I80F48::ZERO.into(),
                     liability_shares: I80F48::ZERO.into(),
-                    emissions_outstanding: I80F48::ZERO.into(),
-                    last_update: Clock::get()?.unix_timestamp as u64,
+                    emissions_outstanding,
+                    last_update: current_timestamp,
                     _padding: [0; 1],
                 };
 
                 Ok(Self {
-                    balance: lending_account.balances.get_mut(empty_index).unwrap(),
+                    balance: &mut lending_account.balances[empty_index],
                     bank,
                 })
             }
@@ -899,25 +2209,44 @@ impl<'a> BankAccountWrapper<'a> {
     }
 
     // ------------ Borrow / Lend primitives
+    //
+    // The `_at` variants settle emissions at `current_timestamp` instead of reading the clock
+    // sysvar, so the share accounting can run outside of the runtime.
 
     /// Deposit an asset, will repay any outstanding liabilities.
     pub fn deposit(&mut self, amount: I80F48) -> AstrolendResult {
-        self.increase_balance_internal(amount, BalanceIncreaseType::Any)
+        self.deposit_at(amount, current_timestamp()?)
+    }
+
+    pub fn deposit_at(&mut self, amount: I80F48, current_timestamp: u64) -> AstrolendResult {
+        self.increase_balance_internal(amount, BalanceIncreaseType::Any, current_timestamp)
     }
 
     /// Repay a liability, will error if there is not enough liability - depositing is not allowed.
     pub fn repay(&mut self, amount: I80F48) -> AstrolendResult {
-        self.increase_balance_internal(amount, BalanceIncreaseType::RepayOnly)
+        self.repay_at(amount, current_timestamp()?)
+    }
+
+    pub fn repay_at(&mut self, amount: I80F48, current_timestamp: u64) -> AstrolendResult {
+        self.increase_balance_internal(amount, BalanceIncreaseType::RepayOnly, current_timestamp)
     }
 
     /// Withdraw an asset, will error if there is not enough asset - borrowing is not allowed.
     pub fn withdraw(&mut self, amount: I80F48) -> AstrolendResult {
-        self.decrease_balance_internal(amount, BalanceDecreaseType::WithdrawOnly)
+        self.withdraw_at(amount, current_timestamp()?)
+    }
+
+    pub fn withdraw_at(&mut self, amount: I80F48, current_timestamp: u64) -> AstrolendResult {
+        self.decrease_balance_internal(amount, BalanceDecreaseType::WithdrawOnly, current_timestamp)
     }
 
     /// Incur a borrow, will withdraw any existing assets.
     pub fn borrow(&mut self, amount: I80F48) -> AstrolendResult {
-        self.decrease_balance_internal(amount, BalanceDecreaseType::Any)
+        self.borrow_at(amount, current_timestamp()?)
+    }
+
+    pub fn borrow_at(&mut self, amount: I80F48, current_timestamp: u64) -> AstrolendResult {
+        self.decrease_balance_internal(amount, BalanceDecreaseType::Any, current_timestamp)
     }
 
     // ------------ Hybrid operations for seamless repay + deposit / withdraw + borrow
@@ -925,17 +2254,21 @@ impl<'a> BankAccountWrapper<'a> {
     /// Repay liability and deposit/increase asset depending on
     /// the specified deposit amount and the existing balance.
     pub fn increase_balance(&mut self, amount: I80F48) -> AstrolendResult {
-        self.increase_balance_internal(amount, BalanceIncreaseType::Any)
+        self.increase_balance_internal(amount, BalanceIncreaseType::Any, current_timestamp()?)
     }
 
     pub fn increase_balance_in_liquidation(&mut self, amount: I80F48) -> AstrolendResult {
-        self.increase_balance_internal(amount, BalanceIncreaseType::BypassDepositLimit)
+        self.increase_balance_internal(
+            amount,
+            BalanceIncreaseType::BypassDepositLimit,
+            current_timestamp()?,
+        )
     }
 
     /// Withdraw asset and create/increase liability depending on
     /// the specified deposit amount and the existing balance.
     pub fn decrease_balance(&mut self, amount: I80F48) -> AstrolendResult {
-        self.decrease_balance_internal(amount, BalanceDecreaseType::Any)
+        self.decrease_balance_internal(amount, BalanceDecreaseType::Any, current_timestamp()?)
     }
 
     /// Withdraw asset and create/increase liability depending on
@@ -944,12 +2277,20 @@ impl<'a> BankAccountWrapper<'a> {
     /// This function will also bypass borrow limits
     /// so liquidations can happen in banks with maxed out borrows.
     pub fn decrease_balance_in_liquidation(&mut self, amount: I80F48) -> AstrolendResult {
-        self.decrease_balance_internal(amount, BalanceDecreaseType::BypassBorrowLimit)
+        self.decrease_balance_internal(
+            amount,
+            BalanceDecreaseType::BypassBorrowLimit,
+            current_timestamp()?,
+        )
     }
 
     /// Withdraw existing asset in full - will error if there is no asset.
     pub fn withdraw_all(&mut self) -> AstrolendResult<u64> {
-        self.claim_emissions(Clock::get()?.unix_timestamp as u64)?;
+        self.withdraw_all_at(current_timestamp()?)
+    }
+
+    pub fn withdraw_all_at(&mut self, current_timestamp: u64) -> AstrolendResult<u64> {
+        self.claim_emissions(current_timestamp)?;
 
         let balance = &mut self.balance;
         let bank = &mut self.bank;
@@ -982,14 +2323,11 @@ impl<'a> BankAccountWrapper<'a> {
             .checked_floor()
             .ok_or_else(math_error!())?;
 
-        bank.collected_insurance_fees_outstanding = {
+        bank.add_insurance_fees_outstanding(
             current_asset_amount
                 .checked_sub(spl_withdraw_amount)
-                .ok_or_else(math_error!())?
-                .checked_add(bank.collected_insurance_fees_outstanding.into())
-                .ok_or_else(math_error!())?
-                .into()
-        };
+                .ok_or_else(math_error!())?,
+        )?;
 
         Ok(spl_withdraw_amount
             .checked_to_num()
@@ -998,7 +2336,11 @@ impl<'a> BankAccountWrapper<'a> {
 
     /// Repay existing liability in full - will error if there is no liability.
     pub fn repay_all(&mut self) -> AstrolendResult<u64> {
-        self.claim_emissions(Clock::get()?.unix_timestamp as u64)?;
+        self.repay_all_at(current_timestamp()?)
+    }
+
+    pub fn repay_all_at(&mut self, current_timestamp: u64) -> AstrolendResult<u64> {
+        self.claim_emissions(current_timestamp)?;
 
         let balance = &mut self.balance;
         let bank = &mut self.bank;
@@ -1028,14 +2370,11 @@ impl<'a> BankAccountWrapper<'a> {
             .checked_ceil()
             .ok_or_else(math_error!())?;
 
-        bank.collected_insurance_fees_outstanding = {
+        bank.add_insurance_fees_outstanding(
             spl_deposit_amount
                 .checked_sub(current_liability_amount)
-                .ok_or_else(math_error!())?
-                .checked_add(bank.collected_insurance_fees_outstanding.into())
-                .ok_or_else(math_error!())?
-                .into()
-        };
+                .ok_or_else(math_error!())?,
+        )?;
 
         Ok(spl_deposit_amount
             .checked_to_num()
@@ -1043,7 +2382,7 @@ impl<'a> BankAccountWrapper<'a> {
     }
 
     pub fn close_balance(&mut self) -> AstrolendResult<()> {
-        self.claim_emissions(Clock::get()?.unix_timestamp as u64)?;
+        self.claim_emissions(current_timestamp()?)?;
 
         let balance = &mut self.balance;
         let bank = &mut self.bank;
@@ -1069,19 +2408,93 @@ impl<'a> BankAccountWrapper<'a> {
         Ok(())
     }
 
+    // ------------ Position transfers between accounts of the same authority
+    //
+    // Shares move between two balances of the same bank, the bank totals are left unchanged.
+
+    /// Remove `shares` of asset from the balance, to be credited to another account with
+    /// [BankAccountWrapper::transfer_asset_shares_in]. Dust left below the empty balance
+    /// threshold moves along and the balance is closed. Returns the shares removed.
+    pub fn transfer_asset_shares_out(&mut self, shares: I80F48) -> AstrolendResult<I80F48> {
+        self.transfer_asset_shares_out_at(shares, current_timestamp()?)
+    }
+
+    pub fn transfer_asset_shares_out_at(
+        &mut self,
+        shares: I80F48,
+        current_timestamp: u64,
+    ) -> AstrolendResult<I80F48> {
+        self.claim_emissions(current_timestamp)?;
+
+        let balance = &mut self.balance;
+
+        check!(
+            balance.is_empty(BalanceSide::Liabilities),
+            AstrolendError::IllegalPositionTransfer,
+            "Liabilities can't be transferred"
+        );
+        check!(
+            shares.is_positive(),
+            AstrolendError::IllegalPositionTransfer
+        );
+
+        let current_asset_shares: I80F48 = balance.asset_shares.into();
+        check!(
+            shares <= current_asset_shares,
+            AstrolendError::OperationWithdrawOnly
+        );
+
+        let remaining_asset_shares = current_asset_shares
+            .checked_sub(shares)
+            .ok_or_else(math_error!())?;
+
+        if remaining_asset_shares < EMPTY_BALANCE_THRESHOLD {
+            balance.close()?;
+
+            Ok(current_asset_shares)
+        } else {
+            balance.change_asset_shares(-shares)?;
+
+            Ok(shares)
+        }
+    }
+
+    /// Credit asset shares removed from another account with
+    /// [BankAccountWrapper::transfer_asset_shares_out].
+    pub fn transfer_asset_shares_in(&mut self, shares: I80F48) -> AstrolendResult {
+        self.transfer_asset_shares_in_at(shares, current_timestamp()?)
+    }
+
+    pub fn transfer_asset_shares_in_at(
+        &mut self,
+        shares: I80F48,
+        current_timestamp: u64,
+    ) -> AstrolendResult {
+        self.claim_emissions(current_timestamp)?;
+
+        check!(
+            self.balance.is_empty(BalanceSide::Liabilities),
+            AstrolendError::IllegalPositionTransfer,
+            "Destination balance has existing debt"
+        );
+
+        self.balance.change_asset_shares(shares)
+    }
+
     // ------------ Internal accounting logic
 
     fn increase_balance_internal(
         &mut self,
         balance_delta: I80F48,
         operation_type: BalanceIncreaseType,
+        current_timestamp: u64,
     ) -> AstrolendResult {
         debug!(
             "Balance increase: {} (type: {:?})",
             balance_delta, operation_type
         );
 
-        self.claim_emissions(Clock::get()?.unix_timestamp as u64)?;
+        self.claim_emissions(current_timestamp)?;
 
         let balance = &mut self.balance;
         let bank = &mut self.bank;
@@ -1140,13 +2553,14 @@ impl<'a> BankAccountWrapper<'a> {
         &mut self,
         balance_delta: I80F48,
         operation_type: BalanceDecreaseType,
+        current_timestamp: u64,
     ) -> AstrolendResult {
         debug!(
             "Balance decrease: {} of (type: {:?})",
             balance_delta, operation_type
         );
 
-        self.claim_emissions(Clock::get()?.unix_timestamp as u64)?;
+        self.claim_emissions(current_timestamp)?;
 
         let balance = &mut self.balance;
         let bank = &mut self.bank;
@@ -1204,6 +2618,9 @@ impl<'a> BankAccountWrapper<'a> {
 
     /// Claim any unclaimed emissions and add them to the outstanding emissions amount.
     pub fn claim_emissions(&mut self, current_timestamp: u64) -> AstrolendResult {
+        // Before this balance changes the size of the emitting side
+        self.bank.checkpoint_emissions(current_timestamp as i64)?;
+
         if let Some(balance_amount) = match (
             self.balance.get_side(),
             self.bank.get_flag(EMISSIONS_FLAG_LENDING_ACTIVE),
@@ -1219,16 +2636,21 @@ impl<'a> BankAccountWrapper<'a> {
             ),
             _ => None,
         } {
+            // Balances opened before the campaign only accrue from its start
             let last_update = if self.balance.last_update < MIN_EMISSIONS_START_TIME {
                 current_timestamp
             } else {
-                self.balance.last_update
+                max(
+                    self.balance.last_update,
+                    self.bank.emissions_campaign_start_ts.max(0) as u64,
+                )
             };
-            let period = I80F48::from_num(
-                current_timestamp
-                    .checked_sub(last_update)
-                    .ok_or_else(math_error!())?,
-            );
+            // Nothing accrues past the moment the campaign ran out
+            let accrual_end = match self.bank.emissions_exhausted_at {
+                0 => current_timestamp,
+                exhausted_at => min(current_timestamp, exhausted_at.max(0) as u64),
+            };
+            let period = I80F48::from_num(accrual_end.saturating_sub(last_update));
             let emissions_rate = I80F48::from_num(self.bank.emissions_rate);
             let emissions = calc_emissions(
                 period,
@@ -1237,7 +2659,14 @@ impl<'a> BankAccountWrapper<'a> {
                 emissions_rate,
             )?;
 
-            let emissions_real = min(emissions, I80F48::from(self.bank.emissions_remaining));
+            // Accruals from before the first checkpoint were never reserved
+            let reserved = I80F48::from(self.bank.emissions_reserved);
+            let from_reserve = min(emissions, reserved);
+            let from_remaining = min(
+                emissions - from_reserve,
+                I80F48::from(self.bank.emissions_remaining),
+            );
+            let emissions_real = from_reserve + from_remaining;
 
             if emissions != emissions_real {
                 msg!(
@@ -1253,15 +2682,22 @@ impl<'a> BankAccountWrapper<'a> {
                 I80F48::from(self.balance.emissions_outstanding)
             );
 
-            self.balance.emissions_outstanding = {
-                I80F48::from(self.balance.emissions_outstanding)
-                    .checked_add(emissions_real)
+            let outstanding = I80F48::from(self.balance.emissions_outstanding);
+            let new_outstanding = outstanding
+                .checked_add(emissions_real)
+                .ok_or_else(math_error!())?;
+            self.balance.emissions_outstanding = new_outstanding.into();
+            self.bank
+                .update_emissions_outstanding_total(outstanding, new_outstanding)?;
+            self.bank.emissions_reserved = {
+                reserved
+                    .checked_sub(from_reserve)
                     .ok_or_else(math_error!())?
             }
             .into();
             self.bank.emissions_remaining = {
                 I80F48::from(self.bank.emissions_remaining)
-                    .checked_sub(emissions_real)
+                    .checked_sub(from_remaining)
                     .ok_or_else(math_error!())?
             }
             .into();
@@ -1274,16 +2710,24 @@ impl<'a> BankAccountWrapper<'a> {
 
     /// Claim any outstanding emissions, and return the max amount that can be withdrawn.
     pub fn settle_emissions_and_get_transfer_amount(&mut self) -> AstrolendResult<u64> {
-        self.claim_emissions(Clock::get()?.unix_timestamp as u64)?;
+        self.claim_emissions(current_timestamp()?)?;
 
-        let outstanding_emissions_floored = I80F48::from(self.balance.emissions_outstanding)
+        let outstanding_emissions = I80F48::from(self.balance.emissions_outstanding);
+        let outstanding_emissions_floored = outstanding_emissions
             .checked_floor()
             .ok_or_else(math_error!())?;
-        let new_outstanding_amount = I80F48::from(self.balance.emissions_outstanding)
+        let new_outstanding_amount = outstanding_emissions
             .checked_sub(outstanding_emissions_floored)
             .ok_or_else(math_error!())?;
 
         self.balance.emissions_outstanding = new_outstanding_amount.into();
+        self.bank
+            .update_emissions_outstanding_total(outstanding_emissions, new_outstanding_amount)?;
+
+        // Claiming a parked balance frees its slot, the fractional remainder is dropped
+        if !self.balance.active {
+            *self.balance = Balance::empty_deactivated();
+        }
 
         Ok(outstanding_emissions_floored
             .checked_to_num::<u64>()
@@ -1293,7 +2737,7 @@ impl<'a> BankAccountWrapper<'a> {
     // ------------ SPL helpers
 
     pub fn deposit_spl_transfer<'info>(
-        &self,
+        &mut self,
         amount: u64,
         from: AccountInfo<'info>,
         to: AccountInfo<'info>,
@@ -1314,7 +2758,7 @@ impl<'a> BankAccountWrapper<'a> {
     }
 
     pub fn withdraw_spl_transfer<'info>(
-        &self,
+        &mut self,
         amount: u64,
         from: AccountInfo<'info>,
         to: AccountInfo<'info>,
@@ -1375,9 +2819,83 @@ fn calc_emissions(
     Ok(emissions)
 }
 
+/// Operations an account can perform against a bank, see [check_operation_allowed].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BankOperation {
+    Deposit,
+    Withdraw,
+    Borrow,
+    Repay,
+    /// Liquidator receiving collateral from the asset bank.
+    LiquidateSeize,
+    /// Liquidator paying down debt in the liability bank.
+    LiquidateRepay,
+    Bankruptcy,
+    ClaimEmissions,
+    /// Moving asset shares between two accounts of the same authority, checked on both.
+    TransferPosition,
+    /// Crediting asset shares exported as deposit receipts back to an account.
+    RedeemReceipts,
+}
+
+/// Check whether `op` is allowed given the current bank and account state.
+/// Every handler calls this before touching balances.
+///
+/// - Banks with their reentrancy guard set reject everything, see `Bank::reentrancy_guard`.
+/// - Disabled accounts can only be repaid and go through bankruptcy,
+/// repays signed by the authority of a disabled account are rejected by the repay handler.
+/// - Paused banks only allow claiming emissions.
+/// - Reduce only banks reject operations that grow the bank: deposits, borrows and seizing collateral.
+/// - Banks in settlement mode reject deposits and borrows, everything else winds down at the
+/// settlement price.
+///
+/// Amount dependent rules (e.g. a liquidator going into debt in a reduce only bank)
+/// are still enforced when the balance changes, see [Bank::assert_operational_mode].
+/// Risk tier rules depend on the other balances of the account and are enforced by
+/// [LendingAccount::check_deposit_allowed] and the [RiskEngine].
+pub fn check_operation_allowed(
+    op: BankOperation,
+    bank: &Bank,
+    account: &AstrolendAccount,
+) -> AstrolendResult {
+    use BankOperation::*;
+
+    bank.check_reentrancy_guard()?;
+
+    if account.get_flag(DISABLED_FLAG) {
+        check!(
+            matches!(op, Repay | Bankruptcy),
+            AstrolendError::AccountDisabled
+        );
+    }
+
+    match bank.config.operational_state {
+        BankOperationalState::Operational => {}
+        BankOperationalState::Paused => {
+            check!(matches!(op, ClaimEmissions), AstrolendError::BankPaused);
+        }
+        BankOperationalState::ReduceOnly => {
+            check!(
+                !matches!(op, Deposit | Borrow | LiquidateSeize),
+                AstrolendError::BankReduceOnly
+            );
+        }
+    }
+
+    if bank.is_in_settlement() {
+        check!(
+            !matches!(op, Deposit | Borrow),
+            AstrolendError::BankInSettlement
+        );
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::constants::{MAX_EXP_10_I80F48, MAX_MINT_DECIMALS};
     use fixed_macro::types::I80F48;
 
     #[test]
@@ -1398,6 +2916,43 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_calc_value_max_amount_all_decimals() {
+        let max_amount = I80F48::from_num(u64::MAX);
+
+        for (decimals, price) in [
+            (0, I80F48!(1_000)),
+            (6, I80F48!(100_000)),
+            (9, I80F48!(100_000)),
+            (12, I80F48!(100_000)),
+            (15, I80F48!(100_000)),
+            (MAX_MINT_DECIMALS, I80F48!(100_000)),
+        ] {
+            let value = calc_value(max_amount, price, decimals, Some(I80F48!(0.9)));
+            assert!(value.is_ok(), "overflow at {} decimals", decimals);
+
+            let value = calc_value(max_amount, price, decimals, None).unwrap();
+            let amount = calc_amount(value, price, decimals).unwrap();
+            assert!(
+                ((amount - max_amount) / max_amount).abs() < I80F48!(0.000001),
+                "round trip at {} decimals: {}",
+                decimals,
+                amount
+            );
+        }
+    }
+
+    #[test]
+    fn test_native_ui_conversion() {
+        assert_eq!(
+            native_to_ui(I80F48!(1_500_000_000_000), 12).unwrap(),
+            I80F48!(1.5)
+        );
+        assert_eq!(ui_to_native(I80F48!(1.5), 12).unwrap(), I80F48!(1_500_000_000_000));
+        assert_eq!(native_to_ui(I80F48!(42), 0).unwrap(), I80F48!(42));
+        assert!(native_to_ui(I80F48!(42), MAX_EXP_10_I80F48 as u8).is_err());
+    }
+
     #[test]
     fn test_account_authority_transfer() {
         let group: [u8; 32] = [0; 32];
@@ -1422,7 +2977,13 @@ mod test {
                 _padding: [0; 8],
             },
             account_flags: TRANSFER_AUTHORITY_ALLOWED_FLAG,
-            _padding: [0; 63],
+            liquidation_history: [LiquidationRecord::default(); LIQUIDATION_HISTORY_LEN],
+            approved_destination: Pubkey::default(),
+            _padding0: [0; 26],
+            liquidation_count: 0,
+            account_tag: [0; 8],
+            version: ASTROLEND_ACCOUNT_VERSION,
+            _padding: [0; 7],
         };
 
         assert!(acc.get_flag(TRANSFER_AUTHORITY_ALLOWED_FLAG));
@@ -1433,6 +2994,221 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_configure_account() {
+        let mut acc: AstrolendAccount = bytemuck::Zeroable::zeroed();
+
+        acc.configure(&AccountConfigOpt {
+            third_party_deposits_allowed: Some(true),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(acc.get_flag(THIRD_PARTY_DEPOSIT_ALLOWED_FLAG));
+
+        acc.configure(&AccountConfigOpt {
+            third_party_deposits_allowed: Some(false),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(acc.account_flags, 0);
+
+        // Flags reserved to the program or the admin are left alone
+        let admin_flags = DISABLED_FLAG | FLASHLOAN_ENABLED_FLAG | TRANSFER_AUTHORITY_ALLOWED_FLAG;
+        acc.account_flags = admin_flags;
+        acc.configure(&AccountConfigOpt {
+            third_party_deposits_allowed: Some(true),
+            destinations_restricted: Some(false),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(acc.account_flags, admin_flags | THIRD_PARTY_DEPOSIT_ALLOWED_FLAG);
+    }
+
+    #[test]
+    fn test_destination_restrictions() {
+        let mut acc: AstrolendAccount = bytemuck::Zeroable::zeroed();
+        acc.version = ASTROLEND_ACCOUNT_VERSION;
+        let authority_ata = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+
+        assert!(acc
+            .check_destination_allowed(&other, &authority_ata)
+            .is_ok());
+
+        acc.configure(&AccountConfigOpt {
+            destinations_restricted: Some(true),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(acc.get_flag(RESTRICTED_DESTINATIONS_FLAG));
+        assert!(acc
+            .check_destination_allowed(&authority_ata, &authority_ata)
+            .is_ok());
+        assert_eq!(
+            acc.check_destination_allowed(&other, &authority_ata)
+                .unwrap_err(),
+            AstrolendError::DestinationNotAllowed.into()
+        );
+
+        // The default key never matches, even as destination
+        assert!(acc
+            .check_destination_allowed(&Pubkey::default(), &authority_ata)
+            .is_err());
+
+        acc.configure(&AccountConfigOpt {
+            approved_destination: Some(other),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(acc
+            .check_destination_allowed(&other, &authority_ata)
+            .is_ok());
+
+        acc.configure(&AccountConfigOpt {
+            approved_destination: Some(Pubkey::default()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(acc
+            .check_destination_allowed(&other, &authority_ata)
+            .is_err());
+
+        acc.configure(&AccountConfigOpt {
+            destinations_restricted: Some(false),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(acc.account_flags, 0);
+        assert!(acc
+            .check_destination_allowed(&other, &authority_ata)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_liquidation_history_wraparound() {
+        let mut acc: AstrolendAccount = bytemuck::Zeroable::zeroed();
+        acc.version = ASTROLEND_ACCOUNT_VERSION;
+        assert!(acc.get_liquidation_history().is_empty());
+
+        let record = |timestamp: i64| LiquidationRecord {
+            timestamp,
+            asset_amount: timestamp as u64 * 10,
+            ..Default::default()
+        };
+
+        assert_eq!(acc.record_liquidation(record(1)), Some(0));
+        assert_eq!(acc.record_liquidation(record(2)), Some(1));
+        assert_eq!(
+            acc.get_liquidation_history()
+                .iter()
+                .map(|r| r.timestamp)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        for timestamp in 3..=5 {
+            acc.record_liquidation(record(timestamp));
+        }
+
+        assert_eq!(acc.liquidation_count, 5);
+        assert_eq!(acc.liquidation_history[0].timestamp, 5);
+        assert_eq!(
+            acc.get_liquidation_history()
+                .iter()
+                .map(|r| r.timestamp)
+                .collect::<Vec<_>>(),
+            vec![4, 5]
+        );
+        assert_eq!(acc.get_liquidation_history()[1].asset_amount, 50);
+    }
+
+    #[test]
+    fn legacy_account_migration() {
+        let mut acc: AstrolendAccount = bytemuck::Zeroable::zeroed();
+        acc.liquidation_count = 3;
+        acc.liquidation_history[1].timestamp = 42;
+        acc.approved_destination = Pubkey::new_unique();
+        acc._padding0[0] = 7;
+        acc.account_tag = [7; 8];
+        assert!(acc.is_legacy());
+
+        // Leftover data of legacy accounts is neither read nor written to
+        assert!(acc.get_liquidation_history().is_empty());
+        assert_eq!(acc.record_liquidation(LiquidationRecord::default()), None);
+        assert_eq!(acc.liquidation_count, 3);
+        assert!(acc
+            .configure(&AccountConfigOpt {
+                approved_destination: Some(Pubkey::new_unique()),
+                ..Default::default()
+            })
+            .is_err());
+
+        // Whatever follows a legacy account is not part of it, the credit history starts from
+        // scratch
+        let mut credit_history: CreditHistory = bytemuck::Zeroable::zeroed();
+        credit_history.balances[0].total_borrowed_native = 7;
+        credit_history.closed_total_repaid_native = 7;
+        credit_history._padding = [7; 4];
+
+        acc.migrate(&mut credit_history).unwrap();
+        assert_eq!(acc.version, ASTROLEND_ACCOUNT_VERSION);
+        assert_eq!(acc.liquidation_count, 0);
+        assert_eq!(acc.liquidation_history[1].timestamp, 0);
+        assert_eq!(acc.approved_destination, Pubkey::default());
+        assert_eq!(acc._padding0, [0; 26]);
+        assert_eq!(acc.account_tag, [0; 8]);
+        assert_eq!(credit_history, bytemuck::Zeroable::zeroed());
+        assert_eq!(acc.record_liquidation(LiquidationRecord::default()), Some(0));
+
+        assert_eq!(
+            acc.migrate(&mut credit_history).unwrap_err(),
+            AstrolendError::AccountAlreadyMigrated.into()
+        );
+    }
+
+    #[test]
+    fn credit_history_rolls_up_closed_balances() {
+        let (bank_a, bank_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut bank = Bank::default();
+        let mut acc: AstrolendAccount = bytemuck::Zeroable::zeroed();
+        let mut credit: CreditHistory = bytemuck::Zeroable::zeroed();
+
+        // Nothing is counted without a balance
+        assert_eq!(credit.record_borrow(&acc.lending_account, &bank_a, 100), 0);
+
+        BankAccountWrapper::find_or_create_at(&bank_a, &mut bank, &mut acc.lending_account, 10)
+            .unwrap();
+        assert_eq!(credit.record_borrow(&acc.lending_account, &bank_a, 100), 100);
+        assert_eq!(credit.record_repay(&acc.lending_account, &bank_a, 40), 40);
+        credit.record_borrow(&acc.lending_account, &bank_a, u64::MAX);
+        credit.sync(&acc.lending_account);
+
+        let balance = credit.get_balance(&acc.lending_account, &bank_a).unwrap();
+        assert_eq!(balance.total_borrowed_native, u64::MAX);
+        assert_eq!(balance.total_repaid_native, 40);
+
+        // Repaid in full, the balance closes before the repayment is recorded
+        acc.lending_account.balances[0].close().unwrap();
+        assert_eq!(credit.record_repay(&acc.lending_account, &bank_a, 60), 60);
+        assert_eq!(credit.get_balance(&acc.lending_account, &bank_a), None);
+        assert_eq!(credit.get_totals(), (u64::MAX, 100));
+
+        credit.sync(&acc.lending_account);
+        assert_eq!(credit.balances[0], BalanceCreditHistory::default());
+        assert_eq!(credit.closed_total_borrowed_native, u64::MAX);
+        assert_eq!(credit.closed_total_repaid_native, 100);
+
+        // The freed slot counts for the next bank only
+        BankAccountWrapper::find_or_create_at(&bank_b, &mut bank, &mut acc.lending_account, 20)
+            .unwrap();
+        credit.record_borrow(&acc.lending_account, &bank_b, 5);
+        let balance = credit.get_balance(&acc.lending_account, &bank_b).unwrap();
+        assert_eq!(balance.bank_pk, bank_b);
+        assert_eq!(balance.total_borrowed_native, 5);
+        assert_eq!(balance.total_repaid_native, 0);
+        assert_eq!(credit.get_totals(), (u64::MAX, 100));
+    }
+
     #[test]
     fn test_calc_emissions() {
         let balance_amount: u64 = 106153222432271169;
@@ -1538,4 +3314,619 @@ mod test {
             assert!(emissions_new - emissions < I80F48::from_num(0.00000001));
         }
     }
+
+    #[test]
+    fn test_deposit_restricted_with_isolated_liability() {
+        let collateral_bank_pk = Pubkey::new_unique();
+        let isolated_bank_pk = Pubkey::new_unique();
+        let new_bank_pk = Pubkey::new_unique();
+
+        let mut lending_account = LendingAccount {
+            balances: [Balance::empty_deactivated(); 16],
+            _padding: [0; 8],
+        };
+
+        lending_account.balances[0] = Balance {
+            active: true,
+            bank_pk: collateral_bank_pk,
+            asset_shares: I80F48!(1_000).into(),
+            ..Balance::empty_deactivated()
+        };
+        lending_account.balances[1] = Balance {
+            active: true,
+            bank_pk: isolated_bank_pk,
+            liability_shares: I80F48!(100).into(),
+            ..Balance::empty_deactivated()
+        };
+
+        let isolated_tier = |bank_pk: &Pubkey| -> AstrolendResult<RiskTier> {
+            assert_eq!(*bank_pk, isolated_bank_pk, "only liability banks are loaded");
+            Ok(RiskTier::Isolated)
+        };
+
+        // Existing balances can still be topped up / repaid
+        assert!(lending_account
+            .check_deposit_allowed_with(&collateral_bank_pk, isolated_tier)
+            .is_ok());
+        assert!(lending_account
+            .check_deposit_allowed_with(&isolated_bank_pk, isolated_tier)
+            .is_ok());
+
+        // New collateral banks are rejected
+        assert_eq!(
+            lending_account.check_deposit_allowed_with(&new_bank_pk, isolated_tier),
+            Err(AstrolendError::IsolatedAccountIllegalState.into())
+        );
+
+        // The tier is whatever the bank says now, not when the liability was opened
+        assert!(lending_account
+            .check_deposit_allowed_with(&new_bank_pk, |_| Ok(RiskTier::Collateral))
+            .is_ok());
+
+        // The liability bank has to be provided
+        assert_eq!(
+            lending_account.check_deposit_allowed_with(&new_bank_pk, |_| {
+                Err(AstrolendError::MissingPythOrBankAccount.into())
+            }),
+            Err(AstrolendError::MissingPythOrBankAccount.into())
+        );
+
+        // Isolated liability repaid, restriction lifts
+        lending_account.balances[1].liability_shares = I80F48::ZERO.into();
+        assert!(lending_account
+            .check_deposit_allowed_with(&new_bank_pk, isolated_tier)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_project_with_applies_bank_projections() -> anyhow::Result<()> {
+        use crate::state::astrolend_group::{BankConfig, InterestRateConfig};
+
+        let bank = Bank {
+            asset_share_value: I80F48::ONE.into(),
+            liability_share_value: I80F48::ONE.into(),
+            total_asset_shares: I80F48!(1_000_000).into(),
+            total_liability_shares: I80F48!(500_000).into(),
+            config: BankConfig {
+                interest_rate_config: InterestRateConfig {
+                    optimal_utilization_rate: I80F48!(0.6).into(),
+                    plateau_interest_rate: I80F48!(0.4).into(),
+                    max_interest_rate: I80F48!(3).into(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (bank_a, bank_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let mut acc: AstrolendAccount = bytemuck::Zeroable::zeroed();
+        acc.lending_account.balances[0] = Balance {
+            active: true,
+            bank_pk: bank_a,
+            asset_shares: I80F48!(1_000).into(),
+            ..Balance::empty_deactivated()
+        };
+        acc.lending_account.balances[1] = Balance {
+            active: true,
+            bank_pk: bank_b,
+            liability_shares: I80F48!(100).into(),
+            ..Balance::empty_deactivated()
+        };
+
+        let mut banks = HashMap::from([(bank_a, bank)]);
+        assert_eq!(
+            acc.project_with(&banks, 86_400),
+            Err(AstrolendError::BankAccountNotFound.into())
+        );
+
+        banks.insert(bank_b, bank);
+        let projected = bank.project(30 * 86_400)?;
+        let balances = acc.project_with(&banks, 30 * 86_400)?;
+
+        assert_eq!(balances.len(), 2);
+        assert_eq!(balances[0].bank_pk, bank_a);
+        assert_eq!(
+            balances[0].asset_amount,
+            I80F48!(1_000) * projected.asset_share_value
+        );
+        assert_eq!(balances[0].liability_amount, I80F48::ZERO);
+        assert_eq!(balances[1].bank_pk, bank_b);
+        assert_eq!(
+            balances[1].liability_amount,
+            I80F48!(100) * projected.liability_share_value
+        );
+        assert!(balances[1].liability_amount > I80F48!(100));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_close_parks_outstanding_emissions() {
+        let bank_pk = Pubkey::new_unique();
+
+        let mut lending_account = LendingAccount {
+            balances: [Balance::empty_deactivated(); 16],
+            _padding: [0; 8],
+        };
+
+        lending_account.balances[0] = Balance {
+            active: true,
+            bank_pk,
+            emissions_outstanding: I80F48!(0.5).into(),
+            ..Balance::empty_deactivated()
+        };
+        lending_account.balances[1] = Balance {
+            active: true,
+            bank_pk,
+            emissions_outstanding: I80F48!(12.5).into(),
+            ..Balance::empty_deactivated()
+        };
+
+        // Dust is dropped, the slot is free again
+        lending_account.balances[0].close().unwrap();
+        assert!(!lending_account.balances[0].is_parked());
+        assert_eq!(lending_account.balances[0].bank_pk, Pubkey::default());
+        assert_eq!(lending_account.get_first_empty_balance(), Some(0));
+
+        // Claimable emissions are parked, the slot is kept until they are claimed
+        lending_account.balances[1].close().unwrap();
+        let parked = lending_account.balances[1];
+        assert!(!parked.active && parked.is_parked());
+        assert_eq!(parked.bank_pk, bank_pk);
+        assert_eq!(I80F48::from(parked.emissions_outstanding), I80F48!(12.5));
+        assert!(parked.get_side().is_none());
+
+        assert_eq!(lending_account.get_parked_balance(&bank_pk), Some(1));
+        assert_eq!(lending_account.get_parked_balance(&Pubkey::new_unique()), None);
+        assert!(!lending_account.has_balance(&bank_pk));
+
+        lending_account.balances[0] = Balance {
+            active: true,
+            ..Balance::empty_deactivated()
+        };
+        assert_eq!(lending_account.get_first_empty_balance(), Some(2));
+    }
+
+    #[test]
+    fn test_find_or_create_reuses_closed_slots() {
+        let (bank_a, bank_b, bank_c) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let mut bank = Bank::default();
+        let mut lending_account = LendingAccount {
+            balances: [Balance::empty_deactivated(); 16],
+            _padding: [0; 8],
+        };
+
+        for bank_pk in [bank_a, bank_b] {
+            BankAccountWrapper::find_or_create_at(&bank_pk, &mut bank, &mut lending_account, 10)
+                .unwrap();
+        }
+        assert_eq!(lending_account.balances[0].bank_pk, bank_a);
+        assert_eq!(lending_account.balances[1].bank_pk, bank_b);
+
+        {
+            let bank_account =
+                BankAccountWrapper::find_or_create_at(&bank_a, &mut bank, &mut lending_account, 20)
+                    .unwrap();
+            bank_account.balance.asset_shares = I80F48!(100).into();
+            bank_account.balance.emissions_outstanding = I80F48!(0.5).into();
+            bank_account.balance.close().unwrap();
+        }
+        let closed = lending_account.balances[0];
+        assert!(!closed.active && !closed.is_parked());
+        assert_eq!(closed.bank_pk, Pubkey::default());
+        assert_eq!(I80F48::from(closed.asset_shares), I80F48::ZERO);
+
+        // The freed slot goes to the next new bank, with nothing left over from bank A
+        BankAccountWrapper::find_or_create_at(&bank_c, &mut bank, &mut lending_account, 30)
+            .unwrap();
+        let reused = lending_account.balances[0];
+        assert!(reused.active);
+        assert_eq!(reused.bank_pk, bank_c);
+        assert_eq!(reused.last_update, 30);
+        assert_eq!(I80F48::from(reused.asset_shares), I80F48::ZERO);
+        assert_eq!(I80F48::from(reused.emissions_outstanding), I80F48::ZERO);
+
+        // Existing balances are found, not duplicated
+        BankAccountWrapper::find_or_create_at(&bank_b, &mut bank, &mut lending_account, 40)
+            .unwrap();
+        assert_eq!(lending_account.balances[1].last_update, 10);
+        assert_eq!(lending_account.get_active_balances_iter().count(), 2);
+
+        // Bank A comes back in the first free slot
+        BankAccountWrapper::find_or_create_at(&bank_a, &mut bank, &mut lending_account, 50)
+            .unwrap();
+        assert_eq!(lending_account.balances[2].bank_pk, bank_a);
+        assert_eq!(I80F48::from(lending_account.balances[2].asset_shares), I80F48::ZERO);
+    }
+
+    #[test]
+    fn observation_account_keys_follow_balance_order() {
+        let bank_a = Pubkey::new_unique();
+        let bank_b = Pubkey::new_unique();
+        let bank_c = Pubkey::new_unique();
+        let oracle = |bank_pk: &Pubkey| Pubkey::find_program_address(&[b"oracle"], bank_pk).0;
+
+        let mut account: AstrolendAccount = bytemuck::Zeroable::zeroed();
+        account.lending_account.balances = [Balance::empty_deactivated(); 16];
+        for (index, bank_pk) in [(3, bank_b), (1, bank_c), (5, bank_a)] {
+            let balance = &mut account.lending_account.balances[index];
+            balance.active = true;
+            balance.bank_pk = bank_pk;
+        }
+
+        let banks = |bank_pk: &Pubkey| {
+            Some(BankRef {
+                oracle: oracle(bank_pk),
+                emissions_mint: Pubkey::default(),
+            })
+        };
+        assert_eq!(
+            account.get_observation_account_keys(banks),
+            vec![
+                (bank_c, oracle(&bank_c)),
+                (bank_b, oracle(&bank_b)),
+                (bank_a, oracle(&bank_a)),
+            ]
+        );
+
+        // Unresolved banks are left out
+        let without_b = |bank_pk: &Pubkey| banks(bank_pk).filter(|_| *bank_pk != bank_b);
+        assert_eq!(
+            account.get_observation_account_keys(without_b),
+            vec![(bank_c, oracle(&bank_c)), (bank_a, oracle(&bank_a))]
+        );
+    }
+
+    #[test]
+    fn emissions_observation_account_keys_one_per_mint() {
+        let (bank_a, bank_b, bank_c) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (emissions_bank, emissions_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let oracle = |bank_pk: &Pubkey| Pubkey::find_program_address(&[b"oracle"], bank_pk).0;
+
+        let mut account: AstrolendAccount = bytemuck::Zeroable::zeroed();
+        account.lending_account.balances = [Balance::empty_deactivated(); 16];
+        for (index, bank_pk) in [(0, bank_a), (1, bank_b), (2, bank_c)] {
+            let balance = &mut account.lending_account.balances[index];
+            balance.active = true;
+            balance.bank_pk = bank_pk;
+        }
+
+        // Banks a and b emit the same mint, c emits nothing
+        let banks = |bank_pk: &Pubkey| {
+            Some(BankRef {
+                oracle: oracle(bank_pk),
+                emissions_mint: if *bank_pk == bank_c {
+                    Pubkey::default()
+                } else {
+                    emissions_mint
+                },
+            })
+        };
+        let emissions_banks = |mint: &Pubkey| {
+            (*mint == emissions_mint).then(|| (emissions_bank, banks(&emissions_bank).unwrap()))
+        };
+        assert_eq!(
+            account.get_emissions_observation_account_keys(banks, emissions_banks),
+            vec![(emissions_bank, oracle(&emissions_bank))]
+        );
+
+        // Mints without a bank in the group are left out
+        assert!(account
+            .get_emissions_observation_account_keys(banks, |_| None)
+            .is_empty());
+    }
+
+    #[test]
+    fn init_health_skips_liability_free_accounts() {
+        let group: AstrolendGroup = bytemuck::Zeroable::zeroed();
+        let mut account: AstrolendAccount = bytemuck::Zeroable::zeroed();
+        account.lending_account.balances = [Balance::empty_deactivated(); 16];
+        for index in [1, 4] {
+            let balance = &mut account.lending_account.balances[index];
+            balance.active = true;
+            balance.bank_pk = Pubkey::new_unique();
+            balance.asset_shares = I80F48!(100).into();
+        }
+
+        assert!(RiskEngine::check_account_init_health(&group, &account, &[])
+            .unwrap()
+            .is_none());
+
+        // Even dust liabilities need the observation accounts
+        account.lending_account.balances[4].liability_shares = I80F48!(0.00001).into();
+        assert!(RiskEngine::check_account_init_health(&group, &account, &[]).is_err());
+    }
+
+    #[test]
+    fn post_action_check_skips_by_context() {
+        let group: AstrolendGroup = bytemuck::Zeroable::zeroed();
+        let mut account: AstrolendAccount = bytemuck::Zeroable::zeroed();
+        account.lending_account.balances = [Balance::empty_deactivated(); 16];
+        let balance = &mut account.lending_account.balances[0];
+        balance.active = true;
+        balance.bank_pk = Pubkey::new_unique();
+        balance.liability_shares = I80F48!(100).into();
+
+        let check = |account: &AstrolendAccount, ctx_kind| {
+            RiskEngine::check_post_action(&group, account, &[], ctx_kind).map(|_| ())
+        };
+
+        // Without observation accounts the liability can't be valued, only skips pass
+        assert_eq!(
+            HealthCheckContext::of_account(&account),
+            HealthCheckContext::Normal
+        );
+        assert!(check(&account, HealthCheckContext::Normal).is_err());
+        assert!(check(&account, HealthCheckContext::Liquidation).is_err());
+        assert_eq!(
+            check(&account, HealthCheckContext::InFlashloan),
+            Err(AstrolendError::IllegalFlashloan.into())
+        );
+
+        account.set_flag(IN_FLASHLOAN_FLAG);
+        assert_eq!(
+            HealthCheckContext::of_account(&account),
+            HealthCheckContext::InFlashloan
+        );
+        assert_eq!(check(&account, HealthCheckContext::InFlashloan), Ok(()));
+        assert_eq!(check(&account, HealthCheckContext::Liquidation), Ok(()));
+        assert_eq!(
+            check(&account, HealthCheckContext::Normal),
+            Err(AstrolendError::AccountInFlashloan.into())
+        );
+    }
+
+    #[test]
+    fn test_find_or_create_full_account() {
+        let mut bank = Bank::default();
+        let mut lending_account = LendingAccount {
+            balances: [Balance::empty_deactivated(); 16],
+            _padding: [0; 8],
+        };
+
+        let bank_pks = [(); MAX_LENDING_ACCOUNT_BALANCES].map(|_| Pubkey::new_unique());
+        for bank_pk in bank_pks.iter() {
+            BankAccountWrapper::find_or_create_at(bank_pk, &mut bank, &mut lending_account, 0)
+                .unwrap();
+        }
+
+        let new_bank_pk = Pubkey::new_unique();
+        assert!(matches!(
+            BankAccountWrapper::find_or_create_at(&new_bank_pk, &mut bank, &mut lending_account, 0),
+            Err(err) if err == AstrolendError::LendingAccountBalanceSlotsFull.into()
+        ));
+
+        // Balances already open are still reachable
+        assert!(
+            BankAccountWrapper::find_or_create_at(&bank_pks[7], &mut bank, &mut lending_account, 0)
+                .is_ok()
+        );
+
+        // A slot parked with claimable emissions isn't free for other banks
+        lending_account.balances[3].emissions_outstanding = I80F48!(5).into();
+        lending_account.balances[3].close().unwrap();
+        assert!(BankAccountWrapper::find_or_create_at(
+            &new_bank_pk,
+            &mut bank,
+            &mut lending_account,
+            0
+        )
+        .is_err());
+
+        lending_account.balances[5].close().unwrap();
+        BankAccountWrapper::find_or_create_at(&new_bank_pk, &mut bank, &mut lending_account, 0)
+            .unwrap();
+        assert_eq!(lending_account.balances[5].bank_pk, new_bank_pk);
+    }
+
+    #[test]
+    fn test_transfer_asset_shares() {
+        let bank_pk = Pubkey::new_unique();
+        let mut bank = Bank::default();
+
+        let mut src = LendingAccount {
+            balances: [Balance::empty_deactivated(); 16],
+            _padding: [0; 8],
+        };
+        src.balances[0] = Balance {
+            active: true,
+            bank_pk,
+            asset_shares: I80F48!(100).into(),
+            ..Balance::empty_deactivated()
+        };
+        let mut dst = src;
+        dst.balances[0].asset_shares = I80F48::ZERO.into();
+
+        fn transfer(
+            bank_pk: &Pubkey,
+            bank: &mut Bank,
+            src: &mut LendingAccount,
+            dst: &mut LendingAccount,
+            shares: I80F48,
+        ) -> AstrolendResult<I80F48> {
+            let shares = BankAccountWrapper::find(bank_pk, bank, src)?
+                .transfer_asset_shares_out_at(shares, 0)?;
+            BankAccountWrapper::find(bank_pk, bank, dst)?
+                .transfer_asset_shares_in_at(shares, 0)?;
+
+            Ok(shares)
+        }
+
+        assert_eq!(
+            transfer(&bank_pk, &mut bank, &mut src, &mut dst, I80F48!(40)),
+            Ok(I80F48!(40))
+        );
+        assert_eq!(I80F48::from(src.balances[0].asset_shares), I80F48!(60));
+        assert_eq!(I80F48::from(dst.balances[0].asset_shares), I80F48!(40));
+
+        // Can't move more than the balance holds
+        assert_eq!(
+            transfer(&bank_pk, &mut bank, &mut src, &mut dst, I80F48!(61)),
+            Err(AstrolendError::OperationWithdrawOnly.into())
+        );
+
+        // Dust moves along and the source balance is closed
+        assert_eq!(
+            transfer(&bank_pk, &mut bank, &mut src, &mut dst, I80F48!(59.5)),
+            Ok(I80F48!(60))
+        );
+        assert!(!src.balances[0].active);
+        assert_eq!(I80F48::from(dst.balances[0].asset_shares), I80F48!(100));
+
+        // Liabilities stay put, on either side
+        dst.balances[1] = Balance {
+            active: true,
+            bank_pk,
+            liability_shares: I80F48!(10).into(),
+            ..Balance::empty_deactivated()
+        };
+        dst.balances[0] = Balance::empty_deactivated();
+        assert_eq!(
+            transfer(&bank_pk, &mut bank, &mut dst, &mut src, I80F48!(5)),
+            Err(AstrolendError::IllegalPositionTransfer.into())
+        );
+        assert_eq!(
+            BankAccountWrapper::find(&bank_pk, &mut bank, &mut dst)
+                .unwrap()
+                .transfer_asset_shares_in_at(I80F48!(5), 0),
+            Err(AstrolendError::IllegalPositionTransfer.into())
+        );
+    }
+
+    /// Bank states every operation is checked against, see `test_operation_permission_matrix`.
+    #[derive(Copy, Clone, Debug)]
+    enum BankState {
+        Operational,
+        Paused,
+        ReduceOnly,
+        Settlement,
+        Reentered,
+    }
+
+    /// Expected outcome of `op` in each of the `BankState`s, for an active then a disabled account.
+    ///
+    /// The match is exhaustive so a new operation doesn't compile until it is classified here.
+    fn expected_permissions(
+        op: BankOperation,
+    ) -> ([Option<AstrolendError>; 5], [Option<AstrolendError>; 5]) {
+        use BankOperation::*;
+
+        let ok = None;
+        let paused = Some(AstrolendError::BankPaused);
+        let reduce_only = Some(AstrolendError::BankReduceOnly);
+        let settlement = Some(AstrolendError::BankInSettlement);
+        let reentered = Some(AstrolendError::ReentrancyGuardActive);
+        let disabled = Some(AstrolendError::AccountDisabled);
+
+        // [operational, paused, reduce only, settlement, reentered]
+        match op {
+            Deposit => (
+                [ok, paused, reduce_only, settlement, reentered],
+                [disabled, disabled, disabled, disabled, reentered],
+            ),
+            Borrow => (
+                [ok, paused, reduce_only, settlement, reentered],
+                [disabled, disabled, disabled, disabled, reentered],
+            ),
+            LiquidateSeize => (
+                [ok, paused, reduce_only, ok, reentered],
+                [disabled, disabled, disabled, disabled, reentered],
+            ),
+            Withdraw | LiquidateRepay | TransferPosition | RedeemReceipts => (
+                [ok, paused, ok, ok, reentered],
+                [disabled, disabled, disabled, disabled, reentered],
+            ),
+            ClaimEmissions => (
+                [ok, ok, ok, ok, reentered],
+                [disabled, disabled, disabled, disabled, reentered],
+            ),
+            Repay | Bankruptcy => (
+                [ok, paused, ok, ok, reentered],
+                [ok, paused, ok, ok, reentered],
+            ),
+        }
+    }
+
+    /// Every combination of operation, bank state and account state has an explicit expectation
+    /// in `expected_permissions`.
+    #[test]
+    fn test_operation_permission_matrix() {
+        use BankOperation::*;
+
+        let ops = [
+            Deposit,
+            Withdraw,
+            Borrow,
+            Repay,
+            LiquidateSeize,
+            LiquidateRepay,
+            Bankruptcy,
+            ClaimEmissions,
+            TransferPosition,
+            RedeemReceipts,
+        ];
+        let states = [
+            BankState::Operational,
+            BankState::Paused,
+            BankState::ReduceOnly,
+            BankState::Settlement,
+            BankState::Reentered,
+        ];
+
+        let mut account: AstrolendAccount = bytemuck::Zeroable::zeroed();
+
+        for op in ops {
+            let (active_row, disabled_row) = expected_permissions(op);
+
+            for (i, state) in states.iter().enumerate() {
+                let mut bank = Bank::default();
+                bank.config.operational_state = match state {
+                    BankState::Paused => BankOperationalState::Paused,
+                    BankState::ReduceOnly => BankOperationalState::ReduceOnly,
+                    _ => BankOperationalState::Operational,
+                };
+                match state {
+                    BankState::Settlement => bank.enter_settlement(I80F48!(0.5)).unwrap(),
+                    BankState::Reentered => bank.enter_reentrancy_guard(1).unwrap(),
+                    _ => {}
+                }
+
+                for (is_disabled, expected) in
+                    [(false, active_row[i]), (true, disabled_row[i])]
+                {
+                    account.account_flags = if is_disabled { DISABLED_FLAG } else { 0 };
+
+                    let expected: AstrolendResult = expected.map_or(Ok(()), |err| Err(err.into()));
+                    assert_eq!(
+                        check_operation_allowed(op, &bank, &account),
+                        expected,
+                        "{:?} with bank {:?} and disabled = {}",
+                        op,
+                        state,
+                        is_disabled
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_settlement_mode_lifts_on_exit() {
+        let account: AstrolendAccount = bytemuck::Zeroable::zeroed();
+        let mut bank = Bank::default();
+        bank.enter_settlement(I80F48!(0.5)).unwrap();
+        assert!(check_operation_allowed(BankOperation::Deposit, &bank, &account).is_err());
+
+        bank.exit_settlement();
+        assert!(check_operation_allowed(BankOperation::Deposit, &bank, &account).is_ok());
+    }
 }
@@ -1,3 +1,9 @@
+pub mod account_filters;
 pub mod astrolend_account;
 pub mod astrolend_group;
 pub mod price;
+
+#[cfg(test)]
+mod accounting_proptests;
+#[cfg(test)]
+mod oracle_cases;
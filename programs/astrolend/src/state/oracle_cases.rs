@@ -0,0 +1,230 @@
+//! Golden tests running the oracle adapters against raw account data checked into
+//! `tests/fixtures/oracles`, see the README there for the source of each file.
+//!
+//! Adding a case for a new adapter only takes a fixture file and an `OracleCase` entry.
+
+use anchor_lang::prelude::*;
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+
+use super::{
+    astrolend_group::BankConfig,
+    price::{OraclePriceFeedAdapter, OraclePriceType, OracleSetup, PriceAdapter, PriceBias},
+};
+use crate::{constants::MAX_ORACLE_KEYS, errors::AstrolendError, prelude::AstrolendResult};
+
+const PYTH_PUSH_SOL: &[u8] = include_bytes!("../../tests/fixtures/oracles/pyth_push_sol.bin");
+// The next two are edited from `PYTH_PUSH_SOL` rather than dumped from mainnet, see the README
+const PYTH_PUSH_SOL_WIDE_CONF: &[u8] =
+    include_bytes!("../../tests/fixtures/oracles/pyth_push_sol_wide_conf.bin");
+const PYTH_PUSH_USDC: &[u8] = include_bytes!("../../tests/fixtures/oracles/pyth_push_usdc.bin");
+
+const PYTH_PUSH_SOL_FEED_ID: Pubkey = Pubkey::new_from_array([
+    0xef, 0x0d, 0x8b, 0x6f, 0xda, 0x2c, 0xeb, 0xa4, 0x1d, 0xa1, 0x5d, 0x40, 0x95, 0xd1, 0xda, 0x39,
+    0x2a, 0x0d, 0x2f, 0x8e, 0xd0, 0xc6, 0xc7, 0xbc, 0x0f, 0x4c, 0xfa, 0xc8, 0xc2, 0x80, 0xb5, 0x6d,
+]);
+const PYTH_PUSH_USDC_FEED_ID: Pubkey = Pubkey::new_from_array([
+    0xea, 0xa0, 0x20, 0xc6, 0x1c, 0xc4, 0x79, 0x71, 0x28, 0x13, 0x46, 0x1c, 0xe1, 0x53, 0x89, 0x4a,
+    0x96, 0xa6, 0xc0, 0x0b, 0x21, 0xed, 0x0c, 0xfc, 0x27, 0x98, 0xd1, 0xf9, 0xa9, 0xe9, 0xc9, 0x4a,
+]);
+
+/// Publish time of the captured SOL/USD update, shared by the derived fixtures
+const PYTH_PUSH_PUBLISH_TIME: i64 = 1721133402;
+const MAX_AGE: u16 = 60;
+
+const PRICE_TOLERANCE: I80F48 = I80F48!(0.000000001);
+
+struct ExpectedPrices {
+    real_time: I80F48,
+    real_time_low: I80F48,
+    real_time_high: I80F48,
+    time_weighted: I80F48,
+    time_weighted_low: I80F48,
+    time_weighted_high: I80F48,
+}
+
+struct OracleCase {
+    name: &'static str,
+    bytes: &'static [u8],
+    owner: Pubkey,
+    setup: OracleSetup,
+    /// Oracle address, or the feed id for Pyth push oracles
+    oracle_key: Pubkey,
+    unix_timestamp: i64,
+    expected: std::result::Result<ExpectedPrices, AstrolendError>,
+}
+
+impl OracleCase {
+    fn load(&self) -> AstrolendResult<OraclePriceFeedAdapter> {
+        let mut keys = [Pubkey::default(); MAX_ORACLE_KEYS];
+        keys[0] = self.oracle_key;
+
+        let bank_config = BankConfig {
+            oracle_setup: self.setup,
+            oracle_keys: keys,
+            oracle_max_age: MAX_AGE,
+            ..Default::default()
+        };
+        let clock = Clock {
+            unix_timestamp: self.unix_timestamp,
+            ..Default::default()
+        };
+
+        let mut data = self.bytes.to_vec();
+
+        OraclePriceFeedAdapter::try_from_bank_config_and_bytes(
+            &bank_config,
+            &self.oracle_key,
+            &self.owner,
+            &mut data,
+            &clock,
+        )
+    }
+
+    fn run(&self) {
+        let adapter = match (self.load(), &self.expected) {
+            (Ok(adapter), Ok(_)) => adapter,
+            (Err(err), Err(expected)) => {
+                assert_eq!(
+                    ProgramError::from(err),
+                    ProgramError::from(anchor_lang::error::Error::from(*expected)),
+                    "{}: unexpected error",
+                    self.name
+                );
+                return;
+            }
+            (Ok(_), Err(expected)) => panic!("{}: expected {:?}", self.name, expected),
+            (Err(err), Ok(_)) => panic!("{}: failed to load: {:?}", self.name, err),
+        };
+        let expected = self.expected.as_ref().unwrap();
+
+        let checks = [
+            (OraclePriceType::RealTime, None, expected.real_time),
+            (
+                OraclePriceType::RealTime,
+                Some(PriceBias::Low),
+                expected.real_time_low,
+            ),
+            (
+                OraclePriceType::RealTime,
+                Some(PriceBias::High),
+                expected.real_time_high,
+            ),
+            (OraclePriceType::TimeWeighted, None, expected.time_weighted),
+            (
+                OraclePriceType::TimeWeighted,
+                Some(PriceBias::Low),
+                expected.time_weighted_low,
+            ),
+            (
+                OraclePriceType::TimeWeighted,
+                Some(PriceBias::High),
+                expected.time_weighted_high,
+            ),
+        ];
+
+        for (price_type, bias, expected_price) in checks {
+            let price = adapter.get_price_of_type(price_type, bias).unwrap();
+
+            assert!(
+                (price - expected_price).abs() <= PRICE_TOLERANCE,
+                "{}: {:?} {:?} price {} != expected {}",
+                self.name,
+                price_type,
+                bias,
+                price,
+                expected_price
+            );
+        }
+    }
+}
+
+fn cases() -> Vec<OracleCase> {
+    let pyth_receiver = pyth_solana_receiver_sdk::id();
+
+    vec![
+        OracleCase {
+            name: "pyth push sol",
+            bytes: PYTH_PUSH_SOL,
+            owner: pyth_receiver,
+            setup: OracleSetup::PythPushOracle,
+            oracle_key: PYTH_PUSH_SOL_FEED_ID,
+            unix_timestamp: PYTH_PUSH_PUBLISH_TIME + 10,
+            expected: Ok(ExpectedPrices {
+                real_time: I80F48!(156.79769099),
+                real_time_low: I80F48!(156.4543088448),
+                real_time_high: I80F48!(157.1410731352),
+                time_weighted: I80F48!(156.609218),
+                time_weighted_low: I80F48!(156.2717299636),
+                time_weighted_high: I80F48!(156.9467060364),
+            }),
+        },
+        OracleCase {
+            name: "pyth push sol wide confidence",
+            bytes: PYTH_PUSH_SOL_WIDE_CONF,
+            owner: pyth_receiver,
+            setup: OracleSetup::PythPushOracle,
+            oracle_key: PYTH_PUSH_SOL_FEED_ID,
+            unix_timestamp: PYTH_PUSH_PUBLISH_TIME + 10,
+            // Confidence interval capped at 5% of the price
+            expected: Ok(ExpectedPrices {
+                real_time: I80F48!(156.79769099),
+                real_time_low: I80F48!(148.9578064405),
+                real_time_high: I80F48!(164.6375755395),
+                time_weighted: I80F48!(156.609218),
+                time_weighted_low: I80F48!(148.7787571),
+                time_weighted_high: I80F48!(164.4396789),
+            }),
+        },
+        OracleCase {
+            name: "pyth push usdc",
+            bytes: PYTH_PUSH_USDC,
+            owner: pyth_receiver,
+            setup: OracleSetup::PythPushOracle,
+            oracle_key: PYTH_PUSH_USDC_FEED_ID,
+            unix_timestamp: PYTH_PUSH_PUBLISH_TIME,
+            expected: Ok(ExpectedPrices {
+                real_time: I80F48!(0.99994512),
+                real_time_low: I80F48!(0.9982872588),
+                real_time_high: I80F48!(1.0016029812),
+                time_weighted: I80F48!(0.99996001),
+                time_weighted_low: I80F48!(0.9982421316),
+                time_weighted_high: I80F48!(1.0016778884),
+            }),
+        },
+        OracleCase {
+            name: "pyth push sol stale",
+            bytes: PYTH_PUSH_SOL,
+            owner: pyth_receiver,
+            setup: OracleSetup::PythPushOracle,
+            oracle_key: PYTH_PUSH_SOL_FEED_ID,
+            unix_timestamp: PYTH_PUSH_PUBLISH_TIME + MAX_AGE as i64 + 1,
+            expected: Err(AstrolendError::StaleOracle),
+        },
+        OracleCase {
+            name: "pyth push sol with usdc feed id",
+            bytes: PYTH_PUSH_SOL,
+            owner: pyth_receiver,
+            setup: OracleSetup::PythPushOracle,
+            oracle_key: PYTH_PUSH_USDC_FEED_ID,
+            unix_timestamp: PYTH_PUSH_PUBLISH_TIME,
+            expected: Err(AstrolendError::InvalidOracleAccount),
+        },
+        OracleCase {
+            name: "pyth push sol wrong owner",
+            bytes: PYTH_PUSH_SOL,
+            owner: Pubkey::default(),
+            setup: OracleSetup::PythPushOracle,
+            oracle_key: PYTH_PUSH_SOL_FEED_ID,
+            unix_timestamp: PYTH_PUSH_PUBLISH_TIME,
+            expected: Err(AstrolendError::InvalidOracleAccount),
+        },
+    ]
+}
+
+#[test]
+fn oracle_fixture_cases() {
+    for case in cases() {
+        case.run();
+    }
+}
@@ -15,14 +15,15 @@ pub use pyth_sdk_solana;
 use crate::{
     check,
     constants::{
-        CONF_INTERVAL_MULTIPLE, EXP_10, EXP_10_I80F48, MAX_CONF_INTERVAL,
-        MIN_PYTH_PUSH_VERIFICATION_LEVEL, PYTH_ID, STD_DEV_MULTIPLE, SWITCHBOARD_PULL_ID,
+        CONF_INTERVAL_MULTIPLE, EXP_10, EXP_10_I80F48, MAX_CONF_INTERVAL, MAX_EXP_10_I80F48,
+        MIN_PYTH_PUSH_VERIFICATION_LEVEL, PYTH_ID, PYTH_PUSH_PYTH_SPONSORED_SHARD_ID,
+        STD_DEV_MULTIPLE, SWITCHBOARD_PULL_ID,
     },
     debug, math_error,
     prelude::*,
 };
 
-use super::astrolend_group::BankConfig;
+use super::astrolend_group::{Bank, BankConfig};
 use anchor_lang::prelude::borsh;
 use pyth_solana_receiver_sdk::PYTH_PUSH_ORACLE_ID;
 
@@ -43,7 +44,7 @@ pub enum PriceBias {
     High,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, AnchorSerialize, AnchorDeserialize)]
 pub enum OraclePriceType {
     /// Time weighted price
     /// EMA for PythEma
@@ -68,9 +69,22 @@ pub enum OraclePriceFeedAdapter {
     SwitchboardV2(SwitchboardV2PriceFeed),
     PythPushOracle(PythPushOraclePriceFeed),
     SwitchboardPull(SwitchboardPullPriceFeed),
+    Fixed(FixedPriceFeed),
 }
 
 impl OraclePriceFeedAdapter {
+    /// Price `bank`, banks in settlement mode are priced at their settlement price whatever
+    /// oracle accounts are passed.
+    pub fn try_from_bank(bank: &Bank, ais: &[AccountInfo], clock: &Clock) -> AstrolendResult<Self> {
+        if bank.is_in_settlement() {
+            return Ok(OraclePriceFeedAdapter::Fixed(FixedPriceFeed::new(
+                bank.settlement_price.into(),
+            )));
+        }
+
+        Self::try_from_bank_config(&bank.config, ais, clock)
+    }
+
     pub fn try_from_bank_config(
         bank_config: &BankConfig,
         ais: &[AccountInfo],
@@ -90,6 +104,8 @@ impl OraclePriceFeedAdapter {
         clock: &Clock,
         max_age: u64,
     ) -> AstrolendResult<Self> {
+        let max_conf_interval = bank_config.get_oracle_max_conf_interval();
+
         match bank_config.oracle_setup {
             OracleSetup::None => Err(AstrolendError::OracleNotSetup.into()),
             OracleSetup::PythLegacy => {
@@ -102,7 +118,12 @@ impl OraclePriceFeedAdapter {
                 let account_info = &ais[0];
 
                 Ok(OraclePriceFeedAdapter::PythLegacy(
-                    PythLegacyPriceFeed::load_checked(account_info, clock.unix_timestamp, max_age)?,
+                    PythLegacyPriceFeed::load_checked(
+                        account_info,
+                        clock.unix_timestamp,
+                        max_age,
+                        max_conf_interval,
+                    )?,
                 ))
             }
             OracleSetup::SwitchboardV2 => {
@@ -113,7 +134,12 @@ impl OraclePriceFeedAdapter {
                 );
 
                 Ok(OraclePriceFeedAdapter::SwitchboardV2(
-                    SwitchboardV2PriceFeed::load_checked(&ais[0], clock.unix_timestamp, max_age)?,
+                    SwitchboardV2PriceFeed::load_checked(
+                        &ais[0],
+                        clock.unix_timestamp,
+                        max_age,
+                        max_conf_interval,
+                    )?,
                 ))
             }
             OracleSetup::PythPushOracle => {
@@ -134,6 +160,7 @@ impl OraclePriceFeedAdapter {
                         price_feed_id,
                         clock,
                         max_age,
+                        max_conf_interval,
                     )?,
                 ))
             }
@@ -145,12 +172,32 @@ impl OraclePriceFeedAdapter {
                 );
 
                 Ok(OraclePriceFeedAdapter::SwitchboardPull(
-                    SwitchboardPullPriceFeed::load_checked(&ais[0], clock.unix_timestamp, max_age)?,
+                    SwitchboardPullPriceFeed::load_checked(
+                        &ais[0],
+                        clock.unix_timestamp,
+                        max_age,
+                        max_conf_interval,
+                    )?,
                 ))
             }
         }
     }
 
+    /// Load the adapter from raw oracle account data, without a running banks client.
+    #[cfg(any(test, feature = "client"))]
+    pub fn try_from_bank_config_and_bytes(
+        bank_config: &BankConfig,
+        oracle_key: &Pubkey,
+        owner: &Pubkey,
+        data: &mut [u8],
+        clock: &Clock,
+    ) -> AstrolendResult<Self> {
+        let mut lamports = 0;
+        let ai = AccountInfo::new(oracle_key, false, false, &mut lamports, data, owner, false, 0);
+
+        Self::try_from_bank_config(bank_config, &[ai], clock)
+    }
+
     pub fn validate_bank_config(
         bank_config: &BankConfig,
         oracle_ais: &[AccountInfo],
@@ -182,10 +229,13 @@ impl OraclePriceFeedAdapter {
             OracleSetup::PythPushOracle => {
                 check!(oracle_ais.len() == 1, AstrolendError::InvalidOracleAccount);
 
-                PythPushOraclePriceFeed::check_ai_and_feed_id(
+                let feed_id = bank_config.get_pyth_push_oracle_feed_id().unwrap();
+                PythPushOraclePriceFeed::check_ai_shard(
                     &oracle_ais[0],
-                    bank_config.get_pyth_push_oracle_feed_id().unwrap(),
+                    bank_config.pyth_shard_id,
+                    feed_id,
                 )?;
+                PythPushOraclePriceFeed::check_ai_and_feed_id(&oracle_ais[0], feed_id)?;
 
                 Ok(())
             }
@@ -204,14 +254,55 @@ impl OraclePriceFeedAdapter {
     }
 }
 
+/// Fixed price of a bank in settlement mode, set by the group admin.
+#[cfg_attr(feature = "client", derive(Clone, Debug))]
+pub struct FixedPriceFeed {
+    price: I80F48,
+}
+
+impl FixedPriceFeed {
+    pub fn new(price: I80F48) -> Self {
+        Self { price }
+    }
+
+    /// Convert a `price * 10^expo` settlement price to a UI price.
+    pub fn price_from_components(price: u64, expo: i32) -> AstrolendResult<I80F48> {
+        check!(
+            (expo.unsigned_abs() as usize) < MAX_EXP_10_I80F48,
+            AstrolendError::InvalidConfig
+        );
+
+        pyth_price_components_to_i80f48(I80F48::from_num(price), expo)
+    }
+}
+
+impl PriceAdapter for FixedPriceFeed {
+    /// The settlement price has no confidence interval, every price type and bias is the same.
+    fn get_price_of_type(
+        &self,
+        _price_type: OraclePriceType,
+        _bias: Option<PriceBias>,
+    ) -> AstrolendResult<I80F48> {
+        check_price_positive(self.price)
+    }
+}
+
 #[cfg_attr(feature = "client", derive(Clone, Debug))]
 pub struct PythLegacyPriceFeed {
     ema_price: Box<Price>,
     price: Box<Price>,
+    /// Cap of the confidence interval as a share of the price, see
+    /// `BankConfig::get_oracle_max_conf_interval`.
+    max_conf_interval: I80F48,
 }
 
 impl PythLegacyPriceFeed {
-    pub fn load_checked(ai: &AccountInfo, current_time: i64, max_age: u64) -> AstrolendResult<Self> {
+    pub fn load_checked(
+        ai: &AccountInfo,
+        current_time: i64,
+        max_age: u64,
+        max_conf_interval: I80F48,
+    ) -> AstrolendResult<Self> {
         let price_feed = load_pyth_price_feed(ai)?;
 
         let ema_price = price_feed
@@ -225,6 +316,7 @@ impl PythLegacyPriceFeed {
         Ok(Self {
             ema_price: Box::new(ema_price),
             price: Box::new(price),
+            max_conf_interval,
         })
     }
 
@@ -245,11 +337,11 @@ impl PythLegacyPriceFeed {
                 .checked_mul(CONF_INTERVAL_MULTIPLE)
                 .ok_or_else(math_error!())?;
 
-        // Cap confidence interval to 5% of price
+        // Cap confidence interval to a share of the price, 5% by default
         let price = pyth_price_components_to_i80f48(I80F48::from_num(price.price), price.expo)?;
 
         let max_conf_interval = price
-            .checked_mul(MAX_CONF_INTERVAL)
+            .checked_mul(self.max_conf_interval)
             .ok_or_else(math_error!())?;
 
         assert!(
@@ -282,10 +374,10 @@ impl PriceAdapter for PythLegacyPriceFeed {
         price_type: OraclePriceType,
         bias: Option<PriceBias>,
     ) -> AstrolendResult<I80F48> {
-        let price = match price_type {
+        let price = check_price_positive(match price_type {
             OraclePriceType::TimeWeighted => self.get_ema_price()?,
             OraclePriceType::RealTime => self.get_unweighted_price()?,
-        };
+        })?;
 
         match bias {
             None => Ok(price),
@@ -309,6 +401,8 @@ impl PriceAdapter for PythLegacyPriceFeed {
 #[cfg_attr(feature = "client", derive(Clone, Debug))]
 pub struct SwitchboardPullPriceFeed {
     pub feed: Box<LitePullFeedAccountData>,
+    /// See `PythLegacyPriceFeed::max_conf_interval`.
+    pub max_conf_interval: I80F48,
 }
 
 impl SwitchboardPullPriceFeed {
@@ -316,6 +410,7 @@ impl SwitchboardPullPriceFeed {
         ai: &AccountInfo,
         current_timestamp: i64,
         max_age: u64,
+        max_conf_interval: I80F48,
     ) -> AstrolendResult<Self> {
         let ai_data = ai.data.borrow();
 
@@ -335,6 +430,7 @@ impl SwitchboardPullPriceFeed {
 
         Ok(Self {
             feed: Box::new(feed.into()),
+            max_conf_interval,
         })
     }
 
@@ -354,7 +450,8 @@ impl SwitchboardPullPriceFeed {
     fn get_price(&self) -> AstrolendResult<I80F48> {
         let sw_result = self.feed.result;
         // Note: Pull oracles support mean (result.mean) or median (result.value)
-        let price: I80F48 = I80F48::from_num(sw_result.value)
+        let price: I80F48 = I80F48::checked_from_num(sw_result.value)
+            .ok_or(AstrolendError::OraclePriceNonPositive)?
             .checked_div(EXP_10_I80F48[switchboard_on_demand::PRECISION as usize])
             .ok_or_else(math_error!())?;
 
@@ -376,7 +473,7 @@ impl SwitchboardPullPriceFeed {
         let price = self.get_price()?;
 
         let max_conf_interval = price
-            .checked_mul(MAX_CONF_INTERVAL)
+            .checked_mul(self.max_conf_interval)
             .ok_or_else(math_error!())?;
 
         assert!(
@@ -399,7 +496,7 @@ impl PriceAdapter for SwitchboardPullPriceFeed {
         _price_type: OraclePriceType,
         bias: Option<PriceBias>,
     ) -> AstrolendResult<I80F48> {
-        let price = self.get_price()?;
+        let price = check_price_positive(self.get_price()?)?;
 
         match bias {
             Some(price_bias) => {
@@ -422,6 +519,8 @@ impl PriceAdapter for SwitchboardPullPriceFeed {
 #[cfg_attr(feature = "client", derive(Clone, Debug))]
 pub struct SwitchboardV2PriceFeed {
     aggregator_account: Box<LiteAggregatorAccountData>,
+    /// See `PythLegacyPriceFeed::max_conf_interval`.
+    max_conf_interval: I80F48,
 }
 
 impl SwitchboardV2PriceFeed {
@@ -429,6 +528,7 @@ impl SwitchboardV2PriceFeed {
         ai: &AccountInfo,
         current_timestamp: i64,
         max_age: u64,
+        max_conf_interval: I80F48,
     ) -> AstrolendResult<Self> {
         let ai_data = ai.data.borrow();
 
@@ -446,6 +546,7 @@ impl SwitchboardV2PriceFeed {
 
         Ok(Self {
             aggregator_account: Box::new(aggregator_account.into()),
+            max_conf_interval,
         })
     }
 
@@ -485,7 +586,7 @@ impl SwitchboardV2PriceFeed {
         let price = self.get_price()?;
 
         let max_conf_interval = price
-            .checked_mul(MAX_CONF_INTERVAL)
+            .checked_mul(self.max_conf_interval)
             .ok_or_else(math_error!())?;
 
         assert!(
@@ -508,7 +609,7 @@ impl PriceAdapter for SwitchboardV2PriceFeed {
         _price_type: OraclePriceType,
         bias: Option<PriceBias>,
     ) -> AstrolendResult<I80F48> {
-        let price = self.get_price()?;
+        let price = check_price_positive(self.get_price()?)?;
 
         match bias {
             Some(price_bias) => {
@@ -551,6 +652,8 @@ pub fn load_price_update_v2_checked(ai: &AccountInfo) -> AstrolendResult<PriceUp
 pub struct PythPushOraclePriceFeed {
     ema_price: Box<pyth_solana_receiver_sdk::price_update::Price>,
     price: Box<pyth_solana_receiver_sdk::price_update::Price>,
+    /// See `PythLegacyPriceFeed::max_conf_interval`.
+    max_conf_interval: I80F48,
 }
 
 impl PythPushOraclePriceFeed {
@@ -575,6 +678,7 @@ impl PythPushOraclePriceFeed {
         feed_id: &FeedId,
         clock: &Clock,
         max_age: u64,
+        max_conf_interval: I80F48,
     ) -> AstrolendResult<Self> {
         let price_feed_account = load_price_update_v2_checked(ai)?;
 
@@ -616,6 +720,7 @@ impl PythPushOraclePriceFeed {
         Ok(Self {
             price: Box::new(price),
             ema_price: Box::new(ema_price),
+            max_conf_interval,
         })
     }
 
@@ -656,6 +761,7 @@ impl PythPushOraclePriceFeed {
         Ok(Self {
             price: Box::new(price),
             ema_price: Box::new(ema_price),
+            max_conf_interval: MAX_CONF_INTERVAL,
         })
     }
 
@@ -677,6 +783,21 @@ impl PythPushOraclePriceFeed {
         Ok(())
     }
 
+    /// Check that `ai` is the oracle account of `feed_id` on `shard_id`, or on the Pyth
+    /// sponsored shard which is accepted for every bank.
+    pub fn check_ai_shard(ai: &AccountInfo, shard_id: u16, feed_id: &FeedId) -> AstrolendResult {
+        let (shard_address, _) = Self::find_oracle_address(shard_id, feed_id);
+        let (sponsored_address, _) =
+            Self::find_oracle_address(PYTH_PUSH_PYTH_SPONSORED_SHARD_ID, feed_id);
+
+        check!(
+            ai.key == &shard_address || ai.key == &sponsored_address,
+            AstrolendError::WrongOracleAccountKeys
+        );
+
+        Ok(())
+    }
+
     fn get_confidence_interval(&self, use_ema: bool) -> AstrolendResult<I80F48> {
         let price = if use_ema {
             &self.ema_price
@@ -689,11 +810,11 @@ impl PythPushOraclePriceFeed {
                 .checked_mul(CONF_INTERVAL_MULTIPLE)
                 .ok_or_else(math_error!())?;
 
-        // Cap confidence interval to 5% of price
+        // Cap confidence interval to a share of the price, 5% by default
         let price = pyth_price_components_to_i80f48(I80F48::from_num(price.price), price.exponent)?;
 
         let max_conf_interval = price
-            .checked_mul(MAX_CONF_INTERVAL)
+            .checked_mul(self.max_conf_interval)
             .ok_or_else(math_error!())?;
 
         assert!(
@@ -740,10 +861,10 @@ impl PriceAdapter for PythPushOraclePriceFeed {
         price_type: OraclePriceType,
         bias: Option<PriceBias>,
     ) -> AstrolendResult<I80F48> {
-        let price = match price_type {
+        let price = check_price_positive(match price_type {
             OraclePriceType::TimeWeighted => self.get_ema_price()?,
             OraclePriceType::RealTime => self.get_unweighted_price()?,
-        };
+        })?;
 
         match bias {
             None => Ok(price),
@@ -851,9 +972,13 @@ impl LiteAggregatorAccountData {
     }
 }
 
+/// Scale `price * 10^exponent` to a UI price. Exponents past `EXP_10_I80F48` and prices too
+/// large for I80F48 once scaled are as unusable as non-positive prices and fail the same way.
 #[inline(always)]
 fn pyth_price_components_to_i80f48(price: I80F48, exponent: i32) -> AstrolendResult<I80F48> {
-    let scaling_factor = EXP_10_I80F48[exponent.unsigned_abs() as usize];
+    let scaling_factor = *EXP_10_I80F48
+        .get(exponent.unsigned_abs() as usize)
+        .ok_or(AstrolendError::OraclePriceNonPositive)?;
 
     let price = if exponent == 0 {
         price
@@ -864,12 +989,21 @@ fn pyth_price_components_to_i80f48(price: I80F48, exponent: i32) -> AstrolendRes
     } else {
         price
             .checked_mul(scaling_factor)
-            .ok_or_else(math_error!())?
+            .ok_or(AstrolendError::OraclePriceNonPositive)?
     };
 
     Ok(price)
 }
 
+/// Oracle prices are strictly positive. A zero or negative price would value collateral at
+/// nothing, triggering liquidations, or debt at nothing, allowing unbounded borrows.
+#[inline(always)]
+fn check_price_positive(price: I80F48) -> AstrolendResult<I80F48> {
+    check!(price > I80F48::ZERO, AstrolendError::OraclePriceNonPositive);
+
+    Ok(price)
+}
+
 /// Load and validate a pyth price feed account.
 fn load_pyth_price_feed(ai: &AccountInfo) -> AstrolendResult<PriceFeed> {
     check!(ai.owner.eq(&PYTH_ID), AstrolendError::InvalidOracleAccount);
@@ -882,7 +1016,8 @@ fn load_pyth_price_feed(ai: &AccountInfo) -> AstrolendResult<PriceFeed> {
 fn switchboard_decimal_to_i80f48(decimal: SwitchboardDecimal) -> Option<I80F48> {
     let decimal = fit_scale_switchboard_decimal(decimal, MAX_SCALE)?;
 
-    I80F48::from_num(decimal.mantissa).checked_div(EXP_10_I80F48[decimal.scale as usize])
+    I80F48::checked_from_num(decimal.mantissa)?
+        .checked_div(EXP_10_I80F48[decimal.scale as usize])
 }
 
 const MAX_SCALE: u32 = 20;
@@ -965,6 +1100,7 @@ mod tests {
         let pyth_adapter = PythLegacyPriceFeed {
             ema_price: high_confidence_price,
             price: low_confidence_price,
+            max_conf_interval: MAX_CONF_INTERVAL,
         };
 
         // Test confidence interval when using EMA price (high confidence)
@@ -990,6 +1126,7 @@ mod tests {
                 latest_confirmed_round_std_deviation: SwitchboardDecimal::from_f64(10.0),
                 min_oracle_results: 1,
             }),
+            max_conf_interval: MAX_CONF_INTERVAL,
         };
 
         let swb_adapter_low_confidence = SwitchboardV2PriceFeed {
@@ -1000,6 +1137,7 @@ mod tests {
                 latest_confirmed_round_std_deviation: SwitchboardDecimal::from_f64(1.0),
                 min_oracle_results: 1,
             }),
+            max_conf_interval: MAX_CONF_INTERVAL,
         };
 
         // Test confidence interval
@@ -1055,11 +1193,13 @@ mod tests {
         let pyth_legacy = PythLegacyPriceFeed {
             ema_price: Box::new(legacy_ema),
             price: Box::new(legacy_price),
+            max_conf_interval: MAX_CONF_INTERVAL,
         };
 
         let pyth_push = PythPushOraclePriceFeed {
             ema_price: Box::new(push_price_ema),
             price: Box::new(push_price),
+            max_conf_interval: MAX_CONF_INTERVAL,
         };
 
         assert_eq!(
@@ -1130,11 +1270,13 @@ mod tests {
         let pyth_legacy = PythLegacyPriceFeed {
             ema_price: Box::new(legacy_ema),
             price: Box::new(legacy_price),
+            max_conf_interval: MAX_CONF_INTERVAL,
         };
 
         let pyth_push = PythPushOraclePriceFeed {
             ema_price: Box::new(push_price_ema),
             price: Box::new(push_price),
+            max_conf_interval: MAX_CONF_INTERVAL,
         };
 
         // Test high bias ema
@@ -1197,6 +1339,114 @@ mod tests {
         );
     }
 
+    /// Every price type and bias of `adapter` fails with `OraclePriceNonPositive`
+    fn assert_price_non_positive(adapter: &impl PriceAdapter) {
+        for price_type in [OraclePriceType::RealTime, OraclePriceType::TimeWeighted] {
+            for bias in [None, Some(PriceBias::Low), Some(PriceBias::High)] {
+                let err = adapter.get_price_of_type(price_type, bias).unwrap_err();
+                assert_eq!(
+                    ProgramError::from(err),
+                    ProgramError::from(Error::from(AstrolendError::OraclePriceNonPositive)),
+                    "{:?} {:?}",
+                    price_type,
+                    bias
+                );
+            }
+        }
+    }
+
+    /// Zero, negative, an exponent past `EXP_10_I80F48` and a price overflowing I80F48
+    const NON_POSITIVE_PYTH_PRICES: [(i64, i32); 4] = [
+        (0, -6),
+        (-100_000_000, -6),
+        (100_000_000, -30),
+        (i64::MAX, 20),
+    ];
+
+    #[test]
+    fn pyth_legacy_non_positive_price() {
+        for (price, expo) in NON_POSITIVE_PYTH_PRICES {
+            let make_price = || {
+                Box::new(Price {
+                    price,
+                    conf: 0,
+                    expo,
+                    publish_time: 0,
+                })
+            };
+
+            assert_price_non_positive(&PythLegacyPriceFeed {
+                ema_price: make_price(),
+                price: make_price(),
+                max_conf_interval: MAX_CONF_INTERVAL,
+            });
+        }
+    }
+
+    #[test]
+    fn pyth_push_non_positive_price() {
+        for (price, exponent) in NON_POSITIVE_PYTH_PRICES {
+            let make_price = || {
+                Box::new(pyth_solana_receiver_sdk::price_update::Price {
+                    price,
+                    conf: 0,
+                    exponent,
+                    publish_time: 0,
+                })
+            };
+
+            assert_price_non_positive(&PythPushOraclePriceFeed {
+                ema_price: make_price(),
+                price: make_price(),
+                max_conf_interval: MAX_CONF_INTERVAL,
+            });
+        }
+    }
+
+    #[test]
+    fn switchboard_v2_non_positive_price() {
+        // A scale past `MAX_SCALE` rounds the price to zero
+        for (mantissa, scale) in [(0, 0), (-155, 0), (1, 28)] {
+            assert_price_non_positive(&SwitchboardV2PriceFeed {
+                aggregator_account: Box::new(LiteAggregatorAccountData {
+                    resolution_mode: AggregatorResolutionMode::ModeSlidingResolution,
+                    latest_confirmed_round_result: SwitchboardDecimal { mantissa, scale },
+                    latest_confirmed_round_num_success: 1,
+                    latest_confirmed_round_std_deviation: SwitchboardDecimal::from_f64(0.0),
+                    min_oracle_results: 1,
+                }),
+                max_conf_interval: MAX_CONF_INTERVAL,
+            });
+        }
+    }
+
+    #[test]
+    fn switchboard_pull_non_positive_price() {
+        // Values are scaled by 10^18, 1 rounds to zero and `i128::MAX` overflows I80F48
+        for value in [0, -155 * EXP_10[18], 1, i128::MAX] {
+            let mut result: CurrentResult = bytemuck::Zeroable::zeroed();
+            result.value = value;
+
+            assert_price_non_positive(&SwitchboardPullPriceFeed {
+                feed: Box::new(LitePullFeedAccountData {
+                    result,
+                    #[cfg(feature = "client")]
+                    feed_hash: [0; 32],
+                    #[cfg(feature = "client")]
+                    last_update_timestamp: 0,
+                }),
+                max_conf_interval: MAX_CONF_INTERVAL,
+            });
+        }
+    }
+
+    #[test]
+    fn fixed_non_positive_price() {
+        for price in [I80F48::ZERO, I80F48!(-1)] {
+            assert_price_non_positive(&FixedPriceFeed::new(price));
+        }
+    }
+
     use solana_sdk::account::Account;
     use std::cell::RefCell;
     use std::rc::Rc;
@@ -1242,8 +1492,13 @@ mod tests {
 
         let current_timestamp = 42;
         let max_age = 100;
-        let feed: SwitchboardPullPriceFeed =
-            SwitchboardPullPriceFeed::load_checked(&ai, current_timestamp, max_age).unwrap();
+        let feed: SwitchboardPullPriceFeed = SwitchboardPullPriceFeed::load_checked(
+            &ai,
+            current_timestamp,
+            max_age,
+            MAX_CONF_INTERVAL,
+        )
+        .unwrap();
         let price: I80F48 = feed.get_price().unwrap();
         let conf: I80F48 = feed.get_confidence_interval().unwrap();
 
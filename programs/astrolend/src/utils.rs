@@ -1,9 +1,20 @@
 use crate::{
-    bank_authority_seed, bank_seed,
-    state::astrolend_group::{Bank, BankVaultType},
+    bank_authority_seed, bank_seed, check,
+    constants::{
+        LIQUIDITY_VAULT_AUTHORITY_SEED, NATIVE_TOKEN_ACCOUNT_SEED, PENDING_CONFIG_SEED,
+        RECEIPT_MINT_SEED, WHITELIST_SEED,
+    },
+    state::{
+        astrolend_account::calc_value,
+        astrolend_group::{Bank, BankVaultType},
+        price::{OraclePriceType, PriceAdapter},
+    },
     AstrolendError, AstrolendResult,
 };
-use anchor_lang::prelude::*;
+use anchor_lang::{
+    prelude::*,
+    system_program::{self, Assign, Transfer},
+};
 use anchor_spl::{
     token::Token,
     token_2022::spl_token_2022::{
@@ -13,10 +24,61 @@ use anchor_spl::{
             BaseStateWithExtensions, StateWithExtensions,
         },
     },
-    token_interface::Mint,
+    token_interface::{Mint, TokenAccount},
 };
 use fixed::types::I80F48;
 
+/// Address of a bank created with `lending_pool_add_bank_with_seed`.
+pub fn find_bank_pda(astrolend_group: &Pubkey, bank_mint: &Pubkey, bank_seed: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            astrolend_group.as_ref(),
+            bank_mint.as_ref(),
+            &bank_seed.to_le_bytes(),
+        ],
+        &crate::id(),
+    )
+}
+
+/// Address of the whitelist entry of `wallet` in a group.
+pub fn find_whitelist_entry_pda(astrolend_group: &Pubkey, wallet: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            WHITELIST_SEED.as_bytes(),
+            astrolend_group.as_ref(),
+            wallet.as_ref(),
+        ],
+        &crate::id(),
+    )
+}
+
+/// Address of the receipt mint of a bank created with receipts enabled.
+pub fn find_receipt_mint_pda(bank_pk: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[RECEIPT_MINT_SEED.as_bytes(), bank_pk.as_ref()],
+        &crate::id(),
+    )
+}
+
+/// Address of the ephemeral wSOL token account of the native deposit and withdraw instructions.
+pub fn find_native_token_account_pda(astrolend_account: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            NATIVE_TOKEN_ACCOUNT_SEED.as_bytes(),
+            astrolend_account.as_ref(),
+        ],
+        &crate::id(),
+    )
+}
+
+/// Address of the bank config change waiting on the group config timelock.
+pub fn find_pending_config_pda(bank: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[PENDING_CONFIG_SEED.as_bytes(), bank.as_ref()],
+        &crate::id(),
+    )
+}
+
 pub fn find_bank_vault_pda(bank_pk: &Pubkey, vault_type: BankVaultType) -> (Pubkey, u8) {
     Pubkey::find_program_address(bank_seed!(vault_type, bank_pk), &crate::id())
 }
@@ -43,6 +105,14 @@ where
     }
 }
 
+/// Token balance of a token or token-2022 account.
+pub fn token_account_amount(account: &AccountInfo) -> AstrolendResult<u64> {
+    let account_data = account.try_borrow_data()?;
+    let account = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account_data)?;
+
+    Ok(account.base.amount)
+}
+
 pub fn calculate_pre_fee_spl_deposit_amount(
     mint_ai: AccountInfo,
     post_fee_amount: u64,
@@ -92,6 +162,33 @@ pub fn calculate_post_fee_spl_deposit_amount(
     Ok(output_amount)
 }
 
+/// Rejects a transfer of `amount_pre_fee` out of a vault if the destination would receive
+/// less than `min_amount_out` after the Token-2022 transfer fee of the current epoch.
+pub fn check_min_amount_out(
+    maybe_mint: Option<&InterfaceAccount<Mint>>,
+    amount_pre_fee: u64,
+    epoch: u64,
+    min_amount_out: Option<u64>,
+) -> AstrolendResult {
+    let Some(min_amount_out) = min_amount_out else {
+        return Ok(());
+    };
+
+    let amount_out = maybe_mint
+        .map(|mint| {
+            calculate_post_fee_spl_deposit_amount(mint.to_account_info(), amount_pre_fee, epoch)
+        })
+        .transpose()?
+        .unwrap_or(amount_pre_fee);
+
+    check!(
+        amount_out >= min_amount_out,
+        AstrolendError::SlippageExceeded
+    );
+
+    Ok(())
+}
+
 pub fn nonzero_fee(mint_ai: AccountInfo, epoch: u64) -> AstrolendResult<bool> {
     if mint_ai.owner.eq(&Token::id()) {
         return Ok(false);
@@ -113,12 +210,18 @@ pub fn nonzero_fee(mint_ai: AccountInfo, epoch: u64) -> AstrolendResult<bool> {
 
 /// Checks if first account is a mint account. If so, updates remaining_account -> &remaining_account[1..]
 ///
+/// Errors with `InvalidTokenProgram` if `token_program` doesn't match the bank mint, and with
+/// `MintMismatch` if the mint key or decimals differ from the bank's.
+///
 /// Ok(None) if Tokenkeg
 pub fn maybe_take_bank_mint<'info>(
     remaining_accounts: &mut &'info [AccountInfo<'info>],
     bank: &Bank,
     token_program: &Pubkey,
+    vault_owner: &Pubkey,
 ) -> AstrolendResult<Option<InterfaceAccount<'info, Mint>>> {
+    bank.check_token_program(token_program, vault_owner)?;
+
     match *token_program {
         anchor_spl::token::ID => Ok(None),
         anchor_spl::token_2022::ID => {
@@ -127,22 +230,175 @@ pub fn maybe_take_bank_mint<'info>(
                 .ok_or(AstrolendError::T22MintRequired)?;
             *remaining_accounts = remaining;
 
-            if bank.mint != *maybe_mint.key {
-                return err!(AstrolendError::T22MintRequired);
-            }
+            check!(bank.mint == *maybe_mint.key, AstrolendError::MintMismatch);
 
-            InterfaceAccount::try_from(maybe_mint)
-                .map(Option::Some)
-                .map_err(|e| {
-                    msg!("failed to parse mint account: {:?}", e);
-                    AstrolendError::T22MintRequired.into()
-                })
+            let mint = InterfaceAccount::<Mint>::try_from(maybe_mint).map_err(|e| {
+                msg!("failed to parse mint account: {:?}", e);
+                AstrolendError::T22MintRequired
+            })?;
+
+            // The live mint must still match what the bank was created with
+            check!(
+                mint.decimals == bank.mint_decimals,
+                AstrolendError::MintMismatch
+            );
+
+            Ok(Some(mint))
         }
 
         _ => panic!("unsupported token program"),
     }
 }
 
+/// Takes the referrer token account from the front of `remaining_accounts`,
+/// updates remaining_account -> &remaining_account[1..]
+///
+/// Errors with `InvalidReferrer` if the account is not a token account of the bank mint.
+pub fn take_referrer_token_account<'info>(
+    remaining_accounts: &mut &'info [AccountInfo<'info>],
+    bank: &Bank,
+) -> AstrolendResult<InterfaceAccount<'info, TokenAccount>> {
+    let (referrer, remaining) = remaining_accounts
+        .split_first()
+        .ok_or(AstrolendError::InvalidReferrer)?;
+    *remaining_accounts = remaining;
+
+    let referrer_token_account =
+        InterfaceAccount::<TokenAccount>::try_from(referrer).map_err(|e| {
+            msg!("failed to parse referrer token account: {:?}", e);
+            AstrolendError::InvalidReferrer
+        })?;
+
+    check!(
+        referrer_token_account.mint == bank.mint,
+        AstrolendError::InvalidReferrer
+    );
+
+    Ok(referrer_token_account)
+}
+
+/// Accounts of a deposit minting receipts, the liquidity vault authority signs as the receipt
+/// mint authority.
+pub struct ReceiptAccounts<'info> {
+    pub receipt_mint: AccountInfo<'info>,
+    pub receipt_token_account: AccountInfo<'info>,
+    pub liquidity_vault_authority: AccountInfo<'info>,
+}
+
+/// Takes the [ReceiptAccounts] from the front of `remaining_accounts`,
+/// updates remaining_account -> &remaining_account[3..]
+///
+/// Errors with `ReceiptsDisabled` if the bank has no receipt mint, and with
+/// `InvalidReceiptAccounts` if the receipt mint or the liquidity vault authority aren't the
+/// bank's. The receipt token account is checked by the token program on mint.
+pub fn take_receipt_accounts<'info>(
+    remaining_accounts: &mut &'info [AccountInfo<'info>],
+    bank_pk: &Pubkey,
+    bank: &Bank,
+) -> AstrolendResult<ReceiptAccounts<'info>> {
+    let receipt_mint_key = bank.get_receipt_mint(bank_pk)?;
+    let liquidity_vault_authority_key = Pubkey::create_program_address(
+        &[
+            LIQUIDITY_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank_pk.as_ref(),
+            &[bank.liquidity_vault_authority_bump],
+        ],
+        &crate::id(),
+    )
+    .map_err(|_| AstrolendError::InvalidReceiptAccounts)?;
+
+    check!(
+        remaining_accounts.len() >= 3,
+        AstrolendError::InvalidReceiptAccounts
+    );
+    let (receipt_ais, remaining) = remaining_accounts.split_at(3);
+    *remaining_accounts = remaining;
+
+    check!(
+        *receipt_ais[0].key == receipt_mint_key
+            && *receipt_ais[2].key == liquidity_vault_authority_key,
+        AstrolendError::InvalidReceiptAccounts
+    );
+
+    Ok(ReceiptAccounts {
+        receipt_mint: receipt_ais[0].clone(),
+        receipt_token_account: receipt_ais[1].clone(),
+        liquidity_vault_authority: receipt_ais[2].clone(),
+    })
+}
+
+/// Oracle accounts of the bank in `remaining_ais`, which holds the (bank, oracle) observation
+/// pairs of the health check.
+pub fn find_oracle_ais<'info>(
+    bank_pk: &Pubkey,
+    remaining_ais: &'info [AccountInfo<'info>],
+) -> AstrolendResult<&'info [AccountInfo<'info>]> {
+    remaining_ais
+        .chunks_exact(2)
+        .find(|ais| ais[0].key == bank_pk)
+        .map(|ais| &ais[1..])
+        .ok_or(AstrolendError::MissingPythOrBankAccount)
+}
+
+/// USD value of `amount` of the bank mint at the real time oracle price, used to track the
+/// group TVL.
+///
+/// Expects `remaining_ais` to hold the (bank, oracle) observation pairs.
+pub fn calc_tvl_value<'info>(
+    bank_pk: &Pubkey,
+    bank: &mut Bank,
+    amount: u64,
+    remaining_ais: &'info [AccountInfo<'info>],
+    clock: &Clock,
+) -> AstrolendResult<I80F48> {
+    let oracle_ais = find_oracle_ais(bank_pk, remaining_ais)?;
+
+    let price_feed = bank.load_price_feed(oracle_ais, clock)?;
+    let price = price_feed.get_price_of_type(OraclePriceType::RealTime, None)?;
+
+    calc_value(I80F48::from_num(amount), price, bank.mint_decimals, None)
+}
+
+/// Make the empty address `pda` a zero sized account owned by the program, so later
+/// instructions can tell it was claimed. Lamports already sent to the address are topped up to
+/// rent exemption instead of failing the creation.
+pub fn claim_pda<'info>(
+    payer: &AccountInfo<'info>,
+    pda: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    pda_seeds: &[&[u8]],
+) -> AstrolendResult {
+    let top_up = Rent::get()?
+        .minimum_balance(0)
+        .saturating_sub(pda.lamports());
+
+    if top_up > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                Transfer {
+                    from: payer.clone(),
+                    to: pda.clone(),
+                },
+            ),
+            top_up,
+        )?;
+    }
+
+    system_program::assign(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            Assign {
+                account_to_assign: pda.clone(),
+            },
+            &[pda_seeds],
+        ),
+        &crate::ID,
+    )?;
+
+    Ok(())
+}
+
 const ONE_IN_BASIS_POINTS: u128 = 10_000;
 /// backported fix from
 /// https://github.com/solana-labs/solana-program-library/commit/20e6792179fc7f1251579c1c33a4a0feec48e15e
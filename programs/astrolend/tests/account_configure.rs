@@ -0,0 +1,81 @@
+use anchor_lang::Discriminator;
+use fixtures::{assert_custom_error, prelude::*};
+use astrolend::{
+    errors::AstrolendError,
+    events::{AstrolendAccountConfigureEvent, EVENT_VERSION},
+    instruction::LendingAccountConfigure,
+    state::astrolend_account::{AccountConfigOpt, THIRD_PARTY_DEPOSIT_ALLOWED_FLAG},
+};
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer, transaction::Transaction};
+
+#[tokio::test]
+async fn configure_third_party_deposits() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let astrolend_account_f = test_f.create_astrolend_account().await;
+
+    let config = AccountConfigOpt {
+        third_party_deposits_allowed: Some(true),
+        ..Default::default()
+    };
+    let ix = astrolend_account_f.make_configure_ix(config.clone());
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await?
+    };
+    assert!(result.result.is_ok());
+
+    let events = parse_events::<AstrolendAccountConfigureEvent>(&result);
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event.config, config);
+    assert_eq!(event.header.astrolend_account, astrolend_account_f.key);
+    assert_eq!(event.meta.version, EVENT_VERSION);
+    assert_eq!(
+        event.meta.instruction,
+        LendingAccountConfigure::DISCRIMINATOR
+    );
+
+    assert!(astrolend_account_f
+        .load()
+        .await
+        .get_flag(THIRD_PARTY_DEPOSIT_ALLOWED_FLAG));
+
+    let third_party = Keypair::new();
+    let third_party_usdc =
+        TokenAccountFixture::new(test_f.context.clone(), &test_f.usdc_mint, &third_party.pubkey())
+            .await;
+    test_f.usdc_mint.mint_to(&third_party_usdc.key, 100).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    astrolend_account_f
+        .try_bank_deposit_on_behalf_of(&third_party, third_party_usdc.key, usdc_bank, 40)
+        .await?;
+
+    // Opting back out
+    astrolend_account_f
+        .try_configure(AccountConfigOpt {
+            third_party_deposits_allowed: Some(false),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(astrolend_account_f.load().await.account_flags, 0);
+
+    let res = astrolend_account_f
+        .try_bank_deposit_on_behalf_of(&third_party, third_party_usdc.key, usdc_bank, 30)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::ThirdPartyDepositNotAllowed);
+
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use fixtures::prelude::*;
+use astrolend::state::{
+    account_filters::{MemcmpFilter, BANK_GROUP_OFFSET},
+    astrolend_account::AstrolendAccount,
+    astrolend_group::{AstrolendGroup, Bank},
+};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+
+async fn account_data(test_f: &TestFixture, address: Pubkey) -> Vec<u8> {
+    test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(address)
+        .await
+        .unwrap()
+        .unwrap()
+        .data
+}
+
+fn matches_all(filters: &[MemcmpFilter], data: &[u8]) -> bool {
+    filters.iter().all(|filter| filter.matches(data))
+}
+
+#[tokio::test]
+async fn astrolend_account_filters_match_layout() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let astrolend_account_f = test_f.create_astrolend_account().await;
+    let data = account_data(&test_f, astrolend_account_f.key).await;
+
+    let group = test_f.astrolend_group.key;
+    let authority = test_f.payer();
+    assert_eq!(astrolend_account_f.load().await.authority, authority);
+
+    assert_eq!(
+        &data[..8],
+        <AstrolendAccount as anchor_lang::Discriminator>::DISCRIMINATOR
+    );
+    assert!(matches_all(
+        &[
+            AstrolendAccount::discriminator_filter(),
+            AstrolendAccount::group_filter(&group),
+            AstrolendAccount::authority_filter(&authority),
+        ],
+        &data
+    ));
+
+    // Filters of another group, authority or account type don't match
+    let other = Pubkey::new_unique();
+    assert!(!AstrolendAccount::group_filter(&other).matches(&data));
+    assert!(!AstrolendAccount::authority_filter(&other).matches(&data));
+    assert!(!Bank::discriminator_filter().matches(&data));
+
+    let group_data = account_data(&test_f, group).await;
+    assert!(!AstrolendAccount::discriminator_filter().matches(&group_data));
+    assert_eq!(
+        &group_data[..8],
+        <AstrolendGroup as anchor_lang::Discriminator>::DISCRIMINATOR
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn bank_filters_match_layout() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let group = test_f.astrolend_group.key;
+
+    for bank_mint in [BankMint::Usdc, BankMint::Sol] {
+        let bank_f = test_f.get_bank(&bank_mint);
+        let data = account_data(&test_f, bank_f.key).await;
+
+        assert_eq!(&data[..8], Bank::discriminator());
+        assert!(matches_all(
+            &[
+                Bank::discriminator_filter(),
+                Bank::group_filter(&group),
+                Bank::mint_filter(&bank_f.mint.key),
+            ],
+            &data
+        ));
+        assert!(!Bank::group_filter(&Pubkey::new_unique()).matches(&data));
+        assert!(!AstrolendAccount::discriminator_filter().matches(&data));
+    }
+
+    // The mint filter tells banks of the same group apart
+    let usdc_data = account_data(&test_f, test_f.get_bank(&BankMint::Usdc).key).await;
+    assert!(!Bank::mint_filter(&test_f.sol_mint.key).matches(&usdc_data));
+
+    // Truncated data never matches
+    let truncated = &usdc_data[..BANK_GROUP_OFFSET + 31];
+    assert!(!Bank::group_filter(&group).matches(truncated));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn astrolend_account_tag_filter() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+    let tag = *b"partner1";
+
+    let tagged_f = test_f.create_astrolend_account_with_tag(tag).await;
+    let untagged_f = test_f.create_astrolend_account().await;
+    assert_eq!(tagged_f.load().await.account_tag, tag);
+    assert_eq!(untagged_f.load().await.account_tag, [0; 8]);
+
+    let tagged_data = account_data(&test_f, tagged_f.key).await;
+    let untagged_data = account_data(&test_f, untagged_f.key).await;
+    assert!(AstrolendAccount::account_tag_filter(&tag).matches(&tagged_data));
+    assert!(!AstrolendAccount::account_tag_filter(&tag).matches(&untagged_data));
+    assert!(AstrolendAccount::account_tag_filter(&[0; 8]).matches(&untagged_data));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn loading_wrong_account_type_is_reported() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let err = test_f
+        .load_and_deserialize::<AstrolendGroup>(&usdc_bank.key)
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains(&usdc_bank.key.to_string()));
+    assert!(err.contains("discriminator"));
+    assert!(err.contains(&format!("{:?}", Bank::discriminator())));
+    assert!(err.contains("AstrolendGroup"));
+
+    let missing = Pubkey::new_unique();
+    let err = test_f
+        .load_and_deserialize::<Bank>(&missing)
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains(&missing.to_string()));
+    assert!(err.contains("not found"));
+
+    assert_eq!(usdc_bank.try_load().await?.mint, usdc_bank.mint.key);
+    assert_eq!(test_f.astrolend_group.try_load().await?.admin, test_f.payer());
+
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use fixed_macro::types::I80F48;
+use fixtures::{assert_custom_error, native, prelude::*};
+use astrolend::{
+    constants::{ASTROLEND_ACCOUNT_VERSION, LIQUIDATION_HISTORY_LEN},
+    errors::AstrolendError,
+    state::{
+        astrolend_account::{AstrolendAccount, LiquidationRecord},
+        astrolend_group::BankConfigOpt,
+    },
+};
+use solana_program_test::tokio;
+
+#[tokio::test]
+async fn legacy_account_records_history_once_migrated() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let liquidator_f = test_f.create_astrolend_account().await;
+    let liquidator_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    liquidator_f
+        .try_bank_deposit(liquidator_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let liquidatee_f = test_f.create_astrolend_account().await;
+    let liquidatee_sol = test_f.sol_mint.create_token_account_and_mint_to(2).await;
+    let liquidatee_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    liquidatee_f
+        .try_bank_deposit(liquidatee_sol.key, sol_bank, 2)
+        .await?;
+    liquidatee_f
+        .try_bank_borrow(liquidatee_usdc.key, usdc_bank, 10)
+        .await?;
+    assert_eq!(liquidatee_f.load().await.version, ASTROLEND_ACCOUNT_VERSION);
+
+    // Accounts created before versioning may hold anything past the fields they knew of
+    let mut legacy = liquidatee_f.load().await;
+    legacy.version = 0;
+    legacy.liquidation_count = 7;
+    legacy.liquidation_history = [LiquidationRecord {
+        timestamp: 42,
+        ..Default::default()
+    }; LIQUIDATION_HISTORY_LEN];
+    legacy.account_tag = [0xff; 8];
+    liquidatee_f.set_account(&legacy).await?;
+
+    sol_bank
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.25).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    // Liquidations go through, the history is left alone
+    liquidator_f
+        .try_liquidate(&liquidatee_f, sol_bank, 0.1, usdc_bank)
+        .await?;
+
+    let liquidatee = liquidatee_f.load().await;
+    assert!(liquidatee.is_legacy());
+    assert_eq!(liquidatee.liquidation_count, 7);
+    assert_eq!(liquidatee.liquidation_history, legacy.liquidation_history);
+    assert!(liquidatee.get_liquidation_history().is_empty());
+
+    let data = test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(liquidatee_f.key)
+        .await?
+        .unwrap()
+        .data;
+    assert!(AstrolendAccount::version_filter(0).matches(&data));
+
+    liquidatee_f.try_migrate().await?;
+
+    let liquidatee = liquidatee_f.load().await;
+    assert_eq!(liquidatee.version, ASTROLEND_ACCOUNT_VERSION);
+    assert_eq!(liquidatee.liquidation_count, 0);
+    assert_eq!(
+        liquidatee.liquidation_history,
+        [LiquidationRecord::default(); LIQUIDATION_HISTORY_LEN]
+    );
+    assert_eq!(liquidatee.account_tag, [0; 8]);
+
+    test_f.advance_time(1).await;
+    liquidator_f
+        .try_liquidate(&liquidatee_f, sol_bank, 0.2, usdc_bank)
+        .await?;
+
+    let history = liquidatee_f.load().await.get_liquidation_history();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].liquidator_account, liquidator_f.key);
+    assert_eq!(history[0].asset_amount, native!(0.2, "SOL", f64));
+
+    let res = liquidatee_f.try_migrate().await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::AccountAlreadyMigrated);
+
+    Ok(())
+}
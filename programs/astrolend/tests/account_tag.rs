@@ -0,0 +1,71 @@
+use astrolend::events::{LendingAccountBorrowEvent, LendingAccountDepositEvent, EVENT_VERSION};
+use fixtures::prelude::*;
+use solana_program::instruction::Instruction;
+use solana_program_test::{tokio, BanksTransactionResultWithMetadata};
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+async fn process_with_metadata(
+    test_f: &TestFixture,
+    ix: Instruction,
+) -> anyhow::Result<BanksTransactionResultWithMetadata> {
+    let mut ctx = test_f.context.borrow_mut();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer],
+        ctx.last_blockhash,
+    );
+
+    Ok(ctx
+        .banks_client
+        .process_transaction_with_metadata(tx)
+        .await?)
+}
+
+#[tokio::test]
+async fn account_tag_in_event_metadata() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let tag = *b"partner1";
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account_with_tag(tag).await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+
+    let ix = borrower_f
+        .make_bank_deposit_ix(borrower_sol.key, sol_bank, 10)
+        .await;
+    let result = process_with_metadata(&test_f, ix).await?;
+    assert!(result.result.is_ok());
+
+    let events = parse_events::<LendingAccountDepositEvent>(&result);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].header.astrolend_account, borrower_f.key);
+    assert_eq!(events[0].meta.version, EVENT_VERSION);
+    assert_eq!(events[0].meta.account_tag, tag);
+
+    let ix = borrower_f
+        .make_bank_borrow_ix(borrower_usdc.key, usdc_bank, 10)
+        .await;
+    let result = process_with_metadata(&test_f, ix).await?;
+    assert!(result.result.is_ok());
+
+    let events = parse_events::<LendingAccountBorrowEvent>(&result);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].meta.account_tag, tag);
+
+    // Untagged accounts carry the zero tag
+    assert_eq!(lender_f.load().await.account_tag, [0; 8]);
+
+    Ok(())
+}
@@ -0,0 +1,260 @@
+use anchor_lang::{prelude::Pubkey, AnchorDeserialize, Discriminator};
+use astrolend::{
+    events::{GroupEventHeader, LendingPoolBankAccrueInterestEvent, EVENT_VERSION},
+    instruction::{LendingAccountDeposit, LendingPoolAccrueBankInterest},
+    state::{astrolend_account::DISABLED_FLAG, astrolend_group::BankVaultType},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::prelude::*;
+use solana_program::instruction::Instruction;
+use solana_program_test::{tokio, BanksTransactionResultWithMetadata};
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+async fn process(
+    test_f: &TestFixture,
+    ixs: &[Instruction],
+) -> BanksTransactionResultWithMetadata {
+    let mut ctx = test_f.context.borrow_mut();
+    let tx = Transaction::new_signed_with_payer(
+        ixs,
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer],
+        ctx.last_blockhash,
+    );
+    let result = ctx
+        .banks_client
+        .process_transaction_with_metadata(tx)
+        .await
+        .unwrap();
+    assert!(result.result.is_ok());
+
+    result
+}
+
+/// Only the first of several instructions touching a bank in the same second accrues interest
+#[tokio::test]
+async fn repeated_accrual_in_same_second_is_skipped() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 500)
+        .await?;
+
+    // Some utilization, so the accrual runs the full interest math
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 100)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 200)
+        .await?;
+
+    test_f.advance_time(3600).await;
+    let ix = lender_f
+        .make_bank_deposit_ix(lender_usdc.key, usdc_bank, 10)
+        .await;
+    let result = process(&test_f, &[ix]).await;
+    assert_eq!(
+        parse_events::<LendingPoolBankAccrueInterestEvent>(&result).len(),
+        1
+    );
+    let single_deposit_cu = result.metadata.unwrap().compute_units_consumed;
+
+    test_f.advance_time(3600).await;
+    let mut ixs = vec![];
+    for _ in 0..4 {
+        ixs.push(
+            lender_f
+                .make_bank_deposit_ix(lender_usdc.key, usdc_bank, 10)
+                .await,
+        );
+    }
+    let result = process(&test_f, &ixs).await;
+    assert_eq!(
+        parse_events::<LendingPoolBankAccrueInterestEvent>(&result).len(),
+        1
+    );
+    let four_deposits_cu = result.metadata.unwrap().compute_units_consumed;
+
+    // Three of the four deposits skip the accrual math and the event
+    assert!(four_deposits_cu < 4 * single_deposit_cu);
+
+    let bank = usdc_bank.load().await;
+    assert_eq!(bank.last_update, test_f.get_clock().await.unix_timestamp);
+
+    Ok(())
+}
+
+/// Layout of `LendingPoolBankAccrueInterestEvent` before events carried their metadata
+#[derive(AnchorDeserialize)]
+struct LegacyAccrueInterestEvent {
+    header: GroupEventHeader,
+    bank: Pubkey,
+    mint: Pubkey,
+    delta: u64,
+    fees_collected: f64,
+    insurance_collected: f64,
+}
+
+#[tokio::test]
+async fn accrual_event_carries_the_accruing_instruction() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 500)
+        .await?;
+
+    test_f.advance_time(3600).await;
+    let ix = lender_f
+        .make_bank_deposit_ix(lender_usdc.key, usdc_bank, 10)
+        .await;
+    let result = process(&test_f, &[ix]).await;
+    let events = parse_events::<LendingPoolBankAccrueInterestEvent>(&result);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].meta.version, EVENT_VERSION);
+    assert_eq!(
+        events[0].meta.instruction,
+        LendingAccountDeposit::DISCRIMINATOR
+    );
+
+    test_f.advance_time(3600).await;
+    let result = process(
+        &test_f,
+        &[test_f.astrolend_group.make_accrue_interest_ix(usdc_bank)],
+    )
+    .await;
+    let events = parse_events::<LendingPoolBankAccrueInterestEvent>(&result);
+    assert_eq!(events.len(), 1);
+    assert_eq!(
+        events[0].meta.instruction,
+        LendingPoolAccrueBankInterest::DISCRIMINATOR
+    );
+
+    // Parsers of the layout without metadata still decode the event, ignoring the trailing bytes
+    let legacy = result
+        .metadata
+        .as_ref()
+        .unwrap()
+        .log_messages
+        .iter()
+        .filter_map(|log| log.strip_prefix("Program data: "))
+        .map(|data| STANDARD.decode(data).unwrap())
+        .find(|bytes| bytes[..8] == LendingPoolBankAccrueInterestEvent::DISCRIMINATOR)
+        .map(|bytes| LegacyAccrueInterestEvent::deserialize(&mut &bytes[8..]).unwrap())
+        .unwrap();
+    assert_eq!(legacy.header.astrolend_group, test_f.astrolend_group.key);
+    assert_eq!(legacy.bank, usdc_bank.key);
+    assert_eq!(legacy.mint, events[0].mint);
+    assert_eq!(legacy.delta, 3600);
+    assert_eq!(legacy.fees_collected, events[0].fees_collected);
+    assert_eq!(legacy.insurance_collected, events[0].insurance_collected);
+
+    Ok(())
+}
+
+/// A bank whose deposits were mostly socialized away keeps accruing on its liabilities, and
+/// can still be repaid and go through bankruptcy
+#[tokio::test]
+async fn accrual_in_deficit() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let repaying_borrower_f = test_f.create_astrolend_account().await;
+    let repaying_borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let repaying_borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(10).await;
+    repaying_borrower_f
+        .try_bank_deposit(repaying_borrower_sol.key, sol_bank, 100)
+        .await?;
+    repaying_borrower_f
+        .try_bank_borrow(repaying_borrower_usdc.key, usdc_bank, 200)
+        .await?;
+
+    let bankrupt_borrower_f = test_f.create_astrolend_account().await;
+    let bankrupt_borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(1).await;
+    let bankrupt_borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    bankrupt_borrower_f
+        .try_bank_deposit(bankrupt_borrower_sol.key, sol_bank, 1)
+        .await?;
+    bankrupt_borrower_f
+        .try_bank_borrow(bankrupt_borrower_usdc.key, usdc_bank, 8)
+        .await?;
+
+    // 1 USDC of deposits left against 208 USDC borrowed
+    usdc_bank.set_asset_share_value(I80F48!(0.001)).await;
+
+    test_f.advance_time(3600).await;
+    let result = process(
+        &test_f,
+        &[test_f.astrolend_group.make_accrue_interest_ix(usdc_bank)],
+    )
+    .await;
+    let events = parse_events::<LendingPoolBankAccrueInterestEvent>(&result);
+    assert_eq!(events.len(), 1);
+    assert!(events[0].deficit);
+    assert!(events[0].fees_collected > 0.);
+
+    let bank = usdc_bank.load().await;
+    assert!(I80F48::from(bank.liability_share_value) > I80F48::ONE);
+    assert!(I80F48::from(bank.asset_share_value) > I80F48!(0.001));
+
+    repaying_borrower_f
+        .try_bank_repay(repaying_borrower_usdc.key, usdc_bank, 0, Some(true))
+        .await?;
+    assert!(repaying_borrower_f
+        .load()
+        .await
+        .lending_account
+        .get_balance(&usdc_bank.key)
+        .is_none());
+
+    // The insurance vault covers the bad debt
+    test_f
+        .usdc_mint
+        .clone()
+        .mint_to(&usdc_bank.get_vault(BankVaultType::Insurance).0, 100)
+        .await;
+    test_f.context.borrow_mut().set_account(
+        &PYTH_SOL_FEED,
+        &create_pyth_legacy_oracle_account(
+            test_f.sol_mint.key,
+            0.01,
+            SOL_MINT_DECIMALS.into(),
+            None,
+        )
+        .into(),
+    );
+    test_f
+        .astrolend_group
+        .try_handle_bankruptcy(usdc_bank, &bankrupt_borrower_f)
+        .await?;
+    assert!(bankrupt_borrower_f.load().await.get_flag(DISABLED_FLAG));
+
+    Ok(())
+}
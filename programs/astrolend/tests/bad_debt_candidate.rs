@@ -0,0 +1,214 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{
+    assert_custom_error, assert_eq_noise, astrolend_account::AstrolendAccountFixture, native,
+    prelude::*,
+};
+use astrolend::{
+    errors::AstrolendError,
+    state::{
+        astrolend_account::{BAD_DEBT_CANDIDATE_FLAG, DISABLED_FLAG},
+        astrolend_group::{BankVaultType, GroupConfig},
+    },
+};
+use solana_program_test::tokio;
+
+/// Borrows `borrow_amount` USDC against `sol_amount` SOL at $10
+async fn open_usdc_borrow(
+    test_f: &TestFixture,
+    sol_amount: f64,
+    borrow_amount: f64,
+) -> anyhow::Result<AstrolendAccountFixture> {
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(sol_amount)
+        .await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, test_f.get_bank(&BankMint::Sol), sol_amount)
+        .await?;
+    borrower_f
+        .try_bank_borrow(
+            borrower_usdc.key,
+            test_f.get_bank(&BankMint::Usdc),
+            borrow_amount,
+        )
+        .await?;
+
+    Ok(borrower_f)
+}
+
+async fn setup() -> anyhow::Result<TestFixture> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            min_liquidation_value_usd: Some(5),
+            ..Default::default()
+        })
+        .await?;
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, test_f.get_bank(&BankMint::Usdc), 1_000)
+        .await?;
+
+    Ok(test_f)
+}
+
+fn set_sol_price(test_f: &TestFixture, price: f64) {
+    test_f.context.borrow_mut().set_account(
+        &PYTH_SOL_FEED,
+        &create_pyth_legacy_oracle_account(
+            test_f.sol_mint.key,
+            price,
+            SOL_MINT_DECIMALS.into(),
+            None,
+        )
+        .into(),
+    );
+}
+
+#[tokio::test]
+async fn profitable_underwater_account_not_flagged() -> anyhow::Result<()> {
+    let test_f = setup().await?;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let borrower_f = open_usdc_borrow(&test_f, 10., 50.).await?;
+
+    // Healthy accounts can't be flagged
+    let res = borrower_f.try_flag_unprofitable().await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::AccountNotBadDebtCandidate);
+
+    // $20 of SOL against $50 of USDC is underwater, but worth liquidating
+    set_sol_price(&test_f, 2.);
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        ctx.last_blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+    }
+
+    let res = borrower_f.try_flag_unprofitable().await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::AccountNotBadDebtCandidate);
+    assert!(!borrower_f.load().await.get_flag(BAD_DEBT_CANDIDATE_FLAG));
+
+    let res = test_f
+        .astrolend_group
+        .try_handle_bankruptcy(usdc_bank, &borrower_f)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::AccountNotBankrupt);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn dust_underwater_account_flagged_and_bankrupted() -> anyhow::Result<()> {
+    let test_f = setup().await?;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let borrower_f = open_usdc_borrow(&test_f, 1., 8.).await?;
+
+    // $2 of SOL against $8 of USDC, below the $5 minimum liquidation value
+    set_sol_price(&test_f, 2.);
+
+    // The dust is above the regular bankruptcy threshold
+    let res = test_f
+        .astrolend_group
+        .try_handle_bankruptcy(usdc_bank, &borrower_f)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::AccountNotBankrupt);
+
+    borrower_f.try_flag_unprofitable().await?;
+    assert!(borrower_f.load().await.get_flag(BAD_DEBT_CANDIDATE_FLAG));
+
+    let insurance_fees_before =
+        I80F48::from(sol_bank.load().await.collected_insurance_fees_outstanding);
+
+    test_f
+        .astrolend_group
+        .try_handle_bankruptcy_with_nonce(usdc_bank, &borrower_f, 101)
+        .await?;
+
+    // The SOL dust is swept to the insurance fees of the SOL bank
+    let borrower = borrower_f.load().await;
+    assert!(borrower.get_flag(DISABLED_FLAG));
+    assert!(borrower.lending_account.get_balance(&sol_bank.key).is_none());
+
+    let sol_bank_state = sol_bank.load().await;
+    assert_eq_noise!(
+        I80F48::from(sol_bank_state.collected_insurance_fees_outstanding) - insurance_fees_before,
+        I80F48::from_num(native!(1, "SOL")),
+        I80F48!(1)
+    );
+    assert_eq_noise!(
+        sol_bank_state.get_asset_amount(sol_bank_state.total_asset_shares.into())?,
+        I80F48::ZERO,
+        I80F48!(1)
+    );
+
+    // The USDC debt is settled
+    let usdc_bank_state = usdc_bank.load().await;
+    assert_eq_noise!(
+        I80F48::from(usdc_bank_state.cumulative_socialized_loss),
+        I80F48::from_num(native!(8, "USDC")),
+        I80F48!(100)
+    );
+
+    // Fee collection moves the dust from the liquidity vault to the insurance vault
+    let insurance_vault = sol_bank.get_vault(BankVaultType::Insurance).0;
+    let insurance_vault_before = balance_of(test_f.context.clone(), insurance_vault).await;
+    test_f.astrolend_group.try_collect_fees(sol_bank).await?;
+    let insurance_vault_after = balance_of(test_f.context.clone(), insurance_vault).await;
+    assert!(insurance_vault_after - insurance_vault_before >= native!(1, "SOL") - 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn recovered_account_flag_cleared() -> anyhow::Result<()> {
+    let test_f = setup().await?;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let borrower_f = open_usdc_borrow(&test_f, 1., 8.).await?;
+
+    set_sol_price(&test_f, 2.);
+    borrower_f.try_flag_unprofitable().await?;
+    assert!(borrower_f.load().await.get_flag(BAD_DEBT_CANDIDATE_FLAG));
+
+    // Back above the maintenance requirement, the flag is cleared on the next call
+    set_sol_price(&test_f, 10.);
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        ctx.last_blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+    }
+    borrower_f.try_flag_unprofitable().await?;
+    assert!(!borrower_f.load().await.get_flag(BAD_DEBT_CANDIDATE_FLAG));
+
+    // Without the flag the account is a regular healthy account again
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        ctx.last_blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+    }
+    let res = borrower_f.try_flag_unprofitable().await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::AccountNotBadDebtCandidate);
+
+    set_sol_price(&test_f, 2.);
+    let res = test_f
+        .astrolend_group
+        .try_handle_bankruptcy(usdc_bank, &borrower_f)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::AccountNotBankrupt);
+
+    Ok(())
+}
@@ -0,0 +1,534 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{astrolend_account::AstrolendAccountFixture, bank::BankFixture, native, prelude::*};
+use astrolend::state::{
+    astrolend_account::AstrolendAccount,
+    astrolend_group::{Bank, BankConfigOpt, BankVaultType, InterestRateConfigOpt},
+};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+
+/// Every field of the bank and account a balance change could touch, plus both token balances
+struct Snapshot {
+    bank: Bank,
+    account: AstrolendAccount,
+    user_tokens: u64,
+    vault_tokens: u64,
+}
+
+async fn snapshot(
+    bank_f: &BankFixture,
+    account_f: &AstrolendAccountFixture,
+    user_token_account: &TokenAccountFixture,
+) -> Snapshot {
+    Snapshot {
+        bank: bank_f.load().await,
+        account: account_f.load().await,
+        user_tokens: user_token_account.balance().await,
+        vault_tokens: bank_f
+            .get_vault_token_account(BankVaultType::Liquidity)
+            .await
+            .balance()
+            .await,
+    }
+}
+
+/// Expected effect of a balance change, no time passes so the share values don't move
+#[derive(Default)]
+struct Effect {
+    asset_shares: I80F48,
+    liability_shares: I80F48,
+    group_fees: I80F48,
+    /// Rounding dust of closing balances, in native units
+    insurance_fees: I80F48,
+    /// Tokens moved from the user into the liquidity vault, negative for outflows
+    vault_inflow: i64,
+}
+
+/// Shares of the balance of `bank_pk`, zero without one
+fn balance_shares(account: &AstrolendAccount, bank_pk: &Pubkey) -> (I80F48, I80F48) {
+    account
+        .lending_account
+        .balances
+        .iter()
+        .find(|balance| balance.active && balance.bank_pk == *bank_pk)
+        .map(|balance| (balance.asset_shares.into(), balance.liability_shares.into()))
+        .unwrap_or((I80F48::ZERO, I80F48::ZERO))
+}
+
+/// Everything in the account but the balance of `bank_pk` is left untouched
+fn assert_rest_of_account_untouched(before: &Snapshot, after: &Snapshot, bank_pk: &Pubkey) {
+    let position = |account: &AstrolendAccount| {
+        account
+            .lending_account
+            .balances
+            .iter()
+            .position(|balance| balance.active && balance.bank_pk == *bank_pk)
+    };
+    let index = position(&before.account)
+        .or_else(|| position(&after.account))
+        .unwrap();
+
+    let mut expected_account = before.account;
+    expected_account.lending_account.balances[index] =
+        after.account.lending_account.balances[index];
+    assert_eq!(expected_account, after.account);
+}
+
+/// Bank fields maintained next to the ledger by every balance change: the reentrancy guard and
+/// the cached vault balance, and for borrows the price cache and the borrow value of the slot.
+fn expect_bookkeeping(expected_bank: &mut Bank, before: &Snapshot, after: &Snapshot, borrow: bool) {
+    assert_eq!(after.bank.reentrancy_guard, 0);
+    expected_bank.reentrancy_guard_slot = after.bank.reentrancy_guard_slot;
+
+    expected_bank.liquidity_vault_balance_cached =
+        (before.bank.liquidity_vault_balance_cached as i64 + after.vault_tokens as i64
+            - before.vault_tokens as i64) as u64;
+
+    if borrow {
+        expected_bank.cached_price = after.bank.cached_price;
+        expected_bank.cached_conf = after.bank.cached_conf;
+        expected_bank.cached_price_ts = after.bank.cached_price_ts;
+        expected_bank.slot_borrow_value = after.bank.slot_borrow_value;
+        expected_bank.slot_borrow_value_slot = after.bank.slot_borrow_value_slot;
+    }
+}
+
+/// Apply `effect` to `before` and compare the result against `after` field by field, any field
+/// the operation doesn't own must be left untouched.
+fn assert_effect(before: &Snapshot, after: &Snapshot, bank_pk: &Pubkey, effect: Effect) {
+    let mut expected_bank = before.bank;
+    expected_bank.total_asset_shares =
+        (I80F48::from(before.bank.total_asset_shares) + effect.asset_shares).into();
+    expected_bank.total_liability_shares =
+        (I80F48::from(before.bank.total_liability_shares) + effect.liability_shares).into();
+    expected_bank.collected_group_fees_outstanding =
+        (I80F48::from(before.bank.collected_group_fees_outstanding) + effect.group_fees).into();
+    expected_bank.lifetime_group_fees =
+        (I80F48::from(before.bank.lifetime_group_fees) + effect.group_fees).into();
+    expected_bank.collected_insurance_fees_outstanding =
+        (I80F48::from(before.bank.collected_insurance_fees_outstanding) + effect.insurance_fees)
+            .into();
+    expected_bank.lifetime_insurance_fees =
+        (I80F48::from(before.bank.lifetime_insurance_fees) + effect.insurance_fees).into();
+    expect_bookkeeping(
+        &mut expected_bank,
+        before,
+        after,
+        effect.liability_shares > I80F48::ZERO,
+    );
+    assert_eq!(expected_bank, after.bank);
+
+    let (assets_before, liabilities_before) = balance_shares(&before.account, bank_pk);
+    let (assets_after, liabilities_after) = balance_shares(&after.account, bank_pk);
+    assert_eq!(assets_after, assets_before + effect.asset_shares);
+    assert_eq!(liabilities_after, liabilities_before + effect.liability_shares);
+    assert_rest_of_account_untouched(before, after, bank_pk);
+
+    assert_eq!(
+        after.vault_tokens as i64 - before.vault_tokens as i64,
+        effect.vault_inflow
+    );
+    assert_eq!(
+        before.user_tokens as i64 - after.user_tokens as i64,
+        effect.vault_inflow
+    );
+}
+
+/// Balance change in UI amounts
+#[derive(Copy, Clone, Debug)]
+enum Op {
+    Deposit(u32),
+    Withdraw(u32),
+    WithdrawAll,
+    Borrow(u32),
+    Repay(u32),
+    RepayAll,
+}
+
+/// Run `op` through the program and compare the bank, account and token balances against
+/// `effect`
+async fn assert_op_effect(
+    bank_f: &BankFixture,
+    account_f: &AstrolendAccountFixture,
+    token_account: &TokenAccountFixture,
+    op: Op,
+    effect: Effect,
+) -> anyhow::Result<()> {
+    let before = snapshot(bank_f, account_f, token_account).await;
+    match op {
+        Op::Deposit(ui_amount) => {
+            account_f
+                .try_bank_deposit(token_account.key, bank_f, ui_amount)
+                .await?
+        }
+        Op::Withdraw(ui_amount) => {
+            account_f
+                .try_bank_withdraw(token_account.key, bank_f, ui_amount, None)
+                .await?
+        }
+        Op::WithdrawAll => {
+            account_f
+                .try_bank_withdraw(token_account.key, bank_f, 0, Some(true))
+                .await?
+        }
+        Op::Borrow(ui_amount) => {
+            account_f
+                .try_bank_borrow(token_account.key, bank_f, ui_amount)
+                .await?
+        }
+        Op::Repay(ui_amount) => {
+            account_f
+                .try_bank_repay(token_account.key, bank_f, ui_amount, None)
+                .await?
+        }
+        Op::RepayAll => {
+            account_f
+                .try_bank_repay(token_account.key, bank_f, 0, Some(true))
+                .await?
+        }
+    }
+    let after = snapshot(bank_f, account_f, token_account).await;
+
+    assert_effect(&before, &after, &bank_f.key, effect);
+
+    Ok(())
+}
+
+fn native_usdc(amount: u64) -> I80F48 {
+    I80F48::from_num(native!(amount, "USDC"))
+}
+
+fn native_sol(amount: u64) -> I80F48 {
+    I80F48::from_num(native!(amount, "SOL"))
+}
+
+/// Deposit, borrow, repay and withdraw, exact and closing, each only change what they own
+#[tokio::test]
+async fn balance_changes_only_touch_owned_fields() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    usdc_bank
+        .update_config(BankConfigOpt {
+            interest_rate_config: Some(InterestRateConfigOpt {
+                protocol_origination_fee: Some(I80F48!(0.01).into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .await?;
+
+    // Deposit opening a balance
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let before = snapshot(usdc_bank, &lender_f, &lender_usdc).await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+    let after = snapshot(usdc_bank, &lender_f, &lender_usdc).await;
+    assert_effect(
+        &before,
+        &after,
+        &usdc_bank.key,
+        Effect {
+            asset_shares: native_usdc(1_000),
+            vault_inflow: native!(1_000, "USDC") as i64,
+            ..Default::default()
+        },
+    );
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(5).await;
+    let before = snapshot(sol_bank, &borrower_f, &borrower_sol).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 100)
+        .await?;
+    let after = snapshot(sol_bank, &borrower_f, &borrower_sol).await;
+    assert_effect(
+        &before,
+        &after,
+        &sol_bank.key,
+        Effect {
+            asset_shares: native_sol(100),
+            vault_inflow: native!(100, "SOL") as i64,
+            ..Default::default()
+        },
+    );
+
+    // Borrow, the 1% origination fee is added to the liability and collected as group fees
+    let before = snapshot(usdc_bank, &borrower_f, &borrower_usdc).await;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 100)
+        .await?;
+    let after = snapshot(usdc_bank, &borrower_f, &borrower_usdc).await;
+    assert_effect(
+        &before,
+        &after,
+        &usdc_bank.key,
+        Effect {
+            liability_shares: native_usdc(101),
+            group_fees: native_usdc(1),
+            vault_inflow: -(native!(100, "USDC") as i64),
+            ..Default::default()
+        },
+    );
+
+    let before = snapshot(usdc_bank, &borrower_f, &borrower_usdc).await;
+    borrower_f
+        .try_bank_repay(borrower_usdc.key, usdc_bank, 40, None)
+        .await?;
+    let after = snapshot(usdc_bank, &borrower_f, &borrower_usdc).await;
+    assert_effect(
+        &before,
+        &after,
+        &usdc_bank.key,
+        Effect {
+            liability_shares: -native_usdc(40),
+            vault_inflow: native!(40, "USDC") as i64,
+            ..Default::default()
+        },
+    );
+
+    // Withdraw with a liability outstanding goes through the health check
+    let before = snapshot(sol_bank, &borrower_f, &borrower_sol).await;
+    borrower_f
+        .try_bank_withdraw(borrower_sol.key, sol_bank, 40, None)
+        .await?;
+    let after = snapshot(sol_bank, &borrower_f, &borrower_sol).await;
+    assert_effect(
+        &before,
+        &after,
+        &sol_bank.key,
+        Effect {
+            asset_shares: -native_sol(40),
+            vault_inflow: -(native!(40, "SOL") as i64),
+            ..Default::default()
+        },
+    );
+
+    // Closing both balances
+    let before = snapshot(usdc_bank, &borrower_f, &borrower_usdc).await;
+    borrower_f
+        .try_bank_repay(borrower_usdc.key, usdc_bank, 0, Some(true))
+        .await?;
+    let after = snapshot(usdc_bank, &borrower_f, &borrower_usdc).await;
+    assert_effect(
+        &before,
+        &after,
+        &usdc_bank.key,
+        Effect {
+            liability_shares: -native_usdc(61),
+            vault_inflow: native!(61, "USDC") as i64,
+            ..Default::default()
+        },
+    );
+    assert!(after
+        .account
+        .lending_account
+        .get_balance(&usdc_bank.key)
+        .is_none());
+
+    let before = snapshot(sol_bank, &borrower_f, &borrower_sol).await;
+    borrower_f
+        .try_bank_withdraw(borrower_sol.key, sol_bank, 0, Some(true))
+        .await?;
+    let after = snapshot(sol_bank, &borrower_f, &borrower_sol).await;
+    assert_effect(
+        &before,
+        &after,
+        &sol_bank.key,
+        Effect {
+            asset_shares: -native_sol(60),
+            vault_inflow: -(native!(60, "SOL") as i64),
+            ..Default::default()
+        },
+    );
+    assert!(after
+        .account
+        .lending_account
+        .get_balance(&sol_bank.key)
+        .is_none());
+
+    Ok(())
+}
+
+/// Differential test of the shared core against the handlers it replaced. The effects below are
+/// worked out by hand from the baseline deposit, withdraw, borrow and repay handlers, with the
+/// share values pinned off 1 and the clock pinned to their last update so nothing accrues. The
+/// baseline charged no origination fee, the bank keeps the default of none.
+#[tokio::test]
+async fn balance_changes_match_baseline_handlers() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let clock = test_f.get_clock().await;
+    let now = clock.unix_timestamp;
+    test_f.context.borrow_mut().set_sysvar(&clock);
+    test_f.set_pyth_oracle_timestamp(PYTH_USDC_FEED, now).await;
+    test_f.set_pyth_oracle_timestamp(PYTH_SOL_FEED, now).await;
+    usdc_bank
+        .set_share_values(I80F48!(1.25), I80F48!(1.5), now)
+        .await;
+    sol_bank.set_share_values(I80F48!(2), I80F48!(2), now).await;
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_010)
+        .await;
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(1).await;
+
+    // Exact amounts convert to shares at the share values, 1 USDC is 0.8 asset shares and
+    // 0.666.. liability shares, the amounts are picked to land on whole shares
+    for (bank_f, account_f, token_account, op, effect) in [
+        (
+            usdc_bank,
+            &lender_f,
+            &lender_usdc,
+            Op::Deposit(1_000),
+            Effect {
+                asset_shares: native_usdc(800),
+                vault_inflow: 1_000_000_000,
+                ..Default::default()
+            },
+        ),
+        (
+            sol_bank,
+            &borrower_f,
+            &borrower_sol,
+            Op::Deposit(100),
+            Effect {
+                asset_shares: native_sol(50),
+                vault_inflow: 100_000_000_000,
+                ..Default::default()
+            },
+        ),
+        (
+            usdc_bank,
+            &borrower_f,
+            &borrower_usdc,
+            Op::Borrow(150),
+            Effect {
+                liability_shares: native_usdc(100),
+                vault_inflow: -150_000_000,
+                ..Default::default()
+            },
+        ),
+        (
+            usdc_bank,
+            &borrower_f,
+            &borrower_usdc,
+            Op::Borrow(30),
+            Effect {
+                liability_shares: native_usdc(20),
+                vault_inflow: -30_000_000,
+                ..Default::default()
+            },
+        ),
+        (
+            usdc_bank,
+            &borrower_f,
+            &borrower_usdc,
+            Op::Repay(45),
+            Effect {
+                liability_shares: -native_usdc(30),
+                vault_inflow: 45_000_000,
+                ..Default::default()
+            },
+        ),
+        (
+            usdc_bank,
+            &lender_f,
+            &lender_usdc,
+            Op::Deposit(10),
+            Effect {
+                asset_shares: native_usdc(8),
+                vault_inflow: 10_000_000,
+                ..Default::default()
+            },
+        ),
+        (
+            usdc_bank,
+            &lender_f,
+            &lender_usdc,
+            Op::Withdraw(10),
+            Effect {
+                asset_shares: -native_usdc(8),
+                vault_inflow: -10_000_000,
+                ..Default::default()
+            },
+        ),
+        (
+            sol_bank,
+            &borrower_f,
+            &borrower_sol,
+            Op::Withdraw(40),
+            Effect {
+                asset_shares: -native_sol(20),
+                vault_inflow: -40_000_000_000,
+                ..Default::default()
+            },
+        ),
+    ] {
+        assert_op_effect(bank_f, account_f, token_account, op, effect).await?;
+    }
+
+    // Interest accrued in the same second moves the USDC share values by 2^-12 and 2^-8, the
+    // 800 and 90 USDC of shares left are then worth 1_000.1953125 and 135.3515625 USDC
+    usdc_bank
+        .set_share_values(I80F48!(1.250244140625), I80F48!(1.50390625), now)
+        .await;
+
+    // Closing a balance floors the withdrawal and ceils the repayment, the half native unit of
+    // difference goes to the insurance fees
+    for (bank_f, account_f, token_account, op, effect) in [
+        (
+            usdc_bank,
+            &borrower_f,
+            &borrower_usdc,
+            Op::RepayAll,
+            Effect {
+                liability_shares: -native_usdc(90),
+                insurance_fees: I80F48!(0.5),
+                vault_inflow: 135_351_563,
+                ..Default::default()
+            },
+        ),
+        (
+            sol_bank,
+            &borrower_f,
+            &borrower_sol,
+            Op::WithdrawAll,
+            Effect {
+                asset_shares: -native_sol(30),
+                vault_inflow: -60_000_000_000,
+                ..Default::default()
+            },
+        ),
+        (
+            usdc_bank,
+            &lender_f,
+            &lender_usdc,
+            Op::WithdrawAll,
+            Effect {
+                asset_shares: -native_usdc(800),
+                insurance_fees: I80F48!(0.5),
+                vault_inflow: -1_000_195_312,
+                ..Default::default()
+            },
+        ),
+    ] {
+        assert_op_effect(bank_f, account_f, token_account, op, effect).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,214 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{
+    assert_custom_error, assert_eq_noise, astrolend_account::AstrolendAccountFixture,
+    bank::BankFixture, native, prelude::*,
+};
+use astrolend::errors::AstrolendError;
+use solana_program_test::tokio;
+
+async fn borrower_with_sol_collateral(
+    test_f: &TestFixture,
+    usdc_bank: &BankFixture,
+    sol_bank: &BankFixture,
+) -> anyhow::Result<AstrolendAccountFixture> {
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 10)
+        .await?;
+
+    Ok(borrower_f)
+}
+
+#[tokio::test]
+async fn settlement_price_overrides_oracle() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let borrower_f = borrower_with_sol_collateral(&test_f, usdc_bank, sol_bank).await?;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+
+    // Settle SOL at $5
+    sol_bank.try_set_settlement_price(500, -2).await?;
+
+    let bank = sol_bank.load().await;
+    assert!(bank.is_in_settlement());
+    assert_eq_noise!(
+        I80F48::from(bank.settlement_price),
+        I80F48!(5),
+        I80F48!(0.0001)
+    );
+    assert_eq_noise!(sol_bank.get_price().await, 5.0, 0.0001);
+
+    // Whatever the oracle reports is ignored
+    test_f.context.borrow_mut().set_account(
+        &PYTH_SOL_FEED,
+        &create_pyth_legacy_oracle_account(
+            test_f.sol_mint.key,
+            1_000.0,
+            SOL_MINT_DECIMALS.into(),
+            None,
+        )
+        .into(),
+    );
+    assert_eq_noise!(sol_bank.get_price().await, 5.0, 0.0001);
+
+    // 10 SOL are worth $50 at the settlement price
+    let res = borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 60)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::RiskEngineInitRejected);
+
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 40)
+        .await?;
+
+    // Setting a new price moves the bank to it
+    sol_bank.try_set_settlement_price(3, 0).await?;
+    assert_eq_noise!(sol_bank.get_price().await, 3.0, 0.0001);
+
+    // A zero price is never a valid settlement
+    let res = sol_bank.try_set_settlement_price(0, 0).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidConfig);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn settlement_mode_only_allows_winding_down() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let sol_lender_f = test_f.create_astrolend_account().await;
+    let sol_lender_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    sol_lender_f
+        .try_bank_deposit(sol_lender_sol.key, sol_bank, 50)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    borrower_f
+        .try_bank_deposit(borrower_usdc.key, usdc_bank, 1_000)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_sol.key, sol_bank, 5)
+        .await?;
+
+    sol_bank.try_set_settlement_price(10, 0).await?;
+
+    let res = sol_lender_f
+        .try_bank_deposit(sol_lender_sol.key, sol_bank, 10)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::BankInSettlement);
+
+    let res = borrower_f
+        .try_bank_borrow(borrower_sol.key, sol_bank, 1)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::BankInSettlement);
+
+    // Positions can still be closed
+    borrower_f
+        .try_bank_repay(borrower_sol.key, sol_bank, 0, Some(true))
+        .await?;
+    sol_lender_f
+        .try_bank_withdraw(sol_lender_sol.key, sol_bank, 0, Some(true))
+        .await?;
+
+    // Other banks of the group are untouched
+    borrower_f
+        .try_bank_withdraw(borrower_usdc.key, usdc_bank, 100, None)
+        .await?;
+    borrower_f
+        .try_bank_deposit(borrower_usdc.key, usdc_bank, 100)
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn liquidation_uses_settlement_price() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let borrower_f = borrower_with_sol_collateral(&test_f, usdc_bank, sol_bank).await?;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 40)
+        .await?;
+
+    let liquidator_f = test_f.create_astrolend_account().await;
+    let liquidator_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    liquidator_f
+        .try_bank_deposit(liquidator_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    // $50 of collateral still covers the $40 debt
+    sol_bank.try_set_settlement_price(5, 0).await?;
+    let res = liquidator_f
+        .try_liquidate(&borrower_f, sol_bank, 1, usdc_bank)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::IllegalLiquidation);
+
+    // $30 doesn't
+    sol_bank.try_set_settlement_price(3, 0).await?;
+    liquidator_f
+        .try_liquidate(&borrower_f, sol_bank, 1, usdc_bank)
+        .await?;
+
+    let seized = liquidator_f.load_positions(&[sol_bank]).await[0].asset_amount;
+    assert_eq_noise!(seized, I80F48::from(native!(1, "SOL")), I80F48!(1));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn clear_settlement_price_returns_to_oracle() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let res = sol_bank.try_clear_settlement_price().await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidConfig);
+
+    sol_bank.try_set_settlement_price(5, 0).await?;
+    sol_bank.try_clear_settlement_price().await?;
+
+    let bank = sol_bank.load().await;
+    assert!(!bank.is_in_settlement());
+    assert_eq!(I80F48::from(bank.settlement_price), I80F48::ZERO);
+    assert_eq_noise!(sol_bank.get_price().await, 10.0, 0.0001);
+
+    // Deposits are open again
+    let depositor_f = test_f.create_astrolend_account().await;
+    let depositor_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    depositor_f
+        .try_bank_deposit(depositor_sol.key, sol_bank, 10)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use fixed::types::I80F48;
+use fixtures::{assert_custom_error, assert_eq_noise, native, prelude::*};
+use astrolend::errors::AstrolendError;
+use solana_program_test::tokio;
+
+#[tokio::test]
+async fn withdraw_frozen_in_bankruptcy_slot() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    // Borrows 8 USDC against 1 SOL, then SOL drops to $0.01
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(1).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 1)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 8)
+        .await?;
+
+    test_f.context.borrow_mut().set_account(
+        &PYTH_SOL_FEED,
+        &create_pyth_legacy_oracle_account(
+            test_f.sol_mint.key,
+            0.01,
+            SOL_MINT_DECIMALS.into(),
+            None,
+        )
+        .into(),
+    );
+
+    test_f
+        .astrolend_group
+        .try_handle_bankruptcy(usdc_bank, &borrower_f)
+        .await?;
+
+    let bank = usdc_bank.load().await;
+    assert_eq!(bank.last_bankruptcy_slot, test_f.get_clock().await.slot);
+    assert!(I80F48::from(bank.asset_share_value) < I80F48::ONE);
+
+    // No depositor exits ahead of the loss in the bankruptcy slot
+    let res = lender_f
+        .try_bank_withdraw(lender_usdc.key, usdc_bank, 0, Some(true))
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::BankruptcyFreeze);
+
+    // The 8 USDC of bad debt are socialized, the lender takes the whole loss
+    test_f.advance_slot().await;
+    lender_f
+        .try_bank_withdraw(lender_usdc.key, usdc_bank, 0, Some(true))
+        .await?;
+    assert_eq_noise!(
+        lender_usdc.balance().await as i64,
+        native!(992, "USDC") as i64,
+        1
+    );
+
+    Ok(())
+}
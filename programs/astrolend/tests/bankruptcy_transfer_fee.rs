@@ -0,0 +1,131 @@
+use fixed::types::I80F48;
+use fixtures::{astrolend_account::AstrolendAccountFixture, native, prelude::*};
+use astrolend::state::astrolend_group::BankVaultType;
+use solana_program_test::tokio;
+
+const TRANSFER_FEE_BPS: u16 = 100;
+
+/// The test mint charges 5%, lower it to 1% from the next epoch on and move into it
+async fn set_one_pct_transfer_fee(test_f: &TestFixture) {
+    let epoch = test_f.get_clock().await.epoch;
+    test_f
+        .get_bank(&BankMint::T22WithFee)
+        .mint
+        .schedule_transfer_fee(epoch + 1, TRANSFER_FEE_BPS)
+        .await;
+    test_f.advance_epoch().await;
+}
+
+/// Borrows 10 T22 against 100 USDC and wipes the USDC, the bad debt carries the transfer fee
+async fn open_bankrupt_account(test_f: &TestFixture) -> anyhow::Result<AstrolendAccountFixture> {
+    let bank_f = test_f.get_bank(&BankMint::T22WithFee);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_token_account = bank_f.mint.create_token_account_and_mint_to(1_100).await;
+    lender_f
+        .try_bank_deposit(lender_token_account.key, bank_f, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    let destination = bank_f.mint.create_empty_token_account().await;
+    borrower_f
+        .try_bank_deposit(borrower_usdc.key, test_f.get_bank(&BankMint::Usdc), 100)
+        .await?;
+    borrower_f
+        .try_bank_borrow(destination.key, bank_f, 10)
+        .await?;
+
+    borrower_f
+        .make_bankrupt(test_f.get_bank(&BankMint::Usdc), bank_f)
+        .await;
+
+    Ok(borrower_f)
+}
+
+/// Handle the bankruptcy with `insurance` UI tokens in the insurance vault and check the
+/// liquidity vault receives exactly the part of the bad debt that isn't socialized.
+///
+/// Returns the bad debt and the socialized loss.
+async fn handle_bankruptcy_with_insurance(
+    test_f: &TestFixture,
+    insurance: f64,
+) -> anyhow::Result<(I80F48, I80F48)> {
+    let bank_f = test_f.get_bank(&BankMint::T22WithFee);
+    let liquidity_vault = bank_f.get_vault_token_account(BankVaultType::Liquidity).await;
+    let insurance_vault = bank_f.get_vault_token_account(BankVaultType::Insurance).await;
+    bank_f
+        .mint
+        .clone()
+        .mint_to(&insurance_vault.key, insurance)
+        .await;
+
+    let borrower_f = open_bankrupt_account(test_f).await?;
+    let bad_debt = borrower_f
+        .load_positions(&[bank_f])
+        .await
+        .pop()
+        .unwrap()
+        .liability_amount;
+
+    let liquidity_vault_before = liquidity_vault.balance().await;
+    let socialized_before = I80F48::from(bank_f.load().await.cumulative_socialized_loss);
+
+    test_f
+        .astrolend_group
+        .try_handle_bankruptcy(bank_f, &borrower_f)
+        .await?;
+
+    let liquidity_vault_delta = liquidity_vault.balance().await - liquidity_vault_before;
+    let socialized_loss =
+        I80F48::from(bank_f.load().await.cumulative_socialized_loss) - socialized_before;
+
+    assert!(borrower_f.load_positions(&[bank_f]).await.is_empty());
+    assert_eq!(
+        I80F48::from_num(liquidity_vault_delta),
+        bad_debt - socialized_loss
+    );
+
+    Ok((bad_debt, socialized_loss))
+}
+
+#[tokio::test]
+async fn bankruptcy_insurance_payout_covers_transfer_fee() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    set_one_pct_transfer_fee(&test_f).await;
+
+    let (bad_debt, socialized_loss) = handle_bankruptcy_with_insurance(&test_f, 100.).await?;
+
+    // The insurance vault pays the bad debt grossed up by the fee, nothing is socialized
+    assert_eq!(socialized_loss, I80F48::ZERO);
+    let insurance_vault = test_f
+        .get_bank(&BankMint::T22WithFee)
+        .get_vault_token_account(BankVaultType::Insurance)
+        .await;
+    let payout = native!(100, "T22_WITH_FEE") - insurance_vault.balance().await;
+    let fee = (payout * TRANSFER_FEE_BPS as u64).div_ceil(10_000);
+    assert_eq!(I80F48::from_num(payout - fee), bad_debt);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn bankruptcy_socializes_remainder_after_transfer_fee() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    set_one_pct_transfer_fee(&test_f).await;
+
+    let (bad_debt, socialized_loss) = handle_bankruptcy_with_insurance(&test_f, 5.).await?;
+
+    // The whole insurance vault is paid out, the fee on it is socialized along the rest
+    let insurance_vault = test_f
+        .get_bank(&BankMint::T22WithFee)
+        .get_vault_token_account(BankVaultType::Insurance)
+        .await;
+    assert_eq!(insurance_vault.balance().await, 0);
+
+    let payout = native!(5, "T22_WITH_FEE");
+    let fee = (payout * TRANSFER_FEE_BPS as u64).div_ceil(10_000);
+    assert_eq!(socialized_loss, bad_debt - I80F48::from_num(payout - fee));
+
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{assert_custom_error, bank::BankFixture, prelude::*};
+use astrolend::{
+    errors::AstrolendError, events::LendingPoolBankConfigureEvent,
+    state::astrolend_group::BankConfigOpt,
+};
+use solana_program_test::{tokio, BanksClientError};
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+async fn make_borrow_only(bank_f: &BankFixture) -> anyhow::Result<()> {
+    bank_f
+        .update_config(BankConfigOpt {
+            borrow_only: Some(true),
+            asset_weight_init: Some(I80F48::ZERO.into()),
+            asset_weight_maint: Some(I80F48::ZERO.into()),
+            ..Default::default()
+        })
+        .await
+}
+
+#[tokio::test]
+async fn borrow_only_deposits_dont_collateralize() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let sol_eq_bank = test_f.get_bank(&BankMint::SolEquivalent);
+
+    make_borrow_only(sol_bank).await?;
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    // Deposits are still taken, but $100 of SOL backs nothing
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 10)
+        .await?;
+
+    let res = borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 1)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::RiskEngineInitRejected);
+
+    // Real collateral can be borrowed against, the SOL still counts for nothing
+    let borrower_sol_eq = test_f
+        .sol_equivalent_mint
+        .create_token_account_and_mint_to(10)
+        .await;
+    borrower_f
+        .try_bank_deposit(borrower_sol_eq.key, sol_eq_bank, 10)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 90)
+        .await?;
+
+    let res = borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 20)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::RiskEngineInitRejected);
+
+    // The SOL deposits are lent out
+    let sol_borrower_f = test_f.create_astrolend_account().await;
+    let sol_borrower_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(100)
+        .await;
+    let sol_borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    sol_borrower_f
+        .try_bank_deposit(sol_borrower_usdc.key, usdc_bank, 100)
+        .await?;
+    sol_borrower_f
+        .try_bank_borrow(sol_borrower_sol.key, sol_bank, 5)
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn borrow_only_requires_zero_asset_weights() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    // The default test config has asset weights of 1
+    let res = sol_bank
+        .update_config(BankConfigOpt {
+            borrow_only: Some(true),
+            ..Default::default()
+        })
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err().downcast::<BanksClientError>()?,
+        AstrolendError::InvalidConfig
+    );
+    assert!(!sol_bank.load().await.is_borrow_only());
+
+    let ix = test_f.astrolend_group.make_lending_pool_configure_bank_ix(
+        sol_bank,
+        BankConfigOpt {
+            borrow_only: Some(true),
+            asset_weight_init: Some(I80F48::ZERO.into()),
+            asset_weight_maint: Some(I80F48::ZERO.into()),
+            ..Default::default()
+        },
+    );
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await?
+    };
+    assert!(result.result.is_ok());
+
+    let logs = result.metadata.unwrap().log_messages;
+    let event = find_event::<LendingPoolBankConfigureEvent>(&logs).unwrap();
+    assert!(event.borrow_only);
+    assert!(sol_bank.load().await.is_borrow_only());
+
+    // Weights can't be raised while the flag is set
+    let res = sol_bank
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.5).into()),
+            asset_weight_maint: Some(I80F48!(0.6).into()),
+            ..Default::default()
+        })
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err().downcast::<BanksClientError>()?,
+        AstrolendError::InvalidConfig
+    );
+
+    // Clearing the flag and raising the weights in one update is fine
+    sol_bank
+        .update_config(BankConfigOpt {
+            borrow_only: Some(false),
+            asset_weight_init: Some(I80F48!(0.5).into()),
+            asset_weight_maint: Some(I80F48!(0.6).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    let bank = sol_bank.load().await;
+    assert!(!bank.is_borrow_only());
+    assert_eq!(I80F48::from(bank.config.asset_weight_init), I80F48!(0.5));
+
+    Ok(())
+}
@@ -0,0 +1,268 @@
+use anchor_lang::{InstructionData, ToAccountMetas};
+use fixed_macro::types::I80F48;
+use fixtures::{assert_custom_error, prelude::*};
+use astrolend::{
+    errors::AstrolendError,
+    state::{
+        astrolend_group::{BankConfigOpt, BankOperationalState, GroupConfig, OracleConfig},
+        price::OracleSetup,
+    },
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_program_test::{tokio, BanksClientError};
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+const TIMELOCK_SECS: u32 = 3_600;
+
+async fn setup() -> anyhow::Result<TestFixture> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            config_timelock_secs: Some(TIMELOCK_SECS),
+            ..Default::default()
+        })
+        .await?;
+
+    Ok(test_f)
+}
+
+async fn account_exists(test_f: &TestFixture, address: Pubkey) -> bool {
+    test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(address)
+        .await
+        .unwrap()
+        .is_some()
+}
+
+async fn lamports(test_f: &TestFixture, address: Pubkey) -> u64 {
+    test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_balance(address)
+        .await
+        .unwrap()
+}
+
+/// A new USDC feed at a slightly different price, the oracle change is validated on execution
+fn new_usdc_oracle(test_f: &TestFixture) -> OracleConfig {
+    let feed = Pubkey::new_unique();
+    test_f.context.borrow_mut().set_account(
+        &feed,
+        &create_pyth_legacy_oracle_account(
+            test_f.usdc_mint.key,
+            1.01,
+            USDC_MINT_DECIMALS.into(),
+            None,
+        )
+        .into(),
+    );
+
+    OracleConfig {
+        setup: OracleSetup::PythLegacy,
+        keys: create_oracle_key_array(feed),
+    }
+}
+
+#[tokio::test]
+async fn pending_config_executes_after_timelock() -> anyhow::Result<()> {
+    let test_f = setup().await?;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let old_config = usdc_bank.load().await.config;
+    let oracle = new_usdc_oracle(&test_f);
+
+    let config = BankConfigOpt {
+        asset_weight_init: Some(I80F48!(0.5).into()),
+        oracle_keys_and_setup: Some(oracle),
+        ..Default::default()
+    };
+
+    // Straight configs are rejected while the timelock is set
+    let res = usdc_bank.update_config(config.clone()).await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err().downcast::<BanksClientError>()?,
+        AstrolendError::PendingConfigRequired
+    );
+
+    let now = test_f.get_clock().await.unix_timestamp;
+    usdc_bank.try_propose_config(config).await?;
+
+    let pending_config = usdc_bank.load_pending_config().await?;
+    assert_eq!(pending_config.bank, usdc_bank.key);
+    assert_eq!(pending_config.proposer, test_f.payer());
+    assert_eq!(pending_config.executable_at, now + i64::from(TIMELOCK_SECS));
+    assert_eq!(usdc_bank.load().await.config, old_config);
+
+    // Only one change can be pending at a time
+    let res = usdc_bank
+        .try_propose_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.6).into()),
+            ..Default::default()
+        })
+        .await;
+    assert!(res.is_err());
+
+    test_f.advance_time(i64::from(TIMELOCK_SECS) - 1).await;
+    let res = usdc_bank.try_execute_pending_config().await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err().downcast::<BanksClientError>()?,
+        AstrolendError::PendingConfigNotExecutable
+    );
+
+    // Same instruction as the early execution, refresh the blockhash to resend it
+    test_f.advance_time(1).await;
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        ctx.last_blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+    }
+    usdc_bank.try_execute_pending_config().await?;
+
+    let config = usdc_bank.load().await.config;
+    assert_eq!(config.asset_weight_init, I80F48!(0.5).into());
+    assert_eq!(config.oracle_keys, oracle.keys);
+    assert!(!account_exists(&test_f, usdc_bank.get_pending_config_address()).await);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cancelled_pending_config_is_never_applied() -> anyhow::Result<()> {
+    let test_f = setup().await?;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let old_config = usdc_bank.load().await.config;
+
+    usdc_bank
+        .try_propose_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.5).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    // The rent goes back to the proposer
+    let payer_balance = lamports(&test_f, test_f.payer()).await;
+    usdc_bank.try_cancel_pending_config().await?;
+    assert!(lamports(&test_f, test_f.payer()).await > payer_balance);
+    assert!(!account_exists(&test_f, usdc_bank.get_pending_config_address()).await);
+
+    test_f.advance_time(i64::from(TIMELOCK_SECS)).await;
+    assert!(usdc_bank.try_execute_pending_config().await.is_err());
+    assert_eq!(usdc_bank.load().await.config, old_config);
+
+    // The bank can be proposed a new change
+    usdc_bank
+        .try_propose_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.6).into()),
+            ..Default::default()
+        })
+        .await?;
+    assert!(account_exists(&test_f, usdc_bank.get_pending_config_address()).await);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn emergency_configs_skip_timelock() -> anyhow::Result<()> {
+    let test_f = setup().await?;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let deposit_limit = usdc_bank.load().await.config.deposit_limit;
+
+    usdc_bank
+        .update_config(BankConfigOpt {
+            operational_state: Some(BankOperationalState::Paused),
+            deposit_limit: Some(deposit_limit / 2),
+            ..Default::default()
+        })
+        .await?;
+
+    let config = usdc_bank.load().await.config;
+    assert_eq!(config.operational_state, BankOperationalState::Paused);
+    assert_eq!(config.deposit_limit, deposit_limit / 2);
+
+    // Raising the limit back is not an emergency
+    let res = usdc_bank
+        .update_config(BankConfigOpt {
+            deposit_limit: Some(deposit_limit),
+            ..Default::default()
+        })
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err().downcast::<BanksClientError>()?,
+        AstrolendError::PendingConfigRequired
+    );
+
+    // Nor has an emergency change a pending config to write
+    let res = usdc_bank
+        .try_propose_config(BankConfigOpt {
+            deposit_limit: Some(0),
+            ..Default::default()
+        })
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidConfig);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn oracle_unchecked_and_settlement_price_skip_timelock() -> anyhow::Result<()> {
+    let test_f = setup().await?;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let oracle = new_usdc_oracle(&test_f);
+
+    // Proposing either through a pending config is rejected
+    let ixs = [
+        astrolend::instruction::LendingPoolConfigureBankOracleUnchecked { oracle }.data(),
+        astrolend::instruction::LendingPoolSetBankSettlementPrice { price: 1, expo: 0 }.data(),
+    ]
+    .map(|data| {
+        let mut accounts = astrolend::accounts::LendingPoolConfigureBank {
+            astrolend_group: test_f.astrolend_group.key,
+            admin: test_f.payer(),
+            bank: usdc_bank.key,
+            pending_config: Some(usdc_bank.get_pending_config_address()),
+            system_program: Some(system_program::id()),
+        }
+        .to_account_metas(Some(true));
+        accounts.push(AccountMeta::new_readonly(oracle.keys[0], false));
+
+        Instruction {
+            program_id: astrolend::id(),
+            accounts,
+            data,
+        }
+    });
+    for ix in ixs {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        let res = ctx.banks_client.process_transaction(tx).await;
+        assert!(res.is_err());
+        assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidConfig);
+    }
+    assert!(!account_exists(&test_f, usdc_bank.get_pending_config_address()).await);
+
+    // Both apply right away
+    usdc_bank.try_configure_oracle_unchecked(oracle).await?;
+    assert_eq!(usdc_bank.load().await.config.oracle_keys, oracle.keys);
+
+    usdc_bank.try_set_settlement_price(1, 0).await?;
+    assert!(usdc_bank.load().await.is_in_settlement());
+    assert!(!account_exists(&test_f, usdc_bank.get_pending_config_address()).await);
+
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use astrolend::prelude::constants::*;
+use pretty_assertions::assert_eq;
+use serde_json::{json, Value};
+
+/// Committed values of the public protocol constants, see `fixtures/constants.json`
+const SNAPSHOT: &str = include_str!("fixtures/constants.json");
+
+/// Cluster specific program ids are left out, the powers of ten tables only by length
+fn constants() -> Value {
+    json!({
+        "seeds": {
+            "LIQUIDITY_VAULT_AUTHORITY_SEED": LIQUIDITY_VAULT_AUTHORITY_SEED,
+            "INSURANCE_VAULT_AUTHORITY_SEED": INSURANCE_VAULT_AUTHORITY_SEED,
+            "FEE_VAULT_AUTHORITY_SEED": FEE_VAULT_AUTHORITY_SEED,
+            "LIQUIDITY_VAULT_SEED": LIQUIDITY_VAULT_SEED,
+            "INSURANCE_VAULT_SEED": INSURANCE_VAULT_SEED,
+            "FEE_VAULT_SEED": FEE_VAULT_SEED,
+            "EMISSIONS_AUTH_SEED": EMISSIONS_AUTH_SEED,
+            "EMISSIONS_TOKEN_ACCOUNT_SEED": EMISSIONS_TOKEN_ACCOUNT_SEED,
+            "WHITELIST_SEED": WHITELIST_SEED,
+            "INSURANCE_STAKE_SEED": INSURANCE_STAKE_SEED,
+            "RECEIPT_MINT_SEED": RECEIPT_MINT_SEED,
+            "NATIVE_TOKEN_ACCOUNT_SEED": NATIVE_TOKEN_ACCOUNT_SEED,
+            "PENDING_CONFIG_SEED": PENDING_CONFIG_SEED,
+        },
+        "fees": {
+            "LIQUIDATION_LIQUIDATOR_FEE": LIQUIDATION_LIQUIDATOR_FEE.to_string(),
+            "LIQUIDATION_INSURANCE_FEE": LIQUIDATION_INSURANCE_FEE.to_string(),
+            "SELF_LIQUIDATION_INSURANCE_FEE": SELF_LIQUIDATION_INSURANCE_FEE.to_string(),
+            "MAX_WITHDRAW_FEE_RATE": MAX_WITHDRAW_FEE_RATE.to_string(),
+        },
+        "limits": {
+            "LIQUIDATION_HISTORY_LEN": LIQUIDATION_HISTORY_LEN,
+            "MAX_EMODE_ENTRIES": MAX_EMODE_ENTRIES,
+            "EMODE_TAG_NONE": EMODE_TAG_NONE,
+            "UTILIZATION_HISTORY_LEN": UTILIZATION_HISTORY_LEN,
+            "UTILIZATION_SNAPSHOT_INTERVAL": UTILIZATION_SNAPSHOT_INTERVAL,
+            "INSURANCE_STAKE_COOLDOWN": INSURANCE_STAKE_COOLDOWN,
+            "SECONDS_PER_YEAR": SECONDS_PER_YEAR.to_string(),
+            "USDC_EXPONENT": USDC_EXPONENT,
+            "MAX_ORACLE_KEYS": MAX_ORACLE_KEYS,
+            "MAX_LENDING_ACCOUNT_BALANCES": MAX_LENDING_ACCOUNT_BALANCES,
+            "ASTROLEND_ACCOUNT_VERSION": ASTROLEND_ACCOUNT_VERSION,
+            "MAX_MINT_DECIMALS": MAX_MINT_DECIMALS,
+            "MIN_EMISSIONS_START_TIME": MIN_EMISSIONS_START_TIME,
+            "MAX_SETTLE_EMISSIONS_ACCOUNTS": MAX_SETTLE_EMISSIONS_ACCOUNTS,
+            "MAX_EXP_10_I80F48": MAX_EXP_10_I80F48,
+            "EXP_10_I80F48_LEN": EXP_10_I80F48.len(),
+            "MAX_EXP_10": MAX_EXP_10,
+            "EXP_10_LEN": EXP_10.len(),
+            "TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE": TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE,
+            "max_oracle_keys": max_oracle_keys(),
+            "max_balances": max_balances(),
+        },
+        "thresholds": {
+            "EMPTY_BALANCE_THRESHOLD": EMPTY_BALANCE_THRESHOLD.to_string(),
+            "BANKRUPT_THRESHOLD": BANKRUPT_THRESHOLD.to_string(),
+            "ZERO_AMOUNT_THRESHOLD": ZERO_AMOUNT_THRESHOLD.to_string(),
+            "MAX_ACCRUAL_UTILIZATION_RATE": MAX_ACCRUAL_UTILIZATION_RATE.to_string(),
+        },
+        "oracles": {
+            "MAX_PYTH_ORACLE_AGE": MAX_PYTH_ORACLE_AGE,
+            "MAX_SWB_ORACLE_AGE": MAX_SWB_ORACLE_AGE,
+            "CONF_INTERVAL_MULTIPLE": CONF_INTERVAL_MULTIPLE.to_string(),
+            "STD_DEV_MULTIPLE": STD_DEV_MULTIPLE.to_string(),
+            "MAX_CONF_INTERVAL": MAX_CONF_INTERVAL.to_string(),
+            "ORACLE_CHANGE_MAX_PRICE_DEVIATION": ORACLE_CHANGE_MAX_PRICE_DEVIATION.to_string(),
+            "MIN_PYTH_PUSH_VERIFICATION_LEVEL": format!("{:?}", MIN_PYTH_PUSH_VERIFICATION_LEVEL),
+            "PYTH_PUSH_PYTH_SPONSORED_SHARD_ID": PYTH_PUSH_PYTH_SPONSORED_SHARD_ID,
+            "PYTH_PUSH_ASTROLEND_SPONSORED_SHARD_ID": PYTH_PUSH_ASTROLEND_SPONSORED_SHARD_ID,
+        },
+        "bank_flags": {
+            "EMISSIONS_FLAG_BORROW_ACTIVE": EMISSIONS_FLAG_BORROW_ACTIVE,
+            "EMISSIONS_FLAG_LENDING_ACTIVE": EMISSIONS_FLAG_LENDING_ACTIVE,
+            "PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG": PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG,
+            "UTILIZATION_HISTORY_FLAG": UTILIZATION_HISTORY_FLAG,
+            "SEEDED_BANK_FLAG": SEEDED_BANK_FLAG,
+            "INSURANCE_DISABLED_FLAG": INSURANCE_DISABLED_FLAG,
+            "SETTLEMENT_MODE_FLAG": SETTLEMENT_MODE_FLAG,
+            "BORROW_ONLY_FLAG": BORROW_ONLY_FLAG,
+            "RECEIPTS_ENABLED_FLAG": RECEIPTS_ENABLED_FLAG,
+        },
+        "group_flags": {
+            "HEALTH_SNAPSHOT_EVENTS_FLAG": HEALTH_SNAPSHOT_EVENTS_FLAG,
+            "WHITELIST_REQUIRED_FLAG": WHITELIST_REQUIRED_FLAG,
+        },
+    })
+}
+
+#[test]
+fn constants_snapshot() {
+    let snapshot: Value = serde_json::from_str(SNAPSHOT).unwrap();
+
+    assert_eq!(
+        constants(),
+        snapshot,
+        "protocol constants changed, update tests/fixtures/constants.json if intended"
+    );
+}
@@ -0,0 +1,142 @@
+use fixed::types::I80F48;
+use fixtures::{
+    astrolend_cpi, native,
+    prelude::*,
+    test_cpi_caller::{DEPOSIT_IX, TEST_CPI_CALLER_ID, WITHDRAW_IX},
+};
+use astrolend::state::astrolend_group::BankVaultType;
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program_test::tokio;
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+/// Deposit through a caller program built only on `astrolend-cpi`.
+#[tokio::test]
+async fn cpi_deposit_round_trip() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let astrolend_account_f = test_f.create_astrolend_account().await;
+    let token_account_f = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+
+    let payer = test_f.payer();
+    let token_program = usdc_bank.get_token_program().await;
+    let ix = Instruction {
+        program_id: TEST_CPI_CALLER_ID,
+        accounts: vec![
+            AccountMeta::new_readonly(astrolend_cpi::ID, false),
+            AccountMeta::new_readonly(test_f.astrolend_group.key, false),
+            AccountMeta::new(astrolend_account_f.key, false),
+            AccountMeta::new_readonly(payer, true),
+            AccountMeta::new(usdc_bank.key, false),
+            AccountMeta::new_readonly(usdc_bank.mint.key, false),
+            AccountMeta::new(token_account_f.key, false),
+            AccountMeta::new(usdc_bank.get_vault(BankVaultType::Liquidity).0, false),
+            AccountMeta::new_readonly(token_program, false),
+            AccountMeta::new_readonly(
+                astrolend_cpi::find_whitelist_entry_address(&test_f.astrolend_group.key, &payer).0,
+                false,
+            ),
+        ],
+        data: [&[DEPOSIT_IX][..], &native!(1_000, "USDC").to_le_bytes()].concat(),
+    };
+
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&payer),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client.process_transaction(tx).await?;
+    }
+
+    astrolend_account_f
+        .assert_deposit_approx_eq(usdc_bank, I80F48::from(native!(1_000, "USDC")), I80F48::ONE)
+        .await;
+    assert_eq!(token_account_f.balance().await, 0);
+
+    Ok(())
+}
+
+/// Withdraw through a caller program that orders the observation accounts on-chain from the
+/// account balances, given its banks and oracles in arbitrary order.
+#[tokio::test]
+async fn cpi_withdraw_enumerates_balances() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    // SOL collateral in the first slot, USDC liability in the second
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 10)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 10)
+        .await?;
+
+    let payer = test_f.payer();
+    let token_program = sol_bank.get_token_program().await;
+    let mut accounts = vec![
+        AccountMeta::new_readonly(astrolend_cpi::ID, false),
+        AccountMeta::new_readonly(test_f.astrolend_group.key, false),
+        AccountMeta::new(borrower_f.key, false),
+        AccountMeta::new_readonly(payer, true),
+        AccountMeta::new(sol_bank.key, false),
+        AccountMeta::new_readonly(sol_bank.mint.key, false),
+        AccountMeta::new(borrower_sol.key, false),
+        AccountMeta::new(
+            sol_bank.get_vault_authority(BankVaultType::Liquidity).0,
+            false,
+        ),
+        AccountMeta::new(sol_bank.get_vault(BankVaultType::Liquidity).0, false),
+        AccountMeta::new_readonly(token_program, false),
+    ];
+    // Neither the balance order nor bank/oracle pairs
+    accounts.extend([
+        AccountMeta::new_readonly(usdc_bank.load().await.config.get_oracle_account(), false),
+        AccountMeta::new_readonly(sol_bank.key, false),
+        AccountMeta::new_readonly(usdc_bank.key, false),
+        AccountMeta::new_readonly(sol_bank.load().await.config.get_oracle_account(), false),
+    ]);
+
+    let ix = Instruction {
+        program_id: TEST_CPI_CALLER_ID,
+        accounts,
+        data: [&[WITHDRAW_IX][..], &native!(2, "SOL").to_le_bytes()].concat(),
+    };
+
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&payer),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client.process_transaction(tx).await?;
+    }
+
+    borrower_f
+        .assert_deposit_approx_eq(sol_bank, I80F48::from(native!(8, "SOL")), I80F48::ONE)
+        .await;
+    assert_eq!(borrower_sol.balance().await, native!(2, "SOL"));
+
+    Ok(())
+}
@@ -0,0 +1,313 @@
+use fixed_macro::types::I80F48;
+use fixtures::{astrolend_account::AstrolendAccountFixture, native, prelude::*};
+use astrolend::{
+    constants::ASTROLEND_ACCOUNT_VERSION,
+    events::{LendingAccountBorrowEvent, LendingAccountRepayEvent},
+    state::{
+        account_filters::ASTROLEND_ACCOUNT_VERSION_OFFSET,
+        astrolend_account::{AstrolendAccount, BalanceCreditHistory, ASTROLEND_ACCOUNT_SPACE},
+        astrolend_group::BankConfigOpt,
+    },
+};
+use solana_program::instruction::Instruction;
+use solana_program_test::{tokio, BanksTransactionResultWithMetadata};
+use solana_sdk::{account::Account, signer::Signer, transaction::Transaction};
+
+async fn process_with_metadata(
+    test_f: &TestFixture,
+    ix: Instruction,
+) -> anyhow::Result<BanksTransactionResultWithMetadata> {
+    let mut ctx = test_f.context.borrow_mut();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer],
+        ctx.last_blockhash,
+    );
+
+    Ok(ctx
+        .banks_client
+        .process_transaction_with_metadata(tx)
+        .await?)
+}
+
+/// A lender with 1_000 USDC in the bank, and a borrower with 2 SOL of collateral
+async fn setup() -> anyhow::Result<(TestFixture, AstrolendAccountFixture, TokenAccountFixture)> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(2).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 2)
+        .await?;
+    let borrower_usdc = test_f.usdc_mint.create_empty_token_account().await;
+
+    Ok((test_f, borrower_f, borrower_usdc))
+}
+
+#[tokio::test]
+async fn credit_history_survives_balance_close() -> anyhow::Result<()> {
+    let (test_f, borrower_f, borrower_usdc) = setup().await?;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let ix = borrower_f
+        .make_bank_borrow_ix(borrower_usdc.key, usdc_bank, 10)
+        .await;
+    let result = process_with_metadata(&test_f, ix).await?;
+    assert!(result.result.is_ok());
+    let events = parse_events::<LendingAccountBorrowEvent>(&result);
+    assert_eq!(events[0].total_borrowed_native_delta, native!(10, "USDC"));
+
+    let ix = borrower_f
+        .make_bank_repay_ix(borrower_usdc.key, usdc_bank, 4, None)
+        .await;
+    let result = process_with_metadata(&test_f, ix).await?;
+    assert!(result.result.is_ok());
+    let events = parse_events::<LendingAccountRepayEvent>(&result);
+    assert_eq!(events[0].total_repaid_native_delta, native!(4, "USDC"));
+
+    let borrower = borrower_f.load().await;
+    let credit_history = borrower_f.load_credit_history().await.unwrap();
+    assert_eq!(
+        credit_history.get_balance(&borrower.lending_account, &usdc_bank.key),
+        Some(BalanceCreditHistory {
+            bank_pk: usdc_bank.key,
+            total_borrowed_native: native!(10, "USDC"),
+            total_repaid_native: native!(4, "USDC"),
+        })
+    );
+    assert_eq!(credit_history.closed_total_borrowed_native, 0);
+
+    // Repaying the rest closes the balance, its counters are rolled up into the account
+    borrower_f
+        .try_bank_repay(borrower_usdc.key, usdc_bank, 0, Some(true))
+        .await?;
+
+    let borrower = borrower_f.load().await;
+    let credit_history = borrower_f.load_credit_history().await.unwrap();
+    assert!(borrower.lending_account.get_balance(&usdc_bank.key).is_none());
+    assert_eq!(
+        credit_history.get_balance(&borrower.lending_account, &usdc_bank.key),
+        None
+    );
+    assert_eq!(
+        credit_history.closed_total_borrowed_native,
+        native!(10, "USDC")
+    );
+    assert_eq!(credit_history.closed_total_repaid_native, native!(10, "USDC"));
+
+    // A new balance of the same bank starts from scratch, the account totals keep counting
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 5)
+        .await?;
+
+    let borrower = borrower_f.load().await;
+    let credit_history = borrower_f.load_credit_history().await.unwrap();
+    let credit = credit_history
+        .get_balance(&borrower.lending_account, &usdc_bank.key)
+        .unwrap();
+    assert_eq!(credit.total_borrowed_native, native!(5, "USDC"));
+    assert_eq!(credit.total_repaid_native, 0);
+    assert_eq!(
+        credit_history.get_totals(),
+        (native!(15, "USDC"), native!(10, "USDC"))
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn liquidation_repays_count_towards_credit_history() -> anyhow::Result<()> {
+    let (test_f, borrower_f, borrower_usdc) = setup().await?;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let liquidator_f = test_f.create_astrolend_account().await;
+    let liquidator_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    liquidator_f
+        .try_bank_deposit(liquidator_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 10)
+        .await?;
+    sol_bank
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.25).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    liquidator_f
+        .try_liquidate(&borrower_f, sol_bank, 0.1, usdc_bank)
+        .await?;
+
+    let borrower = borrower_f.load().await;
+    let liability_amount = borrower.get_liquidation_history()[0].liability_amount;
+    assert!(liability_amount > 0);
+
+    let credit = borrower_f
+        .load_credit_history()
+        .await
+        .unwrap()
+        .get_balance(&borrower.lending_account, &usdc_bank.key)
+        .unwrap();
+    assert_eq!(credit.total_borrowed_native, native!(10, "USDC"));
+    assert_eq!(credit.total_repaid_native, liability_amount);
+
+    // The liquidator paid with its deposits, it borrowed nothing
+    let liquidator = liquidator_f.load().await;
+    let credit_history = liquidator_f.load_credit_history().await.unwrap();
+    assert_eq!(
+        credit_history.get_balance(&liquidator.lending_account, &usdc_bank.key),
+        None
+    );
+    assert_eq!(credit_history.get_totals(), (0, 0));
+
+    Ok(())
+}
+
+/// Turn the account back into a legacy account, ending with the account
+async fn set_legacy(
+    test_f: &TestFixture,
+    account_f: &AstrolendAccountFixture,
+) -> anyhow::Result<Account> {
+    let mut ctx = test_f.context.borrow_mut();
+    let account = ctx.banks_client.get_account(account_f.key).await?.unwrap();
+    let rent = ctx.banks_client.get_rent().await?;
+
+    let legacy_len = AstrolendAccountFixture::get_size();
+    let mut data = account.data[..legacy_len].to_vec();
+    data[ASTROLEND_ACCOUNT_VERSION_OFFSET] = 0;
+    let account = Account {
+        lamports: rent.minimum_balance(legacy_len),
+        data,
+        ..account
+    };
+    ctx.set_account(&account_f.key, &account.clone().into());
+
+    Ok(account)
+}
+
+/// Legacy accounts end with the account, they keep working without a credit history until
+/// migrated
+#[tokio::test]
+async fn legacy_accounts_load_until_migrated() -> anyhow::Result<()> {
+    let (test_f, borrower_f, borrower_usdc) = setup().await?;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let legacy_len = AstrolendAccountFixture::get_size();
+    assert_eq!(legacy_len, 8 + 2304);
+    let account = set_legacy(&test_f, &borrower_f).await?;
+    assert!(borrower_f.load_credit_history().await.is_none());
+
+    // Deposits, withdraws, borrows and repays go through, nothing is counted
+    let sol_funding = test_f.sol_mint.create_token_account_and_mint_to(1).await;
+    borrower_f
+        .try_bank_deposit(sol_funding.key, sol_bank, 1)
+        .await?;
+    borrower_f
+        .try_bank_withdraw(sol_funding.key, sol_bank, 0.5, None)
+        .await?;
+
+    let ix = borrower_f
+        .make_bank_borrow_ix(borrower_usdc.key, usdc_bank, 10)
+        .await;
+    let result = process_with_metadata(&test_f, ix).await?;
+    assert!(result.result.is_ok());
+    let events = parse_events::<LendingAccountBorrowEvent>(&result);
+    assert_eq!(events[0].total_borrowed_native_delta, 0);
+
+    let ix = borrower_f
+        .make_bank_repay_ix(borrower_usdc.key, usdc_bank, 1, None)
+        .await;
+    let result = process_with_metadata(&test_f, ix).await?;
+    assert!(result.result.is_ok());
+    let events = parse_events::<LendingAccountRepayEvent>(&result);
+    assert_eq!(events[0].total_repaid_native_delta, 0);
+
+    // Liquidations too, on either side
+    let liquidator_f = test_f.create_astrolend_account().await;
+    let liquidator_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    liquidator_f
+        .try_bank_deposit(liquidator_usdc.key, usdc_bank, 1_000)
+        .await?;
+    set_legacy(&test_f, &liquidator_f).await?;
+    sol_bank
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.25).into()),
+            ..Default::default()
+        })
+        .await?;
+    liquidator_f
+        .try_liquidate(&borrower_f, sol_bank, 0.1, usdc_bank)
+        .await?;
+
+    let borrower = borrower_f.load().await;
+    assert!(borrower.is_legacy());
+    let asset_shares = borrower
+        .lending_account
+        .get_balance(&sol_bank.key)
+        .unwrap()
+        .asset_shares;
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        let data = ctx.banks_client.get_account(borrower_f.key).await?.unwrap().data;
+        assert_eq!(data.len(), legacy_len);
+    }
+
+    borrower_f.try_migrate().await?;
+
+    let (data, lamports, rent) = {
+        let mut ctx = test_f.context.borrow_mut();
+        let migrated = ctx.banks_client.get_account(borrower_f.key).await?.unwrap();
+        let rent = ctx.banks_client.get_rent().await?;
+
+        (migrated.data, migrated.lamports, rent)
+    };
+    assert_eq!(data.len(), ASTROLEND_ACCOUNT_SPACE);
+    assert_eq!(data[..8], account.data[..8]);
+    assert_eq!(lamports, rent.minimum_balance(data.len()));
+    assert!(AstrolendAccount::version_filter(ASTROLEND_ACCOUNT_VERSION).matches(&data));
+
+    let borrower = borrower_f.load().await;
+    assert_eq!(
+        borrower
+            .lending_account
+            .get_balance(&sol_bank.key)
+            .unwrap()
+            .asset_shares,
+        asset_shares
+    );
+    let credit_history = borrower_f.load_credit_history().await.unwrap();
+    assert_eq!(credit_history.get_totals(), (0, 0));
+
+    // Counted from the migration on
+    borrower_f
+        .try_bank_repay(borrower_usdc.key, usdc_bank, 0.5, None)
+        .await?;
+    let credit_history = borrower_f.load_credit_history().await.unwrap();
+    assert_eq!(credit_history.get_totals(), (0, native!(0.5, "USDC", f64)));
+
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{assert_custom_error, assert_eq_noise, prelude::*};
+use astrolend::{
+    errors::AstrolendError,
+    events::LendingPoolBankAccrueInterestEvent,
+    state::astrolend_group::{BankConfigOpt, InterestRateConfigOpt},
+};
+use solana_program_test::tokio;
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+#[tokio::test]
+async fn deposit_rate_floor_is_paid_from_group_fees() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    // Above the max interest rate
+    let res = usdc_bank
+        .update_config(BankConfigOpt {
+            interest_rate_config: Some(InterestRateConfigOpt {
+                deposit_rate_floor_apr: Some(I80F48!(4).into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidConfig);
+
+    usdc_bank
+        .update_config(BankConfigOpt {
+            interest_rate_config: Some(InterestRateConfigOpt {
+                protocol_fixed_fee_apr: Some(I80F48!(0.1).into()),
+                deposit_rate_floor_apr: Some(I80F48!(0.05).into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .await?;
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 100)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 100)
+        .await?;
+
+    test_f.advance_time(86_400 * 30).await;
+
+    let ix = test_f.astrolend_group.make_accrue_interest_ix(usdc_bank);
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await?
+    };
+    assert!(result.result.is_ok());
+
+    // 10% utilization pays depositors ~1.2%, far below the 5% floor: the ~0.82 USDC of fees
+    // collected over the period all go to depositors
+    let events = parse_events::<LendingPoolBankAccrueInterestEvent>(&result);
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq_noise!(event.fees_collected, native!(0.8219, "USDC", f64) as f64, 100.);
+    assert_eq_noise!(event.deposit_floor_subsidy, event.fees_collected, 1.);
+
+    let bank = usdc_bank.load().await;
+    assert!(I80F48::from(bank.collected_group_fees_outstanding) < I80F48::ONE);
+
+    Ok(())
+}
@@ -0,0 +1,193 @@
+use anchor_spl::token::spl_token;
+use fixtures::{assert_custom_error, native, prelude::*};
+use astrolend::{
+    errors::AstrolendError, state::astrolend_account::AccountConfigOpt,
+    utils::find_receipt_mint_pda,
+};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer, transaction::Transaction};
+
+/// Token account of the bank receipt mint owned by `owner`
+async fn create_receipt_account(
+    test_f: &TestFixture,
+    bank: &BankFixture,
+    owner: &Pubkey,
+) -> TokenAccountFixture {
+    TokenAccountFixture::new_with_token_program(
+        test_f.context.clone(),
+        &find_receipt_mint_pda(&bank.key).0,
+        owner,
+        &bank.get_token_program().await,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn deposit_receipts_round_trip() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+    let usdc_bank = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_receipts(
+            &test_f.usdc_mint,
+            *DEFAULT_USDC_TEST_BANK_CONFIG,
+        )
+        .await?;
+    let sol_bank = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank(&test_f.sol_mint, *DEFAULT_SOL_TEST_BANK_CONFIG)
+        .await?;
+    assert!(usdc_bank.load().await.is_receipts_enabled());
+
+    let sol_lender_f = test_f.create_astrolend_account().await;
+    let sol_lender_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    sol_lender_f
+        .try_bank_deposit(sol_lender_sol.key, &sol_bank, 100)
+        .await?;
+
+    // Receipts are minted one per asset share, the account isn't credited
+    let depositor_f = test_f.create_astrolend_account().await;
+    let depositor_usdc = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    let depositor_receipts = create_receipt_account(&test_f, &usdc_bank, &test_f.payer()).await;
+    depositor_f
+        .try_bank_deposit_with_receipts(depositor_usdc.key, &usdc_bank, 100, depositor_receipts.key)
+        .await?;
+
+    assert_eq!(depositor_usdc.balance().await, 0);
+    assert_eq!(depositor_receipts.balance().await, native!(100, "USDC"));
+    assert!(depositor_f.load_positions(&[&usdc_bank]).await.is_empty());
+
+    // Receipts don't count as collateral
+    let depositor_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    let res = depositor_f
+        .try_bank_borrow(depositor_sol.key, &sol_bank, 1)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::RiskEngineInitRejected);
+
+    // Another wallet holding receipts redeems them into an account of its choice
+    let holder = Keypair::new();
+    let holder_receipts = create_receipt_account(&test_f, &usdc_bank, &holder.pubkey()).await;
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        let ix = spl_token::instruction::transfer(
+            &spl_token::ID,
+            &depositor_receipts.key,
+            &holder_receipts.key,
+            &ctx.payer.pubkey(),
+            &[],
+            native!(40, "USDC"),
+        )?;
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client.process_transaction(tx).await?;
+    }
+
+    let res = depositor_f
+        .try_redeem_receipts(&holder, holder_receipts.key, &usdc_bank, native!(40, "USDC"))
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::ThirdPartyDepositNotAllowed);
+
+    depositor_f
+        .try_configure(AccountConfigOpt {
+            third_party_deposits_allowed: Some(true),
+            ..Default::default()
+        })
+        .await?;
+    depositor_f
+        .try_redeem_receipts(&holder, holder_receipts.key, &usdc_bank, native!(40, "USDC"))
+        .await?;
+    assert_eq!(holder_receipts.balance().await, 0);
+
+    let positions = depositor_f.load_positions(&[&usdc_bank]).await;
+    assert_eq!(positions.len(), 1);
+    assert_eq!(positions[0].asset_shares.to_num::<u64>(), native!(40, "USDC"));
+
+    // Redeemed shares are regular deposits again
+    depositor_f
+        .try_bank_withdraw(depositor_usdc.key, &usdc_bank, 40, Some(true))
+        .await?;
+    assert_eq!(depositor_usdc.balance().await, native!(40, "USDC"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn deposit_receipts_require_receipt_mint() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    assert!(!usdc_bank.load().await.is_receipts_enabled());
+
+    let depositor_f = test_f.create_astrolend_account().await;
+    let depositor_usdc = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    let res = depositor_f
+        .try_bank_deposit_with_receipts(depositor_usdc.key, usdc_bank, 100, Pubkey::new_unique())
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::ReceiptsDisabled);
+
+    assert_eq!(depositor_usdc.balance().await, native!(100, "USDC"));
+
+    Ok(())
+}
+
+/// Receipts leave the account like withdrawn tokens, restricted accounts only mint them to the
+/// authority's associated token account or the approved destination
+#[tokio::test]
+async fn deposit_receipts_with_restricted_destinations() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+    let usdc_bank = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_receipts(
+            &test_f.usdc_mint,
+            *DEFAULT_USDC_TEST_BANK_CONFIG,
+        )
+        .await?;
+
+    let depositor_f = test_f.create_astrolend_account().await;
+    let depositor_usdc = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    depositor_f
+        .try_configure(AccountConfigOpt {
+            destinations_restricted: Some(true),
+            ..Default::default()
+        })
+        .await?;
+
+    // Owned by the authority is not enough, it has to be the associated token account
+    let receipts = create_receipt_account(&test_f, &usdc_bank, &test_f.payer()).await;
+    let res = depositor_f
+        .try_bank_deposit_with_receipts(depositor_usdc.key, &usdc_bank, 10, receipts.key)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::DestinationNotAllowed);
+
+    let receipts_ata = TokenAccountFixture::new_associated_with_token_program(
+        test_f.context.clone(),
+        &find_receipt_mint_pda(&usdc_bank.key).0,
+        &test_f.payer(),
+        &usdc_bank.get_token_program().await,
+    )
+    .await;
+    depositor_f
+        .try_bank_deposit_with_receipts(depositor_usdc.key, &usdc_bank, 10, receipts_ata.key)
+        .await?;
+    assert_eq!(receipts_ata.balance().await, native!(10, "USDC"));
+
+    depositor_f
+        .try_configure(AccountConfigOpt {
+            approved_destination: Some(receipts.key),
+            ..Default::default()
+        })
+        .await?;
+    depositor_f
+        .try_bank_deposit_with_receipts(depositor_usdc.key, &usdc_bank, 20, receipts.key)
+        .await?;
+    assert_eq!(receipts.balance().await, native!(20, "USDC"));
+
+    Ok(())
+}
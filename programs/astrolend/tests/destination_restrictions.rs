@@ -0,0 +1,230 @@
+use fixed_macro::types::I80F48;
+use fixtures::{assert_custom_error, astrolend_account::AstrolendAccountFixture, native, prelude::*};
+use astrolend::{
+    errors::AstrolendError,
+    state::{
+        astrolend_account::{AccountConfigOpt, RESTRICTED_DESTINATIONS_FLAG},
+        astrolend_group::BankConfigOpt,
+    },
+};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+
+/// A lender with 1_000 USDC in the bank, and a user with 10 SOL ($100) of collateral
+async fn setup() -> anyhow::Result<(TestFixture, AstrolendAccountFixture)> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let user_f = test_f.create_astrolend_account().await;
+    let user_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    user_f.try_bank_deposit(user_sol.key, sol_bank, 10).await?;
+
+    Ok((test_f, user_f))
+}
+
+/// A token account of `mint` owned by a wallet unrelated to the account
+async fn third_party_token_account(
+    test_f: &TestFixture,
+    mint: &MintFixture,
+) -> TokenAccountFixture {
+    TokenAccountFixture::new(test_f.context.clone(), mint, &Pubkey::new_unique()).await
+}
+
+#[tokio::test]
+async fn unrestricted_destinations_accept_any_token_account_of_the_mint() -> anyhow::Result<()> {
+    let (test_f, user_f) = setup().await?;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let third_party_sol = third_party_token_account(&test_f, &test_f.sol_mint).await;
+    let third_party_usdc = third_party_token_account(&test_f, &test_f.usdc_mint).await;
+
+    user_f
+        .try_bank_withdraw(third_party_sol.key, sol_bank, 1, None)
+        .await?;
+    assert_eq!(third_party_sol.balance().await, native!(1, "SOL"));
+
+    user_f
+        .try_bank_borrow(third_party_usdc.key, usdc_bank, 10)
+        .await?;
+    assert_eq!(third_party_usdc.balance().await, native!(10, "USDC"));
+
+    // The destination still has to hold the bank mint
+    let res = user_f
+        .try_bank_withdraw(third_party_usdc.key, sol_bank, 1, None)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::DestinationNotAllowed);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn restricted_destinations_require_the_authority_ata() -> anyhow::Result<()> {
+    let (test_f, user_f) = setup().await?;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    user_f
+        .try_configure(AccountConfigOpt {
+            destinations_restricted: Some(true),
+            ..Default::default()
+        })
+        .await?;
+    assert!(user_f.load().await.get_flag(RESTRICTED_DESTINATIONS_FLAG));
+
+    // Owned by the authority is not enough, it has to be the associated token account
+    let authority_sol = test_f.sol_mint.create_empty_token_account().await;
+    let res = user_f
+        .try_bank_withdraw(authority_sol.key, sol_bank, 1, None)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::DestinationNotAllowed);
+
+    let third_party_usdc = third_party_token_account(&test_f, &test_f.usdc_mint).await;
+    let res = user_f
+        .try_bank_borrow(third_party_usdc.key, usdc_bank, 10)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::DestinationNotAllowed);
+
+    let authority_sol_ata = TokenAccountFixture::new_associated(
+        test_f.context.clone(),
+        &test_f.sol_mint,
+        &test_f.payer(),
+    )
+    .await;
+    user_f
+        .try_bank_withdraw(authority_sol_ata.key, sol_bank, 1, None)
+        .await?;
+    assert_eq!(authority_sol_ata.balance().await, native!(1, "SOL"));
+
+    let authority_usdc_ata = TokenAccountFixture::new_associated(
+        test_f.context.clone(),
+        &test_f.usdc_mint,
+        &test_f.payer(),
+    )
+    .await;
+    user_f
+        .try_bank_borrow(authority_usdc_ata.key, usdc_bank, 10)
+        .await?;
+    assert_eq!(authority_usdc_ata.balance().await, native!(10, "USDC"));
+
+    // Lifting the restriction opens up every destination again
+    user_f
+        .try_configure(AccountConfigOpt {
+            destinations_restricted: Some(false),
+            ..Default::default()
+        })
+        .await?;
+    user_f
+        .try_bank_withdraw(authority_sol.key, sol_bank, 2, None)
+        .await?;
+    assert_eq!(authority_sol.balance().await, native!(2, "SOL"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn restricted_destinations_accept_the_approved_destination() -> anyhow::Result<()> {
+    let (test_f, user_f) = setup().await?;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let approved_sol = third_party_token_account(&test_f, &test_f.sol_mint).await;
+    user_f
+        .try_configure(AccountConfigOpt {
+            destinations_restricted: Some(true),
+            approved_destination: Some(approved_sol.key),
+            ..Default::default()
+        })
+        .await?;
+    assert_eq!(user_f.load().await.approved_destination, approved_sol.key);
+
+    user_f
+        .try_bank_withdraw(approved_sol.key, sol_bank, 1, None)
+        .await?;
+    assert_eq!(approved_sol.balance().await, native!(1, "SOL"));
+
+    // Only the one destination is approved
+    let other_usdc = third_party_token_account(&test_f, &test_f.usdc_mint).await;
+    let res = user_f.try_bank_borrow(other_usdc.key, usdc_bank, 10).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::DestinationNotAllowed);
+
+    let approved_usdc = third_party_token_account(&test_f, &test_f.usdc_mint).await;
+    user_f
+        .try_configure(AccountConfigOpt {
+            approved_destination: Some(approved_usdc.key),
+            ..Default::default()
+        })
+        .await?;
+    user_f
+        .try_bank_borrow(approved_usdc.key, usdc_bank, 10)
+        .await?;
+    assert_eq!(approved_usdc.balance().await, native!(10, "USDC"));
+
+    // Replacing the approved destination revokes the previous one
+    let res = user_f
+        .try_bank_withdraw(approved_sol.key, sol_bank, 2, None)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::DestinationNotAllowed);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn restricted_destinations_apply_to_self_liquidation() -> anyhow::Result<()> {
+    let (test_f, user_f) = setup().await?;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let user_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    user_f.try_bank_borrow(user_usdc.key, usdc_bank, 50).await?;
+    user_f
+        .try_configure(AccountConfigOpt {
+            destinations_restricted: Some(true),
+            ..Default::default()
+        })
+        .await?;
+
+    // $25 of maintenance collateral against $50 of liabilities
+    sol_bank
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.25).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    let third_party_sol = third_party_token_account(&test_f, &test_f.sol_mint).await;
+    let res = user_f
+        .try_self_liquidate(sol_bank, 0.5, usdc_bank, user_usdc.key, third_party_sol.key)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::DestinationNotAllowed);
+
+    let authority_sol_ata = TokenAccountFixture::new_associated(
+        test_f.context.clone(),
+        &test_f.sol_mint,
+        &test_f.payer(),
+    )
+    .await;
+    user_f
+        .try_self_liquidate(sol_bank, 0.5, usdc_bank, user_usdc.key, authority_sol_ata.key)
+        .await?;
+    assert_eq!(authority_sol_ata.balance().await, native!(0.5, "SOL", f64));
+
+    Ok(())
+}
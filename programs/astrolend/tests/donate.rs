@@ -0,0 +1,101 @@
+use astrolend::{errors::AstrolendError, events::LendingPoolDonateEvent};
+use fixed::types::I80F48;
+use fixtures::{assert_custom_error, assert_eq_noise, native, prelude::*};
+use solana_program_test::tokio;
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+#[tokio::test]
+async fn donation_credits_depositors_pro_rata() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let mut lenders = vec![];
+    for ui_amount in [1_000, 3_000] {
+        let lender_f = test_f.create_astrolend_account().await;
+        let lender_usdc = test_f
+            .usdc_mint
+            .create_token_account_and_mint_to(ui_amount)
+            .await;
+        lender_f
+            .try_bank_deposit(lender_usdc.key, usdc_bank, ui_amount)
+            .await?;
+        lenders.push((lender_f, lender_usdc));
+    }
+
+    let donor_usdc = test_f.usdc_mint.create_token_account_and_mint_to(400).await;
+    let ix = usdc_bank
+        .make_donate_ix(&donor_usdc, native!(400, "USDC"))
+        .await;
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await?
+    };
+    assert!(result.result.is_ok());
+
+    let bank = usdc_bank.load().await;
+    let events = parse_events::<LendingPoolDonateEvent>(&result);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].bank, usdc_bank.key);
+    assert_eq!(events[0].donor, test_f.payer());
+    assert_eq!(events[0].amount, native!(400, "USDC"));
+    assert_eq!(
+        events[0].asset_share_value,
+        I80F48::from(bank.asset_share_value).to_num::<f64>()
+    );
+    assert_eq!(donor_usdc.balance().await, 0);
+
+    // Deposits after the donation don't share in it
+    let late_lender_f = test_f.create_astrolend_account().await;
+    let late_lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    late_lender_f
+        .try_bank_deposit(late_lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+    lenders.push((late_lender_f, late_lender_usdc));
+
+    // 10% on top for the depositors before the donation
+    let expected = [
+        native!(1_100, "USDC"),
+        native!(3_300, "USDC"),
+        native!(1_000, "USDC"),
+    ];
+    for ((lender_f, lender_usdc), expected) in lenders.iter().zip(expected) {
+        lender_f
+            .try_bank_withdraw(lender_usdc.key, usdc_bank, 0, Some(true))
+            .await?;
+        assert_eq_noise!(lender_usdc.balance().await as i64, expected as i64, 1);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn donation_without_deposits_is_rejected() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let donor_usdc = test_f.usdc_mint.create_token_account_and_mint_to(400).await;
+    let res = usdc_bank
+        .try_donate(&donor_usdc, native!(400, "USDC"))
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::DonationWithoutDeposits);
+    assert_eq!(donor_usdc.balance().await, native!(400, "USDC"));
+    assert_eq!(
+        I80F48::from(usdc_bank.load().await.asset_share_value),
+        I80F48::ONE
+    );
+
+    Ok(())
+}
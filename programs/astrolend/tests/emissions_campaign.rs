@@ -0,0 +1,636 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{assert_custom_error, assert_eq_noise, native, prelude::*};
+use astrolend::{
+    constants::{
+        EMISSIONS_FLAG_LENDING_ACTIVE, MAX_SETTLE_EMISSIONS_ACCOUNTS, SECONDS_PER_YEAR,
+    },
+    errors::AstrolendError,
+};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+
+/// Emissions only accrue from the campaign start, even for balances opened before it
+#[tokio::test]
+async fn emissions_are_not_paid_retroactively() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    // Emissions are ignored for balances last updated before `MIN_EMISSIONS_START_TIME`
+    test_f.advance_time(1_700_000_000).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let astrolend_account_f = test_f.create_astrolend_account().await;
+    let token_account_f = test_f.usdc_mint.create_token_account_and_mint_to(200).await;
+    astrolend_account_f
+        .try_bank_deposit(token_account_f.key, usdc_bank, 100)
+        .await?;
+
+    test_f.advance_time(86_400).await;
+
+    // 1 SOL per USDC per year
+    let emissions_rate = native!(1, "SOL");
+    let funding_account = test_f.sol_mint.create_token_account_and_mint_to(1_000).await;
+    usdc_bank
+        .try_setup_emissions(
+            EMISSIONS_FLAG_LENDING_ACTIVE,
+            emissions_rate,
+            native!(1_000, "SOL"),
+            test_f.sol_mint.key,
+            funding_account.key,
+            anchor_spl::token::ID,
+        )
+        .await?;
+
+    // Settling right after setup pays nothing for the day before the campaign
+    astrolend_account_f
+        .try_bank_deposit(token_account_f.key, usdc_bank, 1)
+        .await?;
+
+    let account = astrolend_account_f.load().await;
+    let balance = account.lending_account.get_balance(&usdc_bank.key).unwrap();
+    assert_eq!(I80F48::from(balance.emissions_outstanding), I80F48::ZERO);
+
+    test_f.advance_time(86_400).await;
+
+    astrolend_account_f
+        .try_bank_deposit(token_account_f.key, usdc_bank, 2)
+        .await?;
+
+    // Only the day after setup, on the 101 USDC held during it
+    let expected = I80F48::from_num(101 * emissions_rate * 86_400) / SECONDS_PER_YEAR;
+
+    let account = astrolend_account_f.load().await;
+    let balance = account.lending_account.get_balance(&usdc_bank.key).unwrap();
+    assert_eq_noise!(
+        I80F48::from(balance.emissions_outstanding),
+        expected,
+        I80F48!(1_000)
+    );
+
+    Ok(())
+}
+
+/// Closing a balance with unclaimed emissions parks them instead of forfeiting them
+#[tokio::test]
+async fn emissions_claimable_after_withdraw_all() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    test_f.advance_time(1_700_000_000).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    // 1 SOL per USDC per year
+    let emissions_rate = native!(1, "SOL");
+    let funding_account = test_f.sol_mint.create_token_account_and_mint_to(1_000).await;
+    usdc_bank
+        .try_setup_emissions(
+            EMISSIONS_FLAG_LENDING_ACTIVE,
+            emissions_rate,
+            native!(1_000, "SOL"),
+            test_f.sol_mint.key,
+            funding_account.key,
+            anchor_spl::token::ID,
+        )
+        .await?;
+
+    let astrolend_account_f = test_f.create_astrolend_account().await;
+    let token_account_f = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    astrolend_account_f
+        .try_bank_deposit(token_account_f.key, usdc_bank, 100)
+        .await?;
+
+    test_f.advance_time(86_400).await;
+
+    // Emissions are settled before the shares are removed
+    astrolend_account_f
+        .try_bank_withdraw(token_account_f.key, usdc_bank, 0, Some(true))
+        .await?;
+
+    let expected = I80F48::from_num(100 * emissions_rate * 86_400) / SECONDS_PER_YEAR;
+
+    let account = astrolend_account_f.load().await;
+    assert!(account.lending_account.get_balance(&usdc_bank.key).is_none());
+    let parked_index = account
+        .lending_account
+        .get_parked_balance(&usdc_bank.key)
+        .unwrap();
+    let parked = account.lending_account.balances[parked_index];
+    assert_eq_noise!(
+        I80F48::from(parked.emissions_outstanding),
+        expected,
+        I80F48!(1_000)
+    );
+
+    // No more accrual on the closed balance
+    test_f.advance_time(86_400).await;
+
+    let recv_account = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    astrolend_account_f
+        .try_withdraw_emissions(usdc_bank, &recv_account)
+        .await?;
+
+    assert_eq_noise!(
+        I80F48::from(recv_account.balance().await),
+        expected,
+        I80F48!(1_000)
+    );
+
+    // Claiming frees the slot
+    let account = astrolend_account_f.load().await;
+    assert!(account
+        .lending_account
+        .get_parked_balance(&usdc_bank.key)
+        .is_none());
+    let other_recv_account = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    let res = astrolend_account_f
+        .try_withdraw_emissions(usdc_bank, &other_recv_account)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::BankAccountNotFound);
+
+    Ok(())
+}
+
+/// A single crank settles emissions for several accounts, skipping those without a balance
+#[tokio::test]
+async fn emissions_settled_for_many_accounts() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    test_f.advance_time(1_700_000_000).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    // 1 SOL per USDC per year
+    let emissions_rate = native!(1, "SOL");
+    let funding_account = test_f.sol_mint.create_token_account_and_mint_to(1_000).await;
+    usdc_bank
+        .try_setup_emissions(
+            EMISSIONS_FLAG_LENDING_ACTIVE,
+            emissions_rate,
+            native!(1_000, "SOL"),
+            test_f.sol_mint.key,
+            funding_account.key,
+            anchor_spl::token::ID,
+        )
+        .await?;
+
+    let mut astrolend_accounts = vec![];
+    for amount in [100u32, 200, 300] {
+        let astrolend_account_f = test_f.create_astrolend_account().await;
+        let token_account_f = test_f
+            .usdc_mint
+            .create_token_account_and_mint_to(amount)
+            .await;
+        astrolend_account_f
+            .try_bank_deposit(token_account_f.key, usdc_bank, amount)
+            .await?;
+        astrolend_accounts.push((astrolend_account_f, amount));
+    }
+    let empty_account_f = test_f.create_astrolend_account().await;
+
+    test_f.advance_time(86_400).await;
+
+    let mut keys: Vec<_> = astrolend_accounts.iter().map(|(a, _)| a.key).collect();
+    keys.push(empty_account_f.key);
+    usdc_bank.try_settle_emissions_many(&keys).await?;
+
+    let timestamp = test_f.get_clock().await.unix_timestamp as u64;
+    let mut total_settled = I80F48::ZERO;
+    for (astrolend_account_f, amount) in astrolend_accounts.iter() {
+        let expected =
+            I80F48::from_num(u64::from(*amount) * emissions_rate * 86_400) / SECONDS_PER_YEAR;
+
+        let account = astrolend_account_f.load().await;
+        let balance = account.lending_account.get_balance(&usdc_bank.key).unwrap();
+        assert_eq!(balance.last_update, timestamp);
+        assert_eq_noise!(
+            I80F48::from(balance.emissions_outstanding),
+            expected,
+            I80F48!(1_000)
+        );
+        total_settled += I80F48::from(balance.emissions_outstanding);
+    }
+
+    assert!(!empty_account_f
+        .load()
+        .await
+        .lending_account
+        .has_balance(&usdc_bank.key));
+    assert_eq_noise!(
+        I80F48::from(usdc_bank.load().await.emissions_remaining),
+        I80F48::from_num(native!(1_000, "SOL")) - total_settled,
+        I80F48!(1)
+    );
+
+    // The batch is capped
+    let too_many = vec![empty_account_f.key; MAX_SETTLE_EMISSIONS_ACCOUNTS + 1];
+    let res = usdc_bank.try_settle_emissions_many(&too_many).await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err(),
+        AstrolendError::TooManyEmissionsSettlements
+    );
+
+    Ok(())
+}
+
+/// A campaign running dry is shared pro-rata, whatever order balances settle in
+#[tokio::test]
+async fn exhausted_emissions_shared_pro_rata() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    test_f.advance_time(1_700_000_000).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    // 1 SOL per USDC per year, 600 USDC deposited run through 1 SOL in about 15 hours
+    let emissions_rate = native!(1, "SOL");
+    let funding_account = test_f.sol_mint.create_token_account_and_mint_to(1).await;
+    usdc_bank
+        .try_setup_emissions(
+            EMISSIONS_FLAG_LENDING_ACTIVE,
+            emissions_rate,
+            native!(1, "SOL"),
+            test_f.sol_mint.key,
+            funding_account.key,
+            anchor_spl::token::ID,
+        )
+        .await?;
+
+    let mut astrolend_accounts = vec![];
+    for amount in [100u32, 200, 300] {
+        let astrolend_account_f = test_f.create_astrolend_account().await;
+        let token_account_f = test_f
+            .usdc_mint
+            .create_token_account_and_mint_to(2 * amount)
+            .await;
+        astrolend_account_f
+            .try_bank_deposit(token_account_f.key, usdc_bank, amount)
+            .await?;
+        astrolend_accounts.push((astrolend_account_f, token_account_f, amount));
+    }
+
+    // The largest balance settles first, a day in
+    test_f.advance_time(86_400).await;
+    usdc_bank
+        .try_settle_emissions_many(&[astrolend_accounts[2].0.key])
+        .await?;
+
+    let bank = usdc_bank.load().await;
+    assert!(bank.emissions_exhausted_at > 0);
+    assert_eq!(I80F48::from(bank.emissions_remaining), I80F48::ZERO);
+
+    // Deposits after the campaign ran out earn nothing
+    test_f.advance_time(86_400).await;
+    let (astrolend_account_f, token_account_f, amount) = &astrolend_accounts[0];
+    astrolend_account_f
+        .try_bank_deposit(token_account_f.key, usdc_bank, *amount)
+        .await?;
+    usdc_bank
+        .try_settle_emissions_many(&[astrolend_accounts[0].0.key, astrolend_accounts[1].0.key])
+        .await?;
+
+    let mut total_settled = I80F48::ZERO;
+    for (astrolend_account_f, _, amount) in astrolend_accounts.iter() {
+        let expected = I80F48::from_num(native!(1, "SOL") * u64::from(*amount)) / 600;
+
+        let account = astrolend_account_f.load().await;
+        let balance = account.lending_account.get_balance(&usdc_bank.key).unwrap();
+        assert_eq_noise!(
+            I80F48::from(balance.emissions_outstanding),
+            expected,
+            I80F48!(20_000)
+        );
+        total_settled += I80F48::from(balance.emissions_outstanding);
+    }
+
+    assert_eq_noise!(
+        total_settled,
+        I80F48::from_num(native!(1, "SOL")),
+        I80F48!(20_000)
+    );
+
+    Ok(())
+}
+
+/// One instruction claims from several banks, each with its own emissions mint
+#[tokio::test]
+async fn emissions_withdrawn_from_many_banks() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    test_f.advance_time(1_700_000_000).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let sol_eq_bank = test_f.get_bank(&BankMint::SolEquivalent);
+
+    // 1 emissions token per deposited token per year
+    let campaigns = [
+        (usdc_bank, &test_f.sol_mint, native!(1, "SOL")),
+        (sol_bank, &test_f.mnde_mint, native!(1, "MNDE")),
+        (sol_eq_bank, &test_f.usdc_mint, native!(1, "USDC")),
+    ];
+
+    let astrolend_account_f = test_f.create_astrolend_account().await;
+    let mut recv_accounts = vec![];
+    for (bank_f, emissions_mint, emissions_rate) in campaigns.iter() {
+        let funding_account = emissions_mint.create_token_account_and_mint_to(1_000).await;
+        bank_f
+            .try_setup_emissions(
+                EMISSIONS_FLAG_LENDING_ACTIVE,
+                *emissions_rate,
+                emissions_rate * 1_000,
+                emissions_mint.key,
+                funding_account.key,
+                anchor_spl::token::ID,
+            )
+            .await?;
+
+        let token_account_f = bank_f.mint.create_token_account_and_mint_to(100).await;
+        astrolend_account_f
+            .try_bank_deposit(token_account_f.key, bank_f, 100)
+            .await?;
+
+        recv_accounts.push(emissions_mint.create_empty_token_account().await);
+    }
+
+    test_f.advance_time(86_400).await;
+
+    let claims: Vec<_> = campaigns
+        .iter()
+        .zip(recv_accounts.iter())
+        .map(|((bank_f, _, _), recv_account)| (*bank_f, recv_account))
+        .collect();
+    astrolend_account_f
+        .try_withdraw_emissions_many(&claims)
+        .await?;
+
+    let mut claimed = vec![];
+    for ((_, _, emissions_rate), recv_account) in campaigns.iter().zip(recv_accounts.iter()) {
+        let expected = I80F48::from_num(100 * emissions_rate * 86_400) / SECONDS_PER_YEAR;
+        let balance = recv_account.balance().await;
+        assert_eq_noise!(I80F48::from(balance), expected, expected / I80F48!(100));
+        claimed.push(balance);
+    }
+
+    // Nothing accrued since, every bank is skipped
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        ctx.last_blockhash = ctx.get_new_latest_blockhash().await?;
+    }
+    astrolend_account_f
+        .try_withdraw_emissions_many(&claims)
+        .await?;
+    for (recv_account, balance) in recv_accounts.iter().zip(claimed) {
+        assert_eq!(recv_account.balance().await, balance);
+    }
+
+    // A bank without a campaign has no emissions mint to match
+    let pyusd_bank = test_f.get_bank(&BankMint::PyUSD);
+    let pyusd_recv_account = test_f.pyusd_mint.create_empty_token_account().await;
+    let res = astrolend_account_f
+        .try_withdraw_emissions_many(&[claims[0], (pyusd_bank, &pyusd_recv_account)])
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err(),
+        AstrolendError::InvalidEmissionsClaimAccounts
+    );
+
+    Ok(())
+}
+
+/// The emissions vault closes once the campaign ran out and every settled token was withdrawn
+#[tokio::test]
+async fn emissions_vault_closed_once_fully_claimed() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    test_f.advance_time(1_700_000_000).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    // 1 SOL per USDC per year, 100 USDC deposited run through 1 SOL in about 4 days
+    let emissions_rate = native!(1, "SOL");
+    let funding_account = test_f.sol_mint.create_token_account_and_mint_to(1).await;
+    usdc_bank
+        .try_setup_emissions(
+            EMISSIONS_FLAG_LENDING_ACTIVE,
+            emissions_rate,
+            native!(1, "SOL"),
+            test_f.sol_mint.key,
+            funding_account.key,
+            anchor_spl::token::ID,
+        )
+        .await?;
+
+    let astrolend_account_f = test_f.create_astrolend_account().await;
+    let token_account_f = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    astrolend_account_f
+        .try_bank_deposit(token_account_f.key, usdc_bank, 100)
+        .await?;
+
+    let admin_account = test_f.sol_mint.create_empty_token_account().await;
+    let res = usdc_bank
+        .try_close_emissions(admin_account.key, anchor_spl::token::ID)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::EmissionsNotFullyClaimed);
+
+    // Settled emissions are counted on the bank until they are withdrawn
+    test_f.advance_time(5 * 86_400).await;
+    usdc_bank
+        .try_settle_emissions_many(&[astrolend_account_f.key])
+        .await?;
+
+    let account = astrolend_account_f.load().await;
+    let balance = account.lending_account.get_balance(&usdc_bank.key).unwrap();
+    let settled: u64 = I80F48::from(balance.emissions_outstanding).to_num();
+    assert!(settled > 0);
+    assert_eq!(usdc_bank.load().await.emissions_outstanding_total, settled);
+
+    let res = usdc_bank
+        .try_close_emissions(admin_account.key, anchor_spl::token::ID)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::EmissionsNotFullyClaimed);
+
+    let recv_account = test_f.sol_mint.create_empty_token_account().await;
+    astrolend_account_f
+        .try_withdraw_emissions(usdc_bank, &recv_account)
+        .await?;
+    assert_eq!(recv_account.balance().await, settled);
+    assert_eq!(usdc_bank.load().await.emissions_outstanding_total, 0);
+
+    // Fractions of a token left in the vault go to the admin
+    let (emissions_vault, _) =
+        get_emissions_token_account_address(usdc_bank.key, test_f.sol_mint.key);
+    usdc_bank
+        .try_close_emissions(admin_account.key, anchor_spl::token::ID)
+        .await?;
+    assert_eq!(admin_account.balance().await, native!(1, "SOL") - settled);
+    assert!(test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(emissions_vault)
+        .await?
+        .is_none());
+
+    let bank = usdc_bank.load().await;
+    assert_eq!(bank.emissions_mint, Pubkey::default());
+    assert_eq!(I80F48::from(bank.emissions_reserved), I80F48::ZERO);
+
+    // A new campaign can be set up, with the same mint
+    let funding_account = test_f.sol_mint.create_token_account_and_mint_to(1).await;
+    usdc_bank
+        .try_setup_emissions(
+            EMISSIONS_FLAG_LENDING_ACTIVE,
+            emissions_rate,
+            native!(1, "SOL"),
+            test_f.sol_mint.key,
+            funding_account.key,
+            anchor_spl::token::ID,
+        )
+        .await?;
+    assert_eq!(usdc_bank.load().await.emissions_mint, test_f.sol_mint.key);
+
+    Ok(())
+}
+
+/// Campaigns funded in a transfer fee mint are credited what the emissions vault received, on
+/// either side of the fee cap, and claims never reach past the vault
+#[tokio::test]
+async fn emissions_funding_with_capped_transfer_fee() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    test_f.advance_time(1_700_000_000).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let emissions_mint = &test_f.get_bank(&BankMint::T22WithFee).mint;
+
+    // 5% transfer fee, capped at 1_000 native tokens from the next epoch on
+    let epoch = test_f.get_clock().await.epoch;
+    emissions_mint
+        .schedule_transfer_fee_with_max(epoch + 1, 500, 1_000)
+        .await;
+    test_f.advance_epoch().await;
+
+    let (emissions_vault, _) =
+        get_emissions_token_account_address(usdc_bank.key, emissions_mint.key);
+    let funding_account = emissions_mint.create_token_account_and_mint_to(1).await;
+
+    // Capped: 100_000 native tokens cost 101_000
+    usdc_bank
+        .try_setup_emissions(
+            EMISSIONS_FLAG_LENDING_ACTIVE,
+            native!(1, "T22_WITH_FEE"),
+            100_000,
+            emissions_mint.key,
+            funding_account.key,
+            anchor_spl::token_2022::ID,
+        )
+        .await?;
+    assert_eq!(funding_account.balance().await, 1_000_000 - 101_000);
+
+    let emissions_vault_f =
+        TokenAccountFixture::fetch(test_f.context.clone(), emissions_vault).await;
+    let bank = usdc_bank.load().await;
+    assert_eq!(
+        I80F48::from(bank.emissions_remaining),
+        I80F48::from_num(100_000)
+    );
+    assert_eq!(emissions_vault_f.balance().await, 100_000);
+
+    // Under the cap: 5% of the pre-fee amount, rounded up
+    usdc_bank
+        .try_update_emissions(
+            None,
+            None,
+            Some((10_001, funding_account.key)),
+            anchor_spl::token_2022::ID,
+        )
+        .await?;
+
+    let bank = usdc_bank.load().await;
+    assert_eq!(
+        I80F48::from(bank.emissions_remaining),
+        I80F48::from_num(110_001)
+    );
+    assert_eq!(emissions_vault_f.balance().await, 110_001);
+
+    // 100 USDC run through the campaign in well under a day
+    let astrolend_account_f = test_f.create_astrolend_account().await;
+    let token_account_f = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    astrolend_account_f
+        .try_bank_deposit(token_account_f.key, usdc_bank, 100)
+        .await?;
+
+    test_f.advance_time(86_400).await;
+    usdc_bank
+        .try_settle_emissions_many(&[astrolend_account_f.key])
+        .await?;
+
+    let account = astrolend_account_f.load().await;
+    let balance = account.lending_account.get_balance(&usdc_bank.key).unwrap();
+    let settled: u64 = I80F48::from(balance.emissions_outstanding).to_num();
+    assert!(settled > 0 && settled <= 110_001);
+    assert_eq!(
+        I80F48::from(usdc_bank.load().await.emissions_remaining),
+        I80F48::ZERO
+    );
+
+    let recv_account = emissions_mint.create_empty_token_account().await;
+    astrolend_account_f
+        .try_withdraw_emissions(usdc_bank, &recv_account)
+        .await?;
+    assert_eq!(emissions_vault_f.balance().await, 110_001 - settled);
+
+    Ok(())
+}
+
+/// Banks whose emissions were set up before the authority bump was stored still pay out
+#[tokio::test]
+async fn emissions_claimable_without_stored_bump() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    test_f.advance_time(1_700_000_000).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let funding_account = test_f.sol_mint.create_token_account_and_mint_to(1_000).await;
+    usdc_bank
+        .try_setup_emissions(
+            EMISSIONS_FLAG_LENDING_ACTIVE,
+            native!(1, "SOL"),
+            native!(1_000, "SOL"),
+            test_f.sol_mint.key,
+            funding_account.key,
+            anchor_spl::token::ID,
+        )
+        .await?;
+
+    let astrolend_account_f = test_f.create_astrolend_account().await;
+    let token_account_f = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    astrolend_account_f
+        .try_bank_deposit(token_account_f.key, usdc_bank, 100)
+        .await?;
+
+    usdc_bank.clear_emissions_auth_bump().await;
+    assert_eq!(usdc_bank.load().await.emissions_auth_bump, 0);
+
+    test_f.advance_time(86_400).await;
+    let recv_account = test_f.sol_mint.create_empty_token_account().await;
+    astrolend_account_f
+        .try_withdraw_emissions(usdc_bank, &recv_account)
+        .await?;
+    let claimed = recv_account.balance().await;
+    assert!(claimed > 0);
+
+    test_f.advance_time(86_400).await;
+    astrolend_account_f
+        .try_withdraw_emissions_many(&[(usdc_bank, &recv_account)])
+        .await?;
+    assert!(recv_account.balance().await > claimed);
+
+    Ok(())
+}
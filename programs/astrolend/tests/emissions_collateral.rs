@@ -0,0 +1,243 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{
+    assert_custom_error, astrolend_account::AstrolendAccountFixture, bank::BankFixture, native,
+    prelude::*,
+};
+use astrolend::{
+    constants::EMISSIONS_FLAG_LENDING_ACTIVE,
+    errors::AstrolendError,
+    events::LendingAccountLiquidateEvent,
+    state::{
+        astrolend_account::AstrolendAccount,
+        astrolend_group::{BankConfigOpt, GroupConfig},
+    },
+};
+use solana_program_test::tokio;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction, signer::Signer, transaction::Transaction,
+};
+
+const ONE_YEAR: i64 = 31_536_000;
+
+async fn advance_time(test_f: &TestFixture, seconds: i64) {
+    test_f.advance_time(seconds).await;
+    let now = test_f.get_clock().await.unix_timestamp;
+    for feed in [PYTH_USDC_FEED, PYTH_SOL_FEED, PYTH_SOL_EQUIVALENT_FEED] {
+        test_f.set_pyth_oracle_timestamp(feed, now).await;
+    }
+}
+
+/// A borrower with 10 USDC deposited in a bank emitting 1 SOL per USDC per year, and 10 SOL of
+/// emissions settled a year later. 1_000 SOL are deposited by a lender.
+async fn setup(test_f: &TestFixture, emissions_collateral: bool) -> AstrolendAccountFixture {
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    // Emissions are ignored for balances last updated before `MIN_EMISSIONS_START_TIME`
+    advance_time(test_f, 1_700_000_000).await;
+
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            emissions_collateral: Some(emissions_collateral),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let funding_account = test_f.sol_mint.create_token_account_and_mint_to(1_000).await;
+    usdc_bank
+        .try_setup_emissions(
+            EMISSIONS_FLAG_LENDING_ACTIVE,
+            native!(1, "SOL"),
+            native!(1_000, "SOL"),
+            test_f.sol_mint.key,
+            funding_account.key,
+            anchor_spl::token::ID,
+        )
+        .await
+        .unwrap();
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_sol = test_f.sol_mint.create_token_account_and_mint_to(1_000).await;
+    lender_f
+        .try_bank_deposit(lender_sol.key, sol_bank, 1_000)
+        .await
+        .unwrap();
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(10).await;
+    borrower_f
+        .try_bank_deposit(borrower_usdc.key, usdc_bank, 10)
+        .await
+        .unwrap();
+
+    advance_time(test_f, ONE_YEAR).await;
+    usdc_bank
+        .try_settle_emissions_many(&[borrower_f.key])
+        .await
+        .unwrap();
+
+    borrower_f
+}
+
+fn emissions_outstanding(account: &AstrolendAccount, bank_f: &BankFixture) -> I80F48 {
+    let balance = account.lending_account.get_balance(&bank_f.key).unwrap();
+
+    balance.emissions_outstanding.into()
+}
+
+#[tokio::test]
+async fn settled_emissions_ignored_without_flag() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let borrower_f = setup(&test_f, false).await;
+    borrower_f.set_emissions_banks(&[sol_bank]);
+
+    // Only the $10 of USDC collateralize the borrow
+    let borrower_sol = test_f.sol_mint.create_empty_token_account().await;
+    let res = borrower_f
+        .try_bank_borrow(borrower_sol.key, sol_bank, 5)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::RiskEngineInitRejected);
+
+    borrower_f
+        .try_bank_borrow(borrower_sol.key, sol_bank, 0.9)
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn settled_emissions_collateralize_borrows_with_flag() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let borrower_f = setup(&test_f, true).await;
+    let borrower_sol = test_f.sol_mint.create_empty_token_account().await;
+
+    let settled = emissions_outstanding(&borrower_f.load().await, usdc_bank);
+    assert!(settled > I80F48::from_num(native!(9.99, "SOL", f64)));
+
+    // The emissions observation accounts are optional, without them only the USDC counts
+    let res = borrower_f
+        .try_bank_borrow(borrower_sol.key, sol_bank, 5)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::RiskEngineInitRejected);
+
+    // $10 of USDC and $100 of SOL emissions
+    borrower_f.set_emissions_banks(&[sol_bank]);
+    borrower_f
+        .try_bank_borrow(borrower_sol.key, sol_bank, 10)
+        .await?;
+
+    // Claiming the emissions takes away the collateral of the borrow
+    let claim_account = test_f.sol_mint.create_empty_token_account().await;
+    let res = borrower_f
+        .try_withdraw_emissions(usdc_bank, &claim_account)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::RiskEngineInitRejected);
+
+    let res = borrower_f
+        .try_withdraw_emissions_many(&[(usdc_bank, &claim_account)])
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err(),
+        AstrolendError::InvalidEmissionsClaimAccounts
+    );
+
+    assert_eq!(
+        emissions_outstanding(&borrower_f.load().await, usdc_bank),
+        settled
+    );
+    assert_eq!(claim_account.balance().await, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn liquidation_ignores_unsettled_emissions() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let sol_eq_bank = test_f.get_bank(&BankMint::SolEquivalent);
+    let liquidatee_f = setup(&test_f, true).await;
+    liquidatee_f.set_emissions_banks(&[sol_bank]);
+
+    let liquidatee_sol_eq = test_f
+        .sol_equivalent_mint
+        .create_token_account_and_mint_to(1)
+        .await;
+    liquidatee_f
+        .try_bank_deposit(liquidatee_sol_eq.key, sol_eq_bank, 1)
+        .await?;
+
+    // $120 of collateral, $100 of it in settled emissions
+    let liquidatee_sol = test_f.sol_mint.create_empty_token_account().await;
+    liquidatee_f
+        .try_bank_borrow(liquidatee_sol.key, sol_bank, 10)
+        .await?;
+
+    // Emissions now weigh $50 and the SOL_EQ deposit $5, for a maintenance health of -$35
+    for bank in [sol_bank, sol_eq_bank] {
+        bank.update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.5).into()),
+            asset_weight_maint: Some(I80F48!(0.5).into()),
+            ..Default::default()
+        })
+        .await?;
+    }
+
+    // Another 10 SOL accrue, worth another $50 once settled
+    advance_time(&test_f, ONE_YEAR).await;
+    let settled = emissions_outstanding(&liquidatee_f.load().await, usdc_bank);
+
+    let liquidator_f = test_f.create_astrolend_account().await;
+    let liquidator_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    liquidator_f
+        .try_bank_deposit(liquidator_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let ix = liquidator_f
+        .make_liquidate_ix(&liquidatee_f, sol_eq_bank, 0.1, sol_bank)
+        .await;
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(1_400_000),
+                ix,
+            ],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await?
+    };
+    assert!(result.result.is_ok());
+
+    // Valued with the settled emissions only, -$85 without them and $15 with the unsettled ones
+    let events = parse_events::<LendingAccountLiquidateEvent>(&result);
+    assert_eq!(events.len(), 1);
+    assert!(events[0].liquidatee_pre_health < -30.);
+    assert!(events[0].liquidatee_pre_health > -40.);
+    assert!(events[0].liquidatee_post_health > events[0].liquidatee_pre_health);
+
+    // The liquidation left the emitting balance alone, its emissions are still unsettled
+    assert_eq!(
+        emissions_outstanding(&liquidatee_f.load().await, usdc_bank),
+        settled
+    );
+
+    Ok(())
+}
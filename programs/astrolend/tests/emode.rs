@@ -0,0 +1,174 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{assert_custom_error, astrolend_account::AstrolendAccountFixture, prelude::*};
+use astrolend::{
+    errors::AstrolendError,
+    state::astrolend_group::{BankConfigOpt, EmodeEntryConfig},
+};
+use solana_program_test::tokio;
+
+const SOL_TAG: u16 = 1;
+
+/// SOL and SOL equivalent banks share a tag, SOL collateral is boosted from 0.5/0.6 to
+/// `init`/`maint` when the account only borrows SOL equivalent
+async fn setup_emode(test_f: &TestFixture, init: f64, maint: f64) -> anyhow::Result<()> {
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let sol_eq_bank = test_f.get_bank(&BankMint::SolEquivalent);
+
+    sol_bank
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.5).into()),
+            asset_weight_maint: Some(I80F48!(0.6).into()),
+            emode_tag: Some(SOL_TAG),
+            ..Default::default()
+        })
+        .await?;
+    sol_eq_bank
+        .update_config(BankConfigOpt {
+            emode_tag: Some(SOL_TAG),
+            ..Default::default()
+        })
+        .await?;
+
+    configure_sol_pair(test_f, init, maint).await?;
+
+    Ok(())
+}
+
+async fn configure_sol_pair(test_f: &TestFixture, init: f64, maint: f64) -> anyhow::Result<()> {
+    test_f
+        .astrolend_group
+        .try_configure_emode(vec![EmodeEntryConfig {
+            collateral_tag: SOL_TAG,
+            liability_tag: SOL_TAG,
+            asset_weight_init: I80F48::from_num(init).into(),
+            asset_weight_maint: I80F48::from_num(maint).into(),
+        }])
+        .await?;
+
+    Ok(())
+}
+
+async fn create_lender(test_f: &TestFixture) -> anyhow::Result<AstrolendAccountFixture> {
+    let lender_f = test_f.create_astrolend_account().await;
+
+    let lender_sol_eq = test_f
+        .sol_equivalent_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(
+            lender_sol_eq.key,
+            test_f.get_bank(&BankMint::SolEquivalent),
+            1_000,
+        )
+        .await?;
+
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(10_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, test_f.get_bank(&BankMint::Usdc), 10_000)
+        .await?;
+
+    Ok(lender_f)
+}
+
+#[tokio::test]
+async fn emode_boost_requires_only_paired_liabilities() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    setup_emode(&test_f, 0.9, 0.95).await?;
+    create_lender(&test_f).await?;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let sol_eq_bank = test_f.get_bank(&BankMint::SolEquivalent);
+
+    // 10 SOL = $100 of collateral, $50 of borrowing power without the boost
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    let borrower_sol_eq = test_f
+        .sol_equivalent_mint
+        .create_token_account_and_mint_to(0)
+        .await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(20).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 10)
+        .await?;
+
+    // Mixed liabilities disqualify the boost
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 10)
+        .await?;
+    let res = borrower_f
+        .try_bank_borrow(borrower_sol_eq.key, sol_eq_bank, 6)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::RiskEngineInitRejected);
+
+    // Repaying down to only the paired liability re-enables it
+    borrower_f
+        .try_bank_repay(borrower_usdc.key, usdc_bank, 0, Some(true))
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_sol_eq.key, sol_eq_bank, 6.1)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_sol_eq.key, sol_eq_bank, 2.4)
+        .await?;
+
+    // $85 borrowed, right below the $90 boosted limit
+    let res = borrower_f
+        .try_bank_borrow(borrower_sol_eq.key, sol_eq_bank, 0.6)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::RiskEngineInitRejected);
+
+    // Any unpaired liability drops the account back to the base weights
+    let res = borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 1)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::RiskEngineInitRejected);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn emode_liquidation_uses_maint_override() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    setup_emode(&test_f, 0.9, 0.9).await?;
+    let liquidator_f = create_lender(&test_f).await?;
+
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let sol_eq_bank = test_f.get_bank(&BankMint::SolEquivalent);
+
+    let liquidatee_f = test_f.create_astrolend_account().await;
+    let liquidatee_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    let liquidatee_sol_eq = test_f
+        .sol_equivalent_mint
+        .create_token_account_and_mint_to(0)
+        .await;
+    liquidatee_f
+        .try_bank_deposit(liquidatee_sol.key, sol_bank, 10)
+        .await?;
+    liquidatee_f
+        .try_bank_borrow(liquidatee_sol_eq.key, sol_eq_bank, 8.5)
+        .await?;
+
+    // Healthy at the 0.9 maint override, despite the base 0.6 maint weight
+    let res = liquidator_f
+        .try_liquidate(&liquidatee_f, sol_bank, 0.4, sol_eq_bank)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::IllegalLiquidation);
+
+    // $80 of maint collateral against $85 of liabilities
+    configure_sol_pair(&test_f, 0.75, 0.8).await?;
+    liquidator_f
+        .try_liquidate(&liquidatee_f, sol_bank, 0.5, sol_eq_bank)
+        .await?;
+
+    Ok(())
+}
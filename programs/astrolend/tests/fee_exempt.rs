@@ -0,0 +1,161 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{assert_eq_noise, astrolend_account::AstrolendAccountFixture, native, prelude::*};
+use astrolend::{
+    events::{LendingAccountBorrowEvent, LendingAccountWithdrawEvent},
+    state::{
+        astrolend_account::FEE_EXEMPT_FLAG,
+        astrolend_group::{BankConfigOpt, InterestRateConfigOpt},
+    },
+};
+use solana_program::instruction::Instruction;
+use solana_program_test::tokio;
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+async fn send_for_event<T: anchor_lang::Event>(test_f: &TestFixture, ix: Instruction) -> T {
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await
+            .unwrap()
+    };
+
+    assert!(result.result.is_ok());
+
+    let logs = result.metadata.unwrap().log_messages;
+    find_event::<T>(&logs).unwrap()
+}
+
+/// 1% origination and withdraw fees on the USDC bank, 1_000 USDC lent out
+async fn setup_usdc_fees(test_f: &TestFixture) -> anyhow::Result<()> {
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    usdc_bank
+        .update_config(BankConfigOpt {
+            interest_rate_config: Some(InterestRateConfigOpt {
+                protocol_origination_fee: Some(I80F48!(0.01).into()),
+                ..Default::default()
+            }),
+            withdraw_fee_rate: Some(I80F48!(0.01).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    Ok(())
+}
+
+/// Account with 100 SOL of collateral
+async fn create_borrower(test_f: &TestFixture) -> anyhow::Result<AstrolendAccountFixture> {
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, test_f.get_bank(&BankMint::Sol), 100)
+        .await?;
+
+    Ok(borrower_f)
+}
+
+#[tokio::test]
+async fn fee_exempt_account_borrows_without_origination_fee() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    setup_usdc_fees(&test_f).await?;
+
+    let exempt_f = create_borrower(&test_f).await?;
+    let regular_f = create_borrower(&test_f).await?;
+    exempt_f.try_set_flag(FEE_EXEMPT_FLAG).await?;
+    let exempt_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    let regular_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+
+    let ix = exempt_f
+        .make_bank_borrow_ix(exempt_usdc.key, usdc_bank, 100)
+        .await;
+    let event: LendingAccountBorrowEvent = send_for_event(&test_f, ix).await;
+    assert!(event.fee_waived);
+    assert_eq!(event.origination_fee, 0.);
+    exempt_f
+        .assert_liability_approx_eq(usdc_bank, I80F48::from(native!(100, "USDC")), I80F48::ZERO)
+        .await;
+
+    let ix = regular_f
+        .make_bank_borrow_ix(regular_usdc.key, usdc_bank, 100)
+        .await;
+    let event: LendingAccountBorrowEvent = send_for_event(&test_f, ix).await;
+    assert!(!event.fee_waived);
+    assert_eq_noise!(event.origination_fee, native!(1, "USDC") as f64, 1.);
+    regular_f
+        .assert_liability_approx_eq(usdc_bank, I80F48::from(native!(101, "USDC")), I80F48::ONE)
+        .await;
+
+    // Both received what they asked for
+    assert_eq!(exempt_usdc.balance().await, native!(100, "USDC"));
+    assert_eq!(regular_usdc.balance().await, native!(100, "USDC"));
+
+    // Clearing the flag brings the fee back
+    exempt_f.try_unset_flag(FEE_EXEMPT_FLAG).await?;
+    let ix = exempt_f
+        .make_bank_borrow_ix(exempt_usdc.key, usdc_bank, 50)
+        .await;
+    let event: LendingAccountBorrowEvent = send_for_event(&test_f, ix).await;
+    assert!(!event.fee_waived);
+    assert_eq_noise!(event.origination_fee, native!(0.5, "USDC", f64) as f64, 1.);
+    exempt_f
+        .assert_liability_approx_eq(
+            usdc_bank,
+            I80F48::from(native!(150.5, "USDC", f64)),
+            I80F48::ONE,
+        )
+        .await;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn fee_exempt_account_withdraws_without_withdraw_fee() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    setup_usdc_fees(&test_f).await?;
+
+    let depositor_f = test_f.create_astrolend_account().await;
+    let depositor_usdc = test_f.usdc_mint.create_token_account_and_mint_to(200).await;
+    depositor_f
+        .try_bank_deposit(depositor_usdc.key, usdc_bank, 200)
+        .await?;
+    depositor_f.try_set_flag(FEE_EXEMPT_FLAG).await?;
+
+    let ix = depositor_f
+        .make_bank_withdraw_ix(depositor_usdc.key, usdc_bank, 100, None)
+        .await;
+    let event: LendingAccountWithdrawEvent = send_for_event(&test_f, ix).await;
+    assert!(event.fee_waived);
+    assert_eq!(event.amount, native!(100, "USDC"));
+    assert_eq!(event.amount_net, native!(100, "USDC"));
+    assert_eq!(depositor_usdc.balance().await, native!(100, "USDC"));
+
+    depositor_f.try_unset_flag(FEE_EXEMPT_FLAG).await?;
+    let ix = depositor_f
+        .make_bank_withdraw_ix(depositor_usdc.key, usdc_bank, 50, None)
+        .await;
+    let event: LendingAccountWithdrawEvent = send_for_event(&test_f, ix).await;
+    assert!(!event.fee_waived);
+    assert_eq!(event.amount, native!(50, "USDC"));
+    assert_eq!(event.amount_net, native!(49.5, "USDC", f64));
+    assert_eq!(depositor_usdc.balance().await, native!(149.5, "USDC", f64));
+
+    Ok(())
+}
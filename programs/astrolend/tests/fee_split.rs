@@ -0,0 +1,207 @@
+use astrolend::{
+    errors::AstrolendError,
+    events::LendingPoolDistributeFeesEvent,
+    state::astrolend_group::{BankVaultType, FeeSplitEntryConfig},
+};
+use fixtures::{assert_custom_error, bank::BankFixture, prelude::*};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+const TRANSFER_FEE_BPS: u64 = 100;
+
+/// Treasury, insurance backstop and grants wallets splitting 70/20/10
+async fn configure_split(test_f: &TestFixture) -> anyhow::Result<Vec<Pubkey>> {
+    let wallets = vec![
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    ];
+    test_f
+        .astrolend_group
+        .try_configure_fee_split(
+            wallets
+                .iter()
+                .zip([7_000, 2_000, 1_000])
+                .map(|(destination, bps)| FeeSplitEntryConfig {
+                    destination: *destination,
+                    bps,
+                })
+                .collect(),
+        )
+        .await?;
+
+    Ok(wallets)
+}
+
+async fn create_destinations(
+    test_f: &TestFixture,
+    mint: &MintFixture,
+    wallets: &[Pubkey],
+) -> Vec<TokenAccountFixture> {
+    let mut destinations = vec![];
+    for wallet in wallets {
+        destinations
+            .push(TokenAccountFixture::new_associated(test_f.context.clone(), mint, wallet).await);
+    }
+
+    destinations
+}
+
+async fn fund_fee_vault(test_f: &TestFixture, bank_f: &BankFixture, amount: u64) {
+    let fee_vault = bank_f.get_vault_token_account(BankVaultType::Fee).await;
+    let ix = bank_f.mint.make_mint_to_ix(&fee_vault.key, amount);
+
+    let mut ctx = test_f.context.borrow_mut();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer],
+        ctx.last_blockhash,
+    );
+    ctx.banks_client.process_transaction(tx).await.unwrap();
+}
+
+#[tokio::test]
+async fn distribute_fees_splits_by_bps() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let wallets = configure_split(&test_f).await?;
+    let destinations = create_destinations(&test_f, &test_f.usdc_mint, &wallets).await;
+    let destination_keys: Vec<Pubkey> = destinations.iter().map(|d| d.key).collect();
+
+    // 699.3 + 199.8 + 99.9, the 2 units of dust go to the treasury
+    fund_fee_vault(&test_f, usdc_bank, 999).await;
+
+    let ix = usdc_bank.make_distribute_fees_ix(&destination_keys).await;
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await?
+    };
+    assert!(result.result.is_ok());
+
+    let expected = [701, 199, 99];
+    for (destination, amount) in destinations.iter().zip(expected) {
+        assert_eq!(destination.balance().await, amount);
+    }
+    let fee_vault = usdc_bank.get_vault_token_account(BankVaultType::Fee).await;
+    assert_eq!(fee_vault.balance().await, 0);
+    assert_eq!(usdc_bank.load().await.lifetime_fees_withdrawn, 999);
+
+    let events = parse_events::<LendingPoolDistributeFeesEvent>(&result);
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event.bank, usdc_bank.key);
+    assert_eq!(event.amount, 999);
+    assert_eq!(event.lifetime_withdrawn, 999);
+    assert_eq!(event.transfers.len(), 3);
+    for ((transfer, destination), amount) in
+        event.transfers.iter().zip(&destination_keys).zip(expected)
+    {
+        assert_eq!(transfer.destination, *destination);
+        assert_eq!(transfer.amount, amount);
+    }
+
+    Ok(())
+}
+
+/// The vault is debited by the exact shares, the transfer fee comes out of what each
+/// destination receives
+#[tokio::test]
+async fn distribute_fees_with_transfer_fee_mint() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let bank_f = test_f.get_bank(&BankMint::T22WithFee);
+
+    // The test mint charges 5%, lower it to 1% from the next epoch on and move into it
+    let epoch = test_f.get_clock().await.epoch;
+    bank_f
+        .mint
+        .schedule_transfer_fee(epoch + 1, TRANSFER_FEE_BPS as u16)
+        .await;
+    test_f.advance_epoch().await;
+
+    let wallets = configure_split(&test_f).await?;
+    let destinations = create_destinations(&test_f, &bank_f.mint, &wallets).await;
+    let destination_keys: Vec<Pubkey> = destinations.iter().map(|d| d.key).collect();
+
+    fund_fee_vault(&test_f, bank_f, 1_000_001).await;
+    let fee_vault = bank_f.get_vault_token_account(BankVaultType::Fee).await;
+    let amount = fee_vault.balance().await;
+
+    bank_f.try_distribute_fees(&destination_keys).await?;
+
+    let shares = [
+        amount * 7_000 / 10_000,
+        amount * 2_000 / 10_000,
+        amount * 1_000 / 10_000,
+    ];
+    let dust = amount - shares.iter().sum::<u64>();
+    assert!(dust > 0);
+
+    for (i, (destination, share)) in destinations.iter().zip(shares).enumerate() {
+        let share = if i == 0 { share + dust } else { share };
+        let fee = (share * TRANSFER_FEE_BPS).div_ceil(10_000);
+        assert_eq!(destination.balance().await, share - fee);
+    }
+    assert_eq!(fee_vault.balance().await, 0);
+    assert_eq!(bank_f.load().await.lifetime_fees_withdrawn, amount);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn distribute_fees_requires_destinations_in_order() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    fund_fee_vault(&test_f, usdc_bank, 1_000).await;
+
+    let wallets = configure_split(&test_f).await?;
+    let destinations = create_destinations(&test_f, &test_f.usdc_mint, &wallets).await;
+
+    let swapped = [destinations[1].key, destinations[0].key, destinations[2].key];
+    let res = usdc_bank.try_distribute_fees(&swapped).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidFeeSplitDestination);
+
+    let missing = [destinations[0].key, destinations[1].key];
+    let res = usdc_bank.try_distribute_fees(&missing).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidFeeSplitDestination);
+
+    let fee_vault = usdc_bank.get_vault_token_account(BankVaultType::Fee).await;
+    assert_eq!(fee_vault.balance().await, 1_000);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn distribute_fees_requires_a_split() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    fund_fee_vault(&test_f, usdc_bank, 1_000).await;
+
+    let res = usdc_bank.try_distribute_fees(&[]).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::FeeSplitNotSet);
+
+    // An empty table clears the split again
+    let wallets = configure_split(&test_f).await?;
+    let destinations = create_destinations(&test_f, &test_f.usdc_mint, &wallets).await;
+    test_f.astrolend_group.try_configure_fee_split(vec![]).await?;
+
+    let destination_keys: Vec<Pubkey> = destinations.iter().map(|d| d.key).collect();
+    let res = usdc_bank.try_distribute_fees(&destination_keys).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::FeeSplitNotSet);
+
+    Ok(())
+}
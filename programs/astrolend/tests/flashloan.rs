@@ -0,0 +1,166 @@
+use astrolend::{errors::AstrolendError, state::astrolend_account::IN_FLASHLOAN_FLAG};
+use fixtures::{astrolend_account::AstrolendAccountFixture, native, prelude::*};
+use solana_program::{pubkey::Pubkey, system_instruction};
+use solana_program_test::tokio;
+
+/// The account deposits 10 SOL ($100), enough for a 50 USDC borrow
+async fn setup() -> (TestFixture, AstrolendAccountFixture, TokenAccountFixture) {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(10_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 10_000)
+        .await
+        .unwrap();
+
+    let user_f = test_f.create_astrolend_account().await;
+    let user_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    user_f
+        .try_bank_deposit(user_sol.key, sol_bank, 10)
+        .await
+        .unwrap();
+    let user_usdc = test_f.usdc_mint.create_empty_token_account().await;
+
+    (test_f, user_f, user_usdc)
+}
+
+/// Borrows 5_000 USDC inside the flashloan, far over the initial requirement
+#[tokio::test]
+async fn end_flashloan_enforces_health_with_every_oracle() -> anyhow::Result<()> {
+    let (test_f, user_f, user_usdc) = setup().await;
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let start_ix = user_f.make_lending_account_start_flashloan_ix(2).await;
+    let borrow_ix = user_f
+        .make_bank_borrow_ix(user_usdc.key, usdc_bank, 5_000)
+        .await;
+
+    // The end account list is built before the borrow, it lacks the USDC bank and oracle
+    let end_ix = user_f
+        .make_lending_account_end_flashloan_ix(vec![], vec![])
+        .await;
+    let simulation = test_f
+        .simulate_tx(&[start_ix.clone(), borrow_ix.clone(), end_ix], &[])
+        .await;
+    assert_eq!(
+        simulation.custom_error_code(),
+        Some(AstrolendError::MissingPythOrBankAccount.into())
+    );
+
+    // Nor can the liability be valued without its oracle
+    let end_ix = user_f
+        .make_lending_account_end_flashloan_ix(vec![usdc_bank.key], vec![sol_bank.key])
+        .await;
+    let simulation = test_f
+        .simulate_tx(&[start_ix.clone(), borrow_ix.clone(), end_ix], &[])
+        .await;
+    assert_eq!(
+        simulation.custom_error_code(),
+        Some(AstrolendError::MissingPythOrBankAccount.into())
+    );
+
+    // With every oracle the deferred check runs, and rejects the borrow
+    let end_ix = user_f
+        .make_lending_account_end_flashloan_ix(vec![usdc_bank.key], vec![])
+        .await;
+    let simulation = test_f
+        .simulate_tx(&[start_ix, borrow_ix, end_ix], &[])
+        .await;
+    assert_eq!(
+        simulation.custom_error_code(),
+        Some(AstrolendError::RiskEngineInitRejected.into())
+    );
+
+    // A borrow the collateral covers goes through
+    let borrow_ix = user_f
+        .make_bank_borrow_ix(user_usdc.key, usdc_bank, 50)
+        .await;
+    user_f
+        .try_flashloan(vec![borrow_ix], vec![], vec![usdc_bank.key])
+        .await?;
+    assert_eq!(user_usdc.balance().await, native!(50, "USDC"));
+    assert!(!user_f.load().await.get_flag(IN_FLASHLOAN_FLAG));
+
+    Ok(())
+}
+
+/// `start_flashloan` looks up its `end_flashloan` through instruction introspection
+#[tokio::test]
+async fn start_flashloan_requires_its_end() -> anyhow::Result<()> {
+    let (test_f, user_f, user_usdc) = setup().await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let borrow_ix = user_f
+        .make_bank_borrow_ix(user_usdc.key, usdc_bank, 5_000)
+        .await;
+
+    // No instruction at the end index
+    let start_ix = user_f.make_lending_account_start_flashloan_ix(2).await;
+    let simulation = test_f
+        .simulate_tx(&[start_ix, borrow_ix.clone()], &[])
+        .await;
+    assert!(simulation.err.is_some());
+
+    // The end index points at the borrow
+    let start_ix = user_f.make_lending_account_start_flashloan_ix(1).await;
+    let simulation = test_f
+        .simulate_tx(&[start_ix, borrow_ix.clone()], &[])
+        .await;
+    assert_eq!(
+        simulation.custom_error_code(),
+        Some(AstrolendError::IllegalFlashloan.into())
+    );
+
+    // An end for another account
+    let other_f = test_f.create_astrolend_account().await;
+    let start_ix = user_f.make_lending_account_start_flashloan_ix(2).await;
+    let end_ix = other_f
+        .make_lending_account_end_flashloan_ix(vec![], vec![])
+        .await;
+    let simulation = test_f
+        .simulate_tx(&[start_ix, borrow_ix, end_ix], &[])
+        .await;
+    assert_eq!(
+        simulation.custom_error_code(),
+        Some(AstrolendError::IllegalFlashloan.into())
+    );
+
+    assert!(!user_f.load().await.lending_account.has_liabilities());
+    assert_eq!(user_usdc.balance().await, 0);
+
+    Ok(())
+}
+
+/// Other programs may run between the start and end of the flashloan
+#[tokio::test]
+async fn flashloan_around_foreign_instruction() -> anyhow::Result<()> {
+    let (test_f, user_f, user_usdc) = setup().await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let borrow_ix = user_f
+        .make_bank_borrow_ix(user_usdc.key, usdc_bank, 5_000)
+        .await;
+    let foreign_ix =
+        system_instruction::transfer(&test_f.payer(), &Pubkey::new_unique(), 1_000_000);
+    let repay_ix = user_f
+        .make_bank_repay_ix(user_usdc.key, usdc_bank, 5_000, Some(true))
+        .await;
+
+    user_f
+        .try_flashloan(vec![borrow_ix, foreign_ix, repay_ix], vec![], vec![])
+        .await?;
+
+    let user = user_f.load().await;
+    assert!(!user.get_flag(IN_FLASHLOAN_FLAG));
+    assert!(!user.lending_account.has_liabilities());
+    assert_eq!(user_usdc.balance().await, 0);
+
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use anchor_lang::Discriminator;
+use fixed_macro::types::I80F48;
+use astrolend::{
+    events::{AstrolendGroupConfigureEvent, EVENT_VERSION},
+    instruction::AstrolendGroupConfigure,
+    state::astrolend_group::GroupConfig,
+};
+use fixtures::prelude::*;
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+async fn group_data(test_f: &TestFixture) -> Vec<u8> {
+    test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(test_f.astrolend_group.key)
+        .await
+        .unwrap()
+        .unwrap()
+        .data
+}
+
+#[tokio::test]
+async fn configure_group_emits_admin_change() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let old_admin = test_f.payer();
+    let new_admin = Pubkey::new_unique();
+
+    let ix = test_f.astrolend_group.make_update_ix(GroupConfig {
+        admin: Some(new_admin),
+        ..Default::default()
+    });
+
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await?
+    };
+
+    assert!(result.result.is_ok());
+
+    let events = parse_events::<AstrolendGroupConfigureEvent>(&result);
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+
+    assert_eq!(event.meta.version, EVENT_VERSION);
+    assert_eq!(
+        event.meta.instruction,
+        AstrolendGroupConfigure::DISCRIMINATOR
+    );
+    assert_eq!(event.old_admin, old_admin);
+    assert_eq!(event.new_admin, new_admin);
+    assert_eq!(test_f.astrolend_group.load().await.admin, new_admin);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn configure_group_emits_every_old_and_new_value() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+    let group_f = &test_f.astrolend_group;
+
+    group_f
+        .try_update(GroupConfig {
+            group_tvl_cap_usd: Some(1_000_000),
+            whitelist_required: Some(true),
+            ..Default::default()
+        })
+        .await?;
+    let old_values = group_f.load().await.get_config_values();
+
+    let ix = group_f.make_update_ix(GroupConfig {
+        max_borrow_value_per_slot_usd: Some(1_000),
+        liquidation_protocol_fee_pct: Some(I80F48!(0.01).into()),
+        health_snapshot_events: Some(true),
+        whitelist_required: Some(false),
+        min_liquidation_value_usd: Some(5),
+        emissions_collateral: Some(true),
+        init_health_buffer_bps: Some(200),
+        oracle_change_max_deviation_bps: Some(500),
+        config_timelock_secs: Some(3_600),
+        ..Default::default()
+    });
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await?
+    };
+    assert!(result.result.is_ok());
+
+    let events = parse_events::<AstrolendGroupConfigureEvent>(&result);
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+
+    assert_eq!(event.old_values, old_values);
+    assert_eq!(event.new_values, group_f.load().await.get_config_values());
+
+    // Fields left out of the config are reported unchanged
+    assert_eq!(event.new_values.group_tvl_cap_usd, 1_000_000);
+    assert!(event.old_values.whitelist_required);
+    assert!(!event.new_values.whitelist_required);
+    assert_eq!(event.old_values.max_borrow_value_per_slot_usd, 0);
+    assert_eq!(event.new_values.max_borrow_value_per_slot_usd, 1_000);
+    assert_eq!(event.new_values.init_health_buffer_bps, 200);
+    assert_eq!(event.new_values.oracle_change_max_deviation_bps, 500);
+    assert_eq!(event.new_values.config_timelock_secs, 3_600);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn empty_group_config_is_noop() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+    let group_f = &test_f.astrolend_group;
+
+    group_f
+        .try_update(GroupConfig {
+            max_borrow_value_per_slot_usd: Some(1_000),
+            liquidation_protocol_fee_pct: Some(I80F48!(0.01).into()),
+            group_tvl_cap_usd: Some(1_000_000),
+            health_snapshot_events: Some(true),
+            whitelist_required: Some(true),
+            min_liquidation_value_usd: Some(5),
+            ..Default::default()
+        })
+        .await?;
+
+    let configured = group_data(&test_f).await;
+
+    group_f.try_update(GroupConfig::default()).await?;
+    assert_eq!(group_data(&test_f).await, configured);
+
+    Ok(())
+}
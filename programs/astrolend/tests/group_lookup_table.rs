@@ -0,0 +1,86 @@
+use fixtures::{assert_custom_error, prelude::*};
+use astrolend::errors::AstrolendError;
+use solana_program::{
+    address_lookup_table::{
+        self,
+        state::{AddressLookupTable, LookupTableMeta},
+    },
+    pubkey::Pubkey,
+};
+use solana_program_test::tokio;
+use solana_sdk::account::Account;
+use std::borrow::Cow;
+
+/// Writes a lookup table holding `addresses` at a new address
+fn create_lookup_table(test_f: &TestFixture, addresses: &[Pubkey]) -> Pubkey {
+    let lookup_table = AddressLookupTable {
+        meta: LookupTableMeta::default(),
+        addresses: Cow::Borrowed(addresses),
+    };
+
+    let key = Pubkey::new_unique();
+    test_f.context.borrow_mut().set_account(
+        &key,
+        &Account {
+            lamports: 1_000_000_000,
+            data: lookup_table.serialize_for_tests().unwrap(),
+            owner: address_lookup_table::program::ID,
+            ..Default::default()
+        }
+        .into(),
+    );
+
+    key
+}
+
+#[tokio::test]
+async fn set_group_lookup_table() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let lookup_table = create_lookup_table(
+        &test_f,
+        &[usdc_bank.key, PYTH_USDC_FEED, sol_bank.key, PYTH_SOL_FEED],
+    );
+
+    test_f
+        .astrolend_group
+        .try_set_lookup_table(lookup_table, &[usdc_bank, sol_bank])
+        .await?;
+
+    let astrolend_group = test_f.astrolend_group.load().await;
+    assert_eq!(astrolend_group.registered_lookup_table, lookup_table);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn set_group_lookup_table_rejects_invalid_tables() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    // Not owned by the lookup table program
+    let res = test_f
+        .astrolend_group
+        .try_set_lookup_table(test_f.usdc_mint.key, &[usdc_bank])
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidLookupTable);
+
+    // Missing the SOL oracle
+    let lookup_table =
+        create_lookup_table(&test_f, &[usdc_bank.key, PYTH_USDC_FEED, sol_bank.key]);
+    let res = test_f
+        .astrolend_group
+        .try_set_lookup_table(lookup_table, &[usdc_bank, sol_bank])
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidLookupTable);
+
+    let astrolend_group = test_f.astrolend_group.load().await;
+    assert_eq!(astrolend_group.registered_lookup_table, Pubkey::default());
+
+    Ok(())
+}
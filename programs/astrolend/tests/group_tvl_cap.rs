@@ -0,0 +1,187 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{assert_custom_error, assert_eq_noise, prelude::*};
+use astrolend::{
+    errors::AstrolendError, state::astrolend_group::GroupConfig, utils::find_receipt_mint_pda,
+};
+use solana_program_test::tokio;
+
+async fn set_tvl_cap(test_f: &TestFixture, group_tvl_cap_usd: u64) -> anyhow::Result<()> {
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            group_tvl_cap_usd: Some(group_tvl_cap_usd),
+            ..Default::default()
+        })
+        .await?;
+
+    Ok(())
+}
+
+async fn group_tvl(test_f: &TestFixture) -> I80F48 {
+    test_f.astrolend_group.load().await.group_tvl_usd.into()
+}
+
+#[tokio::test]
+async fn group_tvl_cap_limits_deposits() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    set_tvl_cap(&test_f, 1_000).await?;
+
+    let depositor_f = test_f.create_astrolend_account().await;
+    let depositor_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let depositor_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+
+    // $500 + 50 SOL @ $10 sits exactly at the cap
+    depositor_f
+        .try_bank_deposit(depositor_usdc.key, usdc_bank, 500)
+        .await?;
+    depositor_f
+        .try_bank_deposit(depositor_sol.key, sol_bank, 50)
+        .await?;
+    assert_eq_noise!(group_tvl(&test_f).await, I80F48!(1_000), I80F48!(0.001));
+
+    let res = depositor_f
+        .try_bank_deposit(depositor_usdc.key, usdc_bank, 1)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::GroupTvlCapExceeded);
+
+    // Raising the cap lets deposits through again
+    set_tvl_cap(&test_f, 1_100).await?;
+    depositor_f
+        .try_bank_deposit(depositor_usdc.key, usdc_bank, 100)
+        .await?;
+    assert_eq_noise!(group_tvl(&test_f).await, I80F48!(1_100), I80F48!(0.001));
+
+    // Disabling the cap stops tracking
+    set_tvl_cap(&test_f, 0).await?;
+    depositor_f
+        .try_bank_deposit(depositor_usdc.key, usdc_bank, 200)
+        .await?;
+    assert_eq_noise!(group_tvl(&test_f).await, I80F48!(1_100), I80F48!(0.001));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn group_tvl_tracks_deposit_withdraw_cycles() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    set_tvl_cap(&test_f, 1_000).await?;
+
+    let depositor_f = test_f.create_astrolend_account().await;
+    let depositor_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let depositor_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+
+    depositor_f
+        .try_bank_deposit(depositor_usdc.key, usdc_bank, 400)
+        .await?;
+    depositor_f
+        .try_bank_deposit(depositor_sol.key, sol_bank, 60)
+        .await?;
+
+    // Withdrawals free up room under the cap
+    depositor_f
+        .try_bank_withdraw(depositor_sol.key, sol_bank, 20, None)
+        .await?;
+    assert_eq_noise!(group_tvl(&test_f).await, I80F48!(800), I80F48!(0.001));
+
+    depositor_f
+        .try_bank_deposit(depositor_usdc.key, usdc_bank, 200)
+        .await?;
+    assert_eq_noise!(group_tvl(&test_f).await, I80F48!(1_000), I80F48!(0.001));
+
+    // Lowering the cap below the current TVL blocks deposits, not withdrawals
+    set_tvl_cap(&test_f, 500).await?;
+    let res = depositor_f
+        .try_bank_deposit(depositor_usdc.key, usdc_bank, 1)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::GroupTvlCapExceeded);
+
+    depositor_f
+        .try_bank_withdraw(depositor_usdc.key, usdc_bank, 0, Some(true))
+        .await?;
+    assert_eq_noise!(group_tvl(&test_f).await, I80F48!(400), I80F48!(0.001));
+
+    depositor_f
+        .try_bank_deposit(depositor_usdc.key, usdc_bank, 100)
+        .await?;
+    let res = depositor_f
+        .try_bank_deposit(depositor_usdc.key, usdc_bank, 2)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::GroupTvlCapExceeded);
+
+    // Closing the SOL position leaves the USDC deposit made under the lowered cap
+    depositor_f
+        .try_bank_withdraw(depositor_sol.key, sol_bank, 0, Some(true))
+        .await?;
+    assert_eq_noise!(group_tvl(&test_f).await, I80F48!(100), I80F48!(0.001));
+
+    Ok(())
+}
+
+/// Deposits minting receipts bring tokens in and count, moving deposits between accounts doesn't
+#[tokio::test]
+async fn group_tvl_counts_receipt_deposits_not_position_transfers() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+    let usdc_bank = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_receipts(
+            &test_f.usdc_mint,
+            *DEFAULT_USDC_TEST_BANK_CONFIG,
+        )
+        .await?;
+
+    set_tvl_cap(&test_f, 1_000).await?;
+
+    let source_f = test_f.create_astrolend_account().await;
+    let destination_f = test_f.create_astrolend_account().await;
+    let source_usdc = test_f.usdc_mint.create_token_account_and_mint_to(300).await;
+    let source_receipts = TokenAccountFixture::new_with_token_program(
+        test_f.context.clone(),
+        &find_receipt_mint_pda(&usdc_bank.key).0,
+        &test_f.payer(),
+        &usdc_bank.get_token_program().await,
+    )
+    .await;
+
+    source_f
+        .try_bank_deposit_with_receipts(source_usdc.key, &usdc_bank, 100, source_receipts.key)
+        .await?;
+    assert_eq_noise!(group_tvl(&test_f).await, I80F48!(100), I80F48!(0.001));
+
+    source_f
+        .try_bank_deposit(source_usdc.key, &usdc_bank, 200)
+        .await?;
+    assert_eq_noise!(group_tvl(&test_f).await, I80F48!(300), I80F48!(0.001));
+
+    let shares: I80F48 = source_f
+        .load()
+        .await
+        .lending_account
+        .get_balance(&usdc_bank.key)
+        .unwrap()
+        .asset_shares
+        .into();
+    source_f
+        .try_transfer_position(&destination_f, &usdc_bank, shares)
+        .await?;
+    assert_eq_noise!(group_tvl(&test_f).await, I80F48!(300), I80F48!(0.001));
+
+    Ok(())
+}
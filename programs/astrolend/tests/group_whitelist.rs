@@ -0,0 +1,136 @@
+use fixtures::{assert_custom_error, prelude::*};
+use astrolend::{errors::AstrolendError, state::astrolend_group::GroupConfig};
+use solana_program_test::tokio;
+
+async fn require_whitelist(test_f: &TestFixture, required: bool) -> anyhow::Result<()> {
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            whitelist_required: Some(required),
+            ..Default::default()
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Repeating a transaction needs a new blockhash, it would be deduplicated otherwise
+async fn refresh_blockhash(test_f: &TestFixture) {
+    let mut ctx = test_f.context.borrow_mut();
+    ctx.last_blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+}
+
+#[tokio::test]
+async fn whitelist_lifecycle() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let wallet = test_f.payer();
+
+    // Opened before the group required a whitelist
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(2_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    require_whitelist(&test_f, true).await?;
+
+    let res = test_f.try_create_astrolend_account().await;
+    assert!(res.is_err());
+    assert_custom_error!(res.err().unwrap(), AstrolendError::WalletNotWhitelisted);
+
+    let res = lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 100)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::WalletNotWhitelisted);
+
+    // Whitelisted, the wallet can open accounts and use them normally
+    test_f.astrolend_group.try_whitelist_add(wallet).await?;
+
+    let borrower_f = test_f.try_create_astrolend_account().await?;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(20).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 10)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 40)
+        .await?;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 50)
+        .await?;
+
+    // Removed from the whitelist, existing accounts are reduce only
+    test_f.astrolend_group.try_whitelist_remove(wallet).await?;
+
+    let res = borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 1)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::WalletNotWhitelisted);
+
+    let res = borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 1)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::WalletNotWhitelisted);
+
+    let res = test_f.try_create_astrolend_account().await;
+    assert!(res.is_err());
+    assert_custom_error!(res.err().unwrap(), AstrolendError::WalletNotWhitelisted);
+
+    borrower_f
+        .try_bank_repay(borrower_usdc.key, usdc_bank, 20, None)
+        .await?;
+    borrower_f
+        .try_bank_withdraw(borrower_sol.key, sol_bank, 1, None)
+        .await?;
+    lender_f
+        .try_bank_withdraw(lender_usdc.key, usdc_bank, 50, None)
+        .await?;
+
+    // Added back, the same accounts can grow again
+    refresh_blockhash(&test_f).await;
+    test_f.astrolend_group.try_whitelist_add(wallet).await?;
+
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 2)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 10)
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn whitelist_only_enforced_while_required() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let wallet = test_f.payer();
+
+    // Entries can be managed before the whitelist is turned on
+    test_f.astrolend_group.try_whitelist_add(wallet).await?;
+    test_f.astrolend_group.try_whitelist_remove(wallet).await?;
+
+    refresh_blockhash(&test_f).await;
+    let res = test_f.astrolend_group.try_whitelist_remove(wallet).await;
+    assert!(res.is_err());
+
+    require_whitelist(&test_f, true).await?;
+    assert!(test_f.try_create_astrolend_account().await.is_err());
+
+    require_whitelist(&test_f, false).await?;
+    let account_f = test_f.try_create_astrolend_account().await?;
+    let usdc_account = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    account_f
+        .try_bank_deposit(usdc_account.key, usdc_bank, 100)
+        .await?;
+
+    Ok(())
+}
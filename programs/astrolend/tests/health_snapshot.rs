@@ -0,0 +1,123 @@
+use fixtures::{assert_eq_noise, astrolend_account::AstrolendAccountFixture, prelude::*};
+use astrolend::{events::AccountHealthSnapshotEvent, state::astrolend_group::GroupConfig};
+use solana_program::instruction::Instruction;
+use solana_program_test::tokio;
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+async fn health_snapshot(
+    test_f: &TestFixture,
+    ix: Instruction,
+) -> Option<AccountHealthSnapshotEvent> {
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await
+            .unwrap()
+    };
+
+    assert!(result.result.is_ok());
+
+    let logs = result.metadata.unwrap().log_messages;
+    find_event::<AccountHealthSnapshotEvent>(&logs)
+}
+
+async fn borrower_with_sol_collateral(test_f: &TestFixture) -> AstrolendAccountFixture {
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await
+        .unwrap();
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 10)
+        .await
+        .unwrap();
+
+    borrower_f
+}
+
+#[tokio::test]
+async fn health_snapshot_emitted_after_borrow_and_withdraw() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            health_snapshot_events: Some(true),
+            ..Default::default()
+        })
+        .await?;
+
+    let borrower_f = borrower_with_sol_collateral(&test_f).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+
+    // 10 SOL at $10 against $40 of USDC, all weights are 1
+    let ix = borrower_f
+        .make_bank_borrow_ix(borrower_usdc.key, usdc_bank, 40)
+        .await;
+    let event = health_snapshot(&test_f, ix).await.unwrap();
+
+    assert_eq!(event.header.astrolend_account, borrower_f.key);
+    assert_eq_noise!(event.init_assets, 100.0, 0.01);
+    assert_eq_noise!(event.init_liabilities, 40.0, 0.01);
+    assert_eq_noise!(event.init_equity, 60.0, 0.01);
+    assert_eq_noise!(event.maint_assets, 100.0, 0.01);
+    assert_eq_noise!(event.maint_liabilities, 40.0, 0.01);
+    assert_eq_noise!(event.maint_equity, 60.0, 0.01);
+    assert_eq!(event.active_balances, 2);
+
+    let ix = borrower_f
+        .make_bank_withdraw_ix(borrower_sol.key, sol_bank, 5, None)
+        .await;
+    let event = health_snapshot(&test_f, ix).await.unwrap();
+
+    assert_eq_noise!(event.init_assets, 50.0, 0.01);
+    assert_eq_noise!(event.init_equity, 10.0, 0.01);
+    assert_eq!(event.active_balances, 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn health_snapshot_off_by_default() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    assert_eq!(test_f.astrolend_group.load().await.group_flags, 0);
+
+    let borrower_f = borrower_with_sol_collateral(&test_f).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+
+    let ix = borrower_f
+        .make_bank_borrow_ix(borrower_usdc.key, usdc_bank, 40)
+        .await;
+    assert!(health_snapshot(&test_f, ix).await.is_none());
+
+    let ix = borrower_f
+        .make_bank_withdraw_ix(borrower_sol.key, sol_bank, 1, None)
+        .await;
+    assert!(health_snapshot(&test_f, ix).await.is_none());
+
+    Ok(())
+}
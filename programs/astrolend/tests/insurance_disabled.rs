@@ -0,0 +1,206 @@
+use anchor_lang::{InstructionData, ToAccountMetas};
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{
+    assert_custom_error, assert_eq_noise, astrolend_account::AstrolendAccountFixture,
+    bank::BankFixture, native, prelude::*,
+};
+use astrolend::{
+    errors::AstrolendError,
+    state::{
+        astrolend_account::DISABLED_FLAG,
+        astrolend_group::{BankConfig, BankVaultType, GroupConfig, InterestRateConfig},
+    },
+};
+use solana_program::instruction::Instruction;
+use solana_program_test::tokio;
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+/// Only the SOL bank, the group's USDC bank is added by the test
+fn sol_bank_only() -> TestSettings {
+    TestSettings {
+        banks: vec![TestBankSetting {
+            mint: BankMint::Sol,
+            config: None,
+        }],
+        group_config: Some(GroupConfig::default()),
+    }
+}
+
+async fn add_uninsured_usdc_bank(test_f: &TestFixture) -> anyhow::Result<BankFixture> {
+    let bank_f = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_without_insurance(
+            &test_f.usdc_mint,
+            BankConfig {
+                interest_rate_config: InterestRateConfig {
+                    insurance_fee_fixed_apr: I80F48!(0.01).into(),
+                    insurance_ir_fee: I80F48!(0.1).into(),
+                    protocol_fixed_fee_apr: I80F48!(0.01).into(),
+                    protocol_ir_fee: I80F48!(0.1).into(),
+                    ..*DEFAULT_TEST_BANK_INTEREST_RATE_CONFIG
+                },
+                ..*DEFAULT_USDC_TEST_BANK_CONFIG
+            },
+        )
+        .await?;
+
+    let bank = bank_f.load().await;
+    assert!(bank.is_insurance_disabled());
+    assert_eq!(bank.insurance_vault, Default::default());
+
+    Ok(bank_f)
+}
+
+/// Lends 1_000 USDC into `bank_f` and borrows `borrow_amount` USDC against 100 SOL
+async fn open_usdc_borrow(
+    test_f: &TestFixture,
+    bank_f: &BankFixture,
+    borrow_amount: f64,
+) -> anyhow::Result<AstrolendAccountFixture> {
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, bank_f, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, test_f.get_bank(&BankMint::Sol), 100)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, bank_f, borrow_amount)
+        .await?;
+
+    Ok(borrower_f)
+}
+
+#[tokio::test]
+async fn insurance_disabled_fees_go_to_group() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(sol_bank_only())).await;
+    let bank_f = add_uninsured_usdc_bank(&test_f).await?;
+
+    let borrower_f = open_usdc_borrow(&test_f, &bank_f, 500.).await?;
+
+    test_f.advance_time(86_400 * 30).await;
+
+    // Any balance change accrues interest
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(10).await;
+    borrower_f
+        .try_bank_repay(borrower_usdc.key, &bank_f, 1, None)
+        .await?;
+
+    let bank = bank_f.load().await;
+    let group_fees_outstanding = I80F48::from(bank.collected_group_fees_outstanding);
+    assert_eq!(
+        I80F48::from(bank.collected_insurance_fees_outstanding),
+        I80F48::ZERO
+    );
+    assert!(group_fees_outstanding > I80F48::ONE);
+
+    // Collection runs without the insurance vault, everything lands in the fee vault
+    test_f.astrolend_group.try_collect_fees(&bank_f).await?;
+
+    let bank = bank_f.load().await;
+    let fee_vault_balance = balance_of(
+        test_f.context.clone(),
+        bank_f.get_vault(BankVaultType::Fee).0,
+    )
+    .await;
+    assert_eq!(fee_vault_balance, group_fees_outstanding.to_num::<u64>());
+    assert!(I80F48::from(bank.collected_group_fees_outstanding) < I80F48::ONE);
+    assert_eq!(
+        I80F48::from(bank.collected_insurance_fees_outstanding),
+        I80F48::ZERO
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn insured_bank_requires_insurance_vault_on_collect() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let ix = Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::LendingPoolCollectBankFees {
+            astrolend_group: test_f.astrolend_group.key,
+            bank: usdc_bank.key,
+            liquidity_vault_authority: usdc_bank.get_vault_authority(BankVaultType::Liquidity).0,
+            liquidity_vault: usdc_bank.get_vault(BankVaultType::Liquidity).0,
+            insurance_vault: None,
+            fee_vault: usdc_bank.get_vault(BankVaultType::Fee).0,
+            token_program: anchor_spl::token::ID,
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::LendingPoolCollectBankFees {}.data(),
+    };
+
+    let res = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client.process_transaction(tx).await
+    };
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InsuranceVaultRequired);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn insurance_disabled_bankruptcy_socializes_all_bad_debt() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(sol_bank_only())).await;
+    let bank_f = add_uninsured_usdc_bank(&test_f).await?;
+
+    let borrower_f = open_usdc_borrow(&test_f, &bank_f, 50.).await?;
+
+    // The SOL collateral becomes worthless
+    test_f.context.borrow_mut().set_account(
+        &PYTH_SOL_FEED,
+        &create_pyth_legacy_oracle_account(
+            test_f.sol_mint.key,
+            0.000_1,
+            SOL_MINT_DECIMALS.into(),
+            None,
+        )
+        .into(),
+    );
+
+    test_f
+        .astrolend_group
+        .try_handle_bankruptcy(&bank_f, &borrower_f)
+        .await?;
+
+    // Nothing is drawn from an insurance fund, lenders absorb the full 50 USDC
+    let bank = bank_f.load().await;
+    assert_eq_noise!(
+        I80F48::from(bank.cumulative_socialized_loss),
+        I80F48::from_num(native!(50, "USDC")),
+        I80F48!(100)
+    );
+    assert_eq_noise!(
+        bank.get_asset_amount(bank.total_asset_shares.into())?,
+        I80F48::from_num(native!(950, "USDC")),
+        I80F48!(100)
+    );
+
+    let borrower = borrower_f.load().await;
+    assert!(borrower.get_flag(DISABLED_FLAG));
+    assert!(borrower
+        .lending_account
+        .get_balance(&bank_f.key)
+        .map_or(true, |balance| I80F48::from(balance.liability_shares) == I80F48::ZERO));
+
+    Ok(())
+}
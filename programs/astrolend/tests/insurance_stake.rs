@@ -0,0 +1,166 @@
+use fixtures::{
+    assert_custom_error, assert_eq_noise, astrolend_account::AstrolendAccountFixture, native,
+    prelude::*,
+};
+use astrolend::{
+    constants::INSURANCE_STAKE_COOLDOWN, errors::AstrolendError,
+    state::astrolend_group::BankVaultType,
+};
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+/// Staker with a USDC token account holding `ui_amount`
+async fn create_staker(test_f: &TestFixture, ui_amount: f64) -> (Keypair, TokenAccountFixture) {
+    let staker = Keypair::new();
+    let token_account_f =
+        TokenAccountFixture::new(test_f.context.clone(), &test_f.usdc_mint, &staker.pubkey())
+            .await;
+    test_f
+        .usdc_mint
+        .clone()
+        .mint_to(&token_account_f.key, ui_amount)
+        .await;
+
+    (staker, token_account_f)
+}
+
+/// Borrows 8 USDC against 1 SOL and wipes the SOL, leaving 8 USDC of bad debt
+async fn open_bankrupt_account(test_f: &TestFixture) -> anyhow::Result<AstrolendAccountFixture> {
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, test_f.get_bank(&BankMint::Usdc), 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(1).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, test_f.get_bank(&BankMint::Sol), 1)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, test_f.get_bank(&BankMint::Usdc), 8)
+        .await?;
+
+    borrower_f
+        .make_bankrupt(
+            test_f.get_bank(&BankMint::Sol),
+            test_f.get_bank(&BankMint::Usdc),
+        )
+        .await;
+
+    Ok(borrower_f)
+}
+
+#[tokio::test]
+async fn bankruptcy_haircuts_insurance_stakers_pro_rata() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let insurance_vault = usdc_bank.get_vault(BankVaultType::Insurance).0;
+
+    // 100 USDC of fees owned by the admin, 100 and 300 USDC staked by third parties
+    test_f
+        .usdc_mint
+        .clone()
+        .mint_to(&insurance_vault, 100)
+        .await;
+
+    let (staker_a, staker_a_usdc) = create_staker(&test_f, 100.).await;
+    let (staker_b, staker_b_usdc) = create_staker(&test_f, 300.).await;
+    for (staker, token_account_f, amount) in [
+        (&staker_a, &staker_a_usdc, native!(100, "USDC")),
+        (&staker_b, &staker_b_usdc, native!(300, "USDC")),
+    ] {
+        usdc_bank.try_init_insurance_stake(staker).await?;
+        usdc_bank
+            .try_deposit_insurance(staker, token_account_f, amount)
+            .await?;
+    }
+
+    assert_eq!(
+        balance_of(test_f.context.clone(), insurance_vault).await,
+        native!(500, "USDC")
+    );
+
+    // The admin can only withdraw its own part
+    let admin_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    let res = usdc_bank
+        .try_withdraw_insurance(&admin_usdc, native!(101, "USDC"))
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InsufficientAdminInsurance);
+
+    // 8 USDC of bad debt is covered by the insurance vault, every owner loses 1.6%
+    let borrower_f = open_bankrupt_account(&test_f).await?;
+    test_f
+        .astrolend_group
+        .try_handle_bankruptcy(usdc_bank, &borrower_f)
+        .await?;
+
+    assert_eq_noise!(
+        balance_of(test_f.context.clone(), insurance_vault).await as i64,
+        native!(492, "USDC") as i64,
+        1
+    );
+    assert!(usdc_bank.load().await.last_insurance_payout_ts > 0);
+
+    // Stakes are locked right after the payout
+    let res = usdc_bank
+        .try_withdraw_insurance_stake(&staker_a, &staker_a_usdc, 0, Some(true))
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InsuranceStakeCooldown);
+
+    test_f.advance_time(INSURANCE_STAKE_COOLDOWN).await;
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        ctx.last_blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+    }
+
+    usdc_bank
+        .try_withdraw_insurance_stake(&staker_a, &staker_a_usdc, 0, Some(true))
+        .await?;
+    assert_eq_noise!(
+        staker_a_usdc.balance().await as i64,
+        native!(98.4, "USDC", f64) as i64,
+        1
+    );
+    assert!(usdc_bank
+        .load_insurance_stake(&staker_a.pubkey())
+        .await
+        .get_shares(&usdc_bank.load().await)
+        .is_zero());
+
+    // Partial withdrawals burn the shares worth the amount
+    usdc_bank
+        .try_withdraw_insurance_stake(&staker_b, &staker_b_usdc, native!(100, "USDC"), None)
+        .await?;
+    assert_eq!(staker_b_usdc.balance().await, native!(100, "USDC"));
+
+    let usdc_bank_state = usdc_bank.load().await;
+    let remaining_shares = usdc_bank
+        .load_insurance_stake(&staker_b.pubkey())
+        .await
+        .get_shares(&usdc_bank_state);
+    assert_eq_noise!(
+        usdc_bank_state
+            .get_insurance_stake_amount(remaining_shares)?
+            .to_num::<f64>(),
+        native!(195.2, "USDC", f64) as f64,
+        1.
+    );
+
+    // What's left of the admin part
+    let res = usdc_bank
+        .try_withdraw_insurance(&admin_usdc, native!(99, "USDC"))
+        .await;
+    assert!(res.is_err());
+    usdc_bank
+        .try_withdraw_insurance(&admin_usdc, native!(98, "USDC"))
+        .await?;
+
+    Ok(())
+}
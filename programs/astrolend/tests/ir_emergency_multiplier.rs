@@ -0,0 +1,168 @@
+use astrolend::{
+    constants::SECONDS_PER_YEAR,
+    errors::AstrolendError,
+    events::AstrolendGroupIrEmergencyMultiplierEvent,
+    state::astrolend_group::{Bank, GroupConfig},
+};
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{assert_custom_error, bank::BankFixture, prelude::*};
+use solana_program_test::tokio;
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+/// Liability share value after accruing from the `before` bank state to `after.last_update`,
+/// with borrow rates scaled by `multiplier`
+fn expected_liability_share_value(before: &Bank, after: &Bank, multiplier: I80F48) -> I80F48 {
+    let total_assets = before
+        .get_asset_amount(before.total_asset_shares.into())
+        .unwrap();
+    let total_liabilities = before
+        .get_liability_amount(before.total_liability_shares.into())
+        .unwrap();
+    let (_, borrowing_apr, _, _) = before
+        .config
+        .interest_rate_config
+        .calc_interest_rate(total_liabilities / total_assets)
+        .unwrap();
+
+    let time_delta = I80F48::from_num(after.last_update - before.last_update);
+    let ir_per_period = borrowing_apr * multiplier * time_delta / SECONDS_PER_YEAR;
+
+    I80F48::from(before.liability_share_value) * (I80F48::ONE + ir_per_period)
+}
+
+async fn accrue(test_f: &TestFixture, bank_f: &BankFixture) -> (Bank, Bank) {
+    let before = bank_f.load().await;
+    test_f
+        .astrolend_group
+        .try_accrue_interest(bank_f)
+        .await
+        .unwrap();
+    let after = bank_f.load().await;
+
+    (before, after)
+}
+
+fn assert_close(actual: I80F48, expected: I80F48) {
+    assert!(
+        (actual - expected).abs() < I80F48!(0.000000001),
+        "{} != {}",
+        actual,
+        expected
+    );
+}
+
+#[tokio::test]
+async fn ir_emergency_multiplier_expires() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_usdc = test_f.usdc_mint.create_empty_token_account().await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 100)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 500)
+        .await?;
+
+    // Doubled rates for the next hour
+    let ix = test_f.astrolend_group.make_update_ix(GroupConfig {
+        ir_emergency_multiplier: Some(I80F48!(2).into()),
+        ir_emergency_multiplier_duration: Some(3_600),
+        ..Default::default()
+    });
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await?
+    };
+    assert!(result.result.is_ok());
+
+    let now = test_f.get_clock().await.unix_timestamp;
+    let events = parse_events::<AstrolendGroupIrEmergencyMultiplierEvent>(&result);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].multiplier, 2.);
+    assert_eq!(events[0].expires_at, now + 3_600);
+
+    test_f.advance_time(1_800).await;
+    let (before, after) = accrue(&test_f, usdc_bank).await;
+    assert!(after.last_update < now + 3_600);
+    assert_close(
+        after.liability_share_value.into(),
+        expected_liability_share_value(&before, &after, I80F48!(2)),
+    );
+
+    // Past expiry accruals are back to the normal rates, the group still holds the multiplier
+    test_f.advance_time(3_600).await;
+    let (before, after) = accrue(&test_f, usdc_bank).await;
+    assert!(after.last_update >= now + 3_600);
+    assert_close(
+        after.liability_share_value.into(),
+        expected_liability_share_value(&before, &after, I80F48::ONE),
+    );
+    assert_eq!(
+        I80F48::from(test_f.astrolend_group.load().await.ir_emergency_multiplier),
+        I80F48!(2)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ir_emergency_multiplier_config_validation() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    for multiplier in [I80F48!(0.5), I80F48!(5.01)] {
+        let res = test_f
+            .astrolend_group
+            .try_update(GroupConfig {
+                ir_emergency_multiplier: Some(multiplier.into()),
+                ir_emergency_multiplier_duration: Some(3_600),
+                ..Default::default()
+            })
+            .await;
+        assert!(res.is_err());
+        assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidConfig);
+    }
+
+    // The multiplier and its duration go together
+    let res = test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            ir_emergency_multiplier: Some(I80F48!(2).into()),
+            ..Default::default()
+        })
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidConfig);
+
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            ir_emergency_multiplier: Some(I80F48!(5).into()),
+            ir_emergency_multiplier_duration: Some(3_600),
+            ..Default::default()
+        })
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use fixtures::{assert_custom_error, astrolend_account::AstrolendAccountFixture, prelude::*};
+use astrolend::{
+    errors::AstrolendError,
+    state::astrolend_group::{BankConfigOpt, RiskTier},
+};
+use solana_program_test::tokio;
+
+/// A user with 100 USDC of collateral borrowing 1 unit of the isolated bank
+async fn setup() -> anyhow::Result<(TestFixture, AstrolendAccountFixture)> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let isolated_bank = test_f.get_bank(&BankMint::SolEqIsolated);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_isolated = test_f
+        .sol_equivalent_mint
+        .create_token_account_and_mint_to(100)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_isolated.key, isolated_bank, 100)
+        .await?;
+
+    let user_f = test_f.create_astrolend_account().await;
+    let user_usdc = test_f.usdc_mint.create_token_account_and_mint_to(200).await;
+    let user_isolated = test_f.sol_equivalent_mint.create_empty_token_account().await;
+    user_f.try_bank_deposit(user_usdc.key, usdc_bank, 100).await?;
+    user_f
+        .try_bank_borrow(user_isolated.key, isolated_bank, 1)
+        .await?;
+
+    Ok((test_f, user_f))
+}
+
+#[tokio::test]
+async fn isolated_liability_restricts_deposits_to_existing_balances() -> anyhow::Result<()> {
+    let (test_f, user_f) = setup().await?;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let isolated_bank = test_f.get_bank(&BankMint::SolEqIsolated);
+
+    let user_usdc = test_f.usdc_mint.create_token_account_and_mint_to(10).await;
+    let user_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+
+    user_f.try_bank_deposit(user_usdc.key, usdc_bank, 10).await?;
+
+    let res = user_f.try_bank_deposit(user_sol.key, sol_bank, 1).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::IsolatedAccountIllegalState);
+
+    // The tier is read from the liability bank, it can't be skipped
+    let mut ix = user_f.make_bank_deposit_ix(user_sol.key, sol_bank, 1).await;
+    ix.accounts.retain(|meta| meta.pubkey != isolated_bank.key);
+    let simulation = test_f.simulate_tx(&[ix], &[]).await;
+    assert_eq!(
+        simulation.custom_error_code(),
+        Some(AstrolendError::MissingPythOrBankAccount.into())
+    );
+
+    // Repaying the isolated liability lifts the restriction
+    let user_isolated = test_f
+        .sol_equivalent_mint
+        .create_token_account_and_mint_to(2)
+        .await;
+    user_f
+        .try_bank_repay(user_isolated.key, isolated_bank, 0, Some(true))
+        .await?;
+    user_f.try_bank_deposit(user_sol.key, sol_bank, 1).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn isolated_tier_changes_apply_to_open_liabilities() -> anyhow::Result<()> {
+    let (test_f, user_f) = setup().await?;
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let isolated_bank = test_f.get_bank(&BankMint::SolEqIsolated);
+
+    let user_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+
+    isolated_bank
+        .update_config(BankConfigOpt {
+            risk_tier: Some(RiskTier::Collateral),
+            ..Default::default()
+        })
+        .await?;
+    user_f.try_bank_deposit(user_sol.key, sol_bank, 1).await?;
+
+    // Back to isolated, the open liability restricts deposits into new banks again
+    isolated_bank
+        .update_config(BankConfigOpt {
+            risk_tier: Some(RiskTier::Isolated),
+            ..Default::default()
+        })
+        .await?;
+    let pyusd_bank = test_f.get_bank(&BankMint::PyUSD);
+    let user_pyusd = test_f.pyusd_mint.create_token_account_and_mint_to(10).await;
+    let res = user_f.try_bank_deposit(user_pyusd.key, pyusd_bank, 1).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::IsolatedAccountIllegalState);
+
+    Ok(())
+}
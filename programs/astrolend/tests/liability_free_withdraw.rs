@@ -0,0 +1,93 @@
+use fixtures::{assert_custom_error, native, prelude::*};
+use astrolend::errors::AstrolendError;
+use solana_program::instruction::Instruction;
+use solana_program_test::{tokio, BanksClientError};
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+/// Accounts of a withdraw ix before the remaining accounts, for a non Token-2022 bank
+const WITHDRAW_IX_ACCOUNTS: usize = 8;
+
+async fn process(test_f: &TestFixture, ix: Instruction) -> Result<(), BanksClientError> {
+    let mut ctx = test_f.context.borrow_mut();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer],
+        ctx.last_blockhash,
+    );
+
+    ctx.banks_client.process_transaction(tx).await
+}
+
+#[tokio::test]
+async fn liability_free_withdraw_needs_no_oracles() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let depositor_f = test_f.create_astrolend_account().await;
+    let depositor_usdc = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    let depositor_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    depositor_f
+        .try_bank_deposit(depositor_usdc.key, usdc_bank, 100)
+        .await?;
+    depositor_f
+        .try_bank_deposit(depositor_sol.key, sol_bank, 10)
+        .await?;
+
+    // Every oracle is stale by now, none of them is read
+    test_f.advance_time(3_600).await;
+
+    let ix = depositor_f
+        .make_bank_withdraw_ix(depositor_usdc.key, usdc_bank, 40, None)
+        .await;
+    assert_eq!(ix.accounts.len(), WITHDRAW_IX_ACCOUNTS);
+    process(&test_f, ix).await?;
+
+    assert_eq!(depositor_usdc.balance().await, native!(40, "USDC"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn withdraw_with_liability_needs_oracles() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 10)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 10)
+        .await?;
+
+    let ix = borrower_f
+        .make_bank_withdraw_ix(borrower_sol.key, sol_bank, 1, None)
+        .await;
+    assert_eq!(ix.accounts.len(), WITHDRAW_IX_ACCOUNTS + 4);
+
+    let mut ix_without_observations = ix.clone();
+    ix_without_observations
+        .accounts
+        .truncate(WITHDRAW_IX_ACCOUNTS);
+    let res = process(&test_f, ix_without_observations).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::MissingPythOrBankAccount);
+
+    process(&test_f, ix).await?;
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{assert_eq_noise, bank::BankFixture, native, prelude::*};
+use astrolend::state::astrolend_group::{BankConfigOpt, BankVaultType, InterestRateConfigOpt};
+use solana_program_test::tokio;
+
+/// Lifetime insurance fees, group fees and depositor interest of a bank
+async fn lifetime_counters(bank_f: &BankFixture) -> [I80F48; 3] {
+    let bank = bank_f.load().await;
+    [
+        bank.get_lifetime_insurance_fees(),
+        bank.get_lifetime_group_fees(),
+        bank.get_lifetime_interest_paid_to_depositors(),
+    ]
+}
+
+#[tokio::test]
+async fn lifetime_fees_match_vault_inflows() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    usdc_bank
+        .update_config(BankConfigOpt {
+            interest_rate_config: Some(InterestRateConfigOpt {
+                insurance_fee_fixed_apr: Some(I80F48!(0.01).into()),
+                insurance_ir_fee: Some(I80F48!(0.1).into()),
+                protocol_fixed_fee_apr: Some(I80F48!(0.01).into()),
+                protocol_ir_fee: Some(I80F48!(0.05).into()),
+                protocol_origination_fee: Some(I80F48!(0.01).into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .await?;
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, test_f.get_bank(&BankMint::Sol), 100)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 500)
+        .await?;
+
+    // The origination fee is the first group fee
+    let [insurance_fees, group_fees, depositor_interest] = lifetime_counters(usdc_bank).await;
+    assert_eq!(insurance_fees, I80F48::ZERO);
+    assert_eq_noise!(group_fees, I80F48::from(native!(5, "USDC")), I80F48::ONE);
+    assert_eq!(depositor_interest, I80F48::ZERO);
+
+    let fee_vault = usdc_bank.get_vault_token_account(BankVaultType::Fee).await;
+    let insurance_vault = usdc_bank
+        .get_vault_token_account(BankVaultType::Insurance)
+        .await;
+    let fee_receiver = test_f.usdc_mint.create_empty_token_account().await;
+    let insurance_receiver = test_f.usdc_mint.create_empty_token_account().await;
+
+    let mut previous = lifetime_counters(usdc_bank).await;
+    let mut fee_vault_inflows = 0;
+    let mut insurance_vault_inflows = 0;
+    for _ in 0..4 {
+        test_f.advance_time(30 * 24 * 60 * 60).await;
+        test_f
+            .astrolend_group
+            .try_accrue_interest(usdc_bank)
+            .await?;
+
+        let accrued = lifetime_counters(usdc_bank).await;
+        for (before, after) in previous.iter().zip(accrued.iter()) {
+            assert!(after > before);
+        }
+
+        let fee_vault_before = fee_vault.balance().await;
+        let insurance_vault_before = insurance_vault.balance().await;
+        test_f.astrolend_group.try_collect_fees(usdc_bank).await?;
+        let fee_vault_delta = fee_vault.balance().await - fee_vault_before;
+        let insurance_vault_delta = insurance_vault.balance().await - insurance_vault_before;
+        fee_vault_inflows += fee_vault_delta;
+        insurance_vault_inflows += insurance_vault_delta;
+
+        // Collecting and withdrawing the fees leave the counters alone
+        usdc_bank
+            .try_withdraw_fees(&fee_receiver, fee_vault_delta / 2)
+            .await?;
+        usdc_bank
+            .try_withdraw_insurance(&insurance_receiver, insurance_vault_delta / 2)
+            .await?;
+        assert_eq!(lifetime_counters(usdc_bank).await, accrued);
+
+        // Everything counted was either collected into the vaults or is still outstanding
+        let bank = usdc_bank.load().await;
+        assert_eq!(
+            bank.get_lifetime_group_fees(),
+            I80F48::from_num(fee_vault_inflows)
+                + I80F48::from(bank.collected_group_fees_outstanding)
+        );
+        assert_eq!(
+            bank.get_lifetime_insurance_fees(),
+            I80F48::from_num(insurance_vault_inflows)
+                + I80F48::from(bank.collected_insurance_fees_outstanding)
+        );
+
+        previous = accrued;
+    }
+
+    // All the borrower paid on top of the principal ended up with the depositors or the fees
+    let liability = borrower_f
+        .load_positions(&[usdc_bank])
+        .await
+        .pop()
+        .unwrap()
+        .liability_amount;
+    let [insurance_fees, group_fees, depositor_interest] = previous;
+    assert_eq_noise!(
+        insurance_fees + group_fees + depositor_interest,
+        liability - I80F48::from(native!(500, "USDC")),
+        I80F48::ONE
+    );
+
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{assert_eq_noise, native, prelude::*};
+use astrolend::{events::LendingAccountLiquidateEvent, state::astrolend_group::BankConfigOpt};
+use solana_program_test::tokio;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction, signer::Signer, transaction::Transaction,
+};
+
+const ONE_YEAR: i64 = 31_536_000;
+
+/// Liquidation values the liquidatee and liquidator balances at the share values accrued in
+/// the same instruction, not at the ones of the last crank
+#[tokio::test]
+async fn liquidation_accrues_before_valuation() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let liquidator_f = test_f.create_astrolend_account().await;
+    let liquidator_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    liquidator_f
+        .try_bank_deposit(liquidator_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let liquidatee_f = test_f.create_astrolend_account().await;
+    let liquidatee_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let liquidatee_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    liquidatee_f
+        .try_bank_deposit(liquidatee_sol.key, sol_bank, 100)
+        .await?;
+    liquidatee_f
+        .try_bank_borrow(liquidatee_usdc.key, usdc_bank, 500)
+        .await?;
+
+    sol_bank
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.25).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    let stale_bank = usdc_bank.load().await;
+    let liability_shares: I80F48 = liquidatee_f
+        .load()
+        .await
+        .lending_account
+        .get_balance(&usdc_bank.key)
+        .unwrap()
+        .liability_shares
+        .into();
+    let liquidator_asset_shares: I80F48 = liquidator_f
+        .load()
+        .await
+        .lending_account
+        .get_balance(&usdc_bank.key)
+        .unwrap()
+        .asset_shares
+        .into();
+
+    // A year goes by without any crank of the USDC bank
+    test_f.advance_time(ONE_YEAR).await;
+    let now = test_f.get_clock().await.unix_timestamp;
+    test_f.set_pyth_oracle_timestamp(PYTH_USDC_FEED, now).await;
+    test_f.set_pyth_oracle_timestamp(PYTH_SOL_FEED, now).await;
+    assert!(usdc_bank.load().await.last_update < now);
+
+    let ix = liquidator_f
+        .make_liquidate_ix(&liquidatee_f, sol_bank, 1, usdc_bank)
+        .await;
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        ctx.last_blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(1_400_000),
+                ix,
+            ],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await?
+    };
+    assert!(result.result.is_ok());
+
+    let events = parse_events::<LendingAccountLiquidateEvent>(&result);
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+
+    let accrued_bank = usdc_bank.load().await;
+    assert_eq!(accrued_bank.last_update, now);
+
+    let stale_liability = liability_shares * I80F48::from(stale_bank.liability_share_value);
+    let accrued_liability = liability_shares * I80F48::from(accrued_bank.liability_share_value);
+    let accrued_liquidator_deposit =
+        liquidator_asset_shares * I80F48::from(accrued_bank.asset_share_value);
+
+    // Interest accrued meaningfully over the year, so the two valuations tell apart
+    assert!(accrued_liability - stale_liability > I80F48::from_num(native!(1, "USDC")));
+
+    assert_eq_noise!(
+        event.pre_balances.liquidatee_liability_balance,
+        accrued_liability.to_num::<f64>(),
+        1.
+    );
+    assert_eq_noise!(
+        event.pre_balances.liquidator_liability_balance,
+        accrued_liquidator_deposit.to_num::<f64>(),
+        1.
+    );
+    assert_eq!(
+        event.pre_balances.liquidatee_asset_balance,
+        native!(100, "SOL") as f64
+    );
+
+    // The repaid liability is taken off the accrued balance
+    let repaid = liquidatee_f.load().await.get_liquidation_history()[0].liability_amount;
+    assert_eq_noise!(
+        event.pre_balances.liquidatee_liability_balance
+            - event.post_balances.liquidatee_liability_balance,
+        repaid as f64,
+        1.
+    );
+    assert_eq_noise!(
+        event.post_balances.liquidatee_liability_balance,
+        (accrued_liability - I80F48::from_num(repaid)).to_num::<f64>(),
+        1.
+    );
+
+    Ok(())
+}
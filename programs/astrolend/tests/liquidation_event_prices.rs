@@ -0,0 +1,87 @@
+use fixed::types::I80F48;
+use fixtures::{assert_eq_noise, prelude::*};
+use astrolend::{
+    events::LendingAccountLiquidateEvent,
+    state::{astrolend_group::BankVaultType, price::OraclePriceType},
+};
+use solana_program_test::tokio;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction, signer::Signer, transaction::Transaction,
+};
+
+/// The event carries the exact prices the liquidation was valued at
+#[tokio::test]
+async fn liquidation_event_carries_prices_used() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let liquidator_f = test_f.create_astrolend_account().await;
+    let liquidator_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    liquidator_f
+        .try_bank_deposit(liquidator_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let liquidatee_f = test_f.create_astrolend_account().await;
+    let liquidatee_sol = test_f.sol_mint.create_token_account_and_mint_to(2).await;
+    let liquidatee_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    liquidatee_f
+        .try_bank_deposit(liquidatee_sol.key, sol_bank, 2)
+        .await?;
+    liquidatee_f
+        .try_bank_borrow(liquidatee_usdc.key, usdc_bank, 10)
+        .await?;
+
+    // SOL crashes from $10 to $4, the 2 SOL no longer cover the 10 USDC borrowed
+    sol_bank.set_pyth_legacy_price(4.).await;
+
+    let insurance_vault = usdc_bank
+        .get_vault_token_account(BankVaultType::Insurance)
+        .await;
+    let insurance_vault_balance = insurance_vault.balance().await;
+
+    let ix = liquidator_f
+        .make_liquidate_ix(&liquidatee_f, sol_bank, 0.2, usdc_bank)
+        .await;
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(1_400_000),
+                ix,
+            ],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await?
+    };
+    assert!(result.result.is_ok());
+
+    let events = parse_events::<LendingAccountLiquidateEvent>(&result);
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+
+    // Zero confidence, the biased prices are the oracle prices
+    assert_eq!(I80F48::from(event.asset_price.price), I80F48::from_num(4));
+    assert_eq!(event.asset_price.price_type, OraclePriceType::RealTime);
+    assert_eq!(I80F48::from(event.liability_price.price), I80F48::ONE);
+    assert_eq!(event.liability_price.price_type, OraclePriceType::RealTime);
+
+    assert!(event.liquidatee_pre_health < 0.);
+    assert!(event.liquidatee_post_health > event.liquidatee_pre_health);
+
+    // At $1 per USDC the native fee is the USD fee in native units
+    assert_eq_noise!(event.insurance_fee_amount, event.insurance_fee * 1e6, 1e-3);
+    assert_eq!(
+        insurance_vault.balance().await - insurance_vault_balance,
+        event.insurance_fee_amount.floor() as u64
+    );
+
+    Ok(())
+}
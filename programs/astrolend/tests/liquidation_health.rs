@@ -0,0 +1,108 @@
+use fixed::types::I80F48;
+use fixtures::{assert_custom_error, assert_eq_noise, native, prelude::*};
+use astrolend::{
+    constants::LIQUIDATION_LIQUIDATOR_FEE, errors::AstrolendError,
+    state::astrolend_group::BankVaultType,
+};
+use solana_program_test::tokio;
+
+#[tokio::test]
+async fn liquidation_follows_fabricated_maint_health() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let liquidator_f = test_f.create_astrolend_account().await;
+    let liquidator_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    liquidator_f
+        .try_bank_deposit(liquidator_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let liquidatee_f = test_f.create_astrolend_account().await;
+    let liquidatee_sol = test_f.sol_mint.create_token_account_and_mint_to(2).await;
+    let liquidatee_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    liquidatee_f
+        .try_bank_deposit(liquidatee_sol.key, sol_bank, 2)
+        .await?;
+    liquidatee_f
+        .try_bank_borrow(liquidatee_usdc.key, usdc_bank, 10)
+        .await?;
+
+    // Barely healthy, the risk engine agrees
+    let healthy_price = liquidatee_f.make_unhealthy(1., sol_bank, usdc_bank).await;
+    assert!(healthy_price < 10.);
+    let res = liquidator_f
+        .try_liquidate(&liquidatee_f, sol_bank, 0.1, usdc_bank)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::IllegalLiquidation);
+
+    let unhealthy_price = liquidatee_f.make_unhealthy(-1., sol_bank, usdc_bank).await;
+    assert!(unhealthy_price < healthy_price);
+    liquidator_f
+        .try_liquidate(&liquidatee_f, sol_bank, 0.2, usdc_bank)
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn liquidator_receives_collateral_as_shares() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let liquidatee_f = test_f.create_astrolend_account().await;
+    let liquidatee_sol = test_f.sol_mint.create_token_account_and_mint_to(2).await;
+    let liquidatee_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    liquidatee_f
+        .try_bank_deposit(liquidatee_sol.key, sol_bank, 2)
+        .await?;
+    liquidatee_f
+        .try_bank_borrow(liquidatee_usdc.key, usdc_bank, 10)
+        .await?;
+    let sol_price = liquidatee_f.make_unhealthy(-1., sol_bank, usdc_bank).await;
+
+    // The liquidator starts without any balance, the seized collateral backs the liability
+    let liquidator_f = test_f.create_astrolend_account().await;
+    let sol_vault = sol_bank
+        .get_vault_token_account(BankVaultType::Liquidity)
+        .await;
+    let sol_vault_before = sol_vault.balance().await;
+
+    liquidator_f
+        .try_liquidate(&liquidatee_f, sol_bank, 0.2, usdc_bank)
+        .await?;
+
+    assert_eq!(sol_vault.balance().await, sol_vault_before);
+
+    let positions = liquidator_f.load_positions(&[sol_bank, usdc_bank]).await;
+    assert_eq!(positions.len(), 2);
+    let (sol, usdc) = (&positions[0], &positions[1]);
+    assert_eq_noise!(
+        sol.asset_amount,
+        I80F48::from(native!(0.2, "SOL", f64)),
+        I80F48::ONE
+    );
+    assert_eq!(sol.liability_amount, I80F48::ZERO);
+    assert_eq!(usdc.asset_amount, I80F48::ZERO);
+
+    // The liability taken over is the collateral value less the liquidator fee
+    let liability_ui = usdc.liability_amount.to_num::<f64>() / native!(1, "USDC") as f64;
+    let expected_ui = 0.2 * sol_price * (1. - LIQUIDATION_LIQUIDATOR_FEE.to_num::<f64>());
+    assert_eq_noise!(liability_ui, expected_ui, expected_ui * 0.01);
+
+    Ok(())
+}
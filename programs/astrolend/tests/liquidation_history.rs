@@ -0,0 +1,65 @@
+use fixed_macro::types::I80F48;
+use fixtures::{native, prelude::*};
+use astrolend::state::astrolend_group::BankConfigOpt;
+use solana_program_test::tokio;
+
+#[tokio::test]
+async fn liquidations_are_recorded_on_liquidatee() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let liquidator_f = test_f.create_astrolend_account().await;
+    let liquidator_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    liquidator_f
+        .try_bank_deposit(liquidator_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let liquidatee_f = test_f.create_astrolend_account().await;
+    let liquidatee_sol = test_f.sol_mint.create_token_account_and_mint_to(2).await;
+    let liquidatee_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    liquidatee_f
+        .try_bank_deposit(liquidatee_sol.key, sol_bank, 2)
+        .await?;
+    liquidatee_f
+        .try_bank_borrow(liquidatee_usdc.key, usdc_bank, 10)
+        .await?;
+
+    sol_bank
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.25).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    liquidator_f
+        .try_liquidate(&liquidatee_f, sol_bank, 0.1, usdc_bank)
+        .await?;
+    test_f.advance_time(1).await;
+    liquidator_f
+        .try_liquidate(&liquidatee_f, sol_bank, 0.2, usdc_bank)
+        .await?;
+
+    let liquidatee = liquidatee_f.load().await;
+    let history = liquidatee.get_liquidation_history();
+
+    assert_eq!(liquidatee.liquidation_count, 2);
+    assert_eq!(history.len(), 2);
+    assert!(history[0].timestamp < history[1].timestamp);
+    assert_eq!(history[0].asset_amount, native!(0.1, "SOL", f64));
+    assert_eq!(history[1].asset_amount, native!(0.2, "SOL", f64));
+
+    for record in history.iter() {
+        assert_eq!(record.liquidator_account, liquidator_f.key);
+        assert_eq!(record.asset_bank, sol_bank.key);
+        assert_eq!(record.liability_bank, usdc_bank.key);
+        assert!(record.liability_amount > 0);
+    }
+
+    Ok(())
+}
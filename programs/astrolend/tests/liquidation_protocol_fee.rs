@@ -0,0 +1,110 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{assert_custom_error, assert_eq_noise, native, prelude::*};
+use astrolend::{
+    errors::AstrolendError,
+    events::LendingAccountLiquidateEvent,
+    state::astrolend_group::{BankConfigOpt, GroupConfig},
+};
+use solana_program_test::tokio;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction, signer::Signer, transaction::Transaction,
+};
+
+#[tokio::test]
+async fn liquidation_fee_is_split_with_protocol() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let liquidator_f = test_f.create_astrolend_account().await;
+    let liquidator_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    liquidator_f
+        .try_bank_deposit(liquidator_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let liquidatee_f = test_f.create_astrolend_account().await;
+    let liquidatee_sol = test_f.sol_mint.create_token_account_and_mint_to(2).await;
+    let liquidatee_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    liquidatee_f
+        .try_bank_deposit(liquidatee_sol.key, sol_bank, 2)
+        .await?;
+    liquidatee_f
+        .try_bank_borrow(liquidatee_usdc.key, usdc_bank, 10)
+        .await?;
+
+    sol_bank
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.25).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    // Protocol fee can't exceed the insurance fee
+    let res = test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            liquidation_protocol_fee_pct: Some(I80F48!(0.03).into()),
+            ..Default::default()
+        })
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidConfig);
+
+    for (fee, asset_amount) in [(0.0, 0.1), (0.01, 0.11), (0.025, 0.12)] {
+        test_f
+            .astrolend_group
+            .try_update(GroupConfig {
+                liquidation_protocol_fee_pct: Some(I80F48::from_num(fee).into()),
+                ..Default::default()
+            })
+            .await?;
+
+        let group_fees_before =
+            I80F48::from(sol_bank.load().await.collected_group_fees_outstanding);
+
+        let ix = liquidator_f
+            .make_liquidate_ix(&liquidatee_f, sol_bank, asset_amount, usdc_bank)
+            .await;
+        let result = {
+            let mut ctx = test_f.context.borrow_mut();
+            let tx = Transaction::new_signed_with_payer(
+                &[
+                    ComputeBudgetInstruction::set_compute_unit_limit(1_400_000),
+                    ix,
+                ],
+                Some(&ctx.payer.pubkey()),
+                &[&ctx.payer],
+                ctx.last_blockhash,
+            );
+            ctx.banks_client
+                .process_transaction_with_metadata(tx)
+                .await?
+        };
+        assert!(result.result.is_ok());
+
+        let logs = result.metadata.unwrap().log_messages;
+        let event = find_event::<LendingAccountLiquidateEvent>(&logs).unwrap();
+
+        // The three parts add up to the full liquidation discount of 5%
+        let total = event.liquidator_bonus + event.insurance_fee + event.protocol_fee;
+        assert_eq_noise!(total, asset_amount * 10. * 0.05, 0.01);
+        assert_eq_noise!(event.protocol_fee, total * fee / 0.05, 0.000001);
+        assert_eq_noise!(event.liquidator_bonus, total * (1. - fee) * 0.5, 0.000001);
+
+        let group_fees_after =
+            I80F48::from(sol_bank.load().await.collected_group_fees_outstanding);
+        assert_eq_noise!(
+            group_fees_after - group_fees_before,
+            I80F48::from_num(native!(asset_amount, "SOL", f64)) * I80F48::from_num(fee),
+            I80F48!(1)
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{assert_custom_error, assert_eq_noise, native, prelude::*};
+use astrolend::{
+    errors::AstrolendError,
+    events::LendingAccountLiquidateEvent,
+    state::astrolend_group::{BankConfigOpt, BankVaultType},
+};
+use solana_program_test::tokio;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction, signer::Signer, transaction::Transaction,
+};
+
+/// USD value of `amount` native SOL at $10
+fn sol_value(amount: f64) -> f64 {
+    amount / 1e9 * 10.
+}
+
+/// Two seeded banks on the SOL mint, priced by the same feed, with different asset share
+/// values. Each side of the liquidation is converted with the share values of its own bank.
+#[tokio::test]
+async fn liquidation_across_banks_of_one_mint() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+    let asset_bank = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_seed(&test_f.sol_mint, *DEFAULT_SOL_TEST_BANK_CONFIG, 0)
+        .await?;
+    let liab_bank = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_seed(&test_f.sol_mint, *DEFAULT_SOL_TEST_BANK_CONFIG, 1)
+        .await?;
+    assert_eq!(
+        asset_bank.load().await.config.oracle_keys[0],
+        liab_bank.load().await.config.oracle_keys[0]
+    );
+
+    let liquidator_f = test_f.create_astrolend_account().await;
+    let liquidator_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    liquidator_f
+        .try_bank_deposit(liquidator_sol.key, &liab_bank, 100)
+        .await?;
+
+    let liquidatee_f = test_f.create_astrolend_account().await;
+    let liquidatee_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    liquidatee_f
+        .try_bank_deposit(liquidatee_sol.key, &asset_bank, 10)
+        .await?;
+    liquidatee_f
+        .try_bank_borrow(liquidatee_sol.key, &liab_bank, 5)
+        .await?;
+
+    // Doubles the asset share value of the collateral bank only
+    let donor_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    asset_bank
+        .try_donate(&donor_sol, native!(10, "SOL"))
+        .await?;
+    let asset_share_value = I80F48::from(asset_bank.load().await.asset_share_value);
+    assert_eq_noise!(asset_share_value, I80F48!(2), I80F48!(0.000001));
+    assert_eq!(
+        I80F48::from(liab_bank.load().await.liability_share_value),
+        I80F48::ONE
+    );
+
+    // 20 SOL of collateral at 0.2 no longer cover the 5 SOL borrowed
+    asset_bank
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.2).into()),
+            asset_weight_maint: Some(I80F48!(0.2).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    let ix = liquidator_f
+        .make_liquidate_ix(&liquidatee_f, &asset_bank, 1, &liab_bank)
+        .await;
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(1_400_000),
+                ix,
+            ],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await?
+    };
+    assert!(result.result.is_ok());
+
+    let events = parse_events::<LendingAccountLiquidateEvent>(&result);
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+
+    let seized = event.pre_balances.liquidatee_asset_balance
+        - event.post_balances.liquidatee_asset_balance;
+    let repaid = event.pre_balances.liquidatee_liability_balance
+        - event.post_balances.liquidatee_liability_balance;
+    assert_eq_noise!(seized, native!(1, "SOL") as f64, 1.);
+
+    // Value repaid + bonus + insurance fee == value seized
+    assert_eq!(event.protocol_fee, 0.);
+    assert_eq_noise!(
+        sol_value(repaid) + event.liquidator_bonus + event.insurance_fee,
+        sol_value(seized),
+        1e-6
+    );
+    assert_eq_noise!(
+        sol_value(repaid),
+        sol_value(seized) * (1. - 0.025 - 0.025),
+        1e-6
+    );
+
+    // The seized amount is taken at the doubled share value, the repaid one at par
+    let liquidatee = liquidatee_f.load().await;
+    let asset_shares = I80F48::from(
+        liquidatee
+            .lending_account
+            .get_balance(&asset_bank.key)
+            .unwrap()
+            .asset_shares,
+    );
+    let liability_shares = I80F48::from(
+        liquidatee
+            .lending_account
+            .get_balance(&liab_bank.key)
+            .unwrap()
+            .liability_shares,
+    );
+    assert_eq_noise!(
+        asset_shares,
+        I80F48::from_num(native!(10, "SOL"))
+            - I80F48::from_num(native!(1, "SOL")) / asset_share_value,
+        I80F48::ONE
+    );
+    assert_eq_noise!(
+        liability_shares,
+        I80F48::from_num(native!(5, "SOL")) - I80F48::from_num(repaid),
+        I80F48::ONE
+    );
+
+    // The insurance fee goes to the vault of the liability bank
+    let insurance_vault = liab_bank
+        .get_vault_token_account(BankVaultType::Insurance)
+        .await;
+    assert_eq!(
+        insurance_vault.balance().await,
+        event.insurance_fee_amount.floor() as u64
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn liquidation_within_one_bank_is_rejected() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let liquidator_f = test_f.create_astrolend_account().await;
+    let liquidator_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    liquidator_f
+        .try_bank_deposit(liquidator_sol.key, sol_bank, 100)
+        .await?;
+
+    let liquidatee_f = test_f.create_astrolend_account().await;
+    let liquidatee_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    liquidatee_f
+        .try_bank_deposit(liquidatee_sol.key, sol_bank, 10)
+        .await?;
+
+    let res = liquidator_f
+        .try_liquidate(&liquidatee_f, sol_bank, 1, sol_bank)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::IllegalLiquidation);
+
+    Ok(())
+}
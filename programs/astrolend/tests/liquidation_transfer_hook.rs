@@ -0,0 +1,105 @@
+use fixed_macro::types::I80F48;
+use fixtures::prelude::*;
+use astrolend::state::astrolend_group::{BankConfigOpt, BankVaultType, GroupConfig};
+use solana_program_test::tokio;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+
+/// The insurance fee of a liquidation leaves the liquidity vault of the liability bank through
+/// the transfer hook of its mint, which needs its extra accounts after the observations
+#[tokio::test]
+async fn liquidate_liability_with_transfer_hook() -> anyhow::Result<()> {
+    let test_f = TestFixture::new_with_t22_extension(
+        Some(TestSettings {
+            banks: vec![
+                TestBankSetting {
+                    mint: BankMint::UsdcT22,
+                    ..TestBankSetting::default()
+                },
+                TestBankSetting {
+                    mint: BankMint::Sol,
+                    ..TestBankSetting::default()
+                },
+            ],
+            group_config: Some(GroupConfig::default()),
+        }),
+        &[SupportedExtension::TransferHook],
+    )
+    .await;
+    let usdc_t22_bank = test_f.get_bank(&BankMint::UsdcT22);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let liquidator_f = test_f.create_astrolend_account().await;
+    let liquidator_usdc = test_f
+        .usdc_t22_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    liquidator_f
+        .try_bank_deposit(liquidator_usdc.key, usdc_t22_bank, 1_000)
+        .await?;
+
+    let liquidatee_f = test_f.create_astrolend_account().await;
+    let liquidatee_sol = test_f.sol_mint.create_token_account_and_mint_to(2).await;
+    let liquidatee_usdc = test_f.usdc_t22_mint.create_empty_token_account().await;
+    liquidatee_f
+        .try_bank_deposit(liquidatee_sol.key, sol_bank, 2)
+        .await?;
+    liquidatee_f
+        .try_bank_borrow(liquidatee_usdc.key, usdc_t22_bank, 10)
+        .await?;
+
+    sol_bank
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.25).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    let transfer_hook_accounts = usdc_t22_bank
+        .mint
+        .load_transfer_hook_account_metas(
+            &usdc_t22_bank.get_vault(BankVaultType::Liquidity).0,
+            &usdc_t22_bank.get_vault(BankVaultType::Insurance).0,
+            &usdc_t22_bank
+                .get_vault_authority(BankVaultType::Liquidity)
+                .0,
+            0,
+        )
+        .await;
+    assert!(!transfer_hook_accounts.is_empty());
+
+    let ix = liquidator_f
+        .make_liquidate_ix(&liquidatee_f, sol_bank, 0.1, usdc_t22_bank)
+        .await;
+    assert!(ix.accounts.ends_with(&transfer_hook_accounts));
+
+    // Without them the hook of the insurance fee transfer can't run
+    let mut ix_without_hook_accounts = ix.clone();
+    ix_without_hook_accounts
+        .accounts
+        .truncate(ix.accounts.len() - transfer_hook_accounts.len());
+    let simulation = test_f
+        .simulate_tx(
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(1_400_000),
+                ix_without_hook_accounts,
+            ],
+            &[],
+        )
+        .await;
+    assert!(simulation.err.is_some());
+
+    let insurance_vault = usdc_t22_bank
+        .get_vault_token_account(BankVaultType::Insurance)
+        .await;
+    let insurance_balance = insurance_vault.balance().await;
+
+    liquidator_f
+        .try_liquidate(&liquidatee_f, sol_bank, 0.1, usdc_t22_bank)
+        .await?;
+
+    assert!(insurance_vault.balance().await > insurance_balance);
+    assert_eq!(liquidatee_f.load().await.get_liquidation_history().len(), 1);
+
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use fixtures::{bank::BankFixture, native, prelude::*};
+use astrolend::state::astrolend_group::BankVaultType;
+use solana_program_test::tokio;
+
+async fn assert_cache_matches_vault(bank_f: &BankFixture) {
+    let liquidity_vault = bank_f
+        .get_vault_token_account(BankVaultType::Liquidity)
+        .await;
+    assert_eq!(
+        bank_f.load().await.get_liquidity_vault_balance(),
+        liquidity_vault.balance().await
+    );
+}
+
+#[tokio::test]
+async fn liquidity_vault_cache_follows_transfers() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    // The vault receives less than the deposit transfer, the transfer fee is withheld in it
+    let t22_bank = test_f.get_bank(&BankMint::T22WithFee);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_t22 = t22_bank.mint.create_token_account_and_mint_to(1_100).await;
+    lender_f
+        .try_bank_deposit(lender_t22.key, t22_bank, 1_000)
+        .await?;
+    assert_cache_matches_vault(t22_bank).await;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    let borrower_t22 = t22_bank.mint.create_token_account_and_mint_to(10).await;
+    borrower_f
+        .try_bank_deposit(borrower_usdc.key, usdc_bank, 100)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_t22.key, t22_bank, 10)
+        .await?;
+    assert_cache_matches_vault(usdc_bank).await;
+    assert_cache_matches_vault(t22_bank).await;
+
+    borrower_f
+        .try_bank_repay(borrower_t22.key, t22_bank, 0, Some(true))
+        .await?;
+    assert_cache_matches_vault(t22_bank).await;
+
+    lender_f
+        .try_bank_withdraw(lender_t22.key, t22_bank, 0, Some(true))
+        .await?;
+    assert_cache_matches_vault(t22_bank).await;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn liquidity_vault_cache_resynced_on_fee_collection() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 100)
+        .await?;
+
+    // Tokens sent to the vault directly aren't seen by the program
+    let (liquidity_vault, _) = usdc_bank.get_vault(BankVaultType::Liquidity);
+    test_f.usdc_mint.clone().mint_to(&liquidity_vault, 25).await;
+    assert_eq!(
+        usdc_bank.load().await.get_liquidity_vault_balance(),
+        native!(100, "USDC")
+    );
+
+    test_f.astrolend_group.try_collect_fees(usdc_bank).await?;
+    assert_cache_matches_vault(usdc_bank).await;
+    assert_eq!(
+        usdc_bank.load().await.get_liquidity_vault_balance(),
+        native!(125, "USDC")
+    );
+
+    Ok(())
+}
@@ -0,0 +1,317 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{
+    assert_custom_error, astrolend_account::AstrolendAccountFixture, bank::BankFixture, native,
+    prelude::*, ui_to_native,
+};
+use astrolend::{
+    errors::AstrolendError,
+    state::{
+        astrolend_account::RiskEngine,
+        astrolend_group::{BankConfigOpt, GroupConfig, InterestRateConfigOpt},
+        price::{FixedPriceFeed, OraclePriceFeedAdapter},
+    },
+};
+use solana_program_test::tokio;
+use std::collections::HashMap;
+
+enum MaxAmount {
+    Borrow,
+    Withdraw,
+}
+
+/// Max amount quoted offchain from the current bank and account states, at the oracle prices
+async fn get_max_amount(
+    test_f: &TestFixture,
+    account_f: &AstrolendAccountFixture,
+    bank_f: &BankFixture,
+    kind: MaxAmount,
+) -> u64 {
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let mut bank_map = HashMap::new();
+    let mut price_map = HashMap::new();
+    for (bank, price) in [(usdc_bank, I80F48!(1)), (sol_bank, I80F48!(10))] {
+        bank_map.insert(bank.key, bank.load().await);
+        price_map.insert(
+            bank.key,
+            OraclePriceFeedAdapter::Fixed(FixedPriceFeed::new(price)),
+        );
+    }
+
+    let astrolend_group = test_f.astrolend_group.load().await;
+    let astrolend_account = account_f.load().await;
+    let timestamp = test_f.get_clock().await.unix_timestamp;
+
+    let max_amount = match kind {
+        MaxAmount::Borrow => RiskEngine::get_max_borrow_for_bank(
+            &astrolend_group,
+            &astrolend_account,
+            &bank_f.key,
+            &bank_map,
+            &price_map,
+            timestamp,
+        ),
+        MaxAmount::Withdraw => RiskEngine::get_max_withdraw_for_bank(
+            &astrolend_group,
+            &astrolend_account,
+            &bank_f.key,
+            &bank_map,
+            &price_map,
+            timestamp,
+        ),
+    }
+    .unwrap();
+
+    max_amount.to_num()
+}
+
+/// UI amount the fixtures convert back to exactly `amount` native units
+fn ui(amount: u64, decimals: u8) -> f64 {
+    let ui_amount = (amount as f64 + 0.5) / 10_u64.pow(decimals as u32) as f64;
+    assert_eq!(ui_to_native!(ui_amount, decimals), amount);
+
+    ui_amount
+}
+
+async fn setup(test_f: &TestFixture) -> anyhow::Result<AstrolendAccountFixture> {
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    sol_bank
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.8).into()),
+            ..Default::default()
+        })
+        .await?;
+    usdc_bank
+        .update_config(BankConfigOpt {
+            liability_weight_init: Some(I80F48!(1.25).into()),
+            interest_rate_config: Some(InterestRateConfigOpt {
+                protocol_origination_fee: Some(I80F48!(0.01).into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .await?;
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 10)
+        .await?;
+
+    Ok(borrower_f)
+}
+
+#[tokio::test]
+async fn max_borrow_is_the_largest_passing_borrow() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let borrower_f = setup(&test_f).await?;
+    let borrower_usdc = test_f.usdc_mint.create_empty_token_account().await;
+
+    // $80 of weighted collateral covers 80 / 1.25 / 1.01 USDC borrowed net of the fee
+    let max_borrow = get_max_amount(&test_f, &borrower_f, usdc_bank, MaxAmount::Borrow).await;
+    assert!(max_borrow > native!(63.36, "USDC", f64));
+    assert!(max_borrow <= native!(63.37, "USDC", f64));
+
+    let res = borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, ui(max_borrow + 1, 6))
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::RiskEngineInitRejected);
+
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, ui(max_borrow, 6))
+        .await?;
+    assert_eq!(borrower_usdc.balance().await, max_borrow);
+
+    let max_borrow = get_max_amount(&test_f, &borrower_f, usdc_bank, MaxAmount::Borrow).await;
+    assert!(max_borrow <= 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn max_borrow_is_capped_by_utilization() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let borrower_f = setup(&test_f).await?;
+    let borrower_usdc = test_f.usdc_mint.create_empty_token_account().await;
+
+    // A second account takes most of the liquidity out, owing 989.8 USDC with the fee
+    let whale_f = test_f.create_astrolend_account().await;
+    let whale_sol = test_f.sol_mint.create_token_account_and_mint_to(1_000).await;
+    let whale_usdc = test_f.usdc_mint.create_empty_token_account().await;
+    whale_f
+        .try_bank_deposit(whale_sol.key, sol_bank, 1_000)
+        .await?;
+    whale_f
+        .try_bank_borrow(whale_usdc.key, usdc_bank, 980)
+        .await?;
+
+    // 10.2 USDC left to owe, 10.2 / 1.01 borrowed net of the fee
+    let max_borrow = get_max_amount(&test_f, &borrower_f, usdc_bank, MaxAmount::Borrow).await;
+    assert!(max_borrow > native!(10.098, "USDC", f64));
+    assert!(max_borrow <= native!(10.1, "USDC", f64));
+
+    let res = borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, ui(max_borrow + 1, 6))
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::IllegalUtilizationRatio);
+
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, ui(max_borrow, 6))
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn max_withdraw_is_the_largest_passing_withdraw() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let borrower_f = setup(&test_f).await?;
+    let borrower_usdc = test_f.usdc_mint.create_empty_token_account().await;
+    let borrower_sol = test_f.sol_mint.create_empty_token_account().await;
+
+    // Without liabilities the whole deposit can be withdrawn
+    let max_withdraw = get_max_amount(&test_f, &borrower_f, sol_bank, MaxAmount::Withdraw).await;
+    assert_eq!(max_withdraw, native!(10, "SOL"));
+
+    // 20.2 USDC owed weigh $25.25, held by 3.15625 SOL
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 20)
+        .await?;
+
+    let max_withdraw = get_max_amount(&test_f, &borrower_f, sol_bank, MaxAmount::Withdraw).await;
+    assert!(max_withdraw > native!(6.8437, "SOL", f64));
+    assert!(max_withdraw <= native!(6.84375, "SOL", f64));
+
+    let res = borrower_f
+        .try_bank_withdraw(borrower_sol.key, sol_bank, ui(max_withdraw + 1, 9), None)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::RiskEngineInitRejected);
+
+    borrower_f
+        .try_bank_withdraw(borrower_sol.key, sol_bank, ui(max_withdraw, 9), None)
+        .await?;
+    assert_eq!(borrower_sol.balance().await, max_withdraw);
+
+    // Nothing to withdraw from a bank without deposits
+    let max_withdraw = get_max_amount(&test_f, &borrower_f, usdc_bank, MaxAmount::Withdraw).await;
+    assert_eq!(max_withdraw, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn max_borrow_leaves_the_init_health_buffer() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let borrower_f = setup(&test_f).await?;
+    let borrower_usdc = test_f.usdc_mint.create_empty_token_account().await;
+
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            init_health_buffer_bps: Some(200),
+            ..Default::default()
+        })
+        .await?;
+
+    // $80 of weighted collateral covers 80 / 1.02 of weighted liabilities
+    let max_borrow = get_max_amount(&test_f, &borrower_f, usdc_bank, MaxAmount::Borrow).await;
+    assert!(max_borrow > native!(62.12, "USDC", f64));
+    assert!(max_borrow <= native!(62.13, "USDC", f64));
+
+    let res = borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, ui(max_borrow + 1, 6))
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::RiskEngineInitRejected);
+
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, ui(max_borrow, 6))
+        .await?;
+    assert_eq!(borrower_usdc.balance().await, max_borrow);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn init_health_buffer_leaves_liquidations_alone() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let borrower_f = setup(&test_f).await?;
+    let borrower_usdc = test_f.usdc_mint.create_empty_token_account().await;
+
+    // Maintenance weights equal to the initial ones, a buffer on maintenance health would
+    // make the account below liquidatable
+    sol_bank
+        .update_config(BankConfigOpt {
+            asset_weight_maint: Some(I80F48!(0.8).into()),
+            ..Default::default()
+        })
+        .await?;
+    usdc_bank
+        .update_config(BankConfigOpt {
+            liability_weight_maint: Some(I80F48!(1.25).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    // About 1% of the weighted liabilities left as margin
+    let max_borrow = get_max_amount(&test_f, &borrower_f, usdc_bank, MaxAmount::Borrow).await;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, ui(max_borrow * 99 / 100, 6))
+        .await?;
+
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            init_health_buffer_bps: Some(200),
+            ..Default::default()
+        })
+        .await?;
+
+    // Short of the buffer, the account can't take on more
+    let res = borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 0.1)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::RiskEngineInitRejected);
+
+    let liquidator_f = test_f.create_astrolend_account().await;
+    let liquidator_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(100)
+        .await;
+    liquidator_f
+        .try_bank_deposit(liquidator_usdc.key, usdc_bank, 100)
+        .await?;
+
+    let res = liquidator_f
+        .try_liquidate(&borrower_f, sol_bank, 0.1, usdc_bank)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::IllegalLiquidation);
+
+    Ok(())
+}
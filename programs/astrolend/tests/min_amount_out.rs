@@ -0,0 +1,216 @@
+use fixtures::{
+    assert_custom_error, assert_eq_noise, astrolend_account::AstrolendAccountFixture,
+    bank::BankFixture, native, prelude::*,
+};
+use astrolend::errors::AstrolendError;
+use solana_program::instruction::Instruction;
+use solana_program_test::{tokio, BanksClientError};
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+async fn send(test_f: &TestFixture, ix: Instruction) -> Result<(), BanksClientError> {
+    let mut ctx = test_f.context.borrow_mut();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer],
+        ctx.last_blockhash,
+    );
+
+    ctx.banks_client.process_transaction(tx).await
+}
+
+/// The test mint charges 5%, raise it to 10% from the next epoch on and move into it
+async fn raise_transfer_fee(test_f: &TestFixture) {
+    let epoch = test_f.get_clock().await.epoch;
+    test_f
+        .get_bank(&BankMint::T22WithFee)
+        .mint
+        .schedule_transfer_fee(epoch + 1, 1_000)
+        .await;
+    test_f.advance_epoch().await;
+}
+
+#[tokio::test]
+async fn withdraw_all_min_amount_out_across_fee_change() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let bank_f = test_f.get_bank(&BankMint::T22WithFee);
+
+    let account_f = test_f.create_astrolend_account().await;
+    let token_account = bank_f.mint.create_token_account_and_mint_to(110).await;
+    account_f
+        .try_bank_deposit(token_account.key, bank_f, 100)
+        .await?;
+
+    // Quoted at 5%, 100 deposited comes back as 95
+    let min_amount_out = native!(95, "T22_WITH_FEE");
+
+    raise_transfer_fee(&test_f).await;
+
+    let res = account_f
+        .try_bank_withdraw_with_min_out(
+            token_account.key,
+            bank_f,
+            0,
+            Some(true),
+            Some(min_amount_out),
+        )
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::SlippageExceeded);
+
+    // Accepting the new fee goes through
+    let balance_before = token_account.balance().await;
+    account_f
+        .try_bank_withdraw_with_min_out(
+            token_account.key,
+            bank_f,
+            0,
+            Some(true),
+            Some(native!(89, "T22_WITH_FEE")),
+        )
+        .await?;
+    assert_eq_noise!(
+        (token_account.balance().await - balance_before) as i64,
+        native!(90, "T22_WITH_FEE") as i64,
+        1
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn borrow_min_amount_out_across_fee_change() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let bank_f = test_f.get_bank(&BankMint::T22WithFee);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_token_account = bank_f.mint.create_token_account_and_mint_to(1_100).await;
+    lender_f
+        .try_bank_deposit(lender_token_account.key, bank_f, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    borrower_f
+        .try_bank_deposit(borrower_usdc.key, test_f.get_bank(&BankMint::Usdc), 100)
+        .await?;
+
+    raise_transfer_fee(&test_f).await;
+
+    // Quoted at 5%, 10 borrowed arrive as 9.5. With a minimum the borrow isn't grossed up, the
+    // raised fee is taken out of what arrives and trips the minimum
+    let destination = bank_f.mint.create_empty_token_account().await;
+    let ix = borrower_f
+        .make_bank_borrow_with_min_out_ix(
+            destination.key,
+            bank_f,
+            10,
+            None,
+            Some(native!(9.5, "T22_WITH_FEE", f64)),
+        )
+        .await;
+    let res = send(&test_f, ix).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::SlippageExceeded);
+
+    // Accepting the new fee goes through, only the amount borrowed is carried by the liability
+    let ix = borrower_f
+        .make_bank_borrow_with_min_out_ix(
+            destination.key,
+            bank_f,
+            10,
+            None,
+            Some(native!(9, "T22_WITH_FEE")),
+        )
+        .await;
+    send(&test_f, ix).await?;
+    assert_eq!(destination.balance().await, native!(9, "T22_WITH_FEE"));
+    assert_eq_noise!(
+        liability_amount(&borrower_f, bank_f).await as i64,
+        native!(10, "T22_WITH_FEE") as i64,
+        1
+    );
+
+    // Without a minimum the borrow is grossed up at the current fee, the full amount arrives
+    let ix = borrower_f
+        .make_bank_borrow_ix(destination.key, bank_f, 10)
+        .await;
+    send(&test_f, ix).await?;
+    assert_eq!(destination.balance().await, native!(19, "T22_WITH_FEE"));
+    assert!(liability_amount(&borrower_f, bank_f).await > native!(21, "T22_WITH_FEE"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn withdraw_min_amount_out_across_fee_change() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let bank_f = test_f.get_bank(&BankMint::T22WithFee);
+
+    let account_f = test_f.create_astrolend_account().await;
+    let token_account = bank_f.mint.create_token_account_and_mint_to(110).await;
+    account_f
+        .try_bank_deposit(token_account.key, bank_f, 100)
+        .await?;
+    let deposited = asset_amount(&account_f, bank_f).await;
+
+    // Quoted at 5%, withdrawing 50 brings back 47.5
+    raise_transfer_fee(&test_f).await;
+
+    let res = account_f
+        .try_bank_withdraw_with_min_out(
+            token_account.key,
+            bank_f,
+            50,
+            None,
+            Some(native!(47.5, "T22_WITH_FEE", f64)),
+        )
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::SlippageExceeded);
+
+    // Accepting the new fee goes through, exactly the requested amount is debited
+    let balance_before = token_account.balance().await;
+    account_f
+        .try_bank_withdraw_with_min_out(
+            token_account.key,
+            bank_f,
+            50,
+            None,
+            Some(native!(45, "T22_WITH_FEE")),
+        )
+        .await?;
+    assert_eq!(
+        token_account.balance().await - balance_before,
+        native!(45, "T22_WITH_FEE")
+    );
+    assert_eq_noise!(
+        (deposited - asset_amount(&account_f, bank_f).await) as i64,
+        native!(50, "T22_WITH_FEE") as i64,
+        1
+    );
+
+    Ok(())
+}
+
+async fn asset_amount(account_f: &AstrolendAccountFixture, bank_f: &BankFixture) -> u64 {
+    let account = account_f.load().await;
+    let balance = account.lending_account.get_balance(&bank_f.key).unwrap();
+    bank_f
+        .load()
+        .await
+        .get_asset_amount(balance.asset_shares.into())
+        .unwrap()
+        .to_num()
+}
+
+async fn liability_amount(account_f: &AstrolendAccountFixture, bank_f: &BankFixture) -> u64 {
+    let account = account_f.load().await;
+    let balance = account.lending_account.get_balance(&bank_f.key).unwrap();
+    bank_f
+        .load()
+        .await
+        .get_liability_amount(balance.liability_shares.into())
+        .unwrap()
+        .to_num()
+}
@@ -0,0 +1,102 @@
+use fixed::types::I80F48;
+use fixtures::{assert_custom_error, native, prelude::*};
+use astrolend::{errors::AstrolendError, state::astrolend_group::BankConfigOpt};
+use solana_program_test::tokio;
+
+#[tokio::test]
+async fn min_deposit_only_applies_to_new_positions() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    usdc_bank
+        .update_config(BankConfigOpt {
+            min_deposit_amount: Some(native!(10, "USDC")),
+            ..Default::default()
+        })
+        .await?;
+
+    let astrolend_account_f = test_f.create_astrolend_account().await;
+    let token_account_f = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+
+    let res = astrolend_account_f
+        .try_bank_deposit(token_account_f.key, usdc_bank, 5)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::DepositBelowMinimum);
+
+    astrolend_account_f
+        .try_bank_deposit(token_account_f.key, usdc_bank, 10)
+        .await?;
+
+    // Topping up an existing position below the minimum is fine
+    astrolend_account_f
+        .try_bank_deposit(token_account_f.key, usdc_bank, 1)
+        .await?;
+
+    // So is withdrawing to a remainder below the minimum
+    astrolend_account_f
+        .try_bank_withdraw(token_account_f.key, usdc_bank, 10.5, None)
+        .await?;
+
+    astrolend_account_f
+        .assert_deposit_approx_eq(usdc_bank, I80F48::from(native!(0.5, "USDC", f64)), I80F48::ONE)
+        .await;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn min_borrow_only_applies_to_new_positions() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    usdc_bank
+        .update_config(BankConfigOpt {
+            min_borrow_amount: Some(native!(5, "USDC")),
+            ..Default::default()
+        })
+        .await?;
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 10)
+        .await?;
+
+    let res = borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 4)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::BorrowBelowMinimum);
+
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 5)
+        .await?;
+
+    // Topping up an existing position below the minimum is fine
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 1)
+        .await?;
+
+    // So is repaying to a remainder below the minimum
+    borrower_f
+        .try_bank_repay(borrower_usdc.key, usdc_bank, 5.5, None)
+        .await?;
+
+    borrower_f
+        .assert_liability_approx_eq(usdc_bank, I80F48::from(native!(0.5, "USDC", f64)), I80F48::ONE)
+        .await;
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use fixtures::{assert_custom_error, native, prelude::*};
+use astrolend::{
+    constants::MAX_MINT_DECIMALS, errors::AstrolendError, state::astrolend_group::BankConfig,
+};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+
+async fn add_oracle_for_mint(test_f: &TestFixture, mint: &MintFixture) -> Pubkey {
+    let oracle = Pubkey::new_unique();
+    test_f.context.borrow_mut().set_account(
+        &oracle,
+        &create_pyth_legacy_oracle_account(mint.key, 1.0, 6, None).into(),
+    );
+
+    oracle
+}
+
+/// A 12 decimal mint holding close to `u64::MAX` native tokens can be used as collateral.
+#[tokio::test]
+async fn high_decimals_bank_collateral() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let mint_f = MintFixture::new(test_f.context.clone(), None, Some(12)).await;
+    let oracle = add_oracle_for_mint(&test_f, &mint_f).await;
+
+    let bank_f = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank(
+            &mint_f,
+            BankConfig {
+                oracle_keys: create_oracle_key_array(oracle),
+                deposit_limit: u64::MAX,
+                borrow_limit: u64::MAX,
+                ..*DEFAULT_TEST_BANK_CONFIG
+            },
+        )
+        .await?;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let lender_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(10_000)
+        .await;
+    lender_account_f
+        .try_bank_deposit(lender_token_account.key, usdc_bank, 10_000)
+        .await?;
+
+    let borrower_account_f = test_f.create_astrolend_account().await;
+    let collateral_account = mint_f.create_token_account_and_mint_to(18_000_000).await;
+    borrower_account_f
+        .try_bank_deposit(collateral_account.key, &bank_f, 18_000_000)
+        .await?;
+
+    let borrower_usdc_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_account_f
+        .try_bank_borrow(borrower_usdc_account.key, usdc_bank, 1_000)
+        .await?;
+
+    assert_eq!(
+        borrower_usdc_account.balance().await,
+        native!(1_000, "USDC")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn add_bank_rejects_unsupported_decimals() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let mint_f =
+        MintFixture::new(test_f.context.clone(), None, Some(MAX_MINT_DECIMALS + 1)).await;
+    let oracle = add_oracle_for_mint(&test_f, &mint_f).await;
+
+    let res = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank(
+            &mint_f,
+            BankConfig {
+                oracle_keys: create_oracle_key_array(oracle),
+                ..*DEFAULT_TEST_BANK_CONFIG
+            },
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::MintDecimalsNotSupported);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn deposit_rejects_mint_with_mutated_decimals() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let bank_f = test_f.get_bank(&BankMint::T22WithFee);
+
+    let account_f = test_f.create_astrolend_account().await;
+    let token_account = bank_f.mint.create_token_account_and_mint_to(100).await;
+    account_f
+        .try_bank_deposit(token_account.key, bank_f, 50)
+        .await?;
+
+    // Same key, different decimals: the base mint layout keeps decimals at offset 44
+    let mut mint_account = test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(bank_f.mint.key)
+        .await?
+        .unwrap();
+    mint_account.data[44] = T22_WITH_FEE_MINT_DECIMALS + 3;
+    test_f
+        .context
+        .borrow_mut()
+        .set_account(&bank_f.mint.key, &mint_account.into());
+
+    let res = account_f
+        .try_bank_deposit(token_account.key, bank_f, 10)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::MintMismatch);
+
+    Ok(())
+}
@@ -0,0 +1,228 @@
+use astrolend::{
+    errors::AstrolendError,
+    state::{
+        astrolend_account::AccountConfigOpt,
+        astrolend_group::{BankVaultType, GroupConfig},
+    },
+    utils::find_native_token_account_pda,
+};
+use fixed::types::I80F48;
+use fixtures::{assert_custom_error, assert_eq_noise, bank::BankFixture, native, prelude::*};
+use solana_program::{instruction::Instruction, pubkey::Pubkey, system_instruction};
+use solana_program_test::{tokio, BanksClientError};
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+async fn add_native_bank(test_f: &TestFixture) -> anyhow::Result<BankFixture> {
+    let native_mint_f = MintFixture::new_native(test_f.context.clone()).await;
+
+    Ok(test_f
+        .astrolend_group
+        .try_lending_pool_add_bank(&native_mint_f, *DEFAULT_SOL_TEST_BANK_CONFIG)
+        .await?)
+}
+
+async fn lamports(test_f: &TestFixture, address: Pubkey) -> u64 {
+    test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_balance(address)
+        .await
+        .unwrap()
+}
+
+/// Process `ix` signed by the payer, returns the transaction fee it paid
+async fn process_for_fee(test_f: &TestFixture, ix: Instruction) -> anyhow::Result<u64> {
+    let mut ctx = test_f.context.borrow_mut();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer],
+        ctx.last_blockhash,
+    );
+    let fee = ctx
+        .banks_client
+        .get_fee_for_message(tx.message.clone())
+        .await?
+        .unwrap();
+    ctx.banks_client.process_transaction(tx).await?;
+
+    Ok(fee)
+}
+
+/// Lamports in, lamports out, the ephemeral wSOL account rent always comes back
+#[tokio::test]
+async fn native_sol_round_trip() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+    let bank_f = add_native_bank(&test_f).await?;
+    let liquidity_vault = bank_f.get_vault_token_account(BankVaultType::Liquidity).await;
+    let user_f = test_f.create_astrolend_account().await;
+    let payer = test_f.payer();
+    let (native_token_account, _) = find_native_token_account_pda(&user_f.key);
+
+    let before = lamports(&test_f, payer).await;
+    let ix = user_f.make_deposit_native_ix(&bank_f, 2).await;
+    let fee = process_for_fee(&test_f, ix).await?;
+    assert_eq!(
+        before - lamports(&test_f, payer).await,
+        native!(2, "SOL") + fee
+    );
+    assert_eq!(liquidity_vault.balance().await, native!(2, "SOL"));
+    assert_eq!(lamports(&test_f, native_token_account).await, 0);
+
+    let positions = user_f.load_positions(&[&bank_f]).await;
+    assert_eq!(
+        positions[0].asset_amount,
+        I80F48::from_num(native!(2, "SOL"))
+    );
+
+    let before = lamports(&test_f, payer).await;
+    let ix = user_f.make_withdraw_native_ix(&bank_f, 0.5, None).await;
+    let fee = process_for_fee(&test_f, ix).await?;
+    assert_eq!(
+        lamports(&test_f, payer).await + fee - before,
+        native!(0.5, "SOL", f64)
+    );
+    assert_eq!(liquidity_vault.balance().await, native!(1.5, "SOL", f64));
+    assert_eq!(lamports(&test_f, native_token_account).await, 0);
+
+    let before = lamports(&test_f, payer).await;
+    let ix = user_f.make_withdraw_native_ix(&bank_f, 0, Some(true)).await;
+    let fee = process_for_fee(&test_f, ix).await?;
+    assert_eq!(
+        lamports(&test_f, payer).await + fee - before,
+        native!(1.5, "SOL", f64)
+    );
+    assert_eq!(liquidity_vault.balance().await, 0);
+    assert!(user_f.load_positions(&[&bank_f]).await.is_empty());
+
+    Ok(())
+}
+
+/// Lamports sent to the ephemeral account address ahead of time don't block deposits, they
+/// are unwrapped to the signer with the rent
+#[tokio::test]
+async fn native_deposit_with_prefunded_token_account_address() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+    let bank_f = add_native_bank(&test_f).await?;
+    let user_f = test_f.create_astrolend_account().await;
+    let payer = test_f.payer();
+    let (native_token_account, _) = find_native_token_account_pda(&user_f.key);
+
+    let ix = system_instruction::transfer(&payer, &native_token_account, 1_000_000);
+    process_for_fee(&test_f, ix).await?;
+
+    let before = lamports(&test_f, payer).await;
+    let ix = user_f.make_deposit_native_ix(&bank_f, 1).await;
+    let fee = process_for_fee(&test_f, ix).await?;
+    assert_eq!(
+        before + 1_000_000 - lamports(&test_f, payer).await,
+        native!(1, "SOL") + fee
+    );
+    assert_eq!(lamports(&test_f, native_token_account).await, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn native_deposit_requires_a_native_mint_bank() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    MintFixture::new_native(test_f.context.clone()).await;
+    let user_f = test_f.create_astrolend_account().await;
+
+    let ix = user_f
+        .make_deposit_native_ix(test_f.get_bank(&BankMint::Sol), 1)
+        .await;
+    let simulation = test_f.simulate_tx(&[ix], &[]).await;
+    assert_eq!(
+        simulation.custom_error_code(),
+        Some(AstrolendError::NativeMintRequired.into())
+    );
+
+    Ok(())
+}
+
+/// Restricted destinations still allow native withdraws, the lamports go to the authority
+#[tokio::test]
+async fn native_withdraw_with_restricted_destinations() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+    let bank_f = add_native_bank(&test_f).await?;
+    let user_f = test_f.create_astrolend_account().await;
+    let payer = test_f.payer();
+
+    let ix = user_f.make_deposit_native_ix(&bank_f, 2).await;
+    process_for_fee(&test_f, ix).await?;
+    user_f
+        .try_configure(AccountConfigOpt {
+            destinations_restricted: Some(true),
+            approved_destination: Some(Pubkey::new_unique()),
+            ..Default::default()
+        })
+        .await?;
+
+    let before = lamports(&test_f, payer).await;
+    let ix = user_f.make_withdraw_native_ix(&bank_f, 1, None).await;
+    let fee = process_for_fee(&test_f, ix).await?;
+    assert_eq!(
+        lamports(&test_f, payer).await + fee - before,
+        native!(1, "SOL")
+    );
+
+    // Plain withdraws of the same bank are held to the associated token account
+    let wsol_account = TokenAccountFixture::new_with_token_program(
+        test_f.context.clone(),
+        &bank_f.mint.key,
+        &payer,
+        &bank_f.mint.token_program,
+    )
+    .await;
+    let res = user_f
+        .try_bank_withdraw(wsol_account.key, &bank_f, 0.5, None)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::DestinationNotAllowed);
+
+    Ok(())
+}
+
+/// Native deposits and withdrawals count against the group TVL cap like token ones
+#[tokio::test]
+async fn native_sol_tracks_group_tvl() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+    let bank_f = add_native_bank(&test_f).await?;
+    let user_f = test_f.create_astrolend_account().await;
+
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            group_tvl_cap_usd: Some(20),
+            ..Default::default()
+        })
+        .await?;
+
+    // 2 SOL @ $10 sits exactly at the cap
+    let ix = user_f.make_deposit_native_ix(&bank_f, 2).await;
+    process_for_fee(&test_f, ix).await?;
+
+    let ix = user_f.make_deposit_native_ix(&bank_f, 0.5).await;
+    let res = process_for_fee(&test_f, ix).await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err().downcast::<BanksClientError>()?,
+        AstrolendError::GroupTvlCapExceeded
+    );
+
+    let ix = user_f.make_withdraw_native_ix(&bank_f, 1, None).await;
+    process_for_fee(&test_f, ix).await?;
+    assert_eq_noise!(
+        I80F48::from(test_f.astrolend_group.load().await.group_tvl_usd),
+        I80F48::from_num(10),
+        I80F48::from_num(0.001)
+    );
+
+    // The withdrawal freed up room under the cap
+    let ix = user_f.make_deposit_native_ix(&bank_f, 0.4).await;
+    process_for_fee(&test_f, ix).await?;
+
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use fixtures::{assert_custom_error, prelude::*};
+use astrolend::{
+    errors::AstrolendError,
+    events::LendingPoolBankConfigureOracleUncheckedEvent,
+    state::{
+        astrolend_group::{BankConfigOpt, GroupConfig, OracleConfig},
+        price::OracleSetup,
+    },
+};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::{tokio, BanksClientError};
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+fn pyth_legacy_oracle(key: Pubkey) -> OracleConfig {
+    OracleConfig {
+        setup: OracleSetup::PythLegacy,
+        keys: create_oracle_key_array(key),
+    }
+}
+
+#[tokio::test]
+async fn oracle_change_to_same_asset_feed() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let new_feed = Pubkey::new_unique();
+    test_f.context.borrow_mut().set_account(
+        &new_feed,
+        &create_pyth_legacy_oracle_account(
+            test_f.usdc_mint.key,
+            1.02,
+            USDC_MINT_DECIMALS.into(),
+            None,
+        )
+        .into(),
+    );
+
+    usdc_bank
+        .update_config(BankConfigOpt {
+            oracle_keys_and_setup: Some(pyth_legacy_oracle(new_feed)),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(usdc_bank.load().await.config.oracle_keys[0], new_feed);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn oracle_change_to_different_asset_feed_is_rejected() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let res = usdc_bank
+        .update_config(BankConfigOpt {
+            oracle_keys_and_setup: Some(pyth_legacy_oracle(PYTH_SOL_FEED)),
+            ..Default::default()
+        })
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err().downcast::<BanksClientError>()?,
+        AstrolendError::OracleChangePriceMismatch
+    );
+    assert_eq!(usdc_bank.load().await.config.oracle_keys[0], PYTH_USDC_FEED);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn oracle_change_respects_group_max_deviation() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            oracle_change_max_deviation_bps: Some(100),
+            ..Default::default()
+        })
+        .await?;
+
+    // 2% off the current feed, within the default deviation but not the group's
+    let new_feed = Pubkey::new_unique();
+    test_f.context.borrow_mut().set_account(
+        &new_feed,
+        &create_pyth_legacy_oracle_account(
+            test_f.usdc_mint.key,
+            1.02,
+            USDC_MINT_DECIMALS.into(),
+            None,
+        )
+        .into(),
+    );
+
+    let res = usdc_bank
+        .update_config(BankConfigOpt {
+            oracle_keys_and_setup: Some(pyth_legacy_oracle(new_feed)),
+            ..Default::default()
+        })
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err().downcast::<BanksClientError>()?,
+        AstrolendError::OracleChangePriceMismatch
+    );
+
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            oracle_change_max_deviation_bps: Some(300),
+            ..Default::default()
+        })
+        .await?;
+    usdc_bank
+        .update_config(BankConfigOpt {
+            oracle_keys_and_setup: Some(pyth_legacy_oracle(new_feed)),
+            ..Default::default()
+        })
+        .await?;
+    assert_eq!(usdc_bank.load().await.config.oracle_keys[0], new_feed);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn oracle_change_unchecked_emits_event() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let ix = usdc_bank
+        .make_configure_oracle_unchecked_ix(pyth_legacy_oracle(PYTH_SOL_FEED))
+        .await;
+
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await?
+    };
+
+    assert!(result.result.is_ok());
+
+    let logs = result.metadata.unwrap().log_messages;
+    let event = find_event::<LendingPoolBankConfigureOracleUncheckedEvent>(&logs).unwrap();
+
+    assert_eq!(event.bank, usdc_bank.key);
+    assert_eq!(event.old_oracle.keys[0], PYTH_USDC_FEED);
+    assert_eq!(event.new_oracle.keys[0], PYTH_SOL_FEED);
+    assert_eq!(usdc_bank.load().await.config.oracle_keys[0], PYTH_SOL_FEED);
+
+    Ok(())
+}
@@ -0,0 +1,246 @@
+use astrolend::{
+    constants::{MAX_ORACLE_CONFIDENCE_BPS, MAX_ORACLE_MAX_AGE},
+    errors::AstrolendError,
+    events::{LendingPoolBankConfigureEvent, LendingPoolBankOracleParamsUpdateEvent},
+    state::{
+        astrolend_group::{BankConfigOpt, OracleConfig, OracleParamsOpt, PriceBiasMode},
+        price::OracleSetup,
+    },
+};
+use fixtures::{assert_custom_error, prelude::*};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+use solana_program_test::{tokio, BanksClientError};
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+async fn configure_bank(
+    test_f: &TestFixture,
+    ix: Instruction,
+) -> anyhow::Result<LendingPoolBankConfigureEvent> {
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await?
+    };
+    result.result?;
+
+    let mut events = parse_events::<LendingPoolBankConfigureEvent>(&result);
+    assert_eq!(events.len(), 1);
+
+    Ok(events.remove(0))
+}
+
+#[tokio::test]
+async fn oracle_params_change_without_oracle_account() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let config = BankConfigOpt {
+        oracle_params: Some(OracleParamsOpt {
+            max_age: Some(30),
+            max_confidence_bps: Some(100),
+            price_bias_mode: Some(PriceBiasMode::Disabled),
+        }),
+        ..Default::default()
+    };
+
+    // Only the group, admin and bank accounts
+    let ix = test_f
+        .astrolend_group
+        .make_lending_pool_configure_bank_ix(sol_bank, config);
+    assert_eq!(ix.accounts.len(), 3);
+
+    let event = configure_bank(&test_f, ix).await?;
+    assert!(event.oracle_params_changed);
+    assert!(!event.oracle_changed);
+
+    let bank_config = sol_bank.load().await.config;
+    assert_eq!(bank_config.oracle_max_age, 30);
+    assert_eq!(bank_config.oracle_max_confidence_bps, 100);
+    assert_eq!(bank_config.oracle_price_bias_mode, PriceBiasMode::Disabled);
+    assert_eq!(bank_config.oracle_keys[0], PYTH_SOL_FEED);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn oracle_keys_change_requires_oracle_account() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let new_feed = Pubkey::new_unique();
+    test_f.context.borrow_mut().set_account(
+        &new_feed,
+        &create_pyth_legacy_oracle_account(
+            test_f.usdc_mint.key,
+            1.0,
+            USDC_MINT_DECIMALS.into(),
+            None,
+        )
+        .into(),
+    );
+    let config = BankConfigOpt {
+        oracle_keys_and_setup: Some(OracleConfig {
+            setup: OracleSetup::PythLegacy,
+            keys: create_oracle_key_array(new_feed),
+        }),
+        ..Default::default()
+    };
+
+    // Without the oracle accounts the new setup can't be validated
+    let mut ix = test_f
+        .astrolend_group
+        .make_lending_pool_configure_bank_ix(usdc_bank, config);
+    ix.accounts.truncate(3);
+    let res = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix.clone()],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client.process_transaction(tx).await
+    };
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidOracleAccount);
+
+    // The new oracle, then the current one to compare prices
+    ix.accounts.extend(
+        [new_feed, PYTH_USDC_FEED]
+            .into_iter()
+            .map(|key| AccountMeta::new_readonly(key, false)),
+    );
+    let event = configure_bank(&test_f, ix).await?;
+    assert!(event.oracle_changed);
+    assert!(!event.oracle_params_changed);
+    assert_eq!(usdc_bank.load().await.config.oracle_keys[0], new_feed);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn oracle_params_out_of_bounds_are_rejected() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let bank_config = sol_bank.load().await.config;
+
+    for oracle_params in [
+        OracleParamsOpt {
+            max_age: Some(MAX_ORACLE_MAX_AGE + 1),
+            ..Default::default()
+        },
+        OracleParamsOpt {
+            max_confidence_bps: Some(MAX_ORACLE_CONFIDENCE_BPS + 1),
+            ..Default::default()
+        },
+    ] {
+        let res = sol_bank
+            .update_config(BankConfigOpt {
+                oracle_params: Some(oracle_params),
+                ..Default::default()
+            })
+            .await;
+        assert!(res.is_err());
+        assert_custom_error!(
+            res.unwrap_err().downcast::<BanksClientError>()?,
+            AstrolendError::InvalidConfig
+        );
+    }
+
+    assert_eq!(sol_bank.load().await.config, bank_config);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn group_oracle_params_update_skips_frozen_banks() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let banks = [
+        test_f.get_bank(&BankMint::Usdc),
+        test_f.get_bank(&BankMint::Sol),
+        test_f.get_bank(&BankMint::SolEquivalent),
+        test_f.get_bank(&BankMint::PyUSD),
+    ];
+    let frozen_bank = banks[3];
+
+    frozen_bank
+        .update_config(BankConfigOpt {
+            config_frozen: Some(true),
+            ..Default::default()
+        })
+        .await?;
+    let frozen_config = frozen_bank.load().await.config;
+
+    let ix = test_f.astrolend_group.make_update_oracle_max_confidence_ix(
+        &banks,
+        OracleParamsOpt {
+            max_age: Some(30),
+            max_confidence_bps: Some(100),
+            price_bias_mode: Some(PriceBiasMode::Disabled),
+        },
+    );
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await?
+    };
+    assert!(result.result.is_ok());
+
+    let events = parse_events::<LendingPoolBankOracleParamsUpdateEvent>(&result);
+    assert_eq!(events.len(), 4);
+
+    for (bank_f, event) in banks[..3].iter().zip(&events) {
+        assert_eq!(event.bank, bank_f.key);
+        assert!(!event.skipped);
+        assert_eq!(event.old_max_confidence_bps, 0);
+        assert_eq!(event.new_max_confidence_bps, 100);
+
+        let bank_config = bank_f.load().await.config;
+        assert_eq!(bank_config.oracle_max_age, 30);
+        assert_eq!(bank_config.oracle_max_confidence_bps, 100);
+        assert_eq!(bank_config.oracle_price_bias_mode, PriceBiasMode::Disabled);
+    }
+
+    let event = &events[3];
+    assert_eq!(event.bank, frozen_bank.key);
+    assert!(event.skipped);
+    assert_eq!(event.new_max_age, event.old_max_age);
+    assert_eq!(event.new_max_confidence_bps, event.old_max_confidence_bps);
+    assert_eq!(event.new_price_bias_mode, event.old_price_bias_mode);
+    assert_eq!(frozen_bank.load().await.config, frozen_config);
+
+    // The frozen bank only takes a config update unfreezing it
+    let res = frozen_bank
+        .update_config(BankConfigOpt {
+            oracle_params: Some(OracleParamsOpt {
+                max_confidence_bps: Some(100),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err().downcast::<BanksClientError>()?,
+        AstrolendError::BankConfigFrozen
+    );
+
+    Ok(())
+}
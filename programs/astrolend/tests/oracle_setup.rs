@@ -0,0 +1,160 @@
+use fixtures::{assert_custom_error, assert_eq_noise, prelude::*};
+use astrolend::{
+    errors::AstrolendError,
+    state::{
+        astrolend_group::{BankConfigOpt, OracleConfig},
+        price::OracleSetup,
+    },
+};
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_program_test::{tokio, BanksClientError};
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+async fn send(test_f: &TestFixture, ix: Instruction) -> Result<(), BanksClientError> {
+    let mut ctx = test_f.context.borrow_mut();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer],
+        ctx.last_blockhash,
+    );
+
+    ctx.banks_client.process_transaction(tx).await
+}
+
+#[tokio::test]
+async fn add_bank_with_wrong_oracle_account_is_rejected() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let res = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_oracle_ai(
+            &test_f.usdc_mint,
+            *DEFAULT_USDC_TEST_BANK_CONFIG,
+            PYTH_SOL_FEED,
+        )
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::WrongOracleAccountKeys);
+
+    let res = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_seed_and_oracle_ai(
+            &test_f.usdc_mint,
+            *DEFAULT_USDC_TEST_BANK_CONFIG,
+            0,
+            PYTH_SOL_FEED,
+        )
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::WrongOracleAccountKeys);
+
+    // Pyth push feeds are matched on the oracle account derived from the feed id
+    let res = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_oracle_ai(
+            &test_f.sol_mint,
+            *DEFAULT_SOL_TEST_PYTH_PUSH_FULLV_BANK_CONFIG,
+            *PYTH_PUSH_SOL_PARTV_FEED,
+        )
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::WrongOracleAccountKeys);
+
+    test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_oracle_ai(
+            &test_f.sol_mint,
+            *DEFAULT_SOL_TEST_PYTH_PUSH_FULLV_BANK_CONFIG,
+            *PYTH_PUSH_SOL_FULLV_FEED,
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn add_bank_with_unused_oracle_keys_is_rejected() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let mut bank_config = *DEFAULT_USDC_TEST_BANK_CONFIG;
+    bank_config.oracle_keys[1] = Pubkey::new_unique();
+
+    let res = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, bank_config)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::WrongOracleAccountKeys);
+
+    let res = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_seed(&test_f.usdc_mint, bank_config, 0)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::WrongOracleAccountKeys);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn configure_bank_oracle_with_wrong_keys_is_rejected() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    // The first remaining account is the oracle, it must be the configured key
+    let mut ix = test_f.astrolend_group.make_lending_pool_configure_bank_ix(
+        usdc_bank,
+        BankConfigOpt {
+            oracle_keys_and_setup: Some(OracleConfig {
+                setup: OracleSetup::PythLegacy,
+                keys: create_oracle_key_array(PYTH_USDC_FEED),
+            }),
+            ..Default::default()
+        },
+    );
+    ix.accounts[3].pubkey = PYTH_SOL_FEED;
+    let res = send(&test_f, ix).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::WrongOracleAccountKeys);
+
+    // Unused slots must stay empty
+    let mut keys = create_oracle_key_array(PYTH_USDC_FEED);
+    keys[1] = Pubkey::new_unique();
+    let res = usdc_bank
+        .update_config(BankConfigOpt {
+            oracle_keys_and_setup: Some(OracleConfig {
+                setup: OracleSetup::PythLegacy,
+                keys,
+            }),
+            ..Default::default()
+        })
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err().downcast::<BanksClientError>()?,
+        AstrolendError::WrongOracleAccountKeys
+    );
+
+    let bank = usdc_bank.load().await;
+    assert_eq!(bank.config.oracle_keys, create_oracle_key_array(PYTH_USDC_FEED));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn bank_price_goes_stale_past_max_age() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let max_age = sol_bank.load().await.config.get_oracle_max_age();
+
+    let now = test_f.get_clock().await.unix_timestamp;
+    test_f.set_pyth_oracle_timestamp(PYTH_SOL_FEED, now).await;
+    assert_eq_noise!(sol_bank.try_get_price().await?, 10.0, 0.0001);
+
+    test_f.advance_time(max_age as i64 + 1).await;
+    let res = sol_bank.try_get_price().await;
+    assert_eq!(res.unwrap_err(), AstrolendError::StaleOracle.into());
+
+    Ok(())
+}
@@ -0,0 +1,255 @@
+use astrolend::{
+    constants::EMISSIONS_FLAG_LENDING_ACTIVE, state::astrolend_account::AstrolendAccount,
+};
+use fixed::types::I80F48;
+use fixtures::{
+    astrolend_cpi::{self, BankObservation},
+    bank::BankFixture,
+    native,
+    prelude::*,
+    test_cpi_caller::{find_authority_address, INVOKE_AS_AUTHORITY_IX, TEST_CPI_CALLER_ID},
+};
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program_test::{tokio, BanksClientError};
+use solana_sdk::{signature::Keypair, signer::Signer, transaction::Transaction};
+
+/// Have the caller program invoke `ix` with the authority PDA of `owner` signing. `owner` and
+/// `signers` sign the transaction along with the payer.
+async fn invoke_as_authority(
+    test_f: &TestFixture,
+    owner: &Pubkey,
+    ix: Instruction,
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let (authority, _) = find_authority_address(owner);
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(astrolend_cpi::ID, false),
+        AccountMeta::new_readonly(*owner, true),
+    ];
+    accounts.extend(ix.accounts.into_iter().map(|meta| AccountMeta {
+        is_signer: meta.is_signer && meta.pubkey != authority,
+        ..meta
+    }));
+
+    let ix = Instruction {
+        program_id: TEST_CPI_CALLER_ID,
+        accounts,
+        data: [&[INVOKE_AS_AUTHORITY_IX][..], &ix.data].concat(),
+    };
+
+    let mut ctx = test_f.context.borrow_mut();
+    let tx = {
+        let signers = [&[&ctx.payer][..], signers].concat();
+        Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &signers,
+            ctx.last_blockhash,
+        )
+    };
+
+    ctx.banks_client.process_transaction(tx).await
+}
+
+async fn create_token_account(
+    test_f: &TestFixture,
+    mint_f: &MintFixture,
+    owner: &Pubkey,
+) -> TokenAccountFixture {
+    TokenAccountFixture::new(test_f.context.clone(), mint_f, owner).await
+}
+
+async fn observation(bank_f: &BankFixture) -> BankObservation {
+    BankObservation {
+        bank: bank_f.key,
+        oracle: bank_f.load().await.config.get_oracle_account(),
+    }
+}
+
+async fn advance_time(test_f: &TestFixture, seconds: i64) {
+    test_f.advance_time(seconds).await;
+    let now = test_f.get_clock().await.unix_timestamp;
+    for feed in [PYTH_USDC_FEED, PYTH_SOL_FEED] {
+        test_f.set_pyth_oracle_timestamp(feed, now).await;
+    }
+}
+
+/// An account whose authority is a PDA of the caller program, created and operated only
+/// through CPI signed with `invoke_signed`.
+#[tokio::test]
+async fn pda_authority_operates_through_cpi() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let group = test_f.astrolend_group.key;
+    let owner = test_f.payer();
+    let (authority, _) = find_authority_address(&owner);
+
+    // Emissions are ignored for balances last updated before `MIN_EMISSIONS_START_TIME`
+    advance_time(&test_f, 1_700_000_000).await;
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    // SOL deposits earn 1 USDC per SOL per year
+    let funding_account = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    sol_bank
+        .try_setup_emissions(
+            EMISSIONS_FLAG_LENDING_ACTIVE,
+            native!(1, "USDC"),
+            native!(100, "USDC"),
+            test_f.usdc_mint.key,
+            funding_account.key,
+            usdc_bank.get_token_program().await,
+        )
+        .await?;
+
+    let account_keypair = Keypair::new();
+    let account = account_keypair.pubkey();
+    let ix = astrolend_cpi::make_initialize_account_ix(group, account, authority, owner, None);
+    invoke_as_authority(&test_f, &owner, ix, &[&account_keypair]).await?;
+    let astrolend_account: AstrolendAccount = test_f.load_and_deserialize(&account).await?;
+    assert_eq!(astrolend_account.authority, authority);
+
+    let authority_sol = create_token_account(&test_f, &test_f.sol_mint, &authority).await;
+    let authority_usdc = create_token_account(&test_f, &test_f.usdc_mint, &authority).await;
+    test_f.sol_mint.clone().mint_to(&authority_sol.key, 10).await;
+
+    let sol_token_program = sol_bank.get_token_program().await;
+    let usdc_token_program = usdc_bank.get_token_program().await;
+
+    let ix = astrolend_cpi::make_deposit_ix(
+        group,
+        account,
+        authority,
+        sol_bank.key,
+        sol_bank.mint.key,
+        authority_sol.key,
+        sol_token_program,
+        native!(10, "SOL"),
+        None,
+        None,
+    );
+    invoke_as_authority(&test_f, &owner, ix, &[]).await?;
+
+    let observations = [observation(sol_bank).await, observation(usdc_bank).await];
+    let ix = astrolend_cpi::make_borrow_ix(
+        group,
+        account,
+        authority,
+        usdc_bank.key,
+        usdc_bank.mint.key,
+        authority_usdc.key,
+        usdc_token_program,
+        native!(10, "USDC"),
+        None,
+        None,
+        &observations,
+    );
+    invoke_as_authority(&test_f, &owner, ix, &[]).await?;
+    assert_eq!(authority_usdc.balance().await, native!(10, "USDC"));
+
+    let ix = astrolend_cpi::make_withdraw_ix(
+        group,
+        account,
+        authority,
+        sol_bank.key,
+        sol_bank.mint.key,
+        authority_sol.key,
+        sol_token_program,
+        native!(1, "SOL"),
+        None,
+        None,
+        &observations,
+    );
+    invoke_as_authority(&test_f, &owner, ix, &[]).await?;
+    assert_eq!(authority_sol.balance().await, native!(1, "SOL"));
+
+    // 9 SOL deposited for a year
+    advance_time(&test_f, 31_536_000).await;
+    let ix = astrolend_cpi::make_withdraw_emissions_ix(
+        group,
+        account,
+        authority,
+        sol_bank.key,
+        test_f.usdc_mint.key,
+        authority_usdc.key,
+        usdc_token_program,
+        &[],
+    );
+    invoke_as_authority(&test_f, &owner, ix, &[]).await?;
+    let emissions = authority_usdc.balance().await - native!(10, "USDC");
+    assert!(emissions > native!(8.99, "USDC", f64));
+    assert!(emissions <= native!(9, "USDC"));
+
+    let astrolend_account: AstrolendAccount = test_f.load_and_deserialize(&account).await?;
+    let balance = astrolend_account
+        .lending_account
+        .get_balance(&sol_bank.key)
+        .unwrap();
+    assert!(I80F48::from(balance.emissions_outstanding) < I80F48::ONE);
+
+    Ok(())
+}
+
+/// The PDA only signs for its own owner, other wallets going through the caller program get
+/// their own PDA, which isn't the account authority.
+#[tokio::test]
+async fn pda_authority_rejects_other_owners() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let group = test_f.astrolend_group.key;
+    let owner = test_f.payer();
+    let (authority, _) = find_authority_address(&owner);
+
+    let account_keypair = Keypair::new();
+    let account = account_keypair.pubkey();
+    let ix = astrolend_cpi::make_initialize_account_ix(group, account, authority, owner, None);
+    invoke_as_authority(&test_f, &owner, ix, &[&account_keypair]).await?;
+
+    let authority_sol = create_token_account(&test_f, &test_f.sol_mint, &authority).await;
+    test_f.sol_mint.clone().mint_to(&authority_sol.key, 10).await;
+    let sol_token_program = sol_bank.get_token_program().await;
+    let ix = astrolend_cpi::make_deposit_ix(
+        group,
+        account,
+        authority,
+        sol_bank.key,
+        sol_bank.mint.key,
+        authority_sol.key,
+        sol_token_program,
+        native!(10, "SOL"),
+        None,
+        None,
+    );
+    invoke_as_authority(&test_f, &owner, ix, &[]).await?;
+
+    let other_owner = Keypair::new();
+    let (other_authority, _) = find_authority_address(&other_owner.pubkey());
+    let other_sol = create_token_account(&test_f, &test_f.sol_mint, &other_authority).await;
+    let ix = astrolend_cpi::make_withdraw_ix(
+        group,
+        account,
+        other_authority,
+        sol_bank.key,
+        sol_bank.mint.key,
+        other_sol.key,
+        sol_token_program,
+        native!(10, "SOL"),
+        Some(true),
+        None,
+        &[],
+    );
+    let res = invoke_as_authority(&test_f, &other_owner.pubkey(), ix, &[&other_owner]).await;
+    assert!(res.is_err());
+    assert_eq!(other_sol.balance().await, 0);
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{assert_eq_noise, prelude::*};
+use solana_program_test::tokio;
+
+#[tokio::test]
+async fn borrow_caches_bank_price() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 10)
+        .await?;
+
+    // Deposits without a TVL cap don't read the oracle
+    let bank = usdc_bank.load().await;
+    assert_eq!(bank.cached_price_ts, 0);
+    assert_eq!(I80F48::from(bank.cached_price), I80F48::ZERO);
+
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 10)
+        .await?;
+
+    let bank = usdc_bank.load().await;
+    assert_eq!(bank.cached_price_ts, test_f.get_clock().await.unix_timestamp);
+    assert_eq_noise!(I80F48::from(bank.cached_price), I80F48::ONE, I80F48!(0.0001));
+    assert!(I80F48::from(bank.cached_conf) >= I80F48::ZERO);
+
+    // The cache follows the oracle
+    test_f.context.borrow_mut().set_account(
+        &PYTH_USDC_FEED,
+        &create_pyth_legacy_oracle_account(
+            test_f.usdc_mint.key,
+            1.02,
+            USDC_MINT_DECIMALS.into(),
+            None,
+        )
+        .into(),
+    );
+
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 5)
+        .await?;
+
+    let bank = usdc_bank.load().await;
+    assert_eq!(bank.cached_price_ts, test_f.get_clock().await.unix_timestamp);
+    assert_eq_noise!(
+        I80F48::from(bank.cached_price),
+        I80F48!(1.02),
+        I80F48!(0.0001)
+    );
+
+    // SOL was never priced with the bank writable
+    assert_eq!(sol_bank.load().await.cached_price_ts, 0);
+
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use fixtures::{assert_custom_error, prelude::*};
+use astrolend::{errors::AstrolendError, state::astrolend_group::BankConfigOpt};
+use pyth_solana_receiver_sdk::price_update::VerificationLevel;
+use solana_program::{instruction::AccountMeta, pubkey::Pubkey};
+use solana_program_test::{tokio, BanksClientError};
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+const CUSTOM_SHARD_ID: u16 = 7;
+
+/// Mock the full verification SOL push oracle on `shard_id`, returns its address
+fn set_push_oracle(test_f: &TestFixture, shard_id: u16, publish_time: i64) -> Pubkey {
+    let address = get_oracle_id_from_feed_id(PYTH_PUSH_FULLV_FEED_ID.into(), shard_id);
+    test_f.context.borrow_mut().set_account(
+        &address,
+        &create_pyth_push_oracle_account(
+            PYTH_PUSH_FULLV_FEED_ID,
+            10.0,
+            SOL_MINT_DECIMALS.into(),
+            Some(publish_time),
+            VerificationLevel::Full,
+        )
+        .into(),
+    );
+
+    address
+}
+
+#[tokio::test]
+async fn shard_change_requires_fresh_oracle_on_new_shard() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+    let usdc_bank = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await?;
+    let sol_bank = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank(
+            &test_f.sol_mint,
+            *DEFAULT_SOL_TEST_PYTH_PUSH_FULLV_BANK_CONFIG,
+        )
+        .await?;
+    assert_eq!(sol_bank.load().await.config.pyth_shard_id, 0);
+
+    let shard_config = BankConfigOpt {
+        pyth_shard_id: Some(CUSTOM_SHARD_ID),
+        ..Default::default()
+    };
+
+    // Neither a missing account nor the shard 0 account prove the new shard is live
+    let ix = test_f
+        .astrolend_group
+        .make_lending_pool_configure_bank_ix(&sol_bank, shard_config.clone());
+    for oracle in [None, Some(*PYTH_PUSH_SOL_FULLV_FEED)] {
+        let mut ix = ix.clone();
+        ix.accounts
+            .extend(oracle.map(|key| AccountMeta::new_readonly(key, false)));
+
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        let res = ctx.banks_client.process_transaction(tx).await;
+        assert!(res.is_err());
+        assert_custom_error!(res.unwrap_err(), AstrolendError::WrongOracleAccountKeys);
+    }
+
+    // The account exists on the new shard but stopped updating
+    test_f.advance_time(120).await;
+    set_push_oracle(&test_f, CUSTOM_SHARD_ID, 0);
+
+    let res = sol_bank.update_config(shard_config.clone()).await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err().downcast::<BanksClientError>()?,
+        AstrolendError::StaleOracle
+    );
+
+    let now = test_f.get_clock().await.unix_timestamp;
+    set_push_oracle(&test_f, CUSTOM_SHARD_ID, now);
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        ctx.last_blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+    }
+    sol_bank.update_config(shard_config).await?;
+    assert_eq!(sol_bank.load().await.config.pyth_shard_id, CUSTOM_SHARD_ID);
+
+    // The bank is priced from the new shard, the shard 0 account is stale by now
+    test_f.set_pyth_oracle_timestamp(PYTH_USDC_FEED, now).await;
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, &usdc_bank, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, &sol_bank, 10)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, &usdc_bank, 50)
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn add_bank_on_custom_shard() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let mut bank_config = *DEFAULT_SOL_TEST_PYTH_PUSH_FULLV_BANK_CONFIG;
+    bank_config.pyth_shard_id = CUSTOM_SHARD_ID;
+
+    let custom_shard_oracle = set_push_oracle(&test_f, CUSTOM_SHARD_ID, 0);
+    let other_shard_oracle = set_push_oracle(&test_f, 3, 0);
+
+    // Only the configured shard and shard 0 are accepted
+    let res = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_oracle_ai(
+            &test_f.sol_mint,
+            bank_config,
+            other_shard_oracle,
+        )
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::WrongOracleAccountKeys);
+
+    test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_oracle_ai(
+            &test_f.sol_mint,
+            bank_config,
+            custom_shard_oracle,
+        )
+        .await?;
+
+    let bank = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_oracle_ai(
+            &test_f.sol_equivalent_mint,
+            bank_config,
+            *PYTH_PUSH_SOL_FULLV_FEED,
+        )
+        .await?;
+    assert_eq!(bank.load().await.config.pyth_shard_id, CUSTOM_SHARD_ID);
+
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use astrolend::{errors::AstrolendError, state::astrolend_group::GroupConfig};
+use fixed::types::I80F48;
+use fixtures::{astrolend_cpi, native, prelude::*};
+use solana_program::instruction::AccountMeta;
+use solana_program_test::tokio;
+
+/// The test hook deposits the transferred amount again from inside the transfer of a deposit.
+/// The runtime refuses the nested call into astrolend before the bank guard sees it, the guard
+/// is the program side line of defense, see `guard_rejects_bank_instructions_until_cleared`.
+#[tokio::test]
+async fn reentrant_deposit_from_transfer_hook_is_rejected() -> anyhow::Result<()> {
+    let test_f = TestFixture::new_with_t22_extension(
+        Some(TestSettings {
+            banks: vec![TestBankSetting {
+                mint: BankMint::UsdcT22,
+                ..TestBankSetting::default()
+            }],
+            group_config: Some(GroupConfig::default()),
+        }),
+        &[SupportedExtension::TransferHook],
+    )
+    .await;
+    let bank_f = test_f.get_bank(&BankMint::UsdcT22);
+
+    let user_f = test_f.create_astrolend_account().await;
+    let user_token_account = test_f
+        .usdc_t22_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+
+    // Without extra accounts the hook does nothing
+    user_f
+        .try_bank_deposit(user_token_account.key, bank_f, 100)
+        .await?;
+
+    let (whitelist_entry, _) =
+        astrolend_cpi::find_whitelist_entry_address(&test_f.astrolend_group.key, &test_f.payer());
+    test_f
+        .usdc_t22_mint
+        .update_transfer_hook_extra_accounts(&[
+            AccountMeta::new_readonly(astrolend_cpi::ID, false),
+            AccountMeta::new_readonly(test_f.astrolend_group.key, false),
+            AccountMeta::new(user_f.key, false),
+            AccountMeta::new(bank_f.key, false),
+            AccountMeta::new_readonly(whitelist_entry, false),
+            AccountMeta::new_readonly(anchor_spl::token_2022::ID, false),
+        ])
+        .await?;
+
+    let res = user_f
+        .try_bank_deposit(user_token_account.key, bank_f, 200)
+        .await;
+    assert!(res.is_err());
+
+    // Neither the outer nor the reentrant deposit went through
+    assert_eq!(user_token_account.balance().await, native!(900, "USDC"));
+    let bank = bank_f.load().await;
+    assert_eq!(bank.reentrancy_guard, 0);
+    assert_eq!(
+        bank.get_asset_amount(I80F48::from(bank.total_asset_shares))?,
+        I80F48::from_num(native!(100, "USDC"))
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn guard_rejects_bank_instructions_until_cleared() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let user_f = test_f.create_astrolend_account().await;
+    let user_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+
+    // As left by an instruction of the current slot missing its exit
+    let slot = test_f.get_clock().await.slot;
+    usdc_bank.set_reentrancy_guard(slot).await;
+
+    // Simulated, the failed attempts don't use up the transactions sent below
+    let simulation = user_f
+        .simulate_bank_deposit(user_usdc.key, usdc_bank, 100)
+        .await;
+    assert_eq!(
+        simulation.custom_error_code(),
+        Some(AstrolendError::ReentrancyGuardActive.into())
+    );
+
+    let res = test_f.astrolend_group.try_accrue_interest(usdc_bank).await;
+    assert!(res.is_err());
+
+    // The guard may belong to an instruction still running in this slot
+    let simulation = test_f
+        .simulate_tx(&[usdc_bank.make_clear_stuck_guard_ix().await], &[])
+        .await;
+    assert_eq!(
+        simulation.custom_error_code(),
+        Some(AstrolendError::ReentrancyGuardNotStuck.into())
+    );
+
+    test_f.advance_slot().await;
+    usdc_bank.try_clear_stuck_guard().await?;
+
+    let bank = usdc_bank.load().await;
+    assert_eq!(bank.reentrancy_guard, 0);
+    assert_eq!(bank.reentrancy_guard_slot, slot);
+
+    user_f
+        .try_bank_deposit(user_usdc.key, usdc_bank, 100)
+        .await?;
+    assert_eq!(user_usdc.balance().await, native!(900, "USDC"));
+
+    // Instructions exit the guard they enter
+    assert_eq!(usdc_bank.load().await.reentrancy_guard, 0);
+
+    Ok(())
+}
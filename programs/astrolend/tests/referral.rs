@@ -0,0 +1,123 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{assert_custom_error, assert_eq_noise, native, prelude::*};
+use astrolend::{
+    errors::AstrolendError,
+    state::astrolend_group::{BankConfigOpt, InterestRateConfigOpt},
+};
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+#[tokio::test]
+async fn borrow_with_referrer_splits_origination_fee() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    usdc_bank
+        .update_config(BankConfigOpt {
+            interest_rate_config: Some(InterestRateConfigOpt {
+                protocol_origination_fee: Some(I80F48!(0.01).into()),
+                ..Default::default()
+            }),
+            referral_fee_share_pct: Some(50),
+            ..Default::default()
+        })
+        .await?;
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 100)
+        .await?;
+
+    let referrer = Keypair::new();
+    let referrer_usdc =
+        TokenAccountFixture::new(test_f.context.clone(), &test_f.usdc_mint, &referrer.pubkey())
+            .await;
+
+    let group_fees_before = I80F48::from(usdc_bank.load().await.collected_group_fees_outstanding);
+
+    borrower_f
+        .try_bank_borrow_with_referrer(borrower_usdc.key, usdc_bank, 100, referrer_usdc.key)
+        .await?;
+
+    // 1% origination fee on 100 USDC, half of it goes to the referrer
+    assert_eq!(borrower_usdc.balance().await, native!(100, "USDC"));
+    assert_eq!(referrer_usdc.balance().await, native!(0.5, "USDC", f64));
+
+    borrower_f
+        .assert_liability_approx_eq(usdc_bank, I80F48::from(native!(101, "USDC")), I80F48::ONE)
+        .await;
+
+    let group_fees_after = I80F48::from(usdc_bank.load().await.collected_group_fees_outstanding);
+    assert_eq_noise!(
+        group_fees_after - group_fees_before,
+        I80F48::from(native!(0.5, "USDC", f64)),
+        I80F48::ONE
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn borrow_referrer_is_validated() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 100)
+        .await?;
+
+    // Token account owned by the borrower authority
+    let self_referrer_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    let res = borrower_f
+        .try_bank_borrow_with_referrer(borrower_usdc.key, usdc_bank, 10, self_referrer_usdc.key)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::SelfReferral);
+
+    let res = borrower_f
+        .try_bank_borrow_with_referrer(borrower_usdc.key, usdc_bank, 11, borrower_usdc.key)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::SelfReferral);
+
+    // Referrer token account of a different mint
+    let referrer = Keypair::new();
+    let referrer_sol =
+        TokenAccountFixture::new(test_f.context.clone(), &test_f.sol_mint, &referrer.pubkey())
+            .await;
+    let res = borrower_f
+        .try_bank_borrow_with_referrer(borrower_usdc.key, usdc_bank, 12, referrer_sol.key)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidReferrer);
+
+    Ok(())
+}
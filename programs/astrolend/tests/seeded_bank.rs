@@ -0,0 +1,240 @@
+use fixed_macro::types::I80F48;
+use fixtures::{assert_custom_error, prelude::*};
+use astrolend::{
+    constants::{EMISSIONS_FLAG_LENDING_ACTIVE, SEEDED_BANK_FLAG},
+    errors::AstrolendError,
+    state::astrolend_group::{BankConfig, InterestRateConfig, RiskTier},
+    utils::find_bank_pda,
+};
+use solana_program_test::tokio;
+use solana_sdk::account::Account;
+
+#[tokio::test]
+async fn seeded_banks_record_their_seed() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let bank_0 = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_seed(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG, 0)
+        .await?;
+    let bank_1 = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_seed(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG, 1)
+        .await?;
+
+    assert_ne!(bank_0.key, bank_1.key);
+    for (bank_f, seed) in [(&bank_0, 0), (&bank_1, 1)] {
+        let bank = bank_f.load().await;
+        let (address, bump) =
+            find_bank_pda(&test_f.astrolend_group.key, &bank.mint, bank.bank_seed);
+
+        assert_eq!(bank.bank_seed, seed);
+        assert_eq!(address, bank_f.key);
+        assert_eq!(bank.bank_bump, bump);
+    }
+
+    // Keypair banks are told apart by the flag
+    let keypair_bank = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank(&test_f.sol_mint, *DEFAULT_SOL_TEST_BANK_CONFIG)
+        .await?;
+    let bank = keypair_bank.load().await;
+    assert!(!bank.is_seeded());
+    assert_eq!((bank.bank_seed, bank.bank_bump), (0, 0));
+
+    // The same seed can't be used twice for a mint
+    let res = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_seed(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG, 1)
+        .await;
+    assert!(res.is_err());
+
+    Ok(())
+}
+
+/// Flags written by emissions instructions don't clear the seeded flag
+#[tokio::test]
+async fn seeded_flag_survives_emissions_setup() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let bank_f = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_seed(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG, 7)
+        .await?;
+
+    let funding_account = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    bank_f
+        .try_setup_emissions(
+            EMISSIONS_FLAG_LENDING_ACTIVE,
+            1_000_000,
+            1_000_000,
+            test_f.sol_mint.key,
+            funding_account.key,
+            anchor_spl::token::ID,
+        )
+        .await?;
+
+    let bank = bank_f.load().await;
+    assert_eq!(bank.flags, EMISSIONS_FLAG_LENDING_ACTIVE | SEEDED_BANK_FLAG);
+
+    bank_f
+        .try_update_emissions(Some(0), None, None, anchor_spl::token::ID)
+        .await?;
+    assert_eq!(bank_f.load().await.flags, SEEDED_BANK_FLAG);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn second_keypair_bank_for_mint_is_rejected() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    test_f
+        .astrolend_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await?;
+
+    // The first keypair bank holds the seed 0 address of the mint
+    let (seed_zero_bank, _) = find_bank_pda(&test_f.astrolend_group.key, &test_f.usdc_mint.key, 0);
+    let account = test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(seed_zero_bank)
+        .await?
+        .unwrap();
+    assert_eq!(account.owner, astrolend::id());
+    assert!(account.data.is_empty());
+
+    let res = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::DuplicateBankMint);
+
+    // Deliberate duplicates go through the seeded path
+    test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_seed(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG, 1)
+        .await?;
+
+    // Other mints are unaffected
+    test_f
+        .astrolend_group
+        .try_lending_pool_add_bank(&test_f.sol_mint, *DEFAULT_SOL_TEST_BANK_CONFIG)
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn keypair_bank_after_seed_zero_bank_is_rejected() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_seed(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG, 0)
+        .await?;
+
+    let res = test_f
+        .astrolend_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::DuplicateBankMint);
+
+    Ok(())
+}
+
+/// Lamports sent to the seed 0 address ahead of time don't block the keypair bank
+#[tokio::test]
+async fn prefunded_seed_zero_address_is_claimed() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let (seed_zero_bank, _) = find_bank_pda(&test_f.astrolend_group.key, &test_f.usdc_mint.key, 0);
+    test_f.context.borrow_mut().set_account(
+        &seed_zero_bank,
+        &Account {
+            lamports: 1,
+            ..Default::default()
+        }
+        .into(),
+    );
+
+    test_f
+        .astrolend_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await?;
+
+    let account = test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(seed_zero_bank)
+        .await?
+        .unwrap();
+    assert_eq!(account.owner, astrolend::id());
+
+    Ok(())
+}
+
+/// Keypair and seeded banks go through the same config validation
+#[tokio::test]
+async fn add_bank_paths_validate_identically() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let invalid_configs = [
+        BankConfig {
+            risk_tier: RiskTier::Isolated,
+            ..*DEFAULT_USDC_TEST_BANK_CONFIG
+        },
+        BankConfig {
+            min_deposit_amount: DEFAULT_USDC_TEST_BANK_CONFIG.deposit_limit + 1,
+            ..*DEFAULT_USDC_TEST_BANK_CONFIG
+        },
+        BankConfig {
+            interest_rate_config: InterestRateConfig {
+                protocol_ir_fee: I80F48!(0.6).into(),
+                insurance_ir_fee: I80F48!(0.6).into(),
+                ..DEFAULT_USDC_TEST_BANK_CONFIG.interest_rate_config
+            },
+            ..*DEFAULT_USDC_TEST_BANK_CONFIG
+        },
+    ];
+
+    for bank_config in invalid_configs {
+        let res = test_f
+            .astrolend_group
+            .try_lending_pool_add_bank(&test_f.usdc_mint, bank_config)
+            .await;
+        assert!(res.is_err());
+        assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidConfig);
+
+        let res = test_f
+            .astrolend_group
+            .try_lending_pool_add_bank_with_seed(&test_f.usdc_mint, bank_config, 1)
+            .await;
+        assert!(res.is_err());
+        assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidConfig);
+    }
+
+    // Pyth push banks are passed the oracle account of the feed id on both paths
+    test_f
+        .astrolend_group
+        .try_lending_pool_add_bank(
+            &test_f.sol_mint,
+            *DEFAULT_SOL_TEST_PYTH_PUSH_FULLV_BANK_CONFIG,
+        )
+        .await?;
+    test_f
+        .astrolend_group
+        .try_lending_pool_add_bank_with_seed(
+            &test_f.sol_mint,
+            *DEFAULT_SOL_TEST_PYTH_PUSH_FULLV_BANK_CONFIG,
+            1,
+        )
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,243 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{
+    assert_custom_error, assert_eq_noise, astrolend_account::AstrolendAccountFixture, native,
+    prelude::*,
+};
+use astrolend::{
+    errors::AstrolendError,
+    state::astrolend_group::{BankConfigOpt, BankVaultType, GroupConfig},
+};
+use solana_program_test::tokio;
+
+/// 2 SOL of collateral against 10 USDC of debt
+async fn open_position(
+    test_f: &TestFixture,
+) -> anyhow::Result<(AstrolendAccountFixture, TokenAccountFixture, TokenAccountFixture)> {
+    let account_f = test_f.create_astrolend_account().await;
+    let sol_account = test_f.sol_mint.create_token_account_and_mint_to(2).await;
+    let usdc_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    account_f
+        .try_bank_deposit(sol_account.key, test_f.get_bank(&BankMint::Sol), 2)
+        .await?;
+    account_f
+        .try_bank_borrow(usdc_account.key, test_f.get_bank(&BankMint::Usdc), 10)
+        .await?;
+
+    Ok((account_f, sol_account, usdc_account))
+}
+
+async fn create_liquidator(test_f: &TestFixture) -> anyhow::Result<AstrolendAccountFixture> {
+    let liquidator_f = test_f.create_astrolend_account().await;
+    let liquidator_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    liquidator_f
+        .try_bank_deposit(liquidator_usdc.key, test_f.get_bank(&BankMint::Usdc), 1_000)
+        .await?;
+
+    Ok(liquidator_f)
+}
+
+/// $5 of maintenance collateral against $10 of liabilities
+async fn make_unhealthy(test_f: &TestFixture) -> anyhow::Result<()> {
+    test_f
+        .get_bank(&BankMint::Sol)
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.25).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    Ok(())
+}
+
+async fn insurance_vault_balance(test_f: &TestFixture) -> u64 {
+    balance_of(
+        test_f.context.clone(),
+        test_f
+            .get_bank(&BankMint::Usdc)
+            .get_vault(BankVaultType::Insurance)
+            .0,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn self_liquidation_vs_third_party_liquidation() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let liquidator_f = create_liquidator(&test_f).await?;
+    let (liquidatee_f, _, _) = open_position(&test_f).await?;
+    let (self_liquidatee_f, self_sol, self_usdc) = open_position(&test_f).await?;
+    make_unhealthy(&test_f).await?;
+
+    let insurance_before = insurance_vault_balance(&test_f).await;
+    liquidator_f
+        .try_liquidate(&liquidatee_f, sol_bank, 0.1, usdc_bank)
+        .await?;
+    let third_party_insurance_fee = insurance_vault_balance(&test_f).await - insurance_before;
+
+    let insurance_before = insurance_vault_balance(&test_f).await;
+    self_liquidatee_f
+        .try_self_liquidate(sol_bank, 0.1, usdc_bank, self_usdc.key, self_sol.key)
+        .await?;
+    let self_insurance_fee = insurance_vault_balance(&test_f).await - insurance_before;
+
+    // Both give up the same collateral
+    liquidatee_f
+        .assert_deposit_approx_eq(
+            sol_bank,
+            I80F48::from(native!(1.9, "SOL", f64)),
+            I80F48::ONE,
+        )
+        .await;
+    self_liquidatee_f
+        .assert_deposit_approx_eq(
+            sol_bank,
+            I80F48::from(native!(1.9, "SOL", f64)),
+            I80F48::ONE,
+        )
+        .await;
+
+    // $1 of SOL repays $0.95 on a third party liquidation, $0.975 on a self liquidation
+    liquidatee_f
+        .assert_liability_approx_eq(
+            usdc_bank,
+            I80F48::from(native!(9.05, "USDC", f64)),
+            I80F48::ONE,
+        )
+        .await;
+    self_liquidatee_f
+        .assert_liability_approx_eq(
+            usdc_bank,
+            I80F48::from(native!(9.025, "USDC", f64)),
+            I80F48::ONE,
+        )
+        .await;
+
+    // The authority paid the full market value and got the collateral back
+    assert_eq_noise!(
+        self_usdc.balance().await as i64,
+        native!(9, "USDC") as i64,
+        1
+    );
+    assert_eq!(self_sol.balance().await, native!(0.1, "SOL", f64));
+
+    // Insurance gets the same cut, the liquidator bonus stays with the user
+    assert_eq_noise!(
+        self_insurance_fee as i64,
+        native!(0.025, "USDC", f64) as i64,
+        1
+    );
+    assert_eq_noise!(
+        self_insurance_fee as i64,
+        third_party_insurance_fee as i64,
+        1
+    );
+
+    let self_record = self_liquidatee_f.load().await.get_liquidation_history()[0];
+    assert_eq!(self_record.liquidator_account, self_liquidatee_f.key);
+    assert_eq!(self_record.asset_amount, native!(0.1, "SOL", f64));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn self_liquidation_requires_unhealthy_account() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    create_liquidator(&test_f).await?;
+    let (account_f, sol_account, usdc_account) = open_position(&test_f).await?;
+
+    let res = account_f
+        .try_self_liquidate(sol_bank, 0.1, usdc_bank, usdc_account.key, sol_account.key)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::IllegalLiquidation);
+
+    make_unhealthy(&test_f).await?;
+
+    // Can't deleverage past the maintenance requirement, same as a third party liquidation
+    let res = account_f
+        .try_self_liquidate(sol_bank, 0.8, usdc_bank, usdc_account.key, sol_account.key)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::IllegalLiquidation);
+
+    account_f
+        .try_self_liquidate(sol_bank, 0.5, usdc_bank, usdc_account.key, sol_account.key)
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn self_liquidation_frozen_in_bankruptcy_slot() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    create_liquidator(&test_f).await?;
+    let (account_f, sol_account, usdc_account) = open_position(&test_f).await?;
+    make_unhealthy(&test_f).await?;
+
+    // The collateral leaves the vault, same as a withdraw
+    let slot = test_f.get_clock().await.slot;
+    sol_bank.set_last_bankruptcy_slot(slot).await;
+    let res = account_f
+        .try_self_liquidate(sol_bank, 0.1, usdc_bank, usdc_account.key, sol_account.key)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::BankruptcyFreeze);
+
+    test_f.advance_slot().await;
+    account_f
+        .try_self_liquidate(sol_bank, 0.1, usdc_bank, usdc_account.key, sol_account.key)
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn self_liquidation_decreases_group_tvl() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            group_tvl_cap_usd: Some(10_000),
+            ..Default::default()
+        })
+        .await?;
+
+    create_liquidator(&test_f).await?;
+    let (account_f, sol_account, usdc_account) = open_position(&test_f).await?;
+    make_unhealthy(&test_f).await?;
+
+    // $1,000 of USDC and 2 SOL @ $10
+    let tvl_before: I80F48 = test_f.astrolend_group.load().await.group_tvl_usd.into();
+    assert_eq_noise!(tvl_before, I80F48!(1_020), I80F48!(0.001));
+
+    account_f
+        .try_self_liquidate(sol_bank, 0.1, usdc_bank, usdc_account.key, sol_account.key)
+        .await?;
+
+    // 0.1 SOL left the vault, the repaid liability doesn't count towards the TVL
+    let tvl_after: I80F48 = test_f.astrolend_group.load().await.group_tvl_usd.into();
+    assert_eq_noise!(tvl_before - tvl_after, I80F48!(1), I80F48!(0.001));
+
+    Ok(())
+}
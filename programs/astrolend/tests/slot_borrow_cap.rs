@@ -0,0 +1,157 @@
+use fixtures::{assert_custom_error, prelude::*};
+use astrolend::{errors::AstrolendError, state::astrolend_group::GroupConfig};
+use solana_program_test::tokio;
+
+#[tokio::test]
+async fn slot_borrow_cap_limits_borrow_value() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            max_borrow_value_per_slot_usd: Some(1_000),
+            ..Default::default()
+        })
+        .await?;
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_sol = test_f.sol_mint.create_token_account_and_mint_to(1_000).await;
+    lender_f
+        .try_bank_deposit(lender_sol.key, sol_bank, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(100_000)
+        .await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_usdc.key, usdc_bank, 100_000)
+        .await?;
+
+    let res = borrower_f
+        .try_bank_borrow(borrower_sol.key, sol_bank, 101)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::BorrowCapExceeded);
+
+    // 100 SOL @ $10 sits exactly at the cap
+    borrower_f
+        .try_bank_borrow(borrower_sol.key, sol_bank, 100)
+        .await?;
+
+    // Nothing is left for the rest of the slot
+    let res = borrower_f
+        .try_bank_borrow(borrower_sol.key, sol_bank, 1)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::BorrowCapExceeded);
+
+    // A manipulated SOL price binds the cap even though the account stays healthy
+    test_f.advance_slot().await;
+    test_f.context.borrow_mut().set_account(
+        &PYTH_SOL_FEED,
+        &create_pyth_legacy_oracle_account(
+            test_f.sol_mint.key,
+            20.0,
+            SOL_MINT_DECIMALS.into(),
+            None,
+        )
+        .into(),
+    );
+
+    let res = borrower_f
+        .try_bank_borrow(borrower_sol.key, sol_bank, 51)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::BorrowCapExceeded);
+
+    borrower_f
+        .try_bank_borrow(borrower_sol.key, sol_bank, 50)
+        .await?;
+
+    // Lifting the cap restores regular borrowing
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            max_borrow_value_per_slot_usd: Some(0),
+            ..Default::default()
+        })
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_sol.key, sol_bank, 200)
+        .await?;
+
+    Ok(())
+}
+
+/// Borrows split over several instructions of a transaction add up against the cap
+#[tokio::test]
+async fn slot_borrow_cap_accumulates_within_transaction() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            max_borrow_value_per_slot_usd: Some(1_000),
+            ..Default::default()
+        })
+        .await?;
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_sol = test_f.sol_mint.create_token_account_and_mint_to(1_000).await;
+    lender_f
+        .try_bank_deposit(lender_sol.key, sol_bank, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(100_000)
+        .await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_usdc.key, usdc_bank, 100_000)
+        .await?;
+
+    let ix = borrower_f
+        .make_bank_borrow_ix(borrower_sol.key, sol_bank, 60)
+        .await;
+    let simulation = test_f.simulate_tx(&[ix.clone()], &[]).await;
+    assert!(simulation.err.is_none());
+
+    let simulation = test_f.simulate_tx(&[ix.clone(), ix], &[]).await;
+    assert_eq!(
+        simulation.custom_error_code(),
+        Some(AstrolendError::BorrowCapExceeded.into())
+    );
+
+    // Another account borrowing in the same slot shares the bank's budget
+    borrower_f
+        .try_bank_borrow(borrower_sol.key, sol_bank, 60)
+        .await?;
+
+    let other_f = test_f.create_astrolend_account().await;
+    let other_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(100_000)
+        .await;
+    let other_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    other_f
+        .try_bank_deposit(other_usdc.key, usdc_bank, 100_000)
+        .await?;
+    let res = other_f.try_bank_borrow(other_sol.key, sol_bank, 60).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::BorrowCapExceeded);
+
+    other_f.try_bank_borrow(other_sol.key, sol_bank, 40).await?;
+
+    Ok(())
+}
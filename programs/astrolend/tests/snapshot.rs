@@ -0,0 +1,44 @@
+use fixed::types::I80F48;
+use fixtures::prelude::*;
+use solana_program::{pubkey, pubkey::Pubkey};
+use solana_program_test::tokio;
+
+const SNAPSHOT_GROUP: Pubkey = pubkey!("5KNSDaBLzCquK3AMFtkX5MfY6m7BTrCY7xjPMYZScYsB");
+const SNAPSHOT_USDC_BANK: Pubkey = pubkey!("BEgjjojtooZk4g1XfYNbW6Lw6bznnNSt9PB42cxgRPtc");
+const USDC_MINT: Pubkey = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+
+#[tokio::test]
+async fn snapshot_bank_accrues_interest() -> anyhow::Result<()> {
+    let test_f = TestFixture::new_from_snapshot("data/snapshots/group_one_bank").await;
+
+    assert_eq!(test_f.astrolend_group.key, SNAPSHOT_GROUP);
+    assert_eq!(test_f.snapshot_banks.len(), 1);
+
+    let bank_f = test_f.get_snapshot_bank(&SNAPSHOT_USDC_BANK);
+    assert_eq!(bank_f.mint.key, USDC_MINT);
+    assert_eq!(bank_f.mint.mint.decimals, 6);
+
+    let bank_before = bank_f.load().await;
+    assert_eq!(bank_before.group, SNAPSHOT_GROUP);
+
+    test_f.advance_time(86_400).await;
+    test_f.astrolend_group.try_accrue_interest(bank_f).await?;
+
+    let bank_after = bank_f.load().await;
+    assert_eq!(bank_after.last_update, bank_before.last_update + 86_400);
+    assert!(
+        I80F48::from(bank_after.asset_share_value) > I80F48::from(bank_before.asset_share_value)
+    );
+    assert!(
+        I80F48::from(bank_after.liability_share_value)
+            > I80F48::from(bank_before.liability_share_value)
+    );
+    assert_eq!(bank_after.total_asset_shares, bank_before.total_asset_shares);
+    assert_eq!(bank_after.total_liability_shares, bank_before.total_liability_shares);
+    assert!(
+        I80F48::from(bank_after.collected_group_fees_outstanding)
+            > I80F48::from(bank_before.collected_group_fees_outstanding)
+    );
+
+    Ok(())
+}
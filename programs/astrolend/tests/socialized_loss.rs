@@ -0,0 +1,91 @@
+use fixed::types::I80F48;
+use fixtures::{assert_eq_noise, astrolend_account::AstrolendAccountFixture, native, prelude::*};
+use astrolend::state::astrolend_group::BankVaultType;
+use solana_program_test::tokio;
+
+/// Deposits 1 SOL and borrows 8 USDC against it
+async fn create_borrower(test_f: &TestFixture) -> anyhow::Result<AstrolendAccountFixture> {
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(1).await;
+    let borrower_usdc = test_f.usdc_mint.create_empty_token_account().await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, test_f.get_bank(&BankMint::Sol), 1)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, test_f.get_bank(&BankMint::Usdc), 8)
+        .await?;
+
+    Ok(borrower_f)
+}
+
+/// The insurance fund covers 2 of the first 8 USDC of bad debt and nothing of the second, every
+/// uncovered amount adds up on the bank
+#[tokio::test]
+async fn successive_bankruptcies_accumulate_socialized_loss() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let first_borrower_f = create_borrower(&test_f).await?;
+    let second_borrower_f = create_borrower(&test_f).await?;
+
+    test_f
+        .usdc_mint
+        .clone()
+        .mint_to(&usdc_bank.get_vault(BankVaultType::Insurance).0, 2)
+        .await;
+    test_f.context.borrow_mut().set_account(
+        &PYTH_SOL_FEED,
+        &create_pyth_legacy_oracle_account(
+            test_f.sol_mint.key,
+            0.01,
+            SOL_MINT_DECIMALS.into(),
+            None,
+        )
+        .into(),
+    );
+    assert_eq!(usdc_bank.load().await.get_socialized_loss(), I80F48::ZERO);
+
+    test_f
+        .astrolend_group
+        .try_handle_bankruptcy(usdc_bank, &first_borrower_f)
+        .await?;
+
+    let bank = usdc_bank.load().await;
+    assert_eq_noise!(
+        bank.get_socialized_loss(),
+        I80F48::from_num(native!(6, "USDC"))
+    );
+    let asset_share_value = I80F48::from(bank.asset_share_value);
+    assert!(asset_share_value < I80F48::ONE);
+    assert_eq!(
+        usdc_bank
+            .get_vault_token_account(BankVaultType::Insurance)
+            .await
+            .balance()
+            .await,
+        0
+    );
+
+    test_f
+        .astrolend_group
+        .try_handle_bankruptcy(usdc_bank, &second_borrower_f)
+        .await?;
+
+    let bank = usdc_bank.load().await;
+    assert_eq_noise!(
+        bank.get_socialized_loss(),
+        I80F48::from_num(native!(14, "USDC"))
+    );
+    assert!(I80F48::from(bank.asset_share_value) < asset_share_value);
+
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use fixed::types::I80F48;
+use fixtures::{assert_custom_error, native, prelude::*};
+use astrolend::{
+    errors::AstrolendError,
+    state::astrolend_account::{AccountConfigOpt, DISABLED_FLAG},
+};
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+#[tokio::test]
+async fn third_party_repay() -> anyhow::Result<()> {
+    let mut test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, test_f.get_bank(&BankMint::Usdc), 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, test_f.get_bank(&BankMint::Sol), 10)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, test_f.get_bank(&BankMint::Usdc), 50)
+        .await?;
+
+    let third_party = Keypair::new();
+    let third_party_usdc =
+        TokenAccountFixture::new(test_f.context.clone(), &test_f.usdc_mint, &third_party.pubkey())
+            .await;
+    test_f.usdc_mint.mint_to(&third_party_usdc.key, 100).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    borrower_f
+        .try_bank_repay_with_signer(&third_party, third_party_usdc.key, usdc_bank, 20, None)
+        .await?;
+
+    assert_eq!(third_party_usdc.balance().await, native!(80, "USDC"));
+
+    borrower_f
+        .assert_liability_approx_eq(usdc_bank, I80F48::from(native!(30, "USDC")), I80F48::ONE)
+        .await;
+
+    // Disabled accounts still accept repays from third parties
+    let mut borrower = borrower_f.load().await;
+    borrower.account_flags |= DISABLED_FLAG;
+    borrower_f.set_account(&borrower).await?;
+
+    borrower_f
+        .try_bank_repay_with_signer(&third_party, third_party_usdc.key, usdc_bank, 10, None)
+        .await?;
+
+    assert_eq!(third_party_usdc.balance().await, native!(70, "USDC"));
+    borrower_f
+        .assert_liability_approx_eq(usdc_bank, I80F48::from(native!(20, "USDC")), I80F48::ONE)
+        .await;
+
+    // The authority of a disabled account cannot repay itself
+    let res = borrower_f
+        .try_bank_repay(borrower_usdc.key, usdc_bank, 10, None)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::AccountDisabled);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn third_party_deposit_requires_opt_in() -> anyhow::Result<()> {
+    let mut test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let astrolend_account_f = test_f.create_astrolend_account().await;
+
+    let third_party = Keypair::new();
+    let third_party_usdc =
+        TokenAccountFixture::new(test_f.context.clone(), &test_f.usdc_mint, &third_party.pubkey())
+            .await;
+    test_f.usdc_mint.mint_to(&third_party_usdc.key, 100).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let res = astrolend_account_f
+        .try_bank_deposit_on_behalf_of(&third_party, third_party_usdc.key, usdc_bank, 30)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::ThirdPartyDepositNotAllowed);
+
+    astrolend_account_f
+        .try_configure(AccountConfigOpt {
+            third_party_deposits_allowed: Some(true),
+            ..Default::default()
+        })
+        .await?;
+    astrolend_account_f
+        .try_bank_deposit_on_behalf_of(&third_party, third_party_usdc.key, usdc_bank, 40)
+        .await?;
+
+    assert_eq!(third_party_usdc.balance().await, native!(60, "USDC"));
+
+    let positions = astrolend_account_f.load_positions(&[usdc_bank]).await;
+    assert_eq!(positions.len(), 1);
+    assert_eq!(positions[0].mint, usdc_bank.mint.key);
+    assert_eq!(positions[0].liability_amount, I80F48::ZERO);
+    astrolend_account_f
+        .assert_deposit_approx_eq(usdc_bank, I80F48::from(native!(40, "USDC")), I80F48::ONE)
+        .await;
+
+    // Opting out blocks third party deposits again
+    astrolend_account_f
+        .try_configure(AccountConfigOpt {
+            third_party_deposits_allowed: Some(false),
+            ..Default::default()
+        })
+        .await?;
+    let res = astrolend_account_f
+        .try_bank_deposit_on_behalf_of(&third_party, third_party_usdc.key, usdc_bank, 10)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::ThirdPartyDepositNotAllowed);
+
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use anchor_lang::{prelude::Pubkey, InstructionData, ToAccountMetas};
+use fixtures::{assert_custom_error, prelude::*};
+use astrolend::{errors::AstrolendError, state::astrolend_group::BankVaultType};
+use solana_program::instruction::Instruction;
+use solana_program_test::{tokio, BanksClientError};
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+/// Swap the Tokenkeg program for Token-2022 in the instruction accounts
+fn with_wrong_token_program(mut ix: Instruction) -> Instruction {
+    for meta in ix
+        .accounts
+        .iter_mut()
+        .filter(|meta| meta.pubkey == anchor_spl::token::ID)
+    {
+        meta.pubkey = anchor_spl::token_2022::ID;
+    }
+
+    ix
+}
+
+async fn send(test_f: &TestFixture, ix: Instruction) -> Result<(), BanksClientError> {
+    let mut ctx = test_f.context.borrow_mut();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer],
+        ctx.last_blockhash,
+    );
+
+    ctx.banks_client.process_transaction(tx).await
+}
+
+#[tokio::test]
+async fn wrong_token_program_is_rejected() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    assert_eq!(usdc_bank.get_token_program().await, anchor_spl::token::ID);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 10)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 10)
+        .await?;
+
+    // Deposit
+    let ix = lender_f
+        .make_bank_deposit_ix(lender_usdc.key, usdc_bank, 1)
+        .await;
+    let res = send(&test_f, with_wrong_token_program(ix)).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidTokenProgram);
+
+    // Withdraw
+    let ix = lender_f
+        .make_bank_withdraw_ix(lender_usdc.key, usdc_bank, 1, None)
+        .await;
+    let res = send(&test_f, with_wrong_token_program(ix)).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidTokenProgram);
+
+    // Borrow
+    let ix = borrower_f
+        .make_bank_borrow_ix(borrower_usdc.key, usdc_bank, 1)
+        .await;
+    let res = send(&test_f, with_wrong_token_program(ix)).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidTokenProgram);
+
+    // Repay
+    let ix = borrower_f
+        .make_bank_repay_ix(borrower_usdc.key, usdc_bank, 1, None)
+        .await;
+    let res = send(&test_f, with_wrong_token_program(ix)).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidTokenProgram);
+
+    // Collect fees
+    let ix = Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::LendingPoolCollectBankFees {
+            astrolend_group: test_f.astrolend_group.key,
+            bank: usdc_bank.key,
+            liquidity_vault_authority: usdc_bank.get_vault_authority(BankVaultType::Liquidity).0,
+            liquidity_vault: usdc_bank.get_vault(BankVaultType::Liquidity).0,
+            insurance_vault: Some(usdc_bank.get_vault(BankVaultType::Insurance).0),
+            fee_vault: usdc_bank.get_vault(BankVaultType::Fee).0,
+            token_program: anchor_spl::token::ID,
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::LendingPoolCollectBankFees {}.data(),
+    };
+    let res = send(&test_f, with_wrong_token_program(ix)).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidTokenProgram);
+
+    Ok(())
+}
+
+/// Banks created before the token program was recorded are checked against their vaults
+#[tokio::test]
+async fn wrong_token_program_is_rejected_without_recorded_program() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    usdc_bank.clear_token_program().await;
+    assert_eq!(usdc_bank.get_token_program().await, Pubkey::default());
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+
+    let ix = lender_f
+        .make_bank_deposit_ix(lender_usdc.key, usdc_bank, 1)
+        .await;
+    let res = send(&test_f, with_wrong_token_program(ix)).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidTokenProgram);
+
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    Ok(())
+}
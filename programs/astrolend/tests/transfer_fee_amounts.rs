@@ -0,0 +1,119 @@
+use fixed::types::I80F48;
+use fixtures::{native, prelude::*};
+use astrolend::events::{LendingAccountBorrowEvent, LendingAccountWithdrawEvent};
+use solana_program::instruction::Instruction;
+use solana_program_test::tokio;
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+const TRANSFER_FEE_BPS: u64 = 200;
+
+async fn send_for_event<T: anchor_lang::Event>(test_f: &TestFixture, ix: Instruction) -> T {
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await
+            .unwrap()
+    };
+
+    assert!(result.result.is_ok());
+
+    let logs = result.metadata.unwrap().log_messages;
+    find_event::<T>(&logs).unwrap()
+}
+
+/// The test mint charges 5%, lower it to 2% from the next epoch on and move into it
+async fn set_two_pct_transfer_fee(test_f: &TestFixture) {
+    let epoch = test_f.get_clock().await.epoch;
+    test_f
+        .get_bank(&BankMint::T22WithFee)
+        .mint
+        .schedule_transfer_fee(epoch + 1, TRANSFER_FEE_BPS as u16)
+        .await;
+    test_f.advance_epoch().await;
+}
+
+/// Amount to send for `amount` to arrive, the fee cap of the test mint is never reached
+fn pre_fee_amount(amount: u64) -> u64 {
+    (amount * 10_000).div_ceil(10_000 - TRANSFER_FEE_BPS)
+}
+
+#[tokio::test]
+async fn borrow_records_pre_fee_liability() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let bank_f = test_f.get_bank(&BankMint::T22WithFee);
+    set_two_pct_transfer_fee(&test_f).await;
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_token_account = bank_f.mint.create_token_account_and_mint_to(1_100).await;
+    lender_f
+        .try_bank_deposit(lender_token_account.key, bank_f, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    borrower_f
+        .try_bank_deposit(borrower_usdc.key, test_f.get_bank(&BankMint::Usdc), 100)
+        .await?;
+
+    let destination = bank_f.mint.create_empty_token_account().await;
+    let ix = borrower_f
+        .make_bank_borrow_ix(destination.key, bank_f, 10)
+        .await;
+    let event: LendingAccountBorrowEvent = send_for_event(&test_f, ix).await;
+
+    let amount_requested = native!(10, "T22_WITH_FEE");
+    let amount_pre_fee = pre_fee_amount(amount_requested);
+    assert_eq!(event.amount_requested, amount_requested);
+    assert_eq!(event.amount_pre_fee, amount_pre_fee);
+    assert_eq!(event.fee, amount_pre_fee - amount_requested);
+    assert_eq!(event.amount, amount_pre_fee);
+
+    // The destination receives what was asked for, the liability carries the transfer fee
+    assert_eq!(destination.balance().await, amount_requested);
+    let position = borrower_f.load_positions(&[bank_f]).await.pop().unwrap();
+    assert_eq!(position.liability_amount, I80F48::from(amount_pre_fee));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn withdraw_debits_pre_fee_amount() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let bank_f = test_f.get_bank(&BankMint::T22WithFee);
+    set_two_pct_transfer_fee(&test_f).await;
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_token_account = bank_f.mint.create_token_account_and_mint_to(110).await;
+    lender_f
+        .try_bank_deposit(lender_token_account.key, bank_f, 100)
+        .await?;
+
+    let destination = bank_f.mint.create_empty_token_account().await;
+    let ix = lender_f
+        .make_bank_withdraw_ix(destination.key, bank_f, 5, None)
+        .await;
+    let event: LendingAccountWithdrawEvent = send_for_event(&test_f, ix).await;
+
+    let amount_requested = native!(5, "T22_WITH_FEE");
+    let amount_pre_fee = pre_fee_amount(amount_requested);
+    assert_eq!(event.amount_requested, amount_requested);
+    assert_eq!(event.amount_pre_fee, amount_pre_fee);
+    assert_eq!(event.fee, amount_pre_fee - amount_requested);
+    assert_eq!(event.amount, amount_pre_fee);
+
+    assert_eq!(destination.balance().await, amount_requested);
+    let position = lender_f.load_positions(&[bank_f]).await.pop().unwrap();
+    assert_eq!(
+        position.asset_amount,
+        I80F48::from(native!(100, "T22_WITH_FEE") - amount_pre_fee)
+    );
+
+    Ok(())
+}
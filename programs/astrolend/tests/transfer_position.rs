@@ -0,0 +1,163 @@
+use fixed::types::I80F48;
+use fixtures::{
+    assert_custom_error, astrolend_account::AstrolendAccountFixture, bank::BankFixture, native,
+    prelude::*,
+};
+use astrolend::{errors::AstrolendError, state::astrolend_group::BankVaultType};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+
+async fn asset_shares(account_f: &AstrolendAccountFixture, bank_f: &BankFixture) -> I80F48 {
+    account_f
+        .load()
+        .await
+        .lending_account
+        .get_balance(&bank_f.key)
+        .map_or(I80F48::ZERO, |balance| balance.asset_shares.into())
+}
+
+#[tokio::test]
+async fn transfer_position_between_own_accounts() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let source_f = test_f.create_astrolend_account().await;
+    let destination_f = test_f.create_astrolend_account().await;
+    let sol_account = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    source_f
+        .try_bank_deposit(sol_account.key, sol_bank, 10)
+        .await?;
+
+    let shares = asset_shares(&source_f, sol_bank).await;
+    let liquidity_vault = sol_bank
+        .get_vault_token_account(BankVaultType::Liquidity)
+        .await;
+    let vault_balance_before = liquidity_vault.balance().await;
+
+    source_f
+        .try_transfer_position(&destination_f, sol_bank, shares / I80F48::from_num(4))
+        .await?;
+
+    source_f
+        .assert_deposit_approx_eq(
+            sol_bank,
+            I80F48::from(native!(7.5, "SOL", f64)),
+            I80F48::ONE,
+        )
+        .await;
+    destination_f
+        .assert_deposit_approx_eq(
+            sol_bank,
+            I80F48::from(native!(2.5, "SOL", f64)),
+            I80F48::ONE,
+        )
+        .await;
+
+    // Moving the rest closes the source balance
+    let remaining_shares = asset_shares(&source_f, sol_bank).await;
+    source_f
+        .try_transfer_position(&destination_f, sol_bank, remaining_shares)
+        .await?;
+
+    assert!(!source_f.load().await.lending_account.has_balance(&sol_bank.key));
+    assert_eq!(asset_shares(&destination_f, sol_bank).await, shares);
+
+    // No tokens moved, the bank totals are unchanged
+    assert_eq!(liquidity_vault.balance().await, vault_balance_before);
+    assert_eq!(I80F48::from(sol_bank.load().await.total_asset_shares), shares);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn transfer_position_requires_same_authority() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let source_f = test_f.create_astrolend_account().await;
+    let destination_f = test_f.create_astrolend_account().await;
+    let sol_account = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    source_f
+        .try_bank_deposit(sol_account.key, sol_bank, 10)
+        .await?;
+
+    destination_f
+        .try_transfer_account_authority(Pubkey::new_unique(), None)
+        .await?;
+
+    let shares = asset_shares(&source_f, sol_bank).await;
+    let res = source_f
+        .try_transfer_position(&destination_f, sol_bank, shares)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::Unauthorized);
+
+    assert_eq!(asset_shares(&source_f, sol_bank).await, shares);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn transfer_position_keeps_source_healthy() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let source_f = test_f.create_astrolend_account().await;
+    let destination_f = test_f.create_astrolend_account().await;
+    let sol_account = test_f.sol_mint.create_token_account_and_mint_to(2).await;
+    let usdc_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    source_f
+        .try_bank_deposit(sol_account.key, sol_bank, 2)
+        .await?;
+    source_f
+        .try_bank_borrow(usdc_account.key, usdc_bank, 10)
+        .await?;
+
+    // The $20 of SOL collateral backs the $10 borrow, it can't all leave
+    let shares = asset_shares(&source_f, sol_bank).await;
+    let res = source_f
+        .try_transfer_position(&destination_f, sol_bank, shares)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::RiskEngineInitRejected);
+
+    // Liabilities can't be moved
+    let liability_shares: I80F48 = source_f
+        .load()
+        .await
+        .lending_account
+        .get_balance(&usdc_bank.key)
+        .unwrap()
+        .liability_shares
+        .into();
+    let res = source_f
+        .try_transfer_position(&destination_f, usdc_bank, liability_shares)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::IllegalPositionTransfer);
+
+    // Excess collateral can
+    source_f
+        .try_transfer_position(&destination_f, sol_bank, shares / I80F48::from_num(4))
+        .await?;
+
+    source_f
+        .assert_deposit_approx_eq(
+            sol_bank,
+            I80F48::from(native!(1.5, "SOL", f64)),
+            I80F48::ONE,
+        )
+        .await;
+
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use fixtures::{assert_custom_error, native, prelude::*};
+use astrolend::{
+    errors::AstrolendError,
+    state::astrolend_group::{BankConfigOpt, LimitDenomination},
+};
+use solana_program_test::tokio;
+
+#[tokio::test]
+async fn usd_deposit_limit_follows_price() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    // $1_000, or 1 SOL read as native units
+    sol_bank
+        .update_config(BankConfigOpt {
+            deposit_limit: Some(native!(1_000, "USDC")),
+            limit_denomination: Some(LimitDenomination::Usd),
+            ..Default::default()
+        })
+        .await?;
+
+    let depositor_f = test_f.create_astrolend_account().await;
+    let depositor_sol = test_f.sol_mint.create_token_account_and_mint_to(200).await;
+
+    // $900 of deposits at $10
+    depositor_f
+        .try_bank_deposit(depositor_sol.key, sol_bank, 90)
+        .await?;
+
+    let res = depositor_f
+        .try_bank_deposit(depositor_sol.key, sol_bank, 20)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::BankAssetCapacityExceeded);
+
+    // The same 110 SOL are only worth $550 at $5
+    sol_bank.set_pyth_legacy_price(5.).await;
+    depositor_f
+        .try_bank_deposit(depositor_sol.key, sol_bank, 20)
+        .await?;
+
+    // Back in native units the limit is 1 SOL, long exceeded
+    sol_bank
+        .update_config(BankConfigOpt {
+            limit_denomination: Some(LimitDenomination::Native),
+            ..Default::default()
+        })
+        .await?;
+    let res = depositor_f
+        .try_bank_deposit(depositor_sol.key, sol_bank, 1)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::BankAssetCapacityExceeded);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn usd_limits_use_biased_prices() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    sol_bank
+        .update_config(BankConfigOpt {
+            deposit_limit: Some(native!(1_000, "USDC")),
+            borrow_limit: Some(native!(100, "USDC")),
+            limit_denomination: Some(LimitDenomination::Usd),
+            ..Default::default()
+        })
+        .await?;
+
+    // $10 +- $0.212, the confidence interval is 2.12 times the confidence
+    sol_bank.set_pyth_legacy_price_with_conf(10., 0.1).await;
+
+    // $1_000 at $10 would reach the limit, deposits are valued low
+    let depositor_f = test_f.create_astrolend_account().await;
+    let depositor_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    depositor_f
+        .try_bank_deposit(depositor_sol.key, sol_bank, 100)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let borrower_sol = test_f.sol_mint.create_empty_token_account().await;
+    borrower_f
+        .try_bank_deposit(borrower_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    // $99 at $10 would be under the limit, borrows are valued high
+    let res = borrower_f
+        .try_bank_borrow(borrower_sol.key, sol_bank, 9.9)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err(),
+        AstrolendError::BankLiabilityCapacityExceeded
+    );
+
+    borrower_f
+        .try_bank_borrow(borrower_sol.key, sol_bank, 9.5)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use fixtures::prelude::*;
+use astrolend::{constants::UTILIZATION_SNAPSHOT_INTERVAL, state::astrolend_group::BankConfigOpt};
+use solana_program_test::tokio;
+
+#[tokio::test]
+async fn utilization_history_records_hourly_snapshots() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    usdc_bank
+        .update_config(BankConfigOpt {
+            utilization_history: Some(true),
+            ..Default::default()
+        })
+        .await?;
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_010)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, sol_bank, 100)
+        .await?;
+    borrower_f
+        .try_bank_borrow(borrower_usdc.key, usdc_bank, 500)
+        .await?;
+
+    // Every deposit accrues interest on the bank, one snapshot per elapsed hour
+    for amount in 1..=3 {
+        test_f.advance_time(UTILIZATION_SNAPSHOT_INTERVAL).await;
+        lender_f
+            .try_bank_deposit(lender_usdc.key, usdc_bank, amount)
+            .await?;
+    }
+
+    let history = usdc_bank.load().await.get_utilization_history();
+    assert_eq!(history.len(), 3);
+
+    for snapshot in history.iter() {
+        assert!((4_900..=5_000).contains(&snapshot.utilization_bps));
+        assert!(snapshot.borrow_apr_bps > 0);
+    }
+
+    for pair in history.windows(2) {
+        assert_eq!(
+            pair[1].timestamp - pair[0].timestamp,
+            UTILIZATION_SNAPSHOT_INTERVAL
+        );
+    }
+
+    // Accruing less than an hour after the last snapshot does not record a new one
+    test_f.advance_time(UTILIZATION_SNAPSHOT_INTERVAL / 2).await;
+    test_f.astrolend_group.try_accrue_interest(usdc_bank).await?;
+
+    let bank = usdc_bank.load().await;
+    assert_eq!(bank.utilization_history_count, 3);
+    assert_eq!(bank.get_utilization_history().len(), 3);
+
+    Ok(())
+}
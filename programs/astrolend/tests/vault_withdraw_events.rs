@@ -0,0 +1,123 @@
+use anchor_lang::Event;
+use astrolend::{
+    events::{LendingPoolWithdrawFeesEvent, LendingPoolWithdrawInsuranceEvent},
+    state::astrolend_group::BankVaultType,
+};
+use fixtures::{native, prelude::*};
+use solana_program::instruction::Instruction;
+use solana_program_test::tokio;
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+async fn process_and_parse<E: Event>(
+    test_f: &TestFixture,
+    ix: Instruction,
+) -> anyhow::Result<Vec<E>> {
+    let mut ctx = test_f.context.borrow_mut();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer],
+        ctx.last_blockhash,
+    );
+    let result = ctx
+        .banks_client
+        .process_transaction_with_metadata(tx)
+        .await?;
+    assert!(result.result.is_ok());
+
+    Ok(parse_events::<E>(&result))
+}
+
+#[tokio::test]
+async fn withdraw_fees_emits_running_totals() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let fee_vault = usdc_bank.get_vault_token_account(BankVaultType::Fee).await;
+    test_f.usdc_mint.clone().mint_to(&fee_vault.key, 100).await;
+    let receiver = test_f.usdc_mint.create_empty_token_account().await;
+
+    let mut lifetime_withdrawn = 0;
+    for amount in [native!(30, "USDC"), native!(20, "USDC")] {
+        let ix = usdc_bank.make_withdraw_fees_ix(&receiver, amount).await;
+        let events = process_and_parse::<LendingPoolWithdrawFeesEvent>(&test_f, ix).await?;
+        lifetime_withdrawn += amount;
+
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.bank, usdc_bank.key);
+        assert_eq!(event.destination, receiver.key);
+        assert_eq!(event.amount, amount);
+        assert_eq!(event.vault_balance, fee_vault.balance().await);
+        assert_eq!(event.lifetime_withdrawn, lifetime_withdrawn);
+        assert_eq!(receiver.balance().await, lifetime_withdrawn);
+    }
+
+    assert_eq!(fee_vault.balance().await, native!(50, "USDC"));
+    assert_eq!(
+        usdc_bank.load().await.lifetime_fees_withdrawn,
+        native!(50, "USDC")
+    );
+
+    // The plain helper goes through the same instruction
+    usdc_bank
+        .try_withdraw_fees(&receiver, native!(50, "USDC"))
+        .await?;
+    assert_eq!(fee_vault.balance().await, 0);
+    assert_eq!(
+        usdc_bank.load().await.lifetime_fees_withdrawn,
+        native!(100, "USDC")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn withdraw_insurance_emits_running_totals() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let insurance_vault = usdc_bank
+        .get_vault_token_account(BankVaultType::Insurance)
+        .await;
+    test_f
+        .usdc_mint
+        .clone()
+        .mint_to(&insurance_vault.key, 100)
+        .await;
+    let receiver = test_f.usdc_mint.create_empty_token_account().await;
+
+    let mut lifetime_withdrawn = 0;
+    for amount in [native!(30, "USDC"), native!(20, "USDC")] {
+        let ix = usdc_bank
+            .make_withdraw_insurance_ix(&receiver, amount)
+            .await;
+        let events = process_and_parse::<LendingPoolWithdrawInsuranceEvent>(&test_f, ix).await?;
+        lifetime_withdrawn += amount;
+
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.bank, usdc_bank.key);
+        assert_eq!(event.destination, receiver.key);
+        assert_eq!(event.amount, amount);
+        assert_eq!(event.vault_balance, insurance_vault.balance().await);
+        assert_eq!(event.lifetime_withdrawn, lifetime_withdrawn);
+        assert_eq!(receiver.balance().await, lifetime_withdrawn);
+    }
+
+    assert_eq!(insurance_vault.balance().await, native!(50, "USDC"));
+
+    usdc_bank
+        .try_withdraw_insurance(&receiver, native!(50, "USDC"))
+        .await?;
+    assert_eq!(insurance_vault.balance().await, 0);
+    assert_eq!(
+        usdc_bank.load().await.lifetime_insurance_withdrawn,
+        native!(100, "USDC")
+    );
+
+    // The fee vault counter is left alone
+    assert_eq!(usdc_bank.load().await.lifetime_fees_withdrawn, 0);
+
+    Ok(())
+}
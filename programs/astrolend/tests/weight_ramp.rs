@@ -0,0 +1,187 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{
+    assert_custom_error, assert_eq_noise, astrolend_account::AstrolendAccountFixture, prelude::*,
+};
+use astrolend::{
+    errors::AstrolendError,
+    events::AccountHealthSnapshotEvent,
+    state::astrolend_group::{BankConfigOpt, GroupConfig},
+};
+use solana_program::instruction::Instruction;
+use solana_program_test::{tokio, BanksClientError};
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+const RAMP_DURATION: i64 = 7 * 24 * 60 * 60;
+
+async fn health_snapshot(test_f: &TestFixture, ix: Instruction) -> AccountHealthSnapshotEvent {
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await
+            .unwrap()
+    };
+
+    assert!(result.result.is_ok());
+
+    let logs = result.metadata.unwrap().log_messages;
+    find_event::<AccountHealthSnapshotEvent>(&logs).unwrap()
+}
+
+/// Test oracles don't update on their own, keep them fresh across the ramp
+async fn advance_time_with_oracles(test_f: &TestFixture, seconds: i64) {
+    test_f.advance_time(seconds).await;
+    let now = test_f.get_clock().await.unix_timestamp;
+    test_f.set_pyth_oracle_timestamp(PYTH_USDC_FEED, now).await;
+    test_f.set_pyth_oracle_timestamp(PYTH_SOL_FEED, now).await;
+}
+
+/// 10 SOL at $10 of collateral, health snapshots on
+async fn borrower_with_sol_collateral(test_f: &TestFixture) -> AstrolendAccountFixture {
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            health_snapshot_events: Some(true),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let lender_f = test_f.create_astrolend_account().await;
+    let lender_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_f
+        .try_bank_deposit(lender_usdc.key, test_f.get_bank(&BankMint::Usdc), 1_000)
+        .await
+        .unwrap();
+
+    let borrower_f = test_f.create_astrolend_account().await;
+    let borrower_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    borrower_f
+        .try_bank_deposit(borrower_sol.key, test_f.get_bank(&BankMint::Sol), 10)
+        .await
+        .unwrap();
+
+    borrower_f
+}
+
+#[tokio::test]
+async fn weight_ramp_phases_in_linearly() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let borrower_f = borrower_with_sol_collateral(&test_f).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+
+    // SOL weights go from 1/1 down to 0.5/0.6 over a week
+    let start = test_f.get_clock().await.unix_timestamp;
+    sol_bank
+        .try_schedule_weight_change(I80F48!(0.5), I80F48!(0.6), start + RAMP_DURATION)
+        .await?;
+    assert!(sol_bank.load().await.is_weight_ramp_active());
+
+    let res = sol_bank
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.5).into()),
+            ..Default::default()
+        })
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err().downcast::<BanksClientError>()?,
+        AstrolendError::WeightRampActive
+    );
+
+    // Sampled at 0%, 25%, 50% and past the end of the ramp
+    let samples = [
+        (0, 100., 100.),
+        (RAMP_DURATION / 4, 87.5, 90.),
+        (RAMP_DURATION / 4, 75., 80.),
+        (RAMP_DURATION, 50., 60.),
+    ];
+    for (i, (elapsed, init_assets, maint_assets)) in samples.into_iter().enumerate() {
+        advance_time_with_oracles(&test_f, elapsed).await;
+
+        // Amounts differ so no borrow is a duplicate transaction
+        let ix = borrower_f
+            .make_bank_borrow_ix(borrower_usdc.key, usdc_bank, i as f64 + 1.)
+            .await;
+        let event = health_snapshot(&test_f, ix).await;
+        assert_eq_noise!(event.init_assets, init_assets, 0.01);
+        assert_eq_noise!(event.maint_assets, maint_assets, 0.01);
+    }
+
+    // The ended ramp is completed ahead of the next configure, which can change weights again
+    sol_bank
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.4).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    let bank = sol_bank.load().await;
+    assert!(!bank.is_weight_ramp_active());
+    assert_eq!(I80F48::from(bank.config.asset_weight_init), I80F48!(0.4));
+    assert_eq!(I80F48::from(bank.config.asset_weight_maint), I80F48!(0.6));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cancelled_weight_ramp_holds_reached_weights() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let borrower_f = borrower_with_sol_collateral(&test_f).await;
+    let borrower_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+
+    let start = test_f.get_clock().await.unix_timestamp;
+    sol_bank
+        .try_schedule_weight_change(I80F48!(0.5), I80F48!(0.6), start + RAMP_DURATION)
+        .await?;
+
+    advance_time_with_oracles(&test_f, RAMP_DURATION / 2).await;
+    sol_bank.try_cancel_weight_change().await?;
+
+    let bank = sol_bank.load().await;
+    assert!(!bank.is_weight_ramp_active());
+    assert_eq_noise!(
+        I80F48::from(bank.config.asset_weight_init),
+        I80F48!(0.75),
+        I80F48!(0.0001)
+    );
+    assert_eq_noise!(
+        I80F48::from(bank.config.asset_weight_maint),
+        I80F48!(0.8),
+        I80F48!(0.0001)
+    );
+
+    // The weights stay put past the original end of the ramp
+    advance_time_with_oracles(&test_f, RAMP_DURATION).await;
+    let ix = borrower_f
+        .make_bank_borrow_ix(borrower_usdc.key, usdc_bank, 1)
+        .await;
+    let event = health_snapshot(&test_f, ix).await;
+    assert_eq_noise!(event.init_assets, 75., 0.01);
+    assert_eq_noise!(event.maint_assets, 80., 0.01);
+
+    // Same instruction as the successful cancel, refresh the blockhash to resend it
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        ctx.last_blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+    }
+    let res = sol_bank.try_cancel_weight_change().await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidConfig);
+
+    Ok(())
+}
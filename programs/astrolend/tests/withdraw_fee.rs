@@ -0,0 +1,167 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{
+    assert_custom_error, assert_eq_noise, astrolend_account::AstrolendAccountFixture,
+    bank::BankFixture, native, prelude::*,
+};
+use astrolend::{
+    errors::AstrolendError, events::LendingAccountWithdrawEvent,
+    state::astrolend_group::BankConfigOpt,
+};
+use solana_program::instruction::Instruction;
+use solana_program_test::{tokio, BanksClientError};
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+async fn withdraw_event(test_f: &TestFixture, ix: Instruction) -> LendingAccountWithdrawEvent {
+    let result = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction_with_metadata(tx)
+            .await
+            .unwrap()
+    };
+
+    assert!(result.result.is_ok());
+
+    let logs = result.metadata.unwrap().log_messages;
+    find_event::<LendingAccountWithdrawEvent>(&logs).unwrap()
+}
+
+async fn asset_amount(account_f: &AstrolendAccountFixture, bank_f: &BankFixture) -> I80F48 {
+    account_f.load_positions(&[bank_f]).await[0].asset_amount
+}
+
+#[tokio::test]
+async fn withdraw_fee_credited_to_remaining_depositors() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    usdc_bank
+        .update_config(BankConfigOpt {
+            withdraw_fee_rate: Some(I80F48!(0.01).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    let leaving_f = test_f.create_astrolend_account().await;
+    let leaving_usdc = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    leaving_f
+        .try_bank_deposit(leaving_usdc.key, usdc_bank, 100)
+        .await?;
+
+    let staying_f = test_f.create_astrolend_account().await;
+    let staying_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    staying_f
+        .try_bank_deposit(staying_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let staying_before = asset_amount(&staying_f, usdc_bank).await;
+
+    // The balance still closes, 1% of it stays in the vault
+    let ix = leaving_f
+        .make_bank_withdraw_ix(leaving_usdc.key, usdc_bank, 0, Some(true))
+        .await;
+    let event = withdraw_event(&test_f, ix).await;
+
+    assert_eq!(event.amount, native!(100, "USDC"));
+    assert_eq!(event.amount_net, native!(99, "USDC"));
+    assert!(event.close_balance);
+    assert_eq!(leaving_usdc.balance().await, native!(99, "USDC"));
+    assert!(!leaving_f
+        .load()
+        .await
+        .lending_account
+        .has_balance(&usdc_bank.key));
+
+    let fee = I80F48::from(native!(1, "USDC"));
+    assert_eq_noise!(
+        asset_amount(&staying_f, usdc_bank).await - staying_before,
+        fee,
+        I80F48!(0.0001)
+    );
+
+    // The last depositor has no one to pay the fee to
+    staying_f
+        .try_bank_withdraw(staying_usdc.key, usdc_bank, 0, Some(true))
+        .await?;
+    assert_eq_noise!(
+        staying_usdc.balance().await as i64,
+        native!(1_001, "USDC") as i64,
+        1
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn zero_withdraw_fee_is_unchanged() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let account_f = test_f.create_astrolend_account().await;
+    let usdc_account = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    account_f
+        .try_bank_deposit(usdc_account.key, usdc_bank, 100)
+        .await?;
+
+    let other_f = test_f.create_astrolend_account().await;
+    let other_usdc = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    other_f
+        .try_bank_deposit(other_usdc.key, usdc_bank, 100)
+        .await?;
+
+    let bank_before = usdc_bank.load().await;
+    assert_eq!(
+        I80F48::from(bank_before.config.withdraw_fee_rate),
+        I80F48::ZERO
+    );
+
+    let ix = account_f
+        .make_bank_withdraw_ix(usdc_account.key, usdc_bank, 40, None)
+        .await;
+    let event = withdraw_event(&test_f, ix).await;
+
+    assert_eq!(event.amount, native!(40, "USDC"));
+    assert_eq!(event.amount_net, event.amount);
+    assert_eq!(usdc_account.balance().await, native!(40, "USDC"));
+
+    let bank = usdc_bank.load().await;
+    assert_eq!(bank.asset_share_value.value, bank_before.asset_share_value.value);
+    assert_eq!(
+        I80F48::from(bank_before.total_asset_shares) - I80F48::from(bank.total_asset_shares),
+        I80F48::from(native!(40, "USDC"))
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn withdraw_fee_rate_is_capped() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    for rate in [I80F48!(0.0101), I80F48!(-0.001)] {
+        let res = usdc_bank
+            .update_config(BankConfigOpt {
+                withdraw_fee_rate: Some(rate.into()),
+                ..Default::default()
+            })
+            .await;
+        assert!(res.is_err());
+        assert_custom_error!(
+            res.unwrap_err().downcast::<BanksClientError>()?,
+            AstrolendError::InvalidConfig
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,226 @@
+//! Minimal program composing with astrolend through CPI. Deposits use only the `astrolend-cpi`
+//! builders, withdraws enumerate their observation accounts on-chain from the astrolend account.
+//! Accounts can also be owned by a PDA of this program, see [`process_invoke_as_authority`].
+use anchor_lang::prelude::AccountLoader;
+use astrolend::state::astrolend_account::{AstrolendAccount, BankRef};
+use astrolend_cpi::BankObservation;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    pubkey,
+    pubkey::Pubkey,
+};
+
+pub static TEST_CPI_CALLER_ID: Pubkey = pubkey!("CP1CA11ER1111111111111111111111111111111111");
+
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process);
+
+/// Instruction tag of [`process_deposit`], first byte of the instruction data.
+pub const DEPOSIT_IX: u8 = 0;
+/// Instruction tag of [`process_withdraw`], first byte of the instruction data.
+pub const WITHDRAW_IX: u8 = 1;
+/// Instruction tag of [`process_invoke_as_authority`], first byte of the instruction data.
+pub const INVOKE_AS_AUTHORITY_IX: u8 = 2;
+
+/// Seed of the PDA acting as astrolend account authority on behalf of an owner wallet,
+/// `[AUTHORITY_SEED, owner]`.
+pub const AUTHORITY_SEED: &[u8] = b"authority";
+
+pub fn find_authority_address(owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[AUTHORITY_SEED, owner.as_ref()], &TEST_CPI_CALLER_ID)
+}
+
+pub fn process<'info>(
+    program_id: &Pubkey,
+    accounts: &'info [AccountInfo<'info>],
+    data: &[u8],
+) -> ProgramResult {
+    match data.split_first() {
+        Some((&DEPOSIT_IX, data)) => process_deposit(accounts, data),
+        Some((&WITHDRAW_IX, data)) => process_withdraw(accounts, data),
+        Some((&INVOKE_AS_AUTHORITY_IX, data)) => {
+            process_invoke_as_authority(program_id, accounts, data)
+        }
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+fn parse_amount(data: &[u8]) -> Result<u64, ProgramError> {
+    data.get(..8)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u64::from_le_bytes)
+        .ok_or(ProgramError::InvalidInstructionData)
+}
+
+/// Accounts:
+/// 0. `[]` astrolend program
+/// 1. `[]` astrolend group
+/// 2. `[writable]` astrolend account
+/// 3. `[signer]` astrolend account authority
+/// 4. `[writable]` bank
+/// 5. `[]` bank mint
+/// 6. `[writable]` signer token account
+/// 7. `[writable]` bank liquidity vault
+/// 8. `[]` token program
+/// 9. `[]` whitelist entry of the authority, may be uninitialized
+///
+/// Data: deposit amount, u64 little endian.
+fn process_deposit(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let astrolend_program = next_account_info(account_info_iter)?;
+    let astrolend_group = next_account_info(account_info_iter)?;
+    let astrolend_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let bank = next_account_info(account_info_iter)?;
+    let bank_mint = next_account_info(account_info_iter)?;
+    let signer_token_account = next_account_info(account_info_iter)?;
+    let _bank_liquidity_vault = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+    let _whitelist_entry = next_account_info(account_info_iter)?;
+
+    if *astrolend_program.key != astrolend_cpi::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let amount = parse_amount(data)?;
+
+    let ix = astrolend_cpi::make_deposit_ix(
+        *astrolend_group.key,
+        *astrolend_account.key,
+        *authority.key,
+        *bank.key,
+        *bank_mint.key,
+        *signer_token_account.key,
+        *token_program.key,
+        amount,
+        None,
+        None,
+    );
+
+    invoke(&ix, &accounts[1..])
+}
+
+/// Accounts:
+/// 0. `[]` astrolend program
+/// 1. `[]` astrolend group
+/// 2. `[writable]` astrolend account
+/// 3. `[signer]` astrolend account authority
+/// 4. `[writable]` bank
+/// 5. `[]` bank mint
+/// 6. `[writable]` destination token account
+/// 7. `[writable]` bank liquidity vault authority
+/// 8. `[writable]` bank liquidity vault
+/// 9. `[]` token program
+/// 10.. `[]` banks and oracles of the account balances, in any order
+///
+/// The observation accounts are ordered on-chain from the balances of the astrolend account.
+///
+/// Data: withdraw amount, u64 little endian.
+fn process_withdraw<'info>(accounts: &'info [AccountInfo<'info>], data: &[u8]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let astrolend_program = next_account_info(account_info_iter)?;
+    let astrolend_group = next_account_info(account_info_iter)?;
+    let astrolend_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let bank = next_account_info(account_info_iter)?;
+    let bank_mint = next_account_info(account_info_iter)?;
+    let destination_token_account = next_account_info(account_info_iter)?;
+    let _bank_liquidity_vault_authority = next_account_info(account_info_iter)?;
+    let _bank_liquidity_vault = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+    let candidate_ais = account_info_iter.as_slice();
+
+    if *astrolend_program.key != astrolend_cpi::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let amount = parse_amount(data)?;
+
+    let observations = {
+        let astrolend_account_loader =
+            AccountLoader::<AstrolendAccount>::try_from(astrolend_account)?;
+        let astrolend_account = astrolend_account_loader.load()?;
+
+        astrolend_account
+            .get_observation_account_keys(|bank_pk| {
+                let bank_ai = candidate_ais.iter().find(|ai| ai.key == bank_pk)?;
+                BankRef::load(bank_ai).ok()
+            })
+            .into_iter()
+            .map(|(bank, oracle)| BankObservation { bank, oracle })
+            .collect::<Vec<_>>()
+    };
+
+    let ix = astrolend_cpi::make_withdraw_ix(
+        *astrolend_group.key,
+        *astrolend_account.key,
+        *authority.key,
+        *bank.key,
+        *bank_mint.key,
+        *destination_token_account.key,
+        *token_program.key,
+        amount,
+        None,
+        None,
+        &observations,
+    );
+
+    invoke(&ix, &accounts[1..])
+}
+
+/// Accounts:
+/// 0. `[]` astrolend program
+/// 1. `[signer]` owner of the authority PDA
+/// 2.. accounts of the astrolend instruction, in its order
+///
+/// Data: astrolend instruction data.
+///
+/// Invokes the astrolend instruction with the `[AUTHORITY_SEED, owner]` PDA signing, wherever it
+/// appears in the instruction accounts. Any other signer must sign the outer transaction.
+fn process_invoke_as_authority(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let astrolend_program = next_account_info(account_info_iter)?;
+    let owner = next_account_info(account_info_iter)?;
+    let ix_ais = account_info_iter.as_slice();
+
+    if *astrolend_program.key != astrolend_cpi::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    if !owner.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let (authority, bump) =
+        Pubkey::find_program_address(&[AUTHORITY_SEED, owner.key.as_ref()], program_id);
+
+    let ix = Instruction {
+        program_id: astrolend_cpi::ID,
+        accounts: ix_ais
+            .iter()
+            .map(|ai| AccountMeta {
+                pubkey: *ai.key,
+                is_signer: ai.is_signer || *ai.key == authority,
+                is_writable: ai.is_writable,
+            })
+            .collect(),
+        data: data.to_vec(),
+    };
+
+    invoke_signed(
+        &ix,
+        ix_ais,
+        &[&[AUTHORITY_SEED, owner.key.as_ref(), &[bump]]],
+    )
+}
@@ -4,7 +4,7 @@ use {
         account_info::{next_account_info, AccountInfo},
         entrypoint::ProgramResult,
         msg,
-        program::invoke_signed,
+        program::{invoke, invoke_signed},
         program_error::ProgramError,
         pubkey,
         pubkey::Pubkey,
@@ -43,20 +43,95 @@ fn check_token_account_is_transferring(account_info: &AccountInfo) -> Result<(),
 }
 
 /// Processes an [Execute](enum.TransferHookInstruction.html) instruction.
+///
+/// Mints with extra accounts configured make the hook reenter astrolend mid-transfer, see
+/// [`process_reentrant_deposit`].
 pub fn process_execute(
     _program_id: &Pubkey,
     accounts: &[AccountInfo],
-    _amount: u64,
+    amount: u64,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
 
-    let _source_account_info = next_account_info(account_info_iter)?;
-    let _mint_info = next_account_info(account_info_iter)?;
-    let _destination_account_info = next_account_info(account_info_iter)?;
-    let _authority_info = next_account_info(account_info_iter)?;
+    let source_account_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let destination_account_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
     let _extra_account_metas_info = next_account_info(account_info_iter)?;
 
-    Ok(())
+    if account_info_iter.as_slice().is_empty() {
+        return Ok(());
+    }
+
+    process_reentrant_deposit(
+        source_account_info,
+        mint_info,
+        destination_account_info,
+        authority_info,
+        account_info_iter.as_slice(),
+        amount,
+    )
+}
+
+/// Deposits `amount` again from the source of a deposit into an astrolend bank, while the bank
+/// is transferring it, as a malicious hook would.
+///
+/// Extra accounts:
+/// 0. `[]` astrolend program
+/// 1. `[]` astrolend group
+/// 2. `[writable]` astrolend account
+/// 3. `[writable]` bank
+/// 4. `[]` whitelist entry of the transfer authority
+/// 5. `[]` token program
+fn process_reentrant_deposit<'a>(
+    source_account_info: &AccountInfo<'a>,
+    mint_info: &AccountInfo<'a>,
+    destination_account_info: &AccountInfo<'a>,
+    authority_info: &AccountInfo<'a>,
+    extra_account_infos: &[AccountInfo<'a>],
+    amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut extra_account_infos.iter();
+
+    let astrolend_program_info = next_account_info(account_info_iter)?;
+    let astrolend_group_info = next_account_info(account_info_iter)?;
+    let astrolend_account_info = next_account_info(account_info_iter)?;
+    let bank_info = next_account_info(account_info_iter)?;
+    let whitelist_entry_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    if *astrolend_program_info.key != astrolend_cpi::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let ix = astrolend_cpi::make_deposit_ix(
+        *astrolend_group_info.key,
+        *astrolend_account_info.key,
+        *authority_info.key,
+        *bank_info.key,
+        *mint_info.key,
+        *source_account_info.key,
+        *token_program_info.key,
+        amount,
+        None,
+        None,
+    );
+
+    invoke(
+        &ix,
+        &[
+            astrolend_group_info.clone(),
+            astrolend_account_info.clone(),
+            authority_info.clone(),
+            bank_info.clone(),
+            source_account_info.clone(),
+            destination_account_info.clone(),
+            token_program_info.clone(),
+            whitelist_entry_info.clone(),
+            mint_info.clone(),
+            astrolend_program_info.clone(),
+        ],
+    )
 }
 
 /// Processes a
@@ -1,26 +1,46 @@
 use super::{bank::BankFixture, prelude::*};
-use crate::ui_to_native;
+use crate::{assert_eq_noise, ui_to_native};
 use anchor_lang::{prelude::*, system_program, InstructionData, ToAccountMetas};
-
-use astrolend::state::{
-    astrolend_account::AstrolendAccount,
-    astrolend_group::{Bank, BankVaultType},
-    price::OracleSetup,
+use anchor_spl::token::spl_token;
+use fixed::types::I80F48;
+
+use astrolend::{
+    state::{
+        astrolend_account::{
+            calc_value, AccountConfigOpt, AstrolendAccount, BalanceSide, BankRef, CreditHistory,
+            RequirementType, ASTROLEND_ACCOUNT_SPACE,
+        },
+        astrolend_group::{AstrolendGroup, Bank, BankVaultType},
+    },
+    utils::{find_native_token_account_pda, find_whitelist_entry_pda},
 };
+use astrolend_cpi::{add_tvl_observation, observation_account_metas, BankObservation};
 use solana_program::{instruction::Instruction, sysvar};
 use solana_program_test::{BanksClientError, ProgramTestContext};
 use solana_sdk::{
     compute_budget::ComputeBudgetInstruction, signature::Keypair, signer::Signer,
     transaction::Transaction,
 };
-use std::{cell::RefCell, mem, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, mem, rc::Rc};
 
 #[derive(Default, Clone)]
 pub struct AstrolendAccountConfig {}
 
+/// A lending account balance with shares converted to native token amounts
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub asset_shares: I80F48,
+    pub liability_shares: I80F48,
+    pub asset_amount: I80F48,
+    pub liability_amount: I80F48,
+}
+
 pub struct AstrolendAccountFixture {
     ctx: Rc<RefCell<ProgramTestContext>>,
     pub key: Pubkey,
+    emissions_banks: RefCell<Vec<Pubkey>>,
 }
 
 impl AstrolendAccountFixture {
@@ -28,6 +48,21 @@ impl AstrolendAccountFixture {
         ctx: Rc<RefCell<ProgramTestContext>>,
         astrolend_group: &Pubkey,
     ) -> AstrolendAccountFixture {
+        Self::try_new(ctx, astrolend_group).await.unwrap()
+    }
+
+    pub async fn try_new(
+        ctx: Rc<RefCell<ProgramTestContext>>,
+        astrolend_group: &Pubkey,
+    ) -> anyhow::Result<AstrolendAccountFixture, BanksClientError> {
+        Self::try_new_with_tag(ctx, astrolend_group, None).await
+    }
+
+    pub async fn try_new_with_tag(
+        ctx: Rc<RefCell<ProgramTestContext>>,
+        astrolend_group: &Pubkey,
+        account_tag: Option<[u8; 8]>,
+    ) -> anyhow::Result<AstrolendAccountFixture, BanksClientError> {
         let ctx_ref = ctx.clone();
         let account_key = Keypair::new();
 
@@ -40,11 +75,12 @@ impl AstrolendAccountFixture {
                 authority: ctx.payer.pubkey(),
                 fee_payer: ctx.payer.pubkey(),
                 system_program: system_program::ID,
+                whitelist_entry: find_whitelist_entry_pda(astrolend_group, &ctx.payer.pubkey()).0,
             };
             let init_astrolend_account_ix = Instruction {
                 program_id: astrolend::id(),
                 accounts: accounts.to_account_metas(Some(true)),
-                data: astrolend::instruction::AstrolendAccountInitialize {}.data(),
+                data: astrolend::instruction::AstrolendAccountInitialize { account_tag }.data(),
             };
 
             let tx = Transaction::new_signed_with_payer(
@@ -53,13 +89,14 @@ impl AstrolendAccountFixture {
                 &[&ctx.payer, &account_key],
                 ctx.last_blockhash,
             );
-            ctx.banks_client.process_transaction(tx).await.unwrap();
+            ctx.banks_client.process_transaction(tx).await?;
         }
 
-        AstrolendAccountFixture {
+        Ok(AstrolendAccountFixture {
             ctx: ctx_ref,
             key: account_key.pubkey(),
-        }
+            emissions_banks: RefCell::new(vec![]),
+        })
     }
 
     pub async fn make_bank_deposit_ix<T: Into<f64>>(
@@ -69,30 +106,160 @@ impl AstrolendAccountFixture {
         ui_amount: T,
     ) -> Instruction {
         let astrolend_account = self.load().await;
-        let ctx = self.ctx.borrow_mut();
+        let token_program = bank.get_token_program().await;
+
+        let mut ix = astrolend_cpi::make_deposit_ix(
+            astrolend_account.group,
+            self.key,
+            self.ctx.borrow().payer.pubkey(),
+            bank.key,
+            bank.mint.key,
+            funding_account,
+            token_program,
+            ui_to_native!(ui_amount.into(), bank.mint.mint.decimals),
+            None,
+            None,
+        );
+        ix.accounts
+            .extend(self.load_liability_bank_metas(&bank.key).await);
+        self.maybe_add_tvl_observation(&mut ix, astrolend_account.group, bank)
+            .await;
+        self.maybe_add_usd_limit_observation(&mut ix, bank).await;
 
-        let mut accounts = astrolend::accounts::LendingAccountDeposit {
-            astrolend_group: astrolend_account.group,
-            astrolend_account: self.key,
-            signer: ctx.payer.pubkey(),
-            bank: bank.key,
-            signer_token_account: funding_account,
-            bank_liquidity_vault: bank.get_vault(BankVaultType::Liquidity).0,
-            token_program: bank.get_token_program(),
-        }
-        .to_account_metas(Some(true));
-        if bank.mint.token_program == spl_token_2022::ID {
-            accounts.push(AccountMeta::new_readonly(bank.mint.key, false));
-        }
+        ix
+    }
 
-        Instruction {
-            program_id: astrolend::id(),
-            accounts,
-            data: astrolend::instruction::LendingAccountDeposit {
-                amount: ui_to_native!(ui_amount.into(), bank.mint.mint.decimals),
-            }
-            .data(),
-        }
+    /// Deposit into this account with a wallet other than the account authority
+    pub async fn try_bank_deposit_on_behalf_of<T: Into<f64>>(
+        &self,
+        signer: &Keypair,
+        funding_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let astrolend_account = self.load().await;
+        let token_program = bank.get_token_program().await;
+        let mut ix = astrolend_cpi::make_deposit_ix(
+            astrolend_account.group,
+            self.key,
+            signer.pubkey(),
+            bank.key,
+            bank.mint.key,
+            funding_account,
+            token_program,
+            ui_to_native!(ui_amount.into(), bank.mint.mint.decimals),
+            Some(true),
+            None,
+        );
+        ix.accounts
+            .extend(self.load_liability_bank_metas(&bank.key).await);
+        self.maybe_add_tvl_observation(&mut ix, astrolend_account.group, bank)
+            .await;
+        self.maybe_add_usd_limit_observation(&mut ix, bank).await;
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer, signer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    /// Deposit into the bank minting receipts to `receipt_token_account` instead of crediting
+    /// this account
+    pub async fn make_bank_deposit_with_receipts_ix<T: Into<f64>>(
+        &self,
+        funding_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+        receipt_token_account: Pubkey,
+    ) -> Instruction {
+        let astrolend_account = self.load().await;
+        let token_program = bank.get_token_program().await;
+
+        let mut ix = astrolend_cpi::make_deposit_ix(
+            astrolend_account.group,
+            self.key,
+            self.ctx.borrow().payer.pubkey(),
+            bank.key,
+            bank.mint.key,
+            funding_account,
+            token_program,
+            ui_to_native!(ui_amount.into(), bank.mint.mint.decimals),
+            None,
+            Some(receipt_token_account),
+        );
+        ix.accounts
+            .extend(self.load_liability_bank_metas(&bank.key).await);
+        self.maybe_add_tvl_observation(&mut ix, astrolend_account.group, bank)
+            .await;
+        self.maybe_add_usd_limit_observation(&mut ix, bank).await;
+
+        ix
+    }
+
+    pub async fn try_bank_deposit_with_receipts<T: Into<f64>>(
+        &self,
+        funding_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+        receipt_token_account: Pubkey,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let ix = self
+            .make_bank_deposit_with_receipts_ix(
+                funding_account,
+                bank,
+                ui_amount,
+                receipt_token_account,
+            )
+            .await;
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    /// Redeem receipts held by `signer` into this account
+    pub async fn try_redeem_receipts(
+        &self,
+        signer: &Keypair,
+        signer_receipt_account: Pubkey,
+        bank: &BankFixture,
+        receipts: u64,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let astrolend_account = self.load().await;
+        let token_program = bank.get_token_program().await;
+        let mut ix = astrolend_cpi::make_redeem_receipts_ix(
+            astrolend_account.group,
+            self.key,
+            astrolend_account.authority,
+            signer.pubkey(),
+            bank.key,
+            signer_receipt_account,
+            token_program,
+            receipts,
+        );
+        ix.accounts
+            .extend(self.load_liability_bank_metas(&bank.key).await);
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer, signer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
     }
 
     pub async fn try_bank_deposit<T: Into<f64> + Copy>(
@@ -101,6 +268,43 @@ impl AstrolendAccountFixture {
         bank: &BankFixture,
         ui_amount: T,
     ) -> anyhow::Result<(), BanksClientError> {
+        let ix = self
+            .make_bank_deposit_with_hook_ix(funding_account, bank, ui_amount)
+            .await;
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
+    pub async fn simulate_bank_deposit<T: Into<f64> + Copy>(
+        &self,
+        funding_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+    ) -> SimulationResult {
+        let ix = self
+            .make_bank_deposit_with_hook_ix(funding_account, bank, ui_amount)
+            .await;
+
+        simulate_tx(self.ctx.clone(), &[ix], &[]).await
+    }
+
+    /// Deposit instruction with the extra accounts of the test transfer hook on Token-2022 banks
+    async fn make_bank_deposit_with_hook_ix<T: Into<f64> + Copy>(
+        &self,
+        funding_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+    ) -> Instruction {
         let mut ix = self
             .make_bank_deposit_ix(funding_account, bank, ui_amount)
             .await;
@@ -135,17 +339,7 @@ impl AstrolendAccountFixture {
             .await;
         }
 
-        let mut ctx = self.ctx.borrow_mut();
-        let tx = Transaction::new_signed_with_payer(
-            &[ix],
-            Some(&ctx.payer.pubkey().clone()),
-            &[&ctx.payer],
-            ctx.last_blockhash,
-        );
-
-        ctx.banks_client.process_transaction(tx).await?;
-
-        Ok(())
+        ix
     }
 
     pub async fn make_bank_withdraw_ix<T: Into<f64>>(
@@ -155,55 +349,137 @@ impl AstrolendAccountFixture {
         ui_amount: T,
         withdraw_all: Option<bool>,
     ) -> Instruction {
-        let astrolend_account = self.load().await;
+        self.make_bank_withdraw_with_min_out_ix(
+            destination_account,
+            bank,
+            ui_amount,
+            withdraw_all,
+            None,
+        )
+        .await
+    }
 
-        let mut accounts = astrolend::accounts::LendingAccountWithdraw {
-            astrolend_group: astrolend_account.group,
-            astrolend_account: self.key,
-            signer: self.ctx.borrow().payer.pubkey(),
-            bank: bank.key,
-            destination_token_account: destination_account,
-            bank_liquidity_vault: bank.get_vault(BankVaultType::Liquidity).0,
-            bank_liquidity_vault_authority: bank.get_vault_authority(BankVaultType::Liquidity).0,
-            token_program: bank.get_token_program(),
-        }
-        .to_account_metas(Some(true));
-        if bank.mint.token_program == spl_token_2022::ID {
-            accounts.push(AccountMeta::new_readonly(bank.mint.key, false));
-        }
+    pub async fn make_bank_withdraw_with_min_out_ix<T: Into<f64>>(
+        &self,
+        destination_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+        withdraw_all: Option<bool>,
+        min_amount_out: Option<u64>,
+    ) -> Instruction {
+        let astrolend_account = self.load().await;
+        let token_program = bank.get_token_program().await;
 
-        let mut ix = Instruction {
-            program_id: astrolend::id(),
-            accounts,
-            data: astrolend::instruction::LendingAccountWithdraw {
-                amount: ui_to_native!(ui_amount.into(), bank.mint.mint.decimals),
-                withdraw_all,
-            }
-            .data(),
+        // Accounts without liabilities skip the health check and its observations
+        let observations = if astrolend_account.lending_account.has_liabilities() {
+            let exclude_vec = match withdraw_all.unwrap_or(false) {
+                true => vec![bank.key],
+                false => vec![],
+            };
+            self.load_observations(vec![], exclude_vec).await
+        } else {
+            vec![]
         };
 
-        let exclude_vec = match withdraw_all.unwrap_or(false) {
-            true => vec![bank.key],
-            false => vec![],
-        };
-        ix.accounts.extend_from_slice(
-            &self
-                .load_observation_account_metas(vec![], exclude_vec)
-                .await,
+        let mut ix = astrolend_cpi::make_withdraw_ix(
+            astrolend_account.group,
+            self.key,
+            self.ctx.borrow().payer.pubkey(),
+            bank.key,
+            bank.mint.key,
+            destination_account,
+            token_program,
+            ui_to_native!(ui_amount.into(), bank.mint.mint.decimals),
+            withdraw_all,
+            min_amount_out,
+            &observations,
         );
+        self.maybe_add_tvl_observation(&mut ix, astrolend_account.group, bank)
+            .await;
 
         ix
     }
 
+    /// Banks of the liabilities of this account, deposits into a bank it has no balance in yet
+    /// check their risk tier
+    pub async fn load_liability_bank_metas(&self, bank_pk: &Pubkey) -> Vec<AccountMeta> {
+        let lending_account = self.load().await.lending_account;
+        if lending_account.has_balance(bank_pk) {
+            return vec![];
+        }
+
+        lending_account
+            .get_active_balances_iter()
+            .filter(|balance| !balance.is_empty(BalanceSide::Liabilities))
+            .map(|balance| AccountMeta::new_readonly(balance.bank_pk, false))
+            .collect()
+    }
+
+    /// Groups with a TVL cap need the bank oracle to value deposits and withdrawals
+    /// Banks with a USD deposit limit price deposits with their oracle
+    async fn maybe_add_usd_limit_observation(&self, ix: &mut Instruction, bank: &BankFixture) {
+        let config = bank.load().await.config;
+        if config.is_usd_deposit_limit_active() {
+            ix.accounts.extend(observation_account_metas(&[BankObservation {
+                bank: bank.key,
+                oracle: config.get_oracle_account(),
+            }]));
+        }
+    }
+
+    async fn maybe_add_tvl_observation(
+        &self,
+        ix: &mut Instruction,
+        astrolend_group: Pubkey,
+        bank: &BankFixture,
+    ) {
+        let group = load_and_deserialize::<AstrolendGroup>(self.ctx.clone(), &astrolend_group)
+            .await
+            .unwrap();
+        if group.is_tvl_cap_enabled() {
+            add_tvl_observation(
+                ix,
+                BankObservation {
+                    bank: bank.key,
+                    oracle: bank.load().await.config.get_oracle_account(),
+                },
+            );
+        }
+    }
+
     pub async fn try_bank_withdraw<T: Into<f64>>(
         &self,
         destination_account: Pubkey,
         bank: &BankFixture,
         ui_amount: T,
         withdraw_all: Option<bool>,
+    ) -> anyhow::Result<(), BanksClientError> {
+        self.try_bank_withdraw_with_min_out(
+            destination_account,
+            bank,
+            ui_amount,
+            withdraw_all,
+            None,
+        )
+        .await
+    }
+
+    pub async fn try_bank_withdraw_with_min_out<T: Into<f64>>(
+        &self,
+        destination_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+        withdraw_all: Option<bool>,
+        min_amount_out: Option<u64>,
     ) -> anyhow::Result<(), BanksClientError> {
         let ix = self
-            .make_bank_withdraw_ix(destination_account, bank, ui_amount, withdraw_all)
+            .make_bank_withdraw_with_min_out_ix(
+                destination_account,
+                bank,
+                ui_amount,
+                withdraw_all,
+                min_amount_out,
+            )
             .await;
 
         let mut ctx = self.ctx.borrow_mut();
@@ -219,68 +495,252 @@ impl AstrolendAccountFixture {
         Ok(())
     }
 
-    pub async fn make_bank_borrow_ix<T: Into<f64>>(
+    pub async fn simulate_bank_withdraw<T: Into<f64>>(
         &self,
         destination_account: Pubkey,
         bank: &BankFixture,
         ui_amount: T,
+        withdraw_all: Option<bool>,
+    ) -> SimulationResult {
+        let ix = self
+            .make_bank_withdraw_ix(destination_account, bank, ui_amount, withdraw_all)
+            .await;
+
+        simulate_tx(self.ctx.clone(), &[ix], &[]).await
+    }
+
+    /// Deposit lamports of the payer into a bank of the native mint
+    pub async fn make_deposit_native_ix<T: Into<f64>>(
+        &self,
+        bank: &BankFixture,
+        ui_amount: T,
     ) -> Instruction {
         let astrolend_account = self.load().await;
+        let signer = self.ctx.borrow().payer.pubkey();
 
-        let mut accounts = astrolend::accounts::LendingAccountBorrow {
+        let accounts = astrolend::accounts::LendingAccountDepositNative {
             astrolend_group: astrolend_account.group,
             astrolend_account: self.key,
-            signer: self.ctx.borrow().payer.pubkey(),
+            signer,
             bank: bank.key,
-            destination_token_account: destination_account,
+            native_mint: spl_token::native_mint::ID,
+            native_token_account: find_native_token_account_pda(&self.key).0,
             bank_liquidity_vault: bank.get_vault(BankVaultType::Liquidity).0,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+            whitelist_entry: Some(find_whitelist_entry_pda(&astrolend_account.group, &signer).0),
+        }
+        .to_account_metas(Some(true));
+
+        let mut ix = Instruction {
+            program_id: astrolend::id(),
+            accounts,
+            data: astrolend::instruction::LendingAccountDepositNative {
+                amount: ui_to_native!(ui_amount.into(), bank.mint.mint.decimals),
+            }
+            .data(),
+        };
+        ix.accounts
+            .extend(self.load_liability_bank_metas(&bank.key).await);
+        self.maybe_add_tvl_observation(&mut ix, astrolend_account.group, bank)
+            .await;
+        self.maybe_add_usd_limit_observation(&mut ix, bank).await;
+
+        ix
+    }
+
+    /// Withdraw from a bank of the native mint as lamports of the payer
+    pub async fn make_withdraw_native_ix<T: Into<f64>>(
+        &self,
+        bank: &BankFixture,
+        ui_amount: T,
+        withdraw_all: Option<bool>,
+    ) -> Instruction {
+        let astrolend_account = self.load().await;
+
+        let mut accounts = astrolend::accounts::LendingAccountWithdrawNative {
+            astrolend_group: astrolend_account.group,
+            astrolend_account: self.key,
+            signer: self.ctx.borrow().payer.pubkey(),
+            bank: bank.key,
+            native_mint: spl_token::native_mint::ID,
+            native_token_account: find_native_token_account_pda(&self.key).0,
             bank_liquidity_vault_authority: bank.get_vault_authority(BankVaultType::Liquidity).0,
-            token_program: bank.get_token_program(),
+            bank_liquidity_vault: bank.get_vault(BankVaultType::Liquidity).0,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
         }
         .to_account_metas(Some(true));
-        if bank.mint.token_program == spl_token_2022::ID {
-            accounts.push(AccountMeta::new_readonly(bank.mint.key, false));
+
+        // Accounts without liabilities skip the health check and its observations
+        if astrolend_account.lending_account.has_liabilities() {
+            let exclude_vec = match withdraw_all.unwrap_or(false) {
+                true => vec![bank.key],
+                false => vec![],
+            };
+            accounts.extend(self.load_observation_account_metas(vec![], exclude_vec).await);
         }
 
         let mut ix = Instruction {
             program_id: astrolend::id(),
             accounts,
-            data: astrolend::instruction::LendingAccountBorrow {
+            data: astrolend::instruction::LendingAccountWithdrawNative {
                 amount: ui_to_native!(ui_amount.into(), bank.mint.mint.decimals),
+                withdraw_all,
             }
             .data(),
         };
-
-        ix.accounts.extend_from_slice(
-            &self
-                .load_observation_account_metas(vec![bank.key], vec![])
-                .await,
-        );
+        self.maybe_add_tvl_observation(&mut ix, astrolend_account.group, bank)
+            .await;
 
         ix
     }
 
-    pub async fn try_bank_borrow<T: Into<f64> + Copy>(
+    pub async fn make_bank_borrow_ix<T: Into<f64>>(
         &self,
         destination_account: Pubkey,
         bank: &BankFixture,
         ui_amount: T,
-    ) -> anyhow::Result<(), BanksClientError> {
-        self.try_bank_borrow_with_nonce(destination_account, bank, ui_amount, 100)
+    ) -> Instruction {
+        self.make_bank_borrow_with_referrer_ix(destination_account, bank, ui_amount, None)
             .await
     }
 
-    pub async fn try_bank_borrow_with_nonce<T: Into<f64> + Copy>(
+    pub async fn make_bank_borrow_with_referrer_ix<T: Into<f64>>(
         &self,
         destination_account: Pubkey,
         bank: &BankFixture,
         ui_amount: T,
-        nonce: u64,
-    ) -> anyhow::Result<(), BanksClientError> {
-        let mut ix = self
-            .make_bank_borrow_ix(destination_account, bank, ui_amount)
-            .await;
-
+        referrer_token_account: Option<Pubkey>,
+    ) -> Instruction {
+        self.make_bank_borrow_with_min_out_ix(
+            destination_account,
+            bank,
+            ui_amount,
+            referrer_token_account,
+            None,
+        )
+        .await
+    }
+
+    pub async fn make_bank_borrow_with_min_out_ix<T: Into<f64>>(
+        &self,
+        destination_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+        referrer_token_account: Option<Pubkey>,
+        min_amount_out: Option<u64>,
+    ) -> Instruction {
+        let astrolend_account = self.load().await;
+        let token_program = bank.get_token_program().await;
+        let observations = self.load_observations(vec![bank.key], vec![]).await;
+
+        astrolend_cpi::make_borrow_ix(
+            astrolend_account.group,
+            self.key,
+            self.ctx.borrow().payer.pubkey(),
+            bank.key,
+            bank.mint.key,
+            destination_account,
+            token_program,
+            ui_to_native!(ui_amount.into(), bank.mint.mint.decimals),
+            referrer_token_account,
+            min_amount_out,
+            &observations,
+        )
+    }
+
+    pub async fn try_bank_borrow_with_referrer<T: Into<f64>>(
+        &self,
+        destination_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+        referrer_token_account: Pubkey,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let ix = self
+            .make_bank_borrow_with_referrer_ix(
+                destination_account,
+                bank,
+                ui_amount,
+                Some(referrer_token_account),
+            )
+            .await;
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
+    pub async fn try_bank_borrow<T: Into<f64> + Copy>(
+        &self,
+        destination_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+    ) -> anyhow::Result<(), BanksClientError> {
+        self.try_bank_borrow_with_nonce(destination_account, bank, ui_amount, 100)
+            .await
+    }
+
+    pub async fn try_bank_borrow_with_nonce<T: Into<f64> + Copy>(
+        &self,
+        destination_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+        nonce: u64,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let ix = self
+            .make_bank_borrow_with_hook_ix(destination_account, bank, ui_amount)
+            .await;
+
+        let compute_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(1_400_000);
+        let nonce_ix = ComputeBudgetInstruction::set_compute_unit_price(nonce);
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[compute_budget_ix, nonce_ix, ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
+    pub async fn simulate_bank_borrow<T: Into<f64> + Copy>(
+        &self,
+        destination_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+    ) -> SimulationResult {
+        let ix = self
+            .make_bank_borrow_with_hook_ix(destination_account, bank, ui_amount)
+            .await;
+        let compute_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(1_400_000);
+
+        simulate_tx(self.ctx.clone(), &[compute_budget_ix, ix], &[]).await
+    }
+
+    /// Borrow instruction with the extra accounts of the test transfer hook on Token-2022 banks
+    async fn make_bank_borrow_with_hook_ix<T: Into<f64> + Copy>(
+        &self,
+        destination_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+    ) -> Instruction {
+        let mut ix = self
+            .make_bank_borrow_ix(destination_account, bank, ui_amount)
+            .await;
+
         if bank.mint.token_program == spl_token_2022::ID {
             let fetch_account_data_fn = |key| async move {
                 Ok(self
@@ -304,69 +764,388 @@ impl AstrolendAccountFixture {
                 fetch_account_data_fn,
             )
             .await;
-        }
+        }
+
+        ix
+    }
+
+    pub async fn make_bank_repay_ix<T: Into<f64>>(
+        &self,
+        funding_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+        repay_all: Option<bool>,
+    ) -> Instruction {
+        let astrolend_account = self.load().await;
+        let token_program = bank.get_token_program().await;
+
+        astrolend_cpi::make_repay_ix(
+            astrolend_account.group,
+            self.key,
+            self.ctx.borrow().payer.pubkey(),
+            bank.key,
+            bank.mint.key,
+            funding_account,
+            token_program,
+            ui_to_native!(ui_amount.into(), bank.mint.mint.decimals),
+            repay_all,
+        )
+    }
+
+    pub async fn try_bank_repay<T: Into<f64>>(
+        &self,
+        funding_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+        repay_all: Option<bool>,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let ix = self
+            .make_bank_repay_ix(funding_account, bank, ui_amount, repay_all)
+            .await;
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
+    pub async fn simulate_bank_repay<T: Into<f64>>(
+        &self,
+        funding_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+        repay_all: Option<bool>,
+    ) -> SimulationResult {
+        let ix = self
+            .make_bank_repay_ix(funding_account, bank, ui_amount, repay_all)
+            .await;
+
+        simulate_tx(self.ctx.clone(), &[ix], &[]).await
+    }
+
+    /// Repay a liability of this account with a wallet other than the account authority
+    pub async fn try_bank_repay_with_signer<T: Into<f64>>(
+        &self,
+        signer: &Keypair,
+        funding_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+        repay_all: Option<bool>,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let astrolend_account = self.load().await;
+        let token_program = bank.get_token_program().await;
+        let ix = astrolend_cpi::make_repay_ix(
+            astrolend_account.group,
+            self.key,
+            signer.pubkey(),
+            bank.key,
+            bank.mint.key,
+            funding_account,
+            token_program,
+            ui_to_native!(ui_amount.into(), bank.mint.mint.decimals),
+            repay_all,
+        );
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer, signer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub async fn try_balance_close(
+        &self,
+        bank: &BankFixture,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let astrolend_account = self.load().await;
+        let mut ctx = self.ctx.borrow_mut();
+
+        let ix = Instruction {
+            program_id: astrolend::id(),
+            accounts: astrolend::accounts::LendingAccountCloseBalance {
+                astrolend_group: astrolend_account.group,
+                astrolend_account: self.key,
+                signer: ctx.payer.pubkey(),
+                bank: bank.key,
+            }
+            .to_account_metas(Some(true)),
+            data: astrolend::instruction::LendingAccountCloseBalance.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
+    pub async fn make_liquidate_ix<T: Into<f64> + Copy>(
+        &self,
+        liquidatee: &AstrolendAccountFixture,
+        asset_bank_fixture: &BankFixture,
+        asset_ui_amount: T,
+        liab_bank_fixture: &BankFixture,
+    ) -> Instruction {
+        let astrolend_account = self.load().await;
+
+        let asset_bank = asset_bank_fixture.load().await;
+        let liab_bank = liab_bank_fixture.load().await;
+
+        let liquidator_observations = self
+            .load_observations(vec![asset_bank_fixture.key, liab_bank_fixture.key], vec![])
+            .await;
+        let liquidatee_observations = liquidatee.load_observations(vec![], vec![]).await;
+
+        // The insurance fee is transferred out of the liability bank liquidity vault
+        let transfer_hook_accounts = if liab_bank.token_program == spl_token_2022::ID {
+            liab_bank_fixture
+                .mint
+                .load_transfer_hook_account_metas(
+                    &liab_bank_fixture.get_vault(BankVaultType::Liquidity).0,
+                    &liab_bank_fixture.get_vault(BankVaultType::Insurance).0,
+                    &liab_bank_fixture
+                        .get_vault_authority(BankVaultType::Liquidity)
+                        .0,
+                    0,
+                )
+                .await
+        } else {
+            vec![]
+        };
+
+        astrolend_cpi::make_liquidate_ix(
+            astrolend_account.group,
+            self.key,
+            self.ctx.borrow().payer.pubkey(),
+            liquidatee.key,
+            BankObservation {
+                bank: asset_bank_fixture.key,
+                oracle: asset_bank.config.get_oracle_account(),
+            },
+            BankObservation {
+                bank: liab_bank_fixture.key,
+                oracle: liab_bank.config.get_oracle_account(),
+            },
+            liab_bank_fixture.mint.key,
+            liab_bank.token_program,
+            ui_to_native!(
+                asset_ui_amount.into(),
+                asset_bank_fixture.mint.mint.decimals
+            ),
+            &liquidator_observations,
+            &liquidatee_observations,
+            &transfer_hook_accounts,
+        )
+    }
+
+    pub async fn try_liquidate<T: Into<f64> + Copy>(
+        &self,
+        liquidatee: &AstrolendAccountFixture,
+        asset_bank_fixture: &BankFixture,
+        asset_ui_amount: T,
+        liab_bank_fixture: &BankFixture,
+    ) -> std::result::Result<(), BanksClientError> {
+        let ix = self
+            .make_liquidate_ix(
+                liquidatee,
+                asset_bank_fixture,
+                asset_ui_amount,
+                liab_bank_fixture,
+            )
+            .await;
+
+        let compute_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(1_400_000);
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[compute_budget_ix, ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub async fn simulate_liquidate<T: Into<f64> + Copy>(
+        &self,
+        liquidatee: &AstrolendAccountFixture,
+        asset_bank_fixture: &BankFixture,
+        asset_ui_amount: T,
+        liab_bank_fixture: &BankFixture,
+    ) -> SimulationResult {
+        let ix = self
+            .make_liquidate_ix(
+                liquidatee,
+                asset_bank_fixture,
+                asset_ui_amount,
+                liab_bank_fixture,
+            )
+            .await;
+        let compute_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(1_400_000);
+
+        simulate_tx(self.ctx.clone(), &[compute_budget_ix, ix], &[]).await
+    }
+
+    /// Self liquidation by the account authority, paying `liab_bank_fixture` tokens from
+    /// `signer_token_account` and receiving the collateral into `destination_token_account`
+    pub async fn make_self_liquidate_ix<T: Into<f64> + Copy>(
+        &self,
+        asset_bank_fixture: &BankFixture,
+        asset_ui_amount: T,
+        liab_bank_fixture: &BankFixture,
+        signer_token_account: Pubkey,
+        destination_token_account: Pubkey,
+    ) -> Instruction {
+        let astrolend_account = self.load().await;
+
+        let asset_bank = asset_bank_fixture.load().await;
+        let liab_bank = liab_bank_fixture.load().await;
+
+        let mut accounts = astrolend::accounts::LendingAccountSelfLiquidate {
+            astrolend_group: astrolend_account.group,
+            astrolend_account: self.key,
+            signer: self.ctx.borrow().payer.pubkey(),
+            asset_bank: asset_bank_fixture.key,
+            liab_bank: liab_bank_fixture.key,
+            signer_token_account,
+            destination_token_account,
+            asset_liquidity_vault_authority: asset_bank_fixture
+                .get_vault_authority(BankVaultType::Liquidity)
+                .0,
+            asset_liquidity_vault: asset_bank_fixture.get_vault(BankVaultType::Liquidity).0,
+            liab_liquidity_vault_authority: liab_bank_fixture
+                .get_vault_authority(BankVaultType::Liquidity)
+                .0,
+            liab_liquidity_vault: liab_bank_fixture.get_vault(BankVaultType::Liquidity).0,
+            liab_insurance_vault: liab_bank_fixture.get_vault(BankVaultType::Insurance).0,
+            token_program: liab_bank.token_program,
+        }
+        .to_account_metas(Some(true));
+
+        if liab_bank.token_program == spl_token_2022::ID {
+            accounts.push(AccountMeta::new_readonly(asset_bank_fixture.mint.key, false));
+            accounts.push(AccountMeta::new_readonly(liab_bank_fixture.mint.key, false));
+        }
+
+        accounts.push(AccountMeta::new_readonly(asset_bank.config.get_oracle_account(), false));
+        accounts.push(AccountMeta::new_readonly(liab_bank.config.get_oracle_account(), false));
+        accounts.extend_from_slice(&self.load_observation_account_metas(vec![], vec![]).await);
+
+        Instruction {
+            program_id: astrolend::id(),
+            accounts,
+            data: astrolend::instruction::LendingAccountSelfLiquidate {
+                asset_amount: ui_to_native!(
+                    asset_ui_amount.into(),
+                    asset_bank_fixture.mint.mint.decimals
+                ),
+            }
+            .data(),
+        }
+    }
+
+    pub async fn try_self_liquidate<T: Into<f64> + Copy>(
+        &self,
+        asset_bank_fixture: &BankFixture,
+        asset_ui_amount: T,
+        liab_bank_fixture: &BankFixture,
+        signer_token_account: Pubkey,
+        destination_token_account: Pubkey,
+    ) -> std::result::Result<(), BanksClientError> {
+        let ix = self
+            .make_self_liquidate_ix(
+                asset_bank_fixture,
+                asset_ui_amount,
+                liab_bank_fixture,
+                signer_token_account,
+                destination_token_account,
+            )
+            .await;
 
         let compute_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(1_400_000);
-        let nonce_ix = ComputeBudgetInstruction::set_compute_unit_price(nonce);
 
         let mut ctx = self.ctx.borrow_mut();
         let tx = Transaction::new_signed_with_payer(
-            &[compute_budget_ix, nonce_ix, ix],
+            &[compute_budget_ix, ix],
             Some(&ctx.payer.pubkey().clone()),
             &[&ctx.payer],
             ctx.last_blockhash,
         );
 
-        ctx.banks_client.process_transaction(tx).await?;
-
-        Ok(())
+        ctx.banks_client.process_transaction(tx).await
     }
 
-    pub async fn make_bank_repay_ix<T: Into<f64>>(
+    /// Observations cover the balances of this account left after the transfer, the banks of the
+    /// destination liabilities follow
+    pub async fn make_transfer_position_ix(
         &self,
-        funding_account: Pubkey,
+        destination: &AstrolendAccountFixture,
         bank: &BankFixture,
-        ui_amount: T,
-        repay_all: Option<bool>,
+        shares: I80F48,
     ) -> Instruction {
         let astrolend_account = self.load().await;
-        let ctx = self.ctx.borrow_mut();
 
-        let mut accounts = astrolend::accounts::LendingAccountRepay {
+        let remaining_shares = astrolend_account
+            .lending_account
+            .get_balance(&bank.key)
+            .map_or(I80F48::ZERO, |balance| {
+                I80F48::from(balance.asset_shares) - shares
+            });
+        let exclude_vec = match remaining_shares < I80F48::ONE {
+            true => vec![bank.key],
+            false => vec![],
+        };
+
+        let mut accounts = astrolend::accounts::LendingAccountTransferPosition {
             astrolend_group: astrolend_account.group,
-            astrolend_account: self.key,
-            signer: ctx.payer.pubkey(),
-            bank: bank.key,
-            signer_token_account: funding_account,
-            bank_liquidity_vault: bank.get_vault(BankVaultType::Liquidity).0,
-            token_program: bank.get_token_program(),
+            source_astrolend_account: self.key,
+            destination_astrolend_account: destination.key,
+            signer: self.ctx.borrow().payer.pubkey(),
+            asset_bank: bank.key,
         }
         .to_account_metas(Some(true));
-        if bank.mint.token_program == spl_token_2022::ID {
-            accounts.push(AccountMeta::new_readonly(bank.mint.key, false));
-        }
+        accounts.extend(
+            self.load_observation_account_metas(vec![], exclude_vec)
+                .await,
+        );
+        accounts.extend(destination.load_liability_bank_metas(&bank.key).await);
 
         Instruction {
             program_id: astrolend::id(),
             accounts,
-            data: astrolend::instruction::LendingAccountRepay {
-                amount: ui_to_native!(ui_amount.into(), bank.mint.mint.decimals),
-                repay_all,
+            data: astrolend::instruction::LendingAccountTransferPosition {
+                shares: shares.into(),
             }
             .data(),
         }
     }
 
-    pub async fn try_bank_repay<T: Into<f64>>(
+    pub async fn try_transfer_position(
         &self,
-        funding_account: Pubkey,
+        destination: &AstrolendAccountFixture,
         bank: &BankFixture,
-        ui_amount: T,
-        repay_all: Option<bool>,
-    ) -> anyhow::Result<(), BanksClientError> {
+        shares: I80F48,
+    ) -> std::result::Result<(), BanksClientError> {
         let ix = self
-            .make_bank_repay_ix(funding_account, bank, ui_amount, repay_all)
+            .make_transfer_position_ix(destination, bank, shares)
             .await;
+
         let mut ctx = self.ctx.borrow_mut();
         let tx = Transaction::new_signed_with_payer(
             &[ix],
@@ -375,30 +1154,34 @@ impl AstrolendAccountFixture {
             ctx.last_blockhash,
         );
 
-        ctx.banks_client.process_transaction(tx).await?;
-
-        Ok(())
+        ctx.banks_client.process_transaction(tx).await
     }
 
-    pub async fn try_balance_close(
+    pub async fn try_withdraw_emissions(
         &self,
         bank: &BankFixture,
-    ) -> anyhow::Result<(), BanksClientError> {
-        let astrolend_account = self.load().await;
-        let mut ctx = self.ctx.borrow_mut();
-
-        let ix = Instruction {
+        recv_account: &TokenAccountFixture,
+    ) -> std::result::Result<(), BanksClientError> {
+        let emissions_mint = bank.load().await.emissions_mint;
+        let mut ix = Instruction {
             program_id: astrolend::id(),
-            accounts: astrolend::accounts::LendingAccountCloseBalance {
-                astrolend_group: astrolend_account.group,
+            accounts: astrolend::accounts::LendingAccountWithdrawEmissions {
+                astrolend_group: self.load().await.group,
                 astrolend_account: self.key,
-                signer: ctx.payer.pubkey(),
+                signer: self.ctx.borrow().payer.pubkey(),
+                emissions_mint,
+                emissions_auth: get_emissions_authority_address(bank.key, emissions_mint).0,
+                emissions_vault: get_emissions_token_account_address(bank.key, emissions_mint).0,
+                destination_account: recv_account.key,
                 bank: bank.key,
+                token_program: recv_account.token_program,
             }
             .to_account_metas(Some(true)),
-            data: astrolend::instruction::LendingAccountCloseBalance.data(),
+            data: astrolend::instruction::LendingAccountWithdrawEmissions {}.data(),
         };
+        ix.accounts.extend(self.load_observation_account_metas(vec![], vec![]).await);
 
+        let mut ctx = self.ctx.borrow_mut();
         let tx = Transaction::new_signed_with_payer(
             &[ix],
             Some(&ctx.payer.pubkey().clone()),
@@ -406,119 +1189,74 @@ impl AstrolendAccountFixture {
             ctx.last_blockhash,
         );
 
-        ctx.banks_client.process_transaction(tx).await?;
-
-        Ok(())
+        ctx.banks_client.process_transaction(tx).await
     }
 
-    pub async fn try_liquidate<T: Into<f64> + Copy>(
+    /// Withdraw the emissions of each bank to its receiving account in a single instruction
+    pub async fn try_withdraw_emissions_many(
         &self,
-        liquidatee: &AstrolendAccountFixture,
-        asset_bank_fixture: &BankFixture,
-        asset_ui_amount: T,
-        liab_bank_fixture: &BankFixture,
+        claims: &[(&BankFixture, &TokenAccountFixture)],
     ) -> std::result::Result<(), BanksClientError> {
-        let astrolend_account = self.load().await;
-
-        let asset_bank = asset_bank_fixture.load().await;
-        let liab_bank = liab_bank_fixture.load().await;
-
-        let mut accounts = astrolend::accounts::LendingAccountLiquidate {
-            astrolend_group: astrolend_account.group,
-            asset_bank: asset_bank_fixture.key,
-            liab_bank: liab_bank_fixture.key,
-            liquidator_astrolend_account: self.key,
+        let mut accounts = astrolend::accounts::LendingAccountWithdrawEmissionsMany {
+            astrolend_group: self.load().await.group,
+            astrolend_account: self.key,
             signer: self.ctx.borrow().payer.pubkey(),
-            liquidatee_astrolend_account: liquidatee.key,
-            bank_liquidity_vault_authority: liab_bank_fixture
-                .get_vault_authority(BankVaultType::Liquidity)
-                .0,
-            bank_liquidity_vault: liab_bank_fixture.get_vault(BankVaultType::Liquidity).0,
-            bank_insurance_vault: liab_bank_fixture.get_vault(BankVaultType::Insurance).0,
-            token_program: liab_bank_fixture.get_token_program(),
+            token_program: claims[0].1.token_program,
         }
         .to_account_metas(Some(true));
-
-        if liab_bank_fixture.mint.token_program == spl_token_2022::ID {
-            accounts.push(AccountMeta::new_readonly(liab_bank_fixture.mint.key, false));
-        }
-
-        let oracle_accounts = vec![asset_bank.config, liab_bank.config]
-            .iter()
-            .map(|config| {
+        for (bank, recv_account) in claims {
+            let emissions_mint = bank.load().await.emissions_mint;
+            accounts.extend([
+                AccountMeta::new(bank.key, false),
+                AccountMeta::new_readonly(emissions_mint, false),
                 AccountMeta::new_readonly(
-                    {
-                        match config.oracle_setup {
-                            OracleSetup::PythPushOracle => {
-                                get_oracle_id_from_feed_id(config.oracle_keys[0]).unwrap()
-                            }
-                            _ => config.oracle_keys[0],
-                        }
-                    },
+                    get_emissions_authority_address(bank.key, emissions_mint).0,
                     false,
-                )
-            })
-            .collect::<Vec<AccountMeta>>();
-
-        accounts.extend(oracle_accounts);
+                ),
+                AccountMeta::new(
+                    get_emissions_token_account_address(bank.key, emissions_mint).0,
+                    false,
+                ),
+                AccountMeta::new(recv_account.key, false),
+            ]);
+        }
 
-        let mut ix = Instruction {
+        let ix = Instruction {
             program_id: astrolend::id(),
             accounts,
-            data: astrolend::instruction::LendingAccountLiquidate {
-                asset_amount: ui_to_native!(
-                    asset_ui_amount.into(),
-                    asset_bank_fixture.mint.mint.decimals
-                ),
-            }
-            .data(),
+            data: astrolend::instruction::LendingAccountWithdrawEmissionsMany {}.data(),
         };
 
-        if liab_bank_fixture.mint.token_program == spl_token_2022::ID {
-            let payer = self.ctx.borrow().payer.pubkey();
-            let fetch_account_data_fn = |key| async move {
-                Ok(self
-                    .ctx
-                    .borrow_mut()
-                    .banks_client
-                    .get_account(key)
-                    .await
-                    .map(|acc| acc.map(|a| a.data))?)
-            };
-
-            let _ = spl_transfer_hook_interface::offchain::add_extra_account_metas_for_execute(
-                &mut ix,
-                &super::transfer_hook::TEST_HOOK_ID,
-                &liab_bank_fixture.mint.key,
-                &liab_bank_fixture.mint.key,
-                &liab_bank_fixture.mint.key,
-                &payer,
-                0,
-                fetch_account_data_fn,
-            )
-            .await;
-        }
-
-        ix.accounts.extend_from_slice(
-            &self
-                .load_observation_account_metas(
-                    vec![asset_bank_fixture.key, liab_bank_fixture.key],
-                    vec![],
-                )
-                .await,
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
         );
 
-        ix.accounts.extend_from_slice(
-            &liquidatee
-                .load_observation_account_metas(vec![], vec![])
-                .await,
-        );
+        ctx.banks_client.process_transaction(tx).await
+    }
 
-        let compute_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(1_400_000);
+    /// Flag the account as a bad debt candidate, the payer signs
+    pub async fn try_flag_unprofitable(&self) -> std::result::Result<(), BanksClientError> {
+        let mut accounts = astrolend::accounts::LendingAccountFlagUnprofitable {
+            astrolend_group: self.load().await.group,
+            astrolend_account: self.key,
+            signer: self.ctx.borrow().payer.pubkey(),
+        }
+        .to_account_metas(Some(true));
+        accounts.extend(self.load_observation_account_metas(vec![], vec![]).await);
+
+        let ix = Instruction {
+            program_id: astrolend::id(),
+            accounts,
+            data: astrolend::instruction::LendingAccountFlagUnprofitable {}.data(),
+        };
 
         let mut ctx = self.ctx.borrow_mut();
         let tx = Transaction::new_signed_with_payer(
-            &[compute_budget_ix, ix],
+            &[ix],
             Some(&ctx.payer.pubkey().clone()),
             &[&ctx.payer],
             ctx.last_blockhash,
@@ -527,27 +1265,17 @@ impl AstrolendAccountFixture {
         ctx.banks_client.process_transaction(tx).await
     }
 
-    pub async fn try_withdraw_emissions(
-        &self,
-        bank: &BankFixture,
-        recv_account: &TokenAccountFixture,
-    ) -> std::result::Result<(), BanksClientError> {
-        let emissions_mint = bank.load().await.emissions_mint;
+    /// Migrate an account to the current layout version, the payer signs and pays the rent
+    pub async fn try_migrate(&self) -> std::result::Result<(), BanksClientError> {
         let ix = Instruction {
             program_id: astrolend::id(),
-            accounts: astrolend::accounts::LendingAccountWithdrawEmissions {
-                astrolend_group: self.load().await.group,
+            accounts: astrolend::accounts::LendingAccountMigrate {
                 astrolend_account: self.key,
                 signer: self.ctx.borrow().payer.pubkey(),
-                emissions_mint,
-                emissions_auth: get_emissions_authority_address(bank.key, emissions_mint).0,
-                emissions_vault: get_emissions_token_account_address(bank.key, emissions_mint).0,
-                destination_account: recv_account.key,
-                bank: bank.key,
-                token_program: recv_account.token_program,
+                system_program: system_program::ID,
             }
             .to_account_metas(Some(true)),
-            data: astrolend::instruction::LendingAccountWithdrawEmissions {}.data(),
+            data: astrolend::instruction::LendingAccountMigrate {}.data(),
         };
 
         let mut ctx = self.ctx.borrow_mut();
@@ -613,6 +1341,35 @@ impl AstrolendAccountFixture {
         ctx.banks_client.process_transaction(tx).await
     }
 
+    pub fn make_configure_ix(&self, config: AccountConfigOpt) -> Instruction {
+        Instruction {
+            program_id: astrolend::id(),
+            accounts: astrolend::accounts::LendingAccountConfigure {
+                astrolend_account: self.key,
+                signer: self.ctx.borrow().payer.pubkey(),
+            }
+            .to_account_metas(Some(true)),
+            data: astrolend::instruction::LendingAccountConfigure { config }.data(),
+        }
+    }
+
+    pub async fn try_configure(
+        &self,
+        config: AccountConfigOpt,
+    ) -> std::result::Result<(), BanksClientError> {
+        let ix = self.make_configure_ix(config);
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
     pub async fn make_lending_account_start_flashloan_ix(&self, end_index: u64) -> Instruction {
         Instruction {
             program_id: astrolend::id(),
@@ -631,9 +1388,12 @@ impl AstrolendAccountFixture {
         include_banks: Vec<Pubkey>,
         exclude_banks: Vec<Pubkey>,
     ) -> Instruction {
+        let astrolend_group = self.load().await.group;
+
         let mut account_metas = astrolend::accounts::LendingAccountEndFlashloan {
             astrolend_account: self.key,
             signer: self.ctx.borrow().payer.pubkey(),
+            astrolend_group,
         }
         .to_account_metas(Some(true));
 
@@ -685,76 +1445,248 @@ impl AstrolendAccountFixture {
         include_banks: Vec<Pubkey>,
         exclude_banks: Vec<Pubkey>,
     ) -> Vec<AccountMeta> {
+        observation_account_metas(&self.load_observations(include_banks, exclude_banks).await)
+    }
+
+    /// Banks of the emissions mints whose settled emissions count as collateral in groups with
+    /// `EMISSIONS_COLLATERAL_FLAG` set, their observations follow the others in
+    /// `load_observations`. Not supported for the liquidator side of liquidations.
+    pub fn set_emissions_banks(&self, banks: &[&BankFixture]) {
+        *self.emissions_banks.borrow_mut() = banks.iter().map(|bank| bank.key).collect();
+    }
+
+    /// Observations of the active balances, in the order the risk engine expects, followed by
+    /// `include_banks` the account has no balance in yet, and by the `set_emissions_banks` banks
+    /// of the balance emissions mints.
+    pub async fn load_observations(
+        &self,
+        include_banks: Vec<Pubkey>,
+        exclude_banks: Vec<Pubkey>,
+    ) -> Vec<BankObservation> {
         let astrolend_account = self.load().await;
-        // Check all active banks in astrolend account balances
+
         let mut bank_pks = astrolend_account
-            .lending_account
-            .balances
-            .iter()
-            .filter_map(|balance| {
-                if balance.active {
-                    Some(balance.bank_pk)
-                } else {
-                    None
-                }
-            })
+            .get_active_balances_iter()
+            .map(|balance| balance.bank_pk)
             .collect::<Vec<_>>();
-
-        // Add bank pks in include_banks if they are not already in bank_pks
-        // (and exclude the ones contained in exclude_banks)
+        let active_balances = bank_pks.len();
         for bank_pk in include_banks {
             if !bank_pks.contains(&bank_pk) {
                 bank_pks.push(bank_pk);
             }
         }
-        bank_pks.retain(|bank_pk| !exclude_banks.contains(bank_pk));
 
-        // Load all banks
-        let mut banks = vec![];
-        for bank_pk in bank_pks.clone() {
-            let bank = load_and_deserialize::<Bank>(self.ctx.clone(), &bank_pk).await;
-            banks.push(bank);
+        let mut banks = HashMap::new();
+        for bank_pk in bank_pks.iter() {
+            if !exclude_banks.contains(bank_pk) {
+                let bank = load_and_deserialize::<Bank>(self.ctx.clone(), bank_pk)
+                    .await
+                    .unwrap();
+                banks.insert(*bank_pk, BankRef::from(&bank));
+            }
         }
 
-        // Bank -> AccountMetas
-        let account_metas = banks
-            .iter()
-            .zip(bank_pks.iter())
-            .flat_map(|(bank, bank_pk)| {
-                let oracle_key = {
-                    let oracle_key = bank.config.oracle_keys[0];
-                    match bank.config.oracle_setup {
-                        OracleSetup::PythPushOracle => {
-                            get_oracle_id_from_feed_id(oracle_key).unwrap()
-                        }
-                        _ => oracle_key,
-                    }
-                };
-
-                vec![
-                    AccountMeta {
-                        pubkey: *bank_pk,
-                        is_signer: false,
-                        is_writable: false,
-                    },
-                    AccountMeta {
-                        pubkey: oracle_key,
-                        is_signer: false,
-                        is_writable: false,
-                    },
-                ]
-            })
-            .collect::<Vec<_>>();
-        account_metas
+        let mut observation_keys = astrolend_account
+            .get_observation_account_keys(|bank_pk| banks.get(bank_pk).copied());
+        observation_keys.extend(
+            bank_pks[active_balances..]
+                .iter()
+                .filter_map(|bank_pk| Some((*bank_pk, banks.get(bank_pk)?.oracle))),
+        );
+
+        let emissions_bank_pks = self.emissions_banks.borrow().clone();
+        let mut emissions_banks = HashMap::new();
+        for bank_pk in emissions_bank_pks {
+            let bank = load_and_deserialize::<Bank>(self.ctx.clone(), &bank_pk)
+                .await
+                .unwrap();
+            emissions_banks.insert(bank.mint, (bank_pk, BankRef::from(&bank)));
+        }
+        observation_keys.extend(astrolend_account.get_emissions_observation_account_keys(
+            |bank_pk| banks.get(bank_pk).copied(),
+            |mint| emissions_banks.get(mint).copied(),
+        ));
+
+        observation_keys
+            .into_iter()
+            .map(|(bank, oracle)| BankObservation { bank, oracle })
+            .collect()
+    }
+
+    /// Load the account balances in `banks`, converting shares with each bank's current share values
+    pub async fn load_positions(&self, banks: &[&BankFixture]) -> Vec<Position> {
+        let astrolend_account = self.load().await;
+
+        let mut positions = vec![];
+        for bank_f in banks {
+            let Some(balance) = astrolend_account.lending_account.get_balance(&bank_f.key) else {
+                continue;
+            };
+            let bank = bank_f.load().await;
+
+            let asset_shares = I80F48::from(balance.asset_shares);
+            let liability_shares = I80F48::from(balance.liability_shares);
+
+            positions.push(Position {
+                bank: bank_f.key,
+                mint: bank.mint,
+                asset_shares,
+                liability_shares,
+                asset_amount: bank.get_asset_amount(asset_shares).unwrap(),
+                liability_amount: bank.get_liability_amount(liability_shares).unwrap(),
+            });
+        }
+
+        positions
+    }
+
+    async fn load_position(&self, bank: &BankFixture) -> Position {
+        self.load_positions(&[bank])
+            .await
+            .pop()
+            .unwrap_or_else(|| panic!("no balance for bank {}", bank.key))
+    }
+
+    /// Maintenance health of the account in USD, with its balances in `banks` valued at their
+    /// oracle price. Ignores emode and price confidence, test oracles carry none.
+    pub async fn load_maint_health(&self, banks: &[&BankFixture]) -> I80F48 {
+        let mut health = I80F48::ZERO;
+        for position in self.load_positions(banks).await {
+            let bank_f = banks.iter().find(|bank_f| bank_f.key == position.bank).unwrap();
+            let bank = bank_f.load().await;
+            let price = I80F48::from_num(bank_f.get_price().await);
+            let asset_weight = bank_f.get_asset_weight(RequirementType::Maintenance).await;
+            let (_, liability_weight) = bank.config.get_weights(RequirementType::Maintenance);
+
+            health += calc_value(
+                position.asset_amount,
+                price,
+                bank.mint_decimals,
+                Some(asset_weight),
+            )
+            .unwrap();
+            health -= calc_value(
+                position.liability_amount,
+                price,
+                bank.mint_decimals,
+                Some(liability_weight),
+            )
+            .unwrap();
+        }
+
+        health
+    }
+
+    /// Reprice the oracle of `collateral_bank` so the maintenance health of an account holding
+    /// `collateral_bank` deposits against `liability_bank` borrows lands on `target_health` USD.
+    /// Returns the applied UI price.
+    pub async fn make_unhealthy(
+        &self,
+        target_health: f64,
+        collateral_bank: &BankFixture,
+        liability_bank: &BankFixture,
+    ) -> f64 {
+        let collateral = self.load_position(collateral_bank).await;
+        let bank = collateral_bank.load().await;
+        let asset_weight = collateral_bank
+            .get_asset_weight(RequirementType::Maintenance)
+            .await;
+        let weighted_collateral = calc_value(
+            collateral.asset_amount,
+            I80F48::ONE,
+            bank.mint_decimals,
+            Some(asset_weight),
+        )
+        .unwrap();
+
+        let liability_health = self.load_maint_health(&[liability_bank]).await;
+        let price = (I80F48::from_num(target_health) - liability_health) / weighted_collateral;
+        assert!(price.is_positive(), "health {} is out of reach", target_health);
+
+        let price = price.to_num::<f64>();
+        collateral_bank.set_pyth_legacy_price(price).await;
+
+        let health = self
+            .load_maint_health(&[collateral_bank, liability_bank])
+            .await;
+        assert_eq_noise!(health.to_num::<f64>(), target_health, 0.01);
+
+        price
+    }
+
+    /// Wipe the `collateral_bank` deposits of the account, leaving its `liability_bank` borrows
+    /// without collateral. Writes the account and bank directly, no tokens move.
+    pub async fn make_bankrupt(&self, collateral_bank: &BankFixture, liability_bank: &BankFixture) {
+        let collateral = self.load_position(collateral_bank).await;
+
+        let mut astrolend_account = self.load().await;
+        astrolend_account
+            .lending_account
+            .balances
+            .iter_mut()
+            .find(|balance| balance.active && balance.bank_pk == collateral_bank.key)
+            .unwrap()
+            .close()
+            .unwrap();
+        self.set_account(&astrolend_account).await.unwrap();
+
+        let total_asset_shares = I80F48::from(collateral_bank.load().await.total_asset_shares);
+        collateral_bank
+            .set_total_asset_shares(total_asset_shares - collateral.asset_shares)
+            .await;
+
+        assert!(self.load_positions(&[collateral_bank]).await.is_empty());
+        assert!(self
+            .load_maint_health(&[collateral_bank, liability_bank])
+            .await
+            .is_negative());
+    }
+
+    /// Assert the deposited native amount in `bank` is within `tolerance` of `amount`
+    pub async fn assert_deposit_approx_eq(
+        &self,
+        bank: &BankFixture,
+        amount: I80F48,
+        tolerance: I80F48,
+    ) {
+        let position = self.load_position(bank).await;
+        assert!(
+            (position.asset_amount - amount).abs() <= tolerance,
+            "deposit in bank {}: expected {} +/- {}, got {}",
+            bank.key,
+            amount,
+            tolerance,
+            position.asset_amount
+        );
+    }
+
+    /// Assert the borrowed native amount in `bank` is within `tolerance` of `amount`
+    pub async fn assert_liability_approx_eq(
+        &self,
+        bank: &BankFixture,
+        amount: I80F48,
+        tolerance: I80F48,
+    ) {
+        let position = self.load_position(bank).await;
+        assert!(
+            (position.liability_amount - amount).abs() <= tolerance,
+            "liability in bank {}: expected {} +/- {}, got {}",
+            bank.key,
+            amount,
+            tolerance,
+            position.liability_amount
+        );
     }
 
     pub async fn set_account(&self, astl_account: &AstrolendAccount) -> anyhow::Result<()> {
         let mut ctx = self.ctx.borrow_mut();
         let mut account = ctx.banks_client.get_account(self.key).await?.unwrap();
         let mut discriminator = account.data[..8].to_vec();
+        let mut credit_history = account.data[Self::get_size()..].to_vec();
         let mut new_data = vec![];
         new_data.append(&mut discriminator);
         new_data.append(&mut bytemuck::bytes_of(astl_account).to_vec());
+        new_data.append(&mut credit_history);
         account.data = new_data;
         ctx.set_account(&self.key, &account.into());
 
@@ -762,13 +1694,31 @@ impl AstrolendAccountFixture {
     }
 
     pub async fn load(&self) -> AstrolendAccount {
+        self.try_load().await.unwrap()
+    }
+
+    pub async fn try_load(&self) -> anyhow::Result<AstrolendAccount> {
         load_and_deserialize::<AstrolendAccount>(self.ctx.clone(), &self.key).await
     }
 
+    /// Size of the account alone, as legacy accounts are created
     pub fn get_size() -> usize {
         mem::size_of::<AstrolendAccount>() + 8
     }
 
+    /// The credit history after the account, `None` until legacy accounts are migrated
+    pub async fn load_credit_history(&self) -> Option<CreditHistory> {
+        let data = {
+            let mut ctx = self.ctx.borrow_mut();
+            ctx.banks_client.get_account(self.key).await.unwrap()?.data
+        };
+        let astrolend_account: AstrolendAccount =
+            bytemuck::pod_read_unaligned(&data[8..Self::get_size()]);
+        let credit_history = data.get(Self::get_size()..ASTROLEND_ACCOUNT_SPACE)?;
+
+        (!astrolend_account.is_legacy()).then(|| bytemuck::pod_read_unaligned(credit_history))
+    }
+
     /// Use the client to send the transfer ix authority transaction
     /// Pass the new authority as an argument
     /// Optional: use a different signer (for negative test case)
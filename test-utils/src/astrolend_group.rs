@@ -6,7 +6,11 @@ use anchor_lang::{prelude::*, solana_program::system_program, InstructionData};
 use anyhow::Result;
 use astrolend::{
     prelude::AstrolendGroup,
-    state::astrolend_group::{BankConfig, BankConfigOpt, BankVaultType, GroupConfig},
+    state::astrolend_group::{
+        BankConfig, BankConfigOpt, BankVaultType, EmodeEntryConfig, FeeSplitEntryConfig,
+        GroupConfig, OracleParamsOpt,
+    },
+    utils::{find_bank_pda, find_receipt_mint_pda, find_whitelist_entry_pda},
 };
 use solana_program::sysvar;
 use solana_program_test::*;
@@ -22,6 +26,11 @@ pub struct AstrolendGroupFixture {
 }
 
 impl AstrolendGroupFixture {
+    /// Wrap a group that already exists on chain, e.g. one loaded from a snapshot
+    pub fn from_key(ctx: Rc<RefCell<ProgramTestContext>>, key: Pubkey) -> AstrolendGroupFixture {
+        AstrolendGroupFixture { ctx, key }
+    }
+
     pub async fn new(
         ctx: Rc<RefCell<ProgramTestContext>>,
         config: GroupConfig,
@@ -73,6 +82,52 @@ impl AstrolendGroupFixture {
         &self,
         bank_asset_mint_fixture: &MintFixture,
         bank_config: BankConfig,
+    ) -> Result<BankFixture, BanksClientError> {
+        let oracle_ai = oracle_account_key(&bank_config);
+        self.add_bank(bank_asset_mint_fixture, bank_config, oracle_ai, true, false)
+            .await
+    }
+
+    /// Adds a bank with `oracle_ai` passed as the oracle account instead of the configured one
+    pub async fn try_lending_pool_add_bank_with_oracle_ai(
+        &self,
+        bank_asset_mint_fixture: &MintFixture,
+        bank_config: BankConfig,
+        oracle_ai: Pubkey,
+    ) -> Result<BankFixture, BanksClientError> {
+        self.add_bank(bank_asset_mint_fixture, bank_config, oracle_ai, true, false)
+            .await
+    }
+
+    /// Adds a bank created without an insurance vault, which sets `INSURANCE_DISABLED_FLAG`
+    pub async fn try_lending_pool_add_bank_without_insurance(
+        &self,
+        bank_asset_mint_fixture: &MintFixture,
+        bank_config: BankConfig,
+    ) -> Result<BankFixture, BanksClientError> {
+        let oracle_ai = oracle_account_key(&bank_config);
+        self.add_bank(bank_asset_mint_fixture, bank_config, oracle_ai, false, false)
+            .await
+    }
+
+    /// Adds a bank created with a receipt mint, which sets `RECEIPTS_ENABLED_FLAG`
+    pub async fn try_lending_pool_add_bank_with_receipts(
+        &self,
+        bank_asset_mint_fixture: &MintFixture,
+        bank_config: BankConfig,
+    ) -> Result<BankFixture, BanksClientError> {
+        let oracle_ai = oracle_account_key(&bank_config);
+        self.add_bank(bank_asset_mint_fixture, bank_config, oracle_ai, true, true)
+            .await
+    }
+
+    async fn add_bank(
+        &self,
+        bank_asset_mint_fixture: &MintFixture,
+        bank_config: BankConfig,
+        oracle_ai: Pubkey,
+        with_insurance: bool,
+        with_receipts: bool,
     ) -> Result<BankFixture, BanksClientError> {
         let bank_key = Keypair::new();
         let bank_mint = bank_asset_mint_fixture.key;
@@ -85,29 +140,23 @@ impl AstrolendGroupFixture {
             fee_payer: self.ctx.borrow().payer.pubkey(),
             bank_mint,
             bank: bank_key.pubkey(),
+            seed_zero_bank: find_bank_pda(&self.key, &bank_mint, 0).0,
             liquidity_vault_authority: bank_fixture.get_vault_authority(BankVaultType::Liquidity).0,
             liquidity_vault: bank_fixture.get_vault(BankVaultType::Liquidity).0,
             insurance_vault_authority: bank_fixture.get_vault_authority(BankVaultType::Insurance).0,
-            insurance_vault: bank_fixture.get_vault(BankVaultType::Insurance).0,
+            insurance_vault: with_insurance
+                .then(|| bank_fixture.get_vault(BankVaultType::Insurance).0),
             fee_vault_authority: bank_fixture.get_vault_authority(BankVaultType::Fee).0,
             fee_vault: bank_fixture.get_vault(BankVaultType::Fee).0,
             rent: sysvar::rent::id(),
             token_program: bank_asset_mint_fixture.token_program,
             system_program: system_program::id(),
+            receipt_mint: with_receipts
+                .then(|| find_receipt_mint_pda(&bank_key.pubkey()).0),
         }
         .to_account_metas(Some(true));
 
-        let oracle_key = {
-            let oracle_key_or_feed_id = bank_config.oracle_keys[0];
-            match bank_config.oracle_setup {
-                astrolend::state::price::OracleSetup::PythPushOracle => {
-                    get_oracle_id_from_feed_id(oracle_key_or_feed_id).unwrap()
-                }
-                _ => oracle_key_or_feed_id,
-            }
-        };
-
-        accounts.push(AccountMeta::new_readonly(oracle_key, false));
+        accounts.push(AccountMeta::new_readonly(oracle_ai, false));
 
         let ix = Instruction {
             program_id: astrolend::id(),
@@ -139,19 +188,29 @@ impl AstrolendGroupFixture {
         bank_asset_mint_fixture: &MintFixture,
         bank_config: BankConfig,
         bank_seed: u64,
+    ) -> Result<BankFixture, BanksClientError> {
+        let oracle_ai = oracle_account_key(&bank_config);
+        self.try_lending_pool_add_bank_with_seed_and_oracle_ai(
+            bank_asset_mint_fixture,
+            bank_config,
+            bank_seed,
+            oracle_ai,
+        )
+        .await
+    }
+
+    /// Adds a seeded bank with `oracle_ai` passed as the oracle account instead of the
+    /// configured one
+    pub async fn try_lending_pool_add_bank_with_seed_and_oracle_ai(
+        &self,
+        bank_asset_mint_fixture: &MintFixture,
+        bank_config: BankConfig,
+        bank_seed: u64,
+        oracle_ai: Pubkey,
     ) -> Result<BankFixture, BanksClientError> {
         let bank_mint = bank_asset_mint_fixture.key;
 
-        // Create PDA account from seeds
-        let (pda, _bump) = Pubkey::find_program_address(
-            [
-                self.key.as_ref(),
-                bank_mint.as_ref(),
-                &bank_seed.to_le_bytes(),
-            ]
-            .as_slice(),
-            &astrolend::id(),
-        );
+        let (pda, bump) = find_bank_pda(&self.key, &bank_mint, bank_seed);
 
         let bank_mint = bank_asset_mint_fixture.key;
         let bank_fixture = BankFixture::new(self.ctx.clone(), pda, bank_asset_mint_fixture);
@@ -165,16 +224,17 @@ impl AstrolendGroupFixture {
             liquidity_vault_authority: bank_fixture.get_vault_authority(BankVaultType::Liquidity).0,
             liquidity_vault: bank_fixture.get_vault(BankVaultType::Liquidity).0,
             insurance_vault_authority: bank_fixture.get_vault_authority(BankVaultType::Insurance).0,
-            insurance_vault: bank_fixture.get_vault(BankVaultType::Insurance).0,
+            insurance_vault: Some(bank_fixture.get_vault(BankVaultType::Insurance).0),
             fee_vault_authority: bank_fixture.get_vault_authority(BankVaultType::Fee).0,
             fee_vault: bank_fixture.get_vault(BankVaultType::Fee).0,
             rent: sysvar::rent::id(),
-            token_program: bank_fixture.get_token_program(),
+            token_program: bank_asset_mint_fixture.token_program,
             system_program: system_program::id(),
+            receipt_mint: None,
         }
         .to_account_metas(Some(true));
 
-        accounts.push(AccountMeta::new_readonly(bank_config.oracle_keys[0], false));
+        accounts.push(AccountMeta::new_readonly(oracle_ai, false));
 
         let ix = Instruction {
             program_id: astrolend::id(),
@@ -199,6 +259,11 @@ impl AstrolendGroupFixture {
             .process_transaction(tx)
             .await?;
 
+        let bank = bank_fixture.load().await;
+        assert!(bank.is_seeded());
+        assert_eq!(bank.bank_seed, bank_seed);
+        assert_eq!(bank.bank_bump, bump);
+
         Ok(bank_fixture)
     }
 
@@ -211,10 +276,12 @@ impl AstrolendGroupFixture {
             bank: bank.key,
             astrolend_group: self.key,
             admin: self.ctx.borrow().payer.pubkey(),
+            pending_config: None,
+            system_program: None,
         }
         .to_account_metas(Some(true));
 
-        if let Some(oracle_config) = bank_config_opt.oracle {
+        if let Some(oracle_config) = bank_config_opt.oracle_keys_and_setup {
             accounts.extend(
                 oracle_config
                     .keys
@@ -252,10 +319,8 @@ impl AstrolendGroupFixture {
         Ok(())
     }
 
-    pub async fn try_accrue_interest(&self, bank: &BankFixture) -> Result<()> {
-        let mut ctx = self.ctx.borrow_mut();
-
-        let ix = Instruction {
+    pub fn make_accrue_interest_ix(&self, bank: &BankFixture) -> Instruction {
+        Instruction {
             program_id: astrolend::id(),
             accounts: astrolend::accounts::LendingPoolAccrueBankInterest {
                 astrolend_group: self.key,
@@ -263,7 +328,12 @@ impl AstrolendGroupFixture {
             }
             .to_account_metas(Some(true)),
             data: astrolend::instruction::LendingPoolAccrueBankInterest {}.data(),
-        };
+        }
+    }
+
+    pub async fn try_accrue_interest(&self, bank: &BankFixture) -> Result<()> {
+        let ix = self.make_accrue_interest_ix(bank);
+        let mut ctx = self.ctx.borrow_mut();
 
         let tx = Transaction::new_signed_with_payer(
             &[ix],
@@ -277,8 +347,112 @@ impl AstrolendGroupFixture {
         Ok(())
     }
 
-    pub async fn try_update(&self, config: GroupConfig) -> Result<(), BanksClientError> {
+    pub async fn try_checkpoint_banks(
+        &self,
+        banks: &[&BankFixture],
+        checkpoint_id: Option<u64>,
+    ) -> Result<(), BanksClientError> {
+        let mut accounts = astrolend::accounts::LendingPoolCheckpointBanks {
+            astrolend_group: self.key,
+            admin: self.ctx.borrow().payer.pubkey(),
+        }
+        .to_account_metas(Some(true));
+
+        accounts.extend(banks.iter().map(|bank| AccountMeta::new(bank.key, false)));
+
+        let ix = Instruction {
+            program_id: astrolend::id(),
+            accounts,
+            data: astrolend::instruction::LendingPoolCheckpointBanks { checkpoint_id }.data(),
+        };
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub fn make_update_oracle_max_confidence_ix(
+        &self,
+        banks: &[&BankFixture],
+        oracle_params: OracleParamsOpt,
+    ) -> Instruction {
+        let mut accounts = astrolend::accounts::LendingPoolUpdateOracleMaxConfidence {
+            astrolend_group: self.key,
+            admin: self.ctx.borrow().payer.pubkey(),
+        }
+        .to_account_metas(Some(true));
+
+        accounts.extend(banks.iter().map(|bank| AccountMeta::new(bank.key, false)));
+
+        Instruction {
+            program_id: astrolend::id(),
+            accounts,
+            data: astrolend::instruction::LendingPoolUpdateOracleMaxConfidence { oracle_params }
+                .data(),
+        }
+    }
+
+    pub async fn try_update_oracle_max_confidence(
+        &self,
+        banks: &[&BankFixture],
+        oracle_params: OracleParamsOpt,
+    ) -> Result<(), BanksClientError> {
+        let ix = self.make_update_oracle_max_confidence_ix(banks, oracle_params);
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub async fn try_set_lookup_table(
+        &self,
+        lookup_table: Pubkey,
+        banks: &[&BankFixture],
+    ) -> Result<(), BanksClientError> {
+        let mut accounts = astrolend::accounts::AstrolendGroupSetLookupTable {
+            astrolend_group: self.key,
+            admin: self.ctx.borrow().payer.pubkey(),
+            lookup_table,
+        }
+        .to_account_metas(Some(true));
+
+        accounts.extend(
+            banks
+                .iter()
+                .map(|bank| AccountMeta::new_readonly(bank.key, false)),
+        );
+
         let ix = Instruction {
+            program_id: astrolend::id(),
+            accounts,
+            data: astrolend::instruction::AstrolendGroupSetLookupTable {}.data(),
+        };
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub fn make_update_ix(&self, config: GroupConfig) -> Instruction {
+        Instruction {
             program_id: astrolend::id(),
             accounts: astrolend::accounts::AstrolendGroupConfigure {
                 astrolend_group: self.key,
@@ -286,7 +460,11 @@ impl AstrolendGroupFixture {
             }
             .to_account_metas(Some(true)),
             data: astrolend::instruction::AstrolendGroupConfigure { config }.data(),
-        };
+        }
+    }
+
+    pub async fn try_update(&self, config: GroupConfig) -> Result<(), BanksClientError> {
+        let ix = self.make_update_ix(config);
 
         let tx = Transaction::new_signed_with_payer(
             &[ix],
@@ -304,7 +482,108 @@ impl AstrolendGroupFixture {
         Ok(())
     }
 
+    pub async fn try_configure_emode(
+        &self,
+        entries: Vec<EmodeEntryConfig>,
+    ) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: astrolend::id(),
+            accounts: astrolend::accounts::AstrolendGroupConfigure {
+                astrolend_group: self.key,
+                admin: self.ctx.borrow().payer.pubkey(),
+            }
+            .to_account_metas(Some(true)),
+            data: astrolend::instruction::AstrolendGroupConfigureEmode { entries }.data(),
+        };
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub async fn try_configure_fee_split(
+        &self,
+        entries: Vec<FeeSplitEntryConfig>,
+    ) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: astrolend::id(),
+            accounts: astrolend::accounts::AstrolendGroupConfigure {
+                astrolend_group: self.key,
+                admin: self.ctx.borrow().payer.pubkey(),
+            }
+            .to_account_metas(Some(true)),
+            data: astrolend::instruction::AstrolendGroupConfigureFeeSplit { entries }.data(),
+        };
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub async fn try_whitelist_add(&self, wallet: Pubkey) -> Result<(), BanksClientError> {
+        let mut ctx = self.ctx.borrow_mut();
+        let ix = Instruction {
+            program_id: astrolend::id(),
+            accounts: astrolend::accounts::GroupWhitelistAdd {
+                astrolend_group: self.key,
+                admin: ctx.payer.pubkey(),
+                fee_payer: ctx.payer.pubkey(),
+                whitelist_entry: find_whitelist_entry_pda(&self.key, &wallet).0,
+                system_program: system_program::id(),
+            }
+            .to_account_metas(Some(true)),
+            data: astrolend::instruction::GroupWhitelistAdd { wallet }.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub async fn try_whitelist_remove(&self, wallet: Pubkey) -> Result<(), BanksClientError> {
+        let mut ctx = self.ctx.borrow_mut();
+        let ix = Instruction {
+            program_id: astrolend::id(),
+            accounts: astrolend::accounts::GroupWhitelistRemove {
+                astrolend_group: self.key,
+                admin: ctx.payer.pubkey(),
+                whitelist_entry: find_whitelist_entry_pda(&self.key, &wallet).0,
+                rent_receiver: ctx.payer.pubkey(),
+            }
+            .to_account_metas(Some(true)),
+            data: astrolend::instruction::GroupWhitelistRemove {}.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
     pub async fn try_collect_fees(&self, bank: &BankFixture) -> Result<()> {
+        let token_program = bank.get_token_program().await;
+        let insurance_accounts = bank.get_insurance_accounts().await;
         let mut ctx = self.ctx.borrow_mut();
 
         let mut accounts = astrolend::accounts::LendingPoolCollectBankFees {
@@ -312,12 +591,12 @@ impl AstrolendGroupFixture {
             bank: bank.key,
             liquidity_vault_authority: bank.get_vault_authority(BankVaultType::Liquidity).0,
             liquidity_vault: bank.get_vault(BankVaultType::Liquidity).0,
-            insurance_vault: bank.get_vault(BankVaultType::Insurance).0,
+            insurance_vault: insurance_accounts.map(|(insurance_vault, _)| insurance_vault),
             fee_vault: bank.get_vault(BankVaultType::Fee).0,
-            token_program: bank.get_token_program(),
+            token_program,
         }
         .to_account_metas(Some(true));
-        if bank.mint.token_program == spl_token_2022::ID {
+        if token_program == spl_token_2022::ID {
             accounts.push(AccountMeta::new_readonly(bank.mint.key, false));
         }
 
@@ -354,26 +633,32 @@ impl AstrolendGroupFixture {
         astrolend_account: &AstrolendAccountFixture,
         nonce: u64,
     ) -> Result<(), BanksClientError> {
+        let token_program = bank.get_token_program().await;
+        let insurance_accounts = bank.get_insurance_accounts().await;
         let mut accounts = astrolend::accounts::LendingPoolHandleBankruptcy {
             astrolend_group: self.key,
             signer: self.ctx.borrow().payer.pubkey(),
             bank: bank.key,
             astrolend_account: astrolend_account.key,
             liquidity_vault: bank.get_vault(BankVaultType::Liquidity).0,
-            insurance_vault: bank.get_vault(BankVaultType::Insurance).0,
-            insurance_vault_authority: bank.get_vault_authority(BankVaultType::Insurance).0,
-            token_program: bank.get_token_program(),
+            insurance_vault: insurance_accounts.map(|(insurance_vault, _)| insurance_vault),
+            insurance_vault_authority: insurance_accounts.map(|(_, authority)| authority),
+            token_program,
         }
         .to_account_metas(Some(true));
-        if bank.mint.token_program == spl_token_2022::ID {
+        if token_program == spl_token_2022::ID {
             accounts.push(AccountMeta::new_readonly(bank.mint.key, false));
         }
 
-        accounts.append(
-            &mut astrolend_account
-                .load_observation_account_metas(vec![], vec![])
-                .await,
-        );
+        // Dust collateral of bad debt candidates is swept out of the observed banks
+        let mut observation_metas = astrolend_account
+            .load_observation_account_metas(vec![], vec![])
+            .await;
+        observation_metas
+            .iter_mut()
+            .step_by(2)
+            .for_each(|meta| meta.is_writable = true);
+        accounts.append(&mut observation_metas);
 
         let mut ctx = self.ctx.borrow_mut();
 
@@ -400,6 +685,12 @@ impl AstrolendGroupFixture {
     }
 
     pub async fn load(&self) -> astrolend::state::astrolend_group::AstrolendGroup {
+        self.try_load().await.unwrap()
+    }
+
+    pub async fn try_load(
+        &self,
+    ) -> anyhow::Result<astrolend::state::astrolend_group::AstrolendGroup> {
         load_and_deserialize::<astrolend::state::astrolend_group::AstrolendGroup>(
             self.ctx.clone(),
             &self.key,
@@ -407,3 +698,14 @@ impl AstrolendGroupFixture {
         .await
     }
 }
+
+/// The account holding the price of `bank_config`, Pyth push configs store the feed id instead
+fn oracle_account_key(bank_config: &BankConfig) -> Pubkey {
+    let oracle_key_or_feed_id = bank_config.oracle_keys[0];
+    match bank_config.oracle_setup {
+        astrolend::state::price::OracleSetup::PythPushOracle => {
+            get_oracle_id_from_feed_id(oracle_key_or_feed_id, bank_config.pyth_shard_id)
+        }
+        _ => oracle_key_or_feed_id,
+    }
+}
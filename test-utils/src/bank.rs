@@ -1,28 +1,34 @@
 use super::utils::load_and_deserialize;
 use crate::prelude::{
-    get_emissions_authority_address, get_emissions_token_account_address, MintFixture,
+    create_pyth_legacy_oracle_account_with_conf, get_emissions_authority_address,
+    get_emissions_token_account_address, get_oracle_id_from_feed_id, MintFixture,
     TokenAccountFixture,
 };
 use anchor_lang::{
     prelude::{AccountMeta, Pubkey},
-    InstructionData, ToAccountMetas,
+    AccountDeserialize, InstructionData, ToAccountMetas,
 };
 
 use fixed::types::I80F48;
 use astrolend::{
     bank_authority_seed,
+    constants::INSURANCE_STAKE_SEED,
+    prelude::AstrolendResult,
     state::{
-        astrolend_group::{Bank, BankConfigOpt, BankVaultType},
-        price::{OraclePriceFeedAdapter, OraclePriceType, PriceAdapter},
+        astrolend_account::RequirementType,
+        astrolend_group::{
+            Bank, BankConfigOpt, BankVaultType, InsuranceStake, OracleConfig, PendingBankConfig,
+        },
+        price::{OraclePriceFeedAdapter, OraclePriceType, OracleSetup, PriceAdapter},
     },
-    utils::{find_bank_vault_authority_pda, find_bank_vault_pda},
+    utils::{find_bank_vault_authority_pda, find_bank_vault_pda, find_pending_config_pda},
 };
 use solana_program::account_info::IntoAccountInfo;
 use solana_program::instruction::Instruction;
 use solana_program::sysvar::clock::Clock;
 use solana_program_test::BanksClientError;
 use solana_program_test::ProgramTestContext;
-use solana_sdk::{signer::Signer, transaction::Transaction};
+use solana_sdk::{signature::Keypair, signer::Signer, transaction::Transaction};
 use std::{cell::RefCell, fmt::Debug, rc::Rc};
 
 #[derive(Clone)]
@@ -45,8 +51,9 @@ impl BankFixture {
         }
     }
 
-    pub fn get_token_program(&self) -> Pubkey {
-        self.mint.token_program
+    /// Token program recorded on the bank at creation
+    pub async fn get_token_program(&self) -> Pubkey {
+        self.load().await.token_program
     }
 
     pub fn get_vault(&self, vault_type: BankVaultType) -> (Pubkey, u8) {
@@ -57,48 +64,119 @@ impl BankFixture {
         find_bank_vault_authority_pda(&self.key, vault_type)
     }
 
+    /// Insurance vault and its authority, `None` for banks created with insurance disabled
+    pub async fn get_insurance_accounts(&self) -> Option<(Pubkey, Pubkey)> {
+        (!self.load().await.is_insurance_disabled()).then(|| {
+            (
+                self.get_vault(BankVaultType::Insurance).0,
+                self.get_vault_authority(BankVaultType::Insurance).0,
+            )
+        })
+    }
+
     pub async fn get_price(&self) -> f64 {
+        self.try_get_price().await.unwrap()
+    }
+
+    /// Real time price of the bank at the current cluster clock, errors like the program would
+    /// on a stale or invalid oracle.
+    pub async fn try_get_price(&self) -> AstrolendResult<f64> {
         let bank = self.load().await;
         let oracle_key = bank.config.oracle_keys[0];
-        let mut oracle_account = self
-            .ctx
-            .borrow_mut()
+        let mut ctx = self.ctx.borrow_mut();
+        let clock: Clock = ctx.banks_client.get_sysvar().await.unwrap();
+        let mut oracle_account = ctx
             .banks_client
             .get_account(oracle_key)
             .await
             .unwrap()
             .unwrap();
         let ai = (&oracle_key, &mut oracle_account).into_account_info();
-        let oracle_adapter =
-            OraclePriceFeedAdapter::try_from_bank_config(&bank.config, &[ai], &Clock::default())
-                .unwrap();
+        let oracle_adapter = OraclePriceFeedAdapter::try_from_bank(&bank, &[ai], &clock)?;
+
+        Ok(oracle_adapter
+            .get_price_of_type(OraclePriceType::RealTime, None)?
+            .to_num())
+    }
+
+    /// Asset weight the risk engine applies at the current cluster clock, interpolated while a
+    /// weight change is scheduled
+    pub async fn get_asset_weight(&self, requirement_type: RequirementType) -> I80F48 {
+        let bank = self.load().await;
+        let clock: Clock = self
+            .ctx
+            .borrow_mut()
+            .banks_client
+            .get_sysvar()
+            .await
+            .unwrap();
 
-        oracle_adapter
-            .get_price_of_type(OraclePriceType::RealTime, None)
+        bank.get_asset_weight(requirement_type, clock.unix_timestamp)
             .unwrap()
-            .to_num()
+    }
+
+    /// Overwrite the bank's Pyth legacy price account with `ui_price`, at zero confidence
+    pub async fn set_pyth_legacy_price(&self, ui_price: f64) {
+        self.set_pyth_legacy_price_with_conf(ui_price, 0.).await
+    }
+
+    /// Overwrite the bank's Pyth legacy price account with `ui_price` and `ui_conf`
+    pub async fn set_pyth_legacy_price_with_conf(&self, ui_price: f64, ui_conf: f64) {
+        let bank = self.load().await;
+        assert_eq!(bank.config.oracle_setup, OracleSetup::PythLegacy);
+
+        self.ctx.borrow_mut().set_account(
+            &bank.config.oracle_keys[0],
+            &create_pyth_legacy_oracle_account_with_conf(
+                self.mint.key,
+                ui_price,
+                ui_conf,
+                bank.mint_decimals.into(),
+                None,
+            )
+            .into(),
+        );
     }
 
     pub async fn load(&self) -> Bank {
+        self.try_load().await.unwrap()
+    }
+
+    pub async fn try_load(&self) -> anyhow::Result<Bank> {
         load_and_deserialize::<Bank>(self.ctx.clone(), &self.key).await
     }
 
+    /// Oracle accounts `lending_pool_configure_bank` needs to apply `config` to `bank`
+    fn configure_remaining_accounts(bank: &Bank, config: &BankConfigOpt) -> Vec<AccountMeta> {
+        let mut accounts = vec![];
+
+        if let Some(oracle_config) = config.oracle_keys_and_setup {
+            accounts.push(AccountMeta::new_readonly(oracle_config.keys[0], false));
+
+            // The current oracle is needed to compare prices when the oracle changes
+            if oracle_config.keys[0] != bank.config.oracle_keys[0] {
+                accounts.push(AccountMeta::new_readonly(bank.config.oracle_keys[0], false));
+            }
+        } else if let Some(shard_id) = config.pyth_shard_id {
+            // Moving to another shard needs the oracle account on that shard
+            let oracle = get_oracle_id_from_feed_id(bank.config.oracle_keys[0], shard_id);
+            accounts.push(AccountMeta::new_readonly(oracle, false));
+        }
+
+        accounts
+    }
+
     pub async fn update_config(&self, config: BankConfigOpt) -> anyhow::Result<()> {
+        let bank = self.load().await;
         let mut accounts = astrolend::accounts::LendingPoolConfigureBank {
-            astrolend_group: self.load().await.group,
+            astrolend_group: bank.group,
             admin: self.ctx.borrow().payer.pubkey(),
             bank: self.key,
+            pending_config: None,
+            system_program: None,
         }
         .to_account_metas(Some(true));
-
-        if let Some(oracle_config) = config.oracle {
-            accounts.extend(
-                oracle_config
-                    .keys
-                    .iter()
-                    .map(|k| AccountMeta::new_readonly(*k, false)),
-            );
-        }
+        accounts.extend(Self::configure_remaining_accounts(&bank, &config));
 
         let ix = Instruction {
             program_id: astrolend::id(),
@@ -125,51 +203,91 @@ impl BankFixture {
         Ok(())
     }
 
-    pub async fn try_setup_emissions(
-        &self,
-        flags: u64,
-        rate: u64,
-        total_emissions: u64,
-        emissions_mint: Pubkey,
-        funding_account: Pubkey,
-        token_program: Pubkey,
-    ) -> Result<(), BanksClientError> {
-        let ix = Instruction {
+    pub fn get_pending_config_address(&self) -> Pubkey {
+        find_pending_config_pda(&self.key).0
+    }
+
+    pub async fn load_pending_config(&self) -> anyhow::Result<PendingBankConfig> {
+        let account = self
+            .ctx
+            .borrow_mut()
+            .banks_client
+            .get_account(self.get_pending_config_address())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("no pending config for bank {}", self.key))?;
+
+        Ok(PendingBankConfig::try_deserialize(&mut account.data.as_slice())?)
+    }
+
+    /// `lending_pool_configure_bank` writing `config` to the pending config of the bank
+    pub async fn make_propose_config_ix(&self, config: BankConfigOpt) -> Instruction {
+        Instruction {
             program_id: astrolend::id(),
-            accounts: astrolend::accounts::LendingPoolSetupEmissions {
+            accounts: astrolend::accounts::LendingPoolConfigureBank {
                 astrolend_group: self.load().await.group,
                 admin: self.ctx.borrow().payer.pubkey(),
                 bank: self.key,
-                emissions_mint,
-                emissions_funding_account: funding_account,
-                emissions_auth: get_emissions_authority_address(self.key, emissions_mint).0,
-                emissions_token_account: get_emissions_token_account_address(
-                    self.key,
-                    emissions_mint,
-                )
-                .0,
-                token_program,
-                system_program: solana_program::system_program::id(),
+                pending_config: Some(self.get_pending_config_address()),
+                system_program: Some(solana_program::system_program::id()),
             }
             .to_account_metas(Some(true)),
-            data: astrolend::instruction::LendingPoolSetupEmissions {
-                rate,
-                flags,
-                total_emissions,
+            data: astrolend::instruction::LendingPoolConfigureBank {
+                bank_config_opt: config,
             }
             .data(),
-        };
+        }
+    }
 
-        let tx = {
-            let ctx = self.ctx.borrow_mut();
+    pub async fn try_propose_config(&self, config: BankConfigOpt) -> Result<(), BanksClientError> {
+        let ix = self.make_propose_config_ix(config).await;
 
-            Transaction::new_signed_with_payer(
-                &[ix],
-                Some(&ctx.payer.pubkey()),
-                &[&ctx.payer],
-                ctx.last_blockhash,
-            )
-        };
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await
+    }
+
+    /// Passes the oracle accounts the pending config needs, as `update_config` does
+    pub async fn make_execute_pending_config_ix(&self) -> anyhow::Result<Instruction> {
+        let bank = self.load().await;
+        let pending_config = self.load_pending_config().await?;
+
+        let mut accounts = astrolend::accounts::LendingPoolExecutePendingConfig {
+            astrolend_group: bank.group,
+            bank: self.key,
+            pending_config: self.get_pending_config_address(),
+            proposer: pending_config.proposer,
+        }
+        .to_account_metas(Some(true));
+        accounts.extend(Self::configure_remaining_accounts(
+            &bank,
+            &pending_config.config,
+        ));
+
+        Ok(Instruction {
+            program_id: astrolend::id(),
+            accounts,
+            data: astrolend::instruction::LendingPoolExecutePendingConfig {}.data(),
+        })
+    }
+
+    pub async fn try_execute_pending_config(&self) -> anyhow::Result<()> {
+        let ix = self.make_execute_pending_config_ix().await?;
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
 
         self.ctx
             .borrow_mut()
@@ -180,49 +298,28 @@ impl BankFixture {
         Ok(())
     }
 
-    pub async fn try_update_emissions(
-        &self,
-        emissions_flags: Option<u64>,
-        emissions_rate: Option<u64>,
-        additional_emissions: Option<(u64, Pubkey)>,
-        token_program: Pubkey,
-    ) -> Result<(), BanksClientError> {
-        let bank = self.load().await;
+    pub async fn try_cancel_pending_config(&self) -> anyhow::Result<()> {
+        let pending_config = self.load_pending_config().await?;
 
         let ix = Instruction {
             program_id: astrolend::id(),
-            accounts: astrolend::accounts::LendingPoolUpdateEmissionsParameters {
+            accounts: astrolend::accounts::LendingPoolCancelPendingConfig {
                 astrolend_group: self.load().await.group,
                 admin: self.ctx.borrow().payer.pubkey(),
                 bank: self.key,
-                emissions_mint: bank.emissions_mint,
-                emissions_funding_account: additional_emissions.map(|(_, f)| f).unwrap_or_default(),
-                emissions_token_account: get_emissions_token_account_address(
-                    self.key,
-                    bank.emissions_mint,
-                )
-                .0,
-                token_program,
+                pending_config: self.get_pending_config_address(),
+                proposer: pending_config.proposer,
             }
             .to_account_metas(Some(true)),
-            data: astrolend::instruction::LendingPoolUpdateEmissionsParameters {
-                emissions_flags,
-                emissions_rate,
-                additional_emissions: additional_emissions.map(|(a, _)| a),
-            }
-            .data(),
+            data: astrolend::instruction::LendingPoolCancelPendingConfig {}.data(),
         };
 
-        let tx = {
-            let ctx = self.ctx.borrow_mut();
-
-            Transaction::new_signed_with_payer(
-                &[ix],
-                Some(&ctx.payer.pubkey()),
-                &[&ctx.payer],
-                ctx.last_blockhash,
-            )
-        };
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
 
         self.ctx
             .borrow_mut()
@@ -233,95 +330,746 @@ impl BankFixture {
         Ok(())
     }
 
-    pub async fn try_withdraw_fees(
-        &self,
-        receiving_account: &TokenAccountFixture,
-        amount: u64,
-    ) -> Result<(), BanksClientError> {
-        let bank = self.load().await;
-        let mut ctx = self.ctx.borrow_mut();
-        let signer_pk = ctx.payer.pubkey();
-        let (fee_vault_authority, _) = Pubkey::find_program_address(
-            bank_authority_seed!(BankVaultType::Fee, self.key),
-            &astrolend::id(),
-        );
-
-        let mut accounts = astrolend::accounts::LendingPoolWithdrawFees {
-            astrolend_group: bank.group,
-            token_program: receiving_account.token_program,
+    pub async fn make_configure_oracle_unchecked_ix(&self, oracle: OracleConfig) -> Instruction {
+        let mut accounts = astrolend::accounts::LendingPoolConfigureBank {
+            astrolend_group: self.load().await.group,
+            admin: self.ctx.borrow().payer.pubkey(),
             bank: self.key,
-            admin: signer_pk,
-            fee_vault: bank.fee_vault,
-            fee_vault_authority,
-            dst_token_account: receiving_account.key,
+            pending_config: None,
+            system_program: None,
         }
         .to_account_metas(Some(true));
-        if self.mint.token_program == spl_token_2022::ID {
-            accounts.push(AccountMeta::new_readonly(self.mint.key, false));
-        }
 
-        let ix = Instruction {
+        accounts.push(AccountMeta::new_readonly(oracle.keys[0], false));
+
+        Instruction {
             program_id: astrolend::id(),
             accounts,
-            data: astrolend::instruction::LendingPoolWithdrawFees { amount }.data(),
-        };
+            data: astrolend::instruction::LendingPoolConfigureBankOracleUnchecked { oracle }
+                .data(),
+        }
+    }
+
+    pub async fn try_configure_oracle_unchecked(
+        &self,
+        oracle: OracleConfig,
+    ) -> Result<(), BanksClientError> {
+        let ix = self.make_configure_oracle_unchecked_ix(oracle).await;
 
         let tx = Transaction::new_signed_with_payer(
             &[ix],
-            Some(&ctx.payer.pubkey().clone()),
-            &[&ctx.payer],
-            ctx.last_blockhash,
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
         );
 
-        ctx.banks_client.process_transaction(tx).await?;
-
-        Ok(())
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await
     }
 
-    pub async fn try_withdraw_insurance(
+    pub async fn try_set_settlement_price(
         &self,
-        receiving_account: &TokenAccountFixture,
-        amount: u64,
+        price: u64,
+        expo: i32,
     ) -> Result<(), BanksClientError> {
-        let bank = self.load().await;
-        let mut ctx = self.ctx.borrow_mut();
-        let signer_pk = ctx.payer.pubkey();
-        let (insurance_vault_authority, _) = Pubkey::find_program_address(
-            bank_authority_seed!(BankVaultType::Insurance, self.key),
-            &astrolend::id(),
+        let ix = Instruction {
+            program_id: astrolend::id(),
+            accounts: astrolend::accounts::LendingPoolConfigureBank {
+                astrolend_group: self.load().await.group,
+                admin: self.ctx.borrow().payer.pubkey(),
+                bank: self.key,
+                pending_config: None,
+                system_program: None,
+            }
+            .to_account_metas(Some(true)),
+            data: astrolend::instruction::LendingPoolSetBankSettlementPrice { price, expo }.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
         );
 
-        let mut accounts = astrolend::accounts::LendingPoolWithdrawInsurance {
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await
+    }
+
+    pub async fn try_clear_settlement_price(&self) -> Result<(), BanksClientError> {
+        let bank = self.load().await;
+        let mut accounts = astrolend::accounts::LendingPoolConfigureBank {
             astrolend_group: bank.group,
-            token_program: receiving_account.token_program,
+            admin: self.ctx.borrow().payer.pubkey(),
             bank: self.key,
-            admin: signer_pk,
-            insurance_vault: bank.insurance_vault,
-            insurance_vault_authority,
-            dst_token_account: receiving_account.key,
+            pending_config: None,
+            system_program: None,
         }
         .to_account_metas(Some(true));
-        if self.mint.token_program == spl_token_2022::ID {
-            accounts.push(AccountMeta::new_readonly(self.mint.key, false));
-        }
+
+        accounts.push(AccountMeta::new_readonly(bank.config.oracle_keys[0], false));
 
         let ix = Instruction {
             program_id: astrolend::id(),
             accounts,
-            data: astrolend::instruction::LendingPoolWithdrawInsurance { amount }.data(),
+            data: astrolend::instruction::LendingPoolClearBankSettlementPrice {}.data(),
         };
 
         let tx = Transaction::new_signed_with_payer(
             &[ix],
-            Some(&ctx.payer.pubkey().clone()),
-            &[&ctx.payer],
-            ctx.last_blockhash,
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
         );
 
-        ctx.banks_client.process_transaction(tx).await?;
-
-        Ok(())
-    }
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await
+    }
+
+    pub async fn try_schedule_weight_change(
+        &self,
+        target_asset_weight_init: I80F48,
+        target_asset_weight_maint: I80F48,
+        end_timestamp: i64,
+    ) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: astrolend::id(),
+            accounts: astrolend::accounts::LendingPoolConfigureBank {
+                astrolend_group: self.load().await.group,
+                admin: self.ctx.borrow().payer.pubkey(),
+                bank: self.key,
+                pending_config: None,
+                system_program: None,
+            }
+            .to_account_metas(Some(true)),
+            data: astrolend::instruction::LendingPoolScheduleWeightChange {
+                target_asset_weight_init: target_asset_weight_init.into(),
+                target_asset_weight_maint: target_asset_weight_maint.into(),
+                end_timestamp,
+            }
+            .data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await
+    }
+
+    pub async fn try_cancel_weight_change(&self) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: astrolend::id(),
+            accounts: astrolend::accounts::LendingPoolConfigureBank {
+                astrolend_group: self.load().await.group,
+                admin: self.ctx.borrow().payer.pubkey(),
+                bank: self.key,
+                pending_config: None,
+                system_program: None,
+            }
+            .to_account_metas(Some(true)),
+            data: astrolend::instruction::LendingPoolCancelWeightChange {}.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await
+    }
+
+    pub async fn try_setup_emissions(
+        &self,
+        flags: u64,
+        rate: u64,
+        total_emissions: u64,
+        emissions_mint: Pubkey,
+        funding_account: Pubkey,
+        token_program: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: astrolend::id(),
+            accounts: astrolend::accounts::LendingPoolSetupEmissions {
+                astrolend_group: self.load().await.group,
+                admin: self.ctx.borrow().payer.pubkey(),
+                bank: self.key,
+                emissions_mint,
+                emissions_funding_account: funding_account,
+                emissions_auth: get_emissions_authority_address(self.key, emissions_mint).0,
+                emissions_token_account: get_emissions_token_account_address(
+                    self.key,
+                    emissions_mint,
+                )
+                .0,
+                token_program,
+                system_program: solana_program::system_program::id(),
+            }
+            .to_account_metas(Some(true)),
+            data: astrolend::instruction::LendingPoolSetupEmissions {
+                rate,
+                flags,
+                total_emissions,
+            }
+            .data(),
+        };
+
+        let tx = {
+            let ctx = self.ctx.borrow_mut();
+
+            Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&ctx.payer.pubkey()),
+                &[&ctx.payer],
+                ctx.last_blockhash,
+            )
+        };
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn try_update_emissions(
+        &self,
+        emissions_flags: Option<u64>,
+        emissions_rate: Option<u64>,
+        additional_emissions: Option<(u64, Pubkey)>,
+        token_program: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let bank = self.load().await;
+
+        let ix = Instruction {
+            program_id: astrolend::id(),
+            accounts: astrolend::accounts::LendingPoolUpdateEmissionsParameters {
+                astrolend_group: self.load().await.group,
+                admin: self.ctx.borrow().payer.pubkey(),
+                bank: self.key,
+                emissions_mint: bank.emissions_mint,
+                emissions_funding_account: additional_emissions.map(|(_, f)| f).unwrap_or_default(),
+                emissions_token_account: get_emissions_token_account_address(
+                    self.key,
+                    bank.emissions_mint,
+                )
+                .0,
+                token_program,
+            }
+            .to_account_metas(Some(true)),
+            data: astrolend::instruction::LendingPoolUpdateEmissionsParameters {
+                emissions_flags,
+                emissions_rate,
+                additional_emissions: additional_emissions.map(|(a, _)| a),
+            }
+            .data(),
+        };
+
+        let tx = {
+            let ctx = self.ctx.borrow_mut();
+
+            Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&ctx.payer.pubkey()),
+                &[&ctx.payer],
+                ctx.last_blockhash,
+            )
+        };
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn try_close_emissions(
+        &self,
+        destination_account: Pubkey,
+        token_program: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let bank = self.load().await;
+
+        let ix = Instruction {
+            program_id: astrolend::id(),
+            accounts: astrolend::accounts::LendingPoolCloseEmissions {
+                astrolend_group: bank.group,
+                admin: self.ctx.borrow().payer.pubkey(),
+                bank: self.key,
+                emissions_mint: bank.emissions_mint,
+                emissions_auth: get_emissions_authority_address(self.key, bank.emissions_mint).0,
+                emissions_token_account: get_emissions_token_account_address(
+                    self.key,
+                    bank.emissions_mint,
+                )
+                .0,
+                destination_account,
+                token_program,
+            }
+            .to_account_metas(Some(true)),
+            data: astrolend::instruction::LendingPoolCloseEmissions {}.data(),
+        };
+
+        let tx = {
+            let ctx = self.ctx.borrow_mut();
+
+            Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&ctx.payer.pubkey()),
+                &[&ctx.payer],
+                ctx.last_blockhash,
+            )
+        };
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn try_settle_emissions_many(
+        &self,
+        astrolend_accounts: &[Pubkey],
+    ) -> Result<(), BanksClientError> {
+        let mut accounts = astrolend::accounts::LendingAccountSettleEmissionsMany { bank: self.key }
+            .to_account_metas(Some(true));
+        accounts.extend(
+            astrolend_accounts
+                .iter()
+                .map(|account| AccountMeta::new(*account, false)),
+        );
+
+        let ix = Instruction {
+            program_id: astrolend::id(),
+            accounts,
+            data: astrolend::instruction::LendingAccountSettleEmissionsMany {}.data(),
+        };
+
+        let tx = {
+            let ctx = self.ctx.borrow_mut();
+
+            Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&ctx.payer.pubkey()),
+                &[&ctx.payer],
+                ctx.last_blockhash,
+            )
+        };
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn make_withdraw_fees_ix(
+        &self,
+        receiving_account: &TokenAccountFixture,
+        amount: u64,
+    ) -> Instruction {
+        let bank = self.load().await;
+        let (fee_vault_authority, _) = Pubkey::find_program_address(
+            bank_authority_seed!(BankVaultType::Fee, self.key),
+            &astrolend::id(),
+        );
+
+        let mut accounts = astrolend::accounts::LendingPoolWithdrawFees {
+            astrolend_group: bank.group,
+            token_program: receiving_account.token_program,
+            bank: self.key,
+            admin: self.ctx.borrow().payer.pubkey(),
+            fee_vault: bank.fee_vault,
+            fee_vault_authority,
+            dst_token_account: receiving_account.key,
+        }
+        .to_account_metas(Some(true));
+        if self.mint.token_program == spl_token_2022::ID {
+            accounts.push(AccountMeta::new_readonly(self.mint.key, false));
+        }
+
+        Instruction {
+            program_id: astrolend::id(),
+            accounts,
+            data: astrolend::instruction::LendingPoolWithdrawFees { amount }.data(),
+        }
+    }
+
+    pub async fn try_withdraw_fees(
+        &self,
+        receiving_account: &TokenAccountFixture,
+        amount: u64,
+    ) -> Result<(), BanksClientError> {
+        let ix = self.make_withdraw_fees_ix(receiving_account, amount).await;
+        let mut ctx = self.ctx.borrow_mut();
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
+    /// `destinations` are the associated token accounts of the group fee split, in table order
+    pub async fn make_distribute_fees_ix(&self, destinations: &[Pubkey]) -> Instruction {
+        let bank = self.load().await;
+        let (fee_vault_authority, _) = Pubkey::find_program_address(
+            bank_authority_seed!(BankVaultType::Fee, self.key),
+            &astrolend::id(),
+        );
+
+        let mut accounts = astrolend::accounts::LendingPoolDistributeFees {
+            astrolend_group: bank.group,
+            bank: self.key,
+            fee_vault: bank.fee_vault,
+            fee_vault_authority,
+            token_program: self.mint.token_program,
+        }
+        .to_account_metas(Some(true));
+        if self.mint.token_program == spl_token_2022::ID {
+            accounts.push(AccountMeta::new_readonly(self.mint.key, false));
+        }
+        accounts.extend(
+            destinations
+                .iter()
+                .map(|destination| AccountMeta::new(*destination, false)),
+        );
+
+        Instruction {
+            program_id: astrolend::id(),
+            accounts,
+            data: astrolend::instruction::LendingPoolDistributeFees {}.data(),
+        }
+    }
+
+    pub async fn try_distribute_fees(
+        &self,
+        destinations: &[Pubkey],
+    ) -> Result<(), BanksClientError> {
+        let ix = self.make_distribute_fees_ix(destinations).await;
+        let mut ctx = self.ctx.borrow_mut();
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
+    pub async fn make_withdraw_insurance_ix(
+        &self,
+        receiving_account: &TokenAccountFixture,
+        amount: u64,
+    ) -> Instruction {
+        let bank = self.load().await;
+        let (insurance_vault_authority, _) = Pubkey::find_program_address(
+            bank_authority_seed!(BankVaultType::Insurance, self.key),
+            &astrolend::id(),
+        );
+
+        let mut accounts = astrolend::accounts::LendingPoolWithdrawInsurance {
+            astrolend_group: bank.group,
+            token_program: receiving_account.token_program,
+            bank: self.key,
+            admin: self.ctx.borrow().payer.pubkey(),
+            insurance_vault: bank.insurance_vault,
+            insurance_vault_authority,
+            dst_token_account: receiving_account.key,
+        }
+        .to_account_metas(Some(true));
+        if self.mint.token_program == spl_token_2022::ID {
+            accounts.push(AccountMeta::new_readonly(self.mint.key, false));
+        }
+
+        Instruction {
+            program_id: astrolend::id(),
+            accounts,
+            data: astrolend::instruction::LendingPoolWithdrawInsurance { amount }.data(),
+        }
+    }
+
+    pub async fn try_withdraw_insurance(
+        &self,
+        receiving_account: &TokenAccountFixture,
+        amount: u64,
+    ) -> Result<(), BanksClientError> {
+        let ix = self.make_withdraw_insurance_ix(receiving_account, amount).await;
+        let mut ctx = self.ctx.borrow_mut();
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
+    pub fn get_insurance_stake_address(&self, depositor: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[
+                INSURANCE_STAKE_SEED.as_bytes(),
+                self.key.as_ref(),
+                depositor.as_ref(),
+            ],
+            &astrolend::id(),
+        )
+        .0
+    }
+
+    pub async fn load_insurance_stake(&self, depositor: &Pubkey) -> InsuranceStake {
+        load_and_deserialize::<InsuranceStake>(
+            self.ctx.clone(),
+            &self.get_insurance_stake_address(depositor),
+        )
+        .await
+        .unwrap()
+    }
+
+    pub async fn try_init_insurance_stake(
+        &self,
+        depositor: &Keypair,
+    ) -> Result<(), BanksClientError> {
+        let bank = self.load().await;
+        let mut ctx = self.ctx.borrow_mut();
+
+        let ix = Instruction {
+            program_id: astrolend::id(),
+            accounts: astrolend::accounts::LendingPoolInitInsuranceStake {
+                astrolend_group: bank.group,
+                bank: self.key,
+                depositor: depositor.pubkey(),
+                fee_payer: ctx.payer.pubkey(),
+                insurance_stake: self.get_insurance_stake_address(&depositor.pubkey()),
+                system_program: solana_program::system_program::ID,
+            }
+            .to_account_metas(Some(true)),
+            data: astrolend::instruction::LendingPoolInitInsuranceStake {}.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer, depositor],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
+    /// `amount` in native units, donated by the payer
+    pub async fn make_donate_ix(
+        &self,
+        funding_account: &TokenAccountFixture,
+        amount: u64,
+    ) -> Instruction {
+        let bank = self.load().await;
+
+        let mut accounts = astrolend::accounts::LendingPoolDonate {
+            astrolend_group: bank.group,
+            bank: self.key,
+            donor: self.ctx.borrow().payer.pubkey(),
+            signer_token_account: funding_account.key,
+            bank_liquidity_vault: bank.liquidity_vault,
+            token_program: funding_account.token_program,
+        }
+        .to_account_metas(Some(true));
+        if self.mint.token_program == spl_token_2022::ID {
+            accounts.push(AccountMeta::new_readonly(self.mint.key, false));
+        }
+
+        Instruction {
+            program_id: astrolend::id(),
+            accounts,
+            data: astrolend::instruction::LendingPoolDonate { amount }.data(),
+        }
+    }
+
+    pub async fn try_donate(
+        &self,
+        funding_account: &TokenAccountFixture,
+        amount: u64,
+    ) -> Result<(), BanksClientError> {
+        let ix = self.make_donate_ix(funding_account, amount).await;
+        let mut ctx = self.ctx.borrow_mut();
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
+    pub async fn make_clear_stuck_guard_ix(&self) -> Instruction {
+        let bank = self.load().await;
+
+        Instruction {
+            program_id: astrolend::id(),
+            accounts: astrolend::accounts::LendingPoolClearStuckGuard {
+                astrolend_group: bank.group,
+                bank: self.key,
+            }
+            .to_account_metas(Some(true)),
+            data: astrolend::instruction::LendingPoolClearStuckGuard {}.data(),
+        }
+    }
+
+    pub async fn try_clear_stuck_guard(&self) -> Result<(), BanksClientError> {
+        let ix = self.make_clear_stuck_guard_ix().await;
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
+    /// `amount` in native units, the stake must be initialized
+    pub async fn try_deposit_insurance(
+        &self,
+        depositor: &Keypair,
+        funding_account: &TokenAccountFixture,
+        amount: u64,
+    ) -> Result<(), BanksClientError> {
+        let bank = self.load().await;
+        let mut ctx = self.ctx.borrow_mut();
+
+        let mut accounts = astrolend::accounts::LendingPoolDepositInsurance {
+            astrolend_group: bank.group,
+            bank: self.key,
+            depositor: depositor.pubkey(),
+            insurance_stake: self.get_insurance_stake_address(&depositor.pubkey()),
+            signer_token_account: funding_account.key,
+            insurance_vault: bank.insurance_vault,
+            token_program: funding_account.token_program,
+        }
+        .to_account_metas(Some(true));
+        if self.mint.token_program == spl_token_2022::ID {
+            accounts.push(AccountMeta::new_readonly(self.mint.key, false));
+        }
+
+        let ix = Instruction {
+            program_id: astrolend::id(),
+            accounts,
+            data: astrolend::instruction::LendingPoolDepositInsurance { amount }.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer, depositor],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
+    /// `amount` in native units
+    pub async fn try_withdraw_insurance_stake(
+        &self,
+        depositor: &Keypair,
+        receiving_account: &TokenAccountFixture,
+        amount: u64,
+        withdraw_all: Option<bool>,
+    ) -> Result<(), BanksClientError> {
+        let bank = self.load().await;
+        let mut ctx = self.ctx.borrow_mut();
+
+        let mut accounts = astrolend::accounts::LendingPoolWithdrawInsuranceStake {
+            astrolend_group: bank.group,
+            bank: self.key,
+            depositor: depositor.pubkey(),
+            insurance_stake: self.get_insurance_stake_address(&depositor.pubkey()),
+            insurance_vault: bank.insurance_vault,
+            insurance_vault_authority: self.get_vault_authority(BankVaultType::Insurance).0,
+            dst_token_account: receiving_account.key,
+            token_program: receiving_account.token_program,
+        }
+        .to_account_metas(Some(true));
+        if self.mint.token_program == spl_token_2022::ID {
+            accounts.push(AccountMeta::new_readonly(self.mint.key, false));
+        }
+
+        let ix = Instruction {
+            program_id: astrolend::id(),
+            accounts,
+            data: astrolend::instruction::LendingPoolWithdrawInsuranceStake {
+                amount,
+                withdraw_all,
+            }
+            .data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer, depositor],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
 
     pub async fn get_vault_token_account(&self, vault_type: BankVaultType) -> TokenAccountFixture {
         let (vault, _) = self.get_vault(vault_type);
@@ -346,6 +1094,128 @@ impl BankFixture {
             .borrow_mut()
             .set_account(&self.key, &bank_ai.into());
     }
+
+    /// Pins both share values as if interest was accrued up to `last_update`, instructions
+    /// running at that timestamp have nothing left to accrue
+    pub async fn set_share_values(
+        &self,
+        asset_share_value: I80F48,
+        liability_share_value: I80F48,
+        last_update: i64,
+    ) {
+        let mut bank_ai = self
+            .ctx
+            .borrow_mut()
+            .banks_client
+            .get_account(self.key)
+            .await
+            .unwrap()
+            .unwrap();
+        let bank = bytemuck::from_bytes_mut::<Bank>(&mut bank_ai.data.as_mut_slice()[8..]);
+
+        bank.asset_share_value = asset_share_value.into();
+        bank.liability_share_value = liability_share_value.into();
+        bank.last_update = last_update;
+
+        self.ctx
+            .borrow_mut()
+            .set_account(&self.key, &bank_ai.into());
+    }
+
+    pub async fn set_total_asset_shares(&self, value: I80F48) {
+        let mut bank_ai = self
+            .ctx
+            .borrow_mut()
+            .banks_client
+            .get_account(self.key)
+            .await
+            .unwrap()
+            .unwrap();
+        let bank = bytemuck::from_bytes_mut::<Bank>(&mut bank_ai.data.as_mut_slice()[8..]);
+
+        bank.total_asset_shares = value.into();
+
+        self.ctx
+            .borrow_mut()
+            .set_account(&self.key, &bank_ai.into());
+    }
+
+    /// Clears the stored emissions authority bump, as on banks set up before it was stored
+    pub async fn clear_emissions_auth_bump(&self) {
+        let mut bank_ai = self
+            .ctx
+            .borrow_mut()
+            .banks_client
+            .get_account(self.key)
+            .await
+            .unwrap()
+            .unwrap();
+        let bank = bytemuck::from_bytes_mut::<Bank>(&mut bank_ai.data.as_mut_slice()[8..]);
+
+        bank.emissions_auth_bump = 0;
+
+        self.ctx
+            .borrow_mut()
+            .set_account(&self.key, &bank_ai.into());
+    }
+
+    /// Clears the recorded token program, as on banks set up before it was recorded
+    pub async fn clear_token_program(&self) {
+        let mut bank_ai = self
+            .ctx
+            .borrow_mut()
+            .banks_client
+            .get_account(self.key)
+            .await
+            .unwrap()
+            .unwrap();
+        let bank = bytemuck::from_bytes_mut::<Bank>(&mut bank_ai.data.as_mut_slice()[8..]);
+
+        bank.token_program = Pubkey::default();
+
+        self.ctx
+            .borrow_mut()
+            .set_account(&self.key, &bank_ai.into());
+    }
+
+    /// Leaves the reentrancy guard set as if an instruction entered at `slot` never exited
+    pub async fn set_reentrancy_guard(&self, slot: u64) {
+        let mut bank_ai = self
+            .ctx
+            .borrow_mut()
+            .banks_client
+            .get_account(self.key)
+            .await
+            .unwrap()
+            .unwrap();
+        let bank = bytemuck::from_bytes_mut::<Bank>(&mut bank_ai.data.as_mut_slice()[8..]);
+
+        bank.reentrancy_guard = 1;
+        bank.reentrancy_guard_slot = slot;
+
+        self.ctx
+            .borrow_mut()
+            .set_account(&self.key, &bank_ai.into());
+    }
+
+    /// Records a bankruptcy on the bank at `slot`, freezing withdrawals until the next slot
+    pub async fn set_last_bankruptcy_slot(&self, slot: u64) {
+        let mut bank_ai = self
+            .ctx
+            .borrow_mut()
+            .banks_client
+            .get_account(self.key)
+            .await
+            .unwrap()
+            .unwrap();
+        let bank = bytemuck::from_bytes_mut::<Bank>(&mut bank_ai.data.as_mut_slice()[8..]);
+
+        bank.last_bankruptcy_slot = slot;
+
+        self.ctx
+            .borrow_mut()
+            .set_account(&self.key, &bank_ai.into());
+    }
 }
 
 impl Debug for BankFixture {
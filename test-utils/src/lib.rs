@@ -1,3 +1,5 @@
+pub use astrolend_cpi;
+pub use test_cpi_caller;
 pub use transfer_hook;
 pub mod bank;
 pub mod astrolend_account;
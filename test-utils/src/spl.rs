@@ -1,6 +1,7 @@
 use crate::{transfer_hook::TEST_HOOK_ID, ui_to_native};
 use anchor_lang::prelude::*;
 use anchor_spl::{
+    associated_token::{get_associated_token_address_with_program_id, spl_associated_token_account},
     token::{spl_token, Mint, TokenAccount},
     token_2022::{
         self,
@@ -9,18 +10,20 @@ use anchor_spl::{
             extension::{
                 interest_bearing_mint::InterestBearingConfig,
                 mint_close_authority::MintCloseAuthority, permanent_delegate::PermanentDelegate,
-                transfer_fee::TransferFee, transfer_hook::TransferHook, BaseState,
-                BaseStateWithExtensions, ExtensionType, StateWithExtensionsOwned,
+                transfer_fee::{TransferFee, TransferFeeConfig},
+                transfer_hook::TransferHook,
+                BaseState, BaseStateWithExtensions, BaseStateWithExtensionsMut, ExtensionType,
+                StateWithExtensionsMut, StateWithExtensionsOwned,
             },
         },
     },
     token_interface::spl_pod::bytemuck::pod_get_packed_len,
 };
 use solana_cli_output::CliAccount;
-use solana_program_test::ProgramTestContext;
+use solana_program_test::{BanksClientError, ProgramTestContext};
 use solana_sdk::{
     account::{AccountSharedData, ReadableAccount, WritableAccount},
-    instruction::Instruction,
+    instruction::{AccountMeta, Instruction},
     native_token::LAMPORTS_PER_SOL,
     program_pack::{Pack, Sealed},
     signature::Keypair,
@@ -28,8 +31,10 @@ use solana_sdk::{
     system_instruction::{self, create_account},
     transaction::Transaction,
 };
+use spl_tlv_account_resolution::account::ExtraAccountMeta;
 use spl_transfer_hook_interface::{
-    get_extra_account_metas_address, instruction::initialize_extra_account_meta_list,
+    get_extra_account_metas_address,
+    instruction::{initialize_extra_account_meta_list, update_extra_account_meta_list},
 };
 use std::{cell::RefCell, fs::File, io::Read, path::PathBuf, rc::Rc, str::FromStr};
 
@@ -195,6 +200,44 @@ impl MintFixture {
         }
     }
 
+    /// The native mint of the token program, wrapping SOL
+    pub async fn new_native(ctx: Rc<RefCell<ProgramTestContext>>) -> MintFixture {
+        let key = spl_token::native_mint::ID;
+        let existing = ctx.borrow_mut().banks_client.get_account(key).await.unwrap();
+
+        let mint = match existing {
+            Some(account) => spl_token_2022::state::Mint::unpack(&account.data).unwrap(),
+            None => {
+                let mint = spl_token_2022::state::Mint {
+                    decimals: spl_token::native_mint::DECIMALS,
+                    is_initialized: true,
+                    ..Default::default()
+                };
+                let mut data = vec![0; Mint::LEN];
+                spl_token_2022::state::Mint::pack(mint, &mut data).unwrap();
+
+                let mut ctx = ctx.borrow_mut();
+                let rent = ctx.banks_client.get_rent().await.unwrap();
+                let mut account = AccountSharedData::new(
+                    rent.minimum_balance(Mint::LEN),
+                    Mint::LEN,
+                    &spl_token::id(),
+                );
+                account.set_data_from_slice(&data);
+                ctx.set_account(&key, &account);
+
+                mint
+            }
+        };
+
+        MintFixture {
+            ctx,
+            key,
+            mint,
+            token_program: spl_token::id(),
+        }
+    }
+
     pub fn new_from_file(
         ctx: &Rc<RefCell<ProgramTestContext>>,
         relative_path: &str,
@@ -241,6 +284,29 @@ impl MintFixture {
         }
     }
 
+    /// Wrap a mint that already exists on chain, e.g. one loaded from a snapshot
+    pub async fn from_key(ctx: Rc<RefCell<ProgramTestContext>>, key: Pubkey) -> MintFixture {
+        let mint_account = ctx
+            .borrow_mut()
+            .banks_client
+            .get_account(key)
+            .await
+            .unwrap()
+            .unwrap();
+        let token_program = mint_account.owner;
+        let mint =
+            StateWithExtensionsOwned::<spl_token_2022::state::Mint>::unpack(mint_account.data)
+                .unwrap()
+                .base;
+
+        MintFixture {
+            ctx,
+            key,
+            mint,
+            token_program,
+        }
+    }
+
     #[allow(unused)]
     pub async fn reload(&mut self) {
         let mint_account = self
@@ -341,6 +407,113 @@ impl MintFixture {
 
         StateWithExtensionsOwned::unpack(mint_account.data).unwrap()
     }
+
+    /// Schedules a new transfer fee starting at `epoch`. The test mints have no transfer fee
+    /// config authority, so the mint account is rewritten directly.
+    pub async fn schedule_transfer_fee(&self, epoch: u64, transfer_fee_basis_points: u16) {
+        self.schedule_transfer_fee_with_max(epoch, transfer_fee_basis_points, u64::MAX)
+            .await;
+    }
+
+    /// Like `schedule_transfer_fee`, with transfer fees capped at `maximum_fee` native tokens.
+    pub async fn schedule_transfer_fee_with_max(
+        &self,
+        epoch: u64,
+        transfer_fee_basis_points: u16,
+        maximum_fee: u64,
+    ) {
+        let mut ctx = self.ctx.borrow_mut();
+        let mut account = ctx
+            .banks_client
+            .get_account(self.key)
+            .await
+            .unwrap()
+            .unwrap();
+
+        {
+            let mut mint =
+                StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack(&mut account.data)
+                    .unwrap();
+            let transfer_fee_config = mint.get_extension_mut::<TransferFeeConfig>().unwrap();
+            transfer_fee_config.newer_transfer_fee = TransferFee {
+                epoch: epoch.into(),
+                maximum_fee: maximum_fee.into(),
+                transfer_fee_basis_points: transfer_fee_basis_points.into(),
+            };
+        }
+
+        ctx.set_account(&self.key, &AccountSharedData::from(account));
+    }
+
+    /// Replaces the extra accounts the test transfer hook receives on every transfer of the
+    /// mint, which must have been created with `SupportedExtension::TransferHook`.
+    pub async fn update_transfer_hook_extra_accounts(
+        &self,
+        extra_accounts: &[AccountMeta],
+    ) -> Result<(), BanksClientError> {
+        let extra_account_metas = extra_accounts
+            .iter()
+            .map(|meta| {
+                ExtraAccountMeta::new_with_pubkey(&meta.pubkey, meta.is_signer, meta.is_writable)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let mut ctx = self.ctx.borrow_mut();
+        let ix = update_extra_account_meta_list(
+            &TEST_HOOK_ID,
+            &get_extra_account_metas_address(&self.key, &TEST_HOOK_ID),
+            &self.key,
+            &ctx.payer.pubkey(),
+            &extra_account_metas,
+        );
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    /// Extra accounts the test transfer hook needs for a transfer of this mint, empty for mints
+    /// without the hook
+    pub async fn load_transfer_hook_account_metas(
+        &self,
+        source: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> Vec<AccountMeta> {
+        let transfer_accounts = [*source, self.key, *destination, *authority]
+            .map(|key| AccountMeta::new_readonly(key, false))
+            .to_vec();
+        let mut ix = Instruction::new_with_bytes(TEST_HOOK_ID, &[], transfer_accounts);
+
+        let fetch_account_data_fn = |key| async move {
+            Ok(self
+                .ctx
+                .borrow_mut()
+                .banks_client
+                .get_account(key)
+                .await
+                .map(|acc| acc.map(|a| a.data))?)
+        };
+        let _ = spl_transfer_hook_interface::offchain::add_extra_account_metas_for_execute(
+            &mut ix,
+            &TEST_HOOK_ID,
+            source,
+            &self.key,
+            destination,
+            authority,
+            amount,
+            fetch_account_data_fn,
+        )
+        .await;
+
+        ix.accounts.split_off(4)
+    }
 }
 
 pub struct TokenAccountFixture {
@@ -532,6 +705,57 @@ impl TokenAccountFixture {
         TokenAccountFixture::new_with_keypair(ctx, mint_pk, owner_pk, &keypair, token_program).await
     }
 
+    /// Create the associated token account of `owner_pk` for the mint of `mint_fixture`.
+    pub async fn new_associated(
+        ctx: Rc<RefCell<ProgramTestContext>>,
+        mint_fixture: &MintFixture,
+        owner_pk: &Pubkey,
+    ) -> TokenAccountFixture {
+        TokenAccountFixture::new_associated_with_token_program(
+            ctx,
+            &mint_fixture.key,
+            owner_pk,
+            &mint_fixture.token_program,
+        )
+        .await
+    }
+
+    /// Create the associated token account of `owner_pk` for `mint_pk`, e.g. of a mint without
+    /// fixture such as a receipt mint.
+    pub async fn new_associated_with_token_program(
+        ctx: Rc<RefCell<ProgramTestContext>>,
+        mint_pk: &Pubkey,
+        owner_pk: &Pubkey,
+        token_program: &Pubkey,
+    ) -> TokenAccountFixture {
+        let ix = spl_associated_token_account::instruction::create_associated_token_account(
+            &ctx.borrow().payer.pubkey(),
+            owner_pk,
+            mint_pk,
+            token_program,
+        );
+
+        {
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&ctx.borrow().payer.pubkey()),
+                &[&ctx.borrow().payer],
+                ctx.borrow().last_blockhash,
+            );
+
+            ctx.borrow_mut()
+                .banks_client
+                .process_transaction(tx)
+                .await
+                .unwrap();
+        }
+
+        let address =
+            get_associated_token_address_with_program_id(owner_pk, mint_pk, token_program);
+
+        TokenAccountFixture::fetch(ctx, address).await
+    }
+
     pub async fn fetch(
         ctx: Rc<RefCell<ProgramTestContext>>,
         address: Pubkey,
@@ -1,30 +1,33 @@
 use super::astrolend_account::AstrolendAccountFixture;
 use crate::{
-    bank::BankFixture, astrolend_group::*, native, spl::*, transfer_hook::TEST_HOOK_ID, utils::*,
+    bank::BankFixture, astrolend_group::*, native, spl::*, test_cpi_caller::TEST_CPI_CALLER_ID,
+    transfer_hook::TEST_HOOK_ID, utils::*,
 };
 
 use anchor_lang::prelude::*;
 use bincode::deserialize;
 use pyth_sdk_solana::state::SolanaPriceAccount;
 use pyth_solana_receiver_sdk::price_update::VerificationLevel;
+use solana_cli_output::CliAccount;
 use solana_sdk::{account::AccountSharedData, entrypoint::ProgramResult};
 
 use fixed_macro::types::I80F48;
 use lazy_static::lazy_static;
 use astrolend::{
-    constants::MAX_ORACLE_KEYS,
+    constants::{MAX_ORACLE_KEYS, PYTH_PUSH_PYTH_SPONSORED_SHARD_ID},
     state::{
         astrolend_group::{
-            BankConfig, BankOperationalState, GroupConfig, InterestRateConfig, RiskTier,
+            AstrolendGroup, Bank, BankConfig, BankOperationalState, GroupConfig,
+            InterestRateConfig, RiskTier,
         },
-        price::OracleSetup,
+        price::{OracleSetup, PythPushOraclePriceFeed},
     },
 };
-use solana_program::{hash::Hash, sysvar};
+use solana_program::{hash::Hash, instruction::Instruction, sysvar};
 use solana_program_test::*;
 use solana_sdk::{account::Account, pubkey, signature::Keypair, signer::Signer};
 
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, fs, path::PathBuf, rc::Rc, str::FromStr};
 
 #[derive(Default, Debug, Clone)]
 pub struct TestSettings {
@@ -75,7 +78,7 @@ impl TestSettings {
 
         Self {
             banks,
-            group_config: Some(GroupConfig { admin: None }),
+            group_config: Some(GroupConfig::default()),
         }
     }
 
@@ -92,7 +95,7 @@ impl TestSettings {
                     config: Some(*DEFAULT_SOL_TEST_SW_BANK_CONFIG),
                 },
             ],
-            group_config: Some(GroupConfig { admin: None }),
+            group_config: Some(GroupConfig::default()),
         }
     }
 
@@ -140,7 +143,7 @@ impl TestSettings {
                     ..TestBankSetting::default()
                 },
             ],
-            group_config: Some(GroupConfig { admin: None }),
+            group_config: Some(GroupConfig::default()),
         }
     }
 }
@@ -190,6 +193,8 @@ pub struct TestFixture {
     pub mnde_mint: MintFixture,
     pub usdc_t22_mint: MintFixture,
     pub pyusd_mint: MintFixture,
+    /// Banks loaded by `new_from_snapshot`, keyed by bank address
+    pub snapshot_banks: HashMap<Pubkey, BankFixture>,
 }
 
 pub const PYTH_USDC_FEED: Pubkey = pubkey!("PythUsdcPrice111111111111111111111111111111");
@@ -199,8 +204,6 @@ pub const SWITCHBOARD_SOL_FEED: Pubkey = pubkey!("SwchSo1Price111111111111111111
 pub const PYTH_SOL_EQUIVALENT_FEED: Pubkey = pubkey!("PythSo1Equiva1entPrice111111111111111111111");
 pub const PYTH_MNDE_FEED: Pubkey = pubkey!("PythMndePrice111111111111111111111111111111");
 pub const FAKE_PYTH_USDC_FEED: Pubkey = pubkey!("FakePythUsdcPrice11111111111111111111111111");
-pub const PYTH_PUSH_SOL_FULLV_FEED: Pubkey = pubkey!("PythPushFu11So1Price11111111111111111111111");
-pub const PYTH_PUSH_SOL_PARTV_FEED: Pubkey = pubkey!("PythPushHa1fSo1Price11111111111111111111111");
 pub const PYTH_PUSH_FULLV_FEED_ID: [u8; 32] = [17; 32];
 pub const PYTH_PUSH_PARTV_FEED_ID: [u8; 32] = [18; 32];
 pub const PYTH_PUSH_REAL_SOL_FEED_ID: [u8; 32] = [
@@ -213,18 +216,13 @@ pub const PYTH_T22_WITH_FEE_FEED: Pubkey = pubkey!("PythT22WithFeePrice111111111
 pub const PYTH_PYUSD_FEED: Pubkey = pubkey!("PythPyusdPrice11111111111111111111111111111");
 pub const PYTH_SOL_REAL_FEED: Pubkey = pubkey!("PythSo1Rea1Price111111111111111111111111111");
 pub const PYTH_USDC_REAL_FEED: Pubkey = pubkey!("PythUsdcRea1Price11111111111111111111111111");
-pub const PYTH_PUSH_SOL_REAL_FEED: Pubkey = pubkey!("PythPushSo1Rea1Price11111111111111111111111");
 
 pub const SWITCH_PULL_SOL_REAL_FEED: Pubkey =
     pubkey!("BSzfJs4d1tAkSDqkepnfzEVcx2WtDVnwwXa2giy9PLeP");
 
-pub fn get_oracle_id_from_feed_id(feed_id: Pubkey) -> Option<Pubkey> {
-    match feed_id.to_bytes() {
-        PYTH_PUSH_FULLV_FEED_ID => Some(PYTH_PUSH_SOL_FULLV_FEED),
-        PYTH_PUSH_PARTV_FEED_ID => Some(PYTH_PUSH_SOL_PARTV_FEED),
-        PYTH_PUSH_REAL_SOL_FEED_ID => Some(PYTH_PUSH_SOL_REAL_FEED),
-        _ => None,
-    }
+/// Address of the Pyth push oracle account of `feed_id` on `shard_id`
+pub fn get_oracle_id_from_feed_id(feed_id: Pubkey, shard_id: u16) -> Pubkey {
+    PythPushOraclePriceFeed::find_oracle_address(shard_id, &feed_id.to_bytes()).0
 }
 
 pub fn create_oracle_key_array(pyth_oracle: Pubkey) -> [Pubkey; MAX_ORACLE_KEYS] {
@@ -235,6 +233,18 @@ pub fn create_oracle_key_array(pyth_oracle: Pubkey) -> [Pubkey; MAX_ORACLE_KEYS]
 }
 
 lazy_static! {
+    pub static ref PYTH_PUSH_SOL_FULLV_FEED: Pubkey = get_oracle_id_from_feed_id(
+        PYTH_PUSH_FULLV_FEED_ID.into(),
+        PYTH_PUSH_PYTH_SPONSORED_SHARD_ID,
+    );
+    pub static ref PYTH_PUSH_SOL_PARTV_FEED: Pubkey = get_oracle_id_from_feed_id(
+        PYTH_PUSH_PARTV_FEED_ID.into(),
+        PYTH_PUSH_PYTH_SPONSORED_SHARD_ID,
+    );
+    pub static ref PYTH_PUSH_SOL_REAL_FEED: Pubkey = get_oracle_id_from_feed_id(
+        PYTH_PUSH_REAL_SOL_FEED_ID.into(),
+        PYTH_PUSH_PYTH_SPONSORED_SHARD_ID,
+    );
     pub static ref DEFAULT_TEST_BANK_INTEREST_RATE_CONFIG: InterestRateConfig =
         InterestRateConfig {
             insurance_fee_fixed_apr: I80F48!(0).into(),
@@ -394,14 +404,7 @@ impl TestFixture {
         test_settings: Option<TestSettings>,
         extensions: &[SupportedExtension],
     ) -> TestFixture {
-        let mut program = ProgramTest::default();
-
-        let mem_map_not_copy_feature_gate = pubkey!("EenyoWx9UMXYKpR8mW5Jmfmy2fRjzUtM7NduYMY8bx33");
-        program.deactivate_feature(mem_map_not_copy_feature_gate);
-
-        program.prefer_bpf(true);
-        program.add_program("astrolend", astrolend::ID, None);
-        program.add_program("test_transfer_hook", TEST_HOOK_ID, None);
+        let mut program = Self::program_test();
 
         let usdc_keypair = Keypair::new();
         let pyusd_keypair = Keypair::new();
@@ -476,7 +479,7 @@ impl TestFixture {
             create_switchboard_price_feed(10, SOL_MINT_DECIMALS.into()),
         );
         program.add_account(
-            PYTH_PUSH_SOL_FULLV_FEED,
+            *PYTH_PUSH_SOL_FULLV_FEED,
             create_pyth_push_oracle_account(
                 PYTH_PUSH_FULLV_FEED_ID,
                 10.0,
@@ -486,7 +489,7 @@ impl TestFixture {
             ),
         );
         program.add_account(
-            PYTH_PUSH_SOL_PARTV_FEED,
+            *PYTH_PUSH_SOL_PARTV_FEED,
             create_pyth_push_oracle_account(
                 PYTH_PUSH_PARTV_FEED_ID,
                 10.0,
@@ -508,7 +511,7 @@ impl TestFixture {
             ),
         );
         program.add_account(
-            PYTH_PUSH_SOL_REAL_FEED,
+            *PYTH_PUSH_SOL_REAL_FEED,
             create_pyth_push_oracle_account_from_bytes(
                 include_bytes!("../data/pyth_push_sol_price.bin").to_vec(),
             ),
@@ -579,12 +582,14 @@ impl TestFixture {
             Rc::clone(&context),
             test_settings
                 .clone()
-                .map(|ts| ts.group_config.unwrap_or(GroupConfig { admin: None }))
-                .unwrap_or(GroupConfig { admin: None }),
+                .map(|ts| ts.group_config.unwrap_or_default())
+                .unwrap_or_default(),
         )
         .await;
 
         let mut banks = HashMap::new();
+        // Banks sharing a mint past the first are seeded, one keypair bank per mint is allowed
+        let mut banks_per_mint: HashMap<Pubkey, u64> = HashMap::new();
         if let Some(test_settings) = test_settings.clone() {
             for bank in test_settings.banks.iter() {
                 let (bank_mint, default_config) = match bank.mint {
@@ -645,13 +650,20 @@ impl TestFixture {
                     }
                 };
 
-                banks.insert(
-                    bank.mint.clone(),
+                let bank_config = bank.config.unwrap_or(default_config);
+                let bank_seed = banks_per_mint.entry(bank_mint.key).or_default();
+                let bank_f = if *bank_seed == 0 {
+                    tester_group
+                        .try_lending_pool_add_bank(bank_mint, bank_config)
+                        .await
+                } else {
                     tester_group
-                        .try_lending_pool_add_bank(bank_mint, bank.config.unwrap_or(default_config))
+                        .try_lending_pool_add_bank_with_seed(bank_mint, bank_config, *bank_seed)
                         .await
-                        .unwrap(),
-                );
+                };
+                *bank_seed += 1;
+
+                banks.insert(bank.mint.clone(), bank_f.unwrap());
             }
         };
 
@@ -665,6 +677,126 @@ impl TestFixture {
             mnde_mint: mnde_mint_f,
             usdc_t22_mint: usdc_t22_mint_f,
             pyusd_mint: pyusd_mint_f,
+            snapshot_banks: HashMap::new(),
+        }
+    }
+
+    fn program_test() -> ProgramTest {
+        let mut program = ProgramTest::default();
+
+        let mem_map_not_copy_feature_gate = pubkey!("EenyoWx9UMXYKpR8mW5Jmfmy2fRjzUtM7NduYMY8bx33");
+        program.deactivate_feature(mem_map_not_copy_feature_gate);
+
+        program.prefer_bpf(true);
+        program.add_program("astrolend", astrolend::ID, None);
+        program.add_program("test_transfer_hook", TEST_HOOK_ID, None);
+        program.add_program("test_cpi_caller", TEST_CPI_CALLER_ID, None);
+
+        program
+    }
+
+    /// Start from a directory of JSON account dumps (`solana account -o json` format), relative
+    /// to the test-utils crate, e.g. to replay mainnet state.
+    ///
+    /// The snapshot must contain exactly one group, its banks are available through
+    /// `get_snapshot_bank`. The clock starts at the most recent bank `last_update`.
+    pub async fn new_from_snapshot(relative_path: &str) -> TestFixture {
+        TestFixture::new_from_snapshot_with_program_id(relative_path, astrolend::ID).await
+    }
+
+    /// Same as `new_from_snapshot`, but accounts owned by `source_program_id` are reassigned to
+    /// `astrolend::ID`, so state cloned from a deployment under another program id resolves.
+    ///
+    /// Note: PDAs derived from the source program id (e.g. vault authorities) will not match.
+    pub async fn new_from_snapshot_with_program_id(
+        relative_path: &str,
+        source_program_id: Pubkey,
+    ) -> TestFixture {
+        let mut program = Self::program_test();
+
+        let mut path = PathBuf::from_str(env!("CARGO_MANIFEST_DIR")).unwrap();
+        path.push(relative_path);
+
+        let mut files = fs::read_dir(&path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|file| file.extension().is_some_and(|ext| ext == "json"))
+            .collect::<Vec<_>>();
+        files.sort();
+
+        let mut group_key = None;
+        let mut bank_keys = vec![];
+        let mut last_update = 0;
+
+        for file in files {
+            let account: CliAccount =
+                serde_json::from_str(&fs::read_to_string(&file).unwrap()).unwrap();
+            let address = Pubkey::from_str(&account.keyed_account.pubkey).unwrap();
+            let mut account: Account = account.keyed_account.account.decode().unwrap();
+
+            if account.owner == source_program_id {
+                account.owner = astrolend::ID;
+            }
+
+            if account.owner == astrolend::ID {
+                if let Ok(bank) = Bank::try_deserialize(&mut account.data.as_slice()) {
+                    last_update = last_update.max(bank.last_update);
+                    bank_keys.push((address, bank.mint));
+                } else if AstrolendGroup::try_deserialize(&mut account.data.as_slice()).is_ok() {
+                    assert!(group_key.is_none(), "snapshot contains more than one group");
+                    group_key = Some(address);
+                }
+            }
+
+            program.add_account(address, account);
+        }
+
+        let group_key = group_key.expect("snapshot contains no group");
+
+        let context = Rc::new(RefCell::new(program.start_with_context().await));
+
+        {
+            let mut ctx = context.borrow_mut();
+            let mut clock: Clock = ctx.banks_client.get_sysvar().await.unwrap();
+            clock.unix_timestamp = last_update;
+            ctx.set_sysvar(&clock);
+        }
+
+        solana_logger::setup_with_default(RUST_LOG_DEFAULT);
+
+        let mut snapshot_banks = HashMap::new();
+        for (bank_key, mint) in bank_keys {
+            let mint_f = MintFixture::from_key(Rc::clone(&context), mint).await;
+            snapshot_banks.insert(
+                bank_key,
+                BankFixture::new(Rc::clone(&context), bank_key, &mint_f),
+            );
+        }
+
+        let usdc_mint_f =
+            MintFixture::new(Rc::clone(&context), None, Some(USDC_MINT_DECIMALS)).await;
+        let sol_mint_f = MintFixture::new(Rc::clone(&context), None, Some(SOL_MINT_DECIMALS)).await;
+        let sol_equivalent_mint_f =
+            MintFixture::new(Rc::clone(&context), None, Some(SOL_MINT_DECIMALS)).await;
+        let mnde_mint_f =
+            MintFixture::new(Rc::clone(&context), None, Some(MNDE_MINT_DECIMALS)).await;
+        let usdc_t22_mint_f =
+            MintFixture::new_token_22(Rc::clone(&context), None, Some(USDC_MINT_DECIMALS), &[])
+                .await;
+        let pyusd_mint_f =
+            MintFixture::new(Rc::clone(&context), None, Some(PYUSD_MINT_DECIMALS)).await;
+
+        TestFixture {
+            context: Rc::clone(&context),
+            astrolend_group: AstrolendGroupFixture::from_key(Rc::clone(&context), group_key),
+            banks: HashMap::new(),
+            usdc_mint: usdc_mint_f,
+            sol_mint: sol_mint_f,
+            sol_equivalent_mint: sol_equivalent_mint_f,
+            mnde_mint: mnde_mint_f,
+            usdc_t22_mint: usdc_t22_mint_f,
+            pyusd_mint: pyusd_mint_f,
+            snapshot_banks,
         }
     }
 
@@ -672,6 +804,25 @@ impl TestFixture {
         AstrolendAccountFixture::new(Rc::clone(&self.context), &self.astrolend_group.key).await
     }
 
+    pub async fn try_create_astrolend_account(
+        &self,
+    ) -> anyhow::Result<AstrolendAccountFixture, BanksClientError> {
+        AstrolendAccountFixture::try_new(Rc::clone(&self.context), &self.astrolend_group.key).await
+    }
+
+    pub async fn create_astrolend_account_with_tag(
+        &self,
+        account_tag: [u8; 8],
+    ) -> AstrolendAccountFixture {
+        AstrolendAccountFixture::try_new_with_tag(
+            Rc::clone(&self.context),
+            &self.astrolend_group.key,
+            Some(account_tag),
+        )
+        .await
+        .unwrap()
+    }
+
     pub async fn try_load(
         &self,
         address: &Pubkey,
@@ -683,20 +834,16 @@ impl TestFixture {
             .await
     }
 
-    pub async fn load_and_deserialize<T: anchor_lang::AccountDeserialize>(
-        &self,
-        address: &Pubkey,
-    ) -> T {
-        let ai = self
-            .context
-            .borrow_mut()
-            .banks_client
-            .get_account(*address)
-            .await
-            .unwrap()
-            .unwrap();
+    pub async fn load_and_deserialize<T>(&self, address: &Pubkey) -> anyhow::Result<T>
+    where
+        T: anchor_lang::AccountDeserialize + anchor_lang::ZeroCopy,
+    {
+        crate::utils::load_and_deserialize(self.context.clone(), address).await
+    }
 
-        T::try_deserialize(&mut ai.data.as_slice()).unwrap()
+    /// Simulate `ixs` without committing them, see [crate::utils::simulate_tx].
+    pub async fn simulate_tx(&self, ixs: &[Instruction], signers: &[&Keypair]) -> SimulationResult {
+        crate::utils::simulate_tx(self.context.clone(), ixs, signers).await
     }
 
     pub fn payer(&self) -> Pubkey {
@@ -715,6 +862,10 @@ impl TestFixture {
         self.banks.get_mut(bank_mint).unwrap()
     }
 
+    pub fn get_snapshot_bank(&self, bank: &Pubkey) -> &BankFixture {
+        self.snapshot_banks.get(bank).unwrap()
+    }
+
     pub fn set_time(&self, timestamp: i64) {
         let clock = Clock {
             unix_timestamp: timestamp,
@@ -765,6 +916,32 @@ impl TestFixture {
             .unwrap();
     }
 
+    /// Warps the clock into the next epoch, leaving the slot and unix timestamp unchanged
+    pub async fn advance_epoch(&self) {
+        let mut clock: Clock = self
+            .context
+            .borrow_mut()
+            .banks_client
+            .get_sysvar()
+            .await
+            .unwrap();
+        clock.epoch += 1;
+        self.context.borrow_mut().set_sysvar(&clock);
+    }
+
+    /// Warps the clock into the next slot, leaving the epoch and unix timestamp unchanged
+    pub async fn advance_slot(&self) {
+        let mut clock: Clock = self
+            .context
+            .borrow_mut()
+            .banks_client
+            .get_sysvar()
+            .await
+            .unwrap();
+        clock.slot += 1;
+        self.context.borrow_mut().set_sysvar(&clock);
+    }
+
     pub async fn get_minimum_rent_for_size(&self, size: usize) -> u64 {
         self.context
             .borrow_mut()
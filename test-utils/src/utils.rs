@@ -3,6 +3,7 @@ use anchor_lang_29::Discriminator;
 use anchor_spl::token_2022::spl_token_2022::extension::transfer_fee::MAX_FEE_BASIS_POINTS;
 use astrolend::constants::PYTH_ID;
 use astrolend::constants::SWITCHBOARD_PULL_ID;
+use base64::Engine;
 use pyth_sdk_solana::state::{
     AccountType, PriceInfo, PriceStatus, Rational, SolanaPriceAccount, MAGIC, VERSION_2,
 };
@@ -11,7 +12,13 @@ use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
 use pyth_solana_receiver_sdk::price_update::VerificationLevel;
 use solana_program::{instruction::Instruction, pubkey};
 use solana_program_test::*;
-use solana_sdk::{account::Account, signature::Keypair};
+use solana_sdk::{
+    account::Account,
+    instruction::InstructionError,
+    signature::Keypair,
+    signer::Signer,
+    transaction::{Transaction, TransactionError},
+};
 use std::mem::size_of;
 use std::{cell::RefCell, rc::Rc};
 use switchboard_solana::{
@@ -27,19 +34,140 @@ pub const RUST_LOG_DEFAULT: &str = "solana_rbpf::vm=info,\
              solana_program_test=info,\
              solana_bpf_loader_program=debug";
 
-pub async fn load_and_deserialize<T: AccountDeserialize>(
+/// Fetch the zero-copy account at `address` as `T`. Errors name the account when it is missing,
+/// too short for `T` or holds another account type.
+pub async fn load_and_deserialize<T: AccountDeserialize + anchor_lang::ZeroCopy>(
     ctx: Rc<RefCell<ProgramTestContext>>,
     address: &Pubkey,
-) -> T {
+) -> anyhow::Result<T> {
+    let type_name = std::any::type_name::<T>();
     let ai = ctx
         .borrow_mut()
         .banks_client
         .get_account(*address)
-        .await
-        .unwrap()
-        .unwrap();
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("account {} not found, expected {}", address, type_name))?;
+
+    let expected_len = 8 + size_of::<T>();
+    anyhow::ensure!(
+        ai.data.len() >= expected_len,
+        "account {} holds {} bytes, expected {} for {}",
+        address,
+        ai.data.len(),
+        expected_len,
+        type_name
+    );
+
+    let discriminator = <T as anchor_lang::Discriminator>::DISCRIMINATOR;
+    anyhow::ensure!(
+        ai.data[..8] == discriminator,
+        "account {} has discriminator {:?}, expected {:?} for {}",
+        address,
+        &ai.data[..8],
+        discriminator,
+        type_name
+    );
+
+    Ok(T::try_deserialize(&mut ai.data.as_slice())?)
+}
+
+/// Find and deserialize the first event of type `T` in a transaction's log messages.
+pub fn find_event<T: anchor_lang::Event>(log_messages: &[String]) -> Option<T> {
+    log_messages.iter().find_map(|log| decode_event(log))
+}
+
+/// Deserialize every event of type `T` emitted by a transaction, in emission order.
+pub fn parse_events<T: anchor_lang::Event>(result: &BanksTransactionResultWithMetadata) -> Vec<T> {
+    result
+        .metadata
+        .as_ref()
+        .map(|metadata| {
+            metadata
+                .log_messages
+                .iter()
+                .filter_map(|log| decode_event(log))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn decode_event<T: anchor_lang::Event>(log: &str) -> Option<T> {
+    let data = log.strip_prefix("Program data: ")?;
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .ok()?;
+
+    if bytes.len() < 8 || bytes[..8] != <T as anchor_lang::Discriminator>::DISCRIMINATOR {
+        return None;
+    }
 
-    T::try_deserialize(&mut ai.data.as_slice()).unwrap()
+    T::try_from_slice(&bytes[8..]).ok()
+}
+
+/// Outcome of a simulated transaction. Nothing is committed, so the same transaction can still
+/// be sent, or simulated again, with the same blockhash.
+#[derive(Debug)]
+pub struct SimulationResult {
+    pub logs: Vec<String>,
+    pub units_consumed: u64,
+    /// `None` if the transaction would succeed
+    pub err: Option<TransactionError>,
+}
+
+impl SimulationResult {
+    /// Code of the custom program error the transaction failed with, taken from the logs if
+    /// the transaction error doesn't carry it.
+    pub fn custom_error_code(&self) -> Option<u32> {
+        match self.err {
+            Some(TransactionError::InstructionError(_, InstructionError::Custom(code))) => {
+                Some(code)
+            }
+            _ => custom_error_code_from_logs(&self.logs),
+        }
+    }
+
+    /// Deserialize every event of type `T` the transaction would emit, in emission order.
+    pub fn events<T: anchor_lang::Event>(&self) -> Vec<T> {
+        self.logs.iter().filter_map(|log| decode_event(log)).collect()
+    }
+}
+
+/// Code of the first `custom program error: 0x..` reported in `logs`.
+pub fn custom_error_code_from_logs(logs: &[String]) -> Option<u32> {
+    logs.iter().find_map(|log| {
+        let (_, code) = log.split_once("custom program error: 0x")?;
+        u32::from_str_radix(code.trim(), 16).ok()
+    })
+}
+
+/// Simulate `ixs` paid and signed by the context payer, `signers` are the additional signers.
+pub async fn simulate_tx(
+    ctx: Rc<RefCell<ProgramTestContext>>,
+    ixs: &[Instruction],
+    signers: &[&Keypair],
+) -> SimulationResult {
+    let mut ctx = ctx.borrow_mut();
+    let mut all_signers = vec![&ctx.payer];
+    all_signers.extend_from_slice(signers);
+
+    let tx = Transaction::new_signed_with_payer(
+        ixs,
+        Some(&ctx.payer.pubkey()),
+        &all_signers,
+        ctx.last_blockhash,
+    );
+
+    let simulation = ctx.banks_client.simulate_transaction(tx).await.unwrap();
+    let (logs, units_consumed) = match simulation.simulation_details {
+        Some(details) => (details.logs, details.units_consumed),
+        None => (vec![], 0),
+    };
+
+    SimulationResult {
+        logs,
+        units_consumed,
+        err: simulation.result.and_then(|result| result.err()),
+    }
 }
 
 pub fn make_ix<T>(accounts: T, ix_data: Vec<u8>) -> Instruction
@@ -68,12 +196,24 @@ pub fn create_pyth_legacy_oracle_account(
     ui_price: f64,
     mint_decimals: i32,
     timestamp: Option<i64>,
+) -> Account {
+    create_pyth_legacy_oracle_account_with_conf(mint, ui_price, 0., mint_decimals, timestamp)
+}
+
+/// Pyth legacy price account with the same confidence on the real time and the EMA price
+pub fn create_pyth_legacy_oracle_account_with_conf(
+    mint: Pubkey,
+    ui_price: f64,
+    ui_conf: f64,
+    mint_decimals: i32,
+    timestamp: Option<i64>,
 ) -> Account {
     let native_price = (ui_price * 10_f64.powf(mint_decimals as f64)) as i64;
+    let native_conf = (ui_conf * 10_f64.powf(mint_decimals as f64)) as u64;
     let data = bytemuck::bytes_of(&SolanaPriceAccount {
         prod: mint,
         agg: PriceInfo {
-            conf: 0,
+            conf: native_conf,
             price: native_price,
             status: PriceStatus::Trading,
             ..Default::default()
@@ -91,8 +231,8 @@ pub fn create_pyth_legacy_oracle_account(
         },
         prev_timestamp: timestamp.unwrap_or(0),
         ema_conf: Rational {
-            val: 0,
-            numer: 0,
+            val: native_conf as i64,
+            numer: native_conf as i64,
             denom: 1,
         },
         ..Default::default()